@@ -0,0 +1,118 @@
+//! Headless benchmark scenario runner.
+//!
+//! Generates (or loads) a scenario, runs it for a fixed number of ticks, then reports
+//! per-system timings and a hash of the final state. Runs with the same scenario must
+//! always produce the same hash, regardless of execution mode.
+
+mod scenario;
+mod sim;
+
+use std::{path::PathBuf, time::Instant};
+
+use manufactory::game::schedule::ExecutionMode;
+use mffmt::{
+    hex::HexBytes,
+    progress::{ConsoleSink, Progress},
+};
+use mfworld::profile;
+
+use scenario::Scenario;
+
+const USAGE: &str = "\
+Usage: mfbench [options]
+
+Options:
+    --preset <name>       Start from a preset scenario (small, medium, large). Default: small
+    --scenario <file>     Load a scenario file of `key = value` lines.
+    --chunks <n>          Number of chunks.
+    --machines <n>        Number of machines.
+    --belts <n>           Number of belts.
+    --ticks <n>           Number of ticks to run.
+    --edits <n>           Voxel edits per tick.
+    --seed <n>            Generation seed.
+    --parallel            Run non-conflicting systems in parallel.
+    -h, --help            Print this message.";
+
+fn parse_args() -> Result<Scenario, String> {
+    let mut scenario = Scenario::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for `{name}`"));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--scenario" => scenario = Scenario::load(&PathBuf::from(value("--scenario")?))?,
+            "--parallel" => scenario.mode = ExecutionMode::Parallel,
+            flag if flag.starts_with("--") => {
+                let key = &flag[2..];
+                let value = value(flag)?;
+                scenario.set(key, &value)?;
+            }
+            _ => return Err(format!("Unexpected argument: `{arg}`")),
+        }
+    }
+    Ok(scenario)
+}
+
+fn main() {
+    let scenario = match parse_args() {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    println!(
+        "scenario: {} (chunks: {}, machines: {}, belts: {}, ticks: {}, edits: {}, seed: {}, mode: {:?})",
+        scenario.name,
+        scenario.chunks,
+        scenario.machines,
+        scenario.belts,
+        scenario.ticks,
+        scenario.edits,
+        scenario.seed,
+        scenario.mode,
+    );
+
+    let generate_start = Instant::now();
+    let resources = sim::generate(&scenario);
+    println!("generated in {:?}", generate_start.elapsed());
+
+    let timings = sim::Timings::default();
+    let mut scheduler = sim::build_scheduler(&scenario, &timings);
+    let mut progress = Progress::new(ConsoleSink::stderr());
+    progress.begin("ticks", scenario.ticks as u64);
+    let run_start = Instant::now();
+    for _ in 0..scenario.ticks {
+        if let Err(err) = scheduler.run(&resources) {
+            eprintln!("Schedule error: {err}");
+            std::process::exit(1);
+        }
+        progress.advance(1);
+    }
+    let total = run_start.elapsed();
+    progress.end();
+
+    let ticks = scenario.ticks.max(1);
+    println!("{:<16}{:>16}{:>16}", "system", "total", "per tick");
+    for (name, elapsed) in timings.lock().unwrap().iter() {
+        println!("{:<16}{:>16}{:>16}", name, format!("{elapsed:.2?}"), format!("{:.2?}", *elapsed / ticks));
+    }
+    println!("{:<16}{:>16}{:>16}", "total", format!("{total:.2?}"), format!("{:.2?}", total / ticks));
+    if profile::ENABLED {
+        let spans = profile::drain_spans();
+        println!("{:<16}{:>16}{:>16}", "span", "total", "count");
+        for (kind, elapsed, count) in profile::totals(&spans) {
+            if count != 0 {
+                println!("{:<16}{:>16}{:>16}", kind.name(), format!("{elapsed:.2?}"), count);
+            }
+        }
+        let dropped = profile::dropped_spans();
+        if dropped != 0 {
+            println!("({dropped} spans were dropped)");
+        }
+    }
+    println!("state hash: {}", HexBytes(&sim::state_hash(&resources)));
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use manufactory::game::schedule::ExecutionMode;
+
+/// The parameters of a benchmark run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub name: String,
+    pub chunks: u32,
+    pub machines: u32,
+    pub belts: u32,
+    pub ticks: u32,
+    /// Voxel edits made each tick.
+    pub edits: u32,
+    pub seed: u64,
+    pub mode: ExecutionMode,
+}
+
+impl Scenario {
+    pub const PRESETS: [&'static str; 3] = ["small", "medium", "large"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (chunks, machines, belts, ticks, edits) = match name {
+            "small" => (8, 64, 256, 200, 4),
+            "medium" => (64, 512, 4096, 500, 32),
+            "large" => (512, 4096, 32768, 1000, 256),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_owned(),
+            chunks,
+            machines,
+            belts,
+            ticks,
+            edits,
+            seed: 0,
+            mode: ExecutionMode::Sequential,
+        })
+    }
+
+    /// Applies a `key = value` setting.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value.parse().map_err(|_| format!("Invalid value for `{key}`: `{value}`"))
+        }
+        match key {
+            "preset" => {
+                let preset = Self::preset(value).ok_or_else(|| {
+                    format!("Unknown preset: `{value}` (expected one of {:?})", Self::PRESETS)
+                })?;
+                *self = preset;
+            }
+            "name" => self.name = value.to_owned(),
+            "chunks" => self.chunks = parse(key, value)?,
+            "machines" => self.machines = parse(key, value)?,
+            "belts" => self.belts = parse(key, value)?,
+            "ticks" => self.ticks = parse(key, value)?,
+            "edits" => self.edits = parse(key, value)?,
+            "seed" => self.seed = parse(key, value)?,
+            "mode" => self.mode = match value {
+                "sequential" => ExecutionMode::Sequential,
+                "parallel" => ExecutionMode::Parallel,
+                _ => return Err(format!("Invalid mode: `{value}` (expected `sequential` or `parallel`)")),
+            },
+            _ => return Err(format!("Unknown setting: `{key}`")),
+        }
+        Ok(())
+    }
+
+    /// Loads a scenario file made of `key = value` lines. Lines starting with `#` are ignored.
+    /// Settings are applied in order, so a `preset` line should come first.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let mut scenario = Self {
+            name: path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+            ..Self::default()
+        };
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("{}:{}: expected `key = value`", path.display(), line_number + 1))?;
+            scenario.set(key.trim(), value.trim())
+                .map_err(|err| format!("{}:{}: {err}", path.display(), line_number + 1))?;
+        }
+        Ok(scenario)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self::preset("small").unwrap()
+    }
+}
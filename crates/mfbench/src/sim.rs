@@ -0,0 +1,198 @@
+//! A synthetic factory simulation used to exercise the scheduler and world bookkeeping.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use manufactory::game::schedule::{Resources, Scheduler, System, SystemContext};
+use mfhash::{deterministic::DeterministicHasher, Blake3Hasher};
+use mfworld::{
+    chunk::{ChunkPos, LocalPos, CHUNK_VOLUME},
+    invalidation::{Artifact, InvalidationTracker},
+    profile::{self, SpanKind},
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::scenario::Scenario;
+
+pub struct WorldState {
+    pub chunks: Vec<(ChunkPos, Box<[u32]>)>,
+    pub tracker: InvalidationTracker,
+    pub rebuilds: u64,
+    pub edits: u32,
+    pub rng: ChaCha8Rng,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Machine {
+    pub recipe_time: u32,
+    pub progress: u32,
+    pub input: u32,
+    pub output: u32,
+}
+
+pub struct Machines(pub Vec<Machine>);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Belt {
+    /// Items on each of the 4 slots of the belt (`0` is empty).
+    pub slots: [u32; 4],
+    pub source: u32,
+    pub target: u32,
+}
+
+pub struct Belts(pub Vec<Belt>);
+
+pub type Timings = Arc<Mutex<BTreeMap<&'static str, Duration>>>;
+
+/// Builds the resources for `scenario`. Generation is deterministic for a given seed.
+pub fn generate(scenario: &Scenario) -> Resources {
+    let mut rng = ChaCha8Rng::seed_from_u64(scenario.seed);
+    let side = (scenario.chunks as f64).cbrt().ceil().max(1.0) as i32;
+    let chunks = (0..scenario.chunks as i32).map(|i| {
+        let pos = ChunkPos::new(i % side, (i / side) % side, i / (side * side));
+        let _span = profile::span(SpanKind::Generate, pos);
+        let voxels = (0..CHUNK_VOLUME).map(|_| rng.random_range(0..8u32)).collect();
+        (pos, voxels)
+    }).collect();
+    let machine_count = scenario.machines.max(1);
+    let machines = (0..machine_count).map(|_| Machine {
+        recipe_time: rng.random_range(5..40),
+        progress: 0,
+        input: rng.random_range(0..4),
+        output: 0,
+    }).collect();
+    let belts = (0..scenario.belts).map(|_| Belt {
+        slots: [0; 4],
+        source: rng.random_range(0..machine_count),
+        target: rng.random_range(0..machine_count),
+    }).collect();
+    let mut resources = Resources::new();
+    resources.insert(WorldState {
+        chunks,
+        tracker: InvalidationTracker::default(),
+        rebuilds: 0,
+        edits: scenario.edits,
+        rng,
+    });
+    resources.insert(Machines(machines));
+    resources.insert(Belts(belts));
+    resources
+}
+
+fn timed<F: FnMut(&SystemContext) + Send + 'static>(name: &'static str, timings: &Timings, mut run: F) -> System {
+    let timings = timings.clone();
+    System::new(name, move |ctx| {
+        let start = Instant::now();
+        run(ctx);
+        let elapsed = start.elapsed();
+        *timings.lock().unwrap().entry(name).or_default() += elapsed;
+    })
+}
+
+fn world_edits(ctx: &SystemContext) {
+    let mut world = ctx.write::<WorldState>();
+    let world = &mut *world;
+    if world.chunks.is_empty() {
+        return;
+    }
+    for _ in 0..world.edits {
+        let chunk_index = world.rng.random_range(0..world.chunks.len());
+        let voxel_index = world.rng.random_range(0..CHUNK_VOLUME);
+        let id = world.rng.random_range(0..8u32);
+        let (pos, voxels) = &mut world.chunks[chunk_index];
+        voxels[voxel_index] = id;
+        let local = LocalPos::from_index(voxel_index).unwrap();
+        world.tracker.voxel_changed(pos.block(local));
+    }
+}
+
+fn world_rebuild(ctx: &SystemContext) {
+    let mut world = ctx.write::<WorldState>();
+    for job in world.tracker.end_tick() {
+        for artifact in job.artifacts.iter() {
+            let _span = profile::span(artifact.into(), job.chunk);
+            world.tracker.mark_rebuilt(job.chunk, artifact);
+            if artifact == Artifact::Mesh {
+                world.rebuilds += 1;
+            }
+        }
+    }
+}
+
+fn machines(ctx: &SystemContext) {
+    for machine in ctx.write::<Machines>().0.iter_mut() {
+        if machine.input == 0 {
+            continue;
+        }
+        machine.progress += 1;
+        if machine.progress >= machine.recipe_time {
+            machine.progress = 0;
+            machine.input -= 1;
+            machine.output += 1;
+        }
+    }
+}
+
+fn belts(ctx: &SystemContext) {
+    let mut machines = ctx.write::<Machines>();
+    for belt in ctx.write::<Belts>().0.iter_mut() {
+        // Deliver the item at the end of the belt.
+        if belt.slots[3] != 0 {
+            machines.0[belt.target as usize].input += 1;
+            belt.slots[3] = 0;
+        }
+        belt.slots.copy_within(0..3, 1);
+        belt.slots[0] = 0;
+        // Pick up from the source machine.
+        let source = &mut machines.0[belt.source as usize];
+        if source.output != 0 {
+            source.output -= 1;
+            belt.slots[0] = 1;
+        }
+    }
+}
+
+/// Registers the simulation systems, each wrapped to record its run time in `timings`.
+pub fn build_scheduler(scenario: &Scenario, timings: &Timings) -> Scheduler {
+    let mut scheduler = Scheduler::new(scenario.mode);
+    let systems = [
+        timed("world_edits", timings, world_edits).writes::<WorldState>(),
+        timed("world_rebuild", timings, world_rebuild).writes::<WorldState>(),
+        timed("machines", timings, machines).writes::<Machines>(),
+        timed("belts", timings, belts).writes::<Machines>().writes::<Belts>(),
+    ];
+    for system in systems {
+        scheduler.add_system(system).expect("system names are unique");
+    }
+    scheduler
+}
+
+/// Hashes the entire simulation state.
+pub fn state_hash(resources: &Resources) -> [u8; 32] {
+    let mut hasher = Blake3Hasher::new();
+    let world = resources.read::<WorldState>().expect("world state exists");
+    hasher.write_usize(world.chunks.len());
+    for (pos, voxels) in world.chunks.iter() {
+        hasher.write_i32(pos.x);
+        hasher.write_i32(pos.y);
+        hasher.write_i32(pos.z);
+        voxels.iter().for_each(|id| hasher.write_u32(*id));
+    }
+    hasher.write_u64(world.rebuilds);
+    for machine in resources.read::<Machines>().expect("machines exist").0.iter() {
+        hasher.write_u32(machine.recipe_time);
+        hasher.write_u32(machine.progress);
+        hasher.write_u32(machine.input);
+        hasher.write_u32(machine.output);
+    }
+    for belt in resources.read::<Belts>().expect("belts exist").0.iter() {
+        belt.slots.iter().for_each(|slot| hasher.write_u32(*slot));
+        hasher.write_u32(belt.source);
+        hasher.write_u32(belt.target);
+    }
+    hasher.finish()
+}
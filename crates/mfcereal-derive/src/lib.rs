@@ -0,0 +1,264 @@
+//! `#[derive(Encode, Decode)]`, re-exported by `mfcereal` with its `derive` feature.
+//!
+//! Structs are written as their fields in declaration order, with no header. Enums are written as
+//! the variant's tag, then the variant's fields in declaration order (see `mfcereal::enums`).
+//! Decoding a tag that no variant has fails with `DecodeError::UnknownDiscriminant`. Every type
+//! parameter gets an `Encode` (or `Decode`) bound.
+//!
+//! Attributes:
+//! - `#[cereal(tag = N)]` on every variant of an enum sets its tag. Tags are never taken from
+//!   declaration order, so that reordering or adding variants doesn't change the format, and must
+//!   be unique.
+//! - `#[cereal(tag = u8)]` or `#[cereal(tag = varint)]` on an enum sets how its tags are written.
+//!   Tags are a `u8` without it.
+//! - `#[cereal(with = path)]` on a field writes it with `path::encode` and reads it with
+//!   `path::decode` instead of its own impls (see `mfcereal::structs`).
+
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Attribute, Data, DataEnum, DeriveInput, Field, Fields, Ident, LitInt, Path};
+
+#[proc_macro_derive(Encode, attributes(cereal))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(Decode, attributes(cereal))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_encode(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, encodes) = destructure(&data.fields)?;
+            quote! {
+                let Self #pattern = self;
+                let mut written = 0u64;
+                #(#encodes)*
+                Ok(written)
+            }
+        },
+        Data::Enum(data) => {
+            let format = tag_format(&input)?;
+            let tags = variant_tags(data, format)?;
+            let arms = data.variants.iter().zip(tags).map(|(variant, tag)| {
+                let name = &variant.ident;
+                let (pattern, encodes) = destructure(&variant.fields)?;
+                let write_tag = match format {
+                    TagFormat::U8 => quote! { ::mfcereal::encode::Encoder::write_u8(encoder, #tag)? },
+                    TagFormat::Varint => quote! { ::mfcereal::encode::Encoder::write_varint(encoder, #tag)? },
+                };
+                Ok(quote! {
+                    Self::#name #pattern => {
+                        let mut written = #write_tag;
+                        #(#encodes)*
+                        Ok(written)
+                    },
+                })
+            }).collect::<syn::Result<Vec<_>>>()?;
+            if arms.is_empty() {
+                quote! { match *self {} }
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "Encode can't be derived for unions."));
+        },
+    };
+
+    let generics = &mut input.generics;
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::mfcereal::encode::Encode));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics ::mfcereal::encode::Encode for #name #type_generics #where_clause {
+            #[allow(unused_variables, unused_mut)]
+            fn encode<E: ::mfcereal::encode::Encoder>(&self, encoder: &mut E) -> ::core::result::Result<u64, E::Error> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_decode(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let construct = construct(&data.fields)?;
+            quote! { Ok(Self #construct) }
+        },
+        Data::Enum(data) => {
+            let format = tag_format(&input)?;
+            let tags = variant_tags(data, format)?;
+            let arms = data.variants.iter().zip(tags).map(|(variant, tag)| {
+                let name = &variant.ident;
+                let construct = construct(&variant.fields)?;
+                Ok(quote! { #tag => Ok(Self::#name #construct), })
+            }).collect::<syn::Result<Vec<_>>>()?;
+            let read_tag = match format {
+                TagFormat::U8 => quote! { ::mfcereal::decode::Decoder::read_u8(decoder).map(u64::from)? },
+                TagFormat::Varint => quote! { ::mfcereal::decode::Decoder::read_varint(decoder)? },
+            };
+            let ty = input.ident.to_string();
+            quote! {
+                match #read_tag {
+                    #(#arms)*
+                    value => Err(::mfcereal::decode::DecodeError::UnknownDiscriminant { ty: #ty, value }),
+                }
+            }
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "Decode can't be derived for unions."));
+        },
+    };
+
+    let generics = &mut input.generics;
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::mfcereal::decode::Decode));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics ::mfcereal::decode::Decode for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn decode<D: ::mfcereal::decode::Decoder>(decoder: &mut D) -> ::core::result::Result<Self, ::mfcereal::decode::DecodeError<D::Error>> {
+                #body
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagFormat {
+    U8,
+    Varint,
+}
+
+fn cereal_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("cereal"))
+}
+
+/// The `tag = u8` or `tag = varint` of the enum's `#[cereal(...)]` attributes.
+fn tag_format(input: &DeriveInput) -> syn::Result<TagFormat> {
+    let mut format = TagFormat::U8;
+    for attr in cereal_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                let ident: Ident = meta.value()?.parse()?;
+                format = match ident.to_string().as_str() {
+                    "u8" => TagFormat::U8,
+                    "varint" => TagFormat::Varint,
+                    _ => return Err(syn::Error::new(ident.span(), "Expected `u8` or `varint`.")),
+                };
+                Ok(())
+            } else {
+                Err(meta.error("Expected `tag = u8` or `tag = varint` on the enum."))
+            }
+        })?;
+    }
+    Ok(format)
+}
+
+/// The tag of each variant, from its `#[cereal(tag = N)]`. Every variant needs one, and they must
+/// be unique and fit in the tag format.
+fn variant_tags(data: &DataEnum, format: TagFormat) -> syn::Result<Vec<Literal>> {
+    let mut tags = Vec::<u64>::new();
+    for variant in &data.variants {
+        let mut tag = None;
+        for attr in cereal_attrs(&variant.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    let value: LitInt = meta.value()?.parse()?;
+                    let parsed = value.base10_parse::<u64>()?;
+                    if format == TagFormat::U8 && parsed > u8::MAX as u64 {
+                        return Err(syn::Error::new(value.span(), "The tag doesn't fit in a u8 (see `#[cereal(tag = varint)]`)."));
+                    }
+                    if tags.contains(&parsed) {
+                        return Err(syn::Error::new(value.span(), "Duplicate tag."));
+                    }
+                    tag = Some(parsed);
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected `tag = N` on a variant."))
+                }
+            })?;
+        }
+        let Some(tag) = tag else {
+            return Err(syn::Error::new(variant.ident.span(), "Every variant needs a `#[cereal(tag = N)]`."));
+        };
+        tags.push(tag);
+    }
+    Ok(tags.into_iter().map(Literal::u64_unsuffixed).collect())
+}
+
+/// The `with = path` of the field's `#[cereal(...)]` attributes.
+fn field_with(field: &Field) -> syn::Result<Option<Path>> {
+    let mut with = None;
+    for attr in cereal_attrs(&field.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("with") {
+                with = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("Expected `with = path` on a field."))
+            }
+        })?;
+    }
+    Ok(with)
+}
+
+/// The pattern that binds the fields, and the statements that encode them.
+fn destructure(fields: &Fields) -> syn::Result<(TokenStream, Vec<TokenStream>)> {
+    let mut bindings = Vec::new();
+    let mut encodes = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let binding = format_ident!("field_{}", index, span = Span::call_site());
+        encodes.push(match field_with(field)? {
+            Some(with) => quote! { written += #with::encode(#binding, encoder)?; },
+            None => quote! { written += ::mfcereal::encode::Encode::encode(#binding, encoder)?; },
+        });
+        bindings.push(match &field.ident {
+            Some(ident) => quote! { #ident: #binding },
+            None => quote! { #binding },
+        });
+    }
+    let pattern = match fields {
+        Fields::Named(_) => quote! { { #(#bindings),* } },
+        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+        Fields::Unit => quote! {},
+    };
+    Ok((pattern, encodes))
+}
+
+/// The fields of a struct expression, each decoded in order.
+fn construct(fields: &Fields) -> syn::Result<TokenStream> {
+    let mut decodes = Vec::new();
+    for field in fields {
+        let decode = match field_with(field)? {
+            Some(with) => quote! { #with::decode(decoder)? },
+            None => quote! { ::mfcereal::decode::Decode::decode(decoder)? },
+        };
+        decodes.push(match &field.ident {
+            Some(ident) => quote! { #ident: #decode },
+            None => decode,
+        });
+    }
+    Ok(match fields {
+        Fields::Named(_) => quote! { { #(#decodes),* } },
+        Fields::Unnamed(_) => quote! { ( #(#decodes),* ) },
+        Fields::Unit => quote! {},
+    })
+}
@@ -0,0 +1,136 @@
+//! Bit-level packing on top of [Encoder] and [Decoder].
+//!
+//! Bits are written most significant first, matching the big-endian byte order used everywhere else.
+//! The final byte is padded with zeros when the writer is finished.
+
+use crate::{
+    decode::{DecodeError, Decoder},
+    encode::Encoder,
+};
+
+/// Packs values of up to 64 bits into an [Encoder].
+///
+/// [BitWriter::finish] must be called to write the last partial byte.
+pub struct BitWriter<'a, E: Encoder> {
+    encoder: &'a mut E,
+    /// Pending bits, right aligned.
+    buffer: u64,
+    buffered: u32,
+    written: u64,
+}
+
+impl<'a, E: Encoder> BitWriter<'a, E> {
+    #[inline]
+    pub fn new(encoder: &'a mut E) -> Self {
+        Self {
+            encoder,
+            buffer: 0,
+            buffered: 0,
+            written: 0,
+        }
+    }
+
+    /// Writes the low `count` bits of `value`.
+    pub fn write_bits(&mut self, value: u64, count: u32) -> Result<(), E::Error> {
+        assert!(count <= 64, "Can't write more than 64 bits at once: {count}");
+        let mut remaining = count;
+        while remaining > 0 {
+            let take = remaining.min(8 - self.buffered);
+            let shift = remaining - take;
+            let bits = (value >> shift) & ((1u64 << take) - 1);
+            self.buffer = (self.buffer << take) | bits;
+            self.buffered += take;
+            remaining -= take;
+            if self.buffered == 8 {
+                self.written += self.encoder.write_u8(self.buffer as u8)?;
+                self.buffer = 0;
+                self.buffered = 0;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn write_bool(&mut self, value: bool) -> Result<(), E::Error> {
+        self.write_bits(value as u64, 1)
+    }
+
+    /// Pads the last partial byte with zeros and returns the number of bytes written.
+    pub fn finish(mut self) -> Result<u64, E::Error> {
+        if self.buffered > 0 {
+            let padding = 8 - self.buffered;
+            self.write_bits(0, padding)?;
+        }
+        Ok(self.written)
+    }
+}
+
+/// Reads values written by a [BitWriter].
+pub struct BitReader<'a, D: Decoder> {
+    decoder: &'a mut D,
+    buffer: u8,
+    /// Unread bits remaining in `buffer`.
+    available: u32,
+}
+
+impl<'a, D: Decoder> BitReader<'a, D> {
+    #[inline]
+    pub fn new(decoder: &'a mut D) -> Self {
+        Self {
+            decoder,
+            buffer: 0,
+            available: 0,
+        }
+    }
+
+    /// Reads `count` bits into the low bits of the result.
+    pub fn read_bits(&mut self, count: u32) -> Result<u64, DecodeError<D::Error>> {
+        assert!(count <= 64, "Can't read more than 64 bits at once: {count}");
+        let mut value = 0u64;
+        let mut remaining = count;
+        while remaining > 0 {
+            if self.available == 0 {
+                self.buffer = self.decoder.read_u8()?;
+                self.available = 8;
+            }
+            let take = remaining.min(self.available);
+            let bits = (self.buffer >> (self.available - take)) as u64 & ((1u64 << take) - 1);
+            value = (value << take) | bits;
+            self.available -= take;
+            remaining -= take;
+        }
+        Ok(value)
+    }
+
+    #[inline]
+    pub fn read_bool(&mut self) -> Result<bool, DecodeError<D::Error>> {
+        Ok(self.read_bits(1)? != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_test() {
+        let mut bytes = Vec::new();
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_bits(0xA, 4).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_bits(0x1234_5678_9ABC_DEF0, 64).unwrap();
+        writer.write_bits(0b01, 2).unwrap();
+        assert_eq!(writer.finish().unwrap(), 9);
+        assert_eq!(bytes[0], 0b1010_1000);
+
+        let mut input = bytes.as_slice();
+        let mut reader = BitReader::new(&mut input);
+        assert_eq!(reader.read_bits(4).unwrap(), 0xA);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_bits(64).unwrap(), 0x1234_5678_9ABC_DEF0);
+        assert_eq!(reader.read_bits(2).unwrap(), 0b01);
+        // Padding.
+        assert_eq!(reader.read_bits(1).unwrap(), 0);
+        assert!(reader.read_bits(8).is_err());
+    }
+}
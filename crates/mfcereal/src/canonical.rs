@@ -0,0 +1,147 @@
+//! The primitive encoding policy shared by [Encoder](crate::encode::Encoder) and `mfhash`'s
+//! `DeterministicHasher`.
+//!
+//! Both write the same primitives with the same widths and tags, and differ only in byte order:
+//! saves are big-endian ([ByteOrder::WIRE]) and hashes are little-endian ([ByteOrder::HASH]). Keeping
+//! the rules here means a value's encoding and its deterministic hash can't drift apart, so content
+//! addressed data can be hashed from its encoded bytes (see `mfhash::canonical`).
+//!
+//! - Integers are fixed width two's complement.
+//! - `usize` and `isize` are widened to 64 bits ([size], [signed_size]), so they're the same on every
+//!   pointer width.
+//! - `bool` is one byte, 0 or 1 ([bool_byte]).
+//! - `char` is its scalar value as a `u32` ([char_scalar]).
+//! - Floats are only hashed, as their IEEE 754 bits with `-0.0` and NaN payloads canonicalized
+//!   ([f32_bits], [f64_bits]).
+//! - Strings and slices are their length as a size followed by their elements.
+//! - `Option` and `Result` are a one byte tag ([SOME], [NONE], [OK], [ERR]) followed by the value.
+
+/// The tag written before the value of `Some`.
+pub const SOME: u8 = 1;
+/// The tag written for `None`.
+pub const NONE: u8 = 0;
+/// The tag written before the value of `Ok`.
+pub const OK: u8 = 1;
+/// The tag written before the value of `Err`.
+pub const ERR: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ByteOrder {
+    Big,
+    Little,
+}
+
+impl ByteOrder {
+    /// The byte order of encoded data.
+    pub const WIRE: Self = Self::Big;
+    /// The byte order of deterministic hash input.
+    pub const HASH: Self = Self::Little;
+}
+
+macro_rules! byte_order_fns {
+    ($($fn_name:ident($ty:ty; $len:literal)),+$(,)?) => {
+        impl ByteOrder {
+            $(
+                #[inline]
+                #[must_use]
+                pub const fn $fn_name(self, value: $ty) -> [u8; $len] {
+                    match self {
+                        Self::Big => value.to_be_bytes(),
+                        Self::Little => value.to_le_bytes(),
+                    }
+                }
+            )+
+        }
+    };
+}
+
+byte_order_fns!(
+    u16_bytes(u16; 2),
+    u32_bytes(u32; 4),
+    u64_bytes(u64; 8),
+    u128_bytes(u128; 16),
+);
+
+/// Sizes are always 8 bytes wide.
+#[inline]
+#[must_use]
+pub const fn size(value: usize) -> u64 {
+    value as u64
+}
+
+/// Signed sizes are sign extended to 8 bytes.
+#[inline]
+#[must_use]
+pub const fn signed_size(value: isize) -> i64 {
+    value as i64
+}
+
+#[inline]
+#[must_use]
+pub const fn bool_byte(value: bool) -> u8 {
+    value as u8
+}
+
+#[inline]
+#[must_use]
+pub const fn char_scalar(value: char) -> u32 {
+    value as u32
+}
+
+/// The bits of every NaN `f32` once canonicalized: the quiet NaN with no payload.
+pub const CANONICAL_NAN_F32: u32 = 0x7FC0_0000;
+/// The bits of every NaN `f64` once canonicalized: the quiet NaN with no payload.
+pub const CANONICAL_NAN_F64: u64 = 0x7FF8_0000_0000_0000;
+
+/// The IEEE 754 bits of `value`, with `-0.0` written as `0.0` and every NaN (whatever its sign and
+/// payload) written as [CANONICAL_NAN_F32], so that values that compare equal, or are both NaN,
+/// have the same bits. Every other value keeps its bits, including infinities and subnormals.
+#[inline]
+#[must_use]
+pub const fn f32_bits(value: f32) -> u32 {
+    if value.is_nan() {
+        CANONICAL_NAN_F32
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Like [f32_bits], for `f64`.
+#[inline]
+#[must_use]
+pub const fn f64_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        CANONICAL_NAN_F64
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_bits_test() {
+        assert_eq!(f32_bits(-0.0), f32_bits(0.0));
+        assert_eq!(f64_bits(-0.0), 0);
+        assert_eq!(f32_bits(f32::from_bits(0xFFC0_1234)), CANONICAL_NAN_F32);
+        assert_eq!(f64_bits(-f64::NAN), CANONICAL_NAN_F64);
+        assert_eq!(f32_bits(1.5), 1.5f32.to_bits());
+        assert_eq!(f64_bits(f64::NEG_INFINITY), f64::NEG_INFINITY.to_bits());
+        assert_eq!(f64_bits(f64::from_bits(1)), 1);
+    }
+
+    #[test]
+    fn byte_order_test() {
+        assert_eq!(ByteOrder::WIRE.u32_bytes(0x01020304), [1, 2, 3, 4]);
+        assert_eq!(ByteOrder::HASH.u32_bytes(0x01020304), [4, 3, 2, 1]);
+        assert_eq!(ByteOrder::HASH.u16_bytes(0xBEEF), 0xBEEFu16.to_le_bytes());
+        assert_eq!(signed_size(-1), -1i64);
+        assert_eq!(char_scalar('A'), 0x41);
+    }
+}
@@ -0,0 +1,71 @@
+//! Compatibility fixtures for the encoded format.
+//!
+//! Save files must read back the same on every target, including 32-bit WASM and big-endian
+//! machines. Every value is encoded big-endian with a fixed width, so the expected bytes don't
+//! depend on the host: each fixture below is what every target (x86_64, aarch64, wasm32, and
+//! big-endian targets alike) must produce and accept. `usize` and `isize` are always 8 bytes;
+//! on narrower targets, values that don't fit fail with [DecodeError::SizeOverflow].
+
+use crate::{
+    decode::{DecodeError, Decoder},
+    encode::Encoder,
+};
+
+const U16_FIXTURE: &[u8] = &[0x12, 0x34];
+const U32_FIXTURE: &[u8] = &[0x12, 0x34, 0x56, 0x78];
+const U64_FIXTURE: &[u8] = &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+const I32_FIXTURE: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFE];
+const CHAR_FIXTURE: &[u8] = &[0x00, 0x01, 0xF9, 0x80];
+/// `usize` 0x1234_5678, which fits on every pointer width.
+const USIZE_FIXTURE: &[u8] = &[0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78];
+/// `isize` -2, sign extended to 8 bytes even when written by a 32-bit target.
+const ISIZE_FIXTURE: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE];
+/// A `usize` of 2^32, which only fits on 64-bit targets.
+const WIDE_USIZE_FIXTURE: &[u8] = &[0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+/// An `isize` of -2^31 - 1, which only fits on 64-bit targets.
+const WIDE_ISIZE_FIXTURE: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF];
+/// The string "ok", length prefixed by an 8 byte `usize`.
+const STR_FIXTURE: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 2, b'o', b'k'];
+
+fn encoded<F: FnOnce(&mut Vec<u8>) -> Result<u64, <Vec<u8> as Encoder>::Error>>(write: F) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let written = write(&mut bytes).unwrap();
+    assert_eq!(written, bytes.len() as u64);
+    bytes
+}
+
+#[test]
+fn fixed_width_fixtures_test() {
+    assert_eq!(encoded(|bytes| bytes.write_u16(0x1234)), U16_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_u32(0x1234_5678)), U32_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_u64(0x0123_4567_89AB_CDEF)), U64_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_i32(-2)), I32_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_char('🦀')), CHAR_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_str("ok")), STR_FIXTURE);
+
+    assert_eq!((&mut &U16_FIXTURE[..]).read_u16().unwrap(), 0x1234);
+    assert_eq!((&mut &U32_FIXTURE[..]).read_u32().unwrap(), 0x1234_5678);
+    assert_eq!((&mut &U64_FIXTURE[..]).read_u64().unwrap(), 0x0123_4567_89AB_CDEF);
+    assert_eq!((&mut &I32_FIXTURE[..]).read_i32().unwrap(), -2);
+    assert_eq!((&mut &CHAR_FIXTURE[..]).read_char().unwrap(), '🦀');
+    assert_eq!((&mut &STR_FIXTURE[..]).read_str().unwrap(), "ok");
+}
+
+#[test]
+fn pointer_width_fixtures_test() {
+    assert_eq!(encoded(|bytes| bytes.write_usize(0x1234_5678)), USIZE_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_isize(-2)), ISIZE_FIXTURE);
+    assert_eq!(encoded(|bytes| bytes.write_isize_slice(&[-2], false)), ISIZE_FIXTURE);
+    assert_eq!((&mut &USIZE_FIXTURE[..]).read_usize().unwrap(), 0x1234_5678);
+    assert_eq!((&mut &ISIZE_FIXTURE[..]).read_isize().unwrap(), -2);
+
+    let wide_usize = (&mut &WIDE_USIZE_FIXTURE[..]).read_usize();
+    let wide_isize = (&mut &WIDE_ISIZE_FIXTURE[..]).read_isize();
+    if usize::BITS >= 64 {
+        assert_eq!(wide_usize.unwrap() as u64, 1 << 32);
+        assert_eq!(wide_isize.unwrap() as i64, -(1 << 31) - 1);
+    } else {
+        assert!(matches!(wide_usize, Err(DecodeError::SizeOverflow(value)) if value == 1 << 32));
+        assert!(matches!(wide_isize, Err(DecodeError::SizeOverflow(value)) if value == -(1 << 31) - 1));
+    }
+}
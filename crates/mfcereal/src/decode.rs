@@ -1,5 +1,12 @@
 use std::ffi::CString;
 
+use crate::frame::{Frame, FrameHeader};
+
+/// Derives [Decode], reading what the [Encode](crate::encode::Encode) derive writes. An enum tag
+/// that no variant has fails with [DecodeError::UnknownDiscriminant].
+#[cfg(feature = "derive")]
+pub use mfcereal_derive::Decode;
+
 
 #[inline(always)]
 const fn size_align_eq<L: Sized, R: Sized>() -> bool {
@@ -39,6 +46,11 @@ pub fn decoder_read_value<
     Ok(f(buf))
 }
 
+/// The most elements reserved up front for a length read from the input. Longer vecs grow as
+/// they're read, so a corrupt length fails with an error at the end of the input instead of
+/// allocating whatever it says.
+pub const MAX_PREALLOCATION: usize = 4096;
+
 /// Read [Vec<T>] from `decoder` ([Decoder]) using function `f`
 /// to read individual values.
 #[inline(always)]
@@ -51,13 +63,24 @@ pub fn decoder_read_vec<
     f: F,
 ) -> Result<Vec<T>, DecodeError<D::Error>> {
     let capacity = decoder.read_usize()?;
-    let mut buf = Vec::with_capacity(capacity);
+    let mut buf = Vec::with_capacity(capacity.min(MAX_PREALLOCATION));
     for _ in 0..capacity {
         buf.push(f(decoder)?);
     }
     Ok(buf)
 }
 
+/// Read `len` bytes from `decoder`, growing the buffer as they're read (see [MAX_PREALLOCATION]).
+pub fn decoder_read_bytes<D: Decoder>(decoder: &mut D, len: usize) -> Result<Vec<u8>, DecodeError<D::Error>> {
+    let mut buf = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    while buf.len() < len {
+        let start = buf.len();
+        buf.resize(start + (len - start).min(MAX_PREALLOCATION), 0);
+        decoder.read_exact(&mut buf[start..])?;
+    }
+    Ok(buf)
+}
+
 #[inline(always)]
 pub fn decoder_read_slice<
     D: Decoder,
@@ -83,10 +106,26 @@ pub enum DecodeError<E> {
     Utf8Error(#[from] ::std::string::FromUtf8Error),
     #[error("From Vec With Nul Error: {0}")]
     FromVecWithNul(#[from] ::std::ffi::FromVecWithNulError),
+    #[error("Invalid data: {0}")]
+    InvalidData(&'static str),
+    /// A `usize` or `isize` was too large for this platform's pointer width (see [Decoder::read_usize]).
+    #[error("Size {0} does not fit in {bits} bits", bits = usize::BITS)]
+    SizeOverflow(i128),
+    /// An enum tag that doesn't belong to any variant.
+    #[error("Unknown discriminant {value} for {ty}")]
+    UnknownDiscriminant {
+        ty: &'static str,
+        value: u64,
+    },
     #[error("Decoder Error: {0}")]
     DecoderError(E),
 }
 
+/// Returned by decoders that ran out of input before a value was fully read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Unexpected end of input")]
+pub struct UnexpectedEof;
+
 impl<E> DecodeError<E> {
     #[inline(always)]
     pub fn map<T>(result: Result<T, E>) -> Result<T, Self> {
@@ -98,6 +137,31 @@ pub trait Decoder: Sized {
     type Error;
     fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>>;
     
+    /// Skips the next `len` bytes. Reads and discards them by default; decoders that can jump
+    /// ahead (over slices, or seekable files) override this to skip in O(1).
+    fn skip(&mut self, len: usize) -> Result<(), DecodeError<Self::Error>> {
+        let mut buf = [0u8; 512];
+        let mut remaining = len;
+        while remaining > 0 {
+            let count = remaining.min(buf.len());
+            self.read_exact(&mut buf[..count])?;
+            remaining -= count;
+        }
+        Ok(())
+    }
+    
+    /// Reads a frame header (see [crate::frame]) and returns a decoder over the frame's body.
+    fn enter_frame(&mut self) -> Result<Frame<'_, Self>, DecodeError<Self::Error>> {
+        Frame::enter(self)
+    }
+    
+    /// Skips a whole frame (see [crate::frame]) without reading its body, returning its tag.
+    fn skip_frame(&mut self) -> Result<u32, DecodeError<Self::Error>> {
+        let header = FrameHeader::decode(self)?;
+        self.skip(header.len)?;
+        Ok(header.tag)
+    }
+    
     fn read_u8(&mut self) -> Result<u8, DecodeError<Self::Error>> {
         decoder_read_value(self, u8::from_be_bytes)
     }
@@ -118,8 +182,12 @@ pub trait Decoder: Sized {
         decoder_read_value(self, u128::from_be_bytes)
     }
     
+    /// Sizes are always stored as 8 bytes regardless of pointer width. A value that doesn't fit in
+    /// this platform's `usize` (for example, on 32-bit or WASM targets) fails with
+    /// [DecodeError::SizeOverflow] rather than being truncated.
     fn read_usize(&mut self) -> Result<usize, DecodeError<Self::Error>> {
-        decoder_read_value(self, |bytes: [u8; 8]| u64::from_be_bytes(bytes) as usize)
+        let value = self.read_u64()?;
+        usize::try_from(value).map_err(|_| DecodeError::SizeOverflow(value as i128))
     }
     
     fn read_i8(&mut self) -> Result<i8, DecodeError<Self::Error>> {
@@ -142,14 +210,37 @@ pub trait Decoder: Sized {
         decoder_read_value(self, i128::from_be_bytes)
     }
     
+    /// Stored as an `i64`. See [Decoder::read_usize].
     fn read_isize(&mut self) -> Result<isize, DecodeError<Self::Error>> {
-        decoder_read_value(self, |bytes: [u8; 8]| i64::from_be_bytes(bytes) as isize)
+        let value = self.read_i64()?;
+        isize::try_from(value).map_err(|_| DecodeError::SizeOverflow(value as i128))
     }
     
     fn read_bool(&mut self) -> Result<bool, DecodeError<Self::Error>> {
         decoder_read_value(self, |[byte]| byte != 0)
     }
     
+    /// Reads a varint written by [Encoder::write_varint](crate::encode::Encoder::write_varint).
+    /// Fails on varints that are longer than necessary, so that every value has one encoding.
+    fn read_varint(&mut self) -> Result<u64, DecodeError<Self::Error>> {
+        let mut value = 0u64;
+        for index in 0..10 {
+            let byte = self.read_u8()?;
+            let bits = (byte & 0x7F) as u64;
+            if index == 9 && bits > 1 {
+                return Err(DecodeError::InvalidData("varint overflows u64"));
+            }
+            value |= bits << (index * 7);
+            if byte & 0x80 == 0 {
+                if byte == 0 && index != 0 {
+                    return Err(DecodeError::InvalidData("varint is not minimally encoded"));
+                }
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidData("varint is too long"))
+    }
+    
     fn read_char(&mut self) -> Result<char, DecodeError<Self::Error>> {
         let mut bytes = [0u8; 4];
         self.read_exact(&mut bytes)?;
@@ -212,9 +303,7 @@ pub trait Decoder: Sized {
     
     fn read_u8_vec(&mut self) -> Result<Vec<u8>, DecodeError<Self::Error>> {
         let len = self.read_usize()?;
-        let mut buf = vec![0u8; len];
-        self.read_exact(&mut buf)?;
-        Ok(buf)
+        decoder_read_bytes(self, len)
     }
     
     fn read_u16_vec(&mut self) -> Result<Vec<u16>, DecodeError<Self::Error>> {
@@ -272,7 +361,7 @@ pub trait Decoder: Sized {
     
     fn read_char_vec(&mut self) -> Result<Vec<char>, DecodeError<Self::Error>> {
         let len = self.read_usize()?;
-        let mut output = Vec::with_capacity(len);
+        let mut output = Vec::with_capacity(len.min(MAX_PREALLOCATION));
         for _ in 0..len {
             output.push(self.read_char()?);
         }
@@ -290,6 +379,29 @@ pub trait Decoder: Sized {
     }
 }
 
+impl Decoder for &[u8] {
+    type Error = UnexpectedEof;
+    
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>> {
+        let Some((head, tail)) = self.split_at_checked(buf.len()) else {
+            return Err(DecodeError::DecoderError(UnexpectedEof));
+        };
+        buf.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+    
+    #[inline]
+    fn skip(&mut self, len: usize) -> Result<(), DecodeError<Self::Error>> {
+        let Some(tail) = self.get(len..) else {
+            return Err(DecodeError::DecoderError(UnexpectedEof));
+        };
+        *self = tail;
+        Ok(())
+    }
+}
+
 #[inline(always)]
 fn map_err<T, E>(result: Result<T, E>) -> Result<T, DecodeError<E>> {
     DecodeError::map(result)
@@ -340,11 +452,30 @@ impl Decode for char {
     }
 }
 
+impl<T: Decode> Decode for Option<T> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        match decoder.read_u8()? {
+            1 => Ok(Some(T::decode(decoder)?)),
+            0 => Ok(None),
+            value => Err(DecodeError::UnknownDiscriminant { ty: "Option", value: value as u64 }),
+        }
+    }
+}
+
+impl<T: Decode, E: Decode> Decode for Result<T, E> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        match decoder.read_u8()? {
+            1 => Ok(Ok(T::decode(decoder)?)),
+            0 => Ok(Err(E::decode(decoder)?)),
+            value => Err(DecodeError::UnknownDiscriminant { ty: "Result", value: value as u64 }),
+        }
+    }
+}
+
 impl<T: Decode + 'static> Decode for Vec<T> {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
-        let len = decoder.read_usize()?;
-        let vec = decoder_read_vec(decoder, T::decode)?;
-        unimplemented!()
+        // The same layout for every `T`, including the byte slices written for `u8` and `i8`.
+        decoder_read_vec(decoder, T::decode)
     }
 }
 
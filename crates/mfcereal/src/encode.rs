@@ -1,6 +1,15 @@
 use ::core::ffi::CStr;
 use std::any::TypeId;
 
+use crate::canonical::{self, ByteOrder};
+
+/// Derives [Encode] for a struct (its fields in declaration order) or an enum (the variant's tag,
+/// then its fields). Every variant of an enum needs an explicit `#[cereal(tag = N)]`, and
+/// `#[cereal(tag = varint)]` on the enum writes the tags as varints instead of `u8`s (see
+/// [enums](crate::enums)).
+#[cfg(feature = "derive")]
+pub use mfcereal_derive::Encode;
+
 struct Counter {
     count: u64,
 }
@@ -55,27 +64,28 @@ pub trait Encoder {
     }
     
     fn write_u16(&mut self, value: u16) -> EncRes<Self::Error> {
-        let bytes = value.to_be_bytes();
+        let bytes = ByteOrder::WIRE.u16_bytes(value);
         self.write_exact(&bytes)
     }
     
     fn write_u32(&mut self, value: u32) -> EncRes<Self::Error> {
-        let bytes = value.to_be_bytes();
+        let bytes = ByteOrder::WIRE.u32_bytes(value);
         self.write_exact(&bytes)
     }
     
     fn write_u64(&mut self, value: u64) -> EncRes<Self::Error> {
-        let bytes = value.to_be_bytes();
+        let bytes = ByteOrder::WIRE.u64_bytes(value);
         self.write_exact(&bytes)
     }
     
     fn write_u128(&mut self, value: u128) -> EncRes<Self::Error> {
-        let bytes = value.to_be_bytes();
+        let bytes = ByteOrder::WIRE.u128_bytes(value);
         self.write_exact(&bytes)
     }
     
+    /// Sizes are always written as 8 bytes so that they read back the same on every pointer width.
     fn write_usize(&mut self, value: usize) -> EncRes<Self::Error> {
-        self.write_u64(value as u64)
+        self.write_u64(canonical::size(value))
     }
     
     fn write_i8(&mut self, value: i8) -> EncRes<Self::Error> {
@@ -98,20 +108,37 @@ pub trait Encoder {
         self.write_u128(value.cast_unsigned())
     }
     
+    /// Written as an `i64`, so that negative values are sign extended on 32-bit targets.
     fn write_isize(&mut self, value: isize) -> EncRes<Self::Error> {
-        self.write_usize(value.cast_unsigned())
+        self.write_i64(canonical::signed_size(value))
     }
     
     fn write_bool(&mut self, value: bool) -> EncRes<Self::Error> {
-        self.write_u8(if value {
-            1
-        } else {
-            0
-        })
+        self.write_u8(canonical::bool_byte(value))
     }
     
     fn write_char(&mut self, value: char) -> EncRes<Self::Error> {
-        self.write_u32(value as u32)
+        self.write_u32(canonical::char_scalar(value))
+    }
+    
+    /// Written as an unsigned LEB128 varint: 7 bits per byte, least significant first, with the high
+    /// bit set on every byte but the last. Always uses the fewest bytes possible.
+    fn write_varint(&mut self, value: u64) -> EncRes<Self::Error> {
+        let mut buf = [0u8; 10];
+        let mut len = 0;
+        let mut rest = value;
+        loop {
+            let byte = (rest & 0x7F) as u8;
+            rest >>= 7;
+            if rest == 0 {
+                buf[len] = byte;
+                len += 1;
+                break;
+            }
+            buf[len] = byte | 0x80;
+            len += 1;
+        }
+        self.write_exact(&buf[..len])
     }
     
     fn write_str(&mut self, value: &str) -> EncRes<Self::Error> {
@@ -219,9 +246,14 @@ pub trait Encoder {
     }
     
     fn write_isize_slice(&mut self, slice: &[isize], with_len: bool) -> EncRes<Self::Error> {
-        unsafe {
-            self.write_usize_slice(cast_slice(slice), with_len)
+        let mut count = Counter::new();
+        if with_len {
+            count.incr(self.write_usize(slice.len()))?;
+        }
+        for elem in slice.iter().copied() {
+            count.incr(self.write_isize(elem))?;
         }
+        count.ok()
     }
     
     fn write_bool_slice(&mut self, slice: &[bool], with_len: bool) -> EncRes<Self::Error> {
@@ -247,6 +279,16 @@ pub trait Encoder {
     }
 }
 
+impl Encoder for Vec<u8> {
+    type Error = ::core::convert::Infallible;
+    
+    #[inline]
+    fn write_exact(&mut self, bytes: &[u8]) -> EncRes<Self::Error> {
+        self.extend_from_slice(bytes);
+        Ok(bytes.len() as u64)
+    }
+}
+
 pub trait Encode {
     fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error>;
 }
@@ -288,7 +330,10 @@ fn encode_sized_slice<T: Encode + 'static, E: Encoder>(slice: &[T], encoder: &mu
         TypeId::of::<T>() == TypeId::of::<u8>()
         || TypeId::of::<T>() == TypeId::of::<i8>()
     {
-        let slice: &[u8] = unsafe { cast_slice(slice) };
+        // Not cast_slice: its size check would fail to compile for every other `T`.
+        let slice: &[u8] = unsafe {
+            ::core::slice::from_raw_parts(slice.as_ptr().cast(), slice.len())
+        };
         encoder.write_u8_slice(slice, true)
     } else {
         let mut counter = Counter::new();
@@ -300,6 +345,27 @@ fn encode_sized_slice<T: Encode + 'static, E: Encoder>(slice: &[T], encoder: &mu
     }
 }
 
+// Layout: tag (u8): 1 = Some, followed by the value, or 0 = None.
+// The tags are shared with DeterministicHash (see crate::canonical).
+impl<T: Encode> Encode for Option<T> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        match self {
+            Some(value) => Ok(encoder.write_u8(canonical::SOME)? + value.encode(encoder)?),
+            None => encoder.write_u8(canonical::NONE),
+        }
+    }
+}
+
+// Layout: tag (u8): 1 = Ok or 0 = Err, followed by the value.
+impl<T: Encode, Er: Encode> Encode for Result<T, Er> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        match self {
+            Ok(value) => Ok(encoder.write_u8(canonical::OK)? + value.encode(encoder)?),
+            Err(error) => Ok(encoder.write_u8(canonical::ERR)? + error.encode(encoder)?),
+        }
+    }
+}
+
 impl<T: Encode + 'static> Encode for Vec<T> {
     fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
         encode_sized_slice(self.as_slice(), encoder)
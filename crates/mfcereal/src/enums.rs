@@ -0,0 +1,99 @@
+//! The wire format of enums.
+//!
+//! An enum is written as a tag identifying the variant, followed by the variant's fields in order.
+//! Tags are explicit discriminants assigned with `#[cereal(tag = N)]` on each variant, never
+//! derived from declaration order, so reordering or adding variants doesn't change the format. Tags
+//! are written as a `u8`, or as a varint ([Encoder::write_varint](crate::encode::Encoder::write_varint))
+//! for enums marked `#[cereal(tag = varint)]` that might outgrow 256 variants. Decoding an unknown
+//! tag fails with [DecodeError::UnknownDiscriminant](crate::decode::DecodeError::UnknownDiscriminant),
+//! which includes the tag. Every variant must have a tag, and tags must be unique; both are checked
+//! at compile time.
+//!
+//! ```
+//! # use mfcereal::{decode::Decode, encode::Encode};
+//! #[derive(Debug, PartialEq, Encode, Decode)]
+//! enum Shape {
+//!     #[cereal(tag = 0)]
+//!     Point,
+//!     #[cereal(tag = 2)]
+//!     Circle(u32),
+//!     #[cereal(tag = 1)]
+//!     Rect { width: u32, height: u32 },
+//! }
+//!
+//! let mut bytes = Vec::new();
+//! Shape::Circle(5).encode(&mut bytes).unwrap();
+//! assert_eq!(bytes, [2, 0, 0, 0, 5]);
+//! assert_eq!(Shape::decode(&mut bytes.as_slice()).unwrap(), Shape::Circle(5));
+//! ```
+//!
+//! `Option` and `Result` use `u8` tags matching the `DeterministicHash` convention: `1` for
+//! `Some`/`Ok` and `0` for `None`/`Err`.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode::{Decode, DecodeError, Decoder, UnexpectedEof},
+        encode::{Encode, Encoder},
+    };
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[cereal(tag = varint)]
+    enum Command {
+        #[cereal(tag = 0)]
+        Stop,
+        #[cereal(tag = 1)]
+        Move(i32, i32),
+        #[cereal(tag = 300)]
+        Say { volume: u16, loud: bool },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    struct Waypoint<T> {
+        name: u8,
+        at: T,
+    }
+
+    fn round_trip<T: Encode + Decode + PartialEq + std::fmt::Debug>(value: T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let written = value.encode(&mut bytes).unwrap();
+        assert_eq!(written, bytes.len() as u64);
+        let mut input = bytes.as_slice();
+        assert_eq!(T::decode(&mut input).unwrap(), value);
+        assert!(input.is_empty());
+        bytes
+    }
+
+    #[test]
+    fn enum_test() {
+        assert_eq!(round_trip(Command::Stop), [0]);
+        assert_eq!(round_trip(Command::Move(-1, 2)), [1, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 2]);
+        assert_eq!(round_trip(Command::Say { volume: 9, loud: true })[..2], [0xAC, 0x02]);
+        assert_eq!(round_trip(Waypoint { name: 3, at: Command::Move(0, 1) }), [3, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let unknown = Command::decode(&mut [2u8].as_slice());
+        assert!(matches!(unknown, Err(DecodeError::UnknownDiscriminant { ty: "Command", value: 2 })));
+
+        assert_eq!(round_trip(Some(7u8)), [1, 7]);
+        assert_eq!(round_trip(None::<u8>), [0]);
+        assert_eq!(round_trip(Ok::<u8, u16>(3)), [1, 3]);
+        assert_eq!(round_trip(Err::<u8, u16>(3)), [0, 0, 3]);
+        assert!(matches!(
+            Option::<u8>::decode(&mut [5u8].as_slice()),
+            Err(DecodeError::UnknownDiscriminant { ty: "Option", value: 5 }),
+        ));
+    }
+
+    #[test]
+    fn varint_test() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            bytes.write_varint(value).unwrap();
+            assert_eq!(bytes.len(), (64 - value.leading_zeros() as usize).div_ceil(7).max(1));
+            assert_eq!((&mut bytes.as_slice()).read_varint().unwrap(), value);
+        }
+        let overlong: Result<u64, DecodeError<UnexpectedEof>> = (&mut [0x80u8, 0x00].as_slice()).read_varint();
+        assert!(matches!(overlong, Err(DecodeError::InvalidData(_))));
+        let overflow = (&mut [0xFFu8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02].as_slice()).read_varint();
+        assert!(matches!(overflow, Err(DecodeError::InvalidData(_))));
+    }
+}
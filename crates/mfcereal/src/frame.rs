@@ -0,0 +1,256 @@
+//! Length-prefixed nested frames, for sections that readers may want to skip.
+//!
+//! A frame is a tag, the length of its body, and the body. Readers that don't know a tag, or don't
+//! need a section (a chunk's voxels when only its header is wanted), [skip](Decoder::skip_frame) it
+//! without decoding the body, which is O(1) for decoders that can jump ahead. Frames nest: a
+//! frame's body can hold more frames.
+//!
+//! Frames are written with a [FrameEncoder], which fills in each frame's length when it's
+//! [ended](FrameEncoder::end_frame), or with [write_frame] on any [Encoder], which buffers the body.
+//! They're read with [Decoder::enter_frame], which returns a [Frame] decoder that can't read past
+//! the end of the body.
+
+use crate::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+/// The length of a frame's header.
+pub const HEADER_LEN: usize = 12;
+
+/// A frame's tag and the length of its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameHeader {
+    pub tag: u32,
+    pub len: usize,
+}
+
+// Layout: tag (u32), len (u64), then `len` bytes of body.
+// The length is fixed-width so that it can be filled in after the body is written.
+impl Encode for FrameHeader {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_u32(self.tag)? + encoder.write_usize(self.len)?)
+    }
+}
+
+impl Decode for FrameHeader {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self { tag: decoder.read_u32()?, len: decoder.read_usize()? })
+    }
+}
+
+/// Writes a frame with tag `tag` to any encoder. The body is written by `body` to a buffer first,
+/// since the length comes before it. To write frames without buffering, use a [FrameEncoder].
+pub fn write_frame<E: Encoder, F: FnOnce(&mut Vec<u8>)>(encoder: &mut E, tag: u32, body: F) -> Result<u64, E::Error> {
+    let mut bytes = Vec::new();
+    body(&mut bytes);
+    Ok(FrameHeader { tag, len: bytes.len() }.encode(encoder)? + encoder.write_exact(&bytes)?)
+}
+
+/// An in-memory [Encoder] that writes nested frames in place.
+#[derive(Debug, Default, Clone)]
+pub struct FrameEncoder {
+    bytes: Vec<u8>,
+    /// The start of each open frame's header, innermost last.
+    open: Vec<usize>,
+}
+
+impl FrameEncoder {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { bytes: Vec::new(), open: Vec::new() }
+    }
+
+    /// Starts a frame with tag `tag`. Everything written until the matching [Self::end_frame] is
+    /// its body.
+    pub fn begin_frame(&mut self, tag: u32) {
+        self.open.push(self.bytes.len());
+        let Ok(_) = FrameHeader { tag, len: 0 }.encode(&mut self.bytes);
+    }
+
+    /// Ends the innermost open frame, returning the length of its body.
+    ///
+    /// # Panics
+    /// If no frame is open.
+    pub fn end_frame(&mut self) -> usize {
+        let start = self.open.pop().expect("No open frame to end.");
+        let len = self.bytes.len() - start - HEADER_LEN;
+        self.bytes[start + 4..start + HEADER_LEN].copy_from_slice(&(len as u64).to_be_bytes());
+        len
+    }
+
+    /// The number of open frames.
+    #[inline]
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// The bytes written so far. The lengths of open frames aren't filled in yet.
+    #[inline]
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The bytes written.
+    ///
+    /// # Panics
+    /// If a frame is still open.
+    #[must_use]
+    pub fn finish(self) -> Vec<u8> {
+        assert!(self.open.is_empty(), "{} frame(s) not ended.", self.open.len());
+        self.bytes
+    }
+}
+
+impl Encoder for FrameEncoder {
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn write_exact(&mut self, bytes: &[u8]) -> Result<u64, Self::Error> {
+        self.bytes.write_exact(bytes)
+    }
+}
+
+/// A [Decoder] over the body of a frame, from [Decoder::enter_frame]. Reads past the end of the
+/// body fail with [DecodeError::InvalidData].
+#[derive(Debug)]
+pub struct Frame<'a, D: Decoder> {
+    decoder: &'a mut D,
+    tag: u32,
+    remaining: usize,
+}
+
+impl<'a, D: Decoder> Frame<'a, D> {
+    pub(crate) fn enter(decoder: &'a mut D) -> Result<Self, DecodeError<D::Error>> {
+        let FrameHeader { tag, len } = FrameHeader::decode(decoder)?;
+        Ok(Self { decoder, tag, remaining: len })
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    /// The number of body bytes not read yet.
+    #[inline]
+    #[must_use]
+    pub const fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Skips the rest of the body, leaving the outer decoder just after the frame.
+    pub fn exit(self) -> Result<(), DecodeError<D::Error>> {
+        self.decoder.skip(self.remaining)
+    }
+
+    #[inline]
+    fn take(&mut self, len: usize) -> Result<(), DecodeError<D::Error>> {
+        self.remaining = self.remaining.checked_sub(len)
+            .ok_or(DecodeError::InvalidData("read past the end of a frame"))?;
+        Ok(())
+    }
+}
+
+impl<D: Decoder> Decoder for Frame<'_, D> {
+    type Error = D::Error;
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>> {
+        self.take(buf.len())?;
+        self.decoder.read_exact(buf)
+    }
+
+    #[inline]
+    fn skip(&mut self, len: usize) -> Result<(), DecodeError<Self::Error>> {
+        self.take(len)?;
+        self.decoder.skip(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::UnexpectedEof, io::ReadDecoder};
+
+    const HEADER: u32 = 1;
+    const VOXELS: u32 = 2;
+    const UNKNOWN: u32 = 99;
+
+    fn chunk() -> Vec<u8> {
+        let mut encoder = FrameEncoder::new();
+        encoder.begin_frame(HEADER);
+        encoder.write_u32(7).unwrap();
+        encoder.begin_frame(UNKNOWN);
+        encoder.write_str("from a newer version").unwrap();
+        assert_eq!(encoder.depth(), 2);
+        encoder.end_frame();
+        encoder.write_u8(3).unwrap();
+        encoder.end_frame();
+        encoder.begin_frame(VOXELS);
+        encoder.write_exact(&[5; 4096]).unwrap();
+        assert_eq!(encoder.end_frame(), 4096);
+        encoder.write_u8(0xFF).unwrap();
+        encoder.finish()
+    }
+
+    fn read_header<D: Decoder>(decoder: &mut D) -> Result<(u32, u8), DecodeError<D::Error>> {
+        let mut header = decoder.enter_frame()?;
+        assert_eq!(header.tag(), HEADER);
+        let version = header.read_u32()?;
+        assert_eq!(header.skip_frame()?, UNKNOWN);
+        let flags = header.read_u8()?;
+        assert_eq!(header.remaining(), 0);
+        header.exit()?;
+        Ok((version, flags))
+    }
+
+    #[test]
+    fn frame_test() {
+        let bytes = chunk();
+
+        // Read the header and skip the voxels.
+        let mut decoder = bytes.as_slice();
+        assert_eq!(read_header(&mut decoder).unwrap(), (7, 3));
+        assert_eq!(decoder.skip_frame().unwrap(), VOXELS);
+        assert_eq!(decoder, [0xFF]);
+
+        // The same through a reader, which skips by reading.
+        let mut decoder = ReadDecoder::new(bytes.as_slice());
+        assert_eq!(read_header(&mut decoder).unwrap(), (7, 3));
+        assert_eq!(decoder.skip_frame().unwrap(), VOXELS);
+        assert_eq!(decoder.read_u8().unwrap(), 0xFF);
+
+        // Leaving a frame early skips the rest of it.
+        let mut decoder = bytes.as_slice();
+        let mut header = decoder.enter_frame().unwrap();
+        assert_eq!(header.read_u32().unwrap(), 7);
+        header.exit().unwrap();
+        let mut voxels = decoder.enter_frame().unwrap();
+        assert_eq!(voxels.tag(), VOXELS);
+        voxels.skip(4095).unwrap();
+        assert_eq!(voxels.read_u8().unwrap(), 5);
+        assert!(matches!(voxels.read_u8(), Err(DecodeError::InvalidData(_))));
+
+        // Buffered frames are the same bytes.
+        let mut buffered = Vec::new();
+        let Ok(_) = write_frame(&mut buffered, VOXELS, |body| body.extend([5; 4096]));
+        assert_eq!(buffered, bytes[bytes.len() - 1 - HEADER_LEN - 4096..bytes.len() - 1]);
+
+        // Truncated frames fail.
+        let mut truncated = &bytes[..bytes.len() - 2];
+        read_header(&mut truncated).unwrap();
+        assert!(matches!(truncated.skip_frame(), Err(DecodeError::DecoderError(UnexpectedEof))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn unended_frame_test() {
+        let mut encoder = FrameEncoder::new();
+        encoder.begin_frame(HEADER);
+        let _ = encoder.finish();
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::{Read, Write};
+
+use crate::{decode::{DecodeError, Decoder}, encode::Encoder};
+
+/// A [Decoder] that reads from any [Read].
+///
+/// Wrap unbuffered sources (such as [std::fs::File]) in a [std::io::BufReader].
+#[derive(Debug)]
+pub struct ReadDecoder<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> ReadDecoder<R> {
+    #[inline]
+    pub const fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Decoder for ReadDecoder<R> {
+    type Error = std::io::Error;
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>> {
+        self.reader.read_exact(buf).map_err(DecodeError::DecoderError)
+    }
+}
+
+/// An [Encoder] that writes to any [Write].
+#[derive(Debug)]
+pub struct WriteEncoder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> WriteEncoder<W> {
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Encoder for WriteEncoder<W> {
+    type Error = std::io::Error;
+
+    #[inline]
+    fn write_exact(&mut self, bytes: &[u8]) -> Result<u64, Self::Error> {
+        self.writer.write_all(bytes)?;
+        Ok(bytes.len() as u64)
+    }
+}
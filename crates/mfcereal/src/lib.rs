@@ -1,4 +1,20 @@
 //! Deterministic Data Serialization Library.
 
+// Lets `#[derive(Encode, Decode)]` name `::mfcereal` inside this crate too.
+extern crate self as mfcereal;
+
 pub mod encode;
-pub mod decode;
\ No newline at end of file
+pub mod decode;
+pub mod bits;
+pub mod canonical;
+pub mod enums;
+pub mod frame;
+pub mod io;
+pub mod region;
+pub mod slice;
+pub mod structs;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(test)]
+mod compat;
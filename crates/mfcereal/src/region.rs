@@ -0,0 +1,224 @@
+//! Decoding of large region files.
+//!
+//! With the `mmap` feature enabled (on platforms that support it), region files are memory-mapped
+//! and decoded through the borrowed [SliceDecoder] path. Otherwise, or if mapping fails, the file
+//! is read through a buffered reader instead.
+
+use std::{
+    fs::File,
+    io::{BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    decode::{DecodeError, Decoder, UnexpectedEof},
+    io::ReadDecoder,
+    slice::SliceDecoder,
+};
+
+#[derive(Debug)]
+enum Backing {
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    Mapped(memmap2::Mmap),
+    Buffered(File),
+}
+
+/// An open region file.
+#[derive(Debug)]
+pub struct RegionFile {
+    backing: Backing,
+    len: u64,
+}
+
+impl RegionFile {
+    /// Opens a region file, memory-mapping it when possible.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::from_file(file))
+    }
+
+    /// Opens a region file without memory-mapping it.
+    pub fn open_buffered<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            backing: Backing::Buffered(file),
+            len,
+        })
+    }
+
+    /// Memory-maps `file` when possible, falling back to buffered IO.
+    pub fn from_file(file: File) -> Self {
+        let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        #[cfg(all(feature = "mmap", any(unix, windows)))]
+        {
+            // Empty files can't be mapped on some platforms.
+            if len != 0 {
+                // SAFETY: The map is read-only. Region files must not be modified
+                //         by other processes while they are open.
+                if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                    return Self {
+                        backing: Backing::Mapped(map),
+                        len,
+                    };
+                }
+            }
+        }
+        Self {
+            backing: Backing::Buffered(file),
+            len,
+        }
+    }
+
+    /// Returns `true` if the file is memory-mapped.
+    #[inline]
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.backing, Backing::Buffered(_))
+    }
+
+    /// The length of the file in bytes.
+    #[inline]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bytes of the file, if it is memory-mapped.
+    #[inline]
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.backing {
+            #[cfg(all(feature = "mmap", any(unix, windows)))]
+            Backing::Mapped(map) => Some(map),
+            Backing::Buffered(_) => None,
+        }
+    }
+
+    /// Creates a decoder starting at the beginning of the file.
+    #[inline]
+    pub fn decoder(&mut self) -> std::io::Result<RegionDecoder<'_>> {
+        self.decoder_at(0)
+    }
+
+    /// Creates a decoder starting at `offset` bytes into the file.
+    pub fn decoder_at(&mut self, offset: u64) -> std::io::Result<RegionDecoder<'_>> {
+        if offset > self.len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        match &mut self.backing {
+            #[cfg(all(feature = "mmap", any(unix, windows)))]
+            Backing::Mapped(map) => {
+                let bytes: &[u8] = map;
+                Ok(RegionDecoder::Mapped(SliceDecoder::new(&bytes[offset as usize..])))
+            }
+            Backing::Buffered(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(RegionDecoder::Buffered(ReadDecoder::new(BufReader::new(file))))
+            }
+        }
+    }
+}
+
+/// A [Decoder] over a [RegionFile].
+#[derive(Debug)]
+pub enum RegionDecoder<'a> {
+    Mapped(SliceDecoder<'a>),
+    Buffered(ReadDecoder<BufReader<&'a mut File>>),
+}
+
+impl RegionDecoder<'_> {
+    /// Returns `true` if this decoder reads from memory-mapped bytes.
+    #[inline]
+    pub const fn is_mapped(&self) -> bool {
+        matches!(self, Self::Mapped(_))
+    }
+}
+
+impl Decoder for RegionDecoder<'_> {
+    type Error = std::io::Error;
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>> {
+        match self {
+            RegionDecoder::Mapped(decoder) => decoder.read_exact(buf).map_err(mapped_error),
+            RegionDecoder::Buffered(decoder) => decoder.read_exact(buf),
+        }
+    }
+
+    /// Seeks past buffered bytes rather than reading them. Skipping past the end of a buffered
+    /// file isn't caught until the next read.
+    #[inline]
+    fn skip(&mut self, len: usize) -> Result<(), DecodeError<Self::Error>> {
+        match self {
+            RegionDecoder::Mapped(decoder) => decoder.skip(len).map_err(mapped_error),
+            RegionDecoder::Buffered(decoder) => {
+                let offset = i64::try_from(len).map_err(|_| DecodeError::SizeOverflow(len as i128))?;
+                decoder.get_mut().seek_relative(offset).map_err(DecodeError::DecoderError)
+            },
+        }
+    }
+}
+
+fn mapped_error(err: DecodeError<UnexpectedEof>) -> DecodeError<std::io::Error> {
+    match err {
+        DecodeError::DecoderError(_) => DecodeError::DecoderError(std::io::ErrorKind::UnexpectedEof.into()),
+        DecodeError::InvalidChar(code) => DecodeError::InvalidChar(code),
+        DecodeError::Utf8Error(err) => DecodeError::Utf8Error(err),
+        DecodeError::FromVecWithNul(err) => DecodeError::FromVecWithNul(err),
+        DecodeError::InvalidData(msg) => DecodeError::InvalidData(msg),
+        DecodeError::SizeOverflow(value) => DecodeError::SizeOverflow(value),
+        DecodeError::UnknownDiscriminant { ty, value } => DecodeError::UnknownDiscriminant { ty, value },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+
+    fn write_region(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("mfcereal_{name}_{}.region", std::process::id()));
+        let mut bytes = Vec::new();
+        bytes.write_u8(1).unwrap();
+        bytes.write_u64(0x0123456789ABCDEF).unwrap();
+        bytes.write_str("chunk data").unwrap();
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn check(region: &mut RegionFile) {
+        let mut decoder = region.decoder().unwrap();
+        assert_eq!(decoder.read_u8().unwrap(), 1);
+        assert_eq!(decoder.read_u64().unwrap(), 0x0123456789ABCDEF);
+        assert_eq!(decoder.read_str().unwrap(), "chunk data");
+        assert!(decoder.read_u8().is_err());
+        let mut decoder = region.decoder_at(1).unwrap();
+        assert_eq!(decoder.read_u64().unwrap(), 0x0123456789ABCDEF);
+        let mut decoder = region.decoder().unwrap();
+        decoder.skip(9).unwrap();
+        assert_eq!(decoder.read_str().unwrap(), "chunk data");
+        assert!(region.decoder_at(region.len() + 1).is_err());
+    }
+
+    #[test]
+    fn region_buffered_test() {
+        let path = write_region("buffered");
+        let mut region = RegionFile::open_buffered(&path).unwrap();
+        assert!(!region.is_mapped());
+        check(&mut region);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn region_open_test() {
+        let path = write_region("open");
+        let mut region = RegionFile::open(&path).unwrap();
+        #[cfg(all(feature = "mmap", any(unix, windows)))]
+        assert!(region.is_mapped());
+        check(&mut region);
+        std::fs::remove_file(path).unwrap();
+    }
+}
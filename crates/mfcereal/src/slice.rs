@@ -0,0 +1,125 @@
+use crate::decode::{DecodeError, Decoder, UnexpectedEof};
+
+/// A [Decoder] over borrowed bytes that can hand out sub-slices without copying.
+///
+/// Values are always copied out byte-by-byte (never read through a casted pointer),
+/// so the underlying bytes do not need any particular alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SliceDecoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceDecoder<'a> {
+    #[inline]
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+        }
+    }
+
+    #[inline]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    #[inline]
+    pub const fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// The bytes that have not been read yet.
+    #[inline]
+    pub fn remaining_bytes(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    /// Moves the read position to `position`.
+    #[inline]
+    pub fn seek(&mut self, position: usize) -> Result<(), DecodeError<UnexpectedEof>> {
+        if position > self.bytes.len() {
+            return Err(DecodeError::DecoderError(UnexpectedEof));
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    /// Borrows the next `len` bytes without copying.
+    #[inline]
+    pub fn read_borrowed(&mut self, len: usize) -> Result<&'a [u8], DecodeError<UnexpectedEof>> {
+        let end = self.position.checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(DecodeError::DecoderError(UnexpectedEof))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Borrows a length-prefixed byte slice (as written by [crate::encode::Encoder::write_u8_slice]).
+    #[inline]
+    pub fn read_borrowed_u8_slice(&mut self) -> Result<&'a [u8], DecodeError<UnexpectedEof>> {
+        let len = self.read_usize()?;
+        self.read_borrowed(len)
+    }
+
+    /// Borrows a length-prefixed UTF-8 string (as written by [crate::encode::Encoder::write_str]).
+    #[inline]
+    pub fn read_borrowed_str(&mut self) -> Result<&'a str, DecodeError<UnexpectedEof>> {
+        let bytes = self.read_borrowed_u8_slice()?;
+        std::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidData("invalid utf-8"))
+    }
+}
+
+impl Decoder for SliceDecoder<'_> {
+    type Error = UnexpectedEof;
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError<Self::Error>> {
+        let bytes = self.read_borrowed(buf.len())?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn skip(&mut self, len: usize) -> Result<(), DecodeError<Self::Error>> {
+        self.read_borrowed(len)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+
+    #[test]
+    fn slice_decoder_test() {
+        let mut bytes = Vec::new();
+        bytes.write_u8(7).unwrap();
+        bytes.write_str("region").unwrap();
+        bytes.write_u32(0xDEADBEEF).unwrap();
+        let mut decoder = SliceDecoder::new(&bytes);
+        assert_eq!(decoder.read_u8().unwrap(), 7);
+        let name = decoder.read_borrowed_str().unwrap();
+        assert_eq!(name, "region");
+        // Borrowed from the source, not copied.
+        assert!(bytes.as_ptr_range().contains(&name.as_ptr()));
+        // Unaligned read.
+        assert_eq!(decoder.read_u32().unwrap(), 0xDEADBEEF);
+        assert_eq!(decoder.remaining(), 0);
+        assert!(decoder.read_u8().is_err());
+        decoder.seek(0).unwrap();
+        assert_eq!(decoder.read_u8().unwrap(), 7);
+    }
+}
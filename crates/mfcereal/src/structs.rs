@@ -0,0 +1,98 @@
+//! The wire format of structs, and per-field codec hooks.
+//!
+//! A struct is written as its fields in declaration order, with no header, by
+//! `#[derive(Encode, Decode)]` (see [Encode](crate::encode::Encode)). Reordering the fields changes
+//! the format. Most fields use their own [Encode](crate::encode::Encode) and
+//! [Decode](crate::decode::Decode) impls. A field marked `#[cereal(with = path)]` is written by
+//! `path::encode` and read by `path::decode` instead, for fields that need a hand-written codec
+//! (bit-packed arrays, palettes, types from other crates) without giving up the derived impls for the
+//! rest of the struct. The same goes for the fields of enum variants. The module must provide:
+//!
+//! ```ignore
+//! fn encode<E: Encoder>(value: &T, encoder: &mut E) -> Result<u64, E::Error>;
+//! fn decode<D: Decoder>(decoder: &mut D) -> Result<T, DecodeError<D::Error>>;
+//! ```
+//!
+//! ```
+//! # use mfcereal::{decode::Decode, encode::Encode};
+//! #[derive(Debug, PartialEq, Encode, Decode)]
+//! struct Sign {
+//!     facing: u8,
+//!     #[cereal(with = reversed)]
+//!     text: [u8; 3],
+//! }
+//!
+//! /// Writes the text back to front.
+//! mod reversed {
+//!     use mfcereal::{decode::{DecodeError, Decoder}, encode::Encoder};
+//!
+//!     pub fn encode<E: Encoder>(text: &[u8; 3], encoder: &mut E) -> Result<u64, E::Error> {
+//!         let [a, b, c] = *text;
+//!         encoder.write_exact(&[c, b, a])
+//!     }
+//!
+//!     pub fn decode<D: Decoder>(decoder: &mut D) -> Result<[u8; 3], DecodeError<D::Error>> {
+//!         let mut text = [0; 3];
+//!         decoder.read_exact(&mut text)?;
+//!         text.reverse();
+//!         Ok(text)
+//!     }
+//! }
+//!
+//! let sign = Sign { facing: 2, text: *b"abc" };
+//! let mut bytes = Vec::new();
+//! sign.encode(&mut bytes).unwrap();
+//! assert_eq!(bytes, [2, b'c', b'b', b'a']);
+//! assert_eq!(Sign::decode(&mut bytes.as_slice()).unwrap(), sign);
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use crate::{decode::Decode, encode::Encode};
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    struct Column {
+        height: u16,
+        #[cereal(with = self::nibbles)]
+        light: [u8; 6],
+        open: bool,
+    }
+
+    /// Packs 4-bit light levels two to a byte.
+    mod nibbles {
+        use crate::{
+            bits::{BitReader, BitWriter},
+            decode::{DecodeError, Decoder},
+            encode::Encoder,
+        };
+
+        pub fn encode<E: Encoder>(levels: &[u8; 6], encoder: &mut E) -> Result<u64, E::Error> {
+            let mut writer = BitWriter::new(encoder);
+            for &level in levels {
+                writer.write_bits(level as u64, 4)?;
+            }
+            writer.finish()
+        }
+
+        pub fn decode<D: Decoder>(decoder: &mut D) -> Result<[u8; 6], DecodeError<D::Error>> {
+            let mut reader = BitReader::new(decoder);
+            let mut levels = [0; 6];
+            for level in &mut levels {
+                *level = reader.read_bits(4)? as u8;
+            }
+            Ok(levels)
+        }
+    }
+
+    #[test]
+    fn struct_with_test() {
+        let column = Column { height: 0x0102, light: [15, 0, 1, 2, 3, 4], open: true };
+        let mut bytes = Vec::new();
+        let written = column.encode(&mut bytes).unwrap();
+        assert_eq!(written, bytes.len() as u64);
+        assert_eq!(bytes, [1, 2, 0xF0, 0x12, 0x34, 1]);
+        let mut input = bytes.as_slice();
+        assert_eq!(Column::decode(&mut input).unwrap(), column);
+        assert!(input.is_empty());
+    }
+}
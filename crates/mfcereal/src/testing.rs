@@ -0,0 +1,182 @@
+//! Helpers for testing [Encode] and [Decode] impls, for this crate and the crates that use it.
+//!
+//! - [assert_roundtrip] checks that a value decodes back to itself, that the written length is
+//!   right, and that the decoded value encodes to the same bytes.
+//! - [fuzz_decode] decodes truncated and mutated copies of some encoded bytes and checks that
+//!   decoding only ever fails with an error, never a panic.
+//! - [roundtrip_tests](crate::roundtrip_tests) generates a test doing both for each value in a list.
+//!
+//! Only available with the `testing` feature, which crates enable in their dev-dependencies.
+
+use std::{
+    fmt::Debug,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::{
+    decode::Decode,
+    encode::Encode,
+};
+
+/// The number of mutated inputs [roundtrip_tests](crate::roundtrip_tests) decodes per value.
+pub const DEFAULT_FUZZ_ITERATIONS: u32 = 256;
+
+/// The most truncated copies [fuzz_decode] tries.
+const MAX_TRUNCATIONS: usize = 256;
+
+/// Encodes `value`, checking that the length it reported is the number of bytes it wrote.
+#[track_caller]
+pub fn encoded<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let written = value.encode(&mut bytes).expect("Encoding into a Vec can't fail.");
+    assert_eq!(written, bytes.len() as u64, "{} reported the wrong encoded length", std::any::type_name::<T>());
+    bytes
+}
+
+/// Asserts that `value` decodes back to itself from all of its bytes, and that the decoded value
+/// encodes to the same bytes.
+#[track_caller]
+pub fn assert_roundtrip<T: Encode + Decode + PartialEq + Debug>(value: &T) {
+    let bytes = encoded(value);
+    let mut input = bytes.as_slice();
+    let decoded = match T::decode(&mut input) {
+        Ok(decoded) => decoded,
+        Err(error) => panic!("{} failed to decode its own encoding: {error}", std::any::type_name::<T>()),
+    };
+    assert_eq!(&decoded, value, "{} changed in a round trip", std::any::type_name::<T>());
+    assert!(input.is_empty(), "{} left {} bytes unread", std::any::type_name::<T>(), input.len());
+    assert_eq!(encoded(&decoded), bytes, "{} encoded differently after a round trip", std::any::type_name::<T>());
+}
+
+/// Decodes `bytes` as a `T`, returning `false` if decoding panicked.
+#[must_use]
+pub fn decodes_without_panic<T: Decode>(bytes: &[u8]) -> bool {
+    catch_unwind(AssertUnwindSafe(|| {
+        let _ = T::decode(&mut &bytes[..]);
+    })).is_ok()
+}
+
+/// Decodes truncated copies of `bytes` and `iterations` mutated copies (with bits flipped, bytes
+/// overwritten, inserted and removed), asserting that none of them panic.
+///
+/// The mutations are derived from `bytes`, so a failure happens again on every run.
+#[track_caller]
+pub fn fuzz_decode<T: Decode>(bytes: &[u8], iterations: u32) {
+    let check = |input: &[u8]| {
+        assert!(
+            decodes_without_panic::<T>(input),
+            "decoding {} panicked on {input:02x?}",
+            std::any::type_name::<T>(),
+        );
+    };
+    let step = bytes.len().div_ceil(MAX_TRUNCATIONS).max(1);
+    for len in (0..bytes.len()).step_by(step) {
+        check(&bytes[..len]);
+    }
+
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15 ^ bytes.len() as u64);
+    for byte in bytes.iter().take(64) {
+        rng.0 = rng.0.rotate_left(8) ^ u64::from(*byte);
+    }
+    let mut input = Vec::with_capacity(bytes.len() + 8);
+    for _ in 0..iterations {
+        input.clear();
+        input.extend_from_slice(bytes);
+        // A few mutations at a time, so that later fields see corrupt data too.
+        for _ in 0..1 + rng.below(3) {
+            let at = rng.below(input.len() + 1);
+            match rng.below(5) {
+                0 if at < input.len() => input[at] ^= 1 << rng.below(8),
+                1 if at < input.len() => input[at] = rng.next() as u8,
+                2 if at < input.len() => input[at] = if rng.below(2) == 0 { 0x00 } else { 0xFF },
+                3 => input.insert(at, rng.next() as u8),
+                _ if at < input.len() => {
+                    input.remove(at);
+                }
+                _ => input.push(rng.next() as u8),
+            }
+        }
+        check(&input);
+    }
+}
+
+/// Generates a test for each value that checks it round trips with [assert_roundtrip], and that
+/// decoding mutated copies of it doesn't panic with [fuzz_decode].
+///
+/// ```
+/// mfcereal::roundtrip_tests! {
+///     u32_roundtrip: u32 = 0xDEAD_BEEF;
+///     bytes_roundtrip: Vec<u8> = b"manufactory".to_vec();
+/// }
+/// ```
+#[macro_export]
+macro_rules! roundtrip_tests {
+    ($($name:ident: $ty:ty = $value:expr;)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let value: $ty = $value;
+                $crate::testing::assert_roundtrip(&value);
+                $crate::testing::fuzz_decode::<$ty>(&$crate::testing::encoded(&value), $crate::testing::DEFAULT_FUZZ_ITERATIONS);
+            }
+        )*
+    };
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    #[inline]
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    #[inline]
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    roundtrip_tests! {
+        vec_roundtrip: Vec<u32> = vec![1, 2, 0xFFFF_FFFF];
+        bytes_roundtrip: Vec<i8> = vec![-1, 0, 1];
+        option_roundtrip: Option<char> = Some('x');
+        result_roundtrip: Result<i64, bool> = Err(true);
+    }
+
+    #[test]
+    fn huge_length_test() {
+        // A length of 2^60 fails at the end of the input instead of allocating.
+        let bytes = [0x10, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3];
+        assert!(Vec::<u32>::decode(&mut &bytes[..]).is_err());
+        assert!(Vec::<u8>::decode(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "changed in a round trip")]
+    fn roundtrip_mismatch_test() {
+        #[derive(Debug, PartialEq)]
+        struct Lossy(u16);
+
+        impl Encode for Lossy {
+            fn encode<E: crate::encode::Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+                encoder.write_u8(self.0 as u8)
+            }
+        }
+
+        impl Decode for Lossy {
+            fn decode<D: crate::decode::Decoder>(decoder: &mut D) -> Result<Self, crate::decode::DecodeError<D::Error>> {
+                Ok(Self(decoder.read_u8()?.into()))
+            }
+        }
+
+        assert_roundtrip(&Lossy(0x1234));
+    }
+}
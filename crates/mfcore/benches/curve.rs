@@ -0,0 +1,67 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mfcore::curve;
+
+const RANGE: std::ops::Range<i32> = -8..8;
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("curve_encode");
+    group.bench_function("morton3", |b| b.iter(|| {
+        for x in RANGE {
+            for y in RANGE {
+                for z in RANGE {
+                    black_box(curve::morton3(black_box(x), black_box(y), black_box(z)));
+                }
+            }
+        }
+    }));
+    group.bench_function("hilbert3", |b| b.iter(|| {
+        for x in RANGE {
+            for y in RANGE {
+                for z in RANGE {
+                    black_box(curve::hilbert3(black_box(x), black_box(y), black_box(z)));
+                }
+            }
+        }
+    }));
+    group.bench_function("morton3_i64", |b| b.iter(|| {
+        for x in RANGE {
+            for y in RANGE {
+                for z in RANGE {
+                    black_box(curve::morton3_i64(black_box(x as i64), black_box(y as i64), black_box(z as i64)));
+                }
+            }
+        }
+    }));
+    group.bench_function("hilbert3_i64", |b| b.iter(|| {
+        for x in RANGE {
+            for y in RANGE {
+                for z in RANGE {
+                    black_box(curve::hilbert3_i64(black_box(x as i64), black_box(y as i64), black_box(z as i64)));
+                }
+            }
+        }
+    }));
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("curve_decode");
+    let start = curve::morton3(0, 0, 0);
+    group.bench_function("morton3", |b| b.iter(|| {
+        for code in start..start + 4096 {
+            black_box(curve::morton3_decode(black_box(code)));
+        }
+    }));
+    let start = curve::hilbert3(0, 0, 0);
+    group.bench_function("hilbert3", |b| b.iter(|| {
+        for code in start..start + 4096 {
+            black_box(curve::hilbert3_decode(black_box(code)));
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -0,0 +1,446 @@
+//! A bump arena for allocations that only live for a tick.
+//!
+//! Systems take their scratch memory (notification lists, pathfinding frontiers, mesh buffers)
+//! from a [TickArena] instead of the heap, and never free it themselves: the game
+//! [resets](TickArena::reset) the arena once per tick, which frees everything at once and keeps the
+//! memory for the next tick. Allocating is a pointer bump, and after the first few ticks the arena
+//! has grown to what a tick needs and stops allocating at all.
+//!
+//! A system can also allocate from a [scope](TickArena::scope), which frees what was allocated in
+//! it when it's dropped, so that systems running one after another reuse the same memory. Scopes
+//! are named, and the arena keeps the peak usage of every name in its [ArenaStats].
+//!
+//! Only [Copy] types can be allocated, since nothing in the arena is ever dropped. The borrow
+//! checker keeps allocations from outliving a reset or their scope, and in debug builds, freed
+//! memory is overwritten with [POISON] so that reading it through a stray raw pointer stands out.
+
+use std::{
+    alloc::{self, Layout},
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    ops::{Deref, Range},
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// The byte that freed memory is overwritten with in debug builds.
+pub const POISON: u8 = 0xA5;
+
+/// The size of the first chunk. Each chunk after it is twice the size of the last.
+const MIN_CHUNK_LEN: usize = 4096;
+const CHUNK_ALIGN: usize = 16;
+
+/// A block of memory that the arena bumps through.
+struct Chunk {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Chunk {
+    fn new(len: usize) -> Self {
+        let layout = Self::layout(len);
+        // SAFETY: `len` is never 0.
+        let ptr = NonNull::new(unsafe { alloc::alloc(layout) }).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, len }
+    }
+
+    #[inline]
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len.max(1), CHUNK_ALIGN).expect("Arena chunk is too large.")
+    }
+
+    /// Overwrites `range` with [POISON] in debug builds.
+    #[inline]
+    fn poison(&self, range: Range<usize>) {
+        debug_assert!(range.end <= self.len);
+        if cfg!(debug_assertions) {
+            // SAFETY: The range is within the chunk, and nothing borrows freed memory.
+            unsafe { self.ptr.add(range.start).write_bytes(POISON, range.len()) }
+        }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // SAFETY: The chunk was allocated with the same layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.len)) }
+    }
+}
+
+/// A position in the arena to rewind to.
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    chunk: usize,
+    offset: usize,
+    used: usize,
+}
+
+/// Memory usage of a [TickArena]. Sizes are in bytes, and include alignment padding.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Bytes allocated and not freed yet.
+    pub used: usize,
+    /// The most bytes in use at once since the last reset.
+    pub tick_peak: usize,
+    /// The `tick_peak` of the tick before the last reset.
+    pub last_tick_peak: usize,
+    /// The most bytes in use at once, over every tick.
+    pub peak: usize,
+    /// The size of every chunk the arena holds.
+    pub capacity: usize,
+    /// Allocations since the last reset.
+    pub allocations: u64,
+    /// How many times the arena was reset.
+    pub ticks: u64,
+    /// The most bytes each named scope had in use at once, over every tick.
+    pub systems: BTreeMap<&'static str, usize>,
+}
+
+/// A bump arena that is reset every tick. See the [module docs](self).
+pub struct TickArena {
+    chunks: RefCell<Vec<Chunk>>,
+    /// The chunk being bumped through, and how far into it.
+    chunk: Cell<usize>,
+    offset: Cell<usize>,
+    used: Cell<usize>,
+    tick_peak: Cell<usize>,
+    /// The most bytes in use since the innermost scope started.
+    high_water: Cell<usize>,
+    allocations: Cell<u64>,
+    last_tick_peak: usize,
+    peak: usize,
+    ticks: u64,
+    systems: BTreeMap<&'static str, usize>,
+}
+
+impl Default for TickArena {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for TickArena {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TickArena").field("stats", &self.stats()).finish_non_exhaustive()
+    }
+}
+
+// Allocating hands out `&mut` borrows from `&self`. Each one is to memory no other borrow covers,
+// and freeing takes `&mut self`, so none of them can outlive what they point to.
+#[allow(clippy::mut_from_ref)]
+impl TickArena {
+    /// An arena with no memory yet. The first allocation allocates a chunk.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new()),
+            chunk: Cell::new(0),
+            offset: Cell::new(0),
+            used: Cell::new(0),
+            tick_peak: Cell::new(0),
+            high_water: Cell::new(0),
+            allocations: Cell::new(0),
+            last_tick_peak: 0,
+            peak: 0,
+            ticks: 0,
+            systems: BTreeMap::new(),
+        }
+    }
+
+    /// An arena with a chunk of `capacity` bytes.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let arena = Self::new();
+        if capacity > 0 {
+            arena.chunks.borrow_mut().push(Chunk::new(capacity));
+        }
+        arena
+    }
+
+    /// Bytes allocated and not freed yet.
+    #[inline]
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.len).sum()
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            used: self.used.get(),
+            tick_peak: self.tick_peak.get(),
+            last_tick_peak: self.last_tick_peak,
+            peak: self.peak.max(self.tick_peak.get()),
+            capacity: self.capacity(),
+            allocations: self.allocations.get(),
+            ticks: self.ticks,
+            systems: self.systems.clone(),
+        }
+    }
+
+    /// Allocates memory for `layout`, uninitialized.
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            return NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap();
+        }
+        let mut chunks = self.chunks.borrow_mut();
+        loop {
+            let index = self.chunk.get();
+            if let Some(chunk) = chunks.get(index) {
+                let offset = self.offset.get();
+                // SAFETY: `offset` is at most the length of the chunk.
+                let start = unsafe { chunk.ptr.add(offset) };
+                let padding = start.align_offset(layout.align());
+                let end = offset.checked_add(padding).and_then(|start| start.checked_add(layout.size()));
+                if let Some(end) = end.filter(|&end| end <= chunk.len) {
+                    self.offset.set(end);
+                    let used = self.used.get() + padding + layout.size();
+                    self.used.set(used);
+                    self.tick_peak.set(self.tick_peak.get().max(used));
+                    self.high_water.set(self.high_water.get().max(used));
+                    self.allocations.set(self.allocations.get() + 1);
+                    // SAFETY: `start + padding + size` fits in the chunk.
+                    return unsafe { start.add(padding) };
+                }
+                // The rest of this chunk is too small. Later chunks are bigger.
+                if index + 1 < chunks.len() {
+                    self.chunk.set(index + 1);
+                    self.offset.set(0);
+                    continue;
+                }
+            }
+            let len = chunks.last().map_or(MIN_CHUNK_LEN, |chunk| chunk.len * 2).max(layout.size() + layout.align());
+            chunks.push(Chunk::new(len));
+            self.chunk.set(chunks.len() - 1);
+            self.offset.set(0);
+        }
+    }
+
+    #[inline]
+    pub fn alloc<T: Copy>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        // SAFETY: The memory is fresh, and aligned and sized for `T`.
+        unsafe {
+            ptr.write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::for_value(values)).cast::<T>();
+        // SAFETY: The memory is fresh, and aligned and sized for `values`.
+        unsafe {
+            ptr.as_ptr().copy_from_nonoverlapping(values.as_ptr(), values.len());
+            slice::from_raw_parts_mut(ptr.as_ptr(), values.len())
+        }
+    }
+
+    /// Allocates `len` values, each given by `value` from its index.
+    pub fn alloc_slice_fill_with<T: Copy, F: FnMut(usize) -> T>(&self, len: usize, mut value: F) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::array::<T>(len).expect("Arena slice is too large.")).cast::<T>();
+        // SAFETY: The memory is fresh, and aligned and sized for `len` values, every one of which is
+        // written before the slice is made. If `value` panics, the memory is left unused.
+        unsafe {
+            for index in 0..len {
+                ptr.add(index).write(value(index));
+            }
+            slice::from_raw_parts_mut(ptr.as_ptr(), len)
+        }
+    }
+
+    #[inline]
+    pub fn alloc_slice_fill<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        self.alloc_slice_fill_with(len, |_| value)
+    }
+
+    #[inline]
+    fn mark(&self) -> Mark {
+        Mark { chunk: self.chunk.get(), offset: self.offset.get(), used: self.used.get() }
+    }
+
+    /// Frees everything allocated since `mark`.
+    fn rewind(&mut self, mark: Mark) {
+        let (end_chunk, end_offset) = (self.chunk.get(), self.offset.get());
+        for (index, chunk) in self.chunks.get_mut().iter().enumerate().take(end_chunk + 1).skip(mark.chunk) {
+            let start = if index == mark.chunk { mark.offset } else { 0 };
+            let end = if index == end_chunk { end_offset } else { chunk.len };
+            chunk.poison(start..end);
+        }
+        self.chunk.set(mark.chunk);
+        self.offset.set(mark.offset);
+        self.used.set(mark.used);
+    }
+
+    /// Frees every allocation, at the end of a tick. If the tick needed more than one chunk, they're
+    /// replaced with a single chunk as large as all of them, so that the next tick fits in it.
+    pub fn reset(&mut self) {
+        self.rewind(Mark { chunk: 0, offset: 0, used: 0 });
+        let tick_peak = self.tick_peak.replace(0);
+        self.peak = self.peak.max(tick_peak);
+        self.last_tick_peak = tick_peak;
+        self.high_water.set(0);
+        self.allocations.set(0);
+        self.ticks += 1;
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let len = chunks.iter().map(|chunk| chunk.len).sum();
+            chunks.clear();
+            chunks.push(Chunk::new(len));
+        }
+    }
+
+    /// A scope for the system called `name`. Allocations from the scope are freed when it's dropped.
+    pub fn scope(&mut self, name: &'static str) -> ArenaScope<'_> {
+        let start = self.mark();
+        let outer_high_water = self.high_water.replace(start.used);
+        ArenaScope { arena: self, name, start, outer_high_water }
+    }
+}
+
+/// Allocations of one system, freed when the scope is dropped. Allocates through [Deref] to the
+/// [TickArena]. See [TickArena::scope].
+pub struct ArenaScope<'a> {
+    arena: &'a mut TickArena,
+    name: &'static str,
+    start: Mark,
+    outer_high_water: usize,
+}
+
+impl ArenaScope<'_> {
+    #[inline]
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Bytes allocated in this scope (and the scopes in it) and not freed yet.
+    #[inline]
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.arena.used() - self.start.used
+    }
+
+    /// A scope within this one.
+    #[inline]
+    pub fn scope(&mut self, name: &'static str) -> ArenaScope<'_> {
+        self.arena.scope(name)
+    }
+}
+
+impl Deref for ArenaScope<'_> {
+    type Target = TickArena;
+
+    #[inline]
+    fn deref(&self) -> &TickArena {
+        self.arena
+    }
+}
+
+impl Drop for ArenaScope<'_> {
+    fn drop(&mut self) {
+        let arena = &mut *self.arena;
+        let high_water = arena.high_water.get();
+        let peak = arena.systems.entry(self.name).or_default();
+        *peak = (*peak).max(high_water - self.start.used);
+        arena.high_water.set(self.outer_high_water.max(high_water));
+        arena.rewind(self.start);
+    }
+}
+
+impl std::fmt::Debug for ArenaScope<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArenaScope").field("name", &self.name).field("used", &self.used()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_arena_test() {
+        let mut arena = TickArena::new();
+        let byte = arena.alloc(1u8);
+        let wide = arena.alloc(2u64);
+        *byte += 1;
+        assert_eq!((*byte, *wide), (2, 2));
+        assert_eq!(wide as *mut u64 as usize % align_of::<u64>(), 0);
+        let frontier = arena.alloc_slice_fill_with(100, |index| (index as i32, -(index as i32)));
+        assert_eq!(frontier[99], (99, -99));
+        assert_eq!(arena.alloc_slice_copy(&[1u16, 2, 3]), &[1, 2, 3]);
+        assert!(arena.alloc_slice_fill::<()>(3, ()).len() == 3);
+        // Bigger than the first chunk.
+        let big = arena.alloc_slice_fill(MIN_CHUNK_LEN, 7u8);
+        assert!(big.iter().all(|&value| value == 7));
+        let stats = arena.stats();
+        // Zero-sized values take no memory, and aren't counted.
+        assert_eq!(stats.allocations, 5);
+        assert!(stats.used >= 1 + 8 + 800 + 6 + MIN_CHUNK_LEN && stats.capacity >= stats.used);
+        assert_eq!(arena.chunks.borrow().len(), 2);
+
+        arena.reset();
+        let stats = arena.stats();
+        assert_eq!((stats.used, stats.allocations, stats.ticks, stats.tick_peak), (0, 0, 1, 0));
+        assert!(stats.last_tick_peak >= MIN_CHUNK_LEN && stats.peak == stats.last_tick_peak);
+        // The next tick fits in one chunk.
+        assert_eq!(arena.chunks.borrow().len(), 1);
+        assert_eq!(arena.capacity(), stats.capacity);
+    }
+
+    #[test]
+    fn arena_scope_test() {
+        let mut arena = TickArena::with_capacity(1024);
+        {
+            let mut pathfinding = arena.scope("pathfinding");
+            pathfinding.alloc_slice_fill(64, 0u32);
+            {
+                let mesh = pathfinding.scope("mesh");
+                mesh.alloc_slice_fill(128, 0u8);
+                assert_eq!(mesh.used(), 128);
+            }
+            assert_eq!(pathfinding.used(), 256);
+            pathfinding.alloc(0u64);
+        }
+        assert_eq!(arena.used(), 0);
+        let stats = arena.stats();
+        assert_eq!(stats.systems["pathfinding"], 256 + 128);
+        assert_eq!(stats.systems["mesh"], 128);
+        assert_eq!(stats.tick_peak, 384);
+
+        // The next system reuses the memory, and its peak is its own.
+        let notify = arena.scope("notify");
+        let ids = notify.alloc_slice_copy(&[1u32, 2, 3]);
+        assert_eq!(ids, &[1, 2, 3]);
+        drop(notify);
+        assert_eq!(arena.stats().systems["notify"], 12);
+        assert_eq!(arena.stats().tick_peak, 384);
+    }
+
+    #[test]
+    fn arena_poison_test() {
+        let mut arena = TickArena::with_capacity(64);
+        let base = arena.chunks.borrow()[0].ptr;
+        arena.alloc(0x1234_5678u32);
+        {
+            let scope = arena.scope("scratch");
+            scope.alloc_slice_fill(8, 0u8);
+        }
+        // SAFETY: The chunk is still allocated, and nothing borrows it.
+        let bytes = unsafe { slice::from_raw_parts(base.as_ptr(), 12) };
+        assert_eq!(&bytes[..4], &0x1234_5678u32.to_ne_bytes());
+        if cfg!(debug_assertions) {
+            assert!(bytes[4..].iter().all(|&byte| byte == POISON));
+        }
+        arena.reset();
+        let bytes = unsafe { slice::from_raw_parts(base.as_ptr(), 4) };
+        if cfg!(debug_assertions) {
+            assert!(bytes.iter().all(|&byte| byte == POISON));
+        }
+    }
+}
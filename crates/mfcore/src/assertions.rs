@@ -0,0 +1,48 @@
+//! Compile-time assertions about types.
+//!
+//! Simulation state must stay [Send] (and usually [Sync]) so that the world can move between the
+//! server and client threads. Asserting it next to the type turns an accidental `Rc` or `Cell`
+//! into a compile error where it's introduced, instead of wherever the type is next sent.
+
+/// Fails to compile unless every listed type is [Send].
+///
+/// ```compile_fail
+/// mfcore::assert_send!(std::rc::Rc<u32>);
+/// ```
+#[macro_export]
+macro_rules! assert_send {
+    ($($ty:ty),+ $(,)?) => {
+        const _: () = {
+            const fn assert_send<T: ?Sized + ::core::marker::Send>() {}
+            $( assert_send::<$ty>(); )+
+        };
+    };
+}
+
+/// Fails to compile unless every listed type is [Sync].
+///
+/// ```compile_fail
+/// mfcore::assert_sync!(std::cell::Cell<u32>);
+/// ```
+#[macro_export]
+macro_rules! assert_sync {
+    ($($ty:ty),+ $(,)?) => {
+        const _: () = {
+            const fn assert_sync<T: ?Sized + ::core::marker::Sync>() {}
+            $( assert_sync::<$ty>(); )+
+        };
+    };
+}
+
+/// Fails to compile unless every listed type is both [Send] and [Sync].
+///
+/// ```
+/// mfcore::assert_send_sync!(u32, std::sync::Arc<str>, Vec<u8>);
+/// ```
+#[macro_export]
+macro_rules! assert_send_sync {
+    ($($ty:ty),+ $(,)?) => {
+        $crate::assert_send!($($ty),+);
+        $crate::assert_sync!($($ty),+);
+    };
+}
@@ -0,0 +1,240 @@
+//! An intrusive doubly-linked list over a slab.
+//!
+//! The list doesn't own its nodes. Each element of a slab (any slice) embeds a [Link], and the
+//! [IntrusiveList] threads through the elements by index, so linking, unlinking, and moving an
+//! element are O(1) and never allocate. Links can only be changed by the list, and every operation
+//! checks that the element is (or isn't) already linked, so a list can't be corrupted through this
+//! API; the worst a mismatched slab can do is panic.
+
+const NIL: u32 = u32::MAX;
+
+/// The links of an element of an [IntrusiveList].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Link {
+    prev: u32,
+    next: u32,
+    linked: bool,
+}
+
+impl Link {
+    /// A link that isn't in any list.
+    pub const UNLINKED: Self = Self { prev: NIL, next: NIL, linked: false };
+
+    #[inline]
+    #[must_use]
+    pub const fn is_linked(&self) -> bool {
+        self.linked
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn prev(&self) -> Option<u32> {
+        index(self.prev)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn next(&self) -> Option<u32> {
+        index(self.next)
+    }
+}
+
+impl Default for Link {
+    #[inline]
+    fn default() -> Self {
+        Self::UNLINKED
+    }
+}
+
+#[inline]
+const fn index(raw: u32) -> Option<u32> {
+    if raw == NIL { None } else { Some(raw) }
+}
+
+/// An element that embeds a [Link].
+pub trait Linked {
+    fn link(&self) -> &Link;
+    fn link_mut(&mut self) -> &mut Link;
+}
+
+impl Linked for Link {
+    #[inline]
+    fn link(&self) -> &Link {
+        self
+    }
+
+    #[inline]
+    fn link_mut(&mut self) -> &mut Link {
+        self
+    }
+}
+
+/// The ends of an intrusive list whose elements live in a slab owned by someone else.
+///
+/// Every method takes the slab. It must be the same slab (or one with the same links) every time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntrusiveList {
+    head: u32,
+    tail: u32,
+    len: u32,
+}
+
+impl Default for IntrusiveList {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntrusiveList {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { head: NIL, tail: NIL, len: 0 }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn front(&self) -> Option<u32> {
+        index(self.head)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn back(&self) -> Option<u32> {
+        index(self.tail)
+    }
+
+    /// Links the element at `at` as the new front. Panics if it's already linked.
+    pub fn push_front<T: Linked>(&mut self, slab: &mut [T], at: u32) {
+        let old_head = self.head;
+        *Self::unlinked(slab, at) = Link { prev: NIL, next: old_head, linked: true };
+        match index(old_head) {
+            Some(old_head) => slab[old_head as usize].link_mut().prev = at,
+            None => self.tail = at,
+        }
+        self.head = at;
+        self.len += 1;
+    }
+
+    /// Links the element at `at` as the new back. Panics if it's already linked.
+    pub fn push_back<T: Linked>(&mut self, slab: &mut [T], at: u32) {
+        let old_tail = self.tail;
+        *Self::unlinked(slab, at) = Link { prev: old_tail, next: NIL, linked: true };
+        match index(old_tail) {
+            Some(old_tail) => slab[old_tail as usize].link_mut().next = at,
+            None => self.head = at,
+        }
+        self.tail = at;
+        self.len += 1;
+    }
+
+    /// Unlinks the element at `at`. Panics if it isn't linked.
+    pub fn remove<T: Linked>(&mut self, slab: &mut [T], at: u32) {
+        let link = *slab[at as usize].link();
+        assert!(link.linked, "Element {at} is not linked.");
+        match index(link.prev) {
+            Some(prev) => slab[prev as usize].link_mut().next = link.next,
+            None => self.head = link.next,
+        }
+        match index(link.next) {
+            Some(next) => slab[next as usize].link_mut().prev = link.prev,
+            None => self.tail = link.prev,
+        }
+        *slab[at as usize].link_mut() = Link::UNLINKED;
+        self.len -= 1;
+    }
+
+    /// Moves the linked element at `at` to the front.
+    pub fn move_to_front<T: Linked>(&mut self, slab: &mut [T], at: u32) {
+        if self.head != at {
+            self.remove(slab, at);
+            self.push_front(slab, at);
+        }
+    }
+
+    pub fn pop_front<T: Linked>(&mut self, slab: &mut [T]) -> Option<u32> {
+        let front = self.front()?;
+        self.remove(slab, front);
+        Some(front)
+    }
+
+    pub fn pop_back<T: Linked>(&mut self, slab: &mut [T]) -> Option<u32> {
+        let back = self.back()?;
+        self.remove(slab, back);
+        Some(back)
+    }
+
+    /// The indices of the elements, front to back.
+    pub fn iter<'a, T: Linked>(&self, slab: &'a [T]) -> impl Iterator<Item = u32> + 'a {
+        let mut at = self.head;
+        std::iter::from_fn(move || {
+            let current = index(at)?;
+            at = slab[current as usize].link().next;
+            Some(current)
+        })
+    }
+
+    fn unlinked<T: Linked>(slab: &mut [T], at: u32) -> &mut Link {
+        assert!(at != NIL, "Index {at} is reserved.");
+        let link = slab[at as usize].link_mut();
+        assert!(!link.linked, "Element {at} is already linked.");
+        link
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Node {
+        link: Link,
+        value: char,
+    }
+
+    impl Linked for Node {
+        fn link(&self) -> &Link {
+            &self.link
+        }
+
+        fn link_mut(&mut self) -> &mut Link {
+            &mut self.link
+        }
+    }
+
+    #[test]
+    fn intrusive_list_test() {
+        let mut slab: Vec<Node> = "abcd".chars().map(|value| Node { link: Link::UNLINKED, value }).collect();
+        let mut list = IntrusiveList::new();
+        let values = |list: &IntrusiveList, slab: &[Node]| list.iter(slab).map(|at| slab[at as usize].value).collect::<String>();
+        list.push_back(&mut slab, 1);
+        list.push_back(&mut slab, 2);
+        list.push_front(&mut slab, 0);
+        assert_eq!(values(&list, &slab), "abc");
+        list.move_to_front(&mut slab, 2);
+        assert_eq!(values(&list, &slab), "cab");
+        list.remove(&mut slab, 0);
+        assert!(!slab[0].link.is_linked());
+        assert_eq!(values(&list, &slab), "cb");
+        assert_eq!(list.pop_back(&mut slab), Some(1));
+        assert_eq!(list.pop_front(&mut slab), Some(2));
+        assert!(list.is_empty() && list.front().is_none() && list.back().is_none());
+
+        list.push_back(&mut slab, 3);
+        let linked_twice = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| list.push_back(&mut slab, 3)));
+        assert!(linked_twice.is_err());
+    }
+}
@@ -0,0 +1,318 @@
+//! Least-recently-used ordering with O(1) touch and evict.
+//!
+//! [LruIndex] orders the slots of a fixed-size pool. [Lru] is a bounded map that evicts its least
+//! recently used entry when it's full. Both keep their entries in a slab threaded by an
+//! [IntrusiveList], so nothing is allocated per touch or eviction.
+
+use std::{collections::HashMap, hash::Hash};
+
+use super::list::{IntrusiveList, Link, Linked};
+
+/// The recency order of the slots `0..capacity` of a pool. Only tracked slots take part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LruIndex {
+    links: Vec<Link>,
+    /// Most recently used at the front.
+    order: IntrusiveList,
+}
+
+impl LruIndex {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity < u32::MAX as usize, "Capacity is too large.");
+        Self {
+            links: vec![Link::UNLINKED; capacity],
+            order: IntrusiveList::new(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.links.len()
+    }
+
+    /// The number of tracked slots.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, slot: usize) -> bool {
+        self.links.get(slot).is_some_and(Link::is_linked)
+    }
+
+    /// Marks `slot` as the most recently used, tracking it if it wasn't tracked.
+    pub fn touch(&mut self, slot: usize) {
+        let at = slot as u32;
+        if self.links[slot].is_linked() {
+            self.order.move_to_front(&mut self.links, at);
+        } else {
+            self.order.push_front(&mut self.links, at);
+        }
+    }
+
+    /// Stops tracking `slot`. Returns whether it was tracked.
+    pub fn remove(&mut self, slot: usize) -> bool {
+        let tracked = self.contains(slot);
+        if tracked {
+            self.order.remove(&mut self.links, slot as u32);
+        }
+        tracked
+    }
+
+    /// The least recently used slot.
+    #[inline]
+    #[must_use]
+    pub fn peek_lru(&self) -> Option<usize> {
+        self.order.back().map(|at| at as usize)
+    }
+
+    /// Stops tracking the least recently used slot and returns it.
+    pub fn evict(&mut self) -> Option<usize> {
+        self.order.pop_back(&mut self.links).map(|at| at as usize)
+    }
+
+    /// The tracked slots, most recently used first.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.order.iter(&self.links).map(|at| at as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<K, V> {
+    link: Link,
+    /// `None` while the slot is on the free list.
+    item: Option<(K, V)>,
+}
+
+impl<K, V> Entry<K, V> {
+    #[inline]
+    fn item(&self) -> (&K, &V) {
+        let (key, value) = self.item.as_ref().expect("Linked entries are occupied.");
+        (key, value)
+    }
+
+    #[inline]
+    fn value_mut(&mut self) -> &mut V {
+        &mut self.item.as_mut().expect("Linked entries are occupied.").1
+    }
+}
+
+impl<K, V> Linked for Entry<K, V> {
+    #[inline]
+    fn link(&self) -> &Link {
+        &self.link
+    }
+
+    #[inline]
+    fn link_mut(&mut self) -> &mut Link {
+        &mut self.link
+    }
+}
+
+/// A map holding at most `capacity` entries that evicts the least recently used entry to make room.
+#[derive(Debug, Clone)]
+pub struct Lru<K, V> {
+    capacity: usize,
+    entries: Vec<Entry<K, V>>,
+    /// Unoccupied slots in `entries`.
+    free: Vec<u32>,
+    slots: HashMap<K, u32>,
+    /// Most recently used at the front.
+    order: IntrusiveList,
+}
+
+impl<K: Eq + Hash + Clone, V> Lru<K, V> {
+    /// Panics if `capacity` is `0`.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity != 0, "Capacity must not be zero.");
+        assert!(capacity < u32::MAX as usize, "Capacity is too large.");
+        Self {
+            capacity,
+            entries: Vec::new(),
+            free: Vec::new(),
+            slots: HashMap::new(),
+            order: IntrusiveList::new(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, key: &K) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    /// Gets a value without changing its recency.
+    #[inline]
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.slots.get(key).map(|&at| self.entries[at as usize].item().1)
+    }
+
+    /// Gets a value and marks it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let at = self.touch(key)?;
+        Some(self.entries[at as usize].item().1)
+    }
+
+    /// Gets a value mutably and marks it as the most recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let at = self.touch(key)?;
+        Some(self.entries[at as usize].value_mut())
+    }
+
+    /// Inserts or replaces a value and marks it as the most recently used.
+    ///
+    /// Returns the displaced entry: the old value if `key` was present, otherwise the least recently
+    /// used entry if the map was full.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(at) = self.touch(&key) {
+            let old = std::mem::replace(self.entries[at as usize].value_mut(), value);
+            return Some((key, old));
+        }
+        let evicted = if self.len() == self.capacity { self.pop_lru() } else { None };
+        let at = match self.free.pop() {
+            Some(at) => at,
+            None => {
+                self.entries.push(Entry { link: Link::UNLINKED, item: None });
+                (self.entries.len() - 1) as u32
+            }
+        };
+        self.entries[at as usize].item = Some((key.clone(), value));
+        self.order.push_front(&mut self.entries, at);
+        self.slots.insert(key, at);
+        evicted
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let at = self.slots.remove(key)?;
+        Some(self.free_slot(at).1)
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let at = self.order.back()?;
+        let (key, value) = self.free_slot(at);
+        self.slots.remove(&key);
+        Some((key, value))
+    }
+
+    /// The least recently used entry.
+    #[inline]
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        Some(self.entries[self.order.back()? as usize].item())
+    }
+
+    /// The entries, most recently used first.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.order.iter(&self.entries).map(|at| self.entries[at as usize].item())
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.free.clear();
+        self.slots.clear();
+        self.order = IntrusiveList::new();
+    }
+
+    fn touch(&mut self, key: &K) -> Option<u32> {
+        let at = *self.slots.get(key)?;
+        self.order.move_to_front(&mut self.entries, at);
+        Some(at)
+    }
+
+    fn free_slot(&mut self, at: u32) -> (K, V) {
+        self.order.remove(&mut self.entries, at);
+        self.free.push(at);
+        self.entries[at as usize].item.take().expect("Linked entries are occupied.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_index_test() {
+        let mut index = LruIndex::new(4);
+        for slot in 0..4 {
+            index.touch(slot);
+        }
+        index.touch(1);
+        assert_eq!(index.iter().collect::<Vec<_>>(), [1, 3, 2, 0]);
+        assert_eq!(index.evict(), Some(0));
+        assert!(index.remove(2));
+        assert!(!index.remove(2));
+        assert!(!index.contains(0) && index.contains(3));
+        assert_eq!(index.peek_lru(), Some(3));
+        assert_eq!(index.evict(), Some(3));
+        assert_eq!(index.evict(), Some(1));
+        assert_eq!(index.evict(), None);
+    }
+
+    #[test]
+    fn lru_test() {
+        let mut lru = Lru::new(3);
+        assert_eq!(lru.insert("a", 1), None);
+        assert_eq!(lru.insert("b", 2), None);
+        assert_eq!(lru.insert("c", 3), None);
+        assert_eq!(lru.get(&"a"), Some(&1));
+        assert_eq!(lru.insert("d", 4), Some(("b", 2)));
+        assert_eq!(lru.peek(&"c"), Some(&3));
+        assert_eq!(lru.insert("e", 5), Some(("c", 3)));
+        assert_eq!(lru.insert("a", 10), Some(("a", 1)));
+        assert_eq!(lru.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>(), [("a", 10), ("e", 5), ("d", 4)]);
+        *lru.get_mut(&"d").unwrap() += 1;
+        assert_eq!(lru.remove(&"e"), Some(5));
+        assert_eq!(lru.remove(&"e"), None);
+        assert_eq!(lru.peek_lru(), Some((&"a", &10)));
+        // The freed slot is reused rather than growing the slab.
+        lru.insert("f", 6);
+        assert_eq!(lru.entries.len(), 3);
+        assert_eq!(lru.pop_lru(), Some(("a", 10)));
+        assert_eq!(lru.pop_lru(), Some(("d", 5)));
+        assert_eq!(lru.pop_lru(), Some(("f", 6)));
+        assert!(lru.is_empty() && lru.pop_lru().is_none());
+    }
+
+    #[test]
+    fn lru_drops_values_test() {
+        use std::rc::Rc;
+        let value = Rc::new(());
+        let mut lru = Lru::new(2);
+        lru.insert(0, Rc::clone(&value));
+        lru.insert(1, Rc::clone(&value));
+        lru.insert(2, Rc::clone(&value));
+        assert_eq!(Rc::strong_count(&value), 3);
+        lru.remove(&1);
+        assert_eq!(Rc::strong_count(&value), 2);
+        lru.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,10 @@
+//! Allocation-free building blocks for caches and pools.
+//!
+//! These are written in safe code: links are slab indices, not pointers. Their tests use small sizes
+//! so they also run under Miri (`cargo +nightly miri test -p mfcore collections`).
+
+pub mod list;
+pub mod lru;
+
+pub use list::{IntrusiveList, Link, Linked};
+pub use lru::{Lru, LruIndex};
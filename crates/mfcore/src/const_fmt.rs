@@ -0,0 +1,153 @@
+//! Tiny formatting for const evaluation.
+//!
+//! `panic!` in a const context can't format values, but it can print a `&str`. [ConstStr]
+//! builds that string in a fixed buffer so that const table validation can report which
+//! index or value was wrong:
+//! ```
+//! use mfcore::const_fmt::ConstStr;
+//! const fn check(table: &[u8]) {
+//!     let mut i = 0;
+//!     while i < table.len() {
+//!         if table[i] > 5 {
+//!             ConstStr::<64>::new().str("table[").usize(i).str("] = ").u64(table[i] as u64).str(" is out of range").panic();
+//!         }
+//!         i += 1;
+//!     }
+//! }
+//! const _: () = check(&[1, 2, 3]);
+//! ```
+
+/// A string built in a fixed size buffer. Anything that doesn't fit is cut off.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstStr<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ConstStr<N> {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { buf: [0; N], len: 0 }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub const fn str(mut self, s: &str) -> Self {
+        let bytes = s.as_bytes();
+        let mut count = bytes.len();
+        if count > N - self.len {
+            count = N - self.len;
+            // Don't cut a character in half.
+            while count > 0 && (bytes[count] & 0xC0) == 0x80 {
+                count -= 1;
+            }
+        }
+        let mut i = 0;
+        while i < count {
+            self.buf[self.len + i] = bytes[i];
+            i += 1;
+        }
+        self.len += count;
+        self
+    }
+
+    #[must_use]
+    pub const fn u64(mut self, value: u64) -> Self {
+        let mut digits = [0u8; 20];
+        let mut count = 0;
+        let mut value = value;
+        loop {
+            digits[count] = b'0' + (value % 10) as u8;
+            count += 1;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        while count > 0 && self.len < N {
+            count -= 1;
+            self.buf[self.len] = digits[count];
+            self.len += 1;
+        }
+        self
+    }
+
+    #[must_use]
+    pub const fn i64(self, value: i64) -> Self {
+        if value < 0 {
+            self.str("-").u64(value.unsigned_abs())
+        } else {
+            self.u64(value as u64)
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn usize(self, value: usize) -> Self {
+        self.u64(value as u64)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn bool(self, value: bool) -> Self {
+        self.str(if value { "true" } else { "false" })
+    }
+
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        let (bytes, _) = self.buf.split_at(self.len);
+        match ::core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(_) => panic!("ConstStr only ever holds whole characters."),
+        }
+    }
+
+    /// Panics with the built string.
+    #[track_caller]
+    pub const fn panic(&self) -> ! {
+        panic!("{}", self.as_str())
+    }
+}
+
+impl<const N: usize> Default for ConstStr<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ::core::fmt::Display for ConstStr<N> {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn const_str_test() {
+        const MESSAGE: ConstStr<64> = ConstStr::new().str("index ").usize(143).str(": ").i64(-42).str(" ").bool(true);
+        assert_eq!(MESSAGE.as_str(), "index 143: -42 true");
+        assert_eq!(ConstStr::<8>::new().u64(u64::MAX).as_str(), "18446744");
+        assert_eq!(ConstStr::<4>::new().i64(i64::MIN).as_str(), "-922");
+        // "é" is two bytes, so only one fits.
+        assert_eq!(ConstStr::<4>::new().str("aéé").as_str(), "aé");
+        let result = std::panic::catch_unwind(|| ConstStr::<16>::new().str("bad ").u64(7).panic());
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<String>().map(String::as_str), Some("bad 7"));
+    }
+}
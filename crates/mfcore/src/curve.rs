@@ -0,0 +1,397 @@
+//! Space-filling curve indices for 2D and 3D integer coordinates.
+//!
+//! A Z-order (Morton) index interleaves the bits of the coordinates. It's cheap to compute and to
+//! decode, and keeps nearby cells mostly close together, which makes it the default sort key for
+//! chunks. A Hilbert index takes more work, but consecutive indices are always neighbouring cells,
+//! so it gives the best locality for cache-friendly iteration and on-disk layout.
+//!
+//! Signed coordinates are biased (their sign bit flipped) before encoding, so the whole range is one
+//! curve and negative coordinates come before positive ones. In every index, `x` takes the lowest
+//! bit of each group. 3D `i64` indices are 192 bits wide and are returned as three `u64` words,
+//! most significant first, so that arrays compare in curve order.
+//!
+//! Everything here is `const`.
+
+#[inline(always)]
+const fn bias32(value: i32) -> u32 {
+    value.cast_unsigned() ^ (1 << 31)
+}
+
+#[inline(always)]
+const fn unbias32(value: u32) -> i32 {
+    (value ^ (1 << 31)).cast_signed()
+}
+
+#[inline(always)]
+const fn bias64(value: i64) -> u64 {
+    value.cast_unsigned() ^ (1 << 63)
+}
+
+#[inline(always)]
+const fn unbias64(value: u64) -> i64 {
+    (value ^ (1 << 63)).cast_signed()
+}
+
+/// Moves bit `i` of `value` to bit `2 * i`.
+#[inline]
+const fn spread2_32(value: u32) -> u64 {
+    let mut x = value as u64;
+    x = (x | (x << 16)) & 0xFFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+#[inline]
+const fn compact2_32(value: u64) -> u32 {
+    let mut x = value & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0xFFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0xFFFF_FFFF) as u32
+}
+
+/// Moves bit `i` of `value` to bit `2 * i`.
+#[inline]
+const fn spread2_64(value: u64) -> u128 {
+    let mut x = value as u128;
+    x = (x | (x << 32)) & 0xFFFF_FFFF_0000_0000_FFFF_FFFF;
+    x = (x | (x << 16)) & 0xFFFF_0000_FFFF_0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF_00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333_3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555_5555_5555_5555_5555
+}
+
+#[inline]
+const fn compact2_64(value: u128) -> u64 {
+    let mut x = value & 0x5555_5555_5555_5555_5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333_3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF_00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0xFFFF_0000_FFFF_0000_FFFF_0000_FFFF;
+    x = (x | (x >> 16)) & 0xFFFF_FFFF_0000_0000_FFFF_FFFF;
+    ((x | (x >> 32)) & 0xFFFF_FFFF_FFFF_FFFF) as u64
+}
+
+/// Moves bit `i` of `value` to bit `3 * i`.
+#[inline]
+const fn spread3_32(value: u32) -> u128 {
+    let mut x = value as u128;
+    x = (x | (x << 32)) & 0xFFFF_0000_0000_FFFF;
+    x = (x | (x << 16)) & 0xFF00_00FF_0000_FF00_00FF;
+    x = (x | (x << 8)) & 0x00F0_0F00_F00F_00F0_0F00_F00F;
+    x = (x | (x << 4)) & 0x0C30_C30C_30C3_0C30_C30C_30C3;
+    (x | (x << 2)) & 0x2492_4924_9249_2492_4924_9249
+}
+
+#[inline]
+const fn compact3_32(value: u128) -> u32 {
+    let mut x = value & 0x2492_4924_9249_2492_4924_9249;
+    x = (x | (x >> 2)) & 0x0C30_C30C_30C3_0C30_C30C_30C3;
+    x = (x | (x >> 4)) & 0x00F0_0F00_F00F_00F0_0F00_F00F;
+    x = (x | (x >> 8)) & 0xFF00_00FF_0000_FF00_00FF;
+    x = (x | (x >> 16)) & 0xFFFF_0000_0000_FFFF;
+    ((x | (x >> 32)) & 0xFFFF_FFFF) as u32
+}
+
+#[inline]
+const fn interleave3_32(x: u32, y: u32, z: u32) -> u128 {
+    spread3_32(x) | (spread3_32(y) << 1) | (spread3_32(z) << 2)
+}
+
+#[inline]
+const fn deinterleave3_32(code: u128) -> (u32, u32, u32) {
+    (compact3_32(code), compact3_32(code >> 1), compact3_32(code >> 2))
+}
+
+/// Interleaves three 64 bit values into a 192 bit index, as words most significant first.
+#[inline]
+const fn interleave3_64(x: u64, y: u64, z: u64) -> [u64; 3] {
+    // Bit `i` goes to bit `3 * i`, so the high halves land exactly 96 bits above the low halves.
+    let low = interleave3_32(x as u32, y as u32, z as u32);
+    let high = interleave3_32((x >> 32) as u32, (y >> 32) as u32, (z >> 32) as u32);
+    [
+        (high >> 32) as u64,
+        (((high & 0xFFFF_FFFF) << 32) | (low >> 64)) as u64,
+        low as u64,
+    ]
+}
+
+#[inline]
+const fn deinterleave3_64(code: [u64; 3]) -> (u64, u64, u64) {
+    let high = ((code[0] as u128) << 32) | ((code[1] >> 32) as u128);
+    let low = (((code[1] & 0xFFFF_FFFF) as u128) << 64) | code[2] as u128;
+    let (lx, ly, lz) = deinterleave3_32(low);
+    let (hx, hy, hz) = deinterleave3_32(high);
+    (
+        ((hx as u64) << 32) | lx as u64,
+        ((hy as u64) << 32) | ly as u64,
+        ((hz as u64) << 32) | lz as u64,
+    )
+}
+
+/// The Morton index of a 2D coordinate.
+#[inline]
+#[must_use]
+pub const fn morton2(x: i32, y: i32) -> u64 {
+    spread2_32(bias32(x)) | (spread2_32(bias32(y)) << 1)
+}
+
+#[inline]
+#[must_use]
+pub const fn morton2_decode(code: u64) -> (i32, i32) {
+    (unbias32(compact2_32(code)), unbias32(compact2_32(code >> 1)))
+}
+
+/// The Morton index of a 3D coordinate. Only the low 96 bits are used.
+#[inline]
+#[must_use]
+pub const fn morton3(x: i32, y: i32, z: i32) -> u128 {
+    interleave3_32(bias32(x), bias32(y), bias32(z))
+}
+
+#[inline]
+#[must_use]
+pub const fn morton3_decode(code: u128) -> (i32, i32, i32) {
+    let (x, y, z) = deinterleave3_32(code);
+    (unbias32(x), unbias32(y), unbias32(z))
+}
+
+/// The Morton index of a 2D `i64` coordinate.
+#[inline]
+#[must_use]
+pub const fn morton2_i64(x: i64, y: i64) -> u128 {
+    spread2_64(bias64(x)) | (spread2_64(bias64(y)) << 1)
+}
+
+#[inline]
+#[must_use]
+pub const fn morton2_i64_decode(code: u128) -> (i64, i64) {
+    (unbias64(compact2_64(code)), unbias64(compact2_64(code >> 1)))
+}
+
+/// The 192 bit Morton index of a 3D `i64` coordinate, most significant word first.
+#[inline]
+#[must_use]
+pub const fn morton3_i64(x: i64, y: i64, z: i64) -> [u64; 3] {
+    interleave3_64(bias64(x), bias64(y), bias64(z))
+}
+
+#[inline]
+#[must_use]
+pub const fn morton3_i64_decode(code: [u64; 3]) -> (i64, i64, i64) {
+    let (x, y, z) = deinterleave3_64(code);
+    (unbias64(x), unbias64(y), unbias64(z))
+}
+
+// The Hilbert functions use John Skilling's transform ("Programming the Hilbert curve", 2004),
+// which turns coordinates into the "transposed" index: `axes[0]` holds the most significant bit of
+// each group of the index. Interleaving the transposed axes gives the index itself.
+
+/// Turns coordinates of `bits` bits into the transposed Hilbert index.
+const fn axes_to_transpose<const N: usize>(mut axes: [u64; N], bits: u32) -> [u64; N] {
+    let mut level = bits - 1;
+    while level > 0 {
+        let q = 1u64 << level;
+        let p = q - 1;
+        let mut i = 0;
+        while i < N {
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+            i += 1;
+        }
+        level -= 1;
+    }
+    // Gray encode.
+    let mut i = 1;
+    while i < N {
+        axes[i] ^= axes[i - 1];
+        i += 1;
+    }
+    let mut t = 0;
+    let mut level = bits - 1;
+    while level > 0 {
+        let q = 1u64 << level;
+        if axes[N - 1] & q != 0 {
+            t ^= q - 1;
+        }
+        level -= 1;
+    }
+    let mut i = 0;
+    while i < N {
+        axes[i] ^= t;
+        i += 1;
+    }
+    axes
+}
+
+/// The inverse of [axes_to_transpose].
+const fn transpose_to_axes<const N: usize>(mut axes: [u64; N], bits: u32) -> [u64; N] {
+    // Gray decode.
+    let t = axes[N - 1] >> 1;
+    let mut i = N - 1;
+    while i > 0 {
+        axes[i] ^= axes[i - 1];
+        i -= 1;
+    }
+    axes[0] ^= t;
+    let mut level = 1;
+    while level < bits {
+        let q = 1u64 << level;
+        let p = q - 1;
+        let mut i = N;
+        while i > 0 {
+            i -= 1;
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+        }
+        level += 1;
+    }
+    axes
+}
+
+/// The Hilbert index of a 2D coordinate.
+#[inline]
+#[must_use]
+pub const fn hilbert2(x: i32, y: i32) -> u64 {
+    let [high, low] = axes_to_transpose([bias32(x) as u64, bias32(y) as u64], 32);
+    spread2_32(low as u32) | (spread2_32(high as u32) << 1)
+}
+
+#[inline]
+#[must_use]
+pub const fn hilbert2_decode(code: u64) -> (i32, i32) {
+    let transposed = [compact2_32(code >> 1) as u64, compact2_32(code) as u64];
+    let [x, y] = transpose_to_axes(transposed, 32);
+    (unbias32(x as u32), unbias32(y as u32))
+}
+
+/// The Hilbert index of a 3D coordinate. Only the low 96 bits are used.
+#[inline]
+#[must_use]
+pub const fn hilbert3(x: i32, y: i32, z: i32) -> u128 {
+    let [high, mid, low] = axes_to_transpose([bias32(x) as u64, bias32(y) as u64, bias32(z) as u64], 32);
+    interleave3_32(low as u32, mid as u32, high as u32)
+}
+
+#[inline]
+#[must_use]
+pub const fn hilbert3_decode(code: u128) -> (i32, i32, i32) {
+    let (low, mid, high) = deinterleave3_32(code);
+    let [x, y, z] = transpose_to_axes([high as u64, mid as u64, low as u64], 32);
+    (unbias32(x as u32), unbias32(y as u32), unbias32(z as u32))
+}
+
+/// The Hilbert index of a 2D `i64` coordinate.
+#[inline]
+#[must_use]
+pub const fn hilbert2_i64(x: i64, y: i64) -> u128 {
+    let [high, low] = axes_to_transpose([bias64(x), bias64(y)], 64);
+    spread2_64(low) | (spread2_64(high) << 1)
+}
+
+#[inline]
+#[must_use]
+pub const fn hilbert2_i64_decode(code: u128) -> (i64, i64) {
+    let [x, y] = transpose_to_axes([compact2_64(code >> 1), compact2_64(code)], 64);
+    (unbias64(x), unbias64(y))
+}
+
+/// The 192 bit Hilbert index of a 3D `i64` coordinate, most significant word first.
+#[inline]
+#[must_use]
+pub const fn hilbert3_i64(x: i64, y: i64, z: i64) -> [u64; 3] {
+    let [high, mid, low] = axes_to_transpose([bias64(x), bias64(y), bias64(z)], 64);
+    interleave3_64(low, mid, high)
+}
+
+#[inline]
+#[must_use]
+pub const fn hilbert3_i64_decode(code: [u64; 3]) -> (i64, i64, i64) {
+    let (low, mid, high) = deinterleave3_64(code);
+    let [x, y, z] = transpose_to_axes([high, mid, low], 64);
+    (unbias64(x), unbias64(y), unbias64(z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [i32; 7] = [0, 1, -1, 12345, -98765, i32::MAX, i32::MIN];
+
+    #[test]
+    fn morton_test() {
+        const ORIGIN: u64 = morton2(0, 0);
+        assert_eq!(ORIGIN, 0xC000_0000_0000_0000);
+        assert!(morton2(-1, -1) < ORIGIN);
+        assert_eq!(morton3(1, 0, 0) - morton3(0, 0, 0), 1);
+        assert_eq!(morton3(0, 0, 1) - morton3(0, 0, 0), 4);
+        for &x in &SAMPLES {
+            for &y in &SAMPLES {
+                assert_eq!(morton2_decode(morton2(x, y)), (x, y));
+                assert_eq!(morton2_i64_decode(morton2_i64(x as i64 * 3, y as i64)), (x as i64 * 3, y as i64));
+                for &z in &SAMPLES {
+                    assert_eq!(morton3_decode(morton3(x, y, z)), (x, y, z));
+                    let wide = (x as i64 * 5, y as i64 - 7, z as i64 * 1_000_003);
+                    assert_eq!(morton3_i64_decode(morton3_i64(wide.0, wide.1, wide.2)), wide);
+                }
+            }
+        }
+        // The wide index keeps the narrow order.
+        let narrow = [morton3(-2, 5, 1), morton3(3, -1, 0)];
+        let wide = [morton3_i64(-2, 5, 1), morton3_i64(3, -1, 0)];
+        assert_eq!(narrow[0] < narrow[1], wide[0] < wide[1]);
+    }
+
+    #[test]
+    fn hilbert_test() {
+        for &x in &SAMPLES {
+            for &y in &SAMPLES {
+                assert_eq!(hilbert2_decode(hilbert2(x, y)), (x, y));
+                assert_eq!(hilbert2_i64_decode(hilbert2_i64(x as i64, y as i64 * 9)), (x as i64, y as i64 * 9));
+                for &z in &SAMPLES {
+                    assert_eq!(hilbert3_decode(hilbert3(x, y, z)), (x, y, z));
+                    let wide = (x as i64 * 5, y as i64 - 7, z as i64 * 1_000_003);
+                    assert_eq!(hilbert3_i64_decode(hilbert3_i64(wide.0, wide.1, wide.2)), wide);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_adjacency_test() {
+        fn distance(a: (i64, i64, i64), b: (i64, i64, i64)) -> u64 {
+            a.0.abs_diff(b.0) + a.1.abs_diff(b.1) + a.2.abs_diff(b.2)
+        }
+        // Consecutive indices are always neighbours, including across the sign boundary.
+        let start = hilbert3(0, 0, 0) - 500;
+        for code in start..start + 1000 {
+            let (a, b) = (hilbert3_decode(code), hilbert3_decode(code + 1));
+            assert_eq!(distance((a.0 as i64, a.1 as i64, a.2 as i64), (b.0 as i64, b.1 as i64, b.2 as i64)), 1);
+        }
+        let start = hilbert2(-1, 0) - 500;
+        for code in start..start + 1000 {
+            let (a, b) = (hilbert2_decode(code), hilbert2_decode(code + 1));
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+        }
+        let [high, mid, low] = hilbert3_i64(0, 0, 0);
+        for step in 0..500u64 {
+            let code = [high, mid, low + step];
+            let next = [high, mid, low + step + 1];
+            assert_eq!(distance(hilbert3_i64_decode(code), hilbert3_i64_decode(next)), 1);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+//! Fixed-point numbers for deterministic simulation.
+//!
+//! Floating-point results can differ between platforms and compiler settings (fused multiply-add,
+//! x87 precision, library implementations of `sin`), which breaks lockstep simulation and world
+//! hashes. [Fix64] only uses integer arithmetic, so every operation, including [Fix64::sqrt] and the
+//! trigonometric approximations, gives bit-identical results everywhere.
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
+
+/// A signed Q32.32 fixed-point number: 32 integer bits and 32 fractional bits.
+///
+/// Addition and subtraction behave like `i64`. Multiplication and division panic on overflow in
+/// every build, so that overflow can't go unnoticed in release builds.
+#[repr(transparent)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fix64(i64);
+
+impl Fix64 {
+    pub const FRAC_BITS: u32 = 32;
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << Self::FRAC_BITS);
+    pub const HALF: Self = Self(1 << (Self::FRAC_BITS - 1));
+    pub const MIN: Self = Self(i64::MIN);
+    pub const MAX: Self = Self(i64::MAX);
+    /// The smallest positive value.
+    pub const EPSILON: Self = Self(1);
+    pub const PI: Self = Self(13493037705);
+    pub const TAU: Self = Self(26986075409);
+    pub const FRAC_PI_2: Self = Self(6746518852);
+    pub const E: Self = Self(11674931555);
+
+    #[must_use]
+    #[inline]
+    pub const fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn from_int(value: i32) -> Self {
+        Self((value as i64) << Self::FRAC_BITS)
+    }
+
+    /// `numerator / denominator`, rounded toward zero.
+    #[must_use]
+    #[inline]
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        Self(((numerator as i64) << Self::FRAC_BITS) / denominator as i64)
+    }
+
+    /// Converts from `f64`, rounding to the nearest representable value. Out of range values
+    /// saturate and NaN becomes zero.
+    #[must_use]
+    #[inline]
+    pub fn from_f64(value: f64) -> Self {
+        Self((value * (1u64 << Self::FRAC_BITS) as f64).round() as i64)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(value as f64)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / (1u64 << Self::FRAC_BITS) as f64
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    /// The integer part, rounded toward negative infinity.
+    #[must_use]
+    #[inline]
+    pub const fn to_int(self) -> i32 {
+        (self.0 >> Self::FRAC_BITS) as i32
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn floor(self) -> Self {
+        Self(self.0 & !(Self::ONE.0 - 1))
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn ceil(self) -> Self {
+        Self(self.0 + (Self::ONE.0 - 1)).floor()
+    }
+
+    /// Rounds to the nearest integer, with halves rounded up.
+    #[must_use]
+    #[inline]
+    pub const fn round(self) -> Self {
+        Self(self.0 + Self::HALF.0).floor()
+    }
+
+    /// The fractional part, always in `0..1`.
+    #[must_use]
+    #[inline]
+    pub const fn fract(self) -> Self {
+        Self(self.0 & (Self::ONE.0 - 1))
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn signum(self) -> Self {
+        Self::from_int(self.0.signum() as i32)
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(raw) => Some(Self(raw)),
+            None => None,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_sub(rhs.0) {
+            Some(raw) => Some(Self(raw)),
+            None => None,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let product = (self.0 as i128 * rhs.0 as i128) >> Self::FRAC_BITS;
+        if product < i64::MIN as i128 || product > i64::MAX as i128 {
+            None
+        } else {
+            Some(Self(product as i64))
+        }
+    }
+
+    /// Divides, rounding toward zero. Returns `None` on overflow or division by zero.
+    #[must_use]
+    #[inline]
+    pub const fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        let quotient = ((self.0 as i128) << Self::FRAC_BITS) / rhs.0 as i128;
+        if quotient < i64::MIN as i128 || quotient > i64::MAX as i128 {
+            None
+        } else {
+            Some(Self(quotient as i64))
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn saturating_mul(self, rhs: Self) -> Self {
+        match self.checked_mul(rhs) {
+            Some(product) => product,
+            None if (self.0 < 0) == (rhs.0 < 0) => Self::MAX,
+            None => Self::MIN,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn clamp(self, min: Self, max: Self) -> Self {
+        if self.0 < min.0 {
+            min
+        } else if self.0 > max.0 {
+            max
+        } else {
+            self
+        }
+    }
+
+    /// Interpolates from `self` at `t = 0` to `other` at `t = 1`.
+    #[must_use]
+    #[inline]
+    pub fn lerp(self, other: Self, t: Self) -> Self {
+        self + (other - self) * t
+    }
+
+    /// The square root, rounded down.
+    ///
+    /// # Panics
+    /// Panics if `self` is negative.
+    #[must_use]
+    #[inline]
+    pub const fn sqrt(self) -> Self {
+        assert!(self.0 >= 0, "Square root of a negative Fix64.");
+        Self((((self.0 as u128) << Self::FRAC_BITS).isqrt()) as i64)
+    }
+
+    /// Reduces an angle to `-PI..=PI`.
+    #[inline]
+    const fn wrap_angle(self) -> Self {
+        let angle = self.0.rem_euclid(Self::TAU.0);
+        if angle > Self::PI.0 {
+            Self(angle - Self::TAU.0)
+        } else {
+            Self(angle)
+        }
+    }
+
+    /// The sine of an angle in `-PI..=PI`.
+    fn sin_wrapped(self) -> Self {
+        // Fold into -PI/2..=PI/2, where the series converges quickly.
+        let x = if self > Self::FRAC_PI_2 {
+            Self::PI - self
+        } else if self < -Self::FRAC_PI_2 {
+            -Self::PI - self
+        } else {
+            self
+        };
+        let x2 = x * x;
+        // x - x^3/3! + x^5/5! - ... up to x^15, evaluated from the innermost term outward.
+        let mut sum = Self::ONE;
+        for divisor in [210, 156, 110, 72, 42, 20, 6] {
+            sum = Self::ONE - Self(x2.0 / divisor) * sum;
+        }
+        x * sum
+    }
+
+    /// The sine of an angle in radians. Accurate to about `1e-9`.
+    #[must_use]
+    pub fn sin(self) -> Self {
+        self.wrap_angle().sin_wrapped()
+    }
+
+    /// The cosine of an angle in radians. Accurate to about `1e-9`.
+    #[must_use]
+    pub fn cos(self) -> Self {
+        (self.wrap_angle() + Self::FRAC_PI_2).wrap_angle().sin_wrapped()
+    }
+
+    /// The tangent of an angle in radians.
+    ///
+    /// # Panics
+    /// Panics if the result overflows, which can happen close to odd multiples of `PI/2`.
+    #[must_use]
+    pub fn tan(self) -> Self {
+        let angle = self.wrap_angle();
+        angle.sin_wrapped() / (angle + Self::FRAC_PI_2).wrap_angle().sin_wrapped()
+    }
+
+    /// The arctangent of a value in `0..=1`.
+    fn atan_unit(self) -> Self {
+        // atan(x) = 2 * atan(x / (1 + sqrt(1 + x^2))). Applied twice, this leaves x below
+        // tan(PI/16), where the series converges quickly.
+        let mut x = self;
+        for _ in 0..2 {
+            x = x / (Self::ONE + (Self::ONE + x * x).sqrt());
+        }
+        let x2 = x * x;
+        let mut term = x;
+        let mut sum = Self::ZERO;
+        for k in 0..8 {
+            let value = Self(term.0 / (2 * k + 1));
+            sum = if k % 2 == 0 { sum + value } else { sum - value };
+            term *= x2;
+        }
+        Self(sum.0 * 4)
+    }
+
+    /// The arctangent in radians, in `-PI/2..=PI/2`.
+    #[must_use]
+    pub fn atan(self) -> Self {
+        let magnitude = self.abs();
+        let angle = if magnitude > Self::ONE {
+            Self::FRAC_PI_2 - (Self::ONE / magnitude).atan_unit()
+        } else {
+            magnitude.atan_unit()
+        };
+        if self.is_negative() { -angle } else { angle }
+    }
+
+    /// The angle of the point `(x, y)` in radians, in `-PI..=PI`. `atan2(0, 0)` is zero.
+    #[must_use]
+    pub fn atan2(self, x: Self) -> Self {
+        let y = self;
+        if x.0 == 0 {
+            return match y.0.signum() {
+                1 => Self::FRAC_PI_2,
+                -1 => -Self::FRAC_PI_2,
+                _ => Self::ZERO,
+            };
+        }
+        // Divide the smaller by the larger magnitude so the quotient can't overflow.
+        let angle = if y.abs() <= x.abs() {
+            (y / x).atan()
+        } else {
+            let angle = Self::FRAC_PI_2 - (x / y).abs().atan();
+            if y.is_negative() == x.is_negative() { angle } else { -angle }
+        };
+        match (x.is_negative(), y.is_negative()) {
+            (true, false) => angle + Self::PI,
+            (true, true) => angle - Self::PI,
+            _ => angle,
+        }
+    }
+}
+
+impl From<i32> for Fix64 {
+    #[inline]
+    fn from(value: i32) -> Self {
+        Self::from_int(value)
+    }
+}
+
+impl ::core::ops::Neg for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl ::core::ops::Add for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl ::core::ops::Sub for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl ::core::ops::Mul for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.checked_mul(rhs).expect("Fix64 multiplication overflowed.")
+    }
+}
+
+impl ::core::ops::Div for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Self) -> Self::Output {
+        assert!(rhs.0 != 0, "Fix64 division by zero.");
+        self.checked_div(rhs).expect("Fix64 division overflowed.")
+    }
+}
+
+impl ::core::ops::Rem for Fix64 {
+    type Output = Self;
+
+    #[inline]
+    fn rem(self, rhs: Self) -> Self::Output {
+        Self(self.0 % rhs.0)
+    }
+}
+
+macro_rules! assign_ops {
+    ($($trait:ident::$func:ident => $op:tt),*$(,)?) => {
+        $(
+            impl ::core::ops::$trait for Fix64 {
+                #[inline]
+                fn $func(&mut self, rhs: Self) {
+                    *self = *self $op rhs;
+                }
+            }
+        )*
+    };
+}
+
+assign_ops!(
+    AddAssign::add_assign => +,
+    SubAssign::sub_assign => -,
+    MulAssign::mul_assign => *,
+    DivAssign::div_assign => /,
+    RemAssign::rem_assign => %,
+);
+
+impl ::core::iter::Sum for Fix64 {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |sum, value| sum + value)
+    }
+}
+
+impl ::core::fmt::Debug for Fix64 {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        write!(f, "Fix64({})", self.to_f64())
+    }
+}
+
+impl ::core::fmt::Display for Fix64 {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        ::core::fmt::Display::fmt(&self.to_f64(), f)
+    }
+}
+
+impl DeterministicHash for Fix64 {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_i64(self.0);
+    }
+}
+
+// Layout: the raw value (i64).
+impl Encode for Fix64 {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        encoder.write_i64(self.0)
+    }
+}
+
+impl Decode for Fix64 {
+    #[inline]
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self(decoder.read_i64()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(value: Fix64, expected: f64) -> bool {
+        (value.to_f64() - expected).abs() < 1e-8
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let a = Fix64::from_ratio(3, 2);
+        let b = Fix64::from_int(-2);
+        assert_eq!(a + b, Fix64::from_ratio(-1, 2));
+        assert_eq!(a * b, Fix64::from_int(-3));
+        assert_eq!(b / a, Fix64::from_f64(-4.0 / 3.0));
+        assert_eq!(Fix64::from_f64(-1.25).floor(), Fix64::from_int(-2));
+        assert_eq!(Fix64::from_f64(-1.25).ceil(), Fix64::from_int(-1));
+        assert_eq!(Fix64::from_f64(-1.25).fract(), Fix64::from_ratio(3, 4));
+        assert_eq!(Fix64::from_f64(2.5).round().to_int(), 3);
+        assert_eq!(Fix64::MAX.checked_mul(Fix64::from_int(2)), None);
+        assert_eq!(Fix64::ONE.checked_div(Fix64::ZERO), None);
+        assert_eq!(Fix64::from_int(9).sqrt(), Fix64::from_int(3));
+        assert!(close(Fix64::from_int(2).sqrt(), 2f64.sqrt()));
+    }
+
+    #[test]
+    fn trig_test() {
+        for i in -100..=100 {
+            let angle = i as f64 * 0.173;
+            let fixed = Fix64::from_f64(angle);
+            assert!(close(fixed.sin(), angle.sin()), "sin({angle})");
+            assert!(close(fixed.cos(), angle.cos()), "cos({angle})");
+            let value = i as f64 * 0.31;
+            assert!(close(Fix64::from_f64(value).atan(), value.atan()), "atan({value})");
+            for (y, x) in [(value, 1.5), (value, -1.5), (1.5, value), (-1.5, value)] {
+                let fixed = Fix64::from_f64(y).atan2(Fix64::from_f64(x));
+                assert!(close(fixed, y.atan2(x)), "atan2({y}, {x})");
+            }
+        }
+        assert!(close(Fix64::from_f64(0.5).tan(), 0.5f64.tan()));
+    }
+
+    #[test]
+    fn encode_test() {
+        let value = Fix64::from_f64(-1234.5678);
+        let mut bytes = Vec::new();
+        assert_eq!(value.encode(&mut bytes).unwrap(), 8);
+        assert_eq!(Fix64::decode(&mut bytes.as_slice()).unwrap(), value);
+        assert_eq!(
+            mfhash::deterministic_hash_u64(value),
+            mfhash::deterministic_hash_u64(value.raw()),
+        );
+    }
+}
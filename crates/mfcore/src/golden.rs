@@ -0,0 +1,178 @@
+//! Golden records: the values a test is expected to end with, committed next to it.
+//!
+//! A [Golden] record is an ordered list of `key = value` lines. [check] compares a run's record
+//! with the committed file, failing with every line that differs. When the difference is expected
+//! (the code was changed on purpose), running the tests with [BLESS_VAR] set rewrites the
+//! files with the new values instead, to be reviewed and committed with the change:
+//!
+//! ```text
+//! MANUFACTORY_BLESS=1 cargo test --workspace --test golden
+//! ```
+//!
+//! The game's scenarios are checked this way by `tests/golden.rs`, and the terrain generator's
+//! outputs by `crates/mfprocgen/tests/golden.rs`.
+
+use std::{fmt, fs, io, path::Path};
+
+/// The environment variable that turns on bless mode.
+pub const BLESS_VAR: &str = "MANUFACTORY_BLESS";
+
+const HEADER: &str = "# Golden values. Regenerate with MANUFACTORY_BLESS=1 cargo test --workspace --test golden";
+
+/// Whether [BLESS_VAR] is set to anything but `0`.
+#[must_use]
+pub fn bless_requested() -> bool {
+    std::env::var_os(BLESS_VAR).is_some_and(|value| value != "0")
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Golden {
+    entries: Vec<(String, String)>,
+}
+
+impl Golden {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an entry. Keys are expected to be unique.
+    pub fn push(&mut self, key: &str, value: String) {
+        debug_assert!(self.get(key).is_none(), "Duplicate golden key: {key}");
+        self.entries.push((key.to_owned(), value));
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(entry, _)| entry == key).map(|(_, value)| value.as_str())
+    }
+
+    /// The entries, in the order they were pushed.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Parses a golden file. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut golden = Self::new();
+        for (line, text) in (1..).zip(source.lines()) {
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (key, value) = text.split_once(" = ").ok_or_else(|| format!("Line {line}: expected `key = value`"))?;
+            golden.entries.push((key.trim().to_owned(), value.trim().to_owned()));
+        }
+        Ok(golden)
+    }
+
+    /// Every difference between `self` (the expected record) and `actual`, in `actual`'s order and
+    /// then the keys only `self` has.
+    #[must_use]
+    pub fn diff(&self, actual: &Golden) -> Vec<Mismatch> {
+        let mut mismatches = actual.iter()
+            .filter(|&(key, value)| self.get(key) != Some(value))
+            .map(|(key, value)| Mismatch {
+                key: key.to_owned(),
+                expected: self.get(key).map(str::to_owned),
+                actual: Some(value.to_owned()),
+            })
+            .collect::<Vec<_>>();
+        mismatches.extend(self.iter().filter(|&(key, _)| actual.get(key).is_none()).map(|(key, value)| Mismatch {
+            key: key.to_owned(),
+            expected: Some(value.to_owned()),
+            actual: None,
+        }));
+        mismatches
+    }
+}
+
+impl fmt::Display for Golden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for (key, value) in self.iter() {
+            writeln!(f, "{key} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A key whose value isn't the expected one. `None` is a missing key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub key: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "<missing>".to_owned());
+        write!(f, "{}: expected {}, got {}", self.key, show(&self.expected), show(&self.actual))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoldenError {
+    #[error("Failed to access {path}: {error}")]
+    Io { path: String, error: io::Error },
+    #[error("{path} has no golden values yet.")]
+    Missing { path: String },
+    #[error("{path} is malformed: {message}")]
+    Malformed { path: String, message: String },
+    #[error("{path} doesn't match:\n{}", .mismatches.iter().map(|mismatch| format!("  {mismatch}")).collect::<Vec<_>>().join("\n"))]
+    Mismatch { path: String, mismatches: Vec<Mismatch> },
+}
+
+/// Compares `actual` with the golden file at `path`, or with `bless`, writes `actual` to it.
+pub fn check(path: &Path, actual: &Golden, bless: bool) -> Result<(), GoldenError> {
+    let display = path.display().to_string();
+    let io = |error| GoldenError::Io { path: display.clone(), error };
+    if bless {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io)?;
+        }
+        return fs::write(path, actual.to_string()).map_err(io);
+    }
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(GoldenError::Missing { path: display }),
+        Err(error) => return Err(io(error)),
+    };
+    let expected = Golden::parse(&source).map_err(|message| GoldenError::Malformed { path: display.clone(), message })?;
+    let mismatches = expected.diff(actual);
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(GoldenError::Mismatch { path: display, mismatches })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_diff_test() {
+        let mut expected = Golden::new();
+        expected.push("ticks", "10".to_owned());
+        expected.push("query loaded", "27".to_owned());
+        expected.push("query drone 0", "(1, 2, 3) energy 4 idle".to_owned());
+        assert_eq!(Golden::parse(&expected.to_string()).unwrap(), expected);
+
+        let mut actual = Golden::new();
+        actual.push("ticks", "10".to_owned());
+        actual.push("query loaded", "26".to_owned());
+        actual.push("query edits", "1".to_owned());
+        let mismatches = expected.diff(&actual).iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(mismatches, [
+            "query loaded: expected 27, got 26",
+            "query edits: expected <missing>, got 1",
+            "query drone 0: expected (1, 2, 3) energy 4 idle, got <missing>",
+        ]);
+        assert!(expected.diff(&expected).is_empty());
+        assert!(Golden::parse("ticks 10").is_err());
+    }
+}
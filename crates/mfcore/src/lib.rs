@@ -1,4 +1,11 @@
+pub mod arena;
+pub mod assertions;
+pub mod collections;
+pub mod const_fmt;
+pub mod curve;
 pub mod extensions;
+pub mod fixed;
+pub mod golden;
 pub mod interface;
 pub mod lowlevel;
 pub mod object;
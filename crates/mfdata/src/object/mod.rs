@@ -0,0 +1,157 @@
+use crate::typing::{int_type::IntType, primitive_type::PrimitiveType, struct_type::StructType, ObjectType, Type};
+
+/// A dynamically typed value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    String(String),
+}
+
+impl Value {
+    #[must_use]
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            Value::Bool(_) => PrimitiveType::Bool.into(),
+            Value::Int(_) => PrimitiveType::Int(IntType::I64).into(),
+            Value::String(_) => ObjectType::String,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn as_bool(&self) -> Option<bool> {
+        match self {
+            &Value::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn as_int(&self) -> Option<i64> {
+        match self {
+            &Value::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Int(value) => write!(f, "{value}"),
+            Value::String(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+/// A named field of a [Record].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Field {
+    pub name: &'static str,
+    pub value: Value,
+}
+
+impl Field {
+    #[inline]
+    #[must_use]
+    pub fn new<V: Into<Value>>(name: &'static str, value: V) -> Self {
+        Self { name, value: value.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FieldError {
+    #[error("Unknown field: `{0}`")]
+    UnknownField(String),
+    #[error("Invalid value for `{name}`: {value}")]
+    InvalidValue {
+        name: &'static str,
+        value: Value,
+    },
+}
+
+/// A struct whose fields can be read and written by name.
+pub trait Record {
+    /// Every field, in a fixed order.
+    fn fields(&self) -> Vec<Field>;
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), FieldError>;
+
+    fn get_field(&self, name: &str) -> Option<Value> {
+        self.fields().into_iter().find(|field| field.name == name).map(|field| field.value)
+    }
+
+    fn schema(&self) -> StructType {
+        StructType {
+            fields: self.fields().iter().map(|field| Type::from(field.value.object_type())).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Settings {
+        enabled: bool,
+        size: i64,
+    }
+
+    impl Record for Settings {
+        fn fields(&self) -> Vec<Field> {
+            vec![Field::new("enabled", self.enabled), Field::new("size", self.size)]
+        }
+
+        fn set_field(&mut self, name: &str, value: Value) -> Result<(), FieldError> {
+            match (name, &value) {
+                ("enabled", &Value::Bool(enabled)) => self.enabled = enabled,
+                ("size", &Value::Int(size)) => self.size = size,
+                ("enabled", _) => return Err(FieldError::InvalidValue { name: "enabled", value }),
+                ("size", _) => return Err(FieldError::InvalidValue { name: "size", value }),
+                _ => return Err(FieldError::UnknownField(name.to_owned())),
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_test() {
+        let mut settings = Settings::default();
+        settings.set_field("size", Value::Int(3)).unwrap();
+        assert_eq!(settings.get_field("size"), Some(Value::Int(3)));
+        assert!(settings.set_field("size", Value::Bool(true)).is_err());
+        assert!(settings.set_field("missing", Value::Bool(true)).is_err());
+        assert_eq!(settings.schema().fields.len(), 2);
+    }
+}
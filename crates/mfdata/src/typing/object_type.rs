@@ -96,4 +96,11 @@ impl Type {
             inner_type: Box::new(ObjectType::Array(ArrayType::new(self.clone(), len))),
         }
     }
-}
\ No newline at end of file
+}
+impl From<ObjectType> for Type {
+    fn from(value: ObjectType) -> Self {
+        Self {
+            inner_type: Box::new(value),
+        }
+    }
+}
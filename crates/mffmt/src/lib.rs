@@ -1 +1,2 @@
-pub mod hex;
\ No newline at end of file
+pub mod hex;
+pub mod progress;
@@ -0,0 +1,286 @@
+//! Progress reporting for long operations (pregeneration, full saves, verifying a save).
+//!
+//! A [Progress] tracks a stack of nested phases, each with a count of work done out of a total,
+//! and hands [ProgressUpdate]s to a [ProgressSink]. Updates from [Progress::advance] are throttled
+//! to one per interval, so reporting every item is cheap; starting and finishing a phase is always
+//! reported. [ConsoleSink] prints updates as lines, and any `FnMut(&ProgressUpdate)` is a sink, so
+//! the game can show the same progress in its UI.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io::Write,
+    time::{Duration, Instant},
+};
+
+/// A snapshot of the innermost phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressUpdate<'a> {
+    /// The names of the phases, outermost first, joined with ` > `.
+    pub path: &'a str,
+    /// How many phases are open, including this one.
+    pub depth: usize,
+    pub done: u64,
+    /// `0` if the total isn't known.
+    pub total: u64,
+    pub elapsed: Duration,
+    /// The estimated time left, once anything is done.
+    pub eta: Option<Duration>,
+    /// Whether the phase just ended.
+    pub finished: bool,
+}
+
+impl ProgressUpdate<'_> {
+    /// The fraction done, if the total is known.
+    #[inline]
+    #[must_use]
+    pub fn fraction(&self) -> Option<f64> {
+        (self.total != 0).then(|| self.done.min(self.total) as f64 / self.total as f64)
+    }
+}
+
+/// `[path] done/total (percent) eta`, or `[path] done in elapsed` when finished.
+impl Display for ProgressUpdate<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.path)?;
+        if self.finished {
+            return write!(f, "{} done in {}", self.done, HumanDuration(self.elapsed));
+        }
+        match self.fraction() {
+            Some(fraction) => write!(f, "{}/{} ({:.1}%)", self.done, self.total, fraction * 100.0)?,
+            None => write!(f, "{}", self.done)?,
+        }
+        if let Some(eta) = self.eta {
+            write!(f, " eta {}", HumanDuration(eta))?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives progress updates.
+pub trait ProgressSink {
+    fn report(&mut self, update: &ProgressUpdate<'_>);
+}
+
+impl<F: FnMut(&ProgressUpdate<'_>)> ProgressSink for F {
+    #[inline]
+    fn report(&mut self, update: &ProgressUpdate<'_>) {
+        self(update)
+    }
+}
+
+/// Writes each update on its own line, indented by its depth.
+#[derive(Debug)]
+pub struct ConsoleSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> ConsoleSink<W> {
+    #[inline]
+    #[must_use]
+    pub const fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl ConsoleSink<std::io::Stderr> {
+    /// Progress goes to stderr so that it doesn't mix with a tool's output.
+    #[inline]
+    #[must_use]
+    pub fn stderr() -> Self {
+        Self::new(std::io::stderr())
+    }
+}
+
+impl<W: Write> ProgressSink for ConsoleSink<W> {
+    fn report(&mut self, update: &ProgressUpdate<'_>) {
+        let indent = update.depth.saturating_sub(1) * 2;
+        // Progress is best effort; a closed pipe shouldn't stop the work.
+        let _ = writeln!(self.out, "{:indent$}{update}", "");
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Phase {
+    /// The length of the path before this phase's name was added.
+    path_len: usize,
+    done: u64,
+    total: u64,
+    started: Instant,
+}
+
+/// Tracks nested phases of a long operation.
+#[derive(Debug)]
+pub struct Progress<S: ProgressSink> {
+    sink: S,
+    interval: Duration,
+    last_report: Option<Instant>,
+    path: String,
+    phases: Vec<Phase>,
+}
+
+impl<S: ProgressSink> Progress<S> {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    #[inline]
+    #[must_use]
+    pub fn new(sink: S) -> Self {
+        Self::with_interval(sink, Self::DEFAULT_INTERVAL)
+    }
+
+    /// Reports [advances](Self::advance) at most once per `interval`.
+    #[inline]
+    #[must_use]
+    pub fn with_interval(sink: S, interval: Duration) -> Self {
+        Self {
+            sink,
+            interval,
+            last_report: None,
+            path: String::new(),
+            phases: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    #[inline]
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Starts a phase inside the current one. `total` is `0` if it isn't known.
+    pub fn begin(&mut self, name: &str, total: u64) {
+        let path_len = self.path.len();
+        if !self.phases.is_empty() {
+            self.path.push_str(" > ");
+        }
+        self.path.push_str(name);
+        self.phases.push(Phase { path_len, done: 0, total, started: Instant::now() });
+        self.report(false);
+    }
+
+    /// Marks `amount` more work done in the current phase.
+    pub fn advance(&mut self, amount: u64) {
+        let Some(phase) = self.phases.last_mut() else {
+            return;
+        };
+        phase.done = phase.done.saturating_add(amount);
+        if self.last_report.is_none_or(|last| last.elapsed() >= self.interval) {
+            self.report(false);
+        }
+    }
+
+    /// Changes the total of the current phase, once it's known.
+    pub fn set_total(&mut self, total: u64) {
+        if let Some(phase) = self.phases.last_mut() {
+            phase.total = total;
+        }
+    }
+
+    /// Ends the current phase.
+    pub fn end(&mut self) {
+        if self.phases.is_empty() {
+            return;
+        }
+        self.report(true);
+        let phase = self.phases.pop().expect("checked above");
+        self.path.truncate(phase.path_len);
+    }
+
+    fn report(&mut self, finished: bool) {
+        let Some(phase) = self.phases.last() else {
+            return;
+        };
+        let elapsed = phase.started.elapsed();
+        let eta = (!finished && phase.done != 0 && phase.total > phase.done).then(|| {
+            let remaining = (phase.total - phase.done) as f64 / phase.done as f64;
+            elapsed.mul_f64(remaining)
+        });
+        self.sink.report(&ProgressUpdate {
+            path: &self.path,
+            depth: self.phases.len(),
+            done: phase.done,
+            total: phase.total,
+            elapsed,
+            eta,
+            finished,
+        });
+        self.last_report = Some(Instant::now());
+    }
+}
+
+/// Formats a duration for people: `850ms`, `12.3s`, `4m 05s`, `2h 13m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        match secs {
+            0 => write!(f, "{}ms", self.0.as_millis()),
+            1..60 => write!(f, "{:.1}s", self.0.as_secs_f64()),
+            60..3600 => write!(f, "{}m {:02}s", secs / 60, secs % 60),
+            _ => write!(f, "{}h {:02}m", secs / 3600, secs % 3600 / 60),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_test() {
+        let mut lines = Vec::new();
+        let mut progress = Progress::with_interval(|update: &ProgressUpdate| lines.push((update.to_string(), update.depth)), Duration::from_secs(3600));
+        progress.begin("save", 2);
+        progress.begin("chunks", 0);
+        progress.set_total(100);
+        // Throttled: nothing is reported until the phase ends.
+        for _ in 0..100 {
+            progress.advance(1);
+        }
+        progress.end();
+        progress.advance(1);
+        progress.end();
+        progress.end();
+        drop(progress);
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], ("[save] 0/2 (0.0%)".to_owned(), 1));
+        assert_eq!(lines[1], ("[save > chunks] 0".to_owned(), 2));
+        assert!(lines[2].0.starts_with("[save > chunks] 100 done in "));
+        assert!(lines[3].0.starts_with("[save] 1 done in "));
+    }
+
+    #[test]
+    fn console_sink_test() {
+        let mut progress = Progress::with_interval(ConsoleSink::new(Vec::new()), Duration::ZERO);
+        progress.begin("pregen", 4);
+        progress.begin("region", 0);
+        progress.end();
+        progress.advance(1);
+        let text = String::from_utf8(progress.into_sink().into_inner()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "[pregen] 0/4 (0.0%)");
+        assert!(lines[2].starts_with("  [pregen > region] 0 done in "));
+        assert!(lines[3].starts_with("[pregen] 1/4 (25.0%) eta "));
+
+        assert_eq!(HumanDuration(Duration::from_millis(850)).to_string(), "850ms");
+        assert_eq!(HumanDuration(Duration::from_secs(245)).to_string(), "4m 05s");
+        assert_eq!(HumanDuration(Duration::from_secs(7980)).to_string(), "2h 13m");
+    }
+}
@@ -0,0 +1,45 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mfgeometry::{Direction, Rotation};
+
+fn source_face(c: &mut Criterion) {
+    let mut group = c.benchmark_group("source_face");
+    group.bench_function("table", |b| b.iter(|| {
+        for rotation in Rotation::iter() {
+            for face in Direction::ALL {
+                black_box(black_box(rotation).source_face(black_box(face)));
+            }
+        }
+    }));
+    group.bench_function("match", |b| b.iter(|| {
+        for rotation in Rotation::iter() {
+            for face in Direction::ALL {
+                black_box(black_box(rotation).source_face_match(black_box(face)));
+            }
+        }
+    }));
+    group.finish();
+}
+
+fn face_angle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("face_angle");
+    group.bench_function("table", |b| b.iter(|| {
+        for rotation in Rotation::iter() {
+            for face in Direction::ALL {
+                black_box(black_box(rotation).face_angle(black_box(face)));
+            }
+        }
+    }));
+    group.bench_function("match", |b| b.iter(|| {
+        for rotation in Rotation::iter() {
+            for face in Direction::ALL {
+                black_box(black_box(rotation).face_angle_match(black_box(face)));
+            }
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, source_face, face_angle);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+//! Builds orientations, rotations and flips from arbitrary bytes, and composes them.
+//!
+//! `cargo +nightly fuzz run from_u8` (from `crates/mfgeometry`).
+//!
+//! Invalid bytes must be rejected by `from_u8` and wrapped into range by `from_u8_wrapping`, and
+//! composing whatever comes out must never reach the `unreachable_unchecked` in `reorient` and
+//! `deorient`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mfgeometry::{Flip, Orientation, Rotation};
+
+fuzz_target!(|data: &[u8]| {
+    for &byte in data {
+        match Orientation::from_u8(byte) {
+            Some(orientation) => {
+                assert!(byte < 192);
+                assert_eq!(orientation.as_u8(), byte);
+                assert_eq!(Orientation::from_u8_wrapping(byte), orientation);
+            }
+            None => assert!(byte >= 192),
+        }
+        match Rotation::from_u8(byte) {
+            Some(rotation) => {
+                assert!(byte < 24);
+                assert_eq!(rotation.as_u8(), byte);
+            }
+            None => assert!(byte >= 24),
+        }
+        assert_eq!(Flip::from_u8(byte).is_some(), byte < 8);
+        assert!(Orientation::from_u8(Orientation::from_u8_wrapping(byte).as_u8()).is_some());
+        assert!(Rotation::from_u8(Rotation::from_u8_wrapping(byte).as_u8()).is_some());
+    }
+
+    let orientations = data.iter().map(|&byte| Orientation::from_u8_wrapping(byte)).collect::<Vec<_>>();
+    let mut composed = Orientation::UNORIENTED;
+    for window in orientations.windows(2) {
+        let (a, b) = (window[0], window[1]);
+        assert_eq!(a.reorient(b).deorient(b), a);
+        assert_eq!(a * b / b, a);
+        composed *= a;
+    }
+    // Undoing the composition in reverse order gets back to where it started.
+    for window in orientations.windows(2).rev() {
+        composed /= window[0];
+    }
+    assert_eq!(composed, Orientation::UNORIENTED);
+});
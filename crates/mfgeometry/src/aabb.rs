@@ -0,0 +1,175 @@
+use crate::{Flip, Orientation, Rotation};
+
+/// An axis-aligned box between two corners, with `min <= max` on every axis.
+///
+/// For [IAabb]s of voxels, `min` is inclusive and `max` exclusive, so transforming the corners
+/// (see [Orientation::transform_aabb]) gives the box of the transformed voxels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aabb<T> {
+    pub min: (T, T, T),
+    pub max: (T, T, T),
+}
+
+/// An integer [Aabb], such as the voxels of a machine.
+pub type IAabb = Aabb<i32>;
+/// A float [Aabb], such as a collision shape.
+pub type FAabb = Aabb<f32>;
+
+#[inline]
+fn sorted<T: PartialOrd>(a: T, b: T) -> (T, T) {
+    if b < a { (b, a) } else { (a, b) }
+}
+
+impl<T> Aabb<T> {
+    /// `min` must not be greater than `max` on any axis. Use [Aabb::from_corners] when it might be.
+    #[inline]
+    #[must_use]
+    pub const fn new(min: (T, T, T), max: (T, T, T)) -> Self {
+        Self { min, max }
+    }
+}
+
+impl<T: Copy + PartialOrd> Aabb<T> {
+    /// The box between two opposite corners, in any order.
+    #[inline]
+    #[must_use]
+    pub fn from_corners(a: (T, T, T), b: (T, T, T)) -> Self {
+        let (min_x, max_x) = sorted(a.0, b.0);
+        let (min_y, max_y) = sorted(a.1, b.1);
+        let (min_z, max_z) = sorted(a.2, b.2);
+        Self::new((min_x, min_y, min_z), (max_x, max_y, max_z))
+    }
+
+    /// Whether the box has no volume.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.min.0 >= self.max.0 || self.min.1 >= self.max.1 || self.min.2 >= self.max.2
+    }
+
+    /// Whether `point` is in the box, with `max` excluded.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, (x, y, z): (T, T, T)) -> bool {
+        self.min.0 <= x && x < self.max.0
+        && self.min.1 <= y && y < self.max.1
+        && self.min.2 <= z && z < self.max.2
+    }
+
+    /// Whether the boxes overlap. Boxes that only touch don't.
+    #[inline]
+    #[must_use]
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.0 < other.max.0 && other.min.0 < self.max.0
+        && self.min.1 < other.max.1 && other.min.1 < self.max.1
+        && self.min.2 < other.max.2 && other.min.2 < self.max.2
+    }
+
+    /// The smallest box containing both.
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        let min = |a: T, b: T| if b < a { b } else { a };
+        let max = |a: T, b: T| if b > a { b } else { a };
+        Self::new(
+            (min(self.min.0, other.min.0), min(self.min.1, other.min.1), min(self.min.2, other.min.2)),
+            (max(self.max.0, other.max.0), max(self.max.1, other.max.1), max(self.max.2, other.max.2)),
+        )
+    }
+}
+
+impl<T: Copy + std::ops::Add<Output = T>> Aabb<T> {
+    /// The box moved by `offset`.
+    #[inline]
+    #[must_use]
+    pub fn translate(&self, offset: (T, T, T)) -> Self {
+        Self::new(
+            (self.min.0 + offset.0, self.min.1 + offset.1, self.min.2 + offset.2),
+            (self.max.0 + offset.0, self.max.1 + offset.1, self.max.2 + offset.2),
+        )
+    }
+}
+
+impl<T: Copy + std::ops::Sub<Output = T>> Aabb<T> {
+    #[inline]
+    #[must_use]
+    pub fn size(&self) -> (T, T, T) {
+        (self.max.0 - self.min.0, self.max.1 - self.min.1, self.max.2 - self.min.2)
+    }
+}
+
+impl Orientation {
+    /// Transforms `aabb` around the origin (see [Orientation::transform]), then sorts the corners
+    /// back into `min` and `max`, since rotating and flipping can swap them.
+    ///
+    /// To transform around another point (like the center of a block), [translate](Aabb::translate)
+    /// the box so that the point is at the origin, transform it, and translate it back.
+    #[inline]
+    #[must_use]
+    pub fn transform_aabb<T: Copy + PartialOrd + std::ops::Neg<Output = T>>(self, aabb: Aabb<T>) -> Aabb<T> {
+        Aabb::from_corners(self.transform(aabb.min), self.transform(aabb.max))
+    }
+}
+
+impl Rotation {
+    /// Rotates `aabb` around the origin, sorting the corners back into `min` and `max`. See
+    /// [Orientation::transform_aabb].
+    #[inline]
+    #[must_use]
+    pub fn rotate_aabb<T: Copy + PartialOrd + std::ops::Neg<Output = T>>(self, aabb: Aabb<T>) -> Aabb<T> {
+        Aabb::from_corners(self.rotate_coord(aabb.min), self.rotate_coord(aabb.max))
+    }
+}
+
+impl Flip {
+    /// Flips `aabb` across the origin, sorting the corners back into `min` and `max`. See
+    /// [Orientation::transform_aabb].
+    #[inline]
+    #[must_use]
+    pub fn flip_aabb<T: Copy + PartialOrd + std::ops::Neg<Output = T>>(self, aabb: Aabb<T>) -> Aabb<T> {
+        Aabb::from_corners(self.flip_coord(aabb.min), self.flip_coord(aabb.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_aabb_test() {
+        // A machine's footprint, and the voxels in it.
+        let aabb = IAabb::new((-1, 0, 2), (3, 2, 3));
+        let voxels = |aabb: IAabb| {
+            let mut voxels = (aabb.min.0..aabb.max.0)
+                .flat_map(|x| (aabb.min.1..aabb.max.1).flat_map(move |y| (aabb.min.2..aabb.max.2).map(move |z| (x, y, z))))
+                .collect::<Vec<_>>();
+            voxels.sort();
+            voxels
+        };
+        for orientation in Orientation::UNORIENTED.iter() {
+            let transformed = orientation.transform_aabb(aabb);
+            // Each voxel goes to the box of its transformed corners, and together they fill the
+            // transformed box.
+            let mut moved = voxels(aabb).into_iter()
+                .map(|voxel| orientation.transform_aabb(IAabb::new(voxel, (voxel.0 + 1, voxel.1 + 1, voxel.2 + 1))).min)
+                .collect::<Vec<_>>();
+            moved.sort();
+            assert_eq!(moved, voxels(transformed), "{orientation}");
+            let rotated = orientation.rotation().rotate_aabb(aabb);
+            assert_eq!(orientation.flip().flip_aabb(rotated), transformed);
+        }
+
+        // A half slab, turned around the center of its block.
+        let slab = FAabb::new((0.0, 0.0, 0.0), (1.0, 0.5, 1.0));
+        let center = (0.5, 0.5, 0.5);
+        let upside_down = Orientation::new(Rotation::UNROTATED, Flip::Y)
+            .transform_aabb(slab.translate((-center.0, -center.1, -center.2)))
+            .translate(center);
+        assert_eq!(upside_down, FAabb::new((0.0, 0.5, 0.0), (1.0, 1.0, 1.0)));
+
+        assert_eq!(IAabb::from_corners((3, -1, 0), (0, 2, -4)), IAabb::new((0, -1, -4), (3, 2, 0)));
+        assert!(aabb.intersects(&IAabb::new((2, 1, 2), (5, 5, 5))));
+        assert!(!aabb.intersects(&IAabb::new((3, 0, 2), (5, 2, 3))));
+        assert_eq!(aabb.union(&IAabb::new((0, -3, 0), (1, 1, 1))), IAabb::new((-1, -3, 0), (3, 2, 3)));
+        assert!(IAabb::new((0, 0, 0), (0, 1, 1)).is_empty());
+    }
+}
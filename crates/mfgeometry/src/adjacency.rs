@@ -0,0 +1,239 @@
+use crate::{orient_table::CoordMap, Direction};
+
+// Field  : Bit
+// up     : 0
+// right  : 1
+// down   : 2
+// left   : 3
+/// Which of the four neighbours in the plane of a face connect to it, for connected textures
+/// (pipes, machine casings). Up and right are the face's [Direction::up] and [Direction::right],
+/// the same axes as face UVs.
+///
+/// The mask is a canonical key (`0..16`) for picking a texture variant. Neighbours are found in
+/// world space, but a block's textures are drawn on its unoriented faces, so the mask is remapped
+/// with [Orientation::source_face_adjacency](crate::Orientation::source_face_adjacency) before
+/// it's used as a key.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaceAdjacency(u8);
+
+impl FaceAdjacency {
+    pub const NONE: Self = Self(0b0000);
+    pub const UP: Self = Self(0b0001);
+    pub const RIGHT: Self = Self(0b0010);
+    pub const DOWN: Self = Self(0b0100);
+    pub const LEFT: Self = Self(0b1000);
+    pub const ALL: Self = Self(0b1111);
+
+    /// The single edges, counter-clockwise from right, with their offsets in face UV space.
+    pub const EDGES: [(Self, (i32, i32)); 4] = [
+        (Self::RIGHT, (1, 0)),
+        (Self::UP, (0, 1)),
+        (Self::LEFT, (-1, 0)),
+        (Self::DOWN, (0, -1)),
+    ];
+
+    #[inline]
+    #[must_use]
+    pub const fn new(up: bool, right: bool, down: bool, left: bool) -> Self {
+        Self(up as u8 | (right as u8) << 1 | (down as u8) << 2 | (left as u8) << 3)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        if value > Self::ALL.0 {
+            return None;
+        }
+        Some(Self(value))
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    /// The edge of the face in `direction`, which has to be in the plane of `face`.
+    #[must_use]
+    pub const fn edge(face: Direction, direction: Direction) -> Option<Self> {
+        match direction {
+            _ if direction as u8 == face.up() as u8 => Some(Self::UP),
+            _ if direction as u8 == face.right() as u8 => Some(Self::RIGHT),
+            _ if direction as u8 == face.down() as u8 => Some(Self::DOWN),
+            _ if direction as u8 == face.left() as u8 => Some(Self::LEFT),
+            _ => None,
+        }
+    }
+
+    /// The mask of the neighbours of `face` that `connects` to.
+    pub fn from_neighbors<F: FnMut(Direction) -> bool>(face: Direction, mut connects: F) -> Self {
+        Self::new(connects(face.up()), connects(face.right()), connects(face.down()), connects(face.left()))
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The number of connected edges.
+    #[inline]
+    #[must_use]
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Every mask, in key order.
+    #[inline]
+    pub fn iter() -> impl Iterator<Item = Self> {
+        (0..=Self::ALL.0).map(Self)
+    }
+
+    /// Maps each edge's offset through `coordmap`, as UVs on the face are.
+    pub(crate) const fn map(self, coordmap: CoordMap) -> Self {
+        let mut mapped = 0u8;
+        let mut index = 0;
+        while index < Self::EDGES.len() {
+            let (edge, offset) = Self::EDGES[index];
+            if self.contains(edge) {
+                mapped |= match coordmap.map_i32(offset) {
+                    (1, 0) => Self::RIGHT.0,
+                    (0, 1) => Self::UP.0,
+                    (-1, 0) => Self::LEFT.0,
+                    _ => Self::DOWN.0,
+                };
+            }
+            index += 1;
+        }
+        Self(mapped)
+    }
+}
+
+impl std::ops::BitOr for FaceAdjacency {
+    type Output = Self;
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl std::ops::BitOrAssign for FaceAdjacency {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl std::ops::BitAnd for FaceAdjacency {
+    type Output = Self;
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl std::ops::Sub for FaceAdjacency {
+    type Output = Self;
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(rhs)
+    }
+}
+
+impl std::ops::Not for FaceAdjacency {
+    type Output = Self;
+    #[inline]
+    fn not(self) -> Self::Output {
+        Self(!self.0 & Self::ALL.0)
+    }
+}
+
+impl From<FaceAdjacency> for u8 {
+    #[inline]
+    fn from(value: FaceAdjacency) -> Self {
+        value.as_u8()
+    }
+}
+
+impl std::fmt::Display for FaceAdjacency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("None");
+        }
+        let names = [(Self::UP, "Up"), (Self::RIGHT, "Right"), (Self::DOWN, "Down"), (Self::LEFT, "Left")];
+        let mut first = true;
+        for (edge, name) in names {
+            if self.contains(edge) {
+                if !first {
+                    f.write_str("|")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Orientation;
+
+    #[test]
+    fn face_adjacency_test() {
+        assert_eq!(FaceAdjacency::new(true, true, true, true), FaceAdjacency::ALL);
+        assert_eq!(FaceAdjacency::from_u8(16), None);
+        assert_eq!(!FaceAdjacency::UP, FaceAdjacency::RIGHT | FaceAdjacency::DOWN | FaceAdjacency::LEFT);
+        assert_eq!((FaceAdjacency::UP | FaceAdjacency::LEFT).to_string(), "Up|Left");
+        let face = Direction::PosZ;
+        assert_eq!(FaceAdjacency::edge(face, face.right()), Some(FaceAdjacency::RIGHT));
+        assert_eq!(FaceAdjacency::edge(face, face), None);
+        assert_eq!(FaceAdjacency::from_neighbors(face, |direction| direction == face.down()), FaceAdjacency::DOWN);
+    }
+
+    #[test]
+    fn map_face_adjacency_test() {
+        // Each connected edge of the unoriented face ends up on the edge of the world face that
+        // the orientation turns it to, for every orientation, face and mask.
+        for orientation in Orientation::iter_satisfying(|_| true) {
+            for face in Direction::iter() {
+                let source = orientation.source_face(face);
+                for mask in FaceAdjacency::iter() {
+                    let mapped = orientation.map_face_adjacency(face, mask);
+                    let expected = FaceAdjacency::from_neighbors(face, |world| {
+                        let local = orientation.source_face(world);
+                        FaceAdjacency::edge(source, local).is_some_and(|edge| mask.contains(edge))
+                    });
+                    assert_eq!(mapped, expected, "{orientation:?} {face} {mask}");
+                    assert_eq!(orientation.source_face_adjacency(face, mapped), mask);
+                    assert_eq!(mapped.count(), mask.count());
+                }
+            }
+        }
+        // A quarter turn about the face turns the mask with it.
+        let turned = Orientation::Y_ROTATIONS[1];
+        let mapped = turned.map_face_adjacency(Direction::PosY, FaceAdjacency::UP);
+        assert_ne!(mapped, FaceAdjacency::UP);
+        assert_eq!(mapped.count(), 1);
+    }
+}
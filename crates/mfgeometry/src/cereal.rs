@@ -0,0 +1,83 @@
+//! [Encode] and [Decode] for the geometry types, behind the `cereal` feature.
+//!
+//! Each type is written as one byte, the same byte it [hashes](crate::hash) as. These layouts are
+//! stored in saves, so they must not change:
+//!
+//! - [Direction]: its discriminant, `0..6` (`PosY`, `PosX`, `PosZ`, `NegY`, `NegX`, `NegZ`).
+//! - [Flip]: `0..8`, with bit `0` flipping X, bit `1` Y, and bit `2` Z.
+//! - [Rotation]: `0..24`, with the angle in bits `0..2` and the up [Direction] in bits `2..5`.
+//! - [Orientation]: the [Flip] in bits `0..3` and the [Rotation] in bits `3..8` (see
+//!   [pack_flip_and_rotation](crate::pack_flip_and_rotation)).
+//!
+//! Bytes outside of those ranges fail to decode with [DecodeError::UnknownDiscriminant].
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{Direction, Flip, Orientation, Rotation};
+
+macro_rules! impl_cereal_u8 {
+    ($($type:ident => |$value:ident| $bits:expr, |$byte:ident| $from:expr;)+) => {
+        $(
+            // Layout: see the module docs.
+            impl Encode for $type {
+                #[inline]
+                fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+                    let $value = *self;
+                    encoder.write_u8($bits)
+                }
+            }
+
+            impl Decode for $type {
+                #[inline]
+                fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+                    let $byte = decoder.read_u8()?;
+                    $from.ok_or(DecodeError::UnknownDiscriminant { ty: stringify!($type), value: $byte as u64 })
+                }
+            }
+        )+
+    };
+}
+
+impl_cereal_u8! {
+    Direction => |direction| direction.discriminant(), |byte| Direction::from_rotation_discriminant(byte);
+    Flip => |flip| flip.as_u8(), |byte| Flip::from_u8(byte);
+    Rotation => |rotation| rotation.as_u8(), |byte| Rotation::from_u8(byte);
+    Orientation => |orientation| orientation.as_u8(), |byte| Orientation::from_u8(byte);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: Encode + Decode + PartialEq + std::fmt::Debug>(value: T) -> u8 {
+        let mut bytes = Vec::new();
+        assert_eq!(value.encode(&mut bytes).unwrap(), 1);
+        assert_eq!(T::decode(&mut bytes.as_slice()).unwrap(), value);
+        bytes[0]
+    }
+
+    #[test]
+    fn cereal_test() {
+        for direction in Direction::iter() {
+            assert_eq!(roundtrip(direction), direction.discriminant());
+        }
+        for orientation in Orientation::UNORIENTED.iter() {
+            assert_eq!(roundtrip(orientation), orientation.as_u8());
+            roundtrip(orientation.flip());
+            roundtrip(orientation.rotation());
+        }
+        // The layouts are stored, so pin a few of them.
+        assert_eq!(roundtrip(Direction::NegZ), 5);
+        assert_eq!(roundtrip(Flip::X | Flip::Z), 0b101);
+        assert_eq!(roundtrip(Rotation::new(Direction::PosX, 1)), 0b101);
+        assert_eq!(roundtrip(Orientation::new(Rotation::new(Direction::PosX, 1), Flip::Y)), 0b101_010);
+
+        assert!(matches!(Direction::decode(&mut [6u8].as_slice()), Err(DecodeError::UnknownDiscriminant { ty: "Direction", value: 6 })));
+        assert!(matches!(Flip::decode(&mut [8u8].as_slice()), Err(DecodeError::UnknownDiscriminant { ty: "Flip", .. })));
+        assert!(matches!(Rotation::decode(&mut [24u8].as_slice()), Err(DecodeError::UnknownDiscriminant { ty: "Rotation", .. })));
+        assert!(matches!(Orientation::decode(&mut [24u8 << 3].as_slice()), Err(DecodeError::UnknownDiscriminant { ty: "Orientation", .. })));
+    }
+}
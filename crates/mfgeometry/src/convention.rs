@@ -0,0 +1,146 @@
+//! Adapters between Manufactory's axes and the axes of other tools.
+//!
+//! Manufactory is Y up with -Z forward (north) and +X right (east), which is right-handed. Other
+//! tools use Z up, or +Z forward (left-handed). Converting positions without converting orientations
+//! the same way (or the other way around) mirrors imported structures, so [AxisConvention] converts
+//! both.
+
+use crate::{Direction, Orientation};
+
+/// Where the axes of an external convention point in Manufactory's space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AxisConvention {
+    /// The direction of the external +X, +Y, and +Z axes, indexed by axis.
+    axes: [Direction; 3],
+    /// Maps the external space onto Manufactory's.
+    map: Orientation,
+}
+
+impl AxisConvention {
+    /// Manufactory's own axes (also Minecraft's): Y up, -Z forward, right-handed.
+    pub const NATIVE: Self = Self::new_unchecked(Direction::PosX, Direction::PosY, Direction::PosZ);
+    /// Z up, +Y forward, right-handed (MagicaVoxel, Blender).
+    pub const Z_UP: Self = Self::new_unchecked(Direction::PosX, Direction::NegZ, Direction::PosY);
+    /// Y up, +Z forward, left-handed (Unity).
+    pub const Y_UP_LEFT_HANDED: Self = Self::new_unchecked(Direction::PosX, Direction::PosY, Direction::NegZ);
+
+    const fn new_unchecked(x: Direction, y: Direction, z: Direction) -> Self {
+        match Self::new(x, y, z) {
+            Some(convention) => convention,
+            None => panic!("Axis directions must be orthogonal."),
+        }
+    }
+
+    /// Describes a convention by the directions its +X, +Y, and +Z axes point in Manufactory's space.
+    /// Returns `None` unless the directions are on different axes.
+    pub const fn new(x: Direction, y: Direction, z: Direction) -> Option<Self> {
+        if !x.is_orthogonal_to(y) || !x.is_orthogonal_to(z) || !y.is_orthogonal_to(z) {
+            return None;
+        }
+        let mut i = 0;
+        while i < Orientation::TOTAL_ORIENTATION_COUNT {
+            let map = Orientation::from_u8_wrapping(i);
+            if map.reface(Direction::PosX) as u8 == x as u8
+            && map.reface(Direction::PosY) as u8 == y as u8
+            && map.reface(Direction::PosZ) as u8 == z as u8 {
+                return Some(Self { axes: [x, y, z], map });
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Whether the convention has the opposite handedness from Manufactory.
+    #[inline]
+    pub const fn is_mirrored(self) -> bool {
+        self.map.is_reflection()
+    }
+
+    #[inline]
+    pub const fn axes(self) -> [Direction; 3] {
+        self.axes
+    }
+
+    /// Converts a direction from the external convention.
+    #[inline]
+    pub const fn import_direction(self, direction: Direction) -> Direction {
+        self.map.reface(direction)
+    }
+
+    /// Converts a direction to the external convention.
+    #[inline]
+    pub const fn export_direction(self, direction: Direction) -> Direction {
+        self.map.source_face(direction)
+    }
+
+    /// Converts a position (or offset) from the external convention.
+    #[inline]
+    pub fn import_point(self, point: (i32, i32, i32)) -> (i32, i32, i32) {
+        self.map.transform(point)
+    }
+
+    /// Converts a position (or offset) to the external convention.
+    #[inline]
+    pub fn export_point(self, point: (i32, i32, i32)) -> (i32, i32, i32) {
+        self.map.invert().transform(point)
+    }
+
+    /// Converts a block's orientation from the external convention, so that it turns the block's
+    /// faces to the same places relative to the imported structure.
+    pub fn import_orientation(self, orientation: Orientation) -> Orientation {
+        conjugate(orientation, |dir| self.export_direction(dir), |dir| self.import_direction(dir))
+    }
+
+    /// Converts a block's orientation to the external convention.
+    pub fn export_orientation(self, orientation: Orientation) -> Orientation {
+        conjugate(orientation, |dir| self.import_direction(dir), |dir| self.export_direction(dir))
+    }
+}
+
+impl Default for AxisConvention {
+    #[inline]
+    fn default() -> Self {
+        Self::NATIVE
+    }
+}
+
+/// Finds the orientation that maps `face` to `to(orientation.reface(from(face)))`.
+fn conjugate<F: Fn(Direction) -> Direction, T: Fn(Direction) -> Direction>(orientation: Orientation, from: F, to: T) -> Orientation {
+    let target = [Direction::PosX, Direction::PosY, Direction::PosZ].map(|face| to(orientation.reface(from(face))));
+    Orientation::UNORIENTED.iter()
+        .find(|candidate| [Direction::PosX, Direction::PosY, Direction::PosZ].map(|face| candidate.reface(face)) == target)
+        .expect("Every signed permutation of the axes is an orientation.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convention_test() {
+        assert!(!AxisConvention::Z_UP.is_mirrored());
+        assert!(AxisConvention::Y_UP_LEFT_HANDED.is_mirrored());
+        assert_eq!(AxisConvention::new(Direction::PosX, Direction::NegX, Direction::PosZ), None);
+        assert_eq!(AxisConvention::Z_UP.import_direction(Direction::PosZ), Direction::UP);
+        assert_eq!(AxisConvention::Z_UP.import_point((1, 2, 3)), (1, 3, -2));
+
+        let external_orientations = Orientation::UNORIENTED.iter().collect::<Vec<_>>();
+        for convention in [AxisConvention::NATIVE, AxisConvention::Z_UP, AxisConvention::Y_UP_LEFT_HANDED] {
+            for &external in &external_orientations {
+                let imported = convention.import_orientation(external);
+                let exported = convention.export_orientation(imported);
+                assert!(Direction::iter().all(|face| exported.reface(face) == external.reface(face)));
+                // Handedness is preserved, so rotated blocks don't become mirrored ones.
+                assert_eq!(imported.is_reflection(), external.is_reflection());
+                // Turning a point in external space and importing it is the same as importing it
+                // and turning it in Manufactory's space.
+                let point = (1, 2, 3);
+                assert_eq!(
+                    convention.import_point(external.transform(point)),
+                    imported.transform(convention.import_point(point)),
+                );
+                assert_eq!(convention.export_point(convention.import_point(point)), point);
+            }
+        }
+    }
+}
@@ -341,34 +341,6 @@ impl Direction {
         }
     }
 
-    // /// Converts the [Direction] into a unit vector.
-    // #[inline]
-    // pub const fn to_vec3(self) -> Vec3 {
-    //     use Direction::*;
-    //     match self {
-    //         NegX => Vec3::new(-1.0,  0.0,  0.0),
-    //         NegY => Vec3::new( 0.0, -1.0,  0.0),
-    //         NegZ => Vec3::new( 0.0,  0.0, -1.0),
-    //         PosX => Vec3::new( 1.0,  0.0,  0.0),
-    //         PosY => Vec3::new( 0.0,  1.0,  0.0),
-    //         PosZ => Vec3::new( 0.0,  0.0,  1.0),
-    //     }
-    // }
-
-    // /// Converts the [Direction] into a unit integer vector.
-    // #[inline]
-    // pub const fn to_ivec3(self) -> IVec3 {
-    //     use Direction::*;
-    //     match self {
-    //         NegX => IVec3::new(-1,  0,  0),
-    //         NegY => IVec3::new( 0, -1,  0),
-    //         NegZ => IVec3::new( 0,  0, -1),
-    //         PosX => IVec3::new( 1,  0,  0),
-    //         PosY => IVec3::new( 0,  1,  0),
-    //         PosZ => IVec3::new( 0,  0,  1),
-    //     }
-    // }
-
     // verified (2025-12-28)
     #[inline]
     pub const fn to_ftuple(self) -> (f32, f32, f32) {
@@ -420,20 +392,6 @@ impl std::ops::Neg for Direction {
     }
 }
 
-// impl Into<Vec3> for Direction {
-//     #[inline]
-//     fn into(self) -> Vec3 {
-//         self.to_vec3()
-//     }
-// }
-
-// impl Into<IVec3> for Direction {
-//     #[inline]
-//     fn into(self) -> IVec3 {
-//         self.to_ivec3()
-//     }
-// }
-
 impl Into<(i32, i32, i32)> for Direction {
     #[inline]
     fn into(self) -> (i32, i32, i32) {
@@ -0,0 +1,193 @@
+use paste::paste;
+
+use crate::{direction::Direction, orient_table::{AxisMap, CoordMap}, rotation::Rotation, wrap_angle};
+
+// Packed like [Rotation] (which is also a direction and an angle).
+// Field  : Bit Range
+// angle  : 0..2
+// face   : 2..5 (rotation discriminant)
+/// A face of a cube and how many counter-clockwise quarter turns its texture is rotated by.
+///
+/// This is what [Rotation::face_angle] returns: the angle the texture of the face that ended up
+/// facing `face` was turned by.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaceAngle(u8);
+
+macro_rules! map_coord_impls {
+    ($(
+        $type:ty
+    ),*$(,)?) => {
+        $(
+            paste!{
+                /// Turns a UV on the face by the angle. Center coords around (0, 0) first, as with
+                /// [Orientation::map_face_coord_i32](crate::Orientation::map_face_coord_i32).
+                #[inline]
+                pub const fn [<map_coord_ $type>](self, uv: ($type, $type)) -> ($type, $type) {
+                    self.coord_map().[<map_ $type>](uv)
+                }
+            }
+        )*
+    };
+}
+
+impl FaceAngle {
+    const ANGLE_MASK: u8 = Rotation::ANGLE_MASK;
+    const FACE_SHIFT: u32 = Rotation::UP_SHIFT;
+
+    #[inline]
+    #[must_use]
+    pub const fn new(face: Direction, angle: i32) -> Self {
+        Self(wrap_angle(angle) as u8 | (face.rotation_discriminant() << Self::FACE_SHIFT))
+    }
+
+    /// The face, unturned.
+    #[inline]
+    #[must_use]
+    pub const fn unturned(face: Direction) -> Self {
+        Self::new(face, 0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        if Direction::from_rotation_discriminant(value >> Self::FACE_SHIFT).is_none() {
+            return None;
+        }
+        Some(Self(value))
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn face(self) -> Direction {
+        match Direction::from_rotation_discriminant(self.0 >> Self::FACE_SHIFT) {
+            Some(face) => face,
+            // SAFETY: Only valid discriminants are ever packed.
+            None => unsafe { ::core::hint::unreachable_unchecked() },
+        }
+    }
+
+    /// The number of counter-clockwise quarter turns (0..4).
+    #[inline]
+    #[must_use]
+    pub const fn angle(self) -> u8 {
+        self.0 & Self::ANGLE_MASK
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn degrees(self) -> u16 {
+        self.angle() as u16 * 90
+    }
+
+    /// Turns the face by a further `angle` quarter turns.
+    #[inline]
+    #[must_use]
+    pub const fn turn(self, angle: i32) -> Self {
+        Self::new(self.face(), self.angle() as i32 + angle)
+    }
+
+    /// The angles of two turns of the same face added together, or `None` if they're different faces.
+    #[inline]
+    #[must_use]
+    pub const fn compose(self, other: Self) -> Option<Self> {
+        if self.0 >> Self::FACE_SHIFT != other.0 >> Self::FACE_SHIFT {
+            return None;
+        }
+        Some(self.turn(other.angle() as i32))
+    }
+
+    /// The turn that undoes this one.
+    #[inline]
+    #[must_use]
+    pub const fn invert(self) -> Self {
+        Self::new(self.face(), -(self.angle() as i32))
+    }
+
+    /// The rotation of the whole cube that turns the face by the angle.
+    #[inline]
+    #[must_use]
+    pub const fn rotation(self) -> Rotation {
+        Rotation::face_rotation(self.face(), self.angle() as i32)
+    }
+
+    // Matches [orient_table::MAP_FACE_COORD_TABLE] for unflipped orientations.
+    #[inline]
+    pub(crate) const fn coord_map(self) -> CoordMap {
+        match self.angle() {
+            0 => CoordMap::new(AxisMap::PosX, AxisMap::PosY),
+            1 => CoordMap::new(AxisMap::PosY, AxisMap::NegX),
+            2 => CoordMap::new(AxisMap::NegX, AxisMap::NegY),
+            _ => CoordMap::new(AxisMap::NegY, AxisMap::PosX),
+        }
+    }
+
+    map_coord_impls!(
+        i8,
+        i16,
+        i32,
+        i64,
+        i128,
+        isize,
+        f32,
+        f64,
+    );
+}
+
+impl From<FaceAngle> for u8 {
+    #[inline]
+    fn from(value: FaceAngle) -> Self {
+        value.as_u8()
+    }
+}
+
+impl std::fmt::Display for FaceAngle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}°", self.face(), self.degrees())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Flip, Orientation};
+
+    #[test]
+    fn face_angle_test() {
+        for face in Direction::iter() {
+            for angle in 0..4 {
+                let turned = FaceAngle::new(face, angle);
+                assert_eq!((turned.face(), turned.angle()), (face, angle as u8));
+                assert_eq!(FaceAngle::from_u8(turned.as_u8()), Some(turned));
+                assert_eq!(turned.compose(turned.invert()), Some(FaceAngle::unturned(face)));
+                assert_eq!(turned.compose(FaceAngle::new(face, 3)), Some(turned.turn(-1)));
+                assert_eq!(turned.compose(FaceAngle::new(face.invert(), 0)), None);
+                assert_eq!(turned.rotation().reface(face), face);
+            }
+        }
+        assert_eq!(FaceAngle::from_u8(6 << FaceAngle::FACE_SHIFT), None);
+        assert_eq!(FaceAngle::new(Direction::PosX, -1).to_string(), "PosX@270°");
+    }
+
+    #[test]
+    fn face_angle_coord_map_test() {
+        // Turning the UV of a face by its angle is what orientations do to UVs.
+        for rotation in Rotation::iter() {
+            let orientation = Orientation::new(rotation, Flip::NONE);
+            for face in Direction::iter() {
+                let turned = rotation.face_angle(face);
+                assert_eq!(turned.face(), face);
+                for uv in [(1, 0), (0, 1), (3, -2)] {
+                    assert_eq!(turned.map_coord_i32(uv), orientation.map_face_coord_i32(face, uv), "{rotation} {turned}");
+                    assert_eq!(turned.invert().map_coord_i32(turned.map_coord_i32(uv)), uv);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Conversions to and from the facing strings used by other voxel tools (Minecraft-style block
+//! states), for importing schematics.
+//!
+//! Those tools use the same axes as Manufactory (Y up, north toward -Z, east toward +X), so facings
+//! map onto [Direction]s directly. Formats with different axes go through an
+//! [AxisConvention](crate::convention::AxisConvention) first.
+
+use crate::{Axis, Direction, Flip, Orientation, Rotation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FacingError {
+    /// A property had a value that isn't valid for it. Holds the property's name.
+    InvalidValue(&'static str),
+    /// More than one of `facing`, `rotation`, and `axis` was given.
+    Conflicting,
+    /// A property isn't in `key=value` form.
+    Malformed,
+}
+
+impl std::fmt::Display for FacingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacingError::InvalidValue(key) => write!(f, "Invalid value for block state property `{key}`."),
+            FacingError::Conflicting => write!(f, "Block state has more than one of `facing`, `rotation`, and `axis`."),
+            FacingError::Malformed => write!(f, "Block state property is not in `key=value` form."),
+        }
+    }
+}
+
+impl std::error::Error for FacingError {}
+
+impl Direction {
+    /// The facing name of the direction: `"north"`, `"south"`, `"east"`, `"west"`, `"up"`, or `"down"`.
+    #[inline]
+    pub const fn facing_name(self) -> &'static str {
+        match self {
+            Direction::NegZ => "north",
+            Direction::PosZ => "south",
+            Direction::PosX => "east",
+            Direction::NegX => "west",
+            Direction::PosY => "up",
+            Direction::NegY => "down",
+        }
+    }
+
+    /// Parses a facing name (see [Direction::facing_name]), ignoring case.
+    pub fn from_facing_name(name: &str) -> Option<Self> {
+        Direction::ALL.into_iter().find(|dir| dir.facing_name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Orientation {
+    /// The orientation of a block whose front faces `facing`.
+    ///
+    /// Horizontal facings turn the block around the Y axis. Vertical facings tip the block over
+    /// from facing north, so that its top faces south (for `up`) or north (for `down`).
+    #[inline]
+    pub const fn from_facing(facing: Direction) -> Self {
+        let up = match facing {
+            Direction::PosY => Direction::PosZ,
+            Direction::NegY => Direction::NegZ,
+            _ => Direction::PosY,
+        };
+        match Rotation::from_up_and_forward(up, facing) {
+            Some(rotation) => Orientation::new(rotation, Flip::NONE),
+            None => unreachable!(),
+        }
+    }
+
+    /// The facing of the orientation, if it's one created by [Orientation::from_facing].
+    #[inline]
+    pub fn to_facing(self) -> Option<Direction> {
+        let facing = self.forward();
+        (Self::from_facing(facing) == self).then_some(facing)
+    }
+
+    /// The orientation of a pillar-like block (logs, for example) along `axis`.
+    #[inline]
+    pub const fn from_pillar_axis(axis: Axis) -> Self {
+        let (up, forward) = match axis {
+            Axis::X => (Direction::PosX, Direction::NegZ),
+            Axis::Y => (Direction::PosY, Direction::NegZ),
+            Axis::Z => (Direction::PosZ, Direction::PosY),
+        };
+        match Rotation::from_up_and_forward(up, forward) {
+            Some(rotation) => Orientation::new(rotation, Flip::NONE),
+            None => unreachable!(),
+        }
+    }
+
+    /// The orientation for a 16-step `rotation` property (signs, banners), where `0` faces south and
+    /// each step turns clockwise when seen from above. Only the four steps that are multiples of 4
+    /// line up with the grid.
+    #[inline]
+    pub const fn from_rotation16(rotation: u8) -> Option<Self> {
+        match rotation {
+            0 => Some(Self::from_facing(Direction::SOUTH)),
+            4 => Some(Self::from_facing(Direction::WEST)),
+            8 => Some(Self::from_facing(Direction::NORTH)),
+            12 => Some(Self::from_facing(Direction::EAST)),
+            _ => None,
+        }
+    }
+
+    /// Parses the orientation from block state properties, such as `facing=north,half=top`
+    /// (surrounding brackets are allowed).
+    ///
+    /// `facing`, `rotation` (16-step), and `axis` give the orientation, and `half=top` flips it
+    /// upside down (for stairs). Other properties are ignored. Without any of those properties,
+    /// the orientation is [Orientation::UNORIENTED].
+    pub fn from_block_state(state: &str) -> Result<Self, FacingError> {
+        let state = state.trim();
+        let state = state.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(state);
+        let mut orientation = None;
+        let mut upside_down = false;
+        for property in state.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = property.split_once('=').ok_or(FacingError::Malformed)?;
+            let (key, value) = (key.trim(), value.trim());
+            let base = match key {
+                "facing" => Direction::from_facing_name(value)
+                    .map(Self::from_facing)
+                    .ok_or(FacingError::InvalidValue("facing"))?,
+                "rotation" => value.parse().ok()
+                    .and_then(Self::from_rotation16)
+                    .ok_or(FacingError::InvalidValue("rotation"))?,
+                "axis" => match value {
+                    "x" => Self::from_pillar_axis(Axis::X),
+                    "y" => Self::from_pillar_axis(Axis::Y),
+                    "z" => Self::from_pillar_axis(Axis::Z),
+                    _ => return Err(FacingError::InvalidValue("axis")),
+                },
+                "half" => {
+                    upside_down = match value {
+                        "top" => true,
+                        "bottom" => false,
+                        _ => return Err(FacingError::InvalidValue("half")),
+                    };
+                    continue;
+                }
+                _ => continue,
+            };
+            if orientation.replace(base).is_some() {
+                return Err(FacingError::Conflicting);
+            }
+        }
+        let orientation = orientation.unwrap_or(Self::UNORIENTED);
+        Ok(if upside_down { orientation.flip_y() } else { orientation })
+    }
+
+    /// Writes the orientation as block state properties (`facing=...`, plus `half=top` if upside
+    /// down), if it can be expressed that way.
+    pub fn to_block_state(self) -> Option<String> {
+        if let Some(facing) = self.to_facing() {
+            return Some(format!("facing={}", facing.facing_name()));
+        }
+        let facing = self.flip_y().to_facing()?;
+        Some(format!("facing={},half=top", facing.facing_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facing_test() {
+        for dir in Direction::iter() {
+            assert_eq!(Direction::from_facing_name(&dir.facing_name().to_uppercase()), Some(dir));
+            let orientation = Orientation::from_facing(dir);
+            assert_eq!(orientation.forward(), dir);
+            assert_eq!(orientation.to_facing(), Some(dir));
+            let state = orientation.to_block_state().unwrap();
+            assert_eq!(Orientation::from_block_state(&state), Ok(orientation));
+            let flipped = Orientation::from_block_state(&format!("[{state},half=top,waterlogged=false]")).unwrap();
+            assert_eq!(flipped, orientation.flip_y());
+            assert_eq!(flipped.to_block_state().unwrap(), format!("{state},half=top"));
+        }
+        assert_eq!(Orientation::from_facing(Direction::UP).up(), Direction::SOUTH);
+        assert_eq!(Orientation::from_block_state("rotation=4"), Ok(Orientation::from_facing(Direction::WEST)));
+        assert_eq!(Orientation::from_block_state("axis=x").unwrap().up(), Direction::PosX);
+        assert_eq!(Orientation::from_block_state("axis=z").unwrap().up(), Direction::PosZ);
+        assert_eq!(Orientation::from_block_state("rotation=3"), Err(FacingError::InvalidValue("rotation")));
+        assert_eq!(Orientation::from_block_state("facing=up,axis=y"), Err(FacingError::Conflicting));
+        assert_eq!(Orientation::from_block_state("lit"), Err(FacingError::Malformed));
+        assert_eq!(Orientation::from_block_state(""), Ok(Orientation::UNORIENTED));
+    }
+}
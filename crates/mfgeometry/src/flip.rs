@@ -265,9 +265,11 @@ impl Flip {
         self.0 as u8
     }
 
+    /// Applies `flip` on top of this [Flip]. Axes flipped by both are flipped back, so flipping
+    /// twice by the same [Flip] undoes it.
     #[inline]
     pub const fn flip(self, flip: Flip) -> Self {
-        Self(self.0.or(flip.0))
+        Self(self.0.xor(flip.0))
     }
     
     #[inline]
@@ -330,28 +332,6 @@ impl Flip {
     // pub const fn reverse_indices(self) -> bool {
     //     self.x() ^ self.y() ^ self.z()
     // }
-
-    // #[inline]
-    // pub fn to_scale(self) -> glam::Vec3 {
-    //     fn select_scale(flipped: bool) -> f32 {
-    //         if flipped {
-    //             -1.0
-    //         } else {
-    //             1.0
-    //         }
-    //     }
-    //     glam::vec3(
-    //         select_scale(self.x()),
-    //         select_scale(self.y()),
-    //         select_scale(self.z()),
-    //     )
-    // }
-
-    // #[inline]
-    // pub fn to_matrix(self) -> glam::Mat4 {
-    //     let scale = self.to_scale();
-    //     glam::Mat4::from_scale(scale)
-    // }
 }
 
 impl std::ops::BitOr<Flip> for Flip {
@@ -448,4 +428,27 @@ impl std::fmt::Display for Flip {
         }
         write!(f, ")")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_test() {
+        // Mirroring along an axis twice is no mirroring at all, which or-ing the axes got wrong.
+        assert_eq!(Flip::X.flip(Flip::X), Flip::NONE);
+        assert_eq!(Flip::XY.flip(Flip::YZ), Flip::XZ);
+        for bits in 0..8 {
+            let flip = Flip::from_u8(bits).unwrap();
+            assert_eq!(flip.flip(Flip::NONE), flip);
+            assert_eq!(flip.flip(flip), Flip::NONE);
+            assert_eq!(flip.flip(Flip::ALL), flip.invert());
+            let point = (1, 2, 3);
+            for other in 0..8 {
+                let other = Flip::from_u8(other).unwrap();
+                assert_eq!(flip.flip(other).flip_coord(point), other.flip_coord(flip.flip_coord(point)), "{flip} {other}");
+            }
+        }
+    }
+}
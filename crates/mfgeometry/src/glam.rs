@@ -0,0 +1,134 @@
+//! [glam] vectors, quaternions and matrices from the geometry types, behind the `glam` feature, so
+//! renderers can use orientations directly.
+//!
+//! The conversions agree with the coordinate methods: transforming a point with
+//! [Orientation::to_mat4] gives the same point as [Orientation::transform], and so on. An
+//! [Orientation] rotates and then flips, so its matrix is `Mat4::from_scale(flip.to_scale()) *
+//! Mat4::from_quat(rotation.to_quat())`. Flips mirror, which a quaternion can't, so only
+//! [Rotation]s have one.
+
+use ::glam::{IVec3, Mat3, Mat4, Quat, Vec3};
+
+use crate::{Direction, Flip, Orientation, Rotation};
+
+impl Direction {
+    /// Converts the [Direction] into a unit vector.
+    #[inline]
+    #[must_use]
+    pub const fn to_vec3(self) -> Vec3 {
+        let (x, y, z) = self.to_ftuple();
+        Vec3::new(x, y, z)
+    }
+
+    /// Converts the [Direction] into a unit integer vector.
+    #[inline]
+    #[must_use]
+    pub const fn to_ivec3(self) -> IVec3 {
+        let (x, y, z) = self.to_ituple();
+        IVec3::new(x, y, z)
+    }
+}
+
+impl From<Direction> for Vec3 {
+    #[inline]
+    fn from(value: Direction) -> Self {
+        value.to_vec3()
+    }
+}
+
+impl From<Direction> for IVec3 {
+    #[inline]
+    fn from(value: Direction) -> Self {
+        value.to_ivec3()
+    }
+}
+
+impl Flip {
+    /// The scale that flips like this [Flip]: `-1` on the flipped axes and `1` on the others.
+    #[inline]
+    #[must_use]
+    pub const fn to_scale(self) -> Vec3 {
+        const fn select_scale(flipped: bool) -> f32 {
+            if flipped { -1.0 } else { 1.0 }
+        }
+        Vec3::new(select_scale(self.x()), select_scale(self.y()), select_scale(self.z()))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_scale(self.to_scale())
+    }
+}
+
+impl Rotation {
+    /// The rotation as a matrix. Its columns are where the X, Y and Z axes end up.
+    #[inline]
+    #[must_use]
+    pub fn to_mat3(self) -> Mat3 {
+        Mat3::from_cols(
+            self.reface(Direction::PosX).to_vec3(),
+            self.reface(Direction::PosY).to_vec3(),
+            self.reface(Direction::PosZ).to_vec3(),
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_mat3(self.to_mat3())
+    }
+
+    /// The rotation as a unit quaternion.
+    #[inline]
+    #[must_use]
+    pub fn to_quat(self) -> Quat {
+        Quat::from_mat3(&self.to_mat3())
+    }
+}
+
+impl Orientation {
+    /// The orientation as a matrix, rotating and then flipping (like [Orientation::transform]).
+    /// When an odd number of axes are flipped, its determinant is `-1` and transformed faces have
+    /// their winding reversed.
+    #[inline]
+    #[must_use]
+    pub fn to_mat4(self) -> Mat4 {
+        let scale = self.flip().to_scale();
+        let rotation = self.rotation();
+        Mat4::from_mat3(Mat3::from_cols(
+            rotation.reface(Direction::PosX).to_vec3() * scale,
+            rotation.reface(Direction::PosY).to_vec3() * scale,
+            rotation.reface(Direction::PosZ).to_vec3() * scale,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glam_test() {
+        let points = [(1, 2, 3), (-4, 0, 7), (0, -1, 0)];
+        for direction in Direction::iter() {
+            assert_eq!(direction.to_vec3().as_ivec3(), direction.to_ivec3());
+            assert_eq!(direction.to_vec3().length(), 1.0);
+        }
+        for orientation in Orientation::UNORIENTED.iter() {
+            let (rotation, flip) = (orientation.rotation(), orientation.flip());
+            let matrix = orientation.to_mat4();
+            assert_eq!(matrix, flip.to_mat4() * rotation.to_mat4(), "{orientation}");
+            assert!(rotation.to_quat().is_normalized());
+            assert_eq!(matrix.determinant(), if flip.x() ^ flip.y() ^ flip.z() { -1.0 } else { 1.0 });
+            for point in points {
+                let expected: (i32, i32, i32) = orientation.transform(point);
+                let vector = IVec3::from(point).as_vec3();
+                assert_eq!(matrix.transform_point3(vector).round().as_ivec3(), IVec3::from(expected), "{orientation}");
+                let rotated: (i32, i32, i32) = rotation.rotate_coord(point);
+                assert_eq!((rotation.to_quat() * vector).round().as_ivec3(), IVec3::from(rotated), "{rotation}");
+                assert_eq!(flip.to_scale() * vector, IVec3::from(flip.flip_coord(point)).as_vec3());
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! [DeterministicHash] for the geometry types.
+//!
+//! Each type hashes as the single byte of its packed representation, so hashes match those of
+//! call sites that used to hash the `u8` directly.
+
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
+
+use crate::{cardinal::Cardinal, Axis, Direction, FaceAngle, Flip, Orientation, Rotation};
+
+macro_rules! impl_hash_u8 {
+    ($($type:ty => |$value:ident| $bits:expr),+ $(,)?) => {
+        $(
+            impl DeterministicHash for $type {
+                #[inline]
+                fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+                    let $value = *self;
+                    hasher.write_u8($bits);
+                }
+            }
+        )+
+    };
+}
+
+impl_hash_u8!(
+    Axis => |axis| axis as u8,
+    Cardinal => |cardinal| cardinal.discriminant(),
+    Direction => |direction| direction.discriminant(),
+    FaceAngle => |face_angle| face_angle.as_u8(),
+    Flip => |flip| flip.as_u8(),
+    Rotation => |rotation| rotation.as_u8(),
+    Orientation => |orientation| orientation.as_u8(),
+);
+
+#[cfg(test)]
+mod tests {
+    use mfhash::HashSeed;
+
+    use super::*;
+
+    #[test]
+    fn deterministic_hash_impls_test() {
+        let seed = HashSeed::derived("mfgeometry::hash_test");
+        let orientation = Orientation::UNORIENTED.rotate_y(1);
+        assert_eq!(seed.hash_u64(orientation), seed.hash_u64(orientation.as_u8()));
+        assert_eq!(seed.hash_u64(Direction::NegX), seed.hash_u64(Direction::NegX.discriminant()));
+        assert_ne!(seed.hash_u64((Axis::X, Direction::PosY)), seed.hash_u64((Axis::Y, Direction::PosY)));
+    }
+}
@@ -13,21 +13,39 @@ Changelog:
 [Nothing here yet]
 */
 
+pub mod aabb;
+pub mod adjacency;
 pub mod axis;
 pub mod cardinal;
+#[cfg(feature = "cereal")]
+mod cereal;
+pub mod convention;
 pub mod direction;
+pub mod face_angle;
+pub mod facing;
 pub mod faces;
 pub mod flip;
+#[cfg(feature = "glam")]
+mod glam;
+mod hash;
+pub mod marker;
+pub mod moves;
 pub mod orient_table;
 pub mod orientation_enum;
 pub mod orientation;
+pub mod per_face;
 pub mod polarity;
 pub mod rotation;
+mod rotation_table;
 
+pub use aabb::{Aabb, FAabb, IAabb};
+pub use adjacency::FaceAdjacency;
 pub use axis::Axis;
 pub use direction::Direction;
+pub use face_angle::FaceAngle;
 pub use flip::Flip;
-pub use orientation::Orientation;
+pub use orientation::{Handedness, Orientation};
+pub use per_face::PerFace;
 pub use rotation::Rotation;
 use mfcore::lowlevel::CachePadded;
 
@@ -0,0 +1,192 @@
+//! Compile-time orientations for code that wants a transform monomorphized per orientation.
+//!
+//! Table-driven functions like [Orientation::reface] are cheap, but in hot loops (meshing, bulk
+//! voxel transforms) it can pay to let the compiler constant-fold the table lookups away entirely.
+//! Code generic over an [OrientationMarker] does exactly that, and [dispatch] picks the right
+//! instantiation for an orientation only known at runtime:
+//! ```
+//! use mfgeometry::{Direction, Orientation, marker::{dispatch, OrientationMarker, OrientationVisitor}};
+//! struct Forward;
+//! impl OrientationVisitor for Forward {
+//!     type Output = Direction;
+//!     fn visit<O: OrientationMarker>(self) -> Direction {
+//!         O::reface(Direction::FORWARD)
+//!     }
+//! }
+//! let orientation = Orientation::ROTATE_Y;
+//! assert_eq!(dispatch(orientation, Forward), orientation.forward());
+//! ```
+
+use mfcore::const_fmt::ConstStr;
+
+use crate::{Direction, Orientation};
+
+/// A type standing in for a single [Direction].
+pub trait DirectionMarker: Copy + Default + 'static {
+    const DIRECTION: Direction;
+}
+
+macro_rules! direction_markers {
+    ($(
+        $marker:ident => $direction:ident
+    ),*$(,)?) => {
+        $(
+            #[doc = concat!("Marker for [Direction::", stringify!($direction), "].")]
+            #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct $marker;
+
+            impl DirectionMarker for $marker {
+                const DIRECTION: Direction = Direction::$direction;
+            }
+        )*
+    };
+}
+
+direction_markers!(
+    NegXMarker => NegX,
+    NegYMarker => NegY,
+    NegZMarker => NegZ,
+    PosXMarker => PosX,
+    PosYMarker => PosY,
+    PosZMarker => PosZ,
+);
+
+/// A type standing in for a single [Orientation].
+///
+/// The provided methods are the const equivalents of the [Orientation] methods of the same name,
+/// evaluated on [OrientationMarker::ORIENTATION] so that they fold to constants.
+pub trait OrientationMarker: Copy + Default + 'static {
+    const ORIENTATION: Orientation;
+
+    #[inline(always)]
+    fn reface(face: Direction) -> Direction {
+        Self::ORIENTATION.reface(face)
+    }
+
+    #[inline(always)]
+    fn source_face(face: Direction) -> Direction {
+        Self::ORIENTATION.source_face(face)
+    }
+
+    #[inline(always)]
+    fn transform_i32(point: (i32, i32, i32)) -> (i32, i32, i32) {
+        let rotated = Self::ORIENTATION.rotation().rotate_coord_i32(point);
+        Self::ORIENTATION.flip().flip_coord_i32(rotated)
+    }
+
+    #[inline(always)]
+    fn map_face_coord_i32(face: Direction, uv: (i32, i32)) -> (i32, i32) {
+        Self::ORIENTATION.map_face_coord_i32(face, uv)
+    }
+}
+
+/// The orientation with the packed value `ORIENT` (see [Orientation::as_u8]).
+///
+/// Using an `ORIENT` that isn't a valid orientation fails to compile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Orient<const ORIENT: u8>;
+
+impl<const ORIENT: u8> OrientationMarker for Orient<ORIENT> {
+    const ORIENTATION: Orientation = match Orientation::from_u8(ORIENT) {
+        Some(orientation) => orientation,
+        None => ConstStr::<64>::new().str("Orient<").u64(ORIENT as u64).str("> is not a valid orientation").panic(),
+    };
+}
+
+/// A function generic over an orientation, for [dispatch].
+pub trait OrientationVisitor {
+    type Output;
+
+    fn visit<O: OrientationMarker>(self) -> Self::Output;
+}
+
+/// A function generic over a direction, for [dispatch_direction].
+pub trait DirectionVisitor {
+    type Output;
+
+    fn visit<D: DirectionMarker>(self) -> Self::Output;
+}
+
+macro_rules! orientation_dispatch {
+    ($orientation:expr, $visitor:expr; $($value:literal),*$(,)?) => {
+        match $orientation.as_u8() {
+            $(
+                $value => $visitor.visit::<Orient<$value>>(),
+            )*
+            // SAFETY: Every valid orientation is matched above.
+            _ => unsafe { ::core::hint::unreachable_unchecked() },
+        }
+    };
+}
+
+/// Calls `visitor` with the [Orient] marker of `orientation`.
+#[inline]
+pub fn dispatch<V: OrientationVisitor>(orientation: Orientation, visitor: V) -> V::Output {
+    orientation_dispatch!(orientation, visitor;
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+        16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+        32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+        48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63,
+        64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79,
+        80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95,
+        96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111,
+        112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127,
+        128, 129, 130, 131, 132, 133, 134, 135, 136, 137, 138, 139, 140, 141, 142, 143,
+        144, 145, 146, 147, 148, 149, 150, 151, 152, 153, 154, 155, 156, 157, 158, 159,
+        160, 161, 162, 163, 164, 165, 166, 167, 168, 169, 170, 171, 172, 173, 174, 175,
+        176, 177, 178, 179, 180, 181, 182, 183, 184, 185, 186, 187, 188, 189, 190, 191,
+    )
+}
+
+/// Calls `visitor` with the marker of `direction`.
+#[inline]
+pub fn dispatch_direction<V: DirectionVisitor>(direction: Direction, visitor: V) -> V::Output {
+    match direction {
+        Direction::NegX => visitor.visit::<NegXMarker>(),
+        Direction::NegY => visitor.visit::<NegYMarker>(),
+        Direction::NegZ => visitor.visit::<NegZMarker>(),
+        Direction::PosX => visitor.visit::<PosXMarker>(),
+        Direction::PosY => visitor.visit::<PosYMarker>(),
+        Direction::PosZ => visitor.visit::<PosZMarker>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Transform((i32, i32, i32));
+
+    impl OrientationVisitor for Transform {
+        type Output = ((i32, i32, i32), Orientation);
+
+        fn visit<O: OrientationMarker>(self) -> Self::Output {
+            (O::transform_i32(self.0), O::ORIENTATION)
+        }
+    }
+
+    struct Invert;
+
+    impl DirectionVisitor for Invert {
+        type Output = Direction;
+
+        fn visit<D: DirectionMarker>(self) -> Direction {
+            D::DIRECTION.invert()
+        }
+    }
+
+    #[test]
+    fn dispatch_test() {
+        for value in 0..Orientation::TOTAL_ORIENTATION_COUNT {
+            let orientation = Orientation::from_u8_wrapping(value);
+            let point = (1, -2, 3);
+            let (transformed, dispatched) = dispatch(orientation, Transform(point));
+            assert_eq!(dispatched, orientation);
+            assert_eq!(transformed, orientation.transform(point));
+        }
+        for direction in Direction::iter() {
+            assert_eq!(dispatch_direction(direction, Invert), direction.invert());
+        }
+        assert_eq!(<Orient<0>>::ORIENTATION, Orientation::UNORIENTED);
+    }
+}
@@ -0,0 +1,123 @@
+//! Arithmetic on sequences of unit moves, for turtle path compression and structure cursors.
+//!
+//! A move is one step in a [Direction]. Offsets are `(x, y, z)` tuples, the same as
+//! [Direction::to_ituple].
+
+use crate::Direction;
+
+/// `position` moved one step in `direction`.
+#[inline]
+#[must_use]
+pub const fn neighbor(position: (i32, i32, i32), direction: Direction) -> (i32, i32, i32) {
+    step(position, direction, 1)
+}
+
+/// `position` moved `distance` steps in `direction` (backwards if `distance` is negative).
+#[inline]
+#[must_use]
+pub const fn step(position: (i32, i32, i32), direction: Direction, distance: i32) -> (i32, i32, i32) {
+    let (x, y, z) = direction.to_ituple();
+    (position.0 + x * distance, position.1 + y * distance, position.2 + z * distance)
+}
+
+/// The sum of the moves.
+#[must_use]
+pub fn net_offset<I: IntoIterator<Item = Direction>>(moves: I) -> (i32, i32, i32) {
+    moves.into_iter().fold((0, 0, 0), neighbor)
+}
+
+/// How far `offset` goes in `direction`: positive along it, negative against it.
+#[inline]
+#[must_use]
+pub const fn signed_distance(offset: (i32, i32, i32), direction: Direction) -> i32 {
+    let (x, y, z) = direction.to_ituple();
+    offset.0 * x + offset.1 * y + offset.2 * z
+}
+
+/// The number of moves it takes to cover `offset`.
+#[inline]
+#[must_use]
+pub const fn manhattan(offset: (i32, i32, i32)) -> u32 {
+    offset.0.unsigned_abs() + offset.1.unsigned_abs() + offset.2.unsigned_abs()
+}
+
+/// The largest component of `offset`, ignoring sign.
+#[inline]
+#[must_use]
+pub const fn chebyshev(offset: (i32, i32, i32)) -> u32 {
+    let (x, y, z) = (offset.0.unsigned_abs(), offset.1.unsigned_abs(), offset.2.unsigned_abs());
+    let xy = if x > y { x } else { y };
+    if xy > z { xy } else { z }
+}
+
+/// The Manhattan distance between the start and end of `moves`.
+#[inline]
+#[must_use]
+pub fn manhattan_distance<I: IntoIterator<Item = Direction>>(moves: I) -> u32 {
+    manhattan(net_offset(moves))
+}
+
+/// The Chebyshev distance between the start and end of `moves`.
+#[inline]
+#[must_use]
+pub fn chebyshev_distance<I: IntoIterator<Item = Direction>>(moves: I) -> u32 {
+    chebyshev(net_offset(moves))
+}
+
+/// Removes every move that's immediately undone, repeatedly, so that `[Up, Left, Right, Down, Up]`
+/// becomes `[Up]`. The route through the remaining positions is unchanged, so this is safe for paths
+/// that must avoid obstacles.
+#[must_use]
+pub fn cancel_backtracks<I: IntoIterator<Item = Direction>>(moves: I) -> Vec<Direction> {
+    let mut kept: Vec<Direction> = Vec::new();
+    for direction in moves {
+        if kept.last() == Some(&direction.invert()) {
+            kept.pop();
+        } else {
+            kept.push(direction);
+        }
+    }
+    kept
+}
+
+/// The shortest sequence of moves covering `offset`: the X moves, then Y, then Z. Every opposite
+/// pair of moves in a sequence cancels, so `shortest_moves(net_offset(moves))` is the fully simplified
+/// form of `moves` (when its route doesn't matter).
+pub fn shortest_moves(offset: (i32, i32, i32)) -> impl Iterator<Item = Direction> {
+    let axis = |distance: i32, negative: Direction, positive: Direction| {
+        let direction = if distance < 0 { negative } else { positive };
+        std::iter::repeat_n(direction, distance.unsigned_abs() as usize)
+    };
+    axis(offset.0, Direction::NegX, Direction::PosX)
+        .chain(axis(offset.1, Direction::NegY, Direction::PosY))
+        .chain(axis(offset.2, Direction::NegZ, Direction::PosZ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Direction::*;
+
+    #[test]
+    fn moves_test() {
+        let moves = [PosY, NegX, PosX, NegY, PosY, PosZ, PosZ, NegX, NegZ];
+        let offset = net_offset(moves);
+        assert_eq!(offset, (-1, 1, 1));
+        assert_eq!(manhattan_distance(moves), 3);
+        assert_eq!(chebyshev_distance(moves), 1);
+        assert_eq!(chebyshev((3, -7, 2)), 7);
+        assert_eq!(signed_distance(offset, Direction::FORWARD), -1);
+        assert_eq!(signed_distance((4, 0, 0), NegX), -4);
+        assert_eq!(step((1, 1, 1), PosY, -3), (1, -2, 1));
+        assert_eq!(neighbor((0, 0, 0), NegZ), (0, 0, -1));
+
+        let cancelled = cancel_backtracks(moves);
+        assert_eq!(cancelled, [PosY, PosZ, PosZ, NegX, NegZ]);
+        assert_eq!(net_offset(cancelled.iter().copied()), offset);
+        let shortest = shortest_moves(offset).collect::<Vec<_>>();
+        assert_eq!(shortest, [NegX, PosY, PosZ]);
+        for direction in Direction::ALL {
+            assert_eq!(net_offset(shortest_moves(direction.to_ituple())), direction.to_ituple());
+        }
+    }
+}
@@ -417,4 +417,65 @@ pub(crate) const SOURCE_FACE_COORD_TABLE: CoordMapTable = {
         flip_i += 1;
     }
     CoordMapTable::new(arr)
-};
\ No newline at end of file
+};
+// ADJACENCY_MAP_TABLE and ADJACENCY_SOURCE_TABLE map the in-plane neighbours of a face (see
+// [FaceAdjacency](crate::FaceAdjacency)). Unlike the UV tables above, these follow the faces
+// exactly through reflections: a neighbour on the right of a mirrored face is on its left.
+const fn adjacency_table(source: bool) -> CoordMapTable {
+    // Which face axis `refaced` (a source axis, turned) is.
+    const fn face_axis(face: Direction, refaced: Direction) -> AxisMap {
+        match refaced as u8 {
+            dir if dir == face.right() as u8 => AxisMap::PosX,
+            dir if dir == face.left() as u8 => AxisMap::NegX,
+            dir if dir == face.up() as u8 => AxisMap::PosY,
+            _ => AxisMap::NegY,
+        }
+    }
+    // Which source axis (`right` and `up`, turned) was turned to `target`.
+    const fn source_axis(right: Direction, up: Direction, target: Direction) -> AxisMap {
+        match target as u8 {
+            dir if dir == right as u8 => AxisMap::PosX,
+            dir if dir == right.invert() as u8 => AxisMap::NegX,
+            dir if dir == up as u8 => AxisMap::PosY,
+            _ => AxisMap::NegY,
+        }
+    }
+    const fn adjacency_naive(orientation: Orientation, face: Direction, source: bool) -> CoordMap {
+        let source_face = orientation.source_face(face);
+        let rsrc_up = orientation.reface(source_face.up());
+        let rsrc_right = orientation.reface(source_face.right());
+        if source {
+            // Face UV to source UV.
+            CoordMap::new(face_axis(face, rsrc_right), face_axis(face, rsrc_up))
+        } else {
+            // Source UV to face UV.
+            CoordMap::new(source_axis(rsrc_right, rsrc_up, face.right()), source_axis(rsrc_right, rsrc_up, face.up()))
+        }
+    }
+    let mut arr = CacheAlignedArray::new([CoordMap::DEFAULT; 1152]);
+    let mut index = 0usize;
+    let mut flip_i = 0u8;
+    // Same order as the UV tables, so that CoordMapTable::get works on these too.
+    while flip_i < 8 {
+        let mut rot_i = 0u8;
+        while rot_i < 24 {
+            let mut dir_i = 0usize;
+            while dir_i < Direction::INDEX_ORDER.len() {
+                let orientation = Orientation::new(
+                    unsafe { Rotation::from_u8_unchecked(rot_i) },
+                    unsafe { Flip::from_u8_unchecked(flip_i) },
+                );
+                let face = Direction::INDEX_ORDER[dir_i];
+                arr.array.value[index] = adjacency_naive(orientation, face, source);
+                index += 1;
+                dir_i += 1;
+            }
+            rot_i += 1;
+        }
+        flip_i += 1;
+    }
+    CoordMapTable::new(arr)
+}
+
+pub(crate) const ADJACENCY_MAP_TABLE: CoordMapTable = adjacency_table(false);
+pub(crate) const ADJACENCY_SOURCE_TABLE: CoordMapTable = adjacency_table(true);
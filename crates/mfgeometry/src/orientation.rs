@@ -1,5 +1,5 @@
 use crate::{
-    direction::Direction, flip::Flip, orient_table, orientation_enum::Orient, pack_flip_and_rotation, polarity::Pol, rotation::Rotation, wrap_angle
+    adjacency::FaceAdjacency, direction::Direction, flip::Flip, orient_table, orientation_enum::Orient, pack_flip_and_rotation, polarity::Pol, rotation::Rotation, wrap_angle
 };
 use mfcore::lowlevel::CachePadded;
 use paste::paste;
@@ -30,6 +30,28 @@ impl DeconstructedOrientation {
     }
 }
 
+/// Whether an [Orientation] preserves or mirrors the winding of a model.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Handedness {
+    /// A pure rotation (or an even number of flips, which is also a rotation).
+    #[default]
+    Right,
+    /// A reflection. Meshes need a mirrored variant (or reversed winding).
+    Left,
+}
+
+impl Handedness {
+    #[inline]
+    pub const fn is_left(self) -> bool {
+        matches!(self, Handedness::Left)
+    }
+
+    #[inline]
+    pub const fn is_right(self) -> bool {
+        matches!(self, Handedness::Right)
+    }
+}
+
 // TODO: Switch to using an enum internally to take advantage
 // ::::: of niche optimization.
 // Field     : Bit Range
@@ -103,6 +125,8 @@ macro_rules! transform_impls {
     )*$(,)?) => {
         $(
             paste!{
+                /// The const version of [Orientation::transform] for this type.
+                #[inline]
                 pub const fn [<transform_ $type>](self, point: ($type, $type, $type)) -> ($type, $type, $type) {
                     let rotated = self.rotation().[<rotate_coord_ $type>](point);
                     self.flip().[<flip_coord_ $type>](rotated)
@@ -324,6 +348,66 @@ impl Orientation {
         ]
     }
 
+    /// Returns `true` if the orientation mirrors (an odd number of flips).
+    #[inline(always)]
+    pub const fn is_reflection(self) -> bool {
+        self.flip().bits_xor()
+    }
+
+    #[inline(always)]
+    pub const fn handedness(self) -> Handedness {
+        if self.is_reflection() {
+            Handedness::Left
+        } else {
+            Handedness::Right
+        }
+    }
+
+    /// The rotation-only orientation that maps the most faces to the same place as `self`.
+    /// Orientations that aren't reflections return an equivalent orientation with no flip.
+    /// Ties are broken in favor of [Self::rotation], then the lowest [Rotation].
+    #[inline]
+    pub const fn nearest_pure_rotation(self) -> Self {
+        const NEAREST_TABLE: CachePadded<[Orientation; 192]> = {
+            const fn agreement(lhs: Orientation, rhs: Orientation) -> u8 {
+                let mut count = 0;
+                let mut i = 0;
+                while i < 6 {
+                    let face = Direction::ALL[i];
+                    if lhs.reface(face) as u8 == rhs.reface(face) as u8 {
+                        count += 1;
+                    }
+                    i += 1;
+                }
+                count
+            }
+            const fn nearest_slow(orient: Orientation) -> Orientation {
+                let mut best = orient.rotation().orientation();
+                let mut best_agreement = agreement(orient, best);
+                let mut rotation = 0u8;
+                while rotation < 24 {
+                    let candidate = unsafe { Rotation::from_u8_unchecked(rotation) }.orientation();
+                    let candidate_agreement = agreement(orient, candidate);
+                    if candidate_agreement > best_agreement {
+                        best = candidate;
+                        best_agreement = candidate_agreement;
+                    }
+                    rotation += 1;
+                }
+                best
+            }
+            let mut table = CachePadded::new([Orientation::UNORIENTED; 192]);
+            let mut orient_i = 0u8;
+            while orient_i < 192 {
+                let orient = unsafe { Orientation::from_u8_unchecked(orient_i) };
+                table.value[orient_i as usize] = nearest_slow(orient);
+                orient_i += 1;
+            }
+            table
+        };
+        NEAREST_TABLE.value[self.0 as usize]
+    }
+
     #[inline(always)]
     pub const fn flip(self) -> Flip {
         unsafe { Flip::from_u8_unchecked(self.0 as u8 & 0b111) }
@@ -415,6 +499,36 @@ impl Orientation {
         RotationFirstOrientationIterator::START
     }
 
+    /// The 24 orientations without a [Flip], in [Rotation] order.
+    #[inline]
+    pub fn iter_rotations_only() -> impl ExactSizeIterator<Item = Self> + DoubleEndedIterator {
+        // With no flip, the packed bits are just the rotation shifted past the flip bits.
+        (0..24u8).map(|rotation| unsafe { Self::from_u8_unchecked(rotation << 3) })
+    }
+
+    /// The 8 orientations without a [Rotation], in [Flip] order.
+    #[inline]
+    pub fn iter_flips_only() -> impl ExactSizeIterator<Item = Self> + DoubleEndedIterator {
+        (0..8u8).map(|flip| Self::new(Self::UNORIENTED.rotation(), unsafe { Flip::from_u8_unchecked(flip) }))
+    }
+
+    /// The 32 orientations whose [up](Self::up) is `up`, in [Flip] order, then by angle.
+    #[inline]
+    pub fn iter_with_up(up: Direction) -> impl ExactSizeIterator<Item = Self> + DoubleEndedIterator {
+        // The flip is applied after the rotation, so the rotation has to point up at the face that
+        // the flip moves to `up`.
+        (0..32u8).map(move |index| {
+            let flip = unsafe { Flip::from_u8_unchecked(index >> 2) };
+            Self::new(Rotation::new(up.flip(flip), (index & 3) as i32), flip)
+        })
+    }
+
+    /// Every orientation that satisfies `predicate`, in packed order (the order of [Self::iter]).
+    #[inline]
+    pub fn iter_satisfying<F: FnMut(&Self) -> bool>(predicate: F) -> impl DoubleEndedIterator<Item = Self> {
+        (0..Self::TOTAL_ORIENTATION_COUNT).map(|i| unsafe { Self::from_u8_unchecked(i) }).filter(predicate)
+    }
+
     // verified (2025-12-30)
     /// `reface` can be used to determine where a face will end up after orientation.
     /// First rotates and then flips the face.
@@ -432,6 +546,19 @@ impl Orientation {
         self.rotation().source_face(flipped)
     }
 
+    /// Where the connected edges `mask` of the unoriented face end up on `face`.
+    #[inline]
+    pub const fn map_face_adjacency(self, face: Direction, mask: FaceAdjacency) -> FaceAdjacency {
+        mask.map(orient_table::ADJACENCY_MAP_TABLE.get(self.rotation(), self.flip(), face))
+    }
+
+    /// Which connected edges of the unoriented face are at the edges `mask` of `face`. This turns
+    /// neighbours found in the world into a block's connected texture key.
+    #[inline]
+    pub const fn source_face_adjacency(self, face: Direction, mask: FaceAdjacency) -> FaceAdjacency {
+        mask.map(orient_table::ADJACENCY_SOURCE_TABLE.get(self.rotation(), self.flip(), face))
+    }
+
     // verified (2025-12-30)
     /// Gets the direction that [Direction::PosY] is pointing towards.
     #[inline]
@@ -520,6 +647,44 @@ impl Orientation {
         f64,
     );
 
+    transform_impls!(i8 i16 i32 i64 i128 isize f32 f64);
+
+    /// Transforms every point of a small fixed model (such as the corners of a block model's
+    /// boxes) at compile time. Like [Self::transform], the points are rotated and then flipped, so
+    /// they should be centered around `(0, 0, 0)`. `i8::MIN` coordinates overflow when negated.
+    ///
+    /// Reflections ([Self::is_reflection]) reverse the winding of the model's faces.
+    /// ```
+    /// # use mfgeometry::{Direction, Orientation, Rotation, Flip};
+    /// const ARROW: [(i8, i8, i8); 2] = [(0, 0, 0), (0, 4, 0)];
+    /// const EAST: [(i8, i8, i8); 2] = Orientation::new(Rotation::new(Direction::PosX, 0), Flip::NONE).transform_points(ARROW);
+    /// assert_eq!(EAST, [(0, 0, 0), (4, 0, 0)]);
+    /// ```
+    #[must_use]
+    pub const fn transform_points<const N: usize>(self, points: [(i8, i8, i8); N]) -> [(i8, i8, i8); N] {
+        let mut transformed = points;
+        let mut index = 0;
+        while index < N {
+            transformed[index] = self.transform_i8(points[index]);
+            index += 1;
+        }
+        transformed
+    }
+
+    /// [Self::transform_points] for every orientation, indexed by [Self::as_u8], so that every
+    /// variant of a block model can be baked into a const table.
+    #[must_use]
+    pub const fn transform_points_table<const N: usize>(points: [(i8, i8, i8); N]) -> [[(i8, i8, i8); N]; 192] {
+        let mut table = [points; 192];
+        let mut orient_int = 0u8;
+        while orient_int < Self::TOTAL_ORIENTATION_COUNT {
+            let orientation = unsafe { Self::from_u8_unchecked(orient_int) };
+            table[orient_int as usize] = orientation.transform_points(points);
+            orient_int += 1;
+        }
+        table
+    }
+
     /// Apply an orientation to an orientation.
     pub const fn reorient(self, orientation: Orientation) -> Self {
         let up = self.up();
@@ -570,6 +735,31 @@ impl Orientation {
         // Orientation::UNORIENTED.deorient(self)
         Self::INVERT_TABLE.value[self.0 as usize]
     }
+
+    /// The identity of composition ([Orientation::UNORIENTED]): `orientation * identity ==
+    /// orientation`.
+    #[inline]
+    #[must_use]
+    pub const fn identity() -> Self {
+        Self::UNORIENTED
+    }
+
+    /// Composes the [Orientation] with itself `exponent` times, or its [inverse](Self::invert) if
+    /// `exponent` is negative. `pow(0)` is the [identity](Self::identity).
+    #[must_use]
+    pub const fn pow(self, exponent: i32) -> Self {
+        let mut base = if exponent < 0 { self.invert() } else { self };
+        let mut exponent = exponent.unsigned_abs();
+        let mut result = Self::UNORIENTED;
+        while exponent != 0 {
+            if exponent & 1 == 1 {
+                result = base.reorient(result);
+            }
+            base = base.reorient(base);
+            exponent >>= 1;
+        }
+        result
+    }
     
     /// Flip the [Orientation] along the `X` axis.
     #[inline]
@@ -667,28 +857,6 @@ impl Orientation {
         let orient = Self::corner_orientation(x, y, z, angle);
         self.reorient_local(orient)
     }
-    
-    // #[inline]
-    // pub fn to_matrix(self) -> glam::Mat4 {
-    //     let flip = self.flip();
-    //     let rotation = self.rotation();
-    //     let scale = flip.to_scale();
-    //     // scale.x = -scale.x;
-    //     let up = rotation.reface(Direction::PosY).to_vec3();
-    //     let forward = rotation.reface(Direction::PosZ).to_vec3();
-    //     let right = self.reface(Direction::PosX).to_vec3();
-
-    //     // let right = glam::vec3(right.x * scale.x, right.y, right.z);
-    //     // let up = glam::vec3(up.x, up.y * scale.y, up.z);
-    //     // let forward = glam::vec3(forward.x, forward.y, forward.z * scale.z);
-
-    //     glam::Mat4::from_cols(
-    //         (right * scale).extend(0.0),
-    //         (up * scale).extend(0.0),
-    //         (forward * scale).extend(0.0),
-    //         glam::Vec3::ZERO.extend(1.0),
-    //     )
-    // }
 }
 
 impl Into<u8> for Orientation {
@@ -712,6 +880,41 @@ impl From<Flip> for Orientation {
     }
 }
 
+/// Composition: `(a * b).transform(p) == a.transform(b.transform(p))`, so `a * b` is `b` and then
+/// `a`, which is `b.reorient(a)`. Like matrices, `a * b` isn't `b * a` in general.
+impl std::ops::Mul<Orientation> for Orientation {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Orientation) -> Self::Output {
+        rhs.reorient(self)
+    }
+}
+
+impl std::ops::MulAssign<Orientation> for Orientation {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Orientation) {
+        *self = *self * rhs;
+    }
+}
+
+/// Inverse composition: `a / b == a * b.invert()`, so `(a * b) / b == a`.
+impl std::ops::Div<Orientation> for Orientation {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Orientation) -> Self::Output {
+        rhs.invert().reorient(self)
+    }
+}
+
+impl std::ops::DivAssign<Orientation> for Orientation {
+    #[inline]
+    fn div_assign(&mut self, rhs: Orientation) {
+        *self = *self / rhs;
+    }
+}
+
 impl std::fmt::Display for Orientation {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -719,31 +922,6 @@ impl std::fmt::Display for Orientation {
     }
 }
 
-// #[cfg(test)]
-// mod testing_sandbox {
-//     // TODO: Remove this sandbox when it is no longer in use.
-//     use super::*;
-//     #[test]
-//     fn sandbox() {
-//         let orientation = Orientation::new(Rotation::new(Direction::PosZ, 1), Flip::XYZ);
-//         // let orientation = Orientation::UNORIENTED;
-//         println!("Forward: {}", orientation.rotation().forward());
-//         let mat = orientation.to_matrix();
-//         let pos_y = mat.transform_point3(glam::Vec3::Y);
-//         let neg_z = mat.transform_point3(glam::Vec3::NEG_Z);
-//         println!("PosY: {pos_y:?}");
-//         println!("NegZ: {neg_z:?}");
-//         let flip = Flip::XYZ;
-//         let mat = flip.to_matrix();
-//         let posx = mat.transform_point3(glam::Vec3::X);
-//         let posy = mat.transform_point3(glam::Vec3::Y);
-//         let posz = mat.transform_point3(glam::Vec3::Z);
-//         println!("!PosX: {posx:?}");
-//         println!("!PosY: {posy:?}");
-//         println!("!PosZ: {posz:?}");
-//     }
-// }
-
 // verified (2025-12-29)
 /// Used to iterate over each [Orientation] in the order where [Rotation] cycles before [Flip].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -820,4 +998,99 @@ mod tests {
             assert_eq!(lhs, rhs);
         }
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn transform_points_test() {
+        const MODEL: [(i8, i8, i8); 4] = [(-8, -8, -8), (8, 0, -8), (3, 5, 7), (0, 0, 0)];
+        const TABLE: [[(i8, i8, i8); 4]; 192] = Orientation::transform_points_table(MODEL);
+        for orientation in Orientation::iter_satisfying(|_| true) {
+            let expected = MODEL.map(|point| orientation.transform(point));
+            assert_eq!(TABLE[orientation.as_u8() as usize], expected, "{orientation}");
+            assert_eq!(orientation.transform_points(MODEL), expected);
+        }
+        assert_eq!(TABLE[Orientation::UNORIENTED.as_u8() as usize], MODEL);
+    }
+
+    #[test]
+    fn constrained_iter_test() {
+        let all = || Orientation::iter_satisfying(|_| true);
+        assert_eq!(all().count(), 192);
+        assert!(all().eq(Orientation::UNORIENTED.iter()));
+
+        let rotations = Orientation::iter_rotations_only().collect::<Vec<_>>();
+        assert_eq!(rotations, Orientation::iter_satisfying(|o| o.flip() == Flip::NONE).collect::<Vec<_>>());
+        assert!(rotations.iter().all(|o| o.handedness().is_right()));
+
+        let flips = Orientation::iter_flips_only().collect::<Vec<_>>();
+        assert_eq!(flips, Orientation::iter_satisfying(|o| o.rotation() == Orientation::UNORIENTED.rotation()).collect::<Vec<_>>());
+
+        for up in Direction::iter() {
+            let mut with_up = Orientation::iter_with_up(up).collect::<Vec<_>>();
+            assert!(with_up.iter().all(|o| o.up() == up), "{up:?}");
+            with_up.sort_by_key(|o| o.as_u8());
+            with_up.dedup();
+            assert_eq!(with_up, Orientation::iter_satisfying(|o| o.up() == up).collect::<Vec<_>>(), "{up:?}");
+        }
+    }
+
+    #[test]
+    fn handedness_test() {
+        for orientation in (0..192).map(|i| Orientation::from_u8(i).unwrap()) {
+            let nearest = orientation.nearest_pure_rotation();
+            assert_eq!(nearest.flip(), Flip::NONE);
+            let agreement = Direction::iter().filter(|&face| orientation.reface(face) == nearest.reface(face)).count();
+            if orientation.is_reflection() {
+                assert_eq!(orientation.handedness(), Handedness::Left);
+                // A mirrored cube can never be rotated to match on every face.
+                assert!(agreement < 6, "{orientation}");
+                let best = (0..24)
+                    .map(|i| Orientation::from(Rotation::from_u8_wrapping(i)))
+                    .map(|rotation| Direction::iter().filter(|&face| orientation.reface(face) == rotation.reface(face)).count())
+                    .max()
+                    .unwrap();
+                assert_eq!(agreement, best, "{orientation}");
+            } else {
+                assert_eq!(orientation.handedness(), Handedness::Right);
+                assert_eq!(agreement, 6, "{orientation}");
+            }
+        }
+        // A single flip keeps the rotation.
+        let orientation = Orientation::new(Rotation::new(Direction::PosZ, 1), Flip::X);
+        assert_eq!(orientation.nearest_pure_rotation(), Rotation::new(Direction::PosZ, 1).orientation());
+    }
+
+    #[test]
+    fn composition_group_test() {
+        let all = Orientation::UNORIENTED.iter().collect::<Vec<_>>();
+        let points = [(1, 2, 3), (-5, 0, 4)];
+        // Every triple is checked by the exhaustive test; a generating set is enough here.
+        let generators = [Orientation::ROTATE_X, Orientation::ROTATE_Y, Orientation::ROTATE_Z, Orientation::new(Rotation::UNROTATED, Flip::X)];
+        for &a in &all {
+            assert_eq!(a * Orientation::identity(), a);
+            assert_eq!(Orientation::identity() * a, a);
+            assert_eq!(a * a.invert(), Orientation::identity(), "{a}");
+            assert_eq!(a.invert() * a, Orientation::identity(), "{a}");
+            assert_eq!(a.pow(0), Orientation::identity());
+            assert_eq!(a.pow(1), a);
+            assert_eq!(a.pow(-1), a.invert());
+            assert_eq!(a.pow(5), a * a * a * a * a, "{a}");
+            assert_eq!(a.pow(-3), a.invert() * a.invert() * a.invert(), "{a}");
+            for &b in &all {
+                let ab = a * b;
+                assert_eq!(ab, b.reorient(a));
+                assert_eq!(ab / b, a, "{a} * {b}");
+                let mut assigned = a;
+                assigned *= b;
+                assigned /= a;
+                assert_eq!(assigned, a * b / a);
+                for point in points {
+                    let expected: (i32, i32, i32) = a.transform(b.transform(point));
+                    assert_eq!(ab.transform(point), expected, "{a} * {b}");
+                }
+                for c in generators {
+                    assert_eq!((a * b) * c, a * (b * c), "{a} * {b} * {c}");
+                }
+            }
+        }
+    }
+}
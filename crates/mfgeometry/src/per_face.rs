@@ -0,0 +1,91 @@
+use crate::{Direction, Orientation};
+
+/// A value for each face of a cube, indexed by [Direction].
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PerFace<T> {
+    /// Indexed by [Direction::discriminant].
+    faces: [T; 6],
+}
+
+impl<T> PerFace<T> {
+    #[inline]
+    pub fn new<F: FnMut(Direction) -> T>(mut f: F) -> Self {
+        Self { faces: Direction::INDEX_ORDER.map(&mut f) }
+    }
+
+    #[inline]
+    pub const fn get(&self, face: Direction) -> &T {
+        &self.faces[face.discriminant() as usize]
+    }
+
+    #[inline]
+    pub const fn get_mut(&mut self, face: Direction) -> &mut T {
+        &mut self.faces[face.discriminant() as usize]
+    }
+
+    #[inline]
+    pub const fn set(&mut self, face: Direction, value: T) -> T {
+        ::core::mem::replace(self.get_mut(face), value)
+    }
+
+    /// Iterates the faces in [Direction::ALL] order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Direction, &T)> {
+        Direction::ALL.into_iter().map(move |face| (face, self.get(face)))
+    }
+
+    #[inline]
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> PerFace<U> {
+        PerFace { faces: self.faces.map(f) }
+    }
+}
+
+impl<T: Copy> PerFace<T> {
+    #[inline]
+    pub const fn splat(value: T) -> Self {
+        Self { faces: [value; 6] }
+    }
+
+    /// Moves each value from its face to the face `orientation` turns it to (see [Orientation::reface]).
+    #[inline]
+    pub fn reoriented(&self, orientation: Orientation) -> Self {
+        Self::new(|face| *self.get(orientation.source_face(face)))
+    }
+}
+
+impl<T> ::core::ops::Index<Direction> for PerFace<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, face: Direction) -> &Self::Output {
+        self.get(face)
+    }
+}
+
+impl<T> ::core::ops::IndexMut<Direction> for PerFace<T> {
+    #[inline]
+    fn index_mut(&mut self, face: Direction) -> &mut Self::Output {
+        self.get_mut(face)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Flip, Rotation};
+
+    #[test]
+    fn per_face_test() {
+        let mut faces = PerFace::new(|face| face);
+        assert!(Direction::iter().all(|face| faces[face] == face));
+        faces[Direction::UP] = Direction::DOWN;
+        assert_eq!(faces.set(Direction::UP, Direction::UP), Direction::DOWN);
+
+        let orientation = Orientation::new(Rotation::new(Direction::PosX, 1), Flip::X);
+        let reoriented = faces.reoriented(orientation);
+        for face in Direction::iter() {
+            assert_eq!(reoriented[orientation.reface(face)], face);
+        }
+    }
+}
@@ -7,7 +7,7 @@
 use paste::paste;
 use mfcore::lowlevel::CachePadded;
 use crate::{
-    direction::Direction, faces::Faces, orientation::Orientation, wrap_angle
+    direction::Direction, face_angle::FaceAngle, faces::Faces, orientation::Orientation, rotation_table, wrap_angle
 };
 
 // verified (2026-1-5)
@@ -62,7 +62,7 @@ pub enum Rot {
 
 impl Rot {
     pub const UNROTATED: Self = Self::PosY0;
-    pub const MAX: Self = Self::PosZ3;
+    pub const MAX: Self = Self::NegZ3;
     #[inline(always)]
     pub const fn as_u8(self) -> u8 {
         self as u8
@@ -687,7 +687,15 @@ impl Rotation {
 
     // verified (2025-12-28): source_face and reface are symmetrical.
     /// Tells which [Direction] rotated to `destination`.
+    #[inline]
     pub const fn source_face(self, destination: Direction) -> Direction {
+        rotation_table::SOURCE_FACE_TABLE.array.value[rotation_table::table_index(self, destination)]
+    }
+
+    // Reference implementation for [Rotation::source_face], used to validate the lookup table.
+    #[cfg(any(test, feature = "reference-impls"))]
+    #[doc(hidden)]
+    pub const fn source_face_match(self, destination: Direction) -> Direction {
         // This code was bootstrap generated. I wrote a naive solution,
         // then generated this code with the naive solution.
         // Besides maybe if you rearrange the order of matching,
@@ -848,7 +856,15 @@ impl Rotation {
     // verified (2025-12-28)
     // double verified (2025-12-29)
     /// Gets the angle of the face oriented to `world_face`.
-    pub fn face_angle(self, world_face: Direction) -> u8 {
+    #[inline]
+    pub const fn face_angle(self, world_face: Direction) -> FaceAngle {
+        FaceAngle::new(world_face, rotation_table::FACE_ANGLE_TABLE.array.value[rotation_table::table_index(self, world_face)] as i32)
+    }
+
+    // Reference implementation for [Rotation::face_angle], used to validate the lookup table.
+    #[cfg(any(test, feature = "reference-impls"))]
+    #[doc(hidden)]
+    pub fn face_angle_match(self, world_face: Direction) -> u8 {
         use Direction::*;
         match (self.angle(), self.up(), world_face) {
             (0, NegX, NegX) => 0,
@@ -1037,6 +1053,30 @@ impl Rotation {
         Self::UNROTATED.deorient(self)
     }
 
+    /// The identity of composition ([Rotation::UNROTATED]): `rotation * identity == rotation`.
+    #[inline]
+    #[must_use]
+    pub const fn identity() -> Self {
+        Self::UNROTATED
+    }
+
+    /// Composes the [Rotation] with itself `exponent` times, or its [inverse](Self::invert) if
+    /// `exponent` is negative. `pow(0)` is the [identity](Self::identity).
+    #[must_use]
+    pub const fn pow(self, exponent: i32) -> Self {
+        let mut base = if exponent < 0 { self.invert() } else { self };
+        let mut exponent = exponent.unsigned_abs();
+        let mut result = Self::UNROTATED;
+        while exponent != 0 {
+            if exponent & 1 == 1 {
+                result = base.reorient(result);
+            }
+            base = base.reorient(base);
+            exponent >>= 1;
+        }
+        result
+    }
+
     #[inline]
     pub const fn rotate_x(self, angle: i32) -> Self {
         self.reorient(Self::X_ROTATIONS[wrap_angle(angle) as usize])
@@ -1065,19 +1105,6 @@ impl Rotation {
         let rot = Self::corner_rotation(x, y, z, angle);
         self.reorient(rot)
     }
-
-    // #[inline]
-    // pub fn to_matrix(self) -> glam::Mat4 {
-    //     let up = self.reface(Direction::PosY).to_vec3();
-    //     let forward = self.reface(Direction::PosZ).to_vec3();
-    //     let right = self.reface(Direction::NegX).to_vec3();
-    //     glam::Mat4::from_cols(
-    //         right.extend(0.0),
-    //         up.extend(0.0),
-    //         forward.extend(0.0),
-    //         glam::Vec3::ZERO.extend(1.0),
-    //     )
-    // }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -1133,8 +1160,68 @@ impl From<Direction> for Rotation {
     }
 }
 
+/// Composition: `(a * b).rotate_coord(p) == a.rotate_coord(b.rotate_coord(p))`, so `a * b` is `b`
+/// and then `a`, which is `b.reorient(a)`. Like matrices, `a * b` isn't `b * a` in general.
+impl std::ops::Mul<Rotation> for Rotation {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Rotation) -> Self::Output {
+        rhs.reorient(self)
+    }
+}
+
+impl std::ops::MulAssign<Rotation> for Rotation {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Rotation) {
+        *self = *self * rhs;
+    }
+}
+
+/// Inverse composition: `a / b == a * b.invert()`, so `(a * b) / b == a`.
+impl std::ops::Div<Rotation> for Rotation {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Rotation) -> Self::Output {
+        rhs.invert().reorient(self)
+    }
+}
+
+impl std::ops::DivAssign<Rotation> for Rotation {
+    #[inline]
+    fn div_assign(&mut self, rhs: Rotation) {
+        *self = *self / rhs;
+    }
+}
+
 impl std::fmt::Display for Rotation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Rotation(up={},forward={},angle={})", self.up(), self.forward(), self.angle())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composition_group_test() {
+        let all = Rotation::iter().collect::<Vec<_>>();
+        for &a in &all {
+            assert_eq!(a * Rotation::identity(), a);
+            assert_eq!(a * a.invert(), Rotation::identity(), "{a}");
+            assert_eq!(a.invert() * a, Rotation::identity(), "{a}");
+            assert_eq!(a.pow(4 * 3), Rotation::identity(), "{a}");
+            assert_eq!(a.pow(-2), a.invert() * a.invert(), "{a}");
+            for &b in &all {
+                assert_eq!(a * b / b, a);
+                let expected: (i32, i32, i32) = a.rotate_coord(b.rotate_coord((1, 2, 3)));
+                assert_eq!((a * b).rotate_coord((1, 2, 3)), expected, "{a} * {b}");
+                for &c in &all {
+                    assert_eq!((a * b) * c, a * (b * c), "{a} * {b} * {c}");
+                }
+            }
+        }
+    }
+}
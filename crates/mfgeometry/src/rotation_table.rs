@@ -0,0 +1,110 @@
+// Lookup tables for [Rotation::source_face] and [Rotation::face_angle].
+// These used to be giant match statements, which were evaluated in meshing hot loops.
+// The match statements are kept (cfg(test)) as reference implementations to validate the tables.
+use mfcore::const_fmt::ConstStr;
+
+use crate::{CacheAlignedArray, direction::Direction, rotation::Rotation};
+
+#[inline(always)]
+pub(crate) const fn table_index(rotation: Rotation, face: Direction) -> usize {
+    rotation.0 as usize * 6 + face.rotation_discriminant() as usize
+}
+
+#[inline(always)]
+const fn dir_eq(lhs: Direction, rhs: Direction) -> bool {
+    lhs as u8 == rhs as u8
+}
+
+// Naive implementation used to generate SOURCE_FACE_TABLE.
+const fn source_face_naive(rotation: Rotation, destination: Direction) -> Direction {
+    let mut i = 0usize;
+    while i < 6 {
+        let source = Direction::INDEX_ORDER[i];
+        if dir_eq(rotation.reface(source), destination) {
+            return source;
+        }
+        i += 1;
+    }
+    ConstStr::<96>::new()
+        .str("reface is not a bijection: no face of rotation ")
+        .u64(rotation.0 as u64)
+        .str(" maps to direction ")
+        .u64(destination as u64)
+        .panic()
+}
+
+// Naive implementation used to generate FACE_ANGLE_TABLE.
+// The angle of a face is the number of counter-clockwise turns from the world face's
+// up to where the source face's up ended up after rotation.
+const fn face_angle_naive(rotation: Rotation, world_face: Direction) -> u8 {
+    let source = source_face_naive(rotation, world_face);
+    let rotated_up = rotation.reface(source.up());
+    let mut angle = 0i32;
+    while angle < 4 {
+        if dir_eq(world_face.up_at_angle(angle), rotated_up) {
+            return angle as u8;
+        }
+        angle += 1;
+    }
+    ConstStr::<96>::new()
+        .str("Rotated up is not orthogonal to the world face: rotation ")
+        .u64(rotation.0 as u64)
+        .str(", face ")
+        .u64(world_face as u64)
+        .panic()
+}
+
+pub(crate) const SOURCE_FACE_TABLE: CacheAlignedArray<Direction, 144> = {
+    let mut table = CacheAlignedArray::new([Direction::PosY; 144]);
+    let mut rot_i = 0u8;
+    while rot_i < 24 {
+        let rotation = Rotation::from_u8_wrapping(rot_i);
+        let mut face_i = 0usize;
+        while face_i < 6 {
+            let face = Direction::INDEX_ORDER[face_i];
+            table.array.value[table_index(rotation, face)] = source_face_naive(rotation, face);
+            face_i += 1;
+        }
+        rot_i += 1;
+    }
+    table
+};
+
+pub(crate) const FACE_ANGLE_TABLE: CacheAlignedArray<u8, 144> = {
+    let mut table = CacheAlignedArray::new([0u8; 144]);
+    let mut rot_i = 0u8;
+    while rot_i < 24 {
+        let rotation = Rotation::from_u8_wrapping(rot_i);
+        let mut face_i = 0usize;
+        while face_i < 6 {
+            let face = Direction::INDEX_ORDER[face_i];
+            table.array.value[table_index(rotation, face)] = face_angle_naive(rotation, face);
+            face_i += 1;
+        }
+        rot_i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_face_table_test() {
+        for rotation in Rotation::iter() {
+            for face in Direction::iter() {
+                assert_eq!(rotation.source_face(face), rotation.source_face_match(face), "{rotation:?} {face}");
+            }
+        }
+    }
+
+    #[test]
+    fn face_angle_table_test() {
+        for rotation in Rotation::iter() {
+            for face in Direction::iter() {
+                assert_eq!(rotation.face_angle(face).angle(), rotation.face_angle_match(face), "{rotation:?} {face}");
+            }
+        }
+    }
+}
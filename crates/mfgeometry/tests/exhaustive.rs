@@ -0,0 +1,96 @@
+//! Exhaustive checks of orientation composition over every triple of the 192 orientations (about
+//! seven million), behind the `exhaustive-tests` feature:
+//!
+//! `cargo test -p mfgeometry --release --features exhaustive-tests --test exhaustive`
+//!
+//! [Orientation::reorient] and [Orientation::deorient] end in `unreachable_unchecked` when the
+//! refaced up and forward aren't a valid [Rotation]. Reaching every input here (and every output,
+//! through the triples) is what backs that up. The unit tests check the same laws on a generating
+//! set, which is enough to catch most mistakes but doesn't prove them for every input.
+
+use mfgeometry::{Direction, Flip, Orientation, Rotation};
+
+const ORIENTATION_COUNT: u8 = 192;
+
+fn orientations() -> Vec<Orientation> {
+    (0..ORIENTATION_COUNT).map(|i| Orientation::from_u8(i).unwrap()).collect()
+}
+
+#[test]
+fn reorient_associativity_test() {
+    let all = orientations();
+    for &a in &all {
+        for &b in &all {
+            let ab = a.reorient(b);
+            for &c in &all {
+                assert_eq!(ab.reorient(c), a.reorient(b.reorient(c)), "{a}, {b}, {c}");
+            }
+        }
+    }
+}
+
+#[test]
+fn deorient_inverse_test() {
+    let all = orientations();
+    let points = [(1, 2, 3), (-3, 0, 5)];
+    for &a in &all {
+        assert_eq!(a.reorient(a.invert()), Orientation::UNORIENTED, "{a}");
+        assert_eq!(a.invert().reorient(a), Orientation::UNORIENTED, "{a}");
+        assert_eq!(a.invert().invert(), a, "{a}");
+        for &b in &all {
+            assert_eq!(a.reorient(b).deorient(b), a, "{a}, {b}");
+            assert_eq!(a.deorient(b).reorient(b), a, "{a}, {b}");
+            assert_eq!(a.deorient(b), a.reorient(b.invert()), "{a}, {b}");
+            // Composition agrees with transforming points and faces one orientation at a time.
+            let ab = a.reorient(b);
+            for point in points {
+                let expected: (i32, i32, i32) = b.transform(a.transform(point));
+                assert_eq!(ab.transform(point), expected, "{a}, {b}");
+            }
+            for face in Direction::iter() {
+                assert_eq!(ab.reface(face), b.reface(a.reface(face)), "{a}, {b}, {face}");
+            }
+        }
+    }
+}
+
+#[test]
+fn rotation_group_test() {
+    let all = Rotation::iter().collect::<Vec<_>>();
+    for &a in &all {
+        for &b in &all {
+            assert_eq!(a.reorient(b).deorient(b), a, "{a}, {b}");
+            for &c in &all {
+                assert_eq!(a.reorient(b).reorient(c), a.reorient(b.reorient(c)), "{a}, {b}, {c}");
+            }
+            // Rotations compose the same way on their own as inside orientations.
+            let (oa, ob) = (Orientation::from(a), Orientation::from(b));
+            assert_eq!(oa.reorient(ob), Orientation::from(a.reorient(b)), "{a}, {b}");
+        }
+    }
+}
+
+#[test]
+fn u8_constructor_test() {
+    for value in 0..=u8::MAX {
+        let orientation = Orientation::from_u8(value);
+        assert_eq!(orientation.is_some(), value < ORIENTATION_COUNT, "{value}");
+        if let Some(orientation) = orientation {
+            assert_eq!(orientation.as_u8(), value);
+            assert_eq!(Orientation::from_u8_wrapping(value), orientation);
+        }
+        assert!(Orientation::from_u8(Orientation::from_u8_wrapping(value).as_u8()).is_some(), "{value}");
+
+        let rotation = Rotation::from_u8(value);
+        assert_eq!(rotation.is_some(), value < 24, "{value}");
+        if let Some(rotation) = rotation {
+            assert_eq!(rotation.as_u8(), value);
+            assert_eq!(Rotation::from_u8_wrapping(value), rotation);
+        }
+        assert!(Rotation::from_u8(Rotation::from_u8_wrapping(value).as_u8()).is_some(), "{value}");
+
+        let flip = Flip::from_u8(value);
+        assert_eq!(flip.is_some(), value < 8, "{value}");
+        assert_eq!(Flip::from_u8_wrapping(value).as_u8(), value & 0b111);
+    }
+}
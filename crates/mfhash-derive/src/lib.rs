@@ -0,0 +1,155 @@
+//! `#[derive(DeterministicHash)]`, re-exported by `mfhash` with its `derive` feature.
+//!
+//! Structs hash their fields in declaration order, exactly like a tuple of those fields. Enums
+//! hash the variant's discriminant as a `u32` (explicit discriminants are respected), then the
+//! variant's fields. Every type parameter gets a `DeterministicHash` bound.
+//!
+//! Attributes:
+//! - `#[deterministic_hash(skip)]` on a field leaves it out of the hash (and out of the version).
+//! - `#[deterministic_hash(version = N)]` on the type sets its own hash version, which is combined
+//!   with the versions of its fields. Without it the type takes the versions of its fields, like a
+//!   tuple does.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, Fields, LitInt, Type};
+
+#[proc_macro_derive(DeterministicHash, attributes(deterministic_hash))]
+pub fn derive_deterministic_hash(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let version = container_version(&input)?;
+    let mut field_types = Vec::new();
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, hashes) = destructure(&data.fields, &mut field_types)?;
+            quote! {
+                let Self #pattern = self;
+                #(#hashes)*
+            }
+        },
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            let mut base: Option<&Expr> = None;
+            let mut offset = 0u32;
+            for variant in &data.variants {
+                if let Some((_, discriminant)) = &variant.discriminant {
+                    base = Some(discriminant);
+                    offset = 0;
+                }
+                let discriminant = match base {
+                    Some(base) => quote! { ((#base) as u32).wrapping_add(#offset) },
+                    None => quote! { #offset },
+                };
+                let name = &variant.ident;
+                let (pattern, hashes) = destructure(&variant.fields, &mut field_types)?;
+                arms.push(quote! {
+                    Self::#name #pattern => {
+                        hasher.write_u32(#discriminant);
+                        #(#hashes)*
+                    },
+                });
+                offset += 1;
+            }
+            if arms.is_empty() {
+                quote! { match *self {} }
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "DeterministicHash can't be derived for unions."));
+        },
+    };
+
+    let generics = &mut input.generics;
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::mfhash::deterministic::DeterministicHash));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+    let versions = version.iter().map(|version| quote! { #version })
+        .chain(field_types.iter().map(|ty| quote! { <#ty as ::mfhash::deterministic::DeterministicHash>::HASH_VERSION }));
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics ::mfhash::deterministic::DeterministicHash for #name #type_generics #where_clause {
+            const HASH_VERSION: u32 = ::mfhash::deterministic::combine_versions(&[#(#versions),*]);
+
+            #[allow(unused_variables)]
+            fn deterministic_hash<H: ::mfhash::deterministic::DeterministicHasher>(&self, hasher: &mut H) {
+                #body
+            }
+        }
+    })
+}
+
+/// The `version = N` of the type's `#[deterministic_hash(...)]` attributes.
+fn container_version(input: &DeriveInput) -> syn::Result<Option<LitInt>> {
+    let mut version = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("deterministic_hash")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("version") {
+                let value: LitInt = meta.value()?.parse()?;
+                value.base10_parse::<u32>()?;
+                version = Some(value);
+                Ok(())
+            } else {
+                Err(meta.error("Expected `version = N` on the type."))
+            }
+        })?;
+    }
+    Ok(version)
+}
+
+/// Whether the field has `#[deterministic_hash(skip)]`.
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("deterministic_hash")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("Expected `skip` on a field."))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+/// The pattern that binds the hashed fields, and the statements that hash them. The types of the
+/// hashed fields are pushed to `field_types`.
+fn destructure(fields: &Fields, field_types: &mut Vec<Type>) -> syn::Result<(TokenStream, Vec<TokenStream>)> {
+    let mut bindings = Vec::new();
+    let mut hashes = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let binding = format_ident!("field_{}", index, span = Span::call_site());
+        let skipped = is_skipped(field)?;
+        if !skipped {
+            field_types.push(field.ty.clone());
+            hashes.push(quote! {
+                ::mfhash::deterministic::DeterministicHash::deterministic_hash(#binding, hasher);
+            });
+        }
+        bindings.push(match (&field.ident, skipped) {
+            (Some(ident), true) => quote! { #ident: _ },
+            (Some(ident), false) => quote! { #ident: #binding },
+            (None, true) => quote! { _ },
+            (None, false) => quote! { #binding },
+        });
+    }
+    let pattern = match fields {
+        Fields::Named(_) => quote! { { #(#bindings),* } },
+        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+        Fields::Unit => quote! {},
+    };
+    Ok((pattern, hashes))
+}
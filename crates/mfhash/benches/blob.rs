@@ -0,0 +1,25 @@
+//! Hashing a 64 MiB save blob on one thread and across rayon's pool.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mfhash::Blake3Hasher;
+
+const BLOB_LEN: usize = 64 << 20;
+
+fn blob() -> Vec<u8> {
+    (0..BLOB_LEN as u32).map(|i| (i.wrapping_mul(0x9E37_79B9) >> 24) as u8).collect()
+}
+
+fn bench_blob(c: &mut Criterion) {
+    let blob = blob();
+    let mut group = c.benchmark_group("blob_64mib");
+    group.throughput(Throughput::Bytes(BLOB_LEN as u64));
+    group.sample_size(10);
+    group.bench_function("update", |b| b.iter(|| Blake3Hasher::new().update(black_box(&blob)).finalize()));
+    group.bench_function("update_rayon", |b| b.iter(|| Blake3Hasher::new().update_rayon(black_box(&blob)).finalize()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_blob);
+criterion_main!(benches);
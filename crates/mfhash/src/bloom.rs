@@ -0,0 +1,427 @@
+//! Probabilistic set membership.
+//!
+//! Both filters hash items with a keyed [Blake3Hasher], then derive every probe
+//! index from that single 128-bit digest using double hashing (`h1 + i * h2`).
+
+use mfcereal::{decode::{Decode, DecodeError, Decoder}, encode::{Encode, Encoder}};
+
+use crate::{deterministic::DeterministicHash, Blake3Hasher};
+
+/// The parameters of a bloom filter, derived from the expected number of items and the desired false positive rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BloomParams {
+    /// The number of slots (bits or counters) in the filter.
+    pub slot_count: u64,
+    /// The number of slots that each item sets.
+    pub hash_count: u32,
+}
+
+impl BloomParams {
+    /// Calculates the optimal parameters for `expected_items` with a target `false_positive_rate`.
+    ///
+    /// `false_positive_rate` is clamped to `(0, 1)`.
+    pub fn optimal(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = false_positive_rate.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON);
+        let ln2 = std::f64::consts::LN_2;
+        let slot_count = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hash_count = ((slot_count as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            slot_count,
+            hash_count,
+        }
+    }
+
+    /// The expected false positive rate after `items` items have been inserted.
+    pub fn false_positive_rate(self, items: u64) -> f64 {
+        let k = self.hash_count as f64;
+        let m = self.slot_count as f64;
+        (1.0 - (-k * items as f64 / m).exp()).powf(k)
+    }
+}
+
+#[inline]
+fn item_digest<T: DeterministicHash + ?Sized>(key: &[u8; 32], item: &T) -> (u64, u64) {
+    let mut hasher = Blake3Hasher::new_keyed(key);
+    item.deterministic_hash_versioned(&mut hasher);
+    let digest = hasher.finalize_u128();
+    // h2 is forced odd so that probes never collapse onto a single slot.
+    ((digest >> 64) as u64, digest as u64 | 1)
+}
+
+#[inline]
+fn probes(params: BloomParams, (h1, h2): (u64, u64)) -> impl Iterator<Item = usize> {
+    (0..params.hash_count as u64).map(move |i| {
+        (h1.wrapping_add(i.wrapping_mul(h2)) % params.slot_count) as usize
+    })
+}
+
+/// A keyed bloom filter.
+///
+/// The key determines which slots an item maps to, so filters can only be
+/// compared or merged with filters that use the same key and parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BloomFilter {
+    key: [u8; 32],
+    params: BloomParams,
+    items: u64,
+    words: Box<[u64]>,
+}
+
+impl BloomFilter {
+    /// Creates a [BloomFilter] sized for `expected_items` with a target `false_positive_rate`.
+    pub fn new(expected_items: usize, false_positive_rate: f64, key: [u8; 32]) -> Self {
+        Self::with_params(BloomParams::optimal(expected_items, false_positive_rate), key)
+    }
+
+    /// # Panics
+    /// Panics if `params.slot_count` or `params.hash_count` is zero.
+    pub fn with_params(params: BloomParams, key: [u8; 32]) -> Self {
+        assert!(params.slot_count != 0 && params.hash_count != 0, "bloom filter parameters must be non-zero");
+        let word_count = params.slot_count.div_ceil(64) as usize;
+        Self {
+            key,
+            params,
+            items: 0,
+            words: vec![0u64; word_count].into_boxed_slice(),
+        }
+    }
+
+    #[inline]
+    pub const fn params(&self) -> BloomParams {
+        self.params
+    }
+
+    #[inline]
+    pub const fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    /// The number of insertions that set at least one new bit.
+    #[inline]
+    pub const fn len(&self) -> u64 {
+        self.items
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    #[inline]
+    fn get_bit(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Inserts `item`, returning `true` if the item was not already (probably) in the set.
+    pub fn insert<T: DeterministicHash + ?Sized>(&mut self, item: &T) -> bool {
+        let mut inserted = false;
+        for index in probes(self.params, item_digest(&self.key, item)) {
+            let bit = 1u64 << (index % 64);
+            let word = &mut self.words[index / 64];
+            inserted |= *word & bit == 0;
+            *word |= bit;
+        }
+        if inserted {
+            self.items += 1;
+        }
+        inserted
+    }
+
+    /// Returns `false` if `item` is definitely not in the set, and `true` if it probably is.
+    pub fn contains<T: DeterministicHash + ?Sized>(&self, item: &T) -> bool {
+        probes(self.params, item_digest(&self.key, item)).all(|index| self.get_bit(index))
+    }
+
+    /// The expected false positive rate given the current number of items.
+    #[inline]
+    pub fn false_positive_rate(&self) -> f64 {
+        self.params.false_positive_rate(self.items)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+        self.items = 0;
+    }
+
+    /// Merges `other` into `self`. Returns `false` (and does nothing) if the filters are incompatible.
+    pub fn union(&mut self, other: &Self) -> bool {
+        if self.key != other.key || self.params != other.params {
+            return false;
+        }
+        self.words.iter_mut().zip(other.words.iter()).for_each(|(dst, src)| *dst |= *src);
+        self.items += other.items;
+        true
+    }
+}
+
+/// A keyed bloom filter with saturating 8-bit counters, which allows removal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CountingBloomFilter {
+    key: [u8; 32],
+    params: BloomParams,
+    items: u64,
+    counters: Box<[u8]>,
+}
+
+impl CountingBloomFilter {
+    /// Creates a [CountingBloomFilter] sized for `expected_items` with a target `false_positive_rate`.
+    pub fn new(expected_items: usize, false_positive_rate: f64, key: [u8; 32]) -> Self {
+        Self::with_params(BloomParams::optimal(expected_items, false_positive_rate), key)
+    }
+
+    /// # Panics
+    /// Panics if `params.slot_count` or `params.hash_count` is zero.
+    pub fn with_params(params: BloomParams, key: [u8; 32]) -> Self {
+        assert!(params.slot_count != 0 && params.hash_count != 0, "bloom filter parameters must be non-zero");
+        Self {
+            key,
+            params,
+            items: 0,
+            counters: vec![0u8; params.slot_count as usize].into_boxed_slice(),
+        }
+    }
+
+    #[inline]
+    pub const fn params(&self) -> BloomParams {
+        self.params
+    }
+
+    #[inline]
+    pub const fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    /// The number of items in the filter (insertions minus removals).
+    #[inline]
+    pub const fn len(&self) -> u64 {
+        self.items
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Inserts `item`. Counters saturate at [u8::MAX], after which they can no longer be decremented.
+    pub fn insert<T: DeterministicHash + ?Sized>(&mut self, item: &T) {
+        for index in probes(self.params, item_digest(&self.key, item)) {
+            let counter = &mut self.counters[index];
+            *counter = counter.saturating_add(1);
+        }
+        self.items += 1;
+    }
+
+    /// Removes `item`, returning `false` if the item was definitely not in the set.
+    ///
+    /// Only remove items that were inserted, otherwise other items may be removed as well.
+    pub fn remove<T: DeterministicHash + ?Sized>(&mut self, item: &T) -> bool {
+        let digest = item_digest(&self.key, item);
+        if !probes(self.params, digest).all(|index| self.counters[index] != 0) {
+            return false;
+        }
+        for index in probes(self.params, digest) {
+            let counter = &mut self.counters[index];
+            // Saturated counters have lost count, so they stay saturated.
+            if *counter != u8::MAX {
+                *counter -= 1;
+            }
+        }
+        self.items = self.items.saturating_sub(1);
+        true
+    }
+
+    /// Returns `false` if `item` is definitely not in the set, and `true` if it probably is.
+    pub fn contains<T: DeterministicHash + ?Sized>(&self, item: &T) -> bool {
+        probes(self.params, item_digest(&self.key, item)).all(|index| self.counters[index] != 0)
+    }
+
+    /// An upper bound on the number of times `item` was inserted.
+    pub fn count<T: DeterministicHash + ?Sized>(&self, item: &T) -> u8 {
+        probes(self.params, item_digest(&self.key, item))
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn false_positive_rate(&self) -> f64 {
+        self.params.false_positive_rate(self.items)
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.fill(0);
+        self.items = 0;
+    }
+
+    /// Collapses the counters into a [BloomFilter] with the same key and parameters.
+    pub fn to_bloom_filter(&self) -> BloomFilter {
+        let mut filter = BloomFilter::with_params(self.params, self.key);
+        for (index, counter) in self.counters.iter().enumerate() {
+            if *counter != 0 {
+                filter.words[index / 64] |= 1 << (index % 64);
+            }
+        }
+        filter.items = self.items;
+        filter
+    }
+}
+
+impl Encode for BloomParams {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_u64(self.slot_count)?
+            + encoder.write_u32(self.hash_count)?
+        )
+    }
+}
+
+impl Decode for BloomParams {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let slot_count = decoder.read_u64()?;
+        let hash_count = decoder.read_u32()?;
+        if slot_count == 0 || hash_count == 0 {
+            return Err(DecodeError::InvalidData("bloom filter parameters must be non-zero"));
+        }
+        Ok(Self {
+            slot_count,
+            hash_count,
+        })
+    }
+}
+
+fn read_key<D: Decoder>(decoder: &mut D) -> Result<[u8; 32], DecodeError<D::Error>> {
+    let mut key = [0u8; 32];
+    decoder.read_exact(&mut key)?;
+    Ok(key)
+}
+
+impl Encode for BloomFilter {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_u8_slice(&self.key, false)?
+            + self.params.encode(encoder)?
+            + encoder.write_u64(self.items)?
+            + encoder.write_u64_slice(&self.words, true)?
+        )
+    }
+}
+
+impl Decode for BloomFilter {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let key = read_key(decoder)?;
+        let params = BloomParams::decode(decoder)?;
+        let items = decoder.read_u64()?;
+        let words = decoder.read_u64_vec()?;
+        if words.len() as u64 != params.slot_count.div_ceil(64) {
+            return Err(DecodeError::InvalidData("bloom filter word count does not match slot count"));
+        }
+        Ok(Self {
+            key,
+            params,
+            items,
+            words: words.into_boxed_slice(),
+        })
+    }
+}
+
+impl Encode for CountingBloomFilter {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_u8_slice(&self.key, false)?
+            + self.params.encode(encoder)?
+            + encoder.write_u64(self.items)?
+            + encoder.write_u8_slice(&self.counters, true)?
+        )
+    }
+}
+
+impl Decode for CountingBloomFilter {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let key = read_key(decoder)?;
+        let params = BloomParams::decode(decoder)?;
+        let items = decoder.read_u64()?;
+        let counters = decoder.read_u8_vec()?;
+        if counters.len() as u64 != params.slot_count {
+            return Err(DecodeError::InvalidData("counting bloom filter counter count does not match slot count"));
+        }
+        Ok(Self {
+            key,
+            params,
+            items,
+            counters: counters.into_boxed_slice(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = *b"manufactory bloom filter test ke";
+
+    #[test]
+    fn bloom_filter_test() {
+        let mut filter = BloomFilter::new(1000, 0.01, KEY);
+        for i in 0..1000u32 {
+            assert!(filter.insert(&i) || filter.contains(&i));
+        }
+        for i in 0..1000u32 {
+            assert!(filter.contains(&i));
+        }
+        let false_positives = (1000..101000u32).filter(|i| filter.contains(i)).count();
+        // Target is 1%, allow some slack.
+        assert!(false_positives < 2000, "false positives: {false_positives}");
+
+        let other_key = BloomFilter::new(1000, 0.01, [0u8; 32]);
+        assert!(!filter.clone().union(&other_key));
+    }
+
+    #[test]
+    fn counting_bloom_filter_test() {
+        let mut filter = CountingBloomFilter::new(100, 0.01, KEY);
+        filter.insert("iron_ore");
+        filter.insert("iron_ore");
+        filter.insert("copper_ore");
+        assert!(filter.count("iron_ore") >= 2);
+        assert!(filter.remove("iron_ore"));
+        assert!(filter.contains("iron_ore"));
+        assert!(filter.remove("iron_ore"));
+        assert!(!filter.contains("iron_ore"));
+        assert!(filter.contains("copper_ore"));
+        assert!(!filter.remove("gold_ore"));
+        assert!(filter.to_bloom_filter().contains("copper_ore"));
+    }
+
+    #[test]
+    #[should_panic(expected = "bloom filter parameters must be non-zero")]
+    fn bloom_zero_slots_test() {
+        BloomFilter::with_params(BloomParams { slot_count: 0, hash_count: 4 }, KEY);
+    }
+
+    #[test]
+    #[should_panic(expected = "bloom filter parameters must be non-zero")]
+    fn counting_bloom_zero_hashes_test() {
+        CountingBloomFilter::with_params(BloomParams { slot_count: 64, hash_count: 0 }, KEY);
+    }
+
+    #[test]
+    fn bloom_roundtrip_test() {
+        let mut filter = BloomFilter::new(64, 0.001, KEY);
+        (0..64u64).for_each(|i| { filter.insert(&i); });
+        let mut bytes = Vec::new();
+        let written = filter.encode(&mut bytes).unwrap();
+        assert_eq!(written, bytes.len() as u64);
+        let decoded = BloomFilter::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, filter);
+
+        let mut counting = CountingBloomFilter::new(64, 0.001, KEY);
+        (0..64u64).for_each(|i| counting.insert(&i));
+        let mut bytes = Vec::new();
+        counting.encode(&mut bytes).unwrap();
+        let decoded = CountingBloomFilter::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, counting);
+
+        assert!(BloomFilter::decode(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Stable keys for derived data caches (meshes, LODs, light).
+//!
+//! A [CacheKey] mixes a [HashSeed], a domain path (such as `world/mesh/lod2`), a hash of the
+//! content the data was derived from, and the format version of the derived data. Changing
+//! any of them changes the key, so stale cache entries are simply never looked up again.
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{
+    deterministic::DeterministicHash,
+    domain::{CacheContent, DomainHash, HashDomain},
+    symbol::Symbol,
+    HashSeed,
+};
+
+/// A 128-bit hash.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash128(pub u128);
+
+impl Hash128 {
+    #[inline]
+    pub const fn to_be_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl std::fmt::Display for Hash128 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Encode for Hash128 {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        encoder.write_u128(self.0)
+    }
+}
+
+impl Decode for Hash128 {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self(decoder.read_u128()?))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CacheKey(pub Hash128);
+
+impl CacheKey {
+    #[inline]
+    pub fn builder(seed: HashSeed) -> CacheKeyBuilder {
+        CacheKeyBuilder {
+            seed,
+            path: Vec::new(),
+            content: DomainHash::from_bytes([0; 32]),
+            version: 0,
+        }
+    }
+
+    #[inline]
+    pub const fn hash(self) -> Hash128 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Encode for CacheKey {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        self.0.encode(encoder)
+    }
+}
+
+impl Decode for CacheKey {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self(Hash128::decode(decoder)?))
+    }
+}
+
+/// Builds a [CacheKey]. The order the parts are given in doesn't matter, except for path segments.
+#[derive(Debug, Clone)]
+pub struct CacheKeyBuilder {
+    seed: HashSeed,
+    path: Vec<Symbol>,
+    content: DomainHash<CacheContent>,
+    version: u32,
+}
+
+impl CacheKeyBuilder {
+    /// Appends a segment to the domain path.
+    #[inline]
+    pub fn segment<S: Into<Symbol>>(mut self, segment: S) -> Self {
+        self.path.push(segment.into());
+        self
+    }
+
+    /// Appends each `/` separated segment of `path` to the domain path.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path.extend(path.split('/').filter(|segment| !segment.is_empty()).map(Symbol::intern));
+        self
+    }
+
+    /// Sets the content that the cached data is derived from.
+    pub fn content<T: DeterministicHash>(mut self, content: T) -> Self {
+        self.content = DomainHash::of(content);
+        self
+    }
+
+    /// Sets the content that the cached data is derived from to the content that `hash` is the hash
+    /// of, without hashing the content again. Hashes from different domains give different keys.
+    pub fn content_hash<D: HashDomain>(mut self, hash: DomainHash<D>) -> Self {
+        self.content = DomainHash::of((D::NAME, hash));
+        self
+    }
+
+    /// Sets the format version of the cached data. Data derived from hashed types can include their
+    /// hash versions with [hash_version](crate::hash_version).
+    #[inline]
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn build(&self) -> CacheKey {
+        let mut hasher = self.seed.build_hasher();
+        hasher.update_part(b"path");
+        hasher.update(&(self.path.len() as u64).to_le_bytes());
+        for segment in &self.path {
+            hasher.update_part(segment.as_str().as_bytes());
+        }
+        hasher.update_part(b"content");
+        hasher.update(self.content.as_bytes());
+        hasher.update_part(b"version");
+        hasher.update(&self.version.to_le_bytes());
+        CacheKey(Hash128(hasher.finalize_u128()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::SaveContent;
+
+    #[test]
+    fn cache_key_test() {
+        let seed = HashSeed::derive_keyed(b"world", None);
+        let key = CacheKey::builder(seed).path("world/mesh").content((1, 2, 3)).version(2).build();
+        assert_eq!(key, CacheKey::builder(seed).version(2).segment("world").segment("mesh").content((1, 2, 3)).build());
+        assert_ne!(key, CacheKey::builder(seed).path("world/mesh").content((1, 2, 3)).version(3).build());
+        assert_ne!(key, CacheKey::builder(seed).path("world/light").content((1, 2, 3)).version(2).build());
+        assert_ne!(key, CacheKey::builder(seed).path("worldmesh").content((1, 2, 3)).version(2).build());
+        assert_ne!(key, CacheKey::builder(HashSeed::new()).path("world/mesh").content((1, 2, 3)).version(2).build());
+        assert_eq!(key.to_string().len(), 32);
+
+        // Keys from content hashes depend on the hash's domain.
+        let save = DomainHash::<SaveContent>::of((1, 2, 3));
+        let from_hash = CacheKey::builder(seed).path("world/mesh").content_hash(save).build();
+        assert_eq!(from_hash, CacheKey::builder(seed).path("world/mesh").content_hash(save).build());
+        assert_ne!(from_hash, key);
+        assert_ne!(from_hash, CacheKey::builder(seed).path("world/mesh").content_hash(save.cast_domain::<CacheContent>()).build());
+
+        let mut bytes = Vec::new();
+        assert_eq!(key.encode(&mut bytes).unwrap(), 16);
+        assert_eq!(CacheKey::decode(&mut bytes.as_slice()).unwrap(), key);
+    }
+}
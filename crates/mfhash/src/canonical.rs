@@ -0,0 +1,137 @@
+//! Hashing encoded values.
+//!
+//! [canonical_encode] writes a value through its [Encode] impl in the hash byte order
+//! ([ByteOrder::HASH]), so for the types that implement both [Encode] and [DeterministicHash], hashing
+//! the canonical bytes gives the same hash as [deterministic_hash](crate::deterministic_hash). This lets
+//! content addressed storage hash what it's about to write without keeping a second code path in sync.
+//!
+//! Supported: the integer types, `bool`, `char`, `Option`, `Result` and `Vec` of supported types, and
+//! strings written with [Encoder::write_str]. C strings are not supported: the encoder writes them with
+//! a length and their nul terminator, and the hasher writes neither.
+
+use mfcereal::{
+    canonical::ByteOrder,
+    encode::{Encode, Encoder},
+};
+
+use crate::deterministic::DeterministicHasher;
+
+/// An [Encoder] that writes in the hash byte order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CanonicalEncoder {
+    bytes: Vec<u8>,
+}
+
+impl CanonicalEncoder {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Encoder for CanonicalEncoder {
+    type Error = ::core::convert::Infallible;
+
+    #[inline]
+    fn write_exact(&mut self, bytes: &[u8]) -> Result<u64, Self::Error> {
+        self.bytes.extend_from_slice(bytes);
+        Ok(bytes.len() as u64)
+    }
+
+    #[inline]
+    fn write_u16(&mut self, value: u16) -> Result<u64, Self::Error> {
+        self.write_exact(&ByteOrder::HASH.u16_bytes(value))
+    }
+
+    #[inline]
+    fn write_u32(&mut self, value: u32) -> Result<u64, Self::Error> {
+        self.write_exact(&ByteOrder::HASH.u32_bytes(value))
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) -> Result<u64, Self::Error> {
+        self.write_exact(&ByteOrder::HASH.u64_bytes(value))
+    }
+
+    #[inline]
+    fn write_u128(&mut self, value: u128) -> Result<u64, Self::Error> {
+        self.write_exact(&ByteOrder::HASH.u128_bytes(value))
+    }
+}
+
+/// Encodes `value` in the hash byte order.
+#[must_use]
+pub fn canonical_encode<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new();
+    let Ok(_) = value.encode(&mut encoder);
+    encoder.into_bytes()
+}
+
+/// Feeds the canonical encoding of `value` to `hasher`.
+pub fn hash_encoded<T: Encode + ?Sized, H: DeterministicHasher>(value: &T, hasher: &mut H) {
+    hasher.write(&canonical_encode(value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deterministic::DeterministicHash, deterministic_hash, Blake3Hasher};
+
+    fn assert_coherent<T: Encode + DeterministicHash>(value: T) {
+        let mut hasher = Blake3Hasher::new();
+        hash_encoded(&value, &mut hasher);
+        assert_eq!(hasher.finalize(), deterministic_hash(&value).finalize());
+    }
+
+    #[test]
+    fn canonical_encode_test() {
+        assert_eq!(canonical_encode(&0x0102u16), [2, 1]);
+        assert_eq!(canonical_encode(&-2isize), (-2i64).to_le_bytes());
+        assert_eq!(canonical_encode(&Some(true)), [1, 1]);
+
+        assert_coherent(0xABu8);
+        assert_coherent(-7i8);
+        assert_coherent(0xBEEFu16);
+        assert_coherent(i16::MIN);
+        assert_coherent(0xDEADBEEFu32);
+        assert_coherent(-123_456i32);
+        assert_coherent(u64::MAX - 1);
+        assert_coherent(i64::MIN);
+        assert_coherent(0x0123_4567_89AB_CDEF_0011_2233_4455_6677u128);
+        assert_coherent(-1i128);
+        assert_coherent(usize::MAX);
+        assert_coherent(-3isize);
+        assert_coherent(true);
+        assert_coherent('é');
+        assert_coherent(Some(42u32));
+        assert_coherent(None::<u32>);
+        assert_coherent(Ok::<u16, i8>(7));
+        assert_coherent(Err::<u16, i8>(-7));
+        assert_coherent(vec![1u8, 2, 3]);
+        assert_coherent(vec![-1i32, 0, 1]);
+        assert_coherent(vec![Some('x'), None]);
+        assert_coherent(Vec::<u64>::new());
+    }
+
+    #[test]
+    fn canonical_str_test() {
+        let mut encoder = CanonicalEncoder::new();
+        encoder.write_str("manufactory").unwrap();
+        let mut hasher = Blake3Hasher::new();
+        hasher.write(encoder.as_bytes());
+        assert_eq!(hasher.finalize(), deterministic_hash("manufactory").finalize());
+    }
+}
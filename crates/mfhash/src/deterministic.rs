@@ -1,5 +1,16 @@
 use std::{any::TypeId, ffi::{CStr, CString}};
 
+use mfcereal::canonical::{self, ByteOrder};
+
+/// Derives [DeterministicHash] from the fields of a type. Structs hash like a tuple of their
+/// fields. Enums hash their discriminant as a `u32`, then the variant's fields.
+///
+/// `#[deterministic_hash(skip)]` leaves a field out, and `#[deterministic_hash(version = N)]`
+/// bumps the type's [DeterministicHash::HASH_VERSION] (which also takes the versions of the
+/// fields).
+#[cfg(feature = "derive")]
+pub use mfhash_derive::DeterministicHash;
+
 pub trait DeterministicHasher {
     fn write(&mut self, input: &[u8]);
     
@@ -10,31 +21,31 @@ pub trait DeterministicHasher {
     
     #[inline]
     fn write_u16(&mut self, input: u16) {
-        let bytes = input.to_le_bytes();
+        let bytes = ByteOrder::HASH.u16_bytes(input);
         self.write(&bytes);
     }
     
     #[inline]
     fn write_u32(&mut self, input: u32) {
-        let bytes = input.to_le_bytes();
+        let bytes = ByteOrder::HASH.u32_bytes(input);
         self.write(&bytes);
     }
     
     #[inline]
     fn write_u64(&mut self, input: u64) {
-        let bytes = input.to_le_bytes();
+        let bytes = ByteOrder::HASH.u64_bytes(input);
         self.write(&bytes);
     }
     
     #[inline]
     fn write_u128(&mut self, input: u128) {
-        let bytes = input.to_le_bytes();
+        let bytes = ByteOrder::HASH.u128_bytes(input);
         self.write(&bytes);
     }
     
     #[inline]
     fn write_usize(&mut self, input: usize) {
-        self.write_u64(input as u64);
+        self.write_u64(canonical::size(input));
     }
     
     #[inline]
@@ -44,45 +55,50 @@ pub trait DeterministicHasher {
     
     #[inline]
     fn write_i16(&mut self, input: i16) {
-        let bytes = input.to_le_bytes();
-        self.write(&bytes);
+        self.write_u16(input.cast_unsigned());
     }
     
     #[inline]
     fn write_i32(&mut self, input: i32) {
-        let bytes = input.to_le_bytes();
-        self.write(&bytes);
+        self.write_u32(input.cast_unsigned());
     }
     
     #[inline]
     fn write_i64(&mut self, input: i64) {
-        let bytes = input.to_le_bytes();
-        self.write(&bytes);
+        self.write_u64(input.cast_unsigned());
     }
     
     #[inline]
     fn write_i128(&mut self, input: i128) {
-        let bytes = input.to_le_bytes();
-        self.write(&bytes);
+        self.write_u128(input.cast_unsigned());
     }
     
     #[inline]
     fn write_isize(&mut self, input: isize) {
-        self.write_i64(input as i64);
+        self.write_i64(canonical::signed_size(input));
     }
     
     #[inline]
     fn write_bool(&mut self, input: bool) {
-        self.write_u8(if input {
-            1
-        } else {
-            0
-        })
+        self.write_u8(canonical::bool_byte(input))
     }
     
     #[inline]
     fn write_char(&mut self, input: char) {
-        self.write_u32(input as u32)
+        self.write_u32(canonical::char_scalar(input))
+    }
+    
+    /// Writes the canonical bits of `input` (see [canonical::f32_bits]): `-0.0` hashes as `0.0`, and
+    /// every NaN hashes the same. Hash with [StrictFloats] to reject NaN instead.
+    #[inline]
+    fn write_f32(&mut self, input: f32) {
+        self.write_u32(canonical::f32_bits(input));
+    }
+
+    /// Like [Self::write_f32], for `f64`.
+    #[inline]
+    fn write_f64(&mut self, input: f64) {
+        self.write_u64(canonical::f64_bits(input));
     }
     
     #[inline]
@@ -99,7 +115,128 @@ pub trait DeterministicHasher {
 }
 
 pub trait DeterministicHash {
+    /// The version of the type's hash layout. Bump it whenever [Self::deterministic_hash] changes
+    /// what it writes, so that caches keyed by old hashes can tell (see [combine_versions]).
+    ///
+    /// Every hash started from a value (with [HashSeed::hash](crate::HashSeed::hash),
+    /// [deterministic_hash](crate::deterministic_hash), ...) writes the version first, except
+    /// version 1, so that types that were never bumped hash exactly as they did before versions
+    /// existed. Wrappers and containers take the version of what they hold.
+    const HASH_VERSION: u32 = 1;
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H);
+
+    /// Writes [Self::HASH_VERSION] (unless it's 1), then the value. This is how hashes of a whole
+    /// value start.
+    #[inline]
+    fn deterministic_hash_versioned<H: DeterministicHasher>(&self, hasher: &mut H) {
+        if Self::HASH_VERSION != 1 {
+            hasher.write_u32(Self::HASH_VERSION);
+        }
+        self.deterministic_hash(hasher);
+    }
+}
+
+/// Combines the hash versions of the parts of a subsystem into the subsystem's version, for
+/// invalidating caches of the whole subsystem. The order of the parts matters.
+///
+/// Parts that are all at version 1 combine to 1, like the parts of a tuple.
+#[must_use]
+pub const fn combine_versions(versions: &[u32]) -> u32 {
+    let mut combined = 0x811C_9DC5u32;
+    let mut bumped = false;
+    let mut index = 0;
+    while index < versions.len() {
+        bumped |= versions[index] != 1;
+        // FNV-1a over the whole version.
+        combined = (combined ^ versions[index]).wrapping_mul(0x0100_0193);
+        index += 1;
+    }
+    match combined {
+        _ if !bumped => 1,
+        // Never 1 (or 0) once a part was bumped.
+        0 | 1 => combined + 2,
+        _ => combined,
+    }
+}
+
+/// The [combine_versions] of the hash versions of some types.
+///
+/// ```
+/// # use mfhash::hash_version;
+/// const CHUNK_CACHE_VERSION: u32 = hash_version!(u32, (i32, i32, i32), Vec<u8>);
+/// assert_eq!(CHUNK_CACHE_VERSION, 1);
+/// ```
+#[macro_export]
+macro_rules! hash_version {
+    ($($type:ty),+ $(,)?) => {
+        $crate::deterministic::combine_versions(&[
+            $(<$type as $crate::deterministic::DeterministicHash>::HASH_VERSION,)+
+        ])
+    };
+}
+
+/// A NaN was hashed by a [StrictFloats] hasher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("A NaN was hashed in strict mode.")]
+pub struct NanError;
+
+/// A [DeterministicHasher] that hashes like `H`, but remembers whether a NaN was hashed, for
+/// inputs (such as worldgen parameters) where a NaN is always a bug.
+#[derive(Debug, Clone, Default)]
+pub struct StrictFloats<H> {
+    hasher: H,
+    nan: bool,
+}
+
+impl<H: DeterministicHasher> StrictFloats<H> {
+    #[inline]
+    #[must_use]
+    pub const fn new(hasher: H) -> Self {
+        Self { hasher, nan: false }
+    }
+
+    /// Whether a NaN was hashed.
+    #[inline]
+    #[must_use]
+    pub const fn saw_nan(&self) -> bool {
+        self.nan
+    }
+
+    /// The inner hasher, unless a NaN was hashed.
+    #[inline]
+    pub fn into_inner(self) -> Result<H, NanError> {
+        if self.nan {
+            Err(NanError)
+        } else {
+            Ok(self.hasher)
+        }
+    }
+}
+
+impl<H: DeterministicHasher> DeterministicHasher for StrictFloats<H> {
+    #[inline]
+    fn write(&mut self, input: &[u8]) {
+        self.hasher.write(input);
+    }
+
+    #[inline]
+    fn write_f32(&mut self, input: f32) {
+        self.nan |= input.is_nan();
+        self.hasher.write_f32(input);
+    }
+
+    #[inline]
+    fn write_f64(&mut self, input: f64) {
+        self.nan |= input.is_nan();
+        self.hasher.write_f64(input);
+    }
+
+    /// The inner hasher's hash, whether or not a NaN was hashed.
+    #[inline]
+    fn finish(&self) -> [u8; 32] {
+        self.hasher.finish()
+    }
 }
 
 macro_rules! impl_hash {
@@ -131,9 +268,12 @@ impl_hash!(
     write_i16(i16),
     write_i32(i32),
     write_i64(i64),
+    write_i128(i128),
     write_isize(isize),
     write_bool(bool),
     write_char(char),
+    write_f32(f32),
+    write_f64(f64),
 );
 
 impl DeterministicHash for &str {
@@ -182,6 +322,8 @@ impl DeterministicHash for CString {
 }
 
 impl<T: DeterministicHash + 'static, const LEN: usize> DeterministicHash for [T; LEN] {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.len().deterministic_hash(hasher);
         if ::core::mem::size_of::<T>() == 1 && (
@@ -201,6 +343,8 @@ impl<T: DeterministicHash + 'static, const LEN: usize> DeterministicHash for [T;
 }
 
 impl<T: DeterministicHash + 'static> DeterministicHash for [T] {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.len().deterministic_hash(hasher);
         if ::core::mem::size_of::<T>() == 1 && (
@@ -220,6 +364,8 @@ impl<T: DeterministicHash + 'static> DeterministicHash for [T] {
 }
 
 impl<'a, T: DeterministicHash + 'static> DeterministicHash for &'a [T] {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.len().deterministic_hash(hasher);
         if ::core::mem::size_of::<T>() == 1 && (
@@ -239,6 +385,8 @@ impl<'a, T: DeterministicHash + 'static> DeterministicHash for &'a [T] {
 }
 
 impl<'a, 'b, T: DeterministicHash + 'b> DeterministicHash for &'a T {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     #[inline]
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         (*self).deterministic_hash(hasher);
@@ -246,25 +394,29 @@ impl<'a, 'b, T: DeterministicHash + 'b> DeterministicHash for &'a T {
 }
 
 impl<T: DeterministicHash> DeterministicHash for Option<T> {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         if let Some(inner) = self {
-            (1u8).deterministic_hash(hasher);
+            canonical::SOME.deterministic_hash(hasher);
             inner.deterministic_hash(hasher);
         } else {
-            (0u8).deterministic_hash(hasher);
+            canonical::NONE.deterministic_hash(hasher);
         }
     }
 }
 
 impl<T: DeterministicHash, E: DeterministicHash> DeterministicHash for Result<T, E> {
+    const HASH_VERSION: u32 = combine_versions(&[T::HASH_VERSION, E::HASH_VERSION]);
+
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         match self {
             Ok(ok) => {
-                (1u8).deterministic_hash(hasher);
+                canonical::OK.deterministic_hash(hasher);
                 ok.deterministic_hash(hasher);
             },
             Err(err) => {
-                (0u8).deterministic_hash(hasher);
+                canonical::ERR.deterministic_hash(hasher);
                 err.deterministic_hash(hasher);
             },
         }
@@ -272,6 +424,8 @@ impl<T: DeterministicHash, E: DeterministicHash> DeterministicHash for Result<T,
 }
 
 impl<T: DeterministicHash> DeterministicHash for Box<T> {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     #[inline]
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.as_ref().deterministic_hash(hasher);
@@ -279,6 +433,8 @@ impl<T: DeterministicHash> DeterministicHash for Box<T> {
 }
 
 impl<T: DeterministicHash + 'static> DeterministicHash for Vec<T> {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     #[inline]
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.as_slice().deterministic_hash(hasher);
@@ -286,6 +442,8 @@ impl<T: DeterministicHash + 'static> DeterministicHash for Vec<T> {
 }
 
 impl<T: DeterministicHash> DeterministicHash for ::std::rc::Rc<T> {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     #[inline]
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.as_ref().deterministic_hash(hasher);
@@ -293,6 +451,8 @@ impl<T: DeterministicHash> DeterministicHash for ::std::rc::Rc<T> {
 }
 
 impl<T: DeterministicHash> DeterministicHash for ::std::sync::Arc<T> {
+    const HASH_VERSION: u32 = T::HASH_VERSION;
+
     #[inline]
     fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
         self.as_ref().deterministic_hash(hasher);
@@ -309,6 +469,8 @@ impl DeterministicHash for () {
 macro_rules! impl_deterministic_hash_tuples {
     (tuple: $($generic:ident),*$(,)?) => {
         impl<$($generic: DeterministicHash),*> DeterministicHash for ($($generic,)*) {
+            const HASH_VERSION: u32 = combine_versions(&[$($generic::HASH_VERSION),*]);
+
             #[allow(non_snake_case)]
             fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
                 let (
@@ -409,4 +571,115 @@ mod tests {
         let hash = hasher.finalize();
         println!("Hash: {hash}");
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn hash_versions() {
+        struct Tile(u16);
+
+        struct TileV2(u16);
+
+        impl DeterministicHash for Tile {
+            fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+                hasher.write_u16(self.0);
+            }
+        }
+
+        impl DeterministicHash for TileV2 {
+            const HASH_VERSION: u32 = 2;
+
+            fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+                hasher.write_u16(self.0);
+            }
+        }
+
+        // Version 1 hashes as if there were no versions. Bumped versions change the hash, even
+        // through references and containers.
+        assert_eq!(crate::deterministic_hash_u64(Tile(7)), crate::deterministic_hash_u64(7u16));
+        assert_ne!(crate::deterministic_hash_u64(TileV2(7)), crate::deterministic_hash_u64(Tile(7)));
+        assert_eq!(<&Vec<Option<TileV2>>>::HASH_VERSION, 2);
+        assert_eq!(crate::hash_version!(u8, (Tile, &str), Vec<Tile>), 1);
+        assert_ne!(crate::hash_version!(u8, (Tile, TileV2)), 1);
+        assert_ne!(crate::hash_version!(Tile, TileV2), crate::hash_version!(TileV2, Tile));
+        assert_eq!(combine_versions(&[1, 1]), 1);
+        assert_ne!(combine_versions(&[1, 2]), combine_versions(&[1, 3]));
+    }
+
+    #[test]
+    fn float_test() {
+        use crate::{deterministic_hash_u64 as hash, HashSeed};
+
+        // Equal floats hash the same, and so do all NaNs.
+        assert_eq!(hash(0.0f32), hash(-0.0f32));
+        assert_eq!(hash(f64::NAN), hash(-f64::from_bits(0x7FF0_0000_0000_0001)));
+        assert_ne!(hash(1.0f64), hash(1.0f64.next_up()));
+        assert_eq!(hash(1.5f32), hash(1.5f32.to_bits()));
+        assert_ne!(hash(f32::INFINITY), hash(f32::NEG_INFINITY));
+
+        // Strict mode hashes the same, but rejects NaN anywhere in the value.
+        let seed = HashSeed::derived("mfhash::deterministic::float_test");
+        let params = (0.25f32, [1.0f64, 2.0], Some(-0.0f64));
+        assert_eq!(seed.hash_strict(params).unwrap().finalize(), seed.hash(params).finalize());
+        assert!(matches!(seed.hash_strict((0.25f32, [1.0f64, f64::NAN], None::<f64>)), Err(NanError)));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_test() {
+        use crate::deterministic_hash_u64 as hash;
+
+        #[derive(DeterministicHash)]
+        struct Named {
+            id: u32,
+            name: &'static str,
+            #[deterministic_hash(skip)]
+            #[allow(unused)]
+            cache: Option<u64>,
+        }
+
+        #[derive(DeterministicHash)]
+        struct Tuple(i8, #[deterministic_hash(skip)] #[allow(unused)] bool, u16);
+
+        #[derive(DeterministicHash)]
+        struct Unit;
+
+        #[derive(DeterministicHash)]
+        #[deterministic_hash(version = 2)]
+        struct Bumped<T>(T);
+
+        #[allow(unused)]
+        #[derive(DeterministicHash)]
+        #[repr(u8)]
+        enum Shape<T> {
+            Empty,
+            Point(T, T),
+            Named { label: u8 } = 7,
+            Next,
+        }
+
+        #[derive(DeterministicHash)]
+        enum Never {}
+
+        // Structs hash like tuples of their (hashed) fields, so deriving doesn't change hashes of
+        // types that used to be hashed by hand.
+        let named = Named { id: 3, name: "drone", cache: Some(1) };
+        assert_eq!(hash(&named), hash((3u32, "drone")));
+        assert_eq!(hash(&named), hash(Named { cache: None, ..named }));
+        assert_eq!(hash(Tuple(-1, true, 9)), hash((-1i8, 9u16)));
+        // Fieldless structs write nothing.
+        assert_eq!(hash((1u8, Unit)), hash(1u8));
+        assert_eq!(Named::HASH_VERSION, 1);
+
+        // Enums write the discriminant, counting on from explicit ones.
+        assert_eq!(hash(Shape::<i32>::Empty), hash(0u32));
+        assert_eq!(hash(Shape::Point(1i32, 2)), hash((1u32, 1i32, 2i32)));
+        assert_eq!(hash(Shape::<i32>::Named { label: 4 }), hash((7u32, 4u8)));
+        assert_eq!(hash(Shape::<i32>::Next), hash(8u32));
+        assert_eq!(Never::HASH_VERSION, 1);
+
+        // Versions combine with those of the fields, and flow through generic parameters.
+        assert_eq!(Bumped::<u8>::HASH_VERSION, combine_versions(&[2, 1]));
+        assert_ne!(hash(Bumped(5u8)), hash(5u8));
+        assert_ne!(Shape::<Bumped<u8>>::HASH_VERSION, 1);
+        assert_eq!(Shape::<u8>::HASH_VERSION, 1);
+    }
+}
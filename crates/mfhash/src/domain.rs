@@ -0,0 +1,217 @@
+//! Hashes tagged with what they're hashes of.
+//!
+//! A [DomainHash] is a 256-bit hash with a [HashDomain] marker type, so that a cache content hash
+//! can't be compared against a save content hash, or passed where one is wanted.
+//! Each domain also hashes under its own context, so equal inputs in different domains give
+//! different hashes. Reinterpreting a hash as another domain takes an explicit
+//! [cast_domain](DomainHash::cast_domain).
+//!
+//! ```compile_fail
+//! # use mfhash::domain::{CacheContent, DomainHash, SaveContent};
+//! let cache = DomainHash::<CacheContent>::of(1u32);
+//! let save = DomainHash::<SaveContent>::of(1u32);
+//! assert_ne!(cache, save);
+//! ```
+
+use std::marker::PhantomData;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{
+    deterministic::{DeterministicHash, DeterministicHasher},
+    HashSeed,
+};
+
+/// A kind of hash. Implemented by marker types.
+pub trait HashDomain: 'static {
+    /// The name of the domain, for debug output.
+    const NAME: &'static str;
+
+    /// The seed the domain's hashes are computed with.
+    fn seed() -> HashSeed;
+}
+
+macro_rules! domains {
+    ($($(#[$meta:meta])* $name:ident => $context:literal;)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub enum $name {}
+
+            impl HashDomain for $name {
+                const NAME: &'static str = stringify!($name);
+
+                #[inline]
+                fn seed() -> HashSeed {
+                    HashSeed::derived($context)
+                }
+            }
+        )*
+    };
+}
+
+domains! {
+    /// The content that a cache entry was derived from (see [CacheKeyBuilder](crate::cache_key::CacheKeyBuilder)).
+    CacheContent => "manufactory/cache-key/content";
+}
+
+/// The canonical encoding of a save, as covered by a save signature. These are plain blake3
+/// hashes, so that they can be checked with any blake3 implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SaveContent {}
+
+impl HashDomain for SaveContent {
+    const NAME: &'static str = "SaveContent";
+
+    #[inline]
+    fn seed() -> HashSeed {
+        HashSeed::new()
+    }
+}
+
+/// A hash in the domain `D`.
+pub struct DomainHash<D: HashDomain> {
+    bytes: [u8; 32],
+    domain: PhantomData<fn() -> D>,
+}
+
+impl<D: HashDomain> DomainHash<D> {
+    /// Wraps `bytes`, which must already be a hash in `D`.
+    #[inline]
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self { bytes, domain: PhantomData }
+    }
+
+    /// The hash of `value` in `D`.
+    #[must_use]
+    pub fn of<T: DeterministicHash>(value: T) -> Self {
+        Self::from_bytes(D::seed().hash_256(value))
+    }
+
+    /// The hash of the raw `bytes` in `D`.
+    #[must_use]
+    pub fn of_bytes(bytes: &[u8]) -> Self {
+        Self::from_bytes(D::seed().build_hasher().update(bytes).finalize_bytes())
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; 32] {
+        self.bytes
+    }
+
+    /// The same bytes as a hash in `E`. The hash isn't recomputed, so this is only right where a
+    /// hash really does belong to both domains.
+    #[inline]
+    #[must_use]
+    pub const fn cast_domain<E: HashDomain>(self) -> DomainHash<E> {
+        DomainHash::from_bytes(self.bytes)
+    }
+}
+
+impl<D: HashDomain> Clone for DomainHash<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D: HashDomain> Copy for DomainHash<D> {}
+
+impl<D: HashDomain> PartialEq for DomainHash<D> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<D: HashDomain> Eq for DomainHash<D> {}
+
+impl<D: HashDomain> PartialOrd for DomainHash<D> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D: HashDomain> Ord for DomainHash<D> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl<D: HashDomain> std::hash::Hash for DomainHash<D> {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+impl<D: HashDomain> std::fmt::Debug for DomainHash<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DomainHash<{}>({self})", D::NAME)
+    }
+}
+
+impl<D: HashDomain> std::fmt::Display for DomainHash<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+// Hashes the bytes alone, so that a hash of a hash doesn't depend on the domain's name.
+impl<D: HashDomain> DeterministicHash for DomainHash<D> {
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write(&self.bytes);
+    }
+}
+
+// Layout: the 32 bytes of the hash. The domain isn't written; it's known from where the hash is.
+impl<D: HashDomain> Encode for DomainHash<D> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        encoder.write_exact(&self.bytes)
+    }
+}
+
+impl<D: HashDomain> Decode for DomainHash<D> {
+    fn decode<Dec: Decoder>(decoder: &mut Dec) -> Result<Self, DecodeError<Dec::Error>> {
+        let mut bytes = [0; 32];
+        decoder.read_exact(&mut bytes)?;
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domain_hash_test() {
+        let content = DomainHash::<CacheContent>::of((1u32, 2u32));
+        assert_eq!(content, DomainHash::of((1u32, 2u32)));
+        assert_ne!(content, DomainHash::of((1u32, 3u32)));
+        // The same input hashes differently in another domain.
+        assert_ne!(content.cast_domain::<SaveContent>(), DomainHash::<SaveContent>::of((1u32, 2u32)));
+        assert_eq!(content.cast_domain::<SaveContent>().cast_domain::<CacheContent>(), content);
+
+        // Save content hashes are plain blake3 hashes.
+        assert_eq!(DomainHash::<SaveContent>::of_bytes(b"world").to_bytes(), *blake3::hash(b"world").as_bytes());
+
+        let mut bytes = Vec::new();
+        assert_eq!(content.encode(&mut bytes).unwrap(), 32);
+        assert_eq!(DomainHash::<CacheContent>::decode(&mut bytes.as_slice()).unwrap(), content);
+        assert_eq!(content.to_string().len(), 64);
+        assert!(format!("{content:?}").starts_with("DomainHash<CacheContent>("));
+    }
+}
@@ -0,0 +1,243 @@
+//! A fast, non-cryptographic 64-bit [DeterministicHasher] for hot paths.
+//!
+//! [FastHash] is an xxHash64-style hasher: much faster than blake3 on small inputs (chunk
+//! coordinates, voxel positions, cache keys), and just as deterministic, but an adversary can
+//! find collisions, and it has only 64 bits of state. Use [Blake3Hasher](crate::Blake3Hasher) for
+//! anything that's saved, compared between peers, or could be attacked.
+//!
+//! Seeds work like [HashSeed]: a [FastSeed] is the default seed, a key, or derived from a context
+//! string, and [FastSeed::from_hash_seed] turns a world's [HashSeed] into one. Hashes don't depend
+//! on how the input was split between writes.
+
+use crate::{deterministic::{DeterministicHash, DeterministicHasher}, HashSeed};
+
+const P1: u64 = 0x9E37_79B1_85EB_CA87;
+const P2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const P3: u64 = 0x1656_67B1_9E37_79F9;
+const P4: u64 = 0x85EB_CA77_C2B2_AE63;
+const P5: u64 = 0x27D4_EB2F_1656_67C5;
+
+#[inline(always)]
+const fn round(acc: u64, word: u64) -> u64 {
+    acc.wrapping_add(word.wrapping_mul(P2)).rotate_left(31).wrapping_mul(P1)
+}
+
+#[inline(always)]
+const fn avalanche(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(P2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(P3);
+    hash ^ (hash >> 32)
+}
+
+/// The seed of a [FastHash].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FastSeed(u64);
+
+impl FastSeed {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn keyed(key: u64) -> Self {
+        Self(key)
+    }
+
+    /// A seed for `context`, computed at compile time when `context` is a constant.
+    #[inline]
+    #[must_use]
+    pub const fn derived(context: &str) -> Self {
+        let mut hasher = FastHash::with_seed(P5);
+        hasher.update(context.as_bytes());
+        Self(hasher.finish_u64())
+    }
+
+    /// A seed from `seed`, for hashing the hot paths of something seeded with `seed`.
+    #[inline]
+    #[must_use]
+    pub fn from_hash_seed(seed: HashSeed) -> Self {
+        Self(seed.hash_u64("mfhash/fast-seed"))
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn key(self) -> u64 {
+        self.0
+    }
+
+    /// A seed for `value` under this one.
+    #[inline]
+    #[must_use]
+    pub fn reseed_hashed<T: DeterministicHash>(self, value: T) -> Self {
+        Self(self.hash_u64(value))
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn build_hasher(self) -> FastHash {
+        FastHash::with_seed(self.0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hash<T: DeterministicHash>(self, value: T) -> FastHash {
+        let mut hasher = self.build_hasher();
+        value.deterministic_hash_versioned(&mut hasher);
+        hasher
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hash_u64<T: DeterministicHash>(self, value: T) -> u64 {
+        self.hash(value).finish_u64()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hash_u32<T: DeterministicHash>(self, value: T) -> u32 {
+        self.hash_u64(value) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FastHash {
+    state: u64,
+    /// Bytes that don't make a whole word yet, in the low bytes.
+    tail: u64,
+    tail_len: u8,
+    len: u64,
+}
+
+impl Default for FastHash {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FastHash {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_seed(seed: u64) -> Self {
+        Self { state: seed.wrapping_add(P5), tail: 0, tail_len: 0, len: 0 }
+    }
+
+    /// The hash of `value` with the default seed.
+    #[inline]
+    #[must_use]
+    pub fn hash_one<T: DeterministicHash>(value: T) -> u64 {
+        FastSeed::new().hash_u64(value)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hash_one_with_seed<T: DeterministicHash>(seed: u64, value: T) -> u64 {
+        FastSeed::keyed(seed).hash_u64(value)
+    }
+
+    pub const fn update(&mut self, bytes: &[u8]) -> &mut Self {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+        let mut index = 0;
+        while index < bytes.len() {
+            if self.tail_len == 0 && index + 8 <= bytes.len() {
+                let word = u64::from_le_bytes([
+                    bytes[index], bytes[index + 1], bytes[index + 2], bytes[index + 3],
+                    bytes[index + 4], bytes[index + 5], bytes[index + 6], bytes[index + 7],
+                ]);
+                self.state = round(self.state, word);
+                index += 8;
+            } else {
+                self.tail |= (bytes[index] as u64) << (self.tail_len * 8);
+                self.tail_len += 1;
+                index += 1;
+                if self.tail_len == 8 {
+                    self.state = round(self.state, self.tail);
+                    self.tail = 0;
+                    self.tail_len = 0;
+                }
+            }
+        }
+        self
+    }
+
+    #[must_use]
+    pub const fn finish_u64(&self) -> u64 {
+        let mut state = self.state;
+        if self.tail_len != 0 {
+            state = round(state, self.tail ^ P3);
+        }
+        avalanche(state ^ self.len.wrapping_mul(P4))
+    }
+}
+
+impl DeterministicHasher for FastHash {
+    #[inline]
+    fn write(&mut self, input: &[u8]) {
+        self.update(input);
+    }
+
+    /// [Self::finish_u64], stretched to 32 bytes. Only 64 bits of it are independent.
+    fn finish(&self) -> [u8; 32] {
+        let hash = self.finish_u64();
+        let mut bytes = [0u8; 32];
+        for (lane, chunk) in bytes.chunks_exact_mut(8).enumerate() {
+            chunk.copy_from_slice(&avalanche(hash ^ (lane as u64).wrapping_mul(P1)).to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_hasher_test() {
+        // Splitting the input doesn't change the hash.
+        let data = (0..100u8).collect::<Vec<_>>();
+        let whole = FastHash::new().update(&data).finish_u64();
+        for split in [0, 1, 7, 8, 9, 50, 100] {
+            let mut hasher = FastHash::new();
+            hasher.update(&data[..split]).update(&data[split..]);
+            assert_eq!(hasher.finish_u64(), whole, "split at {split}");
+        }
+        // Trailing zeros and seeds matter.
+        assert_ne!(FastHash::new().update(&[0]).finish_u64(), FastHash::new().finish_u64());
+        assert_ne!(FastHash::with_seed(1).update(&data).finish_u64(), whole);
+
+        // Stable across platforms and versions.
+        assert_eq!(FastHash::hash_one_with_seed(0xDEADBEEF, (1i32, 2i32, 3i32)), 0x02f5_3076_5519_5935);
+        assert_eq!(FastHash::hash_one(""), 0xa15b_0091_84ab_1bdb);
+        assert_ne!(FastHash::hash_one((1i32, 2i32, 3i32)), FastHash::hash_one((1i32, 2i32, 4i32)));
+
+        const CHUNKS: FastSeed = FastSeed::derived("mfhash::fast::chunks");
+        assert_eq!(CHUNKS, FastSeed::derived("mfhash::fast::chunks"));
+        assert_ne!(CHUNKS, FastSeed::derived("mfhash::fast::regions"));
+        let world = FastSeed::from_hash_seed(HashSeed::derived("mfhash::fast::fast_hasher_test"));
+        assert_ne!(world, FastSeed::from_hash_seed(HashSeed::new()));
+        assert_ne!(world.reseed_hashed(1u8), world);
+
+        // Coordinates spread over every bit.
+        let mut ones = [0u32; 64];
+        for x in 0..32i32 {
+            for z in 0..32i32 {
+                let hash = world.hash_u64((x, 0i32, z));
+                for (bit, count) in ones.iter_mut().enumerate() {
+                    *count += (hash >> bit) as u32 & 1;
+                }
+            }
+        }
+        assert!(ones.iter().all(|&count| (400..624).contains(&count)), "{ones:?}");
+    }
+}
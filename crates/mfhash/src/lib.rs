@@ -1,9 +1,44 @@
+// Lets `#[derive(DeterministicHash)]` name `::mfhash` inside this crate too.
+extern crate self as mfhash;
+
+pub mod bloom;
+pub mod cache_key;
+pub mod canonical;
 pub mod deterministic;
+pub mod domain;
+pub mod fast;
+pub mod sampled;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod std_hash;
+pub mod symbol;
+pub mod verify;
+pub mod xof;
 // use blake3::Hash;
-use deterministic::DeterministicHasher;
+use std::{
+    fs::File,
+    io::{self, IoSlice, Read},
+    path::Path,
+};
+
+use deterministic::{DeterministicHasher, NanError, StrictFloats};
 
 use crate::deterministic::DeterministicHash;
 
+pub use cache_key::{CacheKey, Hash128};
+pub use domain::{DomainHash, HashDomain};
+pub use fast::{FastHash, FastSeed};
+pub use std_hash::{BuildSeededHasher, SeededHashMap, SeededHashSet};
+pub use symbol::Symbol;
+pub use verify::HashVerifier;
+
+/// The buffer size of [Blake3Hasher::update_reader].
+pub const READ_CHUNK_SIZE: usize = 64 * 1024;
+/// The smallest input [Blake3Hasher::update_rayon] splits across threads. Smaller inputs hash
+/// faster on one thread.
+#[cfg(feature = "rayon")]
+pub const RAYON_THRESHOLD: usize = 128 * 1024;
+
 pub const GOLDEN_RATIO_64: u64 = 0x9e3779b97f4a7c15;
 pub const DEADBEEF_64: u64 = 0xDEADBEEF;
 
@@ -53,6 +88,98 @@ impl Blake3Hasher {
         self
     }
     
+    /// Feeds each slice in order. Equivalent to calling [Self::update] with the
+    /// concatenation of `slices`, without building it.
+    #[inline]
+    pub fn update_vectored(&mut self, slices: &[IoSlice<'_>]) -> &mut Self {
+        for slice in slices {
+            self.hasher.update(slice);
+        }
+        self
+    }
+    
+    /// Feeds each slice in order. Equivalent to calling [Self::update] with the
+    /// concatenation of `slices`, without building it.
+    #[inline]
+    pub fn update_iter<'a, I: IntoIterator<Item = &'a [u8]>>(&mut self, slices: I) -> &mut Self {
+        for slice in slices {
+            self.hasher.update(slice);
+        }
+        self
+    }
+    
+    /// Feeds `input` using every thread in rayon's global pool, for large buffers such as region
+    /// snapshots and save blobs. Gives the same hash as [Self::update]. Inputs smaller than
+    /// [RAYON_THRESHOLD] are hashed on this thread.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn update_rayon(&mut self, input: &[u8]) -> &mut Self {
+        if input.len() < RAYON_THRESHOLD {
+            self.hasher.update(input);
+        } else {
+            self.hasher.update_rayon(input);
+        }
+        self
+    }
+
+    /// Feeds everything `reader` reads until the end, a buffer at a time, so that large files
+    /// (such as world saves) are hashed without being read into memory. Returns the number of
+    /// bytes fed. If reading fails, the bytes read before the error have been fed.
+    pub fn update_reader<R: Read>(&mut self, reader: R) -> io::Result<u64> {
+        self.update_reader_chunked(reader, &mut [0; READ_CHUNK_SIZE])
+    }
+
+    /// [Self::update_reader] with a caller provided buffer, which sets the size of each read.
+    pub fn update_reader_chunked<R: Read>(&mut self, mut reader: R, buffer: &mut [u8]) -> io::Result<u64> {
+        assert!(!buffer.is_empty(), "Read buffer must not be empty.");
+        let mut consumed = 0;
+        loop {
+            match reader.read(buffer) {
+                Ok(0) => return Ok(consumed),
+                Ok(read) => {
+                    self.hasher.update(&buffer[..read]);
+                    consumed += read as u64;
+                },
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {},
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Feeds the contents of the file at `path`, memory-mapping it when the `mmap` feature is
+    /// enabled and the platform allows, and reading it with [Self::update_reader] otherwise.
+    /// Mapped files are hashed with [Self::update_rayon] when the `rayon` feature is enabled.
+    /// Returns the number of bytes fed. The file must not be modified while it's hashed.
+    pub fn update_mmap<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64> {
+        let file = File::open(path)?;
+        #[cfg(all(feature = "mmap", any(unix, windows)))]
+        {
+            let len = file.metadata()?.len();
+            // Empty files can't be mapped on some platforms.
+            if len != 0 {
+                // SAFETY: The map is read-only and dropped before returning. The caller must not
+                //         modify the file while it's hashed.
+                if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                    #[cfg(feature = "rayon")]
+                    self.update_rayon(&map);
+                    #[cfg(not(feature = "rayon"))]
+                    self.hasher.update(&map);
+                    return Ok(map.len() as u64);
+                }
+            }
+        }
+        self.update_reader(file)
+    }
+
+    /// Feeds `part` prefixed with its length, so that consecutive parts can't run
+    /// into each other (`"ab", "c"` and `"a", "bc"` hash differently). See [hash_parts].
+    #[inline]
+    pub fn update_part(&mut self, part: &[u8]) -> &mut Self {
+        self.hasher.update(&(part.len() as u64).to_le_bytes());
+        self.hasher.update(part);
+        self
+    }
+    
     #[inline]
     #[must_use]
     pub fn finalize(&self) -> blake3::Hash {
@@ -197,7 +324,7 @@ impl HashSeed {
     #[must_use]
     pub fn derive_keyed_hash<T: DeterministicHash>(hash_material: T, context: Option<&str>) -> Self {
         let mut hasher = Blake3Hasher::new_derive_key(context.unwrap_or(""));
-        hash_material.deterministic_hash(&mut hasher);
+        hash_material.deterministic_hash_versioned(&mut hasher);
         let key_material: [u8; 32] = hasher.finalize_bytes();
         Self::keyed(key_material)
     }
@@ -216,10 +343,24 @@ impl HashSeed {
     #[must_use]
     pub fn hash<T: DeterministicHash>(self, value: T) -> Blake3Hasher {
         let mut hasher = self.build_hasher();
-        value.deterministic_hash(&mut hasher);
+        value.deterministic_hash_versioned(&mut hasher);
         hasher
     }
     
+    /// Like [Self::hash], but fails if `value` contains a NaN (see [StrictFloats]).
+    pub fn hash_strict<T: DeterministicHash>(self, value: T) -> Result<Blake3Hasher, NanError> {
+        let mut hasher = StrictFloats::new(self.build_hasher());
+        value.deterministic_hash_versioned(&mut hasher);
+        hasher.into_inner()
+    }
+    
+    /// A [std::hash::BuildHasher] keyed from this seed, for `HashMap`s and `HashSet`s (see [std_hash]).
+    #[inline]
+    #[must_use]
+    pub fn build_std_hasher(self) -> BuildSeededHasher {
+        BuildSeededHasher::new(self)
+    }
+    
     #[inline]
     #[must_use]
     pub fn hash_bytes<T: DeterministicHash, const LEN: usize>(self, value: T) -> [u8; LEN] {
@@ -342,10 +483,33 @@ impl HashSeed {
     }
 }
 
+/// Builds a [Blake3Hasher] from a [HashSeed] and feeds it each part with [Blake3Hasher::update_part].
+/// Parts can be anything that implements `AsRef<[u8]>`.
+/// ```
+/// # use mfhash::{hash_parts, HashSeed};
+/// let chunk = [3i32, -1, 7].map(i32::to_le_bytes).concat();
+/// let hash = hash_parts!(HashSeed::new(), "mfworld", 42u32.to_le_bytes(), chunk).finalize_u64();
+/// assert_ne!(
+///     hash_parts!(HashSeed::new(), "ab", "c").finalize(),
+///     hash_parts!(HashSeed::new(), "a", "bc").finalize(),
+/// );
+/// # let _ = hash;
+/// ```
+#[macro_export]
+macro_rules! hash_parts {
+    ($seed:expr $(, $part:expr)* $(,)?) => {{
+        let mut hasher = $crate::HashSeed::build_hasher($seed);
+        $(
+            hasher.update_part(::core::convert::AsRef::<[u8]>::as_ref(&$part));
+        )*
+        hasher
+    }};
+}
+
 #[must_use]
 pub fn deterministic_hash<T: DeterministicHash>(value: T) -> Blake3Hasher {
     let mut hasher = Blake3Hasher::new();
-    value.deterministic_hash(&mut hasher);
+    value.deterministic_hash_versioned(&mut hasher);
     hasher
 }
 
@@ -477,6 +641,54 @@ mod tests {
         println!("{}", Hex(&hash1_a));
         println!("{}", Hex(&hash1_b));
     }
+
+    #[test]
+    fn update_reader_test() {
+        let data = (0..200_000u32).map(|i| (i * 31 % 251) as u8).collect::<Vec<_>>();
+        let expected = Blake3Hasher::new().update(&data).finalize();
+
+        let mut hasher = Blake3Hasher::new();
+        assert_eq!(hasher.update_reader(data.as_slice()).unwrap(), data.len() as u64);
+        assert_eq!(hasher.finalize(), expected);
+        let mut hasher = Blake3Hasher::new();
+        assert_eq!(hasher.update_reader_chunked(data.as_slice(), &mut [0; 7]).unwrap(), data.len() as u64);
+        assert_eq!(hasher.finalize(), expected);
+
+        // Errors are passed on, after what was read before them.
+        let failing = data.as_slice().take(1000).chain(FailingReader);
+        let error = Blake3Hasher::new().update_reader(failing).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+
+        let dir = std::env::temp_dir().join(format!("mfhash-update-reader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("save.bin");
+        std::fs::write(&path, &data).unwrap();
+        let mut hasher = Blake3Hasher::new();
+        assert_eq!(hasher.update_mmap(&path).unwrap(), data.len() as u64);
+        assert_eq!(hasher.finalize(), expected);
+        std::fs::write(&path, []).unwrap();
+        assert_eq!(Blake3Hasher::new().update_mmap(&path).unwrap(), 0);
+        assert!(Blake3Hasher::new().update_mmap(dir.join("missing.bin")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn update_rayon_test() {
+        let data = (0..(RAYON_THRESHOLD as u32 * 4)).map(|i| (i * 31 % 251) as u8).collect::<Vec<_>>();
+        for len in [0, 100, RAYON_THRESHOLD, data.len()] {
+            let expected = Blake3Hasher::new().update(&data[..len]).finalize();
+            assert_eq!(Blake3Hasher::new().update_rayon(&data[..len]).finalize(), expected);
+        }
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
     
     #[test]
     fn deterministic_hasher_test() {
@@ -509,6 +721,21 @@ mod tests {
         println!(" i32: {}", deterministic_hash(&value).finalize_i32());
     }
     
+    #[test]
+    fn update_vectored_test() {
+        let parts: [&[u8]; 3] = [b"namespace", b"\x2A\x00\x00\x00", b"coords"];
+        let concatenated = parts.concat();
+        let expected = Blake3Hasher::new().update(&concatenated).finalize();
+        let slices = parts.map(IoSlice::new);
+        assert_eq!(Blake3Hasher::new().update_vectored(&slices).finalize(), expected);
+        assert_eq!(Blake3Hasher::new().update_iter(parts).finalize(), expected);
+        
+        let seed = HashSeed::derive_keyed(b"parts", None);
+        assert_eq!(hash_parts!(seed, "a", b"b", [1u8, 2]).finalize(), hash_parts!(seed, "a", b"b", [1u8, 2]).finalize());
+        assert_ne!(hash_parts!(seed, "ab", "c").finalize(), hash_parts!(seed, "a", "bc").finalize());
+        assert_ne!(hash_parts!(seed, "ab").finalize(), hash_parts!(seed, "ab", "").finalize());
+    }
+    
     #[test]
     fn hash_test() {
         const ITERATIONS: usize = 10usize.pow(9);
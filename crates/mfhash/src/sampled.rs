@@ -0,0 +1,269 @@
+//! Sampled hashes of large arrays, for desync detection that's too expensive to do in full.
+//!
+//! A [SampledHash] covers a deterministic pseudo-random subset of the elements, picked from a seed
+//! and a [Coverage]. Two peers that hash the same array with the same seed and coverage sample the
+//! same elements, so their hashes only differ when a sampled element does. The [SampleInfo] that
+//! comes with the hash says what was sampled, so that hashes sampled differently are never
+//! compared ([SampledHash::compare]). Varying the seed over time (by tick, say) eventually covers
+//! every element.
+//!
+//! Elements are sampled by stratum: the array is split into as many even runs as there are
+//! samples, and one element is picked from each run. Samples are spread over the whole array and
+//! visited in order.
+
+use std::time::Duration;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{deterministic::{DeterministicHash, DeterministicHasher}, Blake3Hasher, Hash128};
+
+const CONTEXT: &str = "manufactory/sampled-hash";
+
+/// The fraction of the elements a [SampledHash] covers, in 65536ths. Integer so that every peer
+/// samples the same number of elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Coverage(u32);
+
+impl Coverage {
+    pub const SCALE: u32 = 1 << 16;
+    /// Every element; the hash is a full hash.
+    pub const FULL: Self = Self(Self::SCALE);
+    /// The smallest coverage [Self::autotune] picks.
+    pub const MIN_TUNED: Self = Self(Self::SCALE / 256);
+
+    /// `parts` 65536ths, clamped to [Self::FULL].
+    #[inline]
+    #[must_use]
+    pub const fn from_parts(parts: u32) -> Self {
+        Self(if parts > Self::SCALE { Self::SCALE } else { parts })
+    }
+
+    /// `numerator / denominator`, rounded up and clamped to [Self::FULL].
+    #[must_use]
+    pub const fn from_fraction(numerator: u32, denominator: u32) -> Self {
+        if denominator == 0 {
+            return Self::FULL;
+        }
+        let parts = (numerator as u64 * Self::SCALE as u64).div_ceil(denominator as u64);
+        Self::from_parts(if parts > u32::MAX as u64 { u32::MAX } else { parts as u32 })
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn parts(self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_full(self) -> bool {
+        self.0 == Self::SCALE
+    }
+
+    /// For display only.
+    #[inline]
+    #[must_use]
+    pub fn fraction(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    /// The number of elements of an array of `len` elements that are sampled. At least one element
+    /// of a non-empty array is sampled unless the coverage is zero.
+    #[must_use]
+    pub const fn sample_count(self, len: u32) -> u32 {
+        (len as u64 * self.0 as u64).div_ceil(Self::SCALE as u64) as u32
+    }
+
+    /// The coverage that fits `budget`, given how long a full hash takes (measured by hashing a
+    /// representative array with [Self::FULL]). Rounded down to a power of two fraction between
+    /// [Self::MIN_TUNED] and [Self::FULL], so that close measurements agree.
+    ///
+    /// Measurements differ between machines, so one peer should tune and share the coverage (it's
+    /// in every [SampleInfo]) rather than each peer tuning for itself.
+    #[must_use]
+    pub fn autotune(budget: Duration, full_hash: Duration) -> Self {
+        let mut coverage = Self::FULL;
+        let mut cost = full_hash;
+        while cost > budget && coverage > Self::MIN_TUNED {
+            coverage = Self(coverage.0 / 2);
+            cost /= 2;
+        }
+        coverage
+    }
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}%", self.fraction() * 100.0)
+    }
+}
+
+/// What a [SampledHash] covers. Hashes are only comparable when their infos are equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SampleInfo {
+    pub seed: u64,
+    pub coverage: Coverage,
+    /// The length of the array.
+    pub len: u32,
+    /// The number of elements hashed.
+    pub sampled: u32,
+}
+
+impl SampleInfo {
+    #[must_use]
+    pub const fn new(seed: u64, coverage: Coverage, len: u32) -> Self {
+        Self { seed, coverage, len, sampled: coverage.sample_count(len) }
+    }
+
+    /// The indices of the sampled elements, in order.
+    pub fn indices(&self) -> impl Iterator<Item = u32> + use<> {
+        let Self { seed, len, sampled, .. } = *self;
+        (0..sampled).map(move |stratum| {
+            let start = (stratum as u64 * len as u64 / sampled as u64) as u32;
+            let end = ((stratum as u64 + 1) * len as u64 / sampled as u64) as u32;
+            start + (mix(seed ^ (stratum as u64).wrapping_mul(crate::GOLDEN_RATIO_64)) % (end - start) as u64) as u32
+        })
+    }
+}
+
+// Layout: seed (u64), coverage (u32 parts), len (u32), sampled (u32).
+impl Encode for SampleInfo {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_u64(self.seed)?
+            + encoder.write_u32(self.coverage.0)?
+            + encoder.write_u32(self.len)?
+            + encoder.write_u32(self.sampled)?)
+    }
+}
+
+impl Decode for SampleInfo {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let seed = decoder.read_u64()?;
+        let parts = decoder.read_u32()?;
+        if parts > Coverage::SCALE {
+            return Err(DecodeError::InvalidData("Sample coverage is over 100%."));
+        }
+        let info = Self::new(seed, Coverage(parts), decoder.read_u32()?);
+        if decoder.read_u32()? != info.sampled {
+            return Err(DecodeError::InvalidData("Sample count doesn't match the coverage."));
+        }
+        Ok(info)
+    }
+}
+
+/// The result of [SampledHash::compare].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SampleComparison {
+    Match,
+    Mismatch,
+    /// The hashes sampled different elements.
+    Incomparable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SampledHash {
+    pub hash: Hash128,
+    pub info: SampleInfo,
+}
+
+impl SampledHash {
+    /// Hashes the sampled elements of an array of `len` elements, getting each with `get`.
+    pub fn new<T: DeterministicHash, F: FnMut(usize) -> T>(seed: u64, coverage: Coverage, len: usize, mut get: F) -> Self {
+        let len = u32::try_from(len).expect("Array too long to sample.");
+        let info = SampleInfo::new(seed, coverage, len);
+        let mut hasher = Blake3Hasher::new_derive_key(CONTEXT);
+        hasher.write_u64(info.seed);
+        hasher.write_u32(info.coverage.0);
+        hasher.write_u32(info.len);
+        for index in info.indices() {
+            hasher.write_u32(index);
+            get(index as usize).deterministic_hash(&mut hasher);
+        }
+        Self { hash: Hash128(hasher.finalize_u128()), info }
+    }
+
+    #[inline]
+    pub fn of_slice<T: DeterministicHash>(seed: u64, coverage: Coverage, values: &[T]) -> Self {
+        Self::new(seed, coverage, values.len(), |index| &values[index])
+    }
+
+    #[must_use]
+    pub fn compare(&self, other: &Self) -> SampleComparison {
+        match () {
+            _ if self.info != other.info => SampleComparison::Incomparable,
+            _ if self.hash == other.hash => SampleComparison::Match,
+            _ => SampleComparison::Mismatch,
+        }
+    }
+}
+
+// Layout: info (SampleInfo), hash (u128).
+impl Encode for SampledHash {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(self.info.encode(encoder)? + self.hash.encode(encoder)?)
+    }
+}
+
+impl Decode for SampledHash {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let info = SampleInfo::decode(decoder)?;
+        Ok(Self { hash: Hash128::decode(decoder)?, info })
+    }
+}
+
+/// SplitMix64's finalizer.
+#[inline]
+const fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_test() {
+        let values = (0..4096u32).collect::<Vec<_>>();
+        let coverage = Coverage::from_fraction(1, 16);
+        assert_eq!(coverage.sample_count(4096), 256);
+        assert_eq!(Coverage::from_fraction(3, 2), Coverage::FULL);
+
+        // One sample per stratum, in order, and the same for the same seed.
+        let info = SampleInfo::new(7, coverage, 4096);
+        let indices = info.indices().collect::<Vec<_>>();
+        assert_eq!(indices.len(), 256);
+        assert!(indices.iter().enumerate().all(|(stratum, &index)| index as usize / 16 == stratum));
+        assert_eq!(indices, info.indices().collect::<Vec<_>>());
+        assert_ne!(indices, SampleInfo::new(8, coverage, 4096).indices().collect::<Vec<_>>());
+        assert!(SampleInfo::new(7, Coverage::FULL, 10).indices().eq(0..10));
+        assert_eq!(SampleInfo::new(7, coverage, 0).indices().count(), 0);
+
+        // Changes to sampled elements are caught, others aren't.
+        let hash = SampledHash::of_slice(7, coverage, &values);
+        let mut changed = values.clone();
+        changed[indices[3] as usize] += 1;
+        assert_eq!(hash.compare(&SampledHash::of_slice(7, coverage, &changed)), SampleComparison::Mismatch);
+        let mut unsampled = values.clone();
+        unsampled[(indices[3] as usize + 1) % 16 + 48] += 1;
+        assert_eq!(hash.compare(&SampledHash::of_slice(7, coverage, &unsampled)), SampleComparison::Match);
+        assert_eq!(hash.compare(&SampledHash::of_slice(8, coverage, &values)), SampleComparison::Incomparable);
+
+        let mut bytes = Vec::new();
+        let Ok(_) = hash.encode(&mut bytes);
+        assert_eq!(SampledHash::decode(&mut bytes.as_slice()).unwrap(), hash);
+    }
+
+    #[test]
+    fn autotune_test() {
+        let full = Duration::from_micros(800);
+        assert_eq!(Coverage::autotune(Duration::from_millis(1), full), Coverage::FULL);
+        assert_eq!(Coverage::autotune(Duration::from_micros(100), full), Coverage::from_fraction(1, 8));
+        assert_eq!(Coverage::autotune(Duration::from_micros(99), full), Coverage::from_fraction(1, 16));
+        assert_eq!(Coverage::autotune(Duration::ZERO, full), Coverage::MIN_TUNED);
+    }
+}
@@ -0,0 +1,182 @@
+//! Opt-in signing of save files.
+//!
+//! A server distributing an authoritative world signs the save with a key derived from a
+//! passphrase, and clients holding the passphrase can check that the save is intact and was
+//! signed with it. The key is derived with blake3's `derive_key` under [KEY_CONTEXT], mixing in a
+//! per-save salt, and the signature is a keyed hash of the save's canonical (blake3) hash.
+//!
+//! Verification tells apart a save that was damaged ([SignatureError::Corrupt]) from one that is
+//! intact but was signed with a different passphrase ([SignatureError::WrongKey]).
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::domain::{DomainHash, SaveContent};
+
+/// The `derive_key` context for signing keys. Changing it invalidates every signature.
+pub const KEY_CONTEXT: &str = "manufactory 2026-01-01 save signing key v1";
+/// The `derive_key` context for key ids, which identify a key without revealing it.
+pub const KEY_ID_CONTEXT: &str = "manufactory 2026-01-01 save signing key id v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SignatureError {
+    /// The save doesn't match the hash it was signed with.
+    #[error("The save file is corrupt.")]
+    Corrupt,
+    /// The save is intact, but was signed with a different passphrase.
+    #[error("The save file was signed with a different key.")]
+    WrongKey,
+    /// The key matches, but the signature doesn't. The signature itself was altered.
+    #[error("The save file's signature is invalid.")]
+    InvalidSignature,
+}
+
+#[derive(Clone)]
+pub struct SigningKey {
+    key: [u8; 32],
+    salt: [u8; 16],
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("key_id", &self.key_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// Derives the key for `passphrase`. `salt` should be unique per save (the world seed works).
+    pub fn from_passphrase(passphrase: &str, salt: [u8; 16]) -> Self {
+        let mut material = Vec::with_capacity(salt.len() + passphrase.len());
+        material.extend_from_slice(&salt);
+        material.extend_from_slice(passphrase.as_bytes());
+        Self {
+            key: blake3::derive_key(KEY_CONTEXT, &material),
+            salt,
+        }
+    }
+
+    /// Identifies the key, so that a wrong passphrase can be told apart from a damaged save.
+    #[inline]
+    pub fn key_id(&self) -> [u8; 8] {
+        let id = blake3::derive_key(KEY_ID_CONTEXT, &self.key);
+        id[..8].try_into().unwrap()
+    }
+
+    fn mac(&self, content_hash: &DomainHash<SaveContent>) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, content_hash.as_bytes())
+    }
+
+    /// Signs the save `content` (the save's canonical encoding).
+    pub fn sign(&self, content: &[u8]) -> SaveSignature {
+        self.sign_hash(DomainHash::of_bytes(content))
+    }
+
+    /// Signs the save whose content hashes to `content_hash`, for saves that were hashed while they
+    /// were written.
+    pub fn sign_hash(&self, content_hash: DomainHash<SaveContent>) -> SaveSignature {
+        SaveSignature {
+            salt: self.salt,
+            key_id: self.key_id(),
+            content_hash,
+            mac: *self.mac(&content_hash).as_bytes(),
+        }
+    }
+}
+
+/// Stored alongside a signed save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SaveSignature {
+    pub salt: [u8; 16],
+    pub key_id: [u8; 8],
+    pub content_hash: DomainHash<SaveContent>,
+    pub mac: [u8; 32],
+}
+
+impl SaveSignature {
+    /// Checks that `content` is intact and was signed with the key for `passphrase`.
+    pub fn verify(&self, content: &[u8], passphrase: &str) -> Result<(), SignatureError> {
+        self.verify_with(content, &SigningKey::from_passphrase(passphrase, self.salt))
+    }
+
+    /// Like [SaveSignature::verify], with an already derived key.
+    pub fn verify_with(&self, content: &[u8], key: &SigningKey) -> Result<(), SignatureError> {
+        self.verify_hash_with(DomainHash::of_bytes(content), key)
+    }
+
+    /// Like [SaveSignature::verify], for saves that were hashed while they were read.
+    pub fn verify_hash(&self, content_hash: DomainHash<SaveContent>, passphrase: &str) -> Result<(), SignatureError> {
+        self.verify_hash_with(content_hash, &SigningKey::from_passphrase(passphrase, self.salt))
+    }
+
+    /// Like [SaveSignature::verify_hash], with an already derived key.
+    pub fn verify_hash_with(&self, content_hash: DomainHash<SaveContent>, key: &SigningKey) -> Result<(), SignatureError> {
+        if content_hash != self.content_hash {
+            return Err(SignatureError::Corrupt);
+        }
+        if key.salt != self.salt || key.key_id() != self.key_id {
+            return Err(SignatureError::WrongKey);
+        }
+        // `blake3::Hash` compares in constant time.
+        if key.mac(&self.content_hash) != blake3::Hash::from_bytes(self.mac) {
+            return Err(SignatureError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+// Layout: salt (16 bytes), key id (8 bytes), content hash (32 bytes), mac (32 bytes).
+impl Encode for SaveSignature {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_exact(&self.salt)?
+            + encoder.write_exact(&self.key_id)?
+            + self.content_hash.encode(encoder)?
+            + encoder.write_exact(&self.mac)?
+        )
+    }
+}
+
+impl Decode for SaveSignature {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut signature = Self {
+            salt: [0; 16],
+            key_id: [0; 8],
+            content_hash: DomainHash::from_bytes([0; 32]),
+            mac: [0; 32],
+        };
+        decoder.read_exact(&mut signature.salt)?;
+        decoder.read_exact(&mut signature.key_id)?;
+        signature.content_hash = DomainHash::decode(decoder)?;
+        decoder.read_exact(&mut signature.mac)?;
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signing_test() {
+        let save = b"world data".to_vec();
+        let key = SigningKey::from_passphrase("hunter2", [7; 16]);
+        let signature = key.sign(&save);
+        assert_eq!(signature.verify(&save, "hunter2"), Ok(()));
+        assert_eq!(signature.verify(&save, "hunter3"), Err(SignatureError::WrongKey));
+        assert_eq!(signature.verify(b"world dato", "hunter2"), Err(SignatureError::Corrupt));
+
+        let mut forged = signature;
+        forged.mac[0] ^= 1;
+        assert_eq!(forged.verify_with(&save, &key), Err(SignatureError::InvalidSignature));
+        assert_eq!(key.sign_hash(DomainHash::of_bytes(&save)), signature);
+        assert_eq!(signature.verify_hash(DomainHash::of_bytes(&save), "hunter3"), Err(SignatureError::WrongKey));
+
+        let mut bytes = Vec::new();
+        assert_eq!(signature.encode(&mut bytes).unwrap(), 88);
+        assert_eq!(SaveSignature::decode(&mut bytes.as_slice()).unwrap(), signature);
+    }
+}
@@ -0,0 +1,180 @@
+//! [HashSeed]s as [std::hash::BuildHasher]s, for salted `HashMap`s and `HashSet`s.
+//!
+//! `HashMap::with_hasher(seed.build_std_hasher())` hashes keys with blake3, keyed from the seed.
+//! The same seed hashes the same keys the same way on every machine and every run (integers are
+//! written little-endian, whatever the platform), and without the seed, nobody can pick keys that
+//! collide. Iteration order is still the map's own, so it's not something to depend on.
+//!
+//! Most keys are small, so a [SeededHasher] keeps up to one blake3 block in a buffer and finishes
+//! with a single [blake3::keyed_hash] of it. Longer inputs go through a full [blake3::Hasher], which
+//! gives the same hash.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::HashSeed;
+
+/// The inputs up to this long are hashed in one call.
+const BLOCK_LEN: usize = blake3::BLOCK_LEN;
+
+/// A `HashMap` salted with a [HashSeed].
+pub type SeededHashMap<K, V> = HashMap<K, V, BuildSeededHasher>;
+/// A `HashSet` salted with a [HashSeed].
+pub type SeededHashSet<T> = HashSet<T, BuildSeededHasher>;
+
+/// Builds [SeededHasher]s for a seed. See [HashSeed::build_std_hasher].
+#[derive(Clone, PartialEq, Eq)]
+pub struct BuildSeededHasher {
+    key: [u8; 32],
+}
+
+impl std::fmt::Debug for BuildSeededHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuildSeededHasher").finish_non_exhaustive()
+    }
+}
+
+impl BuildSeededHasher {
+    #[must_use]
+    pub fn new(seed: HashSeed) -> Self {
+        Self { key: seed.hash_256("mfhash/std-hasher") }
+    }
+}
+
+impl BuildHasher for BuildSeededHasher {
+    type Hasher = SeededHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> SeededHasher {
+        SeededHasher { key: self.key, buffer: [0; BLOCK_LEN], len: 0, stream: None }
+    }
+}
+
+/// A [Hasher] that hashes with blake3, keyed by a [BuildSeededHasher].
+#[derive(Clone)]
+pub struct SeededHasher {
+    key: [u8; 32],
+    buffer: [u8; BLOCK_LEN],
+    len: usize,
+    /// Takes over from the buffer once the input is longer than a block.
+    stream: Option<Box<blake3::Hasher>>,
+}
+
+impl std::fmt::Debug for SeededHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeededHasher").finish_non_exhaustive()
+    }
+}
+
+impl Hasher for SeededHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            stream.update(bytes);
+        } else if self.len + bytes.len() <= BLOCK_LEN {
+            self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        } else {
+            let mut stream = Box::new(blake3::Hasher::new_keyed(&self.key));
+            stream.update(&self.buffer[..self.len]).update(bytes);
+            self.stream = Some(stream);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let hash = match &self.stream {
+            Some(stream) => stream.finalize(),
+            None => blake3::keyed_hash(&self.key, &self.buffer[..self.len]),
+        };
+        u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap())
+    }
+
+    #[inline]
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Sizes are always 8 bytes wide, like in [DeterministicHasher](crate::deterministic::DeterministicHasher).
+    #[inline]
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, value: i16) {
+        self.write_u16(value as u16);
+    }
+
+    #[inline]
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+
+    #[inline]
+    fn write_i64(&mut self, value: i64) {
+        self.write_u64(value as u64);
+    }
+
+    #[inline]
+    fn write_i128(&mut self, value: i128) {
+        self.write_u128(value as u128);
+    }
+
+    #[inline]
+    fn write_isize(&mut self, value: isize) {
+        self.write_i64(value as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std_hasher_test() {
+        let seed = HashSeed::derived("mfhash::std_hash::std_hasher_test");
+        let mut map = SeededHashMap::with_hasher(seed.build_std_hasher());
+        for x in 0..100i32 {
+            map.insert((x, -x), x.to_string());
+        }
+        assert_eq!(map[&(42, -42)], "42");
+        let mut set = SeededHashSet::with_hasher(seed.build_std_hasher());
+        set.extend(["a", "b"].map(String::from));
+        assert!(set.contains("a"));
+
+        // Same seed, same hashes. Other seeds hash differently.
+        let state = seed.build_std_hasher();
+        assert_eq!(state.hash_one((1u64, "key")), seed.build_std_hasher().hash_one((1u64, "key")));
+        assert_ne!(state.hash_one((1u64, "key")), HashSeed::new().build_std_hasher().hash_one((1u64, "key")));
+        assert_eq!(state.hash_one(7usize), state.hash_one(7u64));
+
+        // Short and long inputs, however they're split, hash like one keyed blake3 hash.
+        let data = (0..200u8).collect::<Vec<_>>();
+        for len in [0, 1, 64, 65, 200] {
+            let expected = u64::from_le_bytes(blake3::keyed_hash(&state.key, &data[..len]).as_bytes()[..8].try_into().unwrap());
+            for split in [0, len / 2, len] {
+                let mut hasher = state.build_hasher();
+                hasher.write(&data[..split]);
+                hasher.write(&data[split..len]);
+                assert_eq!(hasher.finish(), expected, "len {len}, split at {split}");
+            }
+        }
+        assert_ne!(state.hash_one("long key".repeat(20)), state.hash_one("long key".repeat(19)));
+    }
+}
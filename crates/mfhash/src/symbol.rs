@@ -0,0 +1,77 @@
+//! Interned strings.
+//!
+//! A [Symbol] is a cheap, copyable handle to a string that lives for the rest of the program.
+//! Symbol ids depend on interning order, so they must never be hashed or saved. Hash or save
+//! [Symbol::as_str] instead.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    strings: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Mutex::default)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Interns `s`. Each distinct string is only ever allocated once.
+    pub fn intern(s: &str) -> Self {
+        let mut interner = interner().lock().unwrap_or_else(|poison| poison.into_inner());
+        if let Some(&id) = interner.ids.get(s) {
+            return Self(id);
+        }
+        let id = interner.strings.len() as u32;
+        let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+        interner.strings.push(s);
+        interner.ids.insert(s, id);
+        Self(id)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let interner = interner().lock().unwrap_or_else(|poison| poison.into_inner());
+        interner.strings[self.0 as usize]
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Symbol {
+    #[inline]
+    fn from(value: &str) -> Self {
+        Self::intern(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_test() {
+        let mesh = Symbol::intern("mesh");
+        assert_eq!(Symbol::intern("mesh"), mesh);
+        assert_ne!(Symbol::intern("light"), mesh);
+        assert_eq!(mesh.as_str(), "mesh");
+        assert_eq!(Symbol::from("mesh"), mesh);
+    }
+}
@@ -0,0 +1,101 @@
+//! Integrity tags for stored data.
+//!
+//! A [HashVerifier] tags data (such as a chunk's encoding in a region file) when it's written, and
+//! checks the tag when it's read back. With a [keyed](HashSeed::keyed) or
+//! [derived](HashSeed::derived) seed the tag is a keyed hash, so tags from another world or
+//! another purpose never verify; with the default seed it's a plain blake3 hash, which only
+//! catches damage.
+//!
+//! Tags are compared in constant time, so checking a tag doesn't leak how much of it matched.
+
+use crate::{Blake3Hasher, HashSeed};
+
+/// A 32 byte integrity tag.
+pub type Tag = [u8; 32];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HashVerifier {
+    seed: HashSeed,
+}
+
+impl HashVerifier {
+    #[inline]
+    #[must_use]
+    pub const fn new(seed: HashSeed) -> Self {
+        Self { seed }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn seed(&self) -> HashSeed {
+        self.seed
+    }
+
+    /// The tag of `data`.
+    #[must_use]
+    pub fn tag(&self, data: &[u8]) -> Tag {
+        *self.hasher().update(data).finalize().as_bytes()
+    }
+
+    /// Whether `tag` is the tag of `data`.
+    #[must_use]
+    pub fn verify(&self, data: &[u8], tag: &Tag) -> bool {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        self.verify_hasher(&hasher, tag)
+    }
+
+    /// A hasher for tagging data incrementally, fed with [Blake3Hasher::update],
+    /// [Blake3Hasher::update_reader] and the like. Feeding it data in pieces gives the same tag as
+    /// [Self::tag] of the whole data.
+    #[inline]
+    #[must_use]
+    pub fn hasher(&self) -> Blake3Hasher {
+        self.seed.build_hasher()
+    }
+
+    /// The tag of the data fed to `hasher` (from [Self::hasher]).
+    #[inline]
+    #[must_use]
+    pub fn finish(&self, hasher: &Blake3Hasher) -> Tag {
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Whether `tag` is the tag of the data fed to `hasher` (from [Self::hasher]).
+    #[inline]
+    #[must_use]
+    pub fn verify_hasher(&self, hasher: &Blake3Hasher, tag: &Tag) -> bool {
+        // `blake3::Hash` compares in constant time.
+        hasher.finalize() == blake3::Hash::from_bytes(*tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_verifier_test() {
+        let verifier = HashVerifier::new(HashSeed::derived("mfhash::verify::hash_verifier_test"));
+        let data = b"chunk 0, 0, 0: 4096 voxels of air";
+        let tag = verifier.tag(data);
+        assert!(verifier.verify(data, &tag));
+
+        let mut damaged = *data;
+        damaged[7] ^= 1;
+        assert!(!verifier.verify(&damaged, &tag));
+        let mut forged = tag;
+        forged[31] ^= 1;
+        assert!(!verifier.verify(data, &forged));
+        // Other seeds make other tags.
+        assert!(!HashVerifier::default().verify(data, &tag));
+        assert_eq!(HashVerifier::default().tag(data), *blake3::hash(data).as_bytes());
+
+        // Incremental tags match whole ones.
+        let mut hasher = verifier.hasher();
+        hasher.update(&data[..10]);
+        assert_eq!(hasher.update_reader(&data[10..]).unwrap(), data.len() as u64 - 10);
+        assert_eq!(verifier.finish(&hasher), tag);
+        assert!(verifier.verify_hasher(&hasher, &tag));
+    }
+}
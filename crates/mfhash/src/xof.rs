@@ -0,0 +1,198 @@
+//! Splitting one hash into independent streams of random bytes.
+//!
+//! Sharing one [OutputReader] between workers makes what each gets depend on the order they read
+//! in. An [XofSplit] instead derives a key per index from the hash and gives each index its own
+//! [XofStream], so worker `3` reads the same bytes however many workers there are and whichever
+//! finishes first.
+//!
+//! ```
+//! # use mfhash::HashSeed;
+//! let split = HashSeed::derived("mfhash xof example").split((12i32, -4i32));
+//! let mut workers = split.streams(4);
+//! assert_eq!(workers[2].next_u64(), split.stream(2).next_u64());
+//! ```
+
+use blake3::OutputReader;
+
+use crate::{deterministic::DeterministicHash, Blake3Hasher, HashSeed};
+
+const SUBSTREAM_CONTEXT: &str = "mfhash xof substream";
+
+/// A root key that streams are derived from by index.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct XofSplit {
+    key: [u8; 32],
+}
+
+impl XofSplit {
+    /// Splits the output of `hasher`.
+    #[must_use]
+    pub fn new(hasher: &Blake3Hasher) -> Self {
+        Self { key: blake3::derive_key(SUBSTREAM_CONTEXT, &hasher.finalize_bytes::<32>()) }
+    }
+
+    /// The stream at `index`.
+    #[must_use]
+    pub fn stream(&self, index: u64) -> XofStream {
+        let mut hasher = Blake3Hasher::new_keyed(&self.key);
+        hasher.update(&index.to_be_bytes());
+        XofStream::new(hasher.finalize_xof())
+    }
+
+    /// The streams at `0..count`.
+    #[must_use]
+    pub fn streams(&self, count: usize) -> Vec<XofStream> {
+        (0..count as u64).map(|index| self.stream(index)).collect()
+    }
+}
+
+// Don't show the key.
+impl std::fmt::Debug for XofSplit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XofSplit").finish_non_exhaustive()
+    }
+}
+
+/// A deterministic stream of random bytes and numbers read from a blake3 XOF.
+#[derive(Debug, Clone)]
+pub struct XofStream {
+    reader: OutputReader,
+    buffer: [u8; 64],
+    /// The number of bytes of `buffer` already used.
+    used: usize,
+}
+
+impl XofStream {
+    #[inline]
+    #[must_use]
+    pub fn new(reader: OutputReader) -> Self {
+        Self { reader, buffer: [0; 64], used: 64 }
+    }
+
+    /// Fills `out` with the next bytes of the stream.
+    pub fn fill(&mut self, mut out: &mut [u8]) {
+        while !out.is_empty() {
+            if self.used == self.buffer.len() {
+                self.reader.fill(&mut self.buffer);
+                self.used = 0;
+            }
+            let count = out.len().min(self.buffer.len() - self.used);
+            out[..count].copy_from_slice(&self.buffer[self.used..self.used + count]);
+            self.used += count;
+            out = &mut out[count..];
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_bytes<const LEN: usize>(&mut self) -> [u8; LEN] {
+        let mut bytes = [0; LEN];
+        self.fill(&mut bytes);
+        bytes
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_u8(&mut self) -> u8 {
+        u8::from_be_bytes(self.next_bytes())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_u32(&mut self) -> u32 {
+        u32::from_be_bytes(self.next_bytes())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.next_bytes())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_u128(&mut self) -> u128 {
+        u128::from_be_bytes(self.next_bytes())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u8() & 1 == 1
+    }
+
+    /// A number in `0..bound`, without modulo bias. `bound` must not be `0`.
+    #[must_use]
+    pub fn next_below(&mut self, bound: u64) -> u64 {
+        assert_ne!(bound, 0, "Bound must not be 0.");
+        // Reject the values past the last whole multiple of bound.
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+
+    /// A number in `0.0..1.0`.
+    #[inline]
+    #[must_use]
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+impl Blake3Hasher {
+    /// Splits the hash into independent streams. See [XofSplit].
+    #[inline]
+    #[must_use]
+    pub fn split_xof(&self) -> XofSplit {
+        XofSplit::new(self)
+    }
+}
+
+impl HashSeed {
+    /// Hashes `value` and splits the hash into independent streams. See [XofSplit].
+    #[inline]
+    #[must_use]
+    pub fn split<T: DeterministicHash>(self, value: T) -> XofSplit {
+        self.hash(value).split_xof()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xof_split_test() {
+        let split = HashSeed::new().split(("chunk", 7i32, -3i32));
+        let mut a = split.stream(0);
+        let mut b = split.stream(1);
+        let first = (a.next_u128(), b.next_u128());
+        assert_ne!(first.0, first.1);
+        // Reading one stream doesn't move the other.
+        let mut fresh = split.stream(1);
+        assert_eq!(fresh.next_u128(), first.1);
+        assert_ne!(HashSeed::new().split(("chunk", 7i32, -2i32)).stream(0).next_u128(), first.0);
+
+        // Reads of any size see the same bytes.
+        let mut whole = [0u8; 150];
+        split.stream(3).fill(&mut whole);
+        let mut pieces = split.stream(3);
+        let mut read = Vec::new();
+        for len in [1, 63, 2, 80, 4] {
+            let mut piece = vec![0u8; len];
+            pieces.fill(&mut piece);
+            read.extend(piece);
+        }
+        assert_eq!(read, whole);
+
+        let mut stream = split.stream(9);
+        for _ in 0..100 {
+            assert!(stream.next_below(7) < 7);
+            assert!((0.0..1.0).contains(&stream.next_f64()));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Save inspection tool.
+//!
+//! Prints what's in a save (header, dimensions, chunks), verifies chunk checksums, and extracts or
+//! replaces single chunks for debugging. Also pregenerates the chunks around a point.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use manufactory::game::{
+    save::dir::{SaveDir, SaveError},
+    world::pregen::{self, PregenArea},
+};
+use mfcereal::{decode::Decode, encode::Encode};
+use mfdata::object::Record;
+use mffmt::{
+    hex::HexBytes,
+    progress::{ConsoleSink, Progress},
+};
+use mfworld::{
+    chunk::{stored::StoredChunk, ChunkPos, CHUNK_MASK, CHUNK_SHIFT},
+    portal::DimensionId,
+    recovery::{self, CHECKSUM_LEN},
+};
+
+const USAGE: &str = "\
+Usage: mfinspect <save> <command> [arguments]
+
+Commands:
+    info                              Print the save header.
+    dims                              List the dimensions and how many chunks each has.
+    chunk <dim> <x> <y> <z>           Print a chunk's palette, orientations, and block entities.
+    verify                            Check the checksum and format of every chunk.
+    extract <dim> <x> <y> <z> <file>  Write a chunk's data (without its checksum) to <file>.
+    replace <dim> <x> <y> <z> <file>  Replace a chunk with data written by `extract`.
+    pregen <dim> <x> <z> <radius> [<min y> <max y>]
+                                      Generate and save the chunk columns within <radius> chunks
+                                      of chunk column <x> <z>, from chunk <min y> to <max y>
+                                      (default 0 to 7). Resumes an interrupted run.";
+
+/// The number of block entity bytes printed by `chunk`.
+const BLOCK_ENTITY_PREVIEW: usize = 16;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return;
+    }
+    match run(&args) {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Runs a command, returning whether the save passed (only `verify` and `pregen` can fail it).
+fn run(args: &[String]) -> Result<bool, String> {
+    let [save, command, rest @ ..] = args else {
+        return Err(format!("Missing command.\n\n{USAGE}"));
+    };
+    let save = SaveDir::open(save).map_err(|err| format!("Failed to open `{save}`: {err}"))?;
+    match (command.as_str(), rest) {
+        ("info", []) => info(&save)?,
+        ("dims", []) => dims(&save)?,
+        ("chunk", [dim, x, y, z]) => chunk(&save, parse_dimension(dim)?, parse_chunk(x, y, z)?)?,
+        ("verify", []) => return verify(&save),
+        ("extract", [dim, x, y, z, file]) => extract(&save, parse_dimension(dim)?, parse_chunk(x, y, z)?, file.into())?,
+        ("replace", [dim, x, y, z, file]) => replace(&save, parse_dimension(dim)?, parse_chunk(x, y, z)?, file.into())?,
+        ("pregen", [dim, x, z, radius, heights @ ..]) if matches!(heights.len(), 0 | 2) => {
+            let parse = |text: &String| text.parse::<i32>().map_err(|_| format!("Invalid coordinate: `{text}`"));
+            let mut area = PregenArea::new(parse(x)?, parse(z)?, radius.parse().map_err(|_| format!("Invalid radius: `{radius}`"))?);
+            if let [min_y, max_y] = heights {
+                (area.min_y, area.max_y) = (parse(min_y)?, parse(max_y)?);
+            }
+            return pregen(&save, parse_dimension(dim)?, area);
+        }
+        _ => return Err(format!("Invalid command: `{}`\n\n{USAGE}", args[1..].join(" "))),
+    }
+    Ok(true)
+}
+
+fn parse_dimension(text: &str) -> Result<DimensionId, String> {
+    text.parse().map(DimensionId).map_err(|_| format!("Invalid dimension: `{text}`"))
+}
+
+fn parse_chunk(x: &str, y: &str, z: &str) -> Result<ChunkPos, String> {
+    let parse = |text: &str| text.parse::<i32>().map_err(|_| format!("Invalid chunk coordinate: `{text}`"));
+    Ok(ChunkPos::new(parse(x)?, parse(y)?, parse(z)?))
+}
+
+fn error(err: SaveError) -> String {
+    err.to_string()
+}
+
+fn info(save: &SaveDir) -> Result<(), String> {
+    let header = save.read_header().map_err(error)?;
+    println!("save:      {}", save.root().display());
+    println!("version:   {}", header.version);
+    println!("seed:      {}", header.seed);
+    println!("game mode: {}", header.game_mode);
+    println!("generator:");
+    for field in header.generator.fields() {
+        println!("    {} = {}", field.name, field.value);
+    }
+    println!("rules:");
+    for (id, def) in header.rules.iter() {
+        println!("    {} = {}", def.name, header.rules.value(id));
+    }
+    Ok(())
+}
+
+fn dims(save: &SaveDir) -> Result<(), String> {
+    let dimensions = save.dimensions().map_err(error)?;
+    if dimensions.is_empty() {
+        println!("no dimensions");
+    }
+    for dimension in dimensions {
+        println!("dimension {}: {} chunks", dimension.0, save.chunks(dimension).map_err(error)?.len());
+    }
+    Ok(())
+}
+
+fn chunk(save: &SaveDir, dimension: DimensionId, pos: ChunkPos) -> Result<(), String> {
+    let stored = save.load_chunk(dimension, pos).map_err(error)?
+        .ok_or_else(|| format!("Chunk {pos} is not stored in dimension {}.", dimension.0))?;
+    println!("chunk {pos} in dimension {}", dimension.0);
+
+    println!("palette:");
+    for (index, (id, count)) in stored.palette().iter().zip(stored.palette_counts()).enumerate() {
+        println!("    {index:>4}: voxel {:<10} {count:>5} voxels", id.get());
+    }
+
+    let mut orientations = BTreeMap::new();
+    let mut invalid = 0usize;
+    for index in 0..mfworld::chunk::CHUNK_VOLUME {
+        match stored.orientation(index) {
+            Ok(orientation) => *orientations.entry(orientation.as_u8()).or_insert(0usize) += 1,
+            Err(_) => invalid += 1,
+        }
+    }
+    println!("orientations:");
+    for (&packed, count) in &orientations {
+        let orientation = mfgeometry::Orientation::from_u8_wrapping(packed);
+        println!("    {packed:>4} (up {:?}, forward {:?}): {count:>5} voxels", orientation.up(), orientation.forward());
+    }
+    if invalid != 0 {
+        println!("    invalid: {invalid} voxels");
+    }
+
+    println!("block entities: {}", stored.block_entities.len());
+    for (&index, data) in &stored.block_entities {
+        let local = local_position(index as usize);
+        let preview = &data[..data.len().min(BLOCK_ENTITY_PREVIEW)];
+        let ellipsis = if data.len() > BLOCK_ENTITY_PREVIEW { "..." } else { "" };
+        println!(
+            "    {local:?} voxel {}: {} bytes {}{ellipsis}",
+            stored.id(index as usize).get(),
+            data.len(),
+            HexBytes(preview),
+        );
+    }
+    Ok(())
+}
+
+/// The inverse of [mfworld::chunk::voxel_index].
+fn local_position(index: usize) -> (i32, i32, i32) {
+    let index = index as i32;
+    (index & CHUNK_MASK, index >> (CHUNK_SHIFT * 2), (index >> CHUNK_SHIFT) & CHUNK_MASK)
+}
+
+fn verify(save: &SaveDir) -> Result<bool, String> {
+    let mut passed = match save.read_header() {
+        Ok(_) => true,
+        Err(err) => {
+            println!("header: {err}");
+            false
+        }
+    };
+    let (mut total, mut bad) = (0usize, 0usize);
+    let mut progress = Progress::new(ConsoleSink::stderr());
+    let dimensions = save.dimensions().map_err(error)?;
+    progress.begin("verify", dimensions.len() as u64);
+    for dimension in dimensions {
+        let chunks = save.chunks(dimension).map_err(error)?;
+        progress.begin(&format!("dimension {}", dimension.0), chunks.len() as u64);
+        for pos in chunks {
+            total += 1;
+            if let Err(err) = save.load_chunk(dimension, pos) {
+                println!("dimension {}: {err}", dimension.0);
+                bad += 1;
+            }
+            progress.advance(1);
+        }
+        progress.end();
+        progress.advance(1);
+    }
+    progress.end();
+    println!("{total} chunks checked, {bad} bad");
+    passed &= bad == 0;
+    Ok(passed)
+}
+
+fn pregen(save: &SaveDir, dimension: DimensionId, area: PregenArea) -> Result<bool, String> {
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut progress = Progress::new(ConsoleSink::stderr());
+    let report = pregen::pregen(save, dimension, area, &[], threads, &mut progress).map_err(error)?;
+    println!(
+        "{} chunks generated ({} already saved, {} regions already done), {} chunks verified, {} bad",
+        report.generated,
+        report.existing,
+        report.resumed_regions,
+        report.verified,
+        report.bad.len(),
+    );
+    for chunk in &report.bad {
+        println!("bad chunk: {chunk}");
+    }
+    Ok(report.bad.is_empty())
+}
+
+fn extract(save: &SaveDir, dimension: DimensionId, pos: ChunkPos, file: PathBuf) -> Result<(), String> {
+    let blob = save.read_chunk_blob(dimension, pos).map_err(error)?
+        .ok_or_else(|| format!("Chunk {pos} is not stored in dimension {}.", dimension.0))?;
+    // Corrupt chunks are extracted anyway; that's usually why they're being extracted.
+    let payload = match recovery::unseal(&blob) {
+        Ok(payload) => payload,
+        Err(failure) if blob.len() >= CHECKSUM_LEN => {
+            eprintln!("warning: {failure}");
+            &blob[CHECKSUM_LEN..]
+        }
+        Err(failure) => return Err(failure.to_string()),
+    };
+    fs::write(&file, payload).map_err(|err| format!("Failed to write `{}`: {err}", file.display()))?;
+    println!("extracted {} bytes to {}", payload.len(), file.display());
+    Ok(())
+}
+
+fn replace(save: &SaveDir, dimension: DimensionId, pos: ChunkPos, file: PathBuf) -> Result<(), String> {
+    let payload = fs::read(&file).map_err(|err| format!("Failed to read `{}`: {err}", file.display()))?;
+    let mut input = payload.as_slice();
+    let stored = StoredChunk::decode(&mut input).map_err(|err| format!("`{}` is not a valid chunk: {err}", file.display()))?;
+    if !input.is_empty() {
+        return Err(format!("`{}` has {} bytes of trailing data.", file.display(), input.len()));
+    }
+    let mut canonical = Vec::new();
+    stored.encode(&mut canonical).expect("Encoding to a Vec can't fail.");
+    save.write_chunk_blob(dimension, pos, &recovery::seal(&canonical)).map_err(error)?;
+    println!("replaced chunk {pos} in dimension {}", dimension.0);
+    Ok(())
+}
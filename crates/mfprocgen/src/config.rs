@@ -0,0 +1,202 @@
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfdata::object::{Field, FieldError, Record, Value};
+
+use crate::{surface::SurfaceRules, veins::VeinRules};
+
+/// The terrain parameters of a world. Stored with the world so that missing chunks are always
+/// regenerated with the settings the world was created with.
+///
+/// Everything is integral so that generation doesn't depend on floating point behavior.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GeneratorConfig {
+    /// The width (in voxels) of a terrain noise cell. Larger values give broader hills.
+    pub terrain_scale: u32,
+    /// The height difference (in voxels) between the lowest and highest terrain.
+    pub terrain_amplitude: u32,
+    /// The average surface height.
+    pub base_height: i32,
+    /// Generate flat terrain at [Self::base_height].
+    pub flat: bool,
+    pub ores: bool,
+    /// Scales the richness of every vein, in eighths: `8` generates the veins as written.
+    ///
+    /// Before veins this was ore voxels per 1000 underground voxels. Stored values aren't
+    /// converted: the old default of `8` reads as the veins as written, and the other presets keep
+    /// their ratio to it.
+    pub ore_density: u32,
+    /// Columns whose surface is at or below this height are underwater.
+    pub sea_level: i32,
+    /// The width (in voxels) of a biome noise cell.
+    pub biome_scale: u32,
+    pub surface_rules: SurfaceRules,
+    pub veins: VeinRules,
+}
+
+/// A setting that generation can't run with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid generator setting `{field}`: {reason}")]
+pub struct InvalidConfig {
+    pub field: &'static str,
+    pub reason: &'static str,
+}
+
+impl GeneratorConfig {
+    pub const PRESETS: [&'static str; 4] = ["default", "flats", "mountains", "rich"];
+
+    /// Checks the settings that would make generation panic. Loaded configs should be checked
+    /// before any chunk is generated with them.
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        if self.terrain_scale == 0 {
+            return Err(InvalidConfig { field: "terrain_scale", reason: "must not be 0" });
+        }
+        if self.biome_scale == 0 {
+            return Err(InvalidConfig { field: "biome_scale", reason: "must not be 0" });
+        }
+        if i32::try_from(self.terrain_amplitude).is_err() {
+            return Err(InvalidConfig { field: "terrain_amplitude", reason: "too large" });
+        }
+        Ok(())
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let default = Self::default();
+        Some(match name {
+            "default" => default,
+            "flats" => Self { flat: true, ores: false, ..default },
+            "mountains" => Self { terrain_scale: 128, terrain_amplitude: 96, base_height: 80, ..default },
+            "rich" => Self { ore_density: 32, ..default },
+            _ => return None,
+        })
+    }
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            terrain_scale: 64,
+            terrain_amplitude: 24,
+            base_height: 64,
+            flat: false,
+            ores: true,
+            ore_density: 8,
+            sea_level: 60,
+            biome_scale: 256,
+            surface_rules: SurfaceRules::default(),
+            veins: VeinRules::default(),
+        }
+    }
+}
+
+impl Record for GeneratorConfig {
+    fn fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("terrain_scale", self.terrain_scale as i64),
+            Field::new("terrain_amplitude", self.terrain_amplitude as i64),
+            Field::new("base_height", self.base_height as i64),
+            Field::new("flat", self.flat),
+            Field::new("ores", self.ores),
+            Field::new("ore_density", self.ore_density as i64),
+            Field::new("sea_level", self.sea_level as i64),
+            Field::new("biome_scale", self.biome_scale as i64),
+            Field::new("surface_rules", self.surface_rules.to_string()),
+            Field::new("veins", self.veins.to_string()),
+        ]
+    }
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), FieldError> {
+        fn int<T: TryFrom<i64>>(name: &'static str, value: Value) -> Result<T, FieldError> {
+            value.as_int().and_then(|int| T::try_from(int).ok()).ok_or(FieldError::InvalidValue { name, value })
+        }
+        fn bool(name: &'static str, value: Value) -> Result<bool, FieldError> {
+            value.as_bool().ok_or(FieldError::InvalidValue { name, value })
+        }
+        match name {
+            "terrain_scale" => self.terrain_scale = int("terrain_scale", value)?,
+            "terrain_amplitude" => self.terrain_amplitude = int("terrain_amplitude", value)?,
+            "base_height" => self.base_height = int("base_height", value)?,
+            "flat" => self.flat = bool("flat", value)?,
+            "ores" => self.ores = bool("ores", value)?,
+            "ore_density" => self.ore_density = int("ore_density", value)?,
+            "sea_level" => self.sea_level = int("sea_level", value)?,
+            "biome_scale" => self.biome_scale = int("biome_scale", value)?,
+            "surface_rules" => {
+                let rules = value.as_str().and_then(|text| SurfaceRules::parse(text).ok());
+                self.surface_rules = rules.ok_or(FieldError::InvalidValue { name: "surface_rules", value })?;
+            }
+            "veins" => {
+                let rules = value.as_str().and_then(|text| VeinRules::parse(text).ok());
+                self.veins = rules.ok_or(FieldError::InvalidValue { name: "veins", value })?;
+            }
+            _ => return Err(FieldError::UnknownField(name.to_owned())),
+        }
+        Ok(())
+    }
+}
+
+// Layout: field count (u64), followed by each field as name (str), tag (u8), value.
+//      0 (Bool)  : bool
+//      1 (Int)   : i64
+//      2 (String): str
+// Fields missing from older saves keep their default values.
+impl Encode for GeneratorConfig {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let fields = self.fields();
+        let mut written = encoder.write_u64(fields.len() as u64)?;
+        for field in fields {
+            written += encoder.write_str(field.name)?;
+            written += match &field.value {
+                Value::Bool(value) => encoder.write_u8(0)? + encoder.write_bool(*value)?,
+                Value::Int(value) => encoder.write_u8(1)? + encoder.write_i64(*value)?,
+                Value::String(value) => encoder.write_u8(2)? + encoder.write_str(value)?,
+            };
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for GeneratorConfig {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut config = Self::default();
+        let count = decoder.read_u64()?;
+        for _ in 0..count {
+            let name = decoder.read_str()?;
+            let value = match decoder.read_u8()? {
+                0 => Value::Bool(decoder.read_bool()?),
+                1 => Value::Int(decoder.read_i64()?),
+                2 => Value::String(decoder.read_str()?),
+                _ => return Err(DecodeError::InvalidData("unknown generator setting tag")),
+            };
+            config.set_field(&name, value).map_err(|_| DecodeError::InvalidData("invalid generator setting"))?;
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_config_test() {
+        for name in GeneratorConfig::PRESETS {
+            let config = GeneratorConfig::preset(name).unwrap();
+            let mut bytes = Vec::new();
+            config.encode(&mut bytes).unwrap();
+            assert_eq!(GeneratorConfig::decode(&mut bytes.as_slice()).unwrap(), config);
+        }
+        let mut config = GeneratorConfig::default();
+        config.set_field("base_height", Value::Int(-12)).unwrap();
+        assert_eq!(config.base_height, -12);
+        assert!(config.set_field("terrain_scale", Value::Int(-1)).is_err());
+        assert!(config.set_field("flat", Value::Int(1)).is_err());
+        assert!(config.set_field("surface_rules", Value::String("fill 1".to_owned())).is_err());
+        config.set_field("surface_rules", Value::String("biome ice\ntop 9".to_owned())).unwrap();
+        assert_eq!(config.surface_rules.biome("ice").unwrap().top, Some(9));
+        assert!(config.validate().is_ok());
+        config.set_field("biome_scale", Value::Int(0)).unwrap();
+        assert_eq!(config.validate().unwrap_err().field, "biome_scale");
+    }
+}
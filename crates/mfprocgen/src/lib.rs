@@ -1,5 +1,16 @@
+pub mod config;
+pub mod loot;
+pub mod rng;
+pub mod stage;
+pub mod stream;
+pub mod structure;
+pub mod surface;
+pub mod veins;
 pub mod world_seed;
 
+pub use config::GeneratorConfig;
+pub use rng::SeededRng;
+
 /* What do I need?
 This procedural generation library will be made specifically for manufactory.
 That means that it doesn't need to be entirely general purpose.
@@ -9,4 +20,4 @@ A way to produce deterministic, platform independent 32-bit seeds from hashable
 Noise generation: TBD
 Regional ID system
 
-*/
\ No newline at end of file
+*/
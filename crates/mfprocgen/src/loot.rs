@@ -0,0 +1,106 @@
+//! Fills the containers of placed structures with loot.
+//!
+//! Each structure gets a seed derived from the world seed, its placer, and its bounds, and each of
+//! its containers gets a random stream derived from that seed and the container's index. Loot only
+//! depends on those, so regenerating a structure always produces the same loot in the same slots.
+//!
+//! Rolling loot tables is left to the game through [LootSource].
+
+use mfhash::HashSeed;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{rng::SeededRng, stage::GenContext, structure::StructureBox};
+
+pub const STAGE: &str = "loot";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LootItem {
+    pub item: u32,
+    pub count: u32,
+}
+
+/// Rolls loot tables.
+pub trait LootSource: Sync {
+    /// Rolls `table`. All randomness must come from `rng`.
+    fn roll(&self, table: u32, rng: &mut ChaCha8Rng) -> Vec<LootItem>;
+}
+
+/// A container in a structure, in the order the structure lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LootContainer {
+    pub position: (i32, i32, i32),
+    pub table: u32,
+    pub slots: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FilledContainer {
+    pub position: (i32, i32, i32),
+    pub slots: Vec<Option<LootItem>>,
+}
+
+/// The seed of the structure placed by the placer called `placer` at `bounds`.
+#[inline]
+pub fn structure_seed(ctx: &GenContext, placer: &'static str, bounds: StructureBox) -> HashSeed {
+    ctx.stage_seed(STAGE).reseed_hashed((placer, bounds.min, bounds.max), None)
+}
+
+/// The random stream of the container at `index` in a structure.
+#[inline]
+pub fn container_rng(structure: HashSeed, index: u32) -> ChaCha8Rng {
+    SeededRng::new(structure, index).into_inner()
+}
+
+/// Rolls the loot of each container and scatters it over random slots. Items that don't fit are
+/// dropped.
+pub fn fill<L: LootSource + ?Sized>(structure: HashSeed, containers: &[LootContainer], loot: &L) -> Vec<FilledContainer> {
+    containers.iter().enumerate().map(|(index, container)| {
+        let mut rng = container_rng(structure, index as u32);
+        let items = loot.roll(container.table, &mut rng);
+        let mut order = (0..container.slots as usize).collect::<Vec<_>>();
+        order.shuffle(&mut rng);
+        let mut slots = vec![None; container.slots as usize];
+        for (slot, item) in order.into_iter().zip(items.into_iter().filter(|item| item.count != 0)) {
+            slots[slot] = Some(item);
+        }
+        FilledContainer { position: container.position, slots }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::config::GeneratorConfig;
+
+    struct Junk;
+
+    impl LootSource for Junk {
+        fn roll(&self, table: u32, rng: &mut ChaCha8Rng) -> Vec<LootItem> {
+            (0..rng.random_range(1..=5)).map(|_| LootItem { item: table, count: rng.random_range(1..=16) }).collect()
+        }
+    }
+
+    #[test]
+    fn fill_test() {
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(3, &config);
+        let bounds = StructureBox::new((10, 64, -20), (8, 8, 8));
+        let containers = [
+            LootContainer { position: (11, 65, -19), table: 1, slots: 27 },
+            LootContainer { position: (12, 65, -19), table: 2, slots: 3 },
+        ];
+        let filled = fill(structure_seed(&ctx, "towers", bounds), &containers, &Junk);
+        assert_eq!(filled, fill(structure_seed(&ctx, "towers", bounds), &containers, &Junk));
+        assert_ne!(filled, fill(structure_seed(&ctx, "huts", bounds), &containers, &Junk));
+        assert_eq!(filled[0].slots.len(), 27);
+        assert!(filled[0].slots.iter().flatten().all(|item| item.item == 1));
+        assert!(filled[1].slots.iter().flatten().count() <= 3);
+
+        // Adding containers doesn't change the loot of the ones before them.
+        let alone = fill(structure_seed(&ctx, "towers", bounds), &containers[..1], &Junk);
+        assert_eq!(alone[0], filled[0]);
+    }
+}
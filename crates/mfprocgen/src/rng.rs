@@ -0,0 +1,98 @@
+//! Random streams derived from a [HashSeed] and a key.
+//!
+//! A [SeededRng] is the stream for one key (a region, a structure, a container) under a seed.
+//! Nested stages [fork](SeededRng::fork) child streams by label instead of deriving seeds by hand.
+//! A child only depends on its parent's seed and key and its own label, never on how much of the
+//! parent has been drawn, so adding a draw to one stage doesn't reshuffle the stages under it.
+
+use mfhash::{deterministic::DeterministicHash, HashSeed};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const FORK_CONTEXT: &str = "mfprocgen/rng-fork";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    /// The seed that children are forked from.
+    seed: HashSeed,
+    rng: ChaCha8Rng,
+}
+
+impl SeededRng {
+    /// The stream for `key` under `seed`. This is the stream of
+    /// `ChaCha8Rng::from_seed(seed.hash_256(key))`, so streams that were built by hand keep their
+    /// values when they're moved to this.
+    #[must_use]
+    pub fn new<K: DeterministicHash>(seed: HashSeed, key: K) -> Self {
+        Self {
+            seed: seed.reseed_hashed(&key, Some(FORK_CONTEXT)),
+            rng: ChaCha8Rng::from_seed(seed.hash_256(key)),
+        }
+    }
+
+    /// The child stream called `label`. Forking the same label twice gives the same stream.
+    #[must_use]
+    pub fn fork<L: DeterministicHash>(&self, label: L) -> Self {
+        Self::new(self.seed, label)
+    }
+
+    /// The seed that children are forked from, for hashing values (priorities, per-position noise)
+    /// that shouldn't come from the stream.
+    #[inline]
+    #[must_use]
+    pub const fn seed(&self) -> HashSeed {
+        self.seed
+    }
+
+    /// The stream itself, for APIs that take a [ChaCha8Rng].
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> ChaCha8Rng {
+        self.rng
+    }
+}
+
+impl RngCore for SeededRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.rng.fill_bytes(dst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_rng_test() {
+        let seed = HashSeed::derived("mfprocgen::rng::seeded_rng_test");
+        let mut rng = SeededRng::new(seed, ("region", 3i32, -2i32));
+        let mut by_hand = ChaCha8Rng::from_seed(seed.hash_256(("region", 3i32, -2i32)));
+        assert_eq!(rng.next_u64(), by_hand.next_u64());
+
+        // Forks don't depend on draws from the parent, and differ by label.
+        let caves = rng.fork("caves").next_u64();
+        rng.next_u64();
+        assert_eq!(rng.fork("caves").next_u64(), caves);
+        assert_ne!(rng.fork("ores").next_u64(), caves);
+        assert_ne!(rng.fork("caves").fork("caves").next_u64(), caves);
+        // Or across parents.
+        assert_ne!(SeededRng::new(seed, ("region", 3i32, -1i32)).fork("caves").next_u64(), caves);
+        assert_ne!(rng.seed(), seed);
+    }
+}
@@ -0,0 +1,122 @@
+//! Generation stages. Every stage reads its parameters from the [GeneratorConfig] in the
+//! [GenContext], and its randomness from a seed derived for that stage alone, so a chunk
+//! generates identically no matter when (or in what order) it is generated.
+
+use mfhash::HashSeed;
+
+use crate::{
+    config::GeneratorConfig,
+    surface::{self, BiomeSurface, SurfaceColumn},
+    veins::{self, VeinRule},
+};
+
+/// Everything a stage needs to generate.
+#[derive(Debug, Clone, Copy)]
+pub struct GenContext<'a> {
+    pub world_seed: u64,
+    pub config: &'a GeneratorConfig,
+    seed: HashSeed,
+}
+
+impl<'a> GenContext<'a> {
+    pub fn new(world_seed: u64, config: &'a GeneratorConfig) -> Self {
+        Self {
+            world_seed,
+            config,
+            seed: HashSeed::derive_keyed(&world_seed.to_le_bytes(), Some("manufactory/procgen")),
+        }
+    }
+
+    /// The seed for the stage called `stage`.
+    #[inline]
+    pub fn stage_seed(&self, stage: &'static str) -> HashSeed {
+        self.seed.reseed_hashed(stage, None)
+    }
+
+    /// The surface height of the column at `(x, z)`.
+    #[inline]
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        terrain::height_at(self, x, z)
+    }
+
+    /// Whether the voxel at `(x, y, z)` is ore.
+    #[inline]
+    pub fn ore_at(&self, x: i32, y: i32, z: i32) -> bool {
+        self.vein_at(x, y, z).is_some()
+    }
+
+    /// The vein the voxel at `(x, y, z)` is ore of, if any.
+    #[inline]
+    pub fn vein_at(&self, x: i32, y: i32, z: i32) -> Option<&'a VeinRule> {
+        veins::vein_at(self, x, y, z)
+    }
+
+    /// The biome of the column at `(x, z)`, or `None` if the surface rules have no biomes.
+    #[inline]
+    pub fn biome_at(&self, x: i32, z: i32) -> Option<&'a BiomeSurface> {
+        surface::biome_at(self, x, z)
+    }
+
+    /// The index of the biome of the column at `(x, z)` in the surface rules, or `None` if they have
+    /// no biomes.
+    #[inline]
+    pub fn biome_index_at(&self, x: i32, z: i32) -> Option<usize> {
+        surface::biome_index_at(self, x, z)
+    }
+
+    /// The surface rules of the column at `(x, z)`, for filling it.
+    #[inline]
+    pub fn surface_column(&self, x: i32, z: i32) -> SurfaceColumn<'a> {
+        SurfaceColumn::new(self, x, z)
+    }
+}
+
+/// 2D value noise in `0..65536`, with lattice points `scale` voxels apart.
+pub(crate) fn value_noise(seed: HashSeed, x: i32, z: i32, scale: u32) -> i64 {
+    let scale = scale.max(1) as i64;
+    let (x, z) = (x as i64, z as i64);
+    let (cell_x, cell_z) = (x.div_euclid(scale), z.div_euclid(scale));
+    let (fx, fz) = (x.rem_euclid(scale), z.rem_euclid(scale));
+    let lattice = |dx: i64, dz: i64| (seed.hash_u32((cell_x + dx, cell_z + dz)) >> 16) as i64;
+    let near = lattice(0, 0) * (scale - fx) + lattice(1, 0) * fx;
+    let far = lattice(0, 1) * (scale - fx) + lattice(1, 1) * fx;
+    (near * (scale - fz) + far * fz) / (scale * scale)
+}
+
+pub mod terrain {
+    use super::*;
+
+    pub const STAGE: &str = "terrain";
+
+    pub fn height_at(ctx: &GenContext, x: i32, z: i32) -> i32 {
+        let config = ctx.config;
+        if config.flat {
+            return config.base_height;
+        }
+        let noise = value_noise(ctx.stage_seed(STAGE), x, z, config.terrain_scale);
+        let offset = ((noise - 32768) * config.terrain_amplitude as i64) >> 16;
+        config.base_height + offset as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_test() {
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(0xDEADBEEF, &config);
+        let heights = (0..256).map(|x| ctx.height_at(x, -x)).collect::<Vec<_>>();
+        let again = GenContext::new(0xDEADBEEF, &config);
+        assert!((0..256).all(|x| again.height_at(x, -x) == heights[x as usize]));
+        let half = config.terrain_amplitude as i32 / 2;
+        assert!(heights.iter().all(|&height| (config.base_height - half..=config.base_height + half).contains(&height)));
+        assert!(heights.iter().any(|&height| height != heights[0]));
+
+        let flats = GeneratorConfig::preset("flats").unwrap();
+        let ctx = GenContext::new(0xDEADBEEF, &flats);
+        assert_eq!(ctx.height_at(1000, -1000), flats.base_height);
+        assert!(!ctx.ore_at(0, 0, 0));
+    }
+}
@@ -0,0 +1,257 @@
+//! Generating many chunks on worker threads, handing each one over as soon as it and every chunk
+//! before it are done.
+//!
+//! Workers take chunks in order and finish them in whatever order they finish, so results go
+//! through a reordering buffer: a chunk is passed to the sink once every chunk before it has been.
+//! Consumers (pregeneration, a server filling in terrain) can save each chunk as it arrives, while
+//! the workers carry on, and what they do with the chunks happens in the same order however many
+//! threads there are. Workers stay at most a few chunks per thread ahead of the sink, so a slow
+//! chunk doesn't let the buffer grow without bound.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex,
+    },
+};
+
+use crate::{config::GeneratorConfig, stage::GenContext};
+
+/// How many chunks per worker may be finished ahead of the sink.
+const WINDOW_PER_WORKER: usize = 4;
+
+/// A box of chunks, `min` inclusive and `max` exclusive, in chunk coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkRegion {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl ChunkRegion {
+    #[inline]
+    #[must_use]
+    pub const fn new(min: (i32, i32, i32), max: (i32, i32, i32)) -> Self {
+        Self { min, max }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.min.0 >= self.max.0 || self.min.1 >= self.max.1 || self.min.2 >= self.max.2
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        self.max.0.abs_diff(self.min.0) as usize * self.max.1.abs_diff(self.min.1) as usize * self.max.2.abs_diff(self.min.2) as usize
+    }
+
+    /// The chunks in the order they're streamed: column by column (`z`, then `x`), each from the
+    /// bottom up.
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32, i32)> + use<> {
+        let Self { min, max } = *self;
+        (min.2..max.2).flat_map(move |z| {
+            (min.0..max.0).flat_map(move |x| (min.1..max.1).map(move |y| (x, y, z)))
+        })
+    }
+
+    /// The chunk at `index` in the order of [ChunkRegion::chunks], without walking the ones
+    /// before it.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than [ChunkRegion::len].
+    #[must_use]
+    pub const fn chunk_at(&self, index: usize) -> (i32, i32, i32) {
+        assert!(index < self.len(), "chunk index out of range");
+        let height = self.max.1.abs_diff(self.min.1) as usize;
+        let width = self.max.0.abs_diff(self.min.0) as usize;
+        (
+            self.min.0.wrapping_add(((index / height) % width) as i32),
+            self.min.1.wrapping_add((index % height) as i32),
+            self.min.2.wrapping_add((index / (height * width)) as i32),
+        )
+    }
+}
+
+/// Generates every chunk of `region` with `generate` on up to `parallelism` threads, passing each
+/// to `sink` in the order of [ChunkRegion::chunks] (see the [module docs](self)).
+///
+/// # Panics
+/// Panics if `generate` or `sink` panics.
+pub fn generate_region_stream<T, G, F>(
+    seed: u64,
+    config: &GeneratorConfig,
+    region: ChunkRegion,
+    parallelism: usize,
+    generate: G,
+    sink: F,
+) where
+    T: Send,
+    G: Fn(&GenContext, (i32, i32, i32)) -> T + Sync,
+    F: FnMut((i32, i32, i32), T),
+{
+    let ctx = GenContext::new(seed, config);
+    stream_indexed(&ctx, region.len(), |index| region.chunk_at(index), parallelism, generate, sink);
+}
+
+/// Generates `chunks` with `generate` on up to `parallelism` threads, passing each to `sink` in
+/// the order of `chunks`.
+///
+/// # Panics
+/// Panics if `generate` or `sink` panics.
+pub fn generate_stream<T, G, F>(ctx: &GenContext, chunks: &[(i32, i32, i32)], parallelism: usize, generate: G, sink: F)
+where
+    T: Send,
+    G: Fn(&GenContext, (i32, i32, i32)) -> T + Sync,
+    F: FnMut((i32, i32, i32), T),
+{
+    stream_indexed(ctx, chunks.len(), |index| chunks[index], parallelism, generate, sink);
+}
+
+/// Streams the `len` chunks given by `chunk_at`, so a region never has to be collected up front.
+fn stream_indexed<T, C, G, F>(ctx: &GenContext, len: usize, chunk_at: C, parallelism: usize, generate: G, mut sink: F)
+where
+    T: Send,
+    C: Fn(usize) -> (i32, i32, i32) + Sync,
+    G: Fn(&GenContext, (i32, i32, i32)) -> T + Sync,
+    F: FnMut((i32, i32, i32), T),
+{
+    let workers = parallelism.clamp(1, len.max(1));
+    if workers == 1 {
+        for index in 0..len {
+            let chunk = chunk_at(index);
+            sink(chunk, generate(ctx, chunk));
+        }
+        return;
+    }
+    let window = workers * WINDOW_PER_WORKER;
+    let next = AtomicUsize::new(0);
+    let aborted = AtomicBool::new(false);
+    // The number of chunks passed to the sink, which workers wait on to stay within the window.
+    let emitted = (Mutex::new(0usize), Condvar::new());
+    let (generate, chunk_at) = (&generate, &chunk_at);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next, aborted, emitted) = (&next, &aborted, &emitted);
+            scope.spawn(move || {
+                let _abort = AbortOnPanic { aborted, emitted };
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= len {
+                        return;
+                    }
+                    let (count, ready) = emitted;
+                    let mut count = count.lock().expect("stream lock poisoned");
+                    while index >= *count + window && !aborted.load(Ordering::Relaxed) {
+                        count = ready.wait(count).expect("stream lock poisoned");
+                    }
+                    drop(count);
+                    if aborted.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let payload = generate(ctx, chunk_at(index));
+                    if sender.send((index, payload)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // A panicking sink has to release the workers too, or the scope waits on them forever.
+        let _abort = AbortOnPanic { aborted: &aborted, emitted: &emitted };
+        let mut pending = BTreeMap::new();
+        let mut done = 0;
+        while done < len {
+            let Ok((index, payload)) = receiver.recv() else {
+                // Every worker stopped early, so one of them panicked. The scope rethrows it.
+                break;
+            };
+            pending.insert(index, payload);
+            let before = done;
+            while let Some(payload) = pending.remove(&done) {
+                sink(chunk_at(done), payload);
+                done += 1;
+            }
+            if done != before {
+                *emitted.0.lock().expect("stream lock poisoned") = done;
+                emitted.1.notify_all();
+            }
+        }
+    });
+}
+
+/// Wakes every waiting worker to stop when a worker or the sink panics, so the workers don't wait
+/// forever for a chunk that will never be finished or passed on.
+struct AbortOnPanic<'a> {
+    aborted: &'a AtomicBool,
+    emitted: &'a (Mutex<usize>, Condvar),
+}
+
+impl Drop for AbortOnPanic<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.aborted.store(true, Ordering::Relaxed);
+            // Taking the lock orders the store before any worker's next check.
+            drop(self.emitted.0.lock());
+            self.emitted.1.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn column_sum(ctx: &GenContext, (x, y, z): (i32, i32, i32)) -> i64 {
+        // Early chunks take longest, so workers finish out of order.
+        std::thread::sleep(Duration::from_micros(200u64.saturating_sub(x.unsigned_abs() as u64 * 20)));
+        (0..4).map(|dx| ctx.height_at(x * 4 + dx, z * 4) as i64).sum::<i64>() + y as i64
+    }
+
+    #[test]
+    fn region_stream_test() {
+        let config = GeneratorConfig::default();
+        let region = ChunkRegion::new((-3, 0, -2), (5, 2, 2));
+        assert_eq!(region.len(), 64);
+        let ctx = GenContext::new(9, &config);
+        let expected = region.chunks().map(|chunk| (chunk, column_sum(&ctx, chunk))).collect::<Vec<_>>();
+        for parallelism in [1, 3, 8] {
+            let mut streamed = Vec::new();
+            generate_region_stream(9, &config, region, parallelism, column_sum, |chunk, sum| streamed.push((chunk, sum)));
+            assert_eq!(streamed, expected, "{parallelism} threads");
+        }
+        for (index, chunk) in region.chunks().enumerate() {
+            assert_eq!(region.chunk_at(index), chunk);
+        }
+        generate_region_stream(9, &config, ChunkRegion::new((0, 0, 0), (0, 4, 4)), 4, column_sum, |_, _| panic!("empty region"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn region_stream_panic_test() {
+        let config = GeneratorConfig::default();
+        generate_region_stream(1, &config, ChunkRegion::new((0, 0, 0), (16, 1, 1)), 4, |_, (x, _, _)| {
+            assert_ne!(x, 5);
+        }, |_, ()| ());
+    }
+
+    #[test]
+    #[should_panic(expected = "sink failed")]
+    fn region_stream_sink_panic_test() {
+        let config = GeneratorConfig::default();
+        let region = ChunkRegion::new((0, 0, 0), (8, 1, 8));
+        let mut received = 0;
+        generate_region_stream(1, &config, region, 2, column_sum, |_, _| {
+            received += 1;
+            assert_ne!(received, 3, "sink failed");
+        });
+    }
+}
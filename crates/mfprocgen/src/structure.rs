@@ -0,0 +1,259 @@
+//! Claims that keep structures spanning several chunks from overlapping.
+//!
+//! Structures are proposed per region by [StructurePlacer]s, from a random stream derived only
+//! from the world seed, the placer, and the region. A proposal is kept unless it overlaps a proposal
+//! with a higher priority (also derived from seeds). Because that decision only looks at proposals
+//! from nearby regions, which any chunk can recompute on its own, every chunk agrees on which
+//! structures exist without ever talking to its neighbors or caring about generation order.
+
+use std::collections::BTreeMap;
+
+use rand_chacha::ChaCha8Rng;
+
+use crate::{rng::SeededRng, stage::GenContext};
+
+pub const STAGE: &str = "structures";
+/// The width of a region along X and Z in voxels. Structures can't be wider than a region.
+pub const REGION_SIZE: i32 = 128;
+
+/// A box of voxels, `min` inclusive and `max` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StructureBox {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl StructureBox {
+    #[inline]
+    pub const fn new(min: (i32, i32, i32), size: (i32, i32, i32)) -> Self {
+        Self {
+            min,
+            max: (min.0 + size.0, min.1 + size.1, min.2 + size.2),
+        }
+    }
+
+    #[inline]
+    pub const fn intersects(self, other: Self) -> bool {
+        self.min.0 < other.max.0 && other.min.0 < self.max.0
+        && self.min.1 < other.max.1 && other.min.1 < self.max.1
+        && self.min.2 < other.max.2 && other.min.2 < self.max.2
+    }
+
+    #[inline]
+    pub const fn contains(self, (x, y, z): (i32, i32, i32)) -> bool {
+        self.min.0 <= x && x < self.max.0
+        && self.min.1 <= y && y < self.max.1
+        && self.min.2 <= z && z < self.max.2
+    }
+}
+
+/// A column of [REGION_SIZE] x [REGION_SIZE] voxels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    #[inline]
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    #[inline]
+    pub const fn containing(x: i32, z: i32) -> Self {
+        Self::new(x.div_euclid(REGION_SIZE), z.div_euclid(REGION_SIZE))
+    }
+
+    #[inline]
+    pub const fn offset(self, dx: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.z + dz)
+    }
+
+    /// The region as a box covering every height.
+    #[inline]
+    pub const fn bounds(self) -> StructureBox {
+        StructureBox {
+            min: (self.x * REGION_SIZE, i32::MIN, self.z * REGION_SIZE),
+            max: ((self.x + 1) * REGION_SIZE, i32::MAX, (self.z + 1) * REGION_SIZE),
+        }
+    }
+}
+
+/// Proposes where a kind of structure goes.
+pub trait StructurePlacer: Sync {
+    /// A name unique among placers, used to derive seeds.
+    fn name(&self) -> &'static str;
+
+    /// Proposes structures whose `min` corner is in `region`. Every proposal must be at most
+    /// [REGION_SIZE] wide along X and Z. All randomness must come from `rng`, so that the
+    /// proposals are the same every time the region is asked for.
+    fn propose(&self, ctx: &GenContext, region: RegionPos, rng: &mut ChaCha8Rng) -> Vec<StructureBox>;
+}
+
+/// A structure that won its space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Claim {
+    /// The index of the placer in the list given to [ClaimRegistry::new].
+    pub placer: usize,
+    pub bounds: StructureBox,
+    priority: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Proposal {
+    placer: usize,
+    bounds: StructureBox,
+    priority: u64,
+}
+
+impl Proposal {
+    /// Orders proposals by priority, breaking ties so that the order is total.
+    #[inline]
+    fn key(&self) -> (u64, usize, StructureBox) {
+        (self.priority, self.placer, self.bounds)
+    }
+}
+
+/// Caches the claims of each region for a set of placers.
+pub struct ClaimRegistry<'a> {
+    placers: Vec<&'a dyn StructurePlacer>,
+    proposals: BTreeMap<RegionPos, Vec<Proposal>>,
+    claims: BTreeMap<RegionPos, Vec<Claim>>,
+}
+
+impl<'a> ClaimRegistry<'a> {
+    pub fn new(placers: Vec<&'a dyn StructurePlacer>) -> Self {
+        Self {
+            placers,
+            proposals: BTreeMap::new(),
+            claims: BTreeMap::new(),
+        }
+    }
+
+    fn proposals(&mut self, ctx: &GenContext, region: RegionPos) -> &[Proposal] {
+        let placers = &self.placers;
+        self.proposals.entry(region).or_insert_with(|| {
+            let seed = ctx.stage_seed(STAGE);
+            let mut proposals = Vec::new();
+            for (placer, structure) in placers.iter().enumerate() {
+                let name = structure.name();
+                let mut rng = SeededRng::new(seed, (name, region.x, region.z));
+                for bounds in structure.propose(ctx, region, rng.rng_mut()) {
+                    debug_assert!(
+                        bounds.max.0 - bounds.min.0 <= REGION_SIZE && bounds.max.2 - bounds.min.2 <= REGION_SIZE,
+                        "{name} proposed a structure wider than a region: {bounds:?}",
+                    );
+                    debug_assert_eq!(RegionPos::containing(bounds.min.0, bounds.min.2), region, "{name} proposed a structure outside of its region");
+                    let priority = seed.hash_u64((name, bounds.min, bounds.max));
+                    proposals.push(Proposal { placer, bounds, priority });
+                }
+            }
+            proposals
+        })
+    }
+
+    /// The name of the placer at `index` (see [Claim::placer]).
+    #[inline]
+    pub fn placer_name(&self, index: usize) -> &'static str {
+        self.placers[index].name()
+    }
+
+    /// The structures that intersect `region`, in priority order.
+    pub fn claims(&mut self, ctx: &GenContext, region: RegionPos) -> &[Claim] {
+        if !self.claims.contains_key(&region) {
+            // A structure intersecting `region` starts in it or in the region before it along X
+            // and/or Z. Anything that could overlap one of those starts at most one more region away.
+            let mut nearby = Vec::new();
+            for dz in -2..=1 {
+                for dx in -2..=1 {
+                    nearby.extend_from_slice(self.proposals(ctx, region.offset(dx, dz)));
+                }
+            }
+            let bounds = region.bounds();
+            let mut claims = nearby.iter()
+                .filter(|proposal| proposal.bounds.intersects(bounds))
+                .filter(|proposal| !nearby.iter().any(|other| {
+                    other.key() > proposal.key() && other.bounds.intersects(proposal.bounds)
+                }))
+                .map(|proposal| Claim { placer: proposal.placer, bounds: proposal.bounds, priority: proposal.priority })
+                .collect::<Vec<_>>();
+            claims.sort_by_key(|claim| ::core::cmp::Reverse((claim.priority, claim.placer, claim.bounds)));
+            self.claims.insert(region, claims);
+        }
+        &self.claims[&region]
+    }
+
+    /// The claims that intersect `bounds`, which must lie within a single region along X and Z.
+    pub fn claims_in(&mut self, ctx: &GenContext, bounds: StructureBox) -> Vec<Claim> {
+        let region = RegionPos::containing(bounds.min.0, bounds.min.2);
+        self.claims(ctx, region).iter()
+            .copied()
+            .filter(|claim| claim.bounds.intersects(bounds))
+            .collect()
+    }
+
+    /// Whether a placer could put something in `bounds` without colliding with a claimed structure.
+    #[inline]
+    pub fn is_free(&mut self, ctx: &GenContext, bounds: StructureBox) -> bool {
+        self.claims_in(ctx, bounds).is_empty()
+    }
+
+    /// Drops cached regions to free memory. Claims are recomputed identically when asked for again.
+    pub fn clear(&mut self) {
+        self.proposals.clear();
+        self.claims.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::config::GeneratorConfig;
+
+    struct Towers;
+
+    impl StructurePlacer for Towers {
+        fn name(&self) -> &'static str {
+            "towers"
+        }
+
+        fn propose(&self, _ctx: &GenContext, region: RegionPos, rng: &mut ChaCha8Rng) -> Vec<StructureBox> {
+            let base = (region.x * REGION_SIZE, 0, region.z * REGION_SIZE);
+            (0..6).map(|_| {
+                let min = (base.0 + rng.random_range(0..REGION_SIZE), 64, base.2 + rng.random_range(0..REGION_SIZE));
+                StructureBox::new(min, (rng.random_range(8..96), 32, rng.random_range(8..96)))
+            }).collect()
+        }
+    }
+
+    #[test]
+    fn claim_registry_test() {
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(42, &config);
+        let towers = Towers;
+        let mut registry = ClaimRegistry::new(vec![&towers]);
+        let mut claims = Vec::new();
+        for z in -2..2 {
+            for x in -2..2 {
+                claims.extend_from_slice(registry.claims(&ctx, RegionPos::new(x, z)));
+            }
+        }
+        assert!(!claims.is_empty());
+        for a in &claims {
+            assert!(claims.iter().all(|b| a.bounds == b.bounds || !a.bounds.intersects(b.bounds)));
+        }
+
+        // Asking in a different order (as chunks in another generation order would) agrees.
+        let mut other = ClaimRegistry::new(vec![&towers]);
+        for z in (-2..2).rev() {
+            for x in (-2..2).rev() {
+                assert_eq!(other.claims(&ctx, RegionPos::new(x, z)), registry.claims(&ctx, RegionPos::new(x, z)));
+            }
+        }
+        let claim = claims[0];
+        assert!(!registry.is_free(&ctx, StructureBox::new(claim.bounds.min, (1, 1, 1))));
+    }
+}
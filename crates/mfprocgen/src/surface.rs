@@ -0,0 +1,320 @@
+//! Surface rules: which voxels make up the top of the terrain in each biome.
+//!
+//! A column is filled from its surface (the top solid voxel, at depth `0`) downwards. Each biome's
+//! [BiomeSurface] decides the voxel at every depth:
+//!
+//! 1. The first [Patch] whose noise is above its threshold, down to the patch's depth.
+//! 2. At depth `0`, the `underwater` voxel if the surface is below sea level, otherwise the `top` voxel.
+//! 3. The first [Stratum] that reaches the depth.
+//! 4. The `fill` voxel.
+//!
+//! Rules are written in a small text format, one rule per line, so that worlds can be customized
+//! without code changes and the rules are saved with the [GeneratorConfig](crate::GeneratorConfig):
+//!
+//! ```text
+//! biome plains
+//! top 2
+//! underwater 4
+//! # depth 1 to 3 is dirt
+//! strata 3 3
+//! # sand patches, 16 voxels wide, covering about a third of the biome
+//! patch 4 scale 16 above 43690 depth 2
+//! fill 1
+//! ```
+//!
+//! Voxels are given by id, the same ids the game registers. `#` starts a comment.
+
+use std::fmt;
+
+use mfhash::HashSeed;
+
+use crate::stage::{value_noise, GenContext};
+
+pub const STAGE: &str = "surface";
+pub const BIOME_STAGE: &str = "biomes";
+
+/// The voxel ids used by the default rules.
+pub mod voxels {
+    pub const AIR: u32 = 0;
+    pub const STONE: u32 = 1;
+    pub const GRASS: u32 = 2;
+    pub const DIRT: u32 = 3;
+    pub const SAND: u32 = 4;
+    pub const GRAVEL: u32 = 5;
+}
+
+/// A layer that covers every depth up to and including `depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Stratum {
+    pub depth: u32,
+    pub voxel: u32,
+}
+
+/// Patches of `voxel` wherever 2D noise with cells `scale` voxels wide is above `threshold`
+/// (out of `65536`), down to `depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Patch {
+    pub voxel: u32,
+    pub scale: u32,
+    pub threshold: u32,
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BiomeSurface {
+    pub name: String,
+    pub top: Option<u32>,
+    pub underwater: Option<u32>,
+    /// In order of depth.
+    pub strata: Vec<Stratum>,
+    pub patches: Vec<Patch>,
+    pub fill: u32,
+}
+
+impl BiomeSurface {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            top: None,
+            underwater: None,
+            strata: Vec::new(),
+            patches: Vec::new(),
+            fill: voxels::STONE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Line {line}: {message}")]
+pub struct SurfaceRuleError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+/// The surface rules of every biome. Biomes are spread over the world by noise, in equal shares.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SurfaceRules {
+    pub biomes: Vec<BiomeSurface>,
+}
+
+impl Default for SurfaceRules {
+    fn default() -> Self {
+        Self::parse(DEFAULT_RULES).expect("The default surface rules are valid.")
+    }
+}
+
+const DEFAULT_RULES: &str = "\
+biome plains
+top 2
+underwater 4
+strata 3 3
+patch 5 scale 12 above 52000 depth 1
+fill 1
+biome desert
+top 4
+underwater 4
+strata 4 4
+fill 1
+";
+
+impl SurfaceRules {
+    pub fn parse(text: &str) -> Result<Self, SurfaceRuleError> {
+        let mut biomes: Vec<BiomeSurface> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message| SurfaceRuleError { line: index + 1, message };
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(rule) = words.next() else {
+                continue;
+            };
+            if rule == "biome" {
+                let name = words.next().ok_or(error("expected a biome name"))?;
+                if biomes.iter().any(|biome| biome.name == name) {
+                    return Err(error("duplicate biome"));
+                }
+                biomes.push(BiomeSurface::new(name));
+                if words.next().is_some() {
+                    return Err(error("unexpected text after the biome name"));
+                }
+                continue;
+            }
+            let biome = biomes.last_mut().ok_or(error("rule outside of a biome"))?;
+            let mut number = |expected: &'static str| -> Result<u32, SurfaceRuleError> {
+                words.next().and_then(|word| word.parse().ok()).ok_or(error(expected))
+            };
+            match rule {
+                "top" => biome.top = Some(number("expected a voxel id")?),
+                "underwater" => biome.underwater = Some(number("expected a voxel id")?),
+                "fill" => biome.fill = number("expected a voxel id")?,
+                "strata" => {
+                    let depth = number("expected a depth")?;
+                    let voxel = number("expected a voxel id")?;
+                    if biome.strata.last().is_some_and(|last| last.depth >= depth) {
+                        return Err(error("strata must be in order of depth"));
+                    }
+                    biome.strata.push(Stratum { depth, voxel });
+                }
+                "patch" => {
+                    let voxel = number("expected a voxel id")?;
+                    let mut patch = Patch { voxel, scale: 16, threshold: 32768, depth: 0 };
+                    while let Some(key) = words.next() {
+                        let mut number = |expected| words.next().and_then(|word| word.parse().ok()).ok_or(error(expected));
+                        match key {
+                            "scale" => patch.scale = number("expected a scale")?,
+                            "above" => patch.threshold = number("expected a threshold")?,
+                            "depth" => patch.depth = number("expected a depth")?,
+                            _ => return Err(error("unknown patch setting")),
+                        }
+                    }
+                    if patch.scale == 0 || patch.threshold > 65536 {
+                        return Err(error("invalid patch setting"));
+                    }
+                    biome.patches.push(patch);
+                }
+                _ => return Err(error("unknown rule")),
+            }
+            if words.next().is_some() {
+                return Err(error("unexpected text after the rule"));
+            }
+        }
+        Ok(Self { biomes })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn biome(&self, name: &str) -> Option<&BiomeSurface> {
+        self.biomes.iter().find(|biome| biome.name == name)
+    }
+}
+
+/// Writes the rules in the format [SurfaceRules::parse] reads.
+impl fmt::Display for SurfaceRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for biome in &self.biomes {
+            writeln!(f, "biome {}", biome.name)?;
+            if let Some(top) = biome.top {
+                writeln!(f, "top {top}")?;
+            }
+            if let Some(underwater) = biome.underwater {
+                writeln!(f, "underwater {underwater}")?;
+            }
+            for stratum in &biome.strata {
+                writeln!(f, "strata {} {}", stratum.depth, stratum.voxel)?;
+            }
+            for patch in &biome.patches {
+                writeln!(f, "patch {} scale {} above {} depth {}", patch.voxel, patch.scale, patch.threshold, patch.depth)?;
+            }
+            writeln!(f, "fill {}", biome.fill)?;
+        }
+        Ok(())
+    }
+}
+
+/// The biome of the column at `(x, z)`, or `None` if there are no biomes.
+pub fn biome_at<'a>(ctx: &GenContext<'a>, x: i32, z: i32) -> Option<&'a BiomeSurface> {
+    biome_index_at(ctx, x, z).map(|index| &ctx.config.surface_rules.biomes[index])
+}
+
+/// The index in [SurfaceRules::biomes] of the biome of the column at `(x, z)`, or `None` if there
+/// are no biomes.
+pub fn biome_index_at(ctx: &GenContext, x: i32, z: i32) -> Option<usize> {
+    let count = ctx.config.surface_rules.biomes.len();
+    if count == 0 {
+        return None;
+    }
+    let noise = value_noise(ctx.stage_seed(BIOME_STAGE), x, z, ctx.config.biome_scale);
+    Some((noise as usize * count) >> 16)
+}
+
+/// The rules of one column, resolved once so that every voxel in it is cheap to look up.
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceColumn<'a> {
+    /// Voxels below this height are solid.
+    pub height: i32,
+    pub biome: Option<&'a BiomeSurface>,
+    pub underwater: bool,
+    /// The patch covering the column, if any.
+    pub patch: Option<Patch>,
+}
+
+impl<'a> SurfaceColumn<'a> {
+    pub fn new(ctx: &GenContext<'a>, x: i32, z: i32) -> Self {
+        let height = ctx.height_at(x, z);
+        let biome = biome_at(ctx, x, z);
+        let patch = biome.and_then(|biome| {
+            let seed: HashSeed = ctx.stage_seed(STAGE).reseed_hashed(biome.name.as_str(), None);
+            biome.patches.iter().enumerate().find_map(|(index, patch)| {
+                let noise = value_noise(seed.reseed_hashed(index as u32, None), x, z, patch.scale);
+                (noise as u32 >= patch.threshold).then_some(*patch)
+            })
+        });
+        Self {
+            height,
+            biome,
+            underwater: height <= ctx.config.sea_level,
+            patch,
+        }
+    }
+
+    /// The voxel at height `y`.
+    pub fn voxel_at(&self, y: i32) -> u32 {
+        if y >= self.height {
+            return voxels::AIR;
+        }
+        let Some(biome) = self.biome else {
+            return voxels::STONE;
+        };
+        let depth = (self.height - 1 - y) as u32;
+        if let Some(patch) = self.patch && depth <= patch.depth {
+            return patch.voxel;
+        }
+        let surface = if self.underwater { biome.underwater.or(biome.top) } else { biome.top };
+        if let (0, Some(voxel)) = (depth, surface) {
+            return voxel;
+        }
+        biome.strata.iter()
+            .find(|stratum| depth <= stratum.depth)
+            .map_or(biome.fill, |stratum| stratum.voxel)
+    }
+
+    /// Fills `column` with the voxels from `min_y` upwards, as chunk generation does.
+    pub fn fill(&self, min_y: i32, column: &mut [u32]) {
+        for (y, voxel) in (min_y..).zip(column) {
+            *voxel = self.voxel_at(y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeneratorConfig;
+
+    #[test]
+    fn surface_rules_test() {
+        let rules = SurfaceRules::default();
+        assert_eq!(SurfaceRules::parse(&rules.to_string()).unwrap(), rules);
+        assert_eq!(SurfaceRules::parse("top 1").unwrap_err(), SurfaceRuleError { line: 1, message: "rule outside of a biome" });
+        assert_eq!(SurfaceRules::parse("biome a\nstrata 4 1\nstrata 2 1").unwrap_err().line, 3);
+        assert!(SurfaceRules::parse("biome a\npatch 1 size 3").is_err());
+
+        let mut config = GeneratorConfig::preset("flats").unwrap();
+        config.surface_rules = SurfaceRules::parse("biome a\ntop 2\nunderwater 4\nstrata 3 3\nfill 1").unwrap();
+        let ctx = GenContext::new(7, &config);
+        let column = SurfaceColumn::new(&ctx, 10, -4);
+        let mut voxels = [0; 8];
+        column.fill(config.base_height - 6, &mut voxels);
+        assert_eq!(voxels, [1, 1, 3, 3, 3, 2, 0, 0]);
+
+        config.sea_level = config.base_height;
+        let ctx = GenContext::new(7, &config);
+        assert_eq!(SurfaceColumn::new(&ctx, 10, -4).voxel_at(config.base_height - 1), voxels::SAND);
+
+        // Patches cover some columns, and not others.
+        config.surface_rules = SurfaceRules::parse("biome a\ntop 2\npatch 4 scale 4 above 32768 depth 1").unwrap();
+        let ctx = GenContext::new(7, &config);
+        let tops = (0..64).map(|x| SurfaceColumn::new(&ctx, x, 0).voxel_at(config.base_height - 1)).collect::<Vec<_>>();
+        assert!(tops.contains(&voxels::SAND) && tops.contains(&voxels::GRASS));
+    }
+}
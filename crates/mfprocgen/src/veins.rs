@@ -0,0 +1,264 @@
+//! Ore veins: sheets of ore that follow the strata underground.
+//!
+//! Each resource family has a [VeinRule]. Its veins are the places where a 3D noise field crosses
+//! the middle of its range, which is a set of connected, folded sheets. The noise changes faster
+//! vertically than horizontally (by the rule's `stretch`), so the sheets lie mostly flat, like
+//! strata, and a vein found in one place can be followed through the rock. Inside a vein, the share
+//! of voxels that are ore depends on the rule's `richness` and on a 2D region noise as wide as a
+//! biome, so some regions are worth mining and others barely are.
+//!
+//! Rules are written in the same kind of text format as the [surface rules](crate::surface), one
+//! vein per line, and saved with the [GeneratorConfig](crate::GeneratorConfig):
+//!
+//! ```text
+//! # family 16 (iron) as voxel 6, from 4 to 48 voxels below the surface
+//! vein 16 6 depth 4 48 scale 48 stretch 4 thickness 3072 richness 40
+//! ```
+//!
+//! The family is the resource family of the ore item in the game's item registry, so a mined vein
+//! voxel can be turned back into its item. `#` starts a comment.
+
+use std::fmt;
+
+use mfhash::HashSeed;
+
+use crate::stage::{value_noise, GenContext};
+
+pub const STAGE: &str = "veins";
+pub const REGION_STAGE: &str = "vein_regions";
+
+/// The voxel ids of the ores placed by the default rules.
+pub mod voxels {
+    pub const IRON_ORE: u32 = 6;
+    pub const COPPER_ORE: u32 = 7;
+    pub const BAUXITE: u32 = 8;
+    pub const QUARTZ: u32 = 9;
+}
+
+/// The veins of one resource family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VeinRule {
+    /// The resource family in the item registry.
+    pub family: u32,
+    pub voxel: u32,
+    /// The shallowest depth below the surface (`0` is the top solid voxel).
+    pub min_depth: u32,
+    /// The deepest depth below the surface.
+    pub max_depth: u32,
+    /// The horizontal width (in voxels) of a noise cell. Larger values give broader folds.
+    pub scale: u32,
+    /// How many times faster the noise changes vertically. Larger values give flatter veins.
+    pub stretch: u32,
+    /// How far (out of `65536`) the noise may be from its middle for a voxel to be in the vein.
+    /// Larger values give thicker veins.
+    pub thickness: u32,
+    /// The percentage of vein voxels that are ore in an average region.
+    pub richness: u32,
+}
+
+impl VeinRule {
+    #[must_use]
+    pub const fn new(family: u32, voxel: u32) -> Self {
+        Self {
+            family,
+            voxel,
+            min_depth: 4,
+            max_depth: 64,
+            scale: 32,
+            stretch: 4,
+            thickness: 2048,
+            richness: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Line {line}: {message}")]
+pub struct VeinRuleError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+/// The vein rules of every resource family. Where veins overlap, the first rule wins.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VeinRules {
+    pub veins: Vec<VeinRule>,
+}
+
+impl Default for VeinRules {
+    fn default() -> Self {
+        Self::parse(DEFAULT_RULES).expect("The default vein rules are valid.")
+    }
+}
+
+const DEFAULT_RULES: &str = "\
+vein 1024 9 depth 24 96 scale 24 stretch 2 thickness 1536 richness 25
+vein 19 8 depth 2 24 scale 64 stretch 6 thickness 3072 richness 35
+vein 18 7 depth 4 40 scale 40 stretch 4 thickness 2560 richness 40
+vein 16 6 depth 4 48 scale 48 stretch 4 thickness 3072 richness 40
+";
+
+impl VeinRules {
+    pub fn parse(text: &str) -> Result<Self, VeinRuleError> {
+        let mut veins: Vec<VeinRule> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message| VeinRuleError { line: index + 1, message };
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(rule) = words.next() else {
+                continue;
+            };
+            if rule != "vein" {
+                return Err(error("unknown rule"));
+            }
+            let mut number = |expected| words.next().and_then(|word| word.parse().ok()).ok_or(error(expected));
+            let family = number("expected a resource family")?;
+            let voxel = number("expected a voxel id")?;
+            if veins.iter().any(|vein| vein.family == family) {
+                return Err(error("duplicate resource family"));
+            }
+            let mut vein = VeinRule::new(family, voxel);
+            while let Some(key) = words.next() {
+                let mut number = |expected| words.next().and_then(|word| word.parse().ok()).ok_or(error(expected));
+                match key {
+                    "depth" => {
+                        vein.min_depth = number("expected a depth")?;
+                        vein.max_depth = number("expected a depth")?;
+                    }
+                    "scale" => vein.scale = number("expected a scale")?,
+                    "stretch" => vein.stretch = number("expected a stretch")?,
+                    "thickness" => vein.thickness = number("expected a thickness")?,
+                    "richness" => vein.richness = number("expected a richness")?,
+                    _ => return Err(error("unknown vein setting")),
+                }
+            }
+            if vein.min_depth > vein.max_depth || vein.scale == 0 || vein.stretch == 0
+                || vein.thickness > 32768 || vein.richness > 100 {
+                return Err(error("invalid vein setting"));
+            }
+            veins.push(vein);
+        }
+        Ok(Self { veins })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn family(&self, family: u32) -> Option<&VeinRule> {
+        self.veins.iter().find(|vein| vein.family == family)
+    }
+}
+
+/// Writes the rules in the format [VeinRules::parse] reads.
+impl fmt::Display for VeinRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for vein in &self.veins {
+            writeln!(
+                f,
+                "vein {} {} depth {} {} scale {} stretch {} thickness {} richness {}",
+                vein.family, vein.voxel, vein.min_depth, vein.max_depth, vein.scale, vein.stretch, vein.thickness, vein.richness,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 3D value noise in `0..65536`, with lattice points `scale` voxels apart.
+///
+/// The interpolation runs in `i128`: it reaches `65536 * scale³`, which overflows `i64` for
+/// scales past about 52 000.
+fn value_noise3(seed: HashSeed, x: i64, y: i64, z: i64, scale: u32) -> i64 {
+    let scale = scale.max(1) as i64;
+    let (cell_x, cell_y, cell_z) = (x.div_euclid(scale), y.div_euclid(scale), z.div_euclid(scale));
+    let (fx, fy, fz) = (x.rem_euclid(scale) as i128, y.rem_euclid(scale) as i128, z.rem_euclid(scale) as i128);
+    let scale = scale as i128;
+    let lattice = |dx: i64, dy: i64, dz: i64| (seed.hash_u32((cell_x + dx, cell_y + dy, cell_z + dz)) >> 16) as i128;
+    let row = |dy: i64, dz: i64| lattice(0, dy, dz) * (scale - fx) + lattice(1, dy, dz) * fx;
+    let plane = |dz: i64| row(0, dz) * (scale - fy) + row(1, dz) * fy;
+    ((plane(0) * (scale - fz) + plane(1) * fz) / (scale * scale * scale)) as i64
+}
+
+/// Whether the voxel at `(x, y, z)` lies on one of `vein`'s sheets.
+fn in_vein(seed: HashSeed, vein: &VeinRule, x: i32, y: i32, z: i32) -> bool {
+    let noise = value_noise3(seed, x as i64, y as i64 * vein.stretch as i64, z as i64, vein.scale);
+    (noise - 32768).unsigned_abs() < vein.thickness as u64
+}
+
+/// The percentage of `vein`'s voxels in the column at `(x, z)` that are ore: the rule's richness,
+/// between none and twice as much depending on the region, scaled by the config's `ore_density`.
+fn richness_at(ctx: &GenContext, vein: &VeinRule, x: i32, z: i32) -> u64 {
+    let seed = ctx.stage_seed(REGION_STAGE).reseed_hashed(vein.family, None);
+    let region = value_noise(seed, x, z, ctx.config.biome_scale) as u64;
+    (vein.richness as u64 * region * ctx.config.ore_density as u64) >> 18
+}
+
+/// The vein the voxel at `(x, y, z)` is ore of, if any.
+pub fn vein_at<'a>(ctx: &GenContext<'a>, x: i32, y: i32, z: i32) -> Option<&'a VeinRule> {
+    let config = ctx.config;
+    let height = ctx.height_at(x, z);
+    if !config.ores || y >= height {
+        return None;
+    }
+    let depth = (height - 1 - y) as u32;
+    let seed = ctx.stage_seed(STAGE);
+    config.veins.veins.iter()
+        .filter(|vein| (vein.min_depth..=vein.max_depth).contains(&depth))
+        .find(|vein| {
+            let seed = seed.reseed_hashed(vein.family, None);
+            in_vein(seed, vein, x, y, z)
+                && u64::from(seed.hash_u32((x, y, z)) % 100) < richness_at(ctx, vein, x, z)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeneratorConfig;
+
+    #[test]
+    fn vein_rules_test() {
+        let rules = VeinRules::default();
+        assert_eq!(VeinRules::parse(&rules.to_string()).unwrap(), rules);
+        assert_eq!(rules.family(16).unwrap().voxel, voxels::IRON_ORE);
+        assert_eq!(VeinRules::parse("\n# iron\nvein 16").unwrap_err(), VeinRuleError { line: 3, message: "expected a voxel id" });
+        assert!(VeinRules::parse("vein 16 6\nvein 16 7").is_err());
+        assert!(VeinRules::parse("vein 16 6 depth 9 3").is_err());
+        assert!(VeinRules::parse("vein 16 6 size 3").is_err());
+        assert!(VeinRules::parse("patch 16 6").is_err());
+    }
+
+    #[test]
+    fn vein_at_test() {
+        let mut config = GeneratorConfig::preset("flats").unwrap();
+        config.ores = true;
+        config.veins = VeinRules::parse("vein 16 6 depth 8 40 richness 100").unwrap();
+        let ctx = GenContext::new(0xDEADBEEF, &config);
+        let top = config.base_height - 1;
+
+        let mut ores = 0;
+        for x in 0..32 {
+            for y in top - 48..=top {
+                let vein = vein_at(&ctx, x, y, 5);
+                assert_eq!(vein_at(&GenContext::new(0xDEADBEEF, &config), x, y, 5), vein);
+                if vein.is_some() {
+                    assert!((8..=40).contains(&(top - y)));
+                    ores += 1;
+                }
+            }
+        }
+        assert!(ores > 0);
+
+        // Veins are sheets rather than scattered voxels, so they continue into their neighbours.
+        let rule = config.veins.veins[0];
+        let seed = ctx.stage_seed(STAGE).reseed_hashed(rule.family, None);
+        let sheet = (0..32).filter(|&x| in_vein(seed, &rule, x, top - 20, 5)).collect::<Vec<_>>();
+        assert!(sheet.windows(2).any(|pair| pair[1] == pair[0] + 1));
+
+        config.ores = false;
+        assert!(vein_at(&GenContext::new(0xDEADBEEF, &config), 0, top - 20, 0).is_none());
+
+        // Huge scales would overflow an i64 interpolation.
+        for scale in [52_000, 1 << 20, u32::MAX] {
+            assert!((0..65536).contains(&value_noise3(seed, -77_777, 1 << 40, 123_456, scale)));
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Generates terrain with fixed seeds for every preset and compares what comes out with the golden
+//! files in `tests/golden`: a CRC of the heightmap, surface columns and ore, how many columns fall in
+//! each biome, and where structures are placed. Chunks that a world hasn't generated yet are
+//! generated by the current code, so a change here would change the terrain of existing worlds.
+//! See [mfcore::golden] for blessing new values, when that's intended.
+
+use std::{collections::BTreeMap, path::Path};
+
+use mfcore::golden::{self, Golden, BLESS_VAR};
+use mfprocgen::{
+    stage::GenContext,
+    structure::{ClaimRegistry, RegionPos, StructureBox, StructurePlacer, REGION_SIZE},
+    GeneratorConfig,
+};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+const SEEDS: [u64; 2] = [0, 0xDEADBEEF];
+
+/// CRC-32 (IEEE), the one zip and PNG use.
+fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    !bytes.into_iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg()))
+    })
+}
+
+/// Places small buildings on the terrain, as a stand-in for the game's placers.
+struct Huts;
+
+impl StructurePlacer for Huts {
+    fn name(&self) -> &'static str {
+        "huts"
+    }
+
+    fn propose(&self, ctx: &GenContext, region: RegionPos, rng: &mut ChaCha8Rng) -> Vec<StructureBox> {
+        (0..4).map(|_| {
+            let x = region.x * REGION_SIZE + rng.random_range(0..REGION_SIZE);
+            let z = region.z * REGION_SIZE + rng.random_range(0..REGION_SIZE);
+            StructureBox::new((x, ctx.height_at(x, z), z), (rng.random_range(5..40), 8, rng.random_range(5..40)))
+        }).collect()
+    }
+}
+
+fn record(golden: &mut Golden, ctx: &GenContext) {
+    let prefix = format!("seed {:x}", ctx.world_seed);
+    let key = |name: &str| format!("{prefix} {name}");
+    let config = ctx.config;
+
+    let heights = (-32..32).flat_map(|z| (-32..32).map(move |x| (x, z)))
+        .map(|(x, z)| ctx.height_at(x, z))
+        .collect::<Vec<_>>();
+    golden.push(&key("heightmap crc"), format!("{:08x}", crc32(heights.iter().flat_map(|height| height.to_le_bytes()))));
+    golden.push(&key("heightmap range"), format!("{}..={}", heights.iter().min().unwrap(), heights.iter().max().unwrap()));
+
+    // Biomes are hundreds of voxels wide, so they're sampled over a wider area.
+    let mut biomes = BTreeMap::<&str, u32>::new();
+    for z in (-512..512).step_by(16) {
+        for x in (-512..512).step_by(16) {
+            *biomes.entry(ctx.biome_at(x, z).map_or("none", |biome| biome.name.as_str())).or_default() += 1;
+        }
+    }
+    let biomes = biomes.iter().map(|(name, count)| format!("{name} {count}")).collect::<Vec<_>>();
+    golden.push(&key("biomes"), biomes.join(", "));
+
+    let min_y = config.base_height - 8;
+    let mut column = [0; 24];
+    let mut surface = Vec::new();
+    for z in 0..16 {
+        for x in 0..16 {
+            ctx.surface_column(x, z).fill(min_y, &mut column);
+            surface.extend(column.iter().flat_map(|voxel| voxel.to_le_bytes()));
+        }
+    }
+    golden.push(&key("surface crc"), format!("{:08x}", crc32(surface)));
+
+    let mut ores = BTreeMap::<u32, u32>::new();
+    let mut voxels = Vec::new();
+    for z in 0..8 {
+        for x in 0..8 {
+            for y in config.base_height - 64..config.base_height {
+                let voxel = ctx.vein_at(x, y, z).map_or(0, |vein| vein.voxel);
+                if voxel != 0 {
+                    *ores.entry(voxel).or_default() += 1;
+                }
+                voxels.extend(voxel.to_le_bytes());
+            }
+        }
+    }
+    let ores = ores.iter().map(|(voxel, count)| format!("{voxel} {count}")).collect::<Vec<_>>();
+    golden.push(&key("ores"), if ores.is_empty() { "none".to_owned() } else { ores.join(", ") });
+    golden.push(&key("ores crc"), format!("{:08x}", crc32(voxels)));
+
+    let huts = Huts;
+    let mut registry = ClaimRegistry::new(vec![&huts]);
+    let mut index = 0;
+    for z in -1..1 {
+        for x in -1..1 {
+            let region = RegionPos::new(x, z);
+            // Each structure is listed by the region its corner is in, not every region it reaches.
+            let claims = registry.claims(ctx, region).iter()
+                .filter(|claim| RegionPos::containing(claim.bounds.min.0, claim.bounds.min.2) == region)
+                .copied()
+                .collect::<Vec<_>>();
+            for claim in claims {
+                let StructureBox { min, max } = claim.bounds;
+                golden.push(&key(&format!("structure {index}")), format!("{} {min:?}..{max:?}", registry.placer_name(claim.placer)));
+                index += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn generator_golden_test() {
+    assert_eq!(crc32(*b"123456789"), 0xCBF4_3926);
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+    let bless = golden::bless_requested();
+    let mut failures = Vec::new();
+    for preset in GeneratorConfig::PRESETS {
+        let config = GeneratorConfig::preset(preset).unwrap();
+        let mut actual = Golden::new();
+        for seed in SEEDS {
+            record(&mut actual, &GenContext::new(seed, &config));
+        }
+        if let Err(err) = golden::check(&root.join(format!("{preset}.golden")), &actual, bless) {
+            failures.push(err.to_string());
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n\nIf the change is intended, rerun with {BLESS_VAR}=1 and commit the updated golden files.",
+        failures.join("\n"),
+    );
+}
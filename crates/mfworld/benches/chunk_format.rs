@@ -0,0 +1,122 @@
+//! Encode and decode speed, and size, of each chunk format on representative chunks. The sizes are
+//! printed before the timings.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mfcereal::decode::Decode;
+use mfgeometry::Orientation;
+use mfhash::HashSeed;
+use mfworld::{
+    chunk::{format::ChunkFormat, stored::StoredChunk, voxel_index, CHUNK_SIZE},
+    history::VoxelState,
+    voxel::id::VoxelId,
+};
+
+fn voxel(id: u32) -> VoxelState {
+    VoxelState::new(VoxelId::new(id), Orientation::UNORIENTED)
+}
+
+/// Stone, dirt and grass layers under air.
+fn plains() -> StoredChunk {
+    let mut chunk = StoredChunk::new();
+    for y in 0..10 {
+        let state = match y {
+            0..6 => voxel(1),
+            6..9 => voxel(2),
+            _ => voxel(3),
+        };
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(voxel_index(x, y, z), state);
+            }
+        }
+    }
+    chunk
+}
+
+/// Machines of many types and orientations packed together, with block entities.
+fn factory() -> StoredChunk {
+    let seed = HashSeed::derived("mfworld::benches::chunk_format::factory");
+    let mut chunk = StoredChunk::new();
+    for index in 0..CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+        let roll = seed.hash_u32(index);
+        if roll.is_multiple_of(4) {
+            continue;
+        }
+        let orientation = Orientation::UNORIENTED.rotate_y((roll >> 8) as i32 % 4);
+        chunk.set(index as usize, VoxelState::new(VoxelId::new(100 + (roll >> 16) % 40), orientation));
+        if roll.is_multiple_of(7) {
+            chunk.block_entities.insert(index as u16, roll.to_be_bytes().to_vec());
+        }
+    }
+    chunk
+}
+
+/// Stone and ore around winding air pockets.
+fn caves() -> StoredChunk {
+    let seed = HashSeed::derived("mfworld::benches::chunk_format::caves");
+    let mut chunk = StoredChunk::new();
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let tunnel = (x - 8 + (y / 3 - 2)).abs() + (y - 8).abs() < 4;
+                let state = match seed.hash_u32((x / 2, y / 2, z / 2)) % 16 {
+                    _ if tunnel => continue,
+                    0 => voxel(4),
+                    1 if z > 4 => continue,
+                    _ => voxel(1),
+                };
+                chunk.set(voxel_index(x, y, z), state);
+            }
+        }
+    }
+    chunk
+}
+
+fn chunk_format(c: &mut Criterion) {
+    let chunks = [("plains", plains()), ("factory", factory()), ("caves", caves())];
+    for (name, chunk) in &chunks {
+        let sizes = ChunkFormat::ALL.map(|format| {
+            let mut bytes = Vec::new();
+            let Ok(len) = chunk.encode_as(format, &mut bytes);
+            format!("{format} {len} bytes")
+        });
+        println!("{name}: {} (picks {})", sizes.join(", "), chunk.format());
+    }
+
+    let mut encode = c.benchmark_group("chunk_encode");
+    for (name, chunk) in &chunks {
+        for format in ChunkFormat::ALL {
+            let mut bytes = Vec::with_capacity(16 * 1024);
+            encode.bench_with_input(BenchmarkId::new(format.name(), name), chunk, |b, chunk| b.iter(|| {
+                bytes.clear();
+                let Ok(_) = black_box(chunk).encode_as(format, &mut bytes);
+                black_box(&bytes);
+            }));
+        }
+    }
+    encode.finish();
+
+    let mut decode = c.benchmark_group("chunk_decode");
+    for (name, chunk) in &chunks {
+        for format in ChunkFormat::ALL {
+            let mut bytes = Vec::new();
+            let Ok(_) = chunk.encode_as(format, &mut bytes);
+            decode.bench_with_input(BenchmarkId::new(format.name(), name), &bytes, |b, bytes| b.iter(|| {
+                black_box(StoredChunk::decode(&mut black_box(bytes.as_slice())).unwrap())
+            }));
+        }
+    }
+    decode.finish();
+
+    // The picker itself, which runs on every save.
+    let mut pick = c.benchmark_group("chunk_pick_format");
+    for (name, chunk) in &chunks {
+        pick.bench_function(*name, |b| b.iter(|| black_box(black_box(chunk).format())));
+    }
+    pick.finish();
+}
+
+criterion_group!(benches, chunk_format);
+criterion_main!(benches);
@@ -0,0 +1,403 @@
+//! Biomes of the loaded chunk columns, and the ambient parameters renderers take from them.
+//!
+//! Generation decides the biome of every column and [loads](BiomeMap::load_column) them here, one
+//! [ColumnBiomes] per chunk column. Each biome has an [Ambient] in the [AmbientTable]: the fog color
+//! and grass tint (as palette ids) and how much sky light shows through.
+//!
+//! [BiomeMap::ambient_at] blends the ambients of the columns within the blend radius, so that
+//! colors don't change in a hard line at biome borders. Ids can't be mixed, so the most common one
+//! in the neighborhood wins (the center's on a tie, then the lowest). Sky factors are averaged.
+//! Columns that aren't loaded don't count. Blended ambients are cached for whole chunk columns, and
+//! dropped when the biomes within the blend radius change.
+
+use std::collections::{BTreeMap, HashMap};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{
+    chunk::{BlockPos, CHUNK_MASK, CHUNK_SHIFT, CHUNK_SIZE},
+    skylight::{column_index, COLUMNS},
+};
+
+/// How many voxels around a column [BiomeMap::ambient_at] blends by default.
+pub const DEFAULT_BLEND_RADIUS: u32 = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BiomeId(pub u16);
+
+/// The ambient parameters of a biome, or blended from the biomes around a column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ambient {
+    /// The palette id of the fog color.
+    pub fog_color_id: u16,
+    /// The palette id of the grass (and foliage) tint.
+    pub grass_tint_id: u16,
+    /// How much sky light reaches the ground, from `0.0` (none) to `1.0` (all of it).
+    pub sky_factor: f32,
+}
+
+impl Ambient {
+    pub const DEFAULT: Self = Self::new(0, 0, 1.0);
+
+    #[inline]
+    #[must_use]
+    pub const fn new(fog_color_id: u16, grass_tint_id: u16, sky_factor: f32) -> Self {
+        Self { fog_color_id, grass_tint_id, sky_factor }
+    }
+}
+
+impl Default for Ambient {
+    #[inline]
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The [Ambient] of every biome. Biomes without one use the fallback.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AmbientTable {
+    biomes: HashMap<BiomeId, Ambient>,
+    fallback: Ambient,
+}
+
+impl AmbientTable {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A table where every biome has the ambient `fallback` until [assigned](Self::assign).
+    #[inline]
+    #[must_use]
+    pub fn with_fallback(fallback: Ambient) -> Self {
+        Self { biomes: HashMap::new(), fallback }
+    }
+
+    #[inline]
+    pub fn assign(&mut self, biome: BiomeId, ambient: Ambient) -> Option<Ambient> {
+        self.biomes.insert(biome, ambient)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, biome: BiomeId) -> Ambient {
+        self.biomes.get(&biome).copied().unwrap_or(self.fallback)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn fallback(&self) -> Ambient {
+        self.fallback
+    }
+}
+
+/// The biome of each column of a chunk column, indexed by [column_index].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColumnBiomes {
+    biomes: Box<[BiomeId; COLUMNS]>,
+}
+
+impl ColumnBiomes {
+    /// Every column in `biome`.
+    #[inline]
+    #[must_use]
+    pub fn filled(biome: BiomeId) -> Self {
+        Self { biomes: Box::new([biome; COLUMNS]) }
+    }
+
+    /// The biome of each column from `f(x, z)`, with local coordinates.
+    #[must_use]
+    pub fn from_fn<F: FnMut(i32, i32) -> BiomeId>(mut f: F) -> Self {
+        let mut biomes = Self::filled(BiomeId::default());
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                biomes.set(x, z, f(x, z));
+            }
+        }
+        biomes
+    }
+
+    /// The biome at local `(x, z)`. Coordinates wrap to the chunk.
+    #[inline]
+    #[must_use]
+    pub const fn get(&self, x: i32, z: i32) -> BiomeId {
+        self.biomes[column_index(x, z)]
+    }
+
+    #[inline]
+    pub const fn set(&mut self, x: i32, z: i32, biome: BiomeId) -> BiomeId {
+        ::core::mem::replace(&mut self.biomes[column_index(x, z)], biome)
+    }
+}
+
+// Layout: COLUMNS * biome (u16), in column index order.
+impl Encode for ColumnBiomes {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = 0;
+        for biome in self.biomes.iter() {
+            written += encoder.write_u16(biome.0)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for ColumnBiomes {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut biomes = Self::filled(BiomeId::default());
+        for biome in biomes.biomes.iter_mut() {
+            *biome = BiomeId(decoder.read_u16()?);
+        }
+        Ok(biomes)
+    }
+}
+
+/// The chunk coordinates of the chunk column containing the voxel column `(x, z)`, or `None` if it's
+/// further out than any chunk reaches.
+#[inline]
+fn chunk_column(x: i64, z: i64) -> Option<(i32, i32)> {
+    Some((i32::try_from(x >> CHUNK_SHIFT).ok()?, i32::try_from(z >> CHUNK_SHIFT).ok()?))
+}
+
+/// The biomes of the loaded chunk columns, and their blended ambients. See the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct BiomeMap {
+    table: AmbientTable,
+    blend_radius: u32,
+    columns: BTreeMap<(i32, i32), ColumnBiomes>,
+    cache: HashMap<(i32, i32), Box<[Ambient; COLUMNS]>>,
+}
+
+impl BiomeMap {
+    #[inline]
+    #[must_use]
+    pub fn new(table: AmbientTable) -> Self {
+        Self::with_blend_radius(table, DEFAULT_BLEND_RADIUS)
+    }
+
+    /// A map that blends the columns up to `blend_radius` voxels away (on each axis), at most
+    /// [CHUNK_SIZE]. `0` doesn't blend at all.
+    #[inline]
+    #[must_use]
+    pub fn with_blend_radius(table: AmbientTable, blend_radius: u32) -> Self {
+        Self {
+            table,
+            blend_radius: blend_radius.min(CHUNK_SIZE as u32),
+            columns: BTreeMap::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn table(&self) -> &AmbientTable {
+        &self.table
+    }
+
+    /// Changes the ambient of `biome`, dropping every cached ambient.
+    pub fn assign(&mut self, biome: BiomeId, ambient: Ambient) -> Option<Ambient> {
+        self.cache.clear();
+        self.table.assign(biome, ambient)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn blend_radius(&self) -> u32 {
+        self.blend_radius
+    }
+
+    /// Whether the biomes of the chunk column at `(x, z)` are loaded.
+    #[inline]
+    #[must_use]
+    pub fn is_loaded(&self, x: i32, z: i32) -> bool {
+        self.columns.contains_key(&(x, z))
+    }
+
+    /// Loads the biomes of the chunk column at `(x, z)`, replacing any that were there.
+    pub fn load_column(&mut self, x: i32, z: i32, biomes: ColumnBiomes) {
+        self.columns.insert((x, z), biomes);
+        self.invalidate_around(x, z);
+    }
+
+    /// Unloads the chunk column at `(x, z)`, returning its biomes to be saved with it.
+    pub fn unload_column(&mut self, x: i32, z: i32) -> Option<ColumnBiomes> {
+        let biomes = self.columns.remove(&(x, z))?;
+        self.invalidate_around(x, z);
+        Some(biomes)
+    }
+
+    /// The biome of the column containing `pos`, if it's loaded.
+    #[must_use]
+    pub fn biome_at(&self, pos: BlockPos) -> Option<BiomeId> {
+        let column = chunk_column(pos.x, pos.z)?;
+        let biomes = self.columns.get(&column)?;
+        Some(biomes.get(pos.x as i32 & CHUNK_MASK, pos.z as i32 & CHUNK_MASK))
+    }
+
+    /// Changes the biome of the column containing `pos`. Returns the previous biome, or `None` (and
+    /// changes nothing) if the column isn't loaded.
+    pub fn set_biome(&mut self, pos: BlockPos, biome: BiomeId) -> Option<BiomeId> {
+        let (x, z) = chunk_column(pos.x, pos.z)?;
+        let biomes = self.columns.get_mut(&(x, z))?;
+        let previous = biomes.set(pos.x as i32 & CHUNK_MASK, pos.z as i32 & CHUNK_MASK, biome);
+        if previous != biome {
+            self.invalidate_around(x, z);
+        }
+        Some(previous)
+    }
+
+    /// The blended ambient of the column containing `pos` (its Y doesn't matter). Columns that aren't
+    /// loaded have the [fallback](AmbientTable::fallback) ambient.
+    pub fn ambient_at(&mut self, pos: BlockPos) -> Ambient {
+        let Some((x, z)) = chunk_column(pos.x, pos.z) else {
+            return self.table.fallback();
+        };
+        if !self.columns.contains_key(&(x, z)) {
+            return self.table.fallback();
+        }
+        if !self.cache.contains_key(&(x, z)) {
+            let ambients = self.blend_column(x, z);
+            self.cache.insert((x, z), ambients);
+        }
+        self.cache[&(x, z)][column_index(pos.x as i32, pos.z as i32)]
+    }
+
+    /// The blended ambients of the loaded chunk column at `(x, z)`.
+    fn blend_column(&self, x: i32, z: i32) -> Box<[Ambient; COLUMNS]> {
+        let radius = self.blend_radius as i32;
+        let mut ambients = Box::new([self.table.fallback(); COLUMNS]);
+        // The 3x3 chunk columns around this one, since the radius is at most a chunk.
+        let neighbors: [[Option<&ColumnBiomes>; 3]; 3] = std::array::from_fn(|dz| {
+            std::array::from_fn(|dx| self.columns.get(&(x.wrapping_add(dx as i32 - 1), z.wrapping_add(dz as i32 - 1))))
+        });
+        let biome_at = |local_x: i32, local_z: i32| {
+            let column = neighbors[((local_z >> CHUNK_SHIFT) + 1) as usize][((local_x >> CHUNK_SHIFT) + 1) as usize]?;
+            Some(column.get(local_x & CHUNK_MASK, local_z & CHUNK_MASK))
+        };
+        let mut fog_votes = BTreeMap::new();
+        let mut tint_votes = BTreeMap::new();
+        for local_z in 0..CHUNK_SIZE {
+            for local_x in 0..CHUNK_SIZE {
+                fog_votes.clear();
+                tint_votes.clear();
+                let mut sky_sum = 0.0;
+                let mut samples = 0u32;
+                for dz in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let Some(biome) = biome_at(local_x + dx, local_z + dz) else {
+                            continue;
+                        };
+                        let ambient = self.table.get(biome);
+                        *fog_votes.entry(ambient.fog_color_id).or_insert(0u32) += 1;
+                        *tint_votes.entry(ambient.grass_tint_id).or_insert(0u32) += 1;
+                        sky_sum += ambient.sky_factor;
+                        samples += 1;
+                    }
+                }
+                // The center is always loaded, so there's at least one sample.
+                let center = self.table.get(biome_at(local_x, local_z).unwrap());
+                ambients[column_index(local_x, local_z)] = Ambient {
+                    fog_color_id: winner(&fog_votes, center.fog_color_id),
+                    grass_tint_id: winner(&tint_votes, center.grass_tint_id),
+                    sky_factor: sky_sum / samples as f32,
+                };
+            }
+        }
+        ambients
+    }
+
+    /// Drops the cached ambients that could blend in the chunk column at `(x, z)`.
+    fn invalidate_around(&mut self, x: i32, z: i32) {
+        if self.cache.is_empty() {
+            return;
+        }
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                self.cache.remove(&(x.wrapping_add(dx), z.wrapping_add(dz)));
+            }
+        }
+    }
+}
+
+/// The id with the most votes: `center` if it's tied for the most, otherwise the lowest.
+fn winner(votes: &BTreeMap<u16, u32>, center: u16) -> u16 {
+    let most = votes.values().copied().max().unwrap_or(0);
+    if votes.get(&center) == Some(&most) {
+        return center;
+    }
+    votes.iter().find(|&(_, &count)| count == most).map_or(center, |(&id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAINS: BiomeId = BiomeId(1);
+    const DESERT: BiomeId = BiomeId(2);
+
+    fn map(blend_radius: u32) -> BiomeMap {
+        let mut table = AmbientTable::with_fallback(Ambient::new(9, 9, 0.5));
+        table.assign(PLAINS, Ambient::new(1, 10, 1.0));
+        table.assign(DESERT, Ambient::new(2, 20, 0.0));
+        BiomeMap::with_blend_radius(table, blend_radius)
+    }
+
+    #[test]
+    fn ambient_test() {
+        let mut biomes = map(1);
+        // Unloaded columns use the fallback.
+        assert_eq!(biomes.ambient_at(BlockPos::new(3, 64, 3)), Ambient::new(9, 9, 0.5));
+
+        // Plains at x < 8 and desert from there on, in the chunk column at (0, 0).
+        biomes.load_column(0, 0, ColumnBiomes::from_fn(|x, _| if x < 8 { PLAINS } else { DESERT }));
+        assert_eq!(biomes.biome_at(BlockPos::new(7, 0, 3)), Some(PLAINS));
+        assert_eq!(biomes.biome_at(BlockPos::new(-1, 0, 3)), None);
+        assert_eq!(biomes.ambient_at(BlockPos::new(3, 64, 3)), Ambient::new(1, 10, 1.0));
+        // Y doesn't matter.
+        assert_eq!(biomes.ambient_at(BlockPos::new(3, -1000, 3)), Ambient::new(1, 10, 1.0));
+        // At the border, two of the three columns across are plains.
+        assert_eq!(biomes.ambient_at(BlockPos::new(7, 0, 3)), Ambient::new(1, 10, 2.0 / 3.0));
+        assert_eq!(biomes.ambient_at(BlockPos::new(8, 0, 3)), Ambient::new(2, 20, 1.0 / 3.0));
+        // At the edge of the chunk column, the unloaded neighbor doesn't count.
+        assert_eq!(biomes.ambient_at(BlockPos::new(0, 0, 0)), Ambient::new(1, 10, 1.0));
+
+        // Loading a desert to the west blends it into the cached edge of (0, 0).
+        biomes.load_column(-1, 0, ColumnBiomes::filled(DESERT));
+        assert_eq!(biomes.ambient_at(BlockPos::new(0, 0, 5)), Ambient::new(1, 10, 2.0 / 3.0));
+        assert_eq!(biomes.ambient_at(BlockPos::new(-1, 0, 5)), Ambient::new(2, 20, 1.0 / 3.0));
+
+        // Changing a biome drops the cached ambients around it.
+        assert_eq!(biomes.set_biome(BlockPos::new(4, 0, 4), DESERT), Some(PLAINS));
+        assert_eq!(biomes.ambient_at(BlockPos::new(4, 0, 4)).sky_factor, 8.0 / 9.0);
+        assert_eq!(biomes.set_biome(BlockPos::new(100, 0, 4), DESERT), None);
+        biomes.assign(PLAINS, Ambient::new(3, 30, 1.0));
+        assert_eq!(biomes.ambient_at(BlockPos::new(3, 0, 3)).fog_color_id, 3);
+
+        let unloaded = biomes.unload_column(-1, 0).unwrap();
+        assert_eq!(biomes.ambient_at(BlockPos::new(0, 0, 5)), Ambient::new(3, 30, 1.0));
+        let mut bytes = Vec::new();
+        let Ok(_) = unloaded.encode(&mut bytes);
+        assert_eq!(ColumnBiomes::decode(&mut bytes.as_slice()).unwrap(), unloaded);
+    }
+
+    #[test]
+    fn ambient_tie_test() {
+        let mut biomes = map(DEFAULT_BLEND_RADIUS);
+        biomes.load_column(0, 0, ColumnBiomes::from_fn(|x, _| if x < 8 { PLAINS } else { DESERT }));
+        // With a radius of four, x = 7 sees 5 plains and 4 desert columns across; x = 8 the other
+        // way around.
+        assert_eq!(biomes.ambient_at(BlockPos::new(7, 0, 7)).fog_color_id, 1);
+        assert_eq!(biomes.ambient_at(BlockPos::new(8, 0, 7)).fog_color_id, 2);
+        // Without blending, the ambient is just the biome's.
+        let mut biomes = map(0);
+        biomes.load_column(0, 0, ColumnBiomes::filled(DESERT));
+        assert_eq!(biomes.ambient_at(BlockPos::new(15, 0, 15)), Ambient::new(2, 20, 0.0));
+        // Ties go to the center's ids.
+        let votes = BTreeMap::from([(4, 2), (5, 2), (6, 1)]);
+        assert_eq!(winner(&votes, 5), 5);
+        assert_eq!(winner(&votes, 6), 4);
+    }
+}
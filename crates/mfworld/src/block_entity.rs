@@ -0,0 +1,328 @@
+//! Scheduling block entity ticks.
+//!
+//! Most machines spend most of their time waiting: for input, for space in their output, or for a
+//! timer. A [BlockEntityTicker] only ticks the block entities that are awake. A block entity puts
+//! itself to sleep by returning [TickResult::Sleep] or [TickResult::SleepFor] from its tick, and is
+//! woken again when its inventory changes, a neighbor updates, or its timer runs out.
+//!
+//! [Priority::High] block entities tick every tick they're awake. [Priority::Low] block entities
+//! share a budget of ticks per tick; the ones that don't fit are deferred to the next tick. Low
+//! priority block entities are visited round-robin, starting after the last one that ticked, so
+//! every one of them ticks eventually. Everything is visited in a fixed order (chunks in Morton
+//! order, then voxel index), so deferral is deterministic.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use mfgeometry::Direction;
+
+use crate::chunk::{voxel_index, BlockPos, ChunkPos};
+
+/// The voxel a block entity belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockEntityPos {
+    pub chunk: ChunkPos,
+    /// The [voxel index](voxel_index) within the chunk.
+    pub index: u16,
+}
+
+impl BlockEntityPos {
+    #[inline]
+    #[must_use]
+    pub const fn new(chunk: ChunkPos, index: u16) -> Self {
+        Self { chunk, index }
+    }
+
+    /// The block entity position of the voxel at `(x, y, z)` in world space.
+    #[inline]
+    #[must_use]
+    pub const fn containing(x: i32, y: i32, z: i32) -> Self {
+        Self::new(ChunkPos::containing(x, y, z), voxel_index(x, y, z) as u16)
+    }
+
+    /// The key block entities are visited in order of.
+    #[inline]
+    fn key(self) -> (u128, u16) {
+        (self.chunk.morton(), self.index)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Ticks every tick it's awake.
+    High,
+    /// Ticks when the budget allows.
+    #[default]
+    Low,
+}
+
+/// Why a sleeping block entity was woken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WakeReason {
+    InventoryChanged,
+    NeighborUpdate,
+    Timer,
+}
+
+/// What a block entity does after ticking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TickResult {
+    /// Stays awake.
+    Continue,
+    /// Sleeps until something wakes it.
+    Sleep,
+    /// Sleeps for this many ticks, or until something wakes it sooner.
+    SleepFor(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ticking {
+    pos: BlockEntityPos,
+    priority: Priority,
+    awake: bool,
+    /// The tick its timer wakes it at.
+    wake_at: Option<u64>,
+    /// Why it was woken, until it next ticks.
+    woken_by: Option<WakeReason>,
+}
+
+/// The block entities that ran and were deferred during one tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TickReport {
+    pub ran: usize,
+    /// Awake low priority block entities that didn't fit in the budget.
+    pub deferred: usize,
+}
+
+/// Decides which block entities tick, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEntityTicker {
+    entities: BTreeMap<(u128, u16), Ticking>,
+    /// Sleeping block entities by the tick their timer wakes them at.
+    timers: BTreeSet<(u64, (u128, u16))>,
+    /// The low priority block entity that ticked last.
+    cursor: Option<(u128, u16)>,
+    budget: usize,
+}
+
+impl Default for BlockEntityTicker {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BUDGET)
+    }
+}
+
+impl BlockEntityTicker {
+    pub const DEFAULT_BUDGET: usize = 1024;
+
+    /// A ticker that ticks at most `budget` low priority block entities per tick.
+    #[inline]
+    #[must_use]
+    pub const fn new(budget: usize) -> Self {
+        Self {
+            entities: BTreeMap::new(),
+            timers: BTreeSet::new(),
+            cursor: None,
+            budget,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn budget(&self) -> usize {
+        self.budget
+    }
+
+    #[inline]
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Adds a block entity, awake. Adding one that's already there changes its priority and wakes it.
+    pub fn add(&mut self, pos: BlockEntityPos, priority: Priority) {
+        self.remove(pos);
+        self.entities.insert(pos.key(), Ticking { pos, priority, awake: true, wake_at: None, woken_by: None });
+    }
+
+    /// Removes a block entity, returning `false` if it wasn't there.
+    pub fn remove(&mut self, pos: BlockEntityPos) -> bool {
+        let Some(entity) = self.entities.remove(&pos.key()) else {
+            return false;
+        };
+        if let Some(tick) = entity.wake_at {
+            self.timers.remove(&(tick, pos.key()));
+        }
+        true
+    }
+
+    /// Removes every block entity in `chunk`, when it unloads.
+    pub fn remove_chunk(&mut self, chunk: ChunkPos) {
+        let morton = chunk.morton();
+        let positions = self.entities.range((morton, 0)..=(morton, u16::MAX)).map(|(_, entity)| entity.pos).collect::<Vec<_>>();
+        for pos in positions {
+            self.remove(pos);
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, pos: BlockEntityPos) -> bool {
+        self.entities.contains_key(&pos.key())
+    }
+
+    /// Whether the block entity is awake. `None` if it isn't in the ticker.
+    #[inline]
+    #[must_use]
+    pub fn is_awake(&self, pos: BlockEntityPos) -> Option<bool> {
+        self.entities.get(&pos.key()).map(|entity| entity.awake)
+    }
+
+    /// Wakes a sleeping block entity. Waking one that's awake (or isn't in the ticker) does nothing.
+    pub fn wake(&mut self, pos: BlockEntityPos, reason: WakeReason) {
+        let Some(entity) = self.entities.get_mut(&pos.key()) else {
+            return;
+        };
+        if entity.awake {
+            return;
+        }
+        entity.awake = true;
+        entity.woken_by = Some(reason);
+        if let Some(tick) = entity.wake_at.take() {
+            self.timers.remove(&(tick, pos.key()));
+        }
+    }
+
+    /// Wakes the block entities next to the voxel at `pos`, after it changed.
+    pub fn wake_neighbors(&mut self, pos: BlockPos) {
+        for face in Direction::ALL {
+            if let Some((chunk, local)) = pos.checked_step(face).and_then(BlockPos::split) {
+                self.wake(BlockEntityPos::new(chunk, local.index() as u16), WakeReason::NeighborUpdate);
+            }
+        }
+    }
+
+    /// Runs one tick: wakes the block entities whose timers ran out, then ticks every awake high
+    /// priority block entity and as many awake low priority ones as the budget allows.
+    ///
+    /// `tick_entity` is given the block entity and why it was woken, if it was asleep.
+    pub fn tick<F>(&mut self, tick: u64, mut tick_entity: F) -> TickReport
+    where F: FnMut(BlockEntityPos, Option<WakeReason>) -> TickResult {
+        while let Some(&(wake_at, key)) = self.timers.first() && wake_at <= tick {
+            self.timers.pop_first();
+            if let Some(entity) = self.entities.get_mut(&key) {
+                entity.awake = true;
+                entity.woken_by = Some(WakeReason::Timer);
+                entity.wake_at = None;
+            }
+        }
+
+        let (mut high, mut low) = (Vec::new(), Vec::new());
+        for (&key, entity) in &self.entities {
+            match (entity.awake, entity.priority) {
+                (false, _) => (),
+                (true, Priority::High) => high.push(key),
+                (true, Priority::Low) => low.push(key),
+            }
+        }
+        // Low priority block entities take turns, starting after the last one that ticked.
+        let start = self.cursor.map_or(0, |cursor| low.partition_point(|&key| key <= cursor));
+        low.rotate_left(start);
+        let mut report = TickReport { ran: 0, deferred: low.len().saturating_sub(self.budget) };
+        low.truncate(self.budget);
+        if let Some(&last) = low.last() {
+            self.cursor = Some(last);
+        }
+
+        for key in high.into_iter().chain(low) {
+            let entity = self.entities.get_mut(&key).expect("Only block entities in the ticker are ticked.");
+            let result = tick_entity(entity.pos, entity.woken_by.take());
+            report.ran += 1;
+            match result {
+                TickResult::Continue => (),
+                TickResult::Sleep => entity.awake = false,
+                TickResult::SleepFor(ticks) => {
+                    entity.awake = false;
+                    let wake_at = tick.saturating_add(ticks.max(1));
+                    entity.wake_at = Some(wake_at);
+                    self.timers.insert((wake_at, key));
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleep_and_wake_test() {
+        let mut ticker = BlockEntityTicker::new(8);
+        let furnace = BlockEntityPos::containing(1, 2, 3);
+        let press = BlockEntityPos::containing(2, 2, 3);
+        ticker.add(furnace, Priority::High);
+        ticker.add(press, Priority::Low);
+
+        let mut woken = Vec::new();
+        let report = ticker.tick(0, |pos, reason| {
+            woken.push(reason);
+            if pos == furnace { TickResult::SleepFor(5) } else { TickResult::Sleep }
+        });
+        assert_eq!(report, TickReport { ran: 2, deferred: 0 });
+        assert_eq!(woken, [None, None]);
+        assert_eq!(ticker.tick(1, |_, _| TickResult::Continue).ran, 0);
+
+        // The furnace's timer wakes it, and placing a voxel next to the press wakes the press.
+        ticker.wake_neighbors(BlockPos::new(3, 2, 3));
+        let mut woken = Vec::new();
+        for tick in 2..=5 {
+            ticker.tick(tick, |pos, reason| {
+                woken.push((tick, pos, reason));
+                TickResult::Sleep
+            });
+        }
+        assert_eq!(woken, [(2, press, Some(WakeReason::NeighborUpdate)), (5, furnace, Some(WakeReason::Timer))]);
+
+        ticker.wake(press, WakeReason::InventoryChanged);
+        assert_eq!(ticker.is_awake(press), Some(true));
+        ticker.remove_chunk(ChunkPos::ORIGIN);
+        assert!(ticker.is_empty());
+    }
+
+    #[test]
+    fn tick_budget_test() {
+        let mut ticker = BlockEntityTicker::new(3);
+        let machines = (0..5).map(|x| BlockEntityPos::containing(x, 0, 0)).collect::<Vec<_>>();
+        for &pos in &machines {
+            ticker.add(pos, Priority::Low);
+        }
+        let boiler = BlockEntityPos::containing(0, 1, 0);
+        ticker.add(boiler, Priority::High);
+
+        let mut ran = Vec::new();
+        for tick in 0..2 {
+            let report = ticker.tick(tick, |pos, _| {
+                ran.push(pos);
+                TickResult::Continue
+            });
+            assert_eq!(report, TickReport { ran: 4, deferred: 2 });
+        }
+        // The boiler runs every tick; the machines take turns.
+        assert_eq!(ran, [
+            boiler, machines[0], machines[1], machines[2],
+            boiler, machines[3], machines[4], machines[0],
+        ]);
+    }
+}
@@ -0,0 +1,220 @@
+//! The height range of a dimension.
+//!
+//! Chunks are cubic, so nothing in the chunk layer stops a world from growing up or down forever.
+//! [HeightBounds] is the range of Y that a dimension allows voxels in. Bounds are whole chunks: the
+//! bottom is the bottom of a chunk and the top is the top of one, so a chunk is either entirely in
+//! a world or entirely out of it. Accesses outside of the bounds fail with a [HeightError].
+//!
+//! [HeightBounds::UNBOUNDED] reaches as far as a [ChunkPos] does, which is how worlds behaved
+//! before they had bounds, and is what saves without bounds load with.
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::chunk::{BlockPos, ChunkPos, CHUNK_SHIFT, CHUNK_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum HeightError {
+    #[error("Y {y} is below the bottom of the world ({min_y}).")]
+    BelowBottom {
+        y: i64,
+        min_y: i64,
+    },
+    #[error("Y {y} is above the build height ({max_y}).")]
+    AboveTop {
+        y: i64,
+        max_y: i64,
+    },
+    /// The bounds given to [HeightBounds::new] aren't whole chunks.
+    #[error("Invalid height bounds {min_y}..={max_y}: they must span whole chunks.")]
+    InvalidBounds {
+        min_y: i64,
+        max_y: i64,
+    },
+}
+
+/// The range of Y (inclusive) that a dimension allows voxels in. See the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeightBounds {
+    min_y: i64,
+    max_y: i64,
+}
+
+impl Default for HeightBounds {
+    #[inline]
+    fn default() -> Self {
+        Self::UNBOUNDED
+    }
+}
+
+impl HeightBounds {
+    /// Every Y that a [ChunkPos] reaches.
+    pub const UNBOUNDED: Self = Self {
+        min_y: (i32::MIN as i64) << CHUNK_SHIFT,
+        max_y: ((i32::MAX as i64 + 1) << CHUNK_SHIFT) - 1,
+    };
+
+    /// The bounds from `min_y` to `max_y` (inclusive). `min_y` must be the bottom of a chunk,
+    /// `max_y` the top of one, and both within [Self::UNBOUNDED].
+    pub const fn new(min_y: i64, max_y: i64) -> Result<Self, HeightError> {
+        let mask = CHUNK_SIZE as i64 - 1;
+        if min_y > max_y
+            || min_y & mask != 0
+            || max_y & mask != mask
+            || min_y < Self::UNBOUNDED.min_y
+            || max_y > Self::UNBOUNDED.max_y
+        {
+            return Err(HeightError::InvalidBounds { min_y, max_y });
+        }
+        Ok(Self { min_y, max_y })
+    }
+
+    /// The bounds spanning the chunks from `min_chunk_y` to `max_chunk_y` (inclusive).
+    pub const fn from_chunks(min_chunk_y: i32, max_chunk_y: i32) -> Result<Self, HeightError> {
+        Self::new(
+            (min_chunk_y as i64) << CHUNK_SHIFT,
+            ((max_chunk_y as i64 + 1) << CHUNK_SHIFT) - 1,
+        )
+    }
+
+    /// The lowest Y that voxels can be at.
+    #[inline]
+    #[must_use]
+    pub const fn min_y(self) -> i64 {
+        self.min_y
+    }
+
+    /// The highest Y that voxels can be at (the build height).
+    #[inline]
+    #[must_use]
+    pub const fn max_y(self) -> i64 {
+        self.max_y
+    }
+
+    /// The Y of the lowest chunks.
+    #[inline]
+    #[must_use]
+    pub const fn min_chunk_y(self) -> i32 {
+        (self.min_y >> CHUNK_SHIFT) as i32
+    }
+
+    /// The Y of the highest chunks.
+    #[inline]
+    #[must_use]
+    pub const fn max_chunk_y(self) -> i32 {
+        (self.max_y >> CHUNK_SHIFT) as i32
+    }
+
+    /// The number of voxels in a column.
+    #[inline]
+    #[must_use]
+    pub const fn height(self) -> u64 {
+        self.max_y.abs_diff(self.min_y) + 1
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn contains_y(self, y: i64) -> bool {
+        self.min_y <= y && y <= self.max_y
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn contains_chunk(self, chunk: ChunkPos) -> bool {
+        self.min_chunk_y() <= chunk.y && chunk.y <= self.max_chunk_y()
+    }
+
+    pub const fn check_y(self, y: i64) -> Result<(), HeightError> {
+        if y < self.min_y {
+            Err(HeightError::BelowBottom { y, min_y: self.min_y })
+        } else if y > self.max_y {
+            Err(HeightError::AboveTop { y, max_y: self.max_y })
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    pub const fn check_block(self, pos: BlockPos) -> Result<(), HeightError> {
+        self.check_y(pos.y)
+    }
+
+    /// Checks that `chunk` is in the world. Errors report the Y of the chunk's nearest voxel.
+    pub const fn check_chunk(self, chunk: ChunkPos) -> Result<(), HeightError> {
+        if chunk.y < self.min_chunk_y() {
+            Err(HeightError::BelowBottom { y: ((chunk.y as i64 + 1) << CHUNK_SHIFT) - 1, min_y: self.min_y })
+        } else if chunk.y > self.max_chunk_y() {
+            Err(HeightError::AboveTop { y: (chunk.y as i64) << CHUNK_SHIFT, max_y: self.max_y })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether `chunk` is the top chunk of its column, with nothing but sky above it.
+    #[inline]
+    #[must_use]
+    pub const fn is_top_chunk(self, chunk: ChunkPos) -> bool {
+        chunk.y == self.max_chunk_y()
+    }
+
+    /// The nearest Y in bounds.
+    #[inline]
+    #[must_use]
+    pub const fn clamp_y(self, y: i64) -> i64 {
+        if y < self.min_y {
+            self.min_y
+        } else if y > self.max_y {
+            self.max_y
+        } else {
+            y
+        }
+    }
+}
+
+// Layout: min y (i64), max y (i64).
+impl Encode for HeightBounds {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_i64(self.min_y)? + encoder.write_i64(self.max_y)?)
+    }
+}
+
+impl Decode for HeightBounds {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Self::new(decoder.read_i64()?, decoder.read_i64()?)
+            .map_err(|_| DecodeError::InvalidData("height bounds must span whole chunks"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn height_bounds_test() {
+        let bounds = HeightBounds::new(-64, 319).unwrap();
+        assert_eq!(HeightBounds::from_chunks(-4, 19), Ok(bounds));
+        assert_eq!((bounds.min_chunk_y(), bounds.max_chunk_y(), bounds.height()), (-4, 19, 384));
+        assert_eq!(bounds.check_y(-64), Ok(()));
+        assert_eq!(bounds.check_y(320), Err(HeightError::AboveTop { y: 320, max_y: 319 }));
+        assert_eq!(bounds.check_block(BlockPos::new(5, -65, 5)), Err(HeightError::BelowBottom { y: -65, min_y: -64 }));
+        assert_eq!(bounds.check_chunk(ChunkPos::new(0, -5, 0)), Err(HeightError::BelowBottom { y: -65, min_y: -64 }));
+        assert_eq!(bounds.check_chunk(ChunkPos::new(0, 20, 0)), Err(HeightError::AboveTop { y: 320, max_y: 319 }));
+        assert!(bounds.contains_chunk(ChunkPos::new(0, 19, 0)) && bounds.is_top_chunk(ChunkPos::new(7, 19, 7)));
+        assert_eq!((bounds.clamp_y(1000), bounds.clamp_y(-1000), bounds.clamp_y(3)), (319, -64, 3));
+
+        for (min_y, max_y) in [(-63, 319), (-64, 320), (16, 15), (i64::MIN, 15)] {
+            assert_eq!(HeightBounds::new(min_y, max_y), Err(HeightError::InvalidBounds { min_y, max_y }));
+        }
+        let unbounded = HeightBounds::UNBOUNDED;
+        assert_eq!(HeightBounds::from_chunks(i32::MIN, i32::MAX), Ok(unbounded));
+        assert!(unbounded.contains_chunk(ChunkPos::new(0, i32::MIN, 0)) && unbounded.contains_chunk(ChunkPos::new(0, i32::MAX, 0)));
+
+        let mut bytes = Vec::new();
+        assert_eq!(bounds.encode(&mut bytes).unwrap(), 16);
+        assert_eq!(HeightBounds::decode(&mut bytes.as_slice()).unwrap(), bounds);
+        bytes[15] ^= 1;
+        assert!(HeightBounds::decode(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! The encodings a [StoredChunk](super::stored::StoredChunk) can be saved in.
+//!
+//! Each chunk is saved in whichever encoding is smallest for it, recorded in a format byte. The
+//! byte is only written for encodings other than the original bit-packed one (the palette length
+//! says whether it's there), so chunks that are best bit-packed are saved exactly as before, and
+//! older readers still read them.
+
+use super::CHUNK_VOLUME;
+
+// Bit : Feature
+// 0   : Palette indices are run-length encoded.
+/// The features of a chunk's encoding, as the bits of its format byte. Readers reject bits they
+/// don't know, so new features can be added without being misread.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChunkFormat(u8);
+
+impl ChunkFormat {
+    /// Palette indices bit-packed in voxel order: the original encoding.
+    pub const PACKED: Self = Self(0);
+    /// Palette indices as runs of (length, index) varints in voxel order. Small for chunks of large
+    /// uniform areas (terrain layers, air, caves), large for busy chunks.
+    pub const RUNS: Self = Self(0b1);
+    /// Every format this version reads.
+    pub const ALL: [Self; 2] = [Self::PACKED, Self::RUNS];
+
+    const KNOWN_BITS: u8 = Self::RUNS.0;
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(bits: u8) -> Option<Self> {
+        if bits & !Self::KNOWN_BITS != 0 {
+            return None;
+        }
+        Some(Self(bits))
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn runs(self) -> bool {
+        self.0 & Self::RUNS.0 != 0
+    }
+
+    /// The format that encodes `indices` smallest. Ties go to [Self::PACKED].
+    #[must_use]
+    pub fn pick(indices: &[u16; CHUNK_VOLUME], palette_len: usize) -> Self {
+        let packed = indices_len(Self::PACKED, indices, palette_len);
+        // One more byte for the format byte.
+        if indices_len(Self::RUNS, indices, palette_len) + 1 < packed {
+            Self::RUNS
+        } else {
+            Self::PACKED
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self.0 {
+            0 => "packed",
+            _ => "runs",
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The number of bits each palette index is packed in.
+#[inline]
+pub(crate) const fn index_bits(palette_len: usize) -> u32 {
+    usize::BITS - palette_len.saturating_sub(1).leading_zeros()
+}
+
+/// Splits `indices` into runs of equal indices, in order.
+pub(crate) fn runs(indices: &[u16]) -> impl Iterator<Item = (usize, u16)> + '_ {
+    indices.chunk_by(|a, b| a == b).map(|run| (run.len(), run[0]))
+}
+
+/// The encoded length of the palette indices in `format`, in bytes.
+fn indices_len(format: ChunkFormat, indices: &[u16; CHUNK_VOLUME], palette_len: usize) -> usize {
+    let bits = index_bits(palette_len) as usize;
+    if bits == 0 {
+        0
+    } else if format.runs() {
+        runs(indices).map(|(len, index)| varint_len(len as u64) + varint_len(index as u64)).sum()
+    } else {
+        (CHUNK_VOLUME * bits).div_ceil(8)
+    }
+}
+
+#[inline]
+const fn varint_len(value: u64) -> usize {
+    (u64::BITS - (value | 1).leading_zeros()).div_ceil(7) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_format_test() {
+        assert_eq!(ChunkFormat::from_u8(0b10), None);
+        assert_eq!(ChunkFormat::from_u8(1), Some(ChunkFormat::RUNS));
+        assert_eq!(varint_len(127), 1);
+        assert_eq!(varint_len(128), 2);
+
+        // Layers pick runs, noise picks packing.
+        let mut layers = [0u16; CHUNK_VOLUME];
+        layers[..1024].fill(1);
+        assert_eq!(ChunkFormat::pick(&layers, 2), ChunkFormat::RUNS);
+        let mut noise = [0u16; CHUNK_VOLUME];
+        for (index, value) in noise.iter_mut().enumerate() {
+            *value = (index * 7 % 13) as u16;
+        }
+        assert_eq!(ChunkFormat::pick(&noise, 13), ChunkFormat::PACKED);
+        assert_eq!(ChunkFormat::pick(&[0; CHUNK_VOLUME], 1), ChunkFormat::PACKED);
+    }
+}
@@ -0,0 +1,164 @@
+//! Deterministic iteration over sets of chunks.
+//!
+//! Anything that visits loaded chunks during a tick has to visit them in the same order on every
+//! machine, or the simulation diverges. [LoadedChunks] keeps its chunks sorted by their
+//! [Morton code](ChunkPos::morton) as they're added and removed, so iterating it is always in the
+//! same order and nearby chunks are visited together.
+//!
+//! Systems that accept chunks from elsewhere can wrap the iterator in [check_order]. In debug
+//! builds it panics as soon as a chunk arrives out of Morton order, which catches chunks that were
+//! collected from a `HashMap` or `HashSet`. In release builds it does nothing.
+
+use std::collections::BTreeMap;
+
+use super::ChunkPos;
+
+/// A set of chunks that iterates in Morton order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadedChunks {
+    chunks: BTreeMap<u128, ChunkPos>,
+}
+
+impl LoadedChunks {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, chunk: ChunkPos) -> bool {
+        self.chunks.contains_key(&chunk.morton())
+    }
+
+    /// Adds `chunk`, returning `false` if it was already in the set.
+    #[inline]
+    pub fn insert(&mut self, chunk: ChunkPos) -> bool {
+        self.chunks.insert(chunk.morton(), chunk).is_none()
+    }
+
+    /// Removes `chunk`, returning `false` if it wasn't in the set.
+    #[inline]
+    pub fn remove(&mut self, chunk: ChunkPos) -> bool {
+        self.chunks.remove(&chunk.morton()).is_some()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// The chunks, in Morton order.
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = ChunkPos> + ExactSizeIterator + '_ {
+        self.chunks.values().copied()
+    }
+}
+
+impl FromIterator<ChunkPos> for LoadedChunks {
+    fn from_iter<T: IntoIterator<Item = ChunkPos>>(iter: T) -> Self {
+        Self {
+            chunks: iter.into_iter().map(|chunk| (chunk.morton(), chunk)).collect(),
+        }
+    }
+}
+
+impl Extend<ChunkPos> for LoadedChunks {
+    fn extend<T: IntoIterator<Item = ChunkPos>>(&mut self, iter: T) {
+        self.chunks.extend(iter.into_iter().map(|chunk| (chunk.morton(), chunk)));
+    }
+}
+
+/// An iterator that checks, in debug builds, that its chunks are in strictly increasing Morton
+/// order. See [check_order].
+#[derive(Debug, Clone)]
+pub struct CheckOrder<I> {
+    iter: I,
+    #[cfg(debug_assertions)]
+    last: Option<u128>,
+}
+
+impl<I: Iterator<Item = ChunkPos>> Iterator for CheckOrder<I> {
+    type Item = ChunkPos;
+
+    #[inline]
+    fn next(&mut self) -> Option<ChunkPos> {
+        let chunk = self.iter.next()?;
+        #[cfg(debug_assertions)]
+        {
+            let code = chunk.morton();
+            if let Some(last) = self.last {
+                assert!(last < code, "Chunk {chunk} was visited out of order. Iterate a LoadedChunks instead.");
+            }
+            self.last = Some(code);
+        }
+        Some(chunk)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Wraps `chunks` so that debug builds panic if they aren't in Morton order (or repeat a chunk).
+#[inline]
+pub fn check_order<I: IntoIterator<Item = ChunkPos>>(chunks: I) -> CheckOrder<I::IntoIter> {
+    CheckOrder {
+        iter: chunks.into_iter(),
+        #[cfg(debug_assertions)]
+        last: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_chunks_test() {
+        let chunks = [
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(-1, 0, 0),
+            ChunkPos::new(0, 0, 1),
+            ChunkPos::new(0, 1, 0),
+            ChunkPos::ORIGIN,
+            ChunkPos::new(-3, 7, -2),
+        ];
+        let mut loaded = chunks.into_iter().collect::<LoadedChunks>();
+        assert!(!loaded.insert(ChunkPos::ORIGIN));
+        // The order doesn't depend on the order the chunks were added in.
+        let mut reversed = LoadedChunks::new();
+        reversed.extend(chunks.into_iter().rev());
+        assert_eq!(loaded.iter().collect::<Vec<_>>(), reversed.iter().collect::<Vec<_>>());
+        assert_eq!(
+            loaded.iter().skip(2).collect::<Vec<_>>(),
+            [ChunkPos::ORIGIN, ChunkPos::new(1, 0, 0), ChunkPos::new(0, 1, 0), ChunkPos::new(0, 0, 1)],
+        );
+        assert_eq!(check_order(loaded.iter()).count(), chunks.len());
+
+        assert!(loaded.remove(ChunkPos::new(-3, 7, -2)));
+        assert!(!loaded.contains(ChunkPos::new(-3, 7, -2)));
+        assert_eq!(loaded.len(), 5);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of order")]
+    fn check_order_test() {
+        check_order([ChunkPos::ORIGIN, ChunkPos::new(1, 0, 0), ChunkPos::ORIGIN]).for_each(drop);
+    }
+}
@@ -0,0 +1,275 @@
+//! Small per-voxel state (growth stage, fill level, orientation) that doesn't warrant a block entity.
+
+use std::collections::HashMap;
+
+use mfcereal::{decode::Decode, encode::Encode};
+use mfgeometry::Orientation;
+
+use super::CHUNK_VOLUME;
+use crate::voxel::id::VoxelId;
+
+const NIBBLE_BYTES: usize = CHUNK_VOLUME / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MetadataError {
+    #[error("Metadata value {0} is not a valid Orientation.")]
+    InvalidOrientation(u8),
+}
+
+/// Packs an [Orientation] into 8 bits of metadata.
+#[inline(always)]
+pub const fn pack_orientation(orientation: Orientation) -> u8 {
+    orientation.as_u8()
+}
+
+/// Unpacks an [Orientation] from 8 bits of metadata, failing if the value is out of range.
+#[inline]
+pub const fn unpack_orientation(metadata: u8) -> Result<Orientation, MetadataError> {
+    match Orientation::from_u8(metadata) {
+        Some(orientation) => Ok(orientation),
+        None => Err(MetadataError::InvalidOrientation(metadata)),
+    }
+}
+
+/// The default metadata value for each voxel type. Voxel types without an entry default to `0`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetadataDefaults {
+    defaults: HashMap<VoxelId, u8>,
+}
+
+impl MetadataDefaults {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn set(&mut self, id: VoxelId, default: u8) -> Option<u8> {
+        self.defaults.insert(id, default)
+    }
+
+    #[inline]
+    pub fn get(&self, id: VoxelId) -> u8 {
+        self.defaults.get(&id).copied().unwrap_or(0)
+    }
+}
+
+/// Per-voxel metadata for a chunk.
+///
+/// Storage starts out uniform (no allocation), becomes a nibble array when a value
+/// that isn't the uniform value is set, and widens to a byte array when a value
+/// that doesn't fit in 4 bits is set.
+// Layout: tag (u8), followed by:
+//      0 (Uniform): value (u8)
+//      1 (Nibbles): 2048 bytes, low nibble first
+//      2 (Bytes)  : 4096 bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum MetadataLayer {
+    #[cereal(tag = 0)]
+    Uniform(u8),
+    #[cereal(tag = 1)]
+    Nibbles(#[cereal(with = boxed_bytes)] Box<[u8; NIBBLE_BYTES]>),
+    #[cereal(tag = 2)]
+    Bytes(#[cereal(with = boxed_bytes)] Box<[u8; CHUNK_VOLUME]>),
+}
+
+/// A boxed byte array, written without its length (the type has it).
+mod boxed_bytes {
+    use mfcereal::{decode::{DecodeError, Decoder}, encode::Encoder};
+
+    pub fn encode<E: Encoder, const N: usize>(bytes: &[u8; N], encoder: &mut E) -> Result<u64, E::Error> {
+        encoder.write_u8_slice(bytes.as_slice(), false)
+    }
+
+    pub fn decode<D: Decoder, const N: usize>(decoder: &mut D) -> Result<Box<[u8; N]>, DecodeError<D::Error>> {
+        let mut bytes = Box::new([0u8; N]);
+        decoder.read_exact(bytes.as_mut_slice())?;
+        Ok(bytes)
+    }
+}
+
+impl Default for MetadataLayer {
+    #[inline]
+    fn default() -> Self {
+        Self::Uniform(0)
+    }
+}
+
+impl MetadataLayer {
+    #[inline]
+    pub const fn uniform(value: u8) -> Self {
+        Self::Uniform(value)
+    }
+
+    /// The number of bits used per voxel (`0` when uniform).
+    #[inline]
+    pub const fn bits_per_voxel(&self) -> u32 {
+        match self {
+            MetadataLayer::Uniform(_) => 0,
+            MetadataLayer::Nibbles(_) => 4,
+            MetadataLayer::Bytes(_) => 8,
+        }
+    }
+
+    /// Gets the metadata at `index` (see [super::voxel_index]).
+    #[inline]
+    pub fn get(&self, index: usize) -> u8 {
+        match self {
+            MetadataLayer::Uniform(value) => *value,
+            MetadataLayer::Nibbles(nibbles) => (nibbles[index / 2] >> ((index & 1) * 4)) & 0xF,
+            MetadataLayer::Bytes(bytes) => bytes[index],
+        }
+    }
+
+    /// Sets the metadata at `index` (see [super::voxel_index]), returning the old value.
+    pub fn set(&mut self, index: usize, value: u8) -> u8 {
+        assert!(index < CHUNK_VOLUME, "Index out of bounds: {index}");
+        let old = self.get(index);
+        if old == value {
+            return old;
+        }
+        match self {
+            MetadataLayer::Uniform(uniform) => {
+                let uniform = *uniform;
+                if uniform <= 0xF && value <= 0xF {
+                    let mut nibbles = Box::new([uniform | (uniform << 4); NIBBLE_BYTES]);
+                    Self::set_nibble(&mut nibbles, index, value);
+                    *self = Self::Nibbles(nibbles);
+                } else {
+                    let mut bytes = Box::new([uniform; CHUNK_VOLUME]);
+                    bytes[index] = value;
+                    *self = Self::Bytes(bytes);
+                }
+            }
+            MetadataLayer::Nibbles(nibbles) => {
+                if value <= 0xF {
+                    Self::set_nibble(nibbles, index, value);
+                } else {
+                    let mut bytes = Box::new([0u8; CHUNK_VOLUME]);
+                    for (i, byte) in bytes.iter_mut().enumerate() {
+                        *byte = (nibbles[i / 2] >> ((i & 1) * 4)) & 0xF;
+                    }
+                    bytes[index] = value;
+                    *self = Self::Bytes(bytes);
+                }
+            }
+            MetadataLayer::Bytes(bytes) => bytes[index] = value,
+        }
+        old
+    }
+
+    #[inline]
+    fn set_nibble(nibbles: &mut [u8; NIBBLE_BYTES], index: usize, value: u8) {
+        let shift = (index & 1) * 4;
+        let byte = &mut nibbles[index / 2];
+        *byte = (*byte & !(0xF << shift)) | ((value & 0xF) << shift);
+    }
+
+    /// Resets the metadata at `index` to the default for `id`.
+    /// This should be called whenever the voxel at `index` changes type.
+    #[inline]
+    pub fn reset(&mut self, index: usize, id: VoxelId, defaults: &MetadataDefaults) -> u8 {
+        self.set(index, defaults.get(id))
+    }
+
+    /// Fills the entire layer with `value`, releasing the storage.
+    #[inline]
+    pub fn fill(&mut self, value: u8) {
+        *self = Self::Uniform(value);
+    }
+
+    /// Collapses the storage to the smallest representation that can hold every value.
+    pub fn compact(&mut self) {
+        let first = self.get(0);
+        let mut max = 0u8;
+        let mut uniform = true;
+        for index in 0..CHUNK_VOLUME {
+            let value = self.get(index);
+            uniform &= value == first;
+            max = max.max(value);
+        }
+        if uniform {
+            *self = Self::Uniform(first);
+        } else if max <= 0xF && matches!(self, Self::Bytes(_)) {
+            let mut nibbles = Box::new([0u8; NIBBLE_BYTES]);
+            for index in 0..CHUNK_VOLUME {
+                Self::set_nibble(&mut nibbles, index, self.get(index));
+            }
+            *self = Self::Nibbles(nibbles);
+        }
+    }
+
+    /// Reads the metadata at `index` as an [Orientation].
+    #[inline]
+    pub fn get_orientation(&self, index: usize) -> Result<Orientation, MetadataError> {
+        unpack_orientation(self.get(index))
+    }
+
+    /// Stores `orientation` at `index`, returning the previous metadata value.
+    #[inline]
+    pub fn set_orientation(&mut self, index: usize, orientation: Orientation) -> u8 {
+        self.set(index, pack_orientation(orientation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::voxel_index;
+
+    #[test]
+    fn metadata_layer_test() {
+        let mut layer = MetadataLayer::default();
+        assert_eq!(layer.bits_per_voxel(), 0);
+        layer.set(voxel_index(1, 2, 3), 7);
+        assert_eq!(layer.bits_per_voxel(), 4);
+        assert_eq!(layer.get(voxel_index(1, 2, 3)), 7);
+        assert_eq!(layer.get(voxel_index(0, 2, 3)), 0);
+        layer.set(voxel_index(0, 2, 3), 200);
+        assert_eq!(layer.bits_per_voxel(), 8);
+        assert_eq!(layer.get(voxel_index(1, 2, 3)), 7);
+        assert_eq!(layer.get(voxel_index(0, 2, 3)), 200);
+        layer.set(voxel_index(0, 2, 3), 0);
+        layer.compact();
+        assert_eq!(layer.bits_per_voxel(), 4);
+        layer.set(voxel_index(1, 2, 3), 0);
+        layer.compact();
+        assert_eq!(layer, MetadataLayer::Uniform(0));
+    }
+
+    #[test]
+    fn defaults_test() {
+        let wheat = VoxelId::new(5);
+        let mut defaults = MetadataDefaults::new();
+        defaults.set(wheat, 3);
+        let mut layer = MetadataLayer::uniform(9);
+        layer.reset(10, wheat, &defaults);
+        layer.reset(11, VoxelId::AIR, &defaults);
+        assert_eq!(layer.get(10), 3);
+        assert_eq!(layer.get(11), 0);
+        assert_eq!(layer.get(12), 9);
+    }
+
+    #[test]
+    fn orientation_test() {
+        let mut layer = MetadataLayer::default();
+        for (index, orientation) in Orientation::UNORIENTED.iter().enumerate() {
+            layer.set_orientation(index, orientation);
+            assert_eq!(layer.get_orientation(index), Ok(orientation));
+        }
+        layer.set(0, 192);
+        assert_eq!(layer.get_orientation(0), Err(MetadataError::InvalidOrientation(192)));
+    }
+
+    #[test]
+    fn roundtrip_test() {
+        let mut layers = vec![MetadataLayer::uniform(4), MetadataLayer::default(), MetadataLayer::default()];
+        layers[1].set(100, 15);
+        layers[2].set(100, 255);
+        for layer in layers {
+            let mut bytes = Vec::new();
+            layer.encode(&mut bytes).unwrap();
+            assert_eq!(MetadataLayer::decode(&mut bytes.as_slice()).unwrap(), layer);
+        }
+    }
+}
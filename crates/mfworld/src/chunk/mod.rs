@@ -1 +1,38 @@
-pub mod section;
\ No newline at end of file
+pub mod format;
+pub mod loaded;
+pub mod metadata;
+pub mod overlay;
+pub mod pos;
+pub mod section;
+pub mod stored;
+
+pub use pos::{BlockPos, ChunkPos, LocalPos};
+use mfcore::const_fmt::ConstStr;
+
+/// The width of a chunk along each axis in voxels.
+pub const CHUNK_SIZE: i32 = 16;
+/// `log2(CHUNK_SIZE)`, used to convert between voxel and chunk coordinates.
+pub const CHUNK_SHIFT: u32 = 4;
+/// Mask for the local (within chunk) part of a voxel coordinate.
+pub const CHUNK_MASK: i32 = CHUNK_SIZE - 1;
+/// The number of voxels in a chunk.
+pub const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Gets the index of a voxel within chunk-sized arrays (YZX order) from its local coordinate.
+/// Each component of the coordinate is wrapped to the chunk.
+#[inline(always)]
+pub const fn voxel_index(x: i32, y: i32, z: i32) -> usize {
+    (((y & CHUNK_MASK) << (CHUNK_SHIFT * 2)) | ((z & CHUNK_MASK) << CHUNK_SHIFT) | (x & CHUNK_MASK)) as usize
+}
+
+const _: () = {
+    if CHUNK_SIZE != 1 << CHUNK_SHIFT {
+        ConstStr::<64>::new()
+            .str("CHUNK_SIZE (")
+            .i64(CHUNK_SIZE as i64)
+            .str(") must equal 1 << CHUNK_SHIFT (")
+            .i64(1 << CHUNK_SHIFT)
+            .str(")")
+            .panic();
+    }
+};
@@ -0,0 +1,295 @@
+//! A second, sparse layer of voxel content that sits on top of the primary voxels.
+//!
+//! Snow cover, floor markings and cables don't replace the block they're on, and don't collide.
+//! Instead of a voxel type for every combination ("grass with snow"), they live in the chunk's
+//! [OverlayLayer], which has its own palette and an orientation per cell, and is only stored for
+//! the cells that have an overlay. The mesher draws each overlay as an attachment on the faces of
+//! the block in the same cell (see [OverlayLayer::attachments]).
+
+use std::collections::{BTreeMap, HashMap};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::{Direction, Orientation, PerFace};
+
+use super::{metadata::{pack_orientation, unpack_orientation}, CHUNK_VOLUME};
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OverlayId(u32);
+
+impl OverlayId {
+    #[inline(always)]
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    #[inline(always)]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// The overlay in a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlayState {
+    pub id: OverlayId,
+    pub orientation: Orientation,
+}
+
+impl OverlayState {
+    #[inline]
+    pub const fn new(id: OverlayId, orientation: Orientation) -> Self {
+        Self { id, orientation }
+    }
+}
+
+/// The overlays of a chunk, by voxel index (see [super::voxel_index]).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct OverlayLayer {
+    palette: Vec<OverlayId>,
+    /// Palette index and orientation of each cell with an overlay.
+    cells: BTreeMap<u16, (u16, Orientation)>,
+}
+
+impl OverlayLayer {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The overlay types in the layer. May contain types that are no longer used until
+    /// [Self::compact] is called, but never more than [CHUNK_VOLUME] of them.
+    #[inline]
+    pub fn palette(&self) -> &[OverlayId] {
+        &self.palette
+    }
+
+    /// The number of cells with an overlay.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<OverlayState> {
+        let &(palette_index, orientation) = self.cells.get(&(index as u16))?;
+        Some(OverlayState::new(self.palette[palette_index as usize], orientation))
+    }
+
+    /// Sets (or with `None`, removes) the overlay at `index`, returning the previous overlay.
+    /// Compacts the layer if the palette would outgrow the chunk.
+    pub fn set(&mut self, index: usize, state: Option<OverlayState>) -> Option<OverlayState> {
+        assert!(index < CHUNK_VOLUME, "Voxel index out of range: {index}");
+        let old = self.get(index);
+        match state {
+            Some(state) => {
+                let palette_index = match self.palette.iter().position(|&id| id == state.id) {
+                    Some(palette_index) => palette_index,
+                    None => {
+                        self.palette.push(state.id);
+                        self.palette.len() - 1
+                    }
+                };
+                // At most one past CHUNK_VOLUME, which fits.
+                self.cells.insert(index as u16, (palette_index as u16, state.orientation));
+                // At most CHUNK_VOLUME types are used at once, so the palette fits again once the
+                // unused ones are gone.
+                if self.palette.len() > CHUNK_VOLUME {
+                    self.compact();
+                }
+            }
+            None => {
+                self.cells.remove(&(index as u16));
+            }
+        }
+        old
+    }
+
+    /// The cells with an overlay, in voxel index order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, OverlayState)> + '_ {
+        self.cells.iter().map(|(&index, &(palette_index, orientation))| {
+            (index as usize, OverlayState::new(self.palette[palette_index as usize], orientation))
+        })
+    }
+
+    /// Removes unused palette entries.
+    pub fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+        for &(palette_index, _) in self.cells.values() {
+            used[palette_index as usize] = true;
+        }
+        let mut remap = vec![0u16; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old, (&id, &used)) in self.palette.iter().zip(&used).enumerate() {
+            if used {
+                remap[old] = palette.len() as u16;
+                palette.push(id);
+            }
+        }
+        for (palette_index, _) in self.cells.values_mut() {
+            *palette_index = remap[*palette_index as usize];
+        }
+        self.palette = palette;
+    }
+
+    /// Every face an overlay is drawn on, for the mesher: the faces of the block in the overlay's
+    /// cell that `registry` says the overlay attaches to, turned by the overlay's orientation.
+    pub fn attachments<'a>(&'a self, registry: &'a OverlayRegistry) -> impl Iterator<Item = OverlayAttachment> + 'a {
+        self.iter().flat_map(move |(index, state)| {
+            let faces = registry.faces(state.id).reoriented(state.orientation);
+            Direction::ALL.into_iter()
+                .filter(move |&face| faces[face])
+                .map(move |face| OverlayAttachment { index, state, face })
+        })
+    }
+}
+
+/// An overlay drawn on a face of the block in its cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverlayAttachment {
+    pub index: usize,
+    pub state: OverlayState,
+    pub face: Direction,
+}
+
+/// The faces each overlay type attaches to, unoriented. Snow attaches to the top face, a cable run
+/// along a wall to one side. Overlays without registered faces attach to every face.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OverlayRegistry {
+    faces: HashMap<OverlayId, PerFace<bool>>,
+}
+
+impl OverlayRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn register(&mut self, id: OverlayId, faces: PerFace<bool>) -> Option<PerFace<bool>> {
+        self.faces.insert(id, faces)
+    }
+
+    #[inline]
+    pub fn faces(&self, id: OverlayId) -> PerFace<bool> {
+        self.faces.get(&id).copied().unwrap_or(PerFace::splat(true))
+    }
+}
+
+// Layout: palette length (u16), palette ids (u32 each), cell count (u16),
+// followed by each cell as voxel index (u16), palette index (u16), orientation (u8), in voxel
+// index order.
+impl Encode for OverlayLayer {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        // `set` keeps the palette within the chunk.
+        assert!(self.palette.len() <= CHUNK_VOLUME, "Overlay palette is larger than a chunk.");
+        let mut written = encoder.write_u16(self.palette.len() as u16)?;
+        for id in &self.palette {
+            written += encoder.write_u32(id.get())?;
+        }
+        written += encoder.write_u16(self.cells.len() as u16)?;
+        for (&index, &(palette_index, orientation)) in &self.cells {
+            written += encoder.write_u16(index)?
+                + encoder.write_u16(palette_index)?
+                + encoder.write_u8(pack_orientation(orientation))?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for OverlayLayer {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let palette_len = decoder.read_u16()? as usize;
+        if palette_len > CHUNK_VOLUME {
+            return Err(DecodeError::InvalidData("invalid overlay palette length"));
+        }
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            palette.push(OverlayId::new(decoder.read_u32()?));
+        }
+        let cell_count = decoder.read_u16()? as usize;
+        if cell_count > CHUNK_VOLUME {
+            return Err(DecodeError::InvalidData("invalid overlay cell count"));
+        }
+        let mut cells = BTreeMap::new();
+        for _ in 0..cell_count {
+            let index = decoder.read_u16()?;
+            if index as usize >= CHUNK_VOLUME {
+                return Err(DecodeError::InvalidData("overlay index out of range"));
+            }
+            if cells.last_key_value().is_some_and(|(&last, _)| last >= index) {
+                return Err(DecodeError::InvalidData("overlay cells out of order"));
+            }
+            let palette_index = decoder.read_u16()?;
+            if palette_index as usize >= palette_len {
+                return Err(DecodeError::InvalidData("overlay palette index out of range"));
+            }
+            let orientation = unpack_orientation(decoder.read_u8()?)
+                .map_err(|_| DecodeError::InvalidData("invalid overlay orientation"))?;
+            cells.insert(index, (palette_index, orientation));
+        }
+        Ok(Self { palette, cells })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::voxel_index;
+
+    #[test]
+    fn overlay_layer_test() {
+        let snow = OverlayId::new(1);
+        let cable = OverlayId::new(2);
+        let mut registry = OverlayRegistry::new();
+        registry.register(snow, PerFace::new(|face| face == Direction::PosY));
+        registry.register(cable, PerFace::new(|face| face == Direction::NegZ));
+
+        let mut layer = OverlayLayer::new();
+        let roof = voxel_index(3, 8, 3);
+        let wall = voxel_index(0, 2, 7);
+        assert_eq!(layer.set(roof, Some(OverlayState::new(snow, Orientation::UNORIENTED))), None);
+        let turned = Orientation::UNORIENTED.rotate_y(1);
+        layer.set(wall, Some(OverlayState::new(cable, turned)));
+        assert_eq!(layer.len(), 2);
+        assert_eq!(layer.get(wall), Some(OverlayState::new(cable, turned)));
+
+        // The cable's face turns with it, and the snow stays on top.
+        let faces = layer.attachments(&registry).map(|attachment| (attachment.index, attachment.face)).collect::<Vec<_>>();
+        assert_eq!(faces, [(wall, turned.reface(Direction::NegZ)), (roof, Direction::PosY)]);
+
+        // Melting the snow leaves its palette entry until the layer is compacted.
+        assert_eq!(layer.set(roof, None).map(|state| state.id), Some(snow));
+        assert_eq!(layer.palette(), [snow, cable]);
+        layer.compact();
+        assert_eq!(layer.palette(), [cable]);
+        assert_eq!(layer.get(wall), Some(OverlayState::new(cable, turned)));
+        assert_eq!(layer.get(roof), None);
+    }
+
+    #[test]
+    fn overlay_palette_churn_test() {
+        // More overlay types than a chunk has cells pass through one cell.
+        let mut layer = OverlayLayer::new();
+        layer.set(1, Some(OverlayState::new(OverlayId::new(0), Orientation::UNORIENTED)));
+        for id in 1..CHUNK_VOLUME as u32 * 2 {
+            layer.set(0, Some(OverlayState::new(OverlayId::new(id), Orientation::UNORIENTED)));
+            assert!(layer.palette().len() <= CHUNK_VOLUME);
+        }
+        assert_eq!(layer.get(0).unwrap().id, OverlayId::new(CHUNK_VOLUME as u32 * 2 - 1));
+        assert_eq!(layer.get(1).unwrap().id, OverlayId::new(0));
+
+        let mut bytes = Vec::new();
+        let Ok(_) = layer.encode(&mut bytes);
+        assert_eq!(OverlayLayer::decode(&mut bytes.as_slice()).unwrap(), layer);
+    }
+}
@@ -0,0 +1,474 @@
+//! Typed world coordinates: [BlockPos] (a voxel), [ChunkPos] (a chunk) and [LocalPos] (a voxel
+//! within its chunk).
+//!
+//! A [BlockPos] splits into the chunk and local position it's in, and a chunk and local position
+//! join back into a [BlockPos]. Arithmetic is checked: stepping off the edge of the world (or of a
+//! chunk, for [LocalPos]) gives `None` instead of wrapping.
+//!
+//! [ChunkPos::containing] and [ChunkPos::min_voxel] stay on `i32` tuples: they serve code that
+//! works in `i32` voxel coordinates throughout ([StructureBounds](crate::structure::StructureBounds),
+//! [ChunkView](crate::debug::border::ChunkView) and [BlockEntityPos](crate::block_entity::BlockEntityPos)),
+//! where every voxel of a chunk fits.
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfcore::curve;
+use mfgeometry::Direction;
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
+
+use super::{CHUNK_MASK, CHUNK_SHIFT, CHUNK_SIZE, CHUNK_VOLUME};
+
+/// The position of a chunk in chunk coordinates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const ORIGIN: Self = Self::new(0, 0, 0);
+    
+    #[inline(always)]
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+    
+    /// Gets the [ChunkPos] of the chunk containing the voxel at `(x, y, z)`.
+    #[inline]
+    pub const fn containing(x: i32, y: i32, z: i32) -> Self {
+        Self::new(
+            x >> CHUNK_SHIFT,
+            y >> CHUNK_SHIFT,
+            z >> CHUNK_SHIFT,
+        )
+    }
+    
+    #[inline]
+    pub const fn offset(self, x: i32, y: i32, z: i32) -> Self {
+        Self::new(
+            self.x + x,
+            self.y + y,
+            self.z + z,
+        )
+    }
+    
+    /// The voxel coordinate of the minimum corner of the chunk, for the `i32` voxel math of
+    /// [StructureBounds](crate::structure::StructureBounds) and the seam checks. Use
+    /// [ChunkPos::min_block] everywhere else.
+    #[inline]
+    pub const fn min_voxel(self) -> (i32, i32, i32) {
+        (
+            self.x << CHUNK_SHIFT,
+            self.y << CHUNK_SHIFT,
+            self.z << CHUNK_SHIFT,
+        )
+    }
+    
+    /// The chunk's position on the Z-order (Morton) curve: the bits of `x`, `y` and `z` interleaved,
+    /// lowest first. Sorting by it keeps nearby chunks close together, and it's the order loaded
+    /// chunks are visited in (see [LoadedChunks](super::loaded::LoadedChunks)). Coordinates are
+    /// biased so that negative coordinates sort before positive ones.
+    #[inline]
+    #[must_use]
+    pub const fn morton(self) -> u128 {
+        curve::morton3(self.x, self.y, self.z)
+    }
+
+    /// The position of the chunk's voxel at `local`.
+    #[inline]
+    #[must_use]
+    pub const fn block(self, local: LocalPos) -> BlockPos {
+        BlockPos::new(
+            ((self.x as i64) << CHUNK_SHIFT) + local.x() as i64,
+            ((self.y as i64) << CHUNK_SHIFT) + local.y() as i64,
+            ((self.z as i64) << CHUNK_SHIFT) + local.z() as i64,
+        )
+    }
+
+    /// The chunk's minimum corner.
+    #[inline]
+    #[must_use]
+    pub const fn min_block(self) -> BlockPos {
+        self.block(LocalPos::MIN)
+    }
+
+    /// The neighbouring chunk through `face`, or `None` past the edge of the world.
+    #[inline]
+    #[must_use]
+    pub const fn checked_step(self, face: Direction) -> Option<Self> {
+        let (dx, dy, dz) = face.to_ituple();
+        match (self.x.checked_add(dx), self.y.checked_add(dy), self.z.checked_add(dz)) {
+            (Some(x), Some(y), Some(z)) => Some(Self::new(x, y, z)),
+            _ => None,
+        }
+    }
+
+    /// The largest per-axis distance between two chunks.
+    #[inline]
+    pub const fn chebyshev_distance(self, other: Self) -> u32 {
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+        let dz = self.z.abs_diff(other.z);
+        let dxy = if dx > dy { dx } else { dy };
+        if dxy > dz { dxy } else { dz }
+    }
+}
+
+impl From<(i32, i32, i32)> for ChunkPos {
+    #[inline]
+    fn from((x, y, z): (i32, i32, i32)) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+impl From<ChunkPos> for (i32, i32, i32) {
+    #[inline]
+    fn from(value: ChunkPos) -> Self {
+        (value.x, value.y, value.z)
+    }
+}
+
+// Hashes the same as the `(x, y, z)` tuple.
+impl DeterministicHash for ChunkPos {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        (self.x, self.y, self.z).deterministic_hash(hasher);
+    }
+}
+
+// Layout: x (i32), y (i32), z (i32).
+impl Encode for ChunkPos {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_i32(self.x)? + encoder.write_i32(self.y)? + encoder.write_i32(self.z)?)
+    }
+}
+
+impl Decode for ChunkPos {
+    #[inline]
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self::new(decoder.read_i32()?, decoder.read_i32()?, decoder.read_i32()?))
+    }
+}
+
+impl std::fmt::Display for ChunkPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}, {}]", self.x, self.y, self.z)
+    }
+}
+
+/// The position of a voxel in world (voxel) coordinates.
+///
+/// Ordered by `x`, then `y`, then `z`. Sort by [BlockPos::morton] to keep nearby voxels together.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockPos {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl BlockPos {
+    pub const ORIGIN: Self = Self::new(0, 0, 0);
+
+    #[inline(always)]
+    pub const fn new(x: i64, y: i64, z: i64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The chunk containing the voxel, or `None` if it's further out than any [ChunkPos] reaches.
+    #[inline]
+    #[must_use]
+    pub const fn chunk(self) -> Option<ChunkPos> {
+        const fn chunk_axis(value: i64) -> Option<i32> {
+            let chunk = value >> CHUNK_SHIFT;
+            if chunk < i32::MIN as i64 || chunk > i32::MAX as i64 {
+                return None;
+            }
+            Some(chunk as i32)
+        }
+        match (chunk_axis(self.x), chunk_axis(self.y), chunk_axis(self.z)) {
+            (Some(x), Some(y), Some(z)) => Some(ChunkPos::new(x, y, z)),
+            _ => None,
+        }
+    }
+
+    /// The voxel's position within its chunk.
+    #[inline]
+    #[must_use]
+    pub const fn local(self) -> LocalPos {
+        LocalPos::wrapping(self.x as i32, self.y as i32, self.z as i32)
+    }
+
+    /// The chunk containing the voxel and its position within it (see [BlockPos::chunk]).
+    #[inline]
+    #[must_use]
+    pub const fn split(self) -> Option<(ChunkPos, LocalPos)> {
+        match self.chunk() {
+            Some(chunk) => Some((chunk, self.local())),
+            None => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn checked_offset(self, dx: i64, dy: i64, dz: i64) -> Option<Self> {
+        match (self.x.checked_add(dx), self.y.checked_add(dy), self.z.checked_add(dz)) {
+            (Some(x), Some(y), Some(z)) => Some(Self::new(x, y, z)),
+            _ => None,
+        }
+    }
+
+    /// The neighbouring voxel through `face`, or `None` past the edge of the world.
+    #[inline]
+    #[must_use]
+    pub const fn checked_step(self, face: Direction) -> Option<Self> {
+        let (dx, dy, dz) = face.to_ituple();
+        self.checked_offset(dx as i64, dy as i64, dz as i64)
+    }
+
+    /// The number of face steps to `other`, saturating.
+    #[inline]
+    #[must_use]
+    pub const fn manhattan_distance(self, other: Self) -> u64 {
+        self.x.abs_diff(other.x).saturating_add(self.y.abs_diff(other.y)).saturating_add(self.z.abs_diff(other.z))
+    }
+
+    /// The voxel's position on the Z-order (Morton) curve, most significant word first (see
+    /// [ChunkPos::morton]).
+    #[inline]
+    #[must_use]
+    pub const fn morton(self) -> [u64; 3] {
+        curve::morton3_i64(self.x, self.y, self.z)
+    }
+
+    /// The position as `i32` coordinates, the way most of the world addresses voxels, or `None`
+    /// if it doesn't fit.
+    #[inline]
+    #[must_use]
+    pub const fn to_i32(self) -> Option<(i32, i32, i32)> {
+        const fn narrow(value: i64) -> Option<i32> {
+            if value < i32::MIN as i64 || value > i32::MAX as i64 {
+                return None;
+            }
+            Some(value as i32)
+        }
+        match (narrow(self.x), narrow(self.y), narrow(self.z)) {
+            (Some(x), Some(y), Some(z)) => Some((x, y, z)),
+            _ => None,
+        }
+    }
+}
+
+impl From<(i32, i32, i32)> for BlockPos {
+    #[inline]
+    fn from((x, y, z): (i32, i32, i32)) -> Self {
+        Self::new(x as i64, y as i64, z as i64)
+    }
+}
+
+impl From<(i64, i64, i64)> for BlockPos {
+    #[inline]
+    fn from((x, y, z): (i64, i64, i64)) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
+/// Steps to the neighbouring voxel through the face.
+///
+/// # Panics
+/// Panics past the edge of the world. Use [BlockPos::checked_step] near it.
+impl std::ops::Add<Direction> for BlockPos {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, face: Direction) -> Self {
+        self.checked_step(face).expect("BlockPos overflowed.")
+    }
+}
+
+/// Steps to the neighbouring voxel through the opposite face.
+///
+/// # Panics
+/// Panics past the edge of the world. Use [BlockPos::checked_step] near it.
+impl std::ops::Sub<Direction> for BlockPos {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, face: Direction) -> Self {
+        self.checked_step(face.invert()).expect("BlockPos overflowed.")
+    }
+}
+
+// Hashes the same as the `(x, y, z)` tuple.
+impl DeterministicHash for BlockPos {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        (self.x, self.y, self.z).deterministic_hash(hasher);
+    }
+}
+
+// Layout: x (i64), y (i64), z (i64).
+impl Encode for BlockPos {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_i64(self.x)? + encoder.write_i64(self.y)? + encoder.write_i64(self.z)?)
+    }
+}
+
+impl Decode for BlockPos {
+    #[inline]
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self::new(decoder.read_i64()?, decoder.read_i64()?, decoder.read_i64()?))
+    }
+}
+
+impl std::fmt::Display for BlockPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}, {})", self.x, self.y, self.z)
+    }
+}
+
+/// The position of a voxel within its chunk, each coordinate in `0..CHUNK_SIZE`.
+///
+/// Stored as the voxel's index in chunk-sized arrays (see [super::voxel_index]), and ordered by it.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalPos(u16);
+
+impl LocalPos {
+    pub const MIN: Self = Self(0);
+    pub const MAX: Self = Self(CHUNK_VOLUME as u16 - 1);
+
+    /// `None` if a coordinate is outside `0..CHUNK_SIZE`.
+    #[inline]
+    #[must_use]
+    pub const fn new(x: i32, y: i32, z: i32) -> Option<Self> {
+        if x < 0 || x >= CHUNK_SIZE || y < 0 || y >= CHUNK_SIZE || z < 0 || z >= CHUNK_SIZE {
+            return None;
+        }
+        Some(Self::wrapping(x, y, z))
+    }
+
+    /// Each coordinate wrapped to the chunk.
+    #[inline]
+    #[must_use]
+    pub const fn wrapping(x: i32, y: i32, z: i32) -> Self {
+        Self(super::voxel_index(x, y, z) as u16)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_index(index: usize) -> Option<Self> {
+        if index >= CHUNK_VOLUME {
+            return None;
+        }
+        Some(Self(index as u16))
+    }
+
+    /// The voxel's index in chunk-sized arrays.
+    #[inline(always)]
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn x(self) -> i32 {
+        self.0 as i32 & CHUNK_MASK
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn y(self) -> i32 {
+        (self.0 as i32 >> (CHUNK_SHIFT * 2)) & CHUNK_MASK
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn z(self) -> i32 {
+        (self.0 as i32 >> CHUNK_SHIFT) & CHUNK_MASK
+    }
+
+    /// The neighbouring voxel through `face`, or `None` if it's in another chunk.
+    #[inline]
+    #[must_use]
+    pub const fn checked_step(self, face: Direction) -> Option<Self> {
+        let (dx, dy, dz) = face.to_ituple();
+        Self::new(self.x() + dx, self.y() + dy, self.z() + dz)
+    }
+
+    /// The voxel's position on the Z-order (Morton) curve within the chunk.
+    #[inline]
+    #[must_use]
+    pub const fn morton(self) -> u16 {
+        curve::morton3(self.x(), self.y(), self.z()) as u16
+    }
+}
+
+impl From<LocalPos> for (i32, i32, i32) {
+    #[inline]
+    fn from(value: LocalPos) -> Self {
+        (value.x(), value.y(), value.z())
+    }
+}
+
+// Hashes the same as its index as a `u16`.
+impl DeterministicHash for LocalPos {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_u16(self.0);
+    }
+}
+
+// Layout: index (u16).
+impl Encode for LocalPos {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        encoder.write_u16(self.0)
+    }
+}
+
+impl Decode for LocalPos {
+    #[inline]
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Self::from_index(decoder.read_u16()? as usize).ok_or(DecodeError::InvalidData("local position out of range"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_pos_test() {
+        let pos = BlockPos::new(-1, 17, 35);
+        let (chunk, local) = pos.split().unwrap();
+        assert_eq!((chunk, <(i32, i32, i32)>::from(local)), (ChunkPos::new(-1, 1, 2), (15, 1, 3)));
+        assert_eq!(chunk.block(local), pos);
+        assert_eq!(local.index(), crate::chunk::voxel_index(-1, 17, 35));
+        assert_eq!(chunk.min_block(), BlockPos::new(-16, 16, 32));
+
+        assert_eq!(pos + Direction::PosX - Direction::PosX, pos);
+        assert_eq!(pos + Direction::NegY, BlockPos::new(-1, 16, 35));
+        assert_eq!(BlockPos::new(i64::MAX, 0, 0).checked_step(Direction::PosX), None);
+        assert_eq!(BlockPos::new(i64::MAX, 0, 0).chunk(), None);
+        assert_eq!(BlockPos::new(i64::MAX, 0, 0).to_i32(), None);
+        assert_eq!(BlockPos::from((3, -4, 5)).to_i32(), Some((3, -4, 5)));
+
+        // Stepping out of a chunk gives no local position, but moves the block into the next chunk.
+        let edge = LocalPos::new(15, 0, 7).unwrap();
+        assert_eq!(edge.checked_step(Direction::PosX), None);
+        assert_eq!(edge.checked_step(Direction::NegX), LocalPos::new(14, 0, 7));
+        assert_eq!((chunk.block(edge) + Direction::PosX).chunk(), chunk.checked_step(Direction::PosX));
+        assert_eq!(LocalPos::new(16, 0, 0), None);
+        assert_eq!(LocalPos::MAX.morton(), 0xFFF);
+
+        let mut positions = [BlockPos::new(1, 0, 0), BlockPos::new(0, 1, 0), BlockPos::new(-1, -1, -1), BlockPos::ORIGIN];
+        positions.sort_by_key(|pos| pos.morton());
+        assert_eq!(positions, [BlockPos::new(-1, -1, -1), BlockPos::ORIGIN, BlockPos::new(1, 0, 0), BlockPos::new(0, 1, 0)]);
+    }
+}
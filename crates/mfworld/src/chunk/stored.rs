@@ -0,0 +1,399 @@
+//! The saved form of a chunk's voxels.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    bits::{BitReader, BitWriter},
+    decode::{decoder_read_bytes, Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::Orientation;
+use mfhash::{
+    deterministic_hash_u64,
+    sampled::{Coverage, SampledHash},
+};
+
+use super::{
+    format::{self, index_bits, ChunkFormat},
+    metadata::{pack_orientation, MetadataError, MetadataLayer},
+    overlay::OverlayLayer,
+    ChunkPos, CHUNK_VOLUME,
+};
+use crate::{history::VoxelState, voxel::id::VoxelId};
+
+/// The voxels of a chunk as they are saved: a palette of the voxel types in the chunk, the palette
+/// index of each voxel, the orientation of each voxel, the block entities, and the overlays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredChunk {
+    palette: Vec<VoxelId>,
+    indices: Box<[u16; CHUNK_VOLUME]>,
+    orientations: MetadataLayer,
+    /// Block entity data by voxel index. The data belongs to the owner of the block entity type
+    /// and isn't interpreted here.
+    pub block_entities: BTreeMap<u16, Vec<u8>>,
+    /// Snow, markings and other content on top of the voxels (see [super::overlay]).
+    pub overlay: OverlayLayer,
+}
+
+impl Default for StoredChunk {
+    /// A chunk of unoriented air.
+    fn default() -> Self {
+        Self {
+            palette: vec![VoxelId::AIR],
+            indices: Box::new([0; CHUNK_VOLUME]),
+            orientations: MetadataLayer::uniform(pack_orientation(Orientation::UNORIENTED)),
+            block_entities: BTreeMap::new(),
+            overlay: OverlayLayer::new(),
+        }
+    }
+}
+
+impl StoredChunk {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The voxel types in the chunk. May contain types that are no longer used until [Self::compact]
+    /// is called, but never more than [CHUNK_VOLUME] of them.
+    #[inline]
+    pub fn palette(&self) -> &[VoxelId] {
+        &self.palette
+    }
+
+    /// The palette index of the voxel at `index` (see [super::voxel_index]).
+    #[inline]
+    pub fn palette_index(&self, index: usize) -> u16 {
+        self.indices[index]
+    }
+
+    #[inline]
+    pub fn id(&self, index: usize) -> VoxelId {
+        self.palette[self.indices[index] as usize]
+    }
+
+    #[inline]
+    pub fn orientation(&self, index: usize) -> Result<Orientation, MetadataError> {
+        self.orientations.get_orientation(index)
+    }
+
+    /// The voxel at `index`. Invalid orientations read as [Orientation::UNORIENTED].
+    #[inline]
+    pub fn get(&self, index: usize) -> VoxelState {
+        VoxelState::new(self.id(index), self.orientation(index).unwrap_or(Orientation::UNORIENTED))
+    }
+
+    /// Sets the voxel at `index`, returning the previous voxel. Compacts the chunk if the palette
+    /// would outgrow it.
+    pub fn set(&mut self, index: usize, state: VoxelState) -> VoxelState {
+        let old = self.get(index);
+        let palette_index = match self.palette.iter().position(|&id| id == state.id) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(state.id);
+                self.palette.len() - 1
+            }
+        };
+        // At most one past CHUNK_VOLUME, which fits.
+        self.indices[index] = palette_index as u16;
+        self.orientations.set_orientation(index, state.orientation);
+        // A chunk uses at most CHUNK_VOLUME types at once, so the palette fits again once the
+        // unused ones are gone.
+        if self.palette.len() > CHUNK_VOLUME {
+            self.compact();
+        }
+        old
+    }
+
+    /// A [SampledHash] of the voxels (not the block entities or overlays), for desync checks. The
+    /// sample is picked from `seed` and the chunk's position, so that neighbouring chunks sample
+    /// different voxels. Varying `seed` (by tick, say) covers the whole chunk over time.
+    pub fn sampled_hash(&self, chunk: ChunkPos, seed: u64, coverage: Coverage) -> SampledHash {
+        SampledHash::new(deterministic_hash_u64((seed, chunk)), coverage, CHUNK_VOLUME, |index| self.get(index))
+    }
+
+    /// The number of voxels using each palette entry, in palette order.
+    pub fn palette_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.palette.len()];
+        for &palette_index in self.indices.iter() {
+            counts[palette_index as usize] += 1;
+        }
+        counts
+    }
+
+    /// Removes unused palette entries (of the voxels and the overlays) and compacts the orientation
+    /// storage.
+    pub fn compact(&mut self) {
+        let counts = self.palette_counts();
+        let mut remap = vec![0u16; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old, (&id, &count)) in self.palette.iter().zip(&counts).enumerate() {
+            if count != 0 {
+                remap[old] = palette.len() as u16;
+                palette.push(id);
+            }
+        }
+        for palette_index in self.indices.iter_mut() {
+            *palette_index = remap[*palette_index as usize];
+        }
+        self.palette = palette;
+        self.orientations.compact();
+        self.overlay.compact();
+    }
+
+    /// The format [Encode] saves the chunk in: the smallest.
+    #[inline]
+    pub fn format(&self) -> ChunkFormat {
+        ChunkFormat::pick(&self.indices, self.palette.len())
+    }
+
+    /// Encodes the chunk in `format` rather than the smallest, for comparing formats.
+    pub fn encode_as<E: Encoder>(&self, format: ChunkFormat, encoder: &mut E) -> Result<u64, E::Error> {
+        // `set` keeps the palette within the chunk, and the length shares its u16 with the flags.
+        assert!(self.palette.len() <= CHUNK_VOLUME, "Palette is larger than a chunk.");
+        let mut flags = if self.overlay.is_empty() { 0 } else { HAS_OVERLAY };
+        if format != ChunkFormat::PACKED {
+            flags |= HAS_FORMAT;
+        }
+        let mut written = encoder.write_u16(self.palette.len() as u16 | flags)?;
+        if flags & HAS_FORMAT != 0 {
+            written += encoder.write_u8(format.as_u8())?;
+        }
+        for id in &self.palette {
+            written += encoder.write_u32(id.get())?;
+        }
+        let bits = self.index_bits();
+        if bits != 0 && format.runs() {
+            for (len, palette_index) in format::runs(self.indices.as_slice()) {
+                written += encoder.write_varint(len as u64)? + encoder.write_varint(palette_index as u64)?;
+            }
+        } else if bits != 0 {
+            let mut writer = BitWriter::new(encoder);
+            for &palette_index in self.indices.iter() {
+                writer.write_bits(palette_index as u64, bits)?;
+            }
+            written += writer.finish()?;
+        }
+        written += self.orientations.encode(encoder)?;
+        written += encoder.write_u32(self.block_entities.len() as u32)?;
+        for (&index, data) in &self.block_entities {
+            written += encoder.write_u16(index)?
+                + encoder.write_u32(data.len() as u32)?
+                + encoder.write_u8_slice(data, false)?;
+        }
+        if flags & HAS_OVERLAY != 0 {
+            written += self.overlay.encode(encoder)?;
+        }
+        Ok(written)
+    }
+
+    /// The number of bits used to store each palette index.
+    #[inline]
+    fn index_bits(&self) -> u32 {
+        index_bits(self.palette.len())
+    }
+}
+
+/// Set in the palette length when the chunk has overlays. Palette lengths are at most
+/// [CHUNK_VOLUME], so the top bits are free, and chunks without overlays are stored as they were
+/// before overlays existed.
+const HAS_OVERLAY: u16 = 0x8000;
+/// Set in the palette length when a format byte follows it (see [super::format]).
+const HAS_FORMAT: u16 = 0x4000;
+
+// Layout: palette length (u16), format (u8, only if the palette length has the HAS_FORMAT bit set),
+// palette ids (u32 each), palette indices (omitted when the palette has one entry; in the packed
+// format ceil(log2(palette length)) bits each in voxel index order, packed by BitWriter; in the
+// runs format (run length, palette index) varint pairs in voxel index order),
+// orientations (metadata layer), block entity count (u32), followed by each block entity as
+// voxel index (u16), length (u32), data, then the overlay layer if the palette length has the
+// HAS_OVERLAY bit set.
+impl Encode for StoredChunk {
+    #[inline]
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        self.encode_as(self.format(), encoder)
+    }
+}
+
+impl Decode for StoredChunk {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let palette_len = decoder.read_u16()?;
+        let has_overlay = palette_len & HAS_OVERLAY != 0;
+        let format = if palette_len & HAS_FORMAT != 0 {
+            ChunkFormat::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("unknown chunk format"))?
+        } else {
+            ChunkFormat::PACKED
+        };
+        let palette_len = (palette_len & !(HAS_OVERLAY | HAS_FORMAT)) as usize;
+        if palette_len == 0 || palette_len > CHUNK_VOLUME {
+            return Err(DecodeError::InvalidData("invalid chunk palette length"));
+        }
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            palette.push(VoxelId::new(decoder.read_u32()?));
+        }
+        let mut indices = Box::new([0u16; CHUNK_VOLUME]);
+        let bits = index_bits(palette_len);
+        if bits != 0 && format.runs() {
+            let mut start = 0;
+            while start < CHUNK_VOLUME {
+                let len = decoder.read_varint()?;
+                let palette_index = decoder.read_varint()?;
+                if len == 0 || len > (CHUNK_VOLUME - start) as u64 {
+                    return Err(DecodeError::InvalidData("chunk index run out of range"));
+                }
+                if palette_index >= palette_len as u64 {
+                    return Err(DecodeError::InvalidData("chunk palette index out of range"));
+                }
+                indices[start..start + len as usize].fill(palette_index as u16);
+                start += len as usize;
+            }
+        } else if bits != 0 {
+            let mut reader = BitReader::new(decoder);
+            for palette_index in indices.iter_mut() {
+                *palette_index = reader.read_bits(bits)? as u16;
+                if *palette_index as usize >= palette_len {
+                    return Err(DecodeError::InvalidData("chunk palette index out of range"));
+                }
+            }
+        }
+        let orientations = MetadataLayer::decode(decoder)?;
+        let mut block_entities = BTreeMap::new();
+        for _ in 0..decoder.read_u32()? {
+            let index = decoder.read_u16()?;
+            if index as usize >= CHUNK_VOLUME {
+                return Err(DecodeError::InvalidData("block entity index out of range"));
+            }
+            let len = decoder.read_u32()? as usize;
+            let data = decoder_read_bytes(decoder, len)?;
+            if block_entities.insert(index, data).is_some() {
+                return Err(DecodeError::InvalidData("duplicate block entity"));
+            }
+        }
+        let overlay = if has_overlay {
+            let overlay = OverlayLayer::decode(decoder)?;
+            if overlay.is_empty() {
+                return Err(DecodeError::InvalidData("empty chunk overlay"));
+            }
+            overlay
+        } else {
+            OverlayLayer::new()
+        };
+        Ok(Self { palette, indices, orientations, block_entities, overlay })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::voxel_index;
+
+    #[test]
+    fn stored_chunk_test() {
+        let mut chunk = StoredChunk::new();
+        let mut bytes = Vec::new();
+        chunk.encode(&mut bytes).unwrap();
+        assert_eq!(StoredChunk::decode(&mut bytes.as_slice()).unwrap(), chunk);
+
+        let machine = VoxelState::new(VoxelId::new(7), Orientation::UNORIENTED.rotate_y(1));
+        for x in 0..3 {
+            chunk.set(voxel_index(x, 0, 0), VoxelState::new(VoxelId::new(x as u32 + 1), Orientation::UNORIENTED));
+        }
+        assert_eq!(chunk.set(voxel_index(5, 5, 5), machine), VoxelState::AIR);
+        chunk.block_entities.insert(voxel_index(5, 5, 5) as u16, vec![1, 2, 3]);
+        chunk.set(voxel_index(2, 0, 0), VoxelState::AIR);
+        assert_eq!(chunk.palette().len(), 5);
+        chunk.compact();
+        assert_eq!(chunk.palette(), [VoxelId::AIR, VoxelId::new(1), VoxelId::new(2), VoxelId::new(7)]);
+        assert_eq!(chunk.palette_counts(), [CHUNK_VOLUME - 3, 1, 1, 1]);
+        assert_eq!(chunk.get(voxel_index(5, 5, 5)), machine);
+
+        bytes.clear();
+        chunk.encode(&mut bytes).unwrap();
+        let decoded = StoredChunk::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, chunk);
+        // Truncated data never decodes.
+        assert!(StoredChunk::decode(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn palette_churn_test() {
+        // More voxel types than a chunk has voxels pass through one voxel.
+        let mut chunk = StoredChunk::new();
+        chunk.set(1, VoxelState::new(VoxelId::new(1), Orientation::UNORIENTED));
+        for id in 2..CHUNK_VOLUME as u32 * 2 {
+            chunk.set(0, VoxelState::new(VoxelId::new(id), Orientation::UNORIENTED));
+            assert!(chunk.palette().len() <= CHUNK_VOLUME);
+        }
+        assert_eq!(chunk.id(0), VoxelId::new(CHUNK_VOLUME as u32 * 2 - 1));
+        assert_eq!(chunk.id(1), VoxelId::new(1));
+
+        let mut bytes = Vec::new();
+        let Ok(_) = chunk.encode(&mut bytes);
+        assert_eq!(StoredChunk::decode(&mut bytes.as_slice()).unwrap(), chunk);
+    }
+
+    #[test]
+    fn chunk_formats_test() {
+        let mut chunk = StoredChunk::new();
+        for y in 0..4 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    chunk.set(voxel_index(x, y, z), VoxelState::new(VoxelId::new(1 + (y == 3) as u32), Orientation::UNORIENTED));
+                }
+            }
+        }
+        chunk.block_entities.insert(9, vec![4]);
+        assert_eq!(chunk.format(), ChunkFormat::RUNS);
+
+        let mut sizes = Vec::new();
+        for format in ChunkFormat::ALL {
+            let mut bytes = Vec::new();
+            let Ok(_) = chunk.encode_as(format, &mut bytes);
+            assert_eq!(StoredChunk::decode(&mut bytes.as_slice()).unwrap(), chunk, "{format}");
+            sizes.push(bytes.len());
+        }
+        let mut bytes = Vec::new();
+        let Ok(_) = chunk.encode(&mut bytes);
+        assert_eq!(bytes.len(), *sizes.iter().min().unwrap());
+
+        // Packed chunks are saved as before the format byte existed.
+        let mut packed = Vec::new();
+        let Ok(_) = StoredChunk::new().encode(&mut packed);
+        assert_eq!(u16::from_be_bytes([packed[0], packed[1]]), 1);
+
+        // Runs must cover the chunk exactly, and unknown formats are rejected.
+        let mut bytes = Vec::new();
+        let Ok(_) = chunk.encode_as(ChunkFormat::RUNS, &mut bytes);
+        let mut unknown = bytes.clone();
+        unknown[2] = 0b10;
+        assert!(StoredChunk::decode(&mut unknown.as_slice()).is_err());
+        // Palette of 3 ids, then the first run's length (1024 as a 2 byte varint).
+        let first_run = 3 + 3 * 4;
+        let mut long = bytes.clone();
+        long[first_run + 1] = 0x7F;
+        assert!(StoredChunk::decode(&mut long.as_slice()).is_err());
+    }
+
+    #[test]
+    fn chunk_sampling_test() {
+        use mfhash::sampled::SampleComparison;
+
+        let mut chunk = StoredChunk::new();
+        let coverage = Coverage::from_fraction(1, 64);
+        let hash = chunk.sampled_hash(ChunkPos::ORIGIN, 1, coverage);
+        assert_eq!(hash.info.sampled, 64);
+        assert_eq!(hash, StoredChunk::new().sampled_hash(ChunkPos::ORIGIN, 1, coverage));
+        let other = chunk.sampled_hash(ChunkPos::new(1, 0, 0), 1, coverage);
+        assert_eq!(hash.compare(&other), SampleComparison::Incomparable);
+
+        // A change to a sampled voxel is caught, and full coverage catches any change.
+        let index = hash.info.indices().nth(10).unwrap() as usize;
+        let full = chunk.sampled_hash(ChunkPos::ORIGIN, 1, Coverage::FULL);
+        chunk.set(index, VoxelState::new(VoxelId::new(3), Orientation::UNORIENTED));
+        assert_eq!(hash.compare(&chunk.sampled_hash(ChunkPos::ORIGIN, 1, coverage)), SampleComparison::Mismatch);
+        chunk.set(index, VoxelState::AIR);
+        chunk.set(index + 1, VoxelState::new(VoxelId::new(3), Orientation::UNORIENTED));
+        assert_eq!(full.compare(&chunk.sampled_hash(ChunkPos::ORIGIN, 1, Coverage::FULL)), SampleComparison::Mismatch);
+    }
+}
@@ -0,0 +1,291 @@
+//! Cross-checks border-dependent derived data between neighboring chunks.
+//!
+//! Seam bugs (light that stops at a chunk border, faces rendered against solid neighbors,
+//! heightmaps that disagree between stacked chunks) usually only show up visually.
+//! This pass finds them directly and reports the voxel position where each one occurs.
+
+use mfgeometry::Direction;
+
+use crate::{
+    chunk::{ChunkPos, CHUNK_MASK, CHUNK_SIZE},
+    voxel::id::VoxelId,
+};
+
+/// Read access to chunk data and the data derived from it.
+///
+/// Local coordinates are always within `0..CHUNK_SIZE`.
+pub trait ChunkView {
+    fn is_loaded(&self, chunk: ChunkPos) -> bool;
+
+    fn voxel(&self, chunk: ChunkPos, local: (i32, i32, i32)) -> VoxelId;
+
+    fn is_opaque(&self, id: VoxelId) -> bool;
+
+    /// The light level of a voxel, or `None` if light has not been computed for the chunk.
+    fn light(&self, chunk: ChunkPos, local: (i32, i32, i32)) -> Option<u8>;
+
+    /// The world Y of the highest opaque voxel in the column at `(x, z)` (local), or `None` if
+    /// the heightmap has not been computed. Columns with no opaque voxels report [i32::MIN].
+    fn heightmap(&self, chunk: ChunkPos, column: (i32, i32)) -> Option<i32>;
+
+    /// Whether the mesh of `chunk` contains the `face` of the voxel at `local`,
+    /// or `None` if the chunk has not been meshed.
+    fn face_visible(&self, chunk: ChunkPos, local: (i32, i32, i32), face: Direction) -> Option<bool>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeamIssueKind {
+    /// Light differs by more than 1 between two adjacent transparent voxels.
+    Light {
+        light: u8,
+        neighbor_light: u8,
+    },
+    /// Vertically stacked chunks disagree on the height of a column.
+    Heightmap {
+        height: i32,
+        neighbor_height: i32,
+    },
+    /// A border face was meshed when it should be culled (or the reverse).
+    FaceCulling {
+        expected_visible: bool,
+    },
+}
+
+/// An inconsistency at a chunk seam.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SeamIssue {
+    pub kind: SeamIssueKind,
+    /// The world position of the voxel on the `chunk` side of the seam.
+    pub position: (i32, i32, i32),
+    pub chunk: ChunkPos,
+    /// The direction from `chunk` toward the neighboring chunk.
+    pub face: Direction,
+}
+
+impl std::fmt::Display for SeamIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (x, y, z) = self.position;
+        write!(f, "({x}, {y}, {z}) in chunk {} toward {}: ", self.chunk, self.face)?;
+        match self.kind {
+            SeamIssueKind::Light { light, neighbor_light } => {
+                write!(f, "light {light} next to {neighbor_light}")
+            }
+            SeamIssueKind::Heightmap { height, neighbor_height } => {
+                write!(f, "height {height} but neighbor has {neighbor_height}")
+            }
+            SeamIssueKind::FaceCulling { expected_visible: true } => write!(f, "face should be visible but was culled"),
+            SeamIssueKind::FaceCulling { expected_visible: false } => write!(f, "face should be culled but is visible"),
+        }
+    }
+}
+
+/// The result of [check_borders].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BorderReport {
+    /// The number of chunk seams that were checked.
+    pub seams_checked: usize,
+    pub issues: Vec<SeamIssue>,
+}
+
+impl BorderReport {
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Positive directions only, so that each seam is checked once.
+const SEAM_DIRECTIONS: [Direction; 3] = [Direction::PosX, Direction::PosY, Direction::PosZ];
+
+/// Maps a 2D seam coordinate `(u, v)` to the local coordinates on both sides of the seam facing `face`.
+#[inline]
+const fn seam_locals(face: Direction, u: i32, v: i32) -> ((i32, i32, i32), (i32, i32, i32)) {
+    match face {
+        Direction::PosX => ((CHUNK_MASK, u, v), (0, u, v)),
+        Direction::PosY => ((u, CHUNK_MASK, v), (u, 0, v)),
+        Direction::PosZ => ((u, v, CHUNK_MASK), (u, v, 0)),
+        _ => panic!("Only positive seam directions are checked."),
+    }
+}
+
+/// Checks every seam between the given chunks and their loaded positive neighbors.
+pub fn check_borders<V: ChunkView, I: IntoIterator<Item = ChunkPos>>(view: &V, chunks: I) -> BorderReport {
+    let mut report = BorderReport::default();
+    for chunk in chunks {
+        if !view.is_loaded(chunk) {
+            continue;
+        }
+        for face in SEAM_DIRECTIONS {
+            let (dx, dy, dz) = face.to_ituple();
+            let neighbor = chunk.offset(dx, dy, dz);
+            if !view.is_loaded(neighbor) {
+                continue;
+            }
+            report.seams_checked += 1;
+            check_seam(view, chunk, neighbor, face, &mut report.issues);
+        }
+    }
+    report
+}
+
+fn check_seam<V: ChunkView>(view: &V, chunk: ChunkPos, neighbor: ChunkPos, face: Direction, issues: &mut Vec<SeamIssue>) {
+    let (min_x, min_y, min_z) = chunk.min_voxel();
+    for v in 0..CHUNK_SIZE {
+        for u in 0..CHUNK_SIZE {
+            let (local, neighbor_local) = seam_locals(face, u, v);
+            let position = (min_x + local.0, min_y + local.1, min_z + local.2);
+            let (dx, dy, dz) = face.to_ituple();
+            let neighbor_position = (position.0 + dx, position.1 + dy, position.2 + dz);
+            let mut report = |kind| issues.push(SeamIssue { kind, position, chunk, face });
+            let id = view.voxel(chunk, local);
+            let neighbor_id = view.voxel(neighbor, neighbor_local);
+            let opaque = view.is_opaque(id);
+            let neighbor_opaque = view.is_opaque(neighbor_id);
+
+            if !opaque && !neighbor_opaque
+                && let (Some(light), Some(neighbor_light)) = (view.light(chunk, local), view.light(neighbor, neighbor_local))
+                && light.abs_diff(neighbor_light) > 1
+            {
+                report(SeamIssueKind::Light { light, neighbor_light });
+            }
+
+            // A face is visible when an opaque voxel borders a transparent one.
+            if let Some(visible) = view.face_visible(chunk, local, face) {
+                let expected_visible = opaque && !neighbor_opaque;
+                if visible != expected_visible {
+                    report(SeamIssueKind::FaceCulling { expected_visible });
+                }
+            }
+            if let Some(visible) = view.face_visible(neighbor, neighbor_local, face.invert()) {
+                let expected_visible = neighbor_opaque && !opaque;
+                if visible != expected_visible {
+                    issues.push(SeamIssue {
+                        kind: SeamIssueKind::FaceCulling { expected_visible },
+                        position: neighbor_position,
+                        chunk: neighbor,
+                        face: face.invert(),
+                    });
+                }
+            }
+        }
+    }
+    // Stacked chunks describe the same columns, so their heightmaps must agree.
+    if face == Direction::PosY {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if let (Some(height), Some(neighbor_height)) = (view.heightmap(chunk, (x, z)), view.heightmap(neighbor, (x, z)))
+                    && height != neighbor_height
+                {
+                    issues.push(SeamIssue {
+                        kind: SeamIssueKind::Heightmap { height, neighbor_height },
+                        position: (min_x + x, min_y + CHUNK_MASK, min_z + z),
+                        chunk,
+                        face,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::chunk::{voxel_index, BlockPos};
+
+    const STONE: VoxelId = VoxelId::new(1);
+
+    #[derive(Default)]
+    struct TestChunk {
+        voxels: Vec<VoxelId>,
+        light: Vec<u8>,
+        heightmap: Vec<i32>,
+    }
+
+    #[derive(Default)]
+    struct TestWorld {
+        chunks: HashMap<ChunkPos, TestChunk>,
+    }
+
+    impl TestWorld {
+        /// A flat world where everything below `y = 0` is stone.
+        fn flat(chunks: &[ChunkPos]) -> Self {
+            let mut world = Self::default();
+            for &chunk in chunks {
+                let mut data = TestChunk {
+                    voxels: vec![VoxelId::AIR; 4096],
+                    light: vec![15; 4096],
+                    heightmap: vec![-1; 256],
+                };
+                if chunk.y < 0 {
+                    data.voxels.fill(STONE);
+                    data.light.fill(0);
+                }
+                world.chunks.insert(chunk, data);
+            }
+            world
+        }
+    }
+
+    impl ChunkView for TestWorld {
+        fn is_loaded(&self, chunk: ChunkPos) -> bool {
+            self.chunks.contains_key(&chunk)
+        }
+
+        fn voxel(&self, chunk: ChunkPos, (x, y, z): (i32, i32, i32)) -> VoxelId {
+            self.chunks[&chunk].voxels[voxel_index(x, y, z)]
+        }
+
+        fn is_opaque(&self, id: VoxelId) -> bool {
+            id != VoxelId::AIR
+        }
+
+        fn light(&self, chunk: ChunkPos, (x, y, z): (i32, i32, i32)) -> Option<u8> {
+            Some(self.chunks[&chunk].light[voxel_index(x, y, z)])
+        }
+
+        fn heightmap(&self, chunk: ChunkPos, (x, z): (i32, i32)) -> Option<i32> {
+            Some(self.chunks[&chunk].heightmap[(z * CHUNK_SIZE + x) as usize])
+        }
+
+        fn face_visible(&self, chunk: ChunkPos, local: (i32, i32, i32), face: Direction) -> Option<bool> {
+            // "Mesher" that culls against loaded neighbors correctly.
+            let (dx, dy, dz) = face.to_ituple();
+            let (min_x, min_y, min_z) = chunk.min_voxel();
+            let (wx, wy, wz) = (min_x + local.0 + dx, min_y + local.1 + dy, min_z + local.2 + dz);
+            let (neighbor, neighbor_local) = BlockPos::from((wx, wy, wz)).split().unwrap();
+            let neighbor_opaque = self.is_opaque(self.voxel(neighbor, neighbor_local.into()));
+            Some(self.is_opaque(self.voxel(chunk, local)) && !neighbor_opaque)
+        }
+    }
+
+    #[test]
+    fn consistent_test() {
+        let chunks = [ChunkPos::new(0, -1, 0), ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)];
+        let world = TestWorld::flat(&chunks);
+        let report = check_borders(&world, chunks);
+        assert_eq!(report.seams_checked, 2);
+        assert!(report.is_consistent(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn seam_bug_test() {
+        let chunks = [ChunkPos::new(0, -1, 0), ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)];
+        let mut world = TestWorld::flat(&chunks);
+        // Light didn't propagate across the seam.
+        world.chunks.get_mut(&ChunkPos::new(1, 0, 0)).unwrap().light[voxel_index(0, 4, 4)] = 3;
+        // The upper chunk has a stale heightmap.
+        world.chunks.get_mut(&ChunkPos::new(0, 0, 0)).unwrap().heightmap[0] = 7;
+        let report = check_borders(&world, chunks);
+        assert_eq!(report.issues.len(), 2, "{:?}", report.issues);
+        assert!(report.issues.contains(&SeamIssue {
+            kind: SeamIssueKind::Light { light: 15, neighbor_light: 3 },
+            position: (15, 4, 4),
+            chunk: ChunkPos::new(0, 0, 0),
+            face: Direction::PosX,
+        }));
+        assert!(report.issues.iter().any(|issue| matches!(issue.kind, SeamIssueKind::Heightmap { height: -1, neighbor_height: 7 })));
+    }
+}
@@ -0,0 +1,3 @@
+//! Debug validation passes. These are slow and meant for debug builds and tests.
+
+pub mod border;
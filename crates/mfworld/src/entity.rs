@@ -0,0 +1,337 @@
+//! Entities stored per chunk.
+//!
+//! Each loaded chunk keeps a list of the entities whose position is inside of it. Moving an entity
+//! with [EntityWorld::set_position] migrates it to the chunk it moved into, so the lists always
+//! agree with positions, and spatial queries ([EntityWorld::entities_in_box]) only look at the
+//! chunks the query overlaps. Entities are saved and loaded with their chunk ([ChunkEntities]).
+
+use std::collections::{BTreeMap, HashMap};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::chunk::ChunkPos;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EntityError {
+    #[error("Chunk {0} is not loaded.")]
+    ChunkNotLoaded(ChunkPos),
+    #[error("Unknown entity: {0:?}")]
+    UnknownEntity(EntityId),
+    #[error("Entity {0:?} already exists.")]
+    DuplicateEntity(EntityId),
+}
+
+/// The chunk containing a position in world space.
+#[inline]
+pub fn chunk_at((x, y, z): (f64, f64, f64)) -> ChunkPos {
+    ChunkPos::containing(x.floor() as i32, y.floor() as i32, z.floor() as i32)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity<T> {
+    pub id: EntityId,
+    position: (f64, f64, f64),
+    pub data: T,
+}
+
+impl<T> Entity<T> {
+    #[inline]
+    pub const fn position(&self) -> (f64, f64, f64) {
+        self.position
+    }
+}
+
+/// The entities in one chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkEntities<T> {
+    entities: Vec<Entity<T>>,
+}
+
+impl<T> Default for ChunkEntities<T> {
+    #[inline]
+    fn default() -> Self {
+        Self { entities: Vec::new() }
+    }
+}
+
+impl<T> ChunkEntities<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Entity<T>> {
+        self.entities.iter()
+    }
+
+    #[inline]
+    fn index_of(&self, id: EntityId) -> Option<usize> {
+        self.entities.iter().position(|entity| entity.id == id)
+    }
+}
+
+/// The entities of every loaded chunk.
+#[derive(Debug, Clone)]
+pub struct EntityWorld<T> {
+    chunks: BTreeMap<ChunkPos, ChunkEntities<T>>,
+    /// The chunk each entity is in.
+    locations: HashMap<EntityId, ChunkPos>,
+    next_id: u64,
+}
+
+impl<T> Default for EntityWorld<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            chunks: BTreeMap::new(),
+            locations: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> EntityWorld<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The id the next spawned entity gets. Saved with the world so that ids are never reused.
+    #[inline]
+    pub const fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    #[inline]
+    pub fn set_next_id(&mut self, next_id: u64) {
+        self.next_id = self.next_id.max(next_id);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    #[inline]
+    pub fn is_loaded(&self, chunk: ChunkPos) -> bool {
+        self.chunks.contains_key(&chunk)
+    }
+
+    /// Loads the entities of `chunk`, replacing them if the chunk is already loaded. Every entity
+    /// must be inside of the chunk.
+    pub fn load_chunk(&mut self, chunk: ChunkPos, entities: ChunkEntities<T>) -> Result<(), EntityError> {
+        let duplicate = entities.iter().find(|entity| {
+            self.location(entity.id).is_some_and(|location| location != chunk)
+        });
+        if let Some(entity) = duplicate {
+            return Err(EntityError::DuplicateEntity(entity.id));
+        }
+        self.unload_chunk(chunk);
+        for entity in entities.iter() {
+            debug_assert_eq!(chunk_at(entity.position), chunk, "Entity {:?} is outside of its chunk.", entity.id);
+            self.locations.insert(entity.id, chunk);
+            self.next_id = self.next_id.max(entity.id.0 + 1);
+        }
+        self.chunks.insert(chunk, entities);
+        Ok(())
+    }
+
+    /// Unloads `chunk`, returning its entities to be saved with it.
+    pub fn unload_chunk(&mut self, chunk: ChunkPos) -> Option<ChunkEntities<T>> {
+        let entities = self.chunks.remove(&chunk)?;
+        for entity in entities.iter() {
+            self.locations.remove(&entity.id);
+        }
+        Some(entities)
+    }
+
+    #[inline]
+    pub fn chunk(&self, chunk: ChunkPos) -> Option<&ChunkEntities<T>> {
+        self.chunks.get(&chunk)
+    }
+
+    /// The loaded chunks and their entities, in chunk order.
+    #[inline]
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (ChunkPos, &ChunkEntities<T>)> {
+        self.chunks.iter().map(|(&chunk, entities)| (chunk, entities))
+    }
+
+    /// Spawns an entity at `position`, whose chunk must be loaded.
+    pub fn spawn(&mut self, position: (f64, f64, f64), data: T) -> Result<EntityId, EntityError> {
+        let chunk = chunk_at(position);
+        let entities = self.chunks.get_mut(&chunk).ok_or(EntityError::ChunkNotLoaded(chunk))?;
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        entities.entities.push(Entity { id, position, data });
+        self.locations.insert(id, chunk);
+        Ok(id)
+    }
+
+    /// Removes an entity from the world.
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity<T>> {
+        let chunk = self.locations.remove(&id)?;
+        let entities = self.chunks.get_mut(&chunk).expect("entity locations only refer to loaded chunks");
+        let index = entities.index_of(id).expect("entity is in the chunk it's located in");
+        Some(entities.entities.swap_remove(index))
+    }
+
+    /// The chunk an entity is in.
+    #[inline]
+    pub fn location(&self, id: EntityId) -> Option<ChunkPos> {
+        self.locations.get(&id).copied()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity<T>> {
+        let entities = &self.chunks[&self.location(id)?];
+        entities.index_of(id).map(|index| &entities.entities[index])
+    }
+
+    /// The entity's data. Positions can only be changed with [EntityWorld::set_position].
+    pub fn data_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        let entities = self.chunks.get_mut(&self.location(id)?)?;
+        let index = entities.index_of(id)?;
+        Some(&mut entities.entities[index].data)
+    }
+
+    /// Moves an entity, migrating it to the chunk it moved into. Entities can't move into chunks
+    /// that aren't loaded. Returns the entity's chunk.
+    pub fn set_position(&mut self, id: EntityId, position: (f64, f64, f64)) -> Result<ChunkPos, EntityError> {
+        let from = self.location(id).ok_or(EntityError::UnknownEntity(id))?;
+        let to = chunk_at(position);
+        if !self.chunks.contains_key(&to) {
+            return Err(EntityError::ChunkNotLoaded(to));
+        }
+        let entities = self.chunks.get_mut(&from).expect("entity locations only refer to loaded chunks");
+        let index = entities.index_of(id).expect("entity is in the chunk it's located in");
+        if from == to {
+            entities.entities[index].position = position;
+            return Ok(to);
+        }
+        let mut entity = entities.entities.swap_remove(index);
+        entity.position = position;
+        self.chunks.get_mut(&to).unwrap().entities.push(entity);
+        self.locations.insert(id, to);
+        Ok(to)
+    }
+
+    /// Every loaded entity whose position is within `min..=max`.
+    pub fn entities_in_box(&self, min: (f64, f64, f64), max: (f64, f64, f64)) -> impl Iterator<Item = &Entity<T>> {
+        let (low, high) = (chunk_at(min), chunk_at(max));
+        self.chunks.range(low..=high)
+            .filter(move |(chunk, _)| {
+                (low.y..=high.y).contains(&chunk.y) && (low.z..=high.z).contains(&chunk.z)
+            })
+            .flat_map(|(_, entities)| entities.iter())
+            .filter(move |entity| {
+                let (x, y, z) = entity.position;
+                min.0 <= x && x <= max.0
+                && min.1 <= y && y <= max.1
+                && min.2 <= z && z <= max.2
+            })
+    }
+}
+
+// Layout: entity count (u32), then for each entity: id (u64), x, y, z (f64 bits as u64), data.
+impl<T: Encode> Encode for ChunkEntities<T> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.entities.len() as u32)?;
+        for entity in &self.entities {
+            let (x, y, z) = entity.position;
+            written += encoder.write_u64(entity.id.0)?
+                + encoder.write_u64(x.to_bits())?
+                + encoder.write_u64(y.to_bits())?
+                + encoder.write_u64(z.to_bits())?
+                + entity.data.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+impl<T: Decode> Decode for ChunkEntities<T> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let count = decoder.read_u32()?;
+        let mut entities = Vec::new();
+        for _ in 0..count {
+            let id = EntityId(decoder.read_u64()?);
+            let position = (
+                f64::from_bits(decoder.read_u64()?),
+                f64::from_bits(decoder.read_u64()?),
+                f64::from_bits(decoder.read_u64()?),
+            );
+            if !(position.0.is_finite() && position.1.is_finite() && position.2.is_finite()) {
+                return Err(DecodeError::InvalidData("entity position is not finite"));
+            }
+            entities.push(Entity { id, position, data: T::decode(decoder)? });
+        }
+        Ok(Self { entities })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migration_test() {
+        let mut world = EntityWorld::<u32>::new();
+        for x in -1..=1 {
+            world.load_chunk(ChunkPos::new(x, 0, 0), ChunkEntities::new()).unwrap();
+        }
+        let cow = world.spawn((1.5, 2.0, 3.0), 7).unwrap();
+        let pig = world.spawn((-4.0, 2.0, 3.0), 8).unwrap();
+        assert_eq!(world.spawn((0.0, 20.0, 0.0), 9), Err(EntityError::ChunkNotLoaded(ChunkPos::new(0, 1, 0))));
+
+        assert_eq!(world.set_position(cow, (17.0, 2.0, 3.0)), Ok(ChunkPos::new(1, 0, 0)));
+        assert_eq!(world.chunk(ChunkPos::ORIGIN).unwrap().len(), 0);
+        assert_eq!(world.set_position(cow, (33.0, 2.0, 3.0)), Err(EntityError::ChunkNotLoaded(ChunkPos::new(2, 0, 0))));
+        assert_eq!(world.get(cow).unwrap().position(), (17.0, 2.0, 3.0));
+        *world.data_mut(cow).unwrap() += 1;
+
+        let found = |world: &EntityWorld<u32>, min, max| {
+            let mut ids = world.entities_in_box(min, max).map(|entity| entity.id).collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        assert_eq!(found(&world, (-16.0, 0.0, 0.0), (20.0, 4.0, 4.0)), [cow, pig]);
+        assert_eq!(found(&world, (0.0, 0.0, 0.0), (20.0, 4.0, 4.0)), [cow]);
+        assert!(found(&world, (0.0, 3.0, 0.0), (20.0, 4.0, 4.0)).is_empty());
+
+        // Unloading saves the entities with the chunk, and loading brings them back.
+        let saved = world.unload_chunk(ChunkPos::new(1, 0, 0)).unwrap();
+        assert_eq!(world.get(cow), None);
+        let mut bytes = Vec::new();
+        saved.encode(&mut bytes).unwrap();
+        let loaded = ChunkEntities::<u32>::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, saved);
+        world.load_chunk(ChunkPos::new(1, 0, 0), loaded.clone()).unwrap();
+        assert_eq!(world.get(cow).unwrap().data, 8);
+        assert_eq!(world.load_chunk(ChunkPos::new(1, 0, 0), loaded.clone()), Ok(()));
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.load_chunk(ChunkPos::new(0, 0, 0), loaded), Err(EntityError::DuplicateEntity(cow)));
+
+        assert_eq!(world.remove(pig).unwrap().data, 8);
+        assert_eq!(world.set_position(pig, (0.0, 0.0, 0.0)), Err(EntityError::UnknownEntity(pig)));
+    }
+}
@@ -0,0 +1,373 @@
+//! Coalescing the saves of unloading chunks.
+//!
+//! Serializing a chunk the moment it unloads stalls the tick, and a chunk at the edge of a player's
+//! view can unload and reload many times in a few seconds. A [ChunkFlusher] queues the data of
+//! unloading chunks (voxels, heightmap and light, whatever the caller saves with a chunk) and writes
+//! it in batches on its own IO thread:
+//!
+//! - Scheduling a chunk that's already queued replaces the queued data, so it's written once.
+//! - [ChunkFlusher::reclaim] takes a queued chunk back when it loads again before it was written,
+//!   so it isn't read back from disk and doesn't need writing at all. A chunk that's being written
+//!   when it loads again is waited for, so it's never read back mid-write.
+//! - The queue is bounded: scheduling waits while it's full, so unloading can't outrun the disk.
+//! - [ChunkFlusher::flush] waits until everything queued is written, and
+//!   [ChunkFlusher::shutdown] (or dropping the flusher) does the same before stopping the thread,
+//!   so nothing queued is lost on exit.
+//!
+//! Batches are written in Morton order. If `write` panics, the IO thread stops and every later call
+//! that would wait on it panics instead.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::JoinHandle,
+};
+
+use crate::chunk::ChunkPos;
+
+/// A chunk that failed to write, with the data that was to be written.
+#[derive(Debug)]
+pub struct FlushFailure<T, E> {
+    pub chunk: ChunkPos,
+    pub data: T,
+    pub error: E,
+}
+
+/// What a [ChunkFlusher] did.
+#[derive(Debug)]
+pub struct FlushReport<T, E> {
+    /// Chunks written.
+    pub written: u64,
+    /// Schedules that replaced data that was already queued.
+    pub coalesced: u64,
+    /// Queued chunks taken back before they were written.
+    pub reclaimed: u64,
+    pub failed: Vec<FlushFailure<T, E>>,
+}
+
+impl<T, E> Default for FlushReport<T, E> {
+    #[inline]
+    fn default() -> Self {
+        Self { written: 0, coalesced: 0, reclaimed: 0, failed: Vec::new() }
+    }
+}
+
+#[derive(Debug)]
+struct Queue<T, E> {
+    pending: BTreeMap<u128, (ChunkPos, T)>,
+    /// The chunks the IO thread is writing, by Morton code.
+    writing: Vec<u128>,
+    shutdown: bool,
+    /// Set when `write` panicked and the IO thread stopped.
+    dead: bool,
+    report: FlushReport<T, E>,
+}
+
+#[derive(Debug)]
+struct Shared<T, E> {
+    queue: Mutex<Queue<T, E>>,
+    /// Signalled when chunks are queued, or on shutdown.
+    queued: Condvar,
+    /// Signalled when chunks are written.
+    written: Condvar,
+}
+
+impl<T, E> Shared<T, E> {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Queue<T, E>> {
+        self.queue.lock().expect("The chunk flush queue was poisoned.")
+    }
+
+    /// Waits for the IO thread to write something.
+    ///
+    /// # Panics
+    /// Panics if the IO thread stopped because `write` panicked.
+    #[inline]
+    fn wait_written<'a>(&self, queue: MutexGuard<'a, Queue<T, E>>) -> MutexGuard<'a, Queue<T, E>> {
+        alive(self.written.wait(queue).expect("The chunk flush queue was poisoned."))
+    }
+}
+
+/// Passes `queue` through, or panics if the IO thread stopped. The lock is released first so the
+/// panic doesn't poison it for the flusher's drop.
+#[inline]
+fn alive<T, E>(queue: MutexGuard<'_, Queue<T, E>>) -> MutexGuard<'_, Queue<T, E>> {
+    if queue.dead {
+        drop(queue);
+        panic!("The chunk flush thread panicked.");
+    }
+    queue
+}
+
+/// Marks the queue dead and wakes everything waiting on it when `write` panics, so nothing waits
+/// for chunks that will never be written.
+struct MarkDeadOnPanic<T, E>(Arc<Shared<T, E>>);
+
+impl<T, E> Drop for MarkDeadOnPanic<T, E> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let mut queue = self.0.queue.lock().unwrap_or_else(PoisonError::into_inner);
+            queue.writing.clear();
+            queue.dead = true;
+            self.0.written.notify_all();
+        }
+    }
+}
+
+/// Writes the data of unloaded chunks on an IO thread.
+#[derive(Debug)]
+pub struct ChunkFlusher<T: Send + 'static, E: Send + 'static> {
+    shared: Arc<Shared<T, E>>,
+    capacity: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static, E: Send + 'static> ChunkFlusher<T, E> {
+    /// Starts the IO thread. At most `capacity` chunks are queued, and at most `batch_size` are
+    /// taken off the queue at once and passed to `write` one by one.
+    pub fn spawn<F>(capacity: usize, batch_size: usize, mut write: F) -> Self
+    where F: FnMut(ChunkPos, &T) -> Result<(), E> + Send + 'static {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                pending: BTreeMap::new(),
+                writing: Vec::new(),
+                shutdown: false,
+                dead: false,
+                report: FlushReport::default(),
+            }),
+            queued: Condvar::new(),
+            written: Condvar::new(),
+        });
+        let worker = Arc::clone(&shared);
+        let batch_size = batch_size.max(1);
+        let thread = std::thread::Builder::new()
+            .name("chunk flush".to_owned())
+            .spawn(move || {
+                let _dead = MarkDeadOnPanic(Arc::clone(&worker));
+                loop {
+                    let batch = {
+                        let mut queue = worker.lock();
+                        while queue.pending.is_empty() && !queue.shutdown {
+                            queue = worker.queued.wait(queue).expect("The chunk flush queue was poisoned.");
+                        }
+                        if queue.pending.is_empty() {
+                            return;
+                        }
+                        let batch = std::iter::from_fn(|| queue.pending.pop_first().map(|(_, entry)| entry))
+                            .take(batch_size)
+                            .collect::<Vec<_>>();
+                        queue.writing = batch.iter().map(|(chunk, _)| chunk.morton()).collect();
+                        batch
+                    };
+                    let results = batch.into_iter().map(|(chunk, data)| {
+                        let result = write(chunk, &data);
+                        (chunk, data, result)
+                    }).collect::<Vec<_>>();
+                    let mut queue = worker.lock();
+                    queue.writing.clear();
+                    for (chunk, data, result) in results {
+                        match result {
+                            Ok(()) => queue.report.written += 1,
+                            Err(error) => queue.report.failed.push(FlushFailure { chunk, data, error }),
+                        }
+                    }
+                    worker.written.notify_all();
+                }
+            })
+            .expect("Failed to spawn the chunk flush thread.");
+        Self { shared, capacity: capacity.max(1), thread: Some(thread) }
+    }
+
+    /// Queues `data` to be written for `chunk`, replacing any data already queued for it. Waits
+    /// while the queue is full.
+    ///
+    /// # Panics
+    /// Panics if the IO thread stopped because `write` panicked.
+    pub fn schedule(&self, chunk: ChunkPos, data: T) {
+        let mut queue = alive(self.shared.lock());
+        if let Some(queued) = queue.pending.get_mut(&chunk.morton()) {
+            queued.1 = data;
+            queue.report.coalesced += 1;
+            return;
+        }
+        while queue.pending.len() >= self.capacity {
+            queue = self.shared.wait_written(queue);
+        }
+        queue.pending.insert(chunk.morton(), (chunk, data));
+        self.shared.queued.notify_one();
+    }
+
+    /// Takes back the data queued for `chunk`, if it hasn't been written yet. If it's being
+    /// written, waits until it is (and returns `None`), so the caller can read it back from disk.
+    ///
+    /// # Panics
+    /// Panics if the IO thread stopped because `write` panicked while writing `chunk`.
+    pub fn reclaim(&self, chunk: ChunkPos) -> Option<T> {
+        let key = chunk.morton();
+        let mut queue = self.shared.lock();
+        // Newer data queued behind the write is returned straight away.
+        while !queue.pending.contains_key(&key) && queue.writing.contains(&key) {
+            queue = self.shared.wait_written(queue);
+        }
+        let (_, data) = queue.pending.remove(&key)?;
+        queue.report.reclaimed += 1;
+        self.shared.written.notify_all();
+        Some(data)
+    }
+
+    /// The number of chunks waiting to be written.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.shared.lock().pending.len()
+    }
+
+    /// Waits until every chunk queued so far is written.
+    ///
+    /// # Panics
+    /// Panics if the IO thread stopped because `write` panicked.
+    pub fn flush(&self) {
+        let mut queue = alive(self.shared.lock());
+        while !queue.pending.is_empty() || !queue.writing.is_empty() {
+            queue = self.shared.wait_written(queue);
+        }
+    }
+
+    /// Takes the chunks that failed to write so far.
+    pub fn take_failed(&self) -> Vec<FlushFailure<T, E>> {
+        std::mem::take(&mut self.shared.lock().report.failed)
+    }
+
+    /// Writes everything queued, stops the IO thread, and reports what was done.
+    ///
+    /// # Panics
+    /// Resumes the panic of `write` if it panicked.
+    pub fn shutdown(mut self) -> FlushReport<T, E> {
+        if let Err(panic) = self.stop() {
+            std::panic::resume_unwind(panic);
+        }
+        std::mem::take(&mut self.shared.lock().report)
+    }
+
+    /// Stops the IO thread once everything queued is written, returning its panic if `write`
+    /// panicked.
+    fn stop(&mut self) -> std::thread::Result<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        self.shared.lock().shutdown = true;
+        self.shared.queued.notify_all();
+        thread.join()
+    }
+}
+
+impl<T: Send + 'static, E: Send + 'static> Drop for ChunkFlusher<T, E> {
+    fn drop(&mut self) {
+        // Dropping can't report a panic of `write` without aborting an unwind already under way.
+        // Use shutdown to see it.
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn flush_coalesce_test() {
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (written_tx, written_rx) = mpsc::channel();
+        let flusher = ChunkFlusher::spawn(4, 8, move |chunk: ChunkPos, data: &u32| {
+            gate_rx.recv().unwrap();
+            written_tx.send((chunk, *data)).unwrap();
+            if *data == 13 { Err("disk full") } else { Ok(()) }
+        });
+        // Hold the IO thread on the first chunk while the rest queue up behind it.
+        let (a, b, c) = (ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0), ChunkPos::new(0, 1, 0));
+        flusher.schedule(a, 1);
+        while flusher.pending() != 0 {
+            std::thread::yield_now();
+        }
+        flusher.schedule(b, 2);
+        flusher.schedule(b, 3);
+        flusher.schedule(c, 13);
+        flusher.schedule(a, 4);
+        assert_eq!(flusher.reclaim(a), Some(4));
+        assert_eq!(flusher.pending(), 2);
+
+        for _ in 0..3 {
+            gate_tx.send(()).unwrap();
+        }
+        flusher.flush();
+        assert_eq!(written_rx.try_iter().collect::<Vec<_>>(), [(a, 1), (b, 3), (c, 13)]);
+        let report = flusher.shutdown();
+        assert_eq!((report.written, report.coalesced, report.reclaimed), (2, 1, 1));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!((report.failed[0].chunk, report.failed[0].data, report.failed[0].error), (c, 13, "disk full"));
+    }
+
+    #[test]
+    fn flush_on_drop_test() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let flusher = {
+            let written = Arc::clone(&written);
+            ChunkFlusher::spawn(2, 1, move |chunk, _: &()| {
+                written.lock().unwrap().push(chunk);
+                Ok::<_, ()>(())
+            })
+        };
+        let chunks = (0..10).map(|x| ChunkPos::new(x, 0, 0)).collect::<Vec<_>>();
+        for &chunk in &chunks {
+            flusher.schedule(chunk, ());
+        }
+        drop(flusher);
+        assert_eq!(written.lock().unwrap().len(), chunks.len());
+    }
+
+    #[test]
+    fn reclaim_while_writing_test() {
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (written_tx, written_rx) = mpsc::channel();
+        let flusher = ChunkFlusher::spawn(4, 1, move |chunk: ChunkPos, data: &u32| {
+            gate_rx.recv().unwrap();
+            written_tx.send((chunk, *data)).unwrap();
+            Ok::<_, ()>(())
+        });
+        let chunk = ChunkPos::new(3, 0, 0);
+        flusher.schedule(chunk, 1);
+        while flusher.pending() != 0 {
+            std::thread::yield_now();
+        }
+        // The chunk is being written, so reclaiming it waits for the write instead of letting the
+        // caller read it back from disk halfway through.
+        std::thread::scope(|scope| {
+            let reclaim = scope.spawn(|| flusher.reclaim(chunk));
+            gate_tx.send(()).unwrap();
+            assert_eq!(reclaim.join().unwrap(), None);
+        });
+        assert_eq!(written_rx.try_recv(), Ok((chunk, 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "The chunk flush thread panicked.")]
+    fn flush_panicking_writer_test() {
+        let flusher = ChunkFlusher::spawn(1, 1, |chunk: ChunkPos, _: &()| {
+            assert_ne!(chunk.x, 2, "disk on fire");
+            Ok::<_, ()>(())
+        });
+        // The queue holds one chunk, so this would wait forever for the dead thread to make room.
+        for x in 0..8 {
+            flusher.schedule(ChunkPos::new(x, 0, 0), ());
+        }
+        flusher.flush();
+    }
+
+    #[test]
+    #[should_panic(expected = "disk on fire")]
+    fn shutdown_panicking_writer_test() {
+        let flusher = ChunkFlusher::spawn(4, 1, |_: ChunkPos, _: &()| -> Result<(), ()> { panic!("disk on fire") });
+        flusher.schedule(ChunkPos::new(0, 0, 0), ());
+        flusher.shutdown();
+    }
+}
@@ -1,3 +1,5 @@
+use mfgeometry::Direction;
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Face {
@@ -16,6 +18,28 @@ impl Face {
     pub const LEFT: Face = Face::NegX;
     pub const BOTTOM: Face = Face::NegY;
     pub const FRONT: Face = Face::NegZ;
-    
-    
+
+    #[inline]
+    pub const fn direction(self) -> Direction {
+        match self {
+            Face::PosX => Direction::PosX,
+            Face::PosY => Direction::PosY,
+            Face::PosZ => Direction::PosZ,
+            Face::NegX => Direction::NegX,
+            Face::NegY => Direction::NegY,
+            Face::NegZ => Direction::NegZ,
+        }
+    }
+
+    #[inline]
+    pub const fn from_direction(direction: Direction) -> Self {
+        match direction {
+            Direction::PosX => Face::PosX,
+            Direction::PosY => Face::PosY,
+            Direction::PosZ => Face::PosZ,
+            Direction::NegX => Face::NegX,
+            Direction::NegY => Face::NegY,
+            Direction::NegZ => Face::NegZ,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,297 @@
+//! Per-player undo/redo of voxel edits (creative mode).
+//!
+//! Each player gets an [EditHistory] that remembers their most recent edits. Undoing and redoing
+//! goes through a [VoxelWorld] like any other edit and reports the change to the
+//! [InvalidationTracker], so meshes and light are rebuilt as usual. An edit is skipped if the voxel
+//! was changed by someone else since, rather than overwriting their work.
+
+use std::collections::VecDeque;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::Orientation;
+use mfhash::deterministic::DeterministicHash;
+
+use crate::{chunk::BlockPos, invalidation::InvalidationTracker, voxel::id::VoxelId};
+
+/// A voxel's palette entry and orientation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, DeterministicHash)]
+pub struct VoxelState {
+    pub id: VoxelId,
+    pub orientation: Orientation,
+}
+
+impl VoxelState {
+    pub const AIR: Self = Self::new(VoxelId::AIR, Orientation::UNORIENTED);
+
+    #[inline]
+    pub const fn new(id: VoxelId, orientation: Orientation) -> Self {
+        Self { id, orientation }
+    }
+}
+
+/// Write access to voxels for [EditHistory].
+pub trait VoxelWorld {
+    /// Gets the voxel at `position`, or `None` if its chunk isn't loaded.
+    fn voxel(&self, position: BlockPos) -> Option<VoxelState>;
+    fn set_voxel(&mut self, position: BlockPos, state: VoxelState);
+
+    /// Called after the voxel at `position` was turned in place from `before` to `after` (see
+    /// [Wrench](crate::wrench::Wrench)). Worlds that store per-face data in world terms, like
+    /// [VoxelEgress](crate::voxel::voxel::VoxelEgress), move it along here.
+    #[allow(unused_variables)]
+    fn voxel_rotated(&mut self, position: BlockPos, before: Orientation, after: Orientation) {}
+}
+
+/// A single voxel placed or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoxelEdit {
+    pub position: BlockPos,
+    pub before: VoxelState,
+    pub after: VoxelState,
+}
+
+impl VoxelEdit {
+    #[inline]
+    pub const fn inverse(self) -> Self {
+        Self {
+            position: self.position,
+            before: self.after,
+            after: self.before,
+        }
+    }
+}
+
+/// A bounded history of one player's edits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditHistory {
+    capacity: usize,
+    /// Oldest first.
+    undo: VecDeque<VoxelEdit>,
+    /// Most recently undone last.
+    redo: Vec<VoxelEdit>,
+}
+
+impl Default for EditHistory {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl EditHistory {
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    #[inline]
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Records an edit the player made. Forgets the oldest edit if the history is full,
+    /// and forgets everything that could be redone.
+    pub fn record(&mut self, edit: VoxelEdit) {
+        if edit.before == edit.after {
+            return;
+        }
+        self.redo.clear();
+        if self.undo.len() == self.capacity {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+    }
+
+    /// Undoes up to `n` of the most recent edits, returning the edits made to the world.
+    pub fn undo<W: VoxelWorld>(&mut self, n: usize, world: &mut W, tracker: &mut InvalidationTracker) -> Vec<VoxelEdit> {
+        let mut applied = Vec::new();
+        for _ in 0..n {
+            let Some(edit) = self.undo.pop_back() else {
+                break;
+            };
+            if let Some(undone) = apply(edit.inverse(), world, tracker) {
+                applied.push(undone);
+                self.redo.push(edit);
+            }
+        }
+        applied
+    }
+
+    /// Redoes up to `n` of the most recently undone edits, returning the edits made to the world.
+    pub fn redo<W: VoxelWorld>(&mut self, n: usize, world: &mut W, tracker: &mut InvalidationTracker) -> Vec<VoxelEdit> {
+        let mut applied = Vec::new();
+        for _ in 0..n {
+            let Some(edit) = self.redo.pop() else {
+                break;
+            };
+            if let Some(redone) = apply(edit, world, tracker) {
+                applied.push(redone);
+                self.undo.push_back(edit);
+            }
+        }
+        applied
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Applies `edit` if the voxel still holds `edit.before`.
+fn apply<W: VoxelWorld>(edit: VoxelEdit, world: &mut W, tracker: &mut InvalidationTracker) -> Option<VoxelEdit> {
+    if world.voxel(edit.position)? != edit.before {
+        return None;
+    }
+    world.set_voxel(edit.position, edit.after);
+    tracker.voxel_changed(edit.position);
+    Some(edit)
+}
+
+fn encode_state<E: Encoder>(state: VoxelState, encoder: &mut E) -> Result<u64, E::Error> {
+    Ok(encoder.write_u32(state.id.get())? + state.orientation.encode(encoder)?)
+}
+
+fn decode_state<D: Decoder>(decoder: &mut D) -> Result<VoxelState, DecodeError<D::Error>> {
+    let id = VoxelId::new(decoder.read_u32()?);
+    let orientation = Orientation::decode(decoder)?;
+    Ok(VoxelState::new(id, orientation))
+}
+
+// Layout: position (x, y, z i64), before (id u32, orientation u8), after (same).
+impl Encode for VoxelEdit {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            self.position.encode(encoder)?
+            + encode_state(self.before, encoder)?
+            + encode_state(self.after, encoder)?
+        )
+    }
+}
+
+impl Decode for VoxelEdit {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self {
+            position: BlockPos::decode(decoder)?,
+            before: decode_state(decoder)?,
+            after: decode_state(decoder)?,
+        })
+    }
+}
+
+// Layout: capacity (u32), undo count (u32), undo edits (oldest first),
+// redo count (u32), redo edits (most recently undone last).
+impl Encode for EditHistory {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.capacity as u32)?;
+        written += encoder.write_u32(self.undo.len() as u32)?;
+        for edit in &self.undo {
+            written += edit.encode(encoder)?;
+        }
+        written += encoder.write_u32(self.redo.len() as u32)?;
+        for edit in &self.redo {
+            written += edit.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for EditHistory {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut history = Self::new(decoder.read_u32()? as usize);
+        let undo_count = decoder.read_u32()? as usize;
+        if undo_count > history.capacity {
+            return Err(DecodeError::InvalidData("edit history is larger than its capacity"));
+        }
+        for _ in 0..undo_count {
+            history.undo.push_back(VoxelEdit::decode(decoder)?);
+        }
+        for _ in 0..decoder.read_u32()? {
+            history.redo.push(VoxelEdit::decode(decoder)?);
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{chunk::ChunkPos, invalidation::Artifact};
+
+    #[derive(Default)]
+    struct TestWorld(HashMap<BlockPos, VoxelState>);
+
+    impl VoxelWorld for TestWorld {
+        fn voxel(&self, position: BlockPos) -> Option<VoxelState> {
+            Some(self.0.get(&position).copied().unwrap_or(VoxelState::AIR))
+        }
+
+        fn set_voxel(&mut self, position: BlockPos, state: VoxelState) {
+            self.0.insert(position, state);
+        }
+    }
+
+    fn place(world: &mut TestWorld, history: &mut EditHistory, position: BlockPos, id: u32) {
+        let before = world.voxel(position).unwrap();
+        let after = VoxelState::new(VoxelId::new(id), Orientation::ROTATE_Y);
+        world.set_voxel(position, after);
+        history.record(VoxelEdit { position, before, after });
+    }
+
+    #[test]
+    fn undo_redo_test() {
+        let mut world = TestWorld::default();
+        let mut tracker = InvalidationTracker::default();
+        let mut history = EditHistory::new(2);
+        place(&mut world, &mut history, BlockPos::new(1, 1, 1), 1);
+        place(&mut world, &mut history, BlockPos::new(2, 1, 1), 2);
+        place(&mut world, &mut history, BlockPos::new(2, 1, 1), 3);
+        // The first edit fell out of the history.
+        assert_eq!(history.undo_len(), 2);
+
+        tracker.end_tick();
+        let undone = history.undo(5, &mut world, &mut tracker);
+        assert_eq!(undone.len(), 2);
+        assert_eq!(world.voxel(BlockPos::new(2, 1, 1)), Some(VoxelState::AIR));
+        assert_eq!(world.voxel(BlockPos::new(1, 1, 1)).unwrap().id, VoxelId::new(1));
+        assert!(tracker.is_stale(ChunkPos::ORIGIN, Artifact::Light));
+
+        let mut bytes = Vec::new();
+        history.encode(&mut bytes).unwrap();
+        assert_eq!(EditHistory::decode(&mut bytes.as_slice()).unwrap(), history);
+
+        assert_eq!(history.redo(1, &mut world, &mut tracker).len(), 1);
+        assert_eq!(world.voxel(BlockPos::new(2, 1, 1)).unwrap().id, VoxelId::new(2));
+        // Someone else replaced the voxel, so redoing on top of it is skipped.
+        world.set_voxel(BlockPos::new(2, 1, 1), VoxelState::AIR);
+        assert!(history.redo(1, &mut world, &mut tracker).is_empty());
+        assert_eq!(history.redo_len(), 0);
+
+        // A new edit clears the redo stack.
+        history.undo(1, &mut world, &mut tracker);
+        place(&mut world, &mut history, BlockPos::new(0, 0, 0), 4);
+        assert_eq!(history.redo_len(), 0);
+    }
+}
@@ -0,0 +1,447 @@
+//! Tracks which derived chunk artifacts (meshes, light, heightmaps, LODs) are stale.
+//!
+//! Invalidations are coalesced until [InvalidationTracker::end_tick] is called, at which
+//! point a single [RebuildJob] is produced for each affected chunk, in a deterministic order.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use mfhash::{deterministic::DeterministicHash, CacheKey, HashSeed};
+
+use crate::{
+    chunk::{BlockPos, ChunkPos, CHUNK_MASK},
+    light::LIGHT_FORMAT_VERSION,
+};
+
+/// Data derived from the voxels of a chunk that must be rebuilt when the voxels change.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Artifact {
+    Mesh = 0,
+    Light = 1,
+    Heightmap = 2,
+    Lod = 3,
+}
+
+impl Artifact {
+    pub const ALL: [Artifact; 4] = [
+        Artifact::Mesh,
+        Artifact::Light,
+        Artifact::Heightmap,
+        Artifact::Lod,
+    ];
+
+    #[inline(always)]
+    pub const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Artifact::Mesh => "mesh",
+            Artifact::Light => "light",
+            Artifact::Heightmap => "heightmap",
+            Artifact::Lod => "lod",
+        }
+    }
+
+    /// The format version of the artifact's cached data. Bump it when the artifact's builder changes.
+    #[inline]
+    pub const fn format_version(self) -> u32 {
+        match self {
+            Artifact::Mesh => 1,
+            Artifact::Light => LIGHT_FORMAT_VERSION,
+            Artifact::Heightmap => 1,
+            Artifact::Lod => 1,
+        }
+    }
+
+    /// The key of the cached artifact of `chunk`, derived from `content`
+    /// (usually the hash of the voxels the artifact was built from).
+    pub fn cache_key<T: DeterministicHash>(self, seed: HashSeed, chunk: ChunkPos, content: T) -> CacheKey {
+        CacheKey::builder(seed)
+            .segment("world")
+            .segment(self.name())
+            .content((chunk, content))
+            .version(self.format_version())
+            .build()
+    }
+}
+
+/// Why an [Artifact] was invalidated.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reason {
+    /// A voxel inside of the chunk changed.
+    VoxelChanged = 0,
+    /// A voxel in a neighboring chunk that borders this chunk changed.
+    NeighborChanged = 1,
+    /// Light propagated into the chunk from a change nearby.
+    LightPropagation = 2,
+    /// The chunk was loaded or generated.
+    ChunkLoaded = 3,
+    /// The artifact was invalidated explicitly.
+    Manual = 4,
+}
+
+impl Reason {
+    pub const ALL: [Reason; 5] = [
+        Reason::VoxelChanged,
+        Reason::NeighborChanged,
+        Reason::LightPropagation,
+        Reason::ChunkLoaded,
+        Reason::Manual,
+    ];
+
+    #[inline(always)]
+    pub const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+macro_rules! bit_set {
+    ($name:ident($item:ident)) => {
+        #[repr(transparent)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name(u8);
+
+        impl $name {
+            pub const EMPTY: Self = Self(0);
+
+            #[inline]
+            pub const fn contains(self, item: $item) -> bool {
+                self.0 & item.bit() != 0
+            }
+
+            #[inline]
+            pub const fn insert(&mut self, item: $item) {
+                self.0 |= item.bit();
+            }
+
+            #[inline]
+            pub const fn remove(&mut self, item: $item) {
+                self.0 &= !item.bit();
+            }
+
+            #[inline]
+            pub const fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+
+            #[inline]
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            #[inline]
+            pub const fn bits(self) -> u8 {
+                self.0
+            }
+
+            #[inline]
+            pub fn iter(self) -> impl Iterator<Item = $item> {
+                $item::ALL.into_iter().filter(move |item| self.contains(*item))
+            }
+        }
+
+        impl From<$item> for $name {
+            #[inline]
+            fn from(value: $item) -> Self {
+                Self(value.bit())
+            }
+        }
+
+        impl FromIterator<$item> for $name {
+            fn from_iter<I: IntoIterator<Item = $item>>(iter: I) -> Self {
+                let mut set = Self::EMPTY;
+                iter.into_iter().for_each(|item| set.insert(item));
+                set
+            }
+        }
+    };
+}
+
+bit_set!(ArtifactSet(Artifact));
+bit_set!(ReasonSet(Reason));
+
+/// The staleness of every [Artifact] of a single chunk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Staleness {
+    reasons: [ReasonSet; 4],
+}
+
+impl Staleness {
+    #[inline]
+    pub const fn reasons(&self, artifact: Artifact) -> ReasonSet {
+        self.reasons[artifact as usize]
+    }
+
+    #[inline]
+    pub const fn is_stale(&self, artifact: Artifact) -> bool {
+        !self.reasons(artifact).is_empty()
+    }
+
+    #[inline]
+    pub fn artifacts(&self) -> ArtifactSet {
+        Artifact::ALL.into_iter().filter(|artifact| self.is_stale(*artifact)).collect()
+    }
+
+    #[inline]
+    pub fn is_fresh(&self) -> bool {
+        self.reasons.iter().all(|reasons| reasons.is_empty())
+    }
+
+    #[inline]
+    fn mark(&mut self, artifact: Artifact, reason: Reason) {
+        self.reasons[artifact as usize].insert(reason);
+    }
+
+    #[inline]
+    fn clear(&mut self, artifact: Artifact) {
+        self.reasons[artifact as usize] = ReasonSet::EMPTY;
+    }
+}
+
+/// A coalesced request to rebuild the stale artifacts of a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RebuildJob {
+    pub chunk: ChunkPos,
+    pub artifacts: ArtifactSet,
+    pub staleness: Staleness,
+}
+
+/// Records which derived artifacts are stale per chunk, and why.
+#[derive(Debug, Clone)]
+pub struct InvalidationTracker {
+    /// All chunks with at least one stale artifact.
+    stale: BTreeMap<ChunkPos, Staleness>,
+    /// Chunks invalidated since the last call to [InvalidationTracker::end_tick].
+    touched: BTreeSet<ChunkPos>,
+    /// The radius (in chunks) that light is invalidated around a changed voxel.
+    light_radius: u32,
+}
+
+impl Default for InvalidationTracker {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIGHT_RADIUS)
+    }
+}
+
+impl InvalidationTracker {
+    /// Light travels at most 15 voxels, so it can only spill into directly adjacent chunks.
+    pub const DEFAULT_LIGHT_RADIUS: u32 = 1;
+
+    #[inline]
+    pub fn new(light_radius: u32) -> Self {
+        Self {
+            stale: BTreeMap::new(),
+            touched: BTreeSet::new(),
+            light_radius,
+        }
+    }
+
+    #[inline]
+    pub const fn light_radius(&self) -> u32 {
+        self.light_radius
+    }
+
+    /// Marks `artifact` of `chunk` as stale because of `reason`.
+    pub fn invalidate(&mut self, chunk: ChunkPos, artifact: Artifact, reason: Reason) {
+        self.stale.entry(chunk).or_default().mark(artifact, reason);
+        self.touched.insert(chunk);
+    }
+
+    /// Marks every artifact of `chunk` as stale because of `reason`.
+    pub fn invalidate_all(&mut self, chunk: ChunkPos, reason: Reason) {
+        for artifact in Artifact::ALL {
+            self.invalidate(chunk, artifact, reason);
+        }
+    }
+
+    /// Invalidates everything that depends on the voxel at `pos`.
+    ///
+    /// The mesh of the containing chunk is invalidated along with the meshes
+    /// of the (up to 6) face and edge neighbors that the voxel borders. Light
+    /// is invalidated in every chunk within the light radius. Voxels outside
+    /// every chunk have nothing depending on them.
+    pub fn voxel_changed(&mut self, pos: BlockPos) {
+        let Some((chunk, local)) = pos.split() else {
+            return;
+        };
+        let (lx, ly, lz) = local.into();
+        const fn border(local: i32) -> i32 {
+            if local == 0 {
+                -1
+            } else if local == CHUNK_MASK {
+                1
+            } else {
+                0
+            }
+        }
+        let (bx, by, bz) = (border(lx), border(ly), border(lz));
+        self.invalidate(chunk, Artifact::Mesh, Reason::VoxelChanged);
+        self.invalidate(chunk, Artifact::Heightmap, Reason::VoxelChanged);
+        self.invalidate(chunk, Artifact::Lod, Reason::VoxelChanged);
+        // Face neighbors (bits 0b001, 0b010, 0b100) and edge neighbors (for ambient occlusion).
+        // The corner neighbor (0b111) shares no face or edge with the voxel, so it is skipped.
+        for axes in 1u8..0b111 {
+            let pick = |bit: u8, border: i32| if axes & bit != 0 { border } else { 0 };
+            let offset = (pick(0b001, bx), pick(0b010, by), pick(0b100, bz));
+            let used = [(0b001, bx), (0b010, by), (0b100, bz)];
+            if used.iter().any(|&(bit, border)| axes & bit != 0 && border == 0) {
+                continue;
+            }
+            self.invalidate(chunk.offset(offset.0, offset.1, offset.2), Artifact::Mesh, Reason::NeighborChanged);
+        }
+        let radius = self.light_radius as i32;
+        for cy in -radius..=radius {
+            for cz in -radius..=radius {
+                for cx in -radius..=radius {
+                    let reason = if (cx, cy, cz) == (0, 0, 0) {
+                        Reason::VoxelChanged
+                    } else {
+                        Reason::LightPropagation
+                    };
+                    self.invalidate(chunk.offset(cx, cy, cz), Artifact::Light, reason);
+                }
+            }
+        }
+    }
+
+    /// Invalidates every artifact of a chunk that was just loaded, as well as the meshes of its face neighbors.
+    pub fn chunk_loaded(&mut self, chunk: ChunkPos) {
+        self.invalidate_all(chunk, Reason::ChunkLoaded);
+        for (x, y, z) in [(-1, 0, 0), (1, 0, 0), (0, -1, 0), (0, 1, 0), (0, 0, -1), (0, 0, 1)] {
+            self.invalidate(chunk.offset(x, y, z), Artifact::Mesh, Reason::NeighborChanged);
+        }
+    }
+
+    /// Removes all tracking for `chunk` (for example, when it is unloaded).
+    pub fn forget(&mut self, chunk: ChunkPos) {
+        self.stale.remove(&chunk);
+        self.touched.remove(&chunk);
+    }
+
+    /// Marks `artifact` of `chunk` as rebuilt.
+    pub fn mark_rebuilt(&mut self, chunk: ChunkPos, artifact: Artifact) {
+        if let Some(staleness) = self.stale.get_mut(&chunk) {
+            staleness.clear(artifact);
+            if staleness.is_fresh() {
+                self.stale.remove(&chunk);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn staleness(&self, chunk: ChunkPos) -> Staleness {
+        self.stale.get(&chunk).copied().unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn is_stale(&self, chunk: ChunkPos, artifact: Artifact) -> bool {
+        self.staleness(chunk).is_stale(artifact)
+    }
+
+    /// Iterates all chunks with stale artifacts, ordered by [ChunkPos].
+    #[inline]
+    pub fn iter_stale(&self) -> impl Iterator<Item = (ChunkPos, Staleness)> + '_ {
+        self.stale.iter().map(|(chunk, staleness)| (*chunk, *staleness))
+    }
+
+    /// The number of chunks with stale artifacts.
+    #[inline]
+    pub fn stale_count(&self) -> usize {
+        self.stale.len()
+    }
+
+    /// Ends the current tick, returning one coalesced [RebuildJob] for each chunk
+    /// that was invalidated during the tick, ordered by [ChunkPos].
+    pub fn end_tick(&mut self) -> Vec<RebuildJob> {
+        std::mem::take(&mut self.touched).into_iter().filter_map(|chunk| {
+            let staleness = *self.stale.get(&chunk)?;
+            Some(RebuildJob {
+                chunk,
+                artifacts: staleness.artifacts(),
+                staleness,
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh_chunks(tracker: &InvalidationTracker) -> Vec<ChunkPos> {
+        tracker.iter_stale()
+            .filter(|(_, staleness)| staleness.is_stale(Artifact::Mesh))
+            .map(|(chunk, _)| chunk)
+            .collect()
+    }
+
+    #[test]
+    fn voxel_changed_test() {
+        let mut tracker = InvalidationTracker::default();
+        tracker.voxel_changed(BlockPos::new(5, 5, 5));
+        assert_eq!(mesh_chunks(&tracker), vec![ChunkPos::ORIGIN]);
+        assert_eq!(tracker.stale_count(), 27);
+        
+        let mut tracker = InvalidationTracker::default();
+        // The minimum corner of chunk (0, 0, 0) borders 3 face neighbors and 3 edge neighbors.
+        tracker.voxel_changed(BlockPos::ORIGIN);
+        let meshes = mesh_chunks(&tracker);
+        assert_eq!(meshes.len(), 7);
+        assert!(!meshes.contains(&ChunkPos::new(-1, -1, -1)));
+        assert!(meshes.contains(&ChunkPos::new(-1, -1, 0)));
+        assert_eq!(
+            tracker.staleness(ChunkPos::new(-1, 0, 0)).reasons(Artifact::Mesh),
+            ReasonSet::from(Reason::NeighborChanged),
+        );
+        
+        let mut tracker = InvalidationTracker::default();
+        tracker.voxel_changed(BlockPos::new(-1, 8, 8));
+        assert_eq!(mesh_chunks(&tracker), vec![ChunkPos::new(-1, 0, 0), ChunkPos::ORIGIN]);
+    }
+
+    #[test]
+    fn coalesce_test() {
+        let mut tracker = InvalidationTracker::new(0);
+        for x in 1..15 {
+            tracker.voxel_changed(BlockPos::new(x, 4, 4));
+        }
+        tracker.invalidate(ChunkPos::ORIGIN, Artifact::Light, Reason::Manual);
+        let jobs = tracker.end_tick();
+        assert_eq!(jobs.len(), 1);
+        let job = jobs[0];
+        assert_eq!(job.chunk, ChunkPos::ORIGIN);
+        assert_eq!(job.artifacts, ArtifactSet::from_iter(Artifact::ALL));
+        assert!(job.staleness.reasons(Artifact::Light).contains(Reason::Manual));
+        assert!(tracker.end_tick().is_empty());
+        
+        for artifact in Artifact::ALL {
+            assert!(tracker.is_stale(ChunkPos::ORIGIN, artifact));
+            tracker.mark_rebuilt(ChunkPos::ORIGIN, artifact);
+        }
+        assert_eq!(tracker.stale_count(), 0);
+    }
+
+    #[test]
+    fn cache_key_test() {
+        let seed = HashSeed::derived("cache_key_test");
+        let key = Artifact::Mesh.cache_key(seed, ChunkPos::ORIGIN, 7u64);
+        assert_eq!(key, Artifact::Mesh.cache_key(seed, ChunkPos::ORIGIN, 7u64));
+        assert_ne!(key, Artifact::Lod.cache_key(seed, ChunkPos::ORIGIN, 7u64));
+        assert_ne!(key, Artifact::Mesh.cache_key(seed, ChunkPos::new(1, 0, 0), 7u64));
+        assert_ne!(key, Artifact::Mesh.cache_key(seed, ChunkPos::ORIGIN, 8u64));
+    }
+
+    #[test]
+    fn deterministic_order_test() {
+        let mut tracker = InvalidationTracker::default();
+        tracker.chunk_loaded(ChunkPos::new(3, 0, 0));
+        tracker.chunk_loaded(ChunkPos::new(-3, 0, 0));
+        let jobs = tracker.end_tick();
+        assert!(jobs.windows(2).all(|pair| pair[0].chunk < pair[1].chunk));
+    }
+}
@@ -0,0 +1,438 @@
+//! A write-ahead journal of world mutations between saves.
+//!
+//! Saving the whole world every few seconds is too slow, and a crash between autosaves loses
+//! everything since the last one. A [Journal] instead appends each tick's mutations (usually
+//! [VoxelEdit](crate::history::VoxelEdit)s) to a file on its own IO thread, so the tick doesn't
+//! wait on the disk:
+//!
+//! - [Journal::append] queues a tick's batch and returns. The IO thread writes whatever batches are
+//!   queued and syncs them to disk together.
+//! - [Journal::sync] waits until every batch appended so far is on disk.
+//! - [Journal::truncate_through] drops the batches a successful save covers.
+//! - [Journal::shutdown] (or dropping the journal) syncs everything before the thread stops, so a
+//!   clean exit loses nothing.
+//!
+//! After an unclean shutdown, [Journal::open] (or [read_journal]) returns the batches that made it to
+//! disk, to be replayed on top of the last save. A batch that was only partly written when the process died fails its
+//! checksum and ends the replay: it's the last one, and it was never reported as synced.
+//!
+//! Entries are replayed on top of a save that may already include some of them (the save can finish
+//! before the journal is truncated), so they should set state, not change it: a voxel edit's `after`
+//! state, not "one more item".
+//!
+//! # Format
+//!
+//! The file starts with [JOURNAL_MAGIC] and the version (u32). Each batch follows as the length
+//! (u32) of the rest of the batch, a checksum (see [seal](crate::recovery::seal)), the tick (u64),
+//! the entry count (u32) and the entries.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::JoinHandle,
+};
+
+use mfcereal::{
+    decode::{Decode, Decoder, MAX_PREALLOCATION},
+    encode::{Encode, Encoder},
+};
+
+use crate::recovery::{seal, unseal, LoadFailure};
+
+pub const JOURNAL_MAGIC: [u8; 4] = *b"MFWJ";
+/// Version 2 widened [VoxelEdit](crate::history::VoxelEdit) positions to `i64`. Older journals
+/// are rejected rather than misread.
+pub const JOURNAL_VERSION: u32 = 2;
+const HEADER_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("Journal IO failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("The file is not a world journal.")]
+    NotAJournal,
+    #[error("Unsupported journal version {0}.")]
+    UnsupportedVersion(u32),
+    #[error("The batch at offset {offset} is invalid: {failure}")]
+    InvalidBatch { offset: u64, failure: LoadFailure },
+    #[error("The journal IO thread stopped.")]
+    Stopped,
+}
+
+/// The mutations of one tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalBatch<T> {
+    pub tick: u64,
+    pub entries: Vec<T>,
+}
+
+/// What [read_journal] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalReplay<T> {
+    /// The complete batches, in the order they were appended.
+    pub batches: Vec<JournalBatch<T>>,
+    /// Whether the journal ended with a partly written batch, which was skipped.
+    pub torn: bool,
+    /// The length of the journal up to the end of the last complete batch.
+    pub valid_len: u64,
+}
+
+impl<T> Default for JournalReplay<T> {
+    #[inline]
+    fn default() -> Self {
+        Self { batches: Vec::new(), torn: false, valid_len: 0 }
+    }
+}
+
+fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&JOURNAL_MAGIC);
+    header[4..].copy_from_slice(&JOURNAL_VERSION.to_be_bytes());
+    header
+}
+
+/// Encodes a batch as it's written to the journal.
+fn encode_batch<T: Encode>(tick: u64, entries: &[T]) -> Vec<u8> {
+    let mut body = Vec::new();
+    let Ok(_) = body.write_u64(tick);
+    let Ok(_) = body.write_u32(entries.len() as u32);
+    for entry in entries {
+        let Ok(_) = entry.encode(&mut body);
+    }
+    let sealed = seal(&body);
+    let mut record = Vec::with_capacity(4 + sealed.len());
+    record.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+    record.extend_from_slice(&sealed);
+    record
+}
+
+/// Reads the batches of a journal. An empty journal (or one that was only created) has none.
+pub fn read_journal<T: Decode>(bytes: &[u8]) -> Result<JournalReplay<T>, JournalError> {
+    if bytes.is_empty() {
+        return Ok(JournalReplay::default());
+    }
+    let Some((head, mut rest)) = bytes.split_at_checked(HEADER_LEN) else {
+        return Err(JournalError::NotAJournal);
+    };
+    if head[..4] != JOURNAL_MAGIC {
+        return Err(JournalError::NotAJournal);
+    }
+    let version = u32::from_be_bytes(head[4..].try_into().unwrap());
+    if version != JOURNAL_VERSION {
+        return Err(JournalError::UnsupportedVersion(version));
+    }
+    let mut replay = JournalReplay { valid_len: HEADER_LEN as u64, ..JournalReplay::default() };
+    while !rest.is_empty() {
+        let offset = replay.valid_len;
+        let sealed = rest.split_first_chunk::<4>().and_then(|(len, rest)| {
+            rest.split_at_checked(u32::from_be_bytes(*len) as usize)
+        });
+        let Some((sealed, after)) = sealed else {
+            replay.torn = true;
+            break;
+        };
+        let Ok(mut body) = unseal(sealed) else {
+            replay.torn = true;
+            break;
+        };
+        // A batch that passed its checksum was written whole, so failing to decode it isn't a torn
+        // write, and isn't skipped.
+        let invalid = |failure| JournalError::InvalidBatch { offset, failure };
+        let tick = body.read_u64().map_err(|error| invalid(error.into()))?;
+        let count = body.read_u32().map_err(|error| invalid(error.into()))? as usize;
+        let mut entries = Vec::with_capacity(count.min(MAX_PREALLOCATION));
+        for _ in 0..count {
+            entries.push(T::decode(&mut body).map_err(|error| invalid(error.into()))?);
+        }
+        if !body.is_empty() {
+            return Err(invalid(LoadFailure::InvalidData("trailing bytes after journal entries")));
+        }
+        replay.batches.push(JournalBatch { tick, entries });
+        replay.valid_len += (4 + sealed.len()) as u64;
+        rest = after;
+    }
+    Ok(replay)
+}
+
+/// Reads the journal at `path`. A missing journal has no batches.
+pub fn read_journal_file<T: Decode>(path: &Path) -> Result<JournalReplay<T>, JournalError> {
+    match fs::read(path) {
+        Ok(bytes) => read_journal(&bytes),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(JournalReplay::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[derive(Debug)]
+enum Op {
+    Append { tick: u64, record: Vec<u8> },
+    Truncate { through: u64 },
+}
+
+#[derive(Debug)]
+struct State {
+    ops: Vec<Op>,
+    /// The number of ops queued, and the number done.
+    queued: u64,
+    done: u64,
+    shutdown: bool,
+    /// The first IO error. The journal stops writing after one, since later batches would be
+    /// replayed without the ones before them.
+    error: Option<io::Error>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when ops are queued, or on shutdown.
+    queued: Condvar,
+    /// Signalled when ops are done.
+    done: Condvar,
+}
+
+impl Shared {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("The journal state was poisoned.")
+    }
+}
+
+/// The file and the batches in it, owned by the IO thread.
+struct Writer {
+    path: PathBuf,
+    file: File,
+    /// The ticks and records of the batches in the file, to rewrite it when it's truncated.
+    records: Vec<(u64, Vec<u8>)>,
+}
+
+impl Writer {
+    fn run(&mut self, ops: Vec<Op>) -> io::Result<()> {
+        let mut appended = false;
+        for op in ops {
+            match op {
+                Op::Append { tick, record } => {
+                    self.file.write_all(&record)?;
+                    self.records.push((tick, record));
+                    appended = true;
+                }
+                Op::Truncate { through } => {
+                    self.records.retain(|&(tick, _)| tick > through);
+                    self.file = rewrite(&self.path, &self.records)?;
+                    appended = false;
+                }
+            }
+        }
+        // Everything written since the last sync is synced together.
+        if appended {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the journal at `path` with one holding `records`, returning it opened for appending.
+/// The new file is written beside the old one and renamed over it, so a crash leaves one or the
+/// other.
+fn rewrite(path: &Path, records: &[(u64, Vec<u8>)]) -> io::Result<File> {
+    let temp = path.with_extension("tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(&header())?;
+    for (_, record) in records {
+        file.write_all(record)?;
+    }
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    OpenOptions::new().append(true).open(path)
+}
+
+/// Appends batches of world mutations to a journal file on an IO thread (see the
+/// [module docs](self)).
+#[derive(Debug)]
+pub struct Journal {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if it's missing, and starts the IO thread. Batches
+    /// already in the journal are kept, to be dropped by the next [Journal::truncate_through], and
+    /// a torn batch at the end is cut off.
+    pub fn open<T: Decode>(path: &Path) -> Result<(Self, JournalReplay<T>), JournalError> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        let replay = read_journal::<T>(&bytes)?;
+        // Keep the records of the batches found, for rewriting.
+        let mut records = Vec::with_capacity(replay.batches.len());
+        let mut at = HEADER_LEN;
+        for batch in &replay.batches {
+            let len = 4 + u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+            records.push((batch.tick, bytes[at..at + len].to_vec()));
+            at += len;
+        }
+        let file = rewrite(path, &records)?;
+        let mut writer = Writer { path: path.to_owned(), file, records };
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { ops: Vec::new(), queued: 0, done: 0, shutdown: false, error: None }),
+            queued: Condvar::new(),
+            done: Condvar::new(),
+        });
+        let worker = Arc::clone(&shared);
+        let thread = std::thread::Builder::new()
+            .name("world journal".to_owned())
+            .spawn(move || loop {
+                let ops = {
+                    let mut state = worker.lock();
+                    while state.ops.is_empty() && !state.shutdown {
+                        state = worker.queued.wait(state).expect("The journal state was poisoned.");
+                    }
+                    if state.ops.is_empty() {
+                        return;
+                    }
+                    std::mem::take(&mut state.ops)
+                };
+                let count = ops.len() as u64;
+                let failed = worker.lock().error.is_some();
+                let result = if failed { Ok(()) } else { writer.run(ops) };
+                let mut state = worker.lock();
+                if let Err(error) = result {
+                    state.error.get_or_insert(error);
+                }
+                state.done += count;
+                worker.done.notify_all();
+            })
+            .expect("Failed to spawn the journal thread.");
+        Ok((Self { shared, thread: Some(thread) }, replay))
+    }
+
+    fn push(&self, op: Op) -> u64 {
+        let mut state = self.shared.lock();
+        state.ops.push(op);
+        state.queued += 1;
+        self.shared.queued.notify_one();
+        state.queued
+    }
+
+    /// Waits until the first `count` ops are done.
+    fn wait(&self, count: u64) -> Result<(), JournalError> {
+        let mut state = self.shared.lock();
+        while state.done < count {
+            if self.thread.as_ref().is_none_or(JoinHandle::is_finished) {
+                return Err(JournalError::Stopped);
+            }
+            state = self.shared.done.wait(state).expect("The journal state was poisoned.");
+        }
+        match &state.error {
+            Some(error) => Err(io::Error::new(error.kind(), error.to_string()).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Queues the mutations of `tick` to be written. Empty batches aren't written.
+    pub fn append<T: Encode>(&self, tick: u64, entries: &[T]) {
+        if !entries.is_empty() {
+            self.push(Op::Append { tick, record: encode_batch(tick, entries) });
+        }
+    }
+
+    /// Waits until every batch appended so far is on disk.
+    pub fn sync(&self) -> Result<(), JournalError> {
+        let queued = self.shared.lock().queued;
+        self.wait(queued)
+    }
+
+    /// Drops the batches of ticks up to and including `tick`, once a save of the world as of the
+    /// end of `tick` has been written. Batches of later ticks are kept. Waits until it's done.
+    pub fn truncate_through(&self, tick: u64) -> Result<(), JournalError> {
+        let count = self.push(Op::Truncate { through: tick });
+        self.wait(count)
+    }
+
+    /// Syncs everything appended and stops the IO thread.
+    pub fn shutdown(mut self) -> Result<(), JournalError> {
+        let result = self.sync();
+        self.stop();
+        result
+    }
+
+    fn stop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        self.shared.lock().shutdown = true;
+        self.shared.queued.notify_all();
+        let _ = thread.join();
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+
+    use super::*;
+    use crate::{chunk::BlockPos, history::{VoxelEdit, VoxelState}, voxel::id::VoxelId};
+
+    fn edit(x: i64, id: u32) -> VoxelEdit {
+        VoxelEdit {
+            position: BlockPos::new(x, 0, 0),
+            before: VoxelState::new(VoxelId::AIR, Orientation::UNORIENTED),
+            after: VoxelState::new(VoxelId::new(id), Orientation::UNORIENTED),
+        }
+    }
+
+    #[test]
+    fn journal_replay_test() {
+        let dir = std::env::temp_dir().join(format!("mfworld-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("world.journal");
+
+        let (journal, replay) = Journal::open::<VoxelEdit>(&path).unwrap();
+        assert!(replay.batches.is_empty());
+        journal.append(1, &[edit(0, 1), edit(1, 2)]);
+        journal.append::<VoxelEdit>(2, &[]);
+        journal.append(3, &[edit(2, 3)]);
+        journal.sync().unwrap();
+        // Dropped without a save, as in a crash after the sync.
+        drop(journal);
+
+        let replay = read_journal_file::<VoxelEdit>(&path).unwrap();
+        let ticks = replay.batches.iter().map(|batch| (batch.tick, batch.entries.len())).collect::<Vec<_>>();
+        assert_eq!(ticks, [(1, 2), (3, 1)]);
+        assert_eq!(replay.batches[1].entries, [edit(2, 3)]);
+        assert!(!replay.torn);
+
+        // A batch cut off partway through is skipped, and cut off when the journal is reopened.
+        let mut bytes = fs::read(&path).unwrap();
+        let full = bytes.len();
+        bytes.extend_from_slice(&encode_batch(4, &[edit(3, 4)])[..10]);
+        fs::write(&path, &bytes).unwrap();
+        let replay = read_journal_file::<VoxelEdit>(&path).unwrap();
+        assert_eq!((replay.batches.len(), replay.torn, replay.valid_len), (2, true, full as u64));
+
+        let (journal, replay) = Journal::open::<VoxelEdit>(&path).unwrap();
+        assert_eq!(replay.batches.len(), 2);
+        assert_eq!(fs::metadata(&path).unwrap().len(), full as u64);
+        journal.append(5, &[edit(5, 5)]);
+        // A save as of tick 3 leaves only the later batch.
+        journal.truncate_through(3).unwrap();
+        journal.shutdown().unwrap();
+        let replay = read_journal_file::<VoxelEdit>(&path).unwrap();
+        assert_eq!(replay.batches, [JournalBatch { tick: 5, entries: vec![edit(5, 5)] }]);
+
+        assert!(matches!(read_journal::<VoxelEdit>(b"MFSV\0\0\0\x01"), Err(JournalError::NotAJournal)));
+        assert!(matches!(read_journal::<VoxelEdit>(b"MFWJ\0\0\0\x01"), Err(JournalError::UnsupportedVersion(1))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,51 @@
+pub mod biome;
+pub mod block_entity;
+pub mod bounds;
 pub mod chunk;
+pub mod debug;
+pub mod entity;
+pub mod flush;
 pub mod geometry;
-pub mod voxel;
\ No newline at end of file
+pub mod history;
+pub mod invalidation;
+pub mod journal;
+pub mod light;
+pub mod listener;
+pub mod path;
+pub mod portal;
+pub mod profile;
+pub mod raycast;
+pub mod recovery;
+#[cfg(test)]
+mod roundtrip;
+pub mod skylight;
+pub mod structure;
+pub mod ticket;
+pub mod voxel;
+pub mod wrench;
+
+// World state is handed between threads (generation and lighting workers, and the server and
+// client halves of the game), so it has to stay thread safe. The profiler is the exception: its
+// span depth is thread local by design, and `SpanGuard` must be dropped on the thread it was
+// opened on.
+mfcore::assert_send_sync!(
+    biome::BiomeMap,
+    block_entity::BlockEntityTicker,
+    chunk::stored::StoredChunk,
+    chunk::metadata::MetadataLayer,
+    chunk::overlay::OverlayRegistry,
+    entity::EntityWorld<u64>,
+    flush::ChunkFlusher<chunk::stored::StoredChunk, std::io::Error>,
+    history::EditHistory,
+    invalidation::InvalidationTracker,
+    light::LightMap,
+    light::LightLoader,
+    listener::VoxelListeners,
+    portal::PortalRegistry,
+    recovery::ChunkRecovery,
+    structure::StructureIndex,
+    ticket::ChunkTickets,
+    voxel::hardness::HardnessRegistry,
+    voxel::shape::ShapeRegistry,
+    wrench::Wrench,
+);
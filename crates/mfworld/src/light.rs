@@ -0,0 +1,287 @@
+//! Stored light data and the policy for trusting it after a chunk is loaded.
+//!
+//! Relighting every chunk on load is expensive, so computed light is saved with the chunk.
+//! [LightLoader] decides, per [RelightPolicy], whether stored light is used as-is, used but
+//! verified in the background a few chunks per tick, or thrown away and recomputed.
+
+use std::collections::VecDeque;
+
+use mfcereal::{
+    bits::{BitReader, BitWriter},
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::{
+    chunk::{ChunkPos, CHUNK_VOLUME},
+    invalidation::{Artifact, InvalidationTracker, Reason},
+    profile::{self, SpanKind},
+};
+
+pub const MAX_LIGHT: u8 = 15;
+/// Bumped whenever the lighting algorithm changes. Stored light from other versions is always recomputed.
+pub const LIGHT_FORMAT_VERSION: u32 = 1;
+
+const NIBBLE_BYTES: usize = CHUNK_VOLUME / 2;
+
+/// The light level of each voxel in a chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LightMap {
+    Uniform(u8),
+    Nibbles(Box<[u8; NIBBLE_BYTES]>),
+}
+
+impl Default for LightMap {
+    #[inline]
+    fn default() -> Self {
+        Self::Uniform(0)
+    }
+}
+
+impl LightMap {
+    #[inline]
+    pub const fn uniform(level: u8) -> Self {
+        Self::Uniform(level & MAX_LIGHT)
+    }
+
+    /// Gets the light at `index` (see [crate::chunk::voxel_index]).
+    #[inline]
+    pub fn get(&self, index: usize) -> u8 {
+        match self {
+            LightMap::Uniform(level) => *level,
+            LightMap::Nibbles(nibbles) => (nibbles[index / 2] >> ((index & 1) * 4)) & 0xF,
+        }
+    }
+
+    /// Sets the light at `index` (see [crate::chunk::voxel_index]), returning the old level.
+    pub fn set(&mut self, index: usize, level: u8) -> u8 {
+        assert!(index < CHUNK_VOLUME, "Index out of bounds: {index}");
+        let level = level.min(MAX_LIGHT);
+        let old = self.get(index);
+        if old == level {
+            return old;
+        }
+        if let LightMap::Uniform(uniform) = *self {
+            *self = LightMap::Nibbles(Box::new([uniform | (uniform << 4); NIBBLE_BYTES]));
+        }
+        if let LightMap::Nibbles(nibbles) = self {
+            let shift = (index & 1) * 4;
+            let byte = &mut nibbles[index / 2];
+            *byte = (*byte & !(0xF << shift)) | (level << shift);
+        }
+        old
+    }
+
+    /// Creates a map from the level of every voxel, in voxel index order.
+    pub fn from_levels(levels: &[u8; CHUNK_VOLUME]) -> Self {
+        let first = levels[0];
+        if levels.iter().all(|&level| level == first) {
+            return Self::uniform(first);
+        }
+        let mut nibbles = Box::new([0u8; NIBBLE_BYTES]);
+        for (byte, pair) in nibbles.iter_mut().zip(levels.chunks_exact(2)) {
+            *byte = pair[0].min(MAX_LIGHT) | (pair[1].min(MAX_LIGHT) << 4);
+        }
+        LightMap::Nibbles(nibbles)
+    }
+
+    #[inline]
+    pub fn fill(&mut self, level: u8) {
+        *self = Self::uniform(level);
+    }
+
+    /// Collapses the storage if every voxel has the same light level.
+    pub fn compact(&mut self) {
+        let first = self.get(0);
+        if (1..CHUNK_VOLUME).all(|index| self.get(index) == first) {
+            *self = LightMap::Uniform(first);
+        }
+    }
+}
+
+// Layout: tag (u8), followed by:
+//      0 (Uniform): level (u8)
+//      1 (Nibbles): 4096 levels, 4 bits each in voxel index order (packed by BitWriter)
+impl Encode for LightMap {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        match self {
+            LightMap::Uniform(level) => Ok(encoder.write_u8(0)? + encoder.write_u8(*level)?),
+            LightMap::Nibbles(_) => {
+                let tag = encoder.write_u8(1)?;
+                let mut writer = BitWriter::new(encoder);
+                for index in 0..CHUNK_VOLUME {
+                    writer.write_bits(self.get(index) as u64, 4)?;
+                }
+                Ok(tag + writer.finish()?)
+            }
+        }
+    }
+}
+
+impl Decode for LightMap {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        match decoder.read_u8()? {
+            0 => match decoder.read_u8()? {
+                level @ 0..=MAX_LIGHT => Ok(LightMap::Uniform(level)),
+                _ => Err(DecodeError::InvalidData("light level out of range")),
+            },
+            1 => {
+                let mut map = LightMap::Nibbles(Box::new([0; NIBBLE_BYTES]));
+                let mut reader = BitReader::new(decoder);
+                for index in 0..CHUNK_VOLUME {
+                    map.set(index, reader.read_bits(4)? as u8);
+                }
+                Ok(map)
+            }
+            _ => Err(DecodeError::InvalidData("unknown light map tag")),
+        }
+    }
+}
+
+/// What to do with stored light when a chunk is loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelightPolicy {
+    /// Use stored light as-is.
+    Trust,
+    /// Use stored light, but recompute it in the background and fix it if it was wrong.
+    #[default]
+    VerifyLazily,
+    /// Always recompute light on load.
+    Recompute,
+}
+
+/// Applies a [RelightPolicy] to loaded chunks.
+#[derive(Debug, Clone)]
+pub struct LightLoader {
+    policy: RelightPolicy,
+    /// The number of chunks handed out for verification per tick.
+    verify_budget: usize,
+    pending: VecDeque<ChunkPos>,
+}
+
+impl LightLoader {
+    pub const DEFAULT_VERIFY_BUDGET: usize = 4;
+
+    #[inline]
+    pub fn new(policy: RelightPolicy, verify_budget: usize) -> Self {
+        Self {
+            policy,
+            verify_budget,
+            pending: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn policy(&self) -> RelightPolicy {
+        self.policy
+    }
+
+    #[inline]
+    pub fn set_policy(&mut self, policy: RelightPolicy) {
+        self.policy = policy;
+    }
+
+    /// The number of chunks waiting to be verified.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Called when `chunk` is loaded, instead of [InvalidationTracker::chunk_loaded].
+    /// `stored_version` is the [LIGHT_FORMAT_VERSION] the stored light was computed with,
+    /// or `None` if the chunk has no stored light.
+    pub fn chunk_loaded(&mut self, chunk: ChunkPos, stored_version: Option<u32>, tracker: &mut InvalidationTracker) {
+        tracker.chunk_loaded(chunk);
+        if stored_version != Some(LIGHT_FORMAT_VERSION) {
+            return;
+        }
+        match self.policy {
+            RelightPolicy::Trust => tracker.mark_rebuilt(chunk, Artifact::Light),
+            RelightPolicy::VerifyLazily => {
+                tracker.mark_rebuilt(chunk, Artifact::Light);
+                self.pending.push_back(chunk);
+            }
+            RelightPolicy::Recompute => (),
+        }
+    }
+
+    /// Drops any pending verification of `chunk` (for example, when it is unloaded).
+    pub fn forget(&mut self, chunk: ChunkPos) {
+        self.pending.retain(|&pending| pending != chunk);
+    }
+
+    /// Takes up to the verify budget of chunks whose light should be recomputed and compared this tick.
+    pub fn take_verifications(&mut self) -> Vec<ChunkPos> {
+        let count = self.verify_budget.min(self.pending.len());
+        self.pending.drain(..count).collect()
+    }
+
+    /// Reports the result of verifying `chunk`. If the stored light was wrong, the recomputed light
+    /// should replace it, and the chunk's mesh is invalidated.
+    pub fn verified(&mut self, chunk: ChunkPos, stored: &LightMap, computed: &LightMap, tracker: &mut InvalidationTracker) -> bool {
+        let _span = profile::span(SpanKind::Light, chunk);
+        let matches = (0..CHUNK_VOLUME).all(|index| stored.get(index) == computed.get(index));
+        if !matches {
+            tracker.invalidate(chunk, Artifact::Mesh, Reason::LightPropagation);
+        }
+        matches
+    }
+}
+
+impl Default for LightLoader {
+    #[inline]
+    fn default() -> Self {
+        Self::new(RelightPolicy::default(), Self::DEFAULT_VERIFY_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::voxel_index;
+
+    #[test]
+    fn light_map_test() {
+        let mut map = LightMap::uniform(15);
+        let mut bytes = Vec::new();
+        map.encode(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 2);
+        map.set(voxel_index(3, 4, 5), 9);
+        map.set(voxel_index(0, 0, 0), 0);
+        bytes.clear();
+        assert_eq!(map.encode(&mut bytes).unwrap(), 1 + 2048);
+        let decoded = LightMap::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, map);
+        assert_eq!(decoded.get(voxel_index(3, 4, 5)), 9);
+        map.set(voxel_index(3, 4, 5), 15);
+        map.set(voxel_index(0, 0, 0), 15);
+        map.compact();
+        assert_eq!(map, LightMap::Uniform(15));
+    }
+
+    #[test]
+    fn light_loader_test() {
+        let chunk = ChunkPos::new(1, 0, 1);
+        let mut tracker = InvalidationTracker::default();
+
+        let mut loader = LightLoader::new(RelightPolicy::Trust, 4);
+        loader.chunk_loaded(chunk, Some(LIGHT_FORMAT_VERSION), &mut tracker);
+        assert!(!tracker.is_stale(chunk, Artifact::Light));
+        // Light from an older lighting algorithm is never trusted.
+        loader.chunk_loaded(chunk, Some(0), &mut tracker);
+        assert!(tracker.is_stale(chunk, Artifact::Light));
+
+        let mut tracker = InvalidationTracker::default();
+        let mut loader = LightLoader::new(RelightPolicy::VerifyLazily, 1);
+        let other = ChunkPos::new(5, 0, 5);
+        loader.chunk_loaded(chunk, Some(LIGHT_FORMAT_VERSION), &mut tracker);
+        loader.chunk_loaded(other, Some(LIGHT_FORMAT_VERSION), &mut tracker);
+        assert!(!tracker.is_stale(chunk, Artifact::Light));
+        assert_eq!(loader.take_verifications(), vec![chunk]);
+        assert_eq!(loader.pending(), 1);
+        tracker.end_tick();
+        tracker.mark_rebuilt(chunk, Artifact::Mesh);
+        assert!(!loader.verified(chunk, &LightMap::uniform(15), &LightMap::uniform(14), &mut tracker));
+        assert!(tracker.is_stale(chunk, Artifact::Mesh));
+    }
+}
@@ -0,0 +1,228 @@
+//! Voxel change listeners for systems outside the simulation (renderers, network sync).
+//!
+//! A listener registers a [ListenerFilter] (a box of voxels, or a box of chunks) and gets back a
+//! [ListenerHandle]. Changes are [recorded](VoxelListeners::record) as they happen and delivered at
+//! the end of the tick in one batch per listener, with repeated changes to the same voxel collapsed
+//! into the last one. Dropping the handle deregisters the listener.
+//!
+//! A listener that doesn't drain keeps at most its `capacity` of changes. Past that, its pending
+//! changes are discarded and the next [Delivery] is marked [overflowed](Delivery::overflowed), so it
+//! knows to resynchronize the whole region instead of applying changes.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+
+use crate::{chunk::{BlockPos, ChunkPos}, history::VoxelState};
+
+/// Which changes a listener receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListenerFilter {
+    /// Voxels between `min` and `max`, inclusive.
+    Box {
+        min: BlockPos,
+        max: BlockPos,
+    },
+    /// Voxels in the chunks between `min` and `max`, inclusive.
+    Chunks {
+        min: ChunkPos,
+        max: ChunkPos,
+    },
+}
+
+impl ListenerFilter {
+    #[inline]
+    #[must_use]
+    pub const fn chunk(chunk: ChunkPos) -> Self {
+        Self::Chunks { min: chunk, max: chunk }
+    }
+
+    #[must_use]
+    pub const fn contains(&self, position: BlockPos) -> bool {
+        const fn within(value: BlockPos, min: BlockPos, max: BlockPos) -> bool {
+            min.x <= value.x && value.x <= max.x
+                && min.y <= value.y && value.y <= max.y
+                && min.z <= value.z && value.z <= max.z
+        }
+        match *self {
+            ListenerFilter::Box { min, max } => within(position, min, max),
+            ListenerFilter::Chunks { min, max } => match position.chunk() {
+                Some(chunk) => within(
+                    BlockPos::new(chunk.x as i64, chunk.y as i64, chunk.z as i64),
+                    BlockPos::new(min.x as i64, min.y as i64, min.z as i64),
+                    BlockPos::new(max.x as i64, max.y as i64, max.z as i64),
+                ),
+                None => false,
+            },
+        }
+    }
+}
+
+/// A voxel's state after a tick's changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoxelChange {
+    pub position: BlockPos,
+    pub state: VoxelState,
+}
+
+/// The changes delivered to a listener since it last drained.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// In the order of the ticks they happened on, and by position within a tick.
+    pub changes: Vec<VoxelChange>,
+    /// Changes were discarded because the listener fell behind.
+    pub overflowed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ListenerId(u32);
+
+/// Keeps a listener registered. Dropping every clone deregisters it at the end of the next tick.
+#[derive(Debug, Clone)]
+pub struct ListenerHandle {
+    id: ListenerId,
+    /// Only held. The registry watches its strong count.
+    _alive: Arc<()>,
+}
+
+impl ListenerHandle {
+    #[inline]
+    #[must_use]
+    pub const fn id(&self) -> ListenerId {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Listener {
+    filter: ListenerFilter,
+    alive: Weak<()>,
+    capacity: usize,
+    delivery: Delivery,
+}
+
+#[derive(Debug, Default)]
+pub struct VoxelListeners {
+    listeners: BTreeMap<ListenerId, Listener>,
+    next_id: u32,
+    /// This tick's changes, collapsed by position.
+    pending: BTreeMap<BlockPos, VoxelState>,
+}
+
+impl VoxelListeners {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a listener that keeps at most `capacity` undrained changes.
+    pub fn register(&mut self, filter: ListenerFilter, capacity: usize) -> ListenerHandle {
+        let id = ListenerId(self.next_id);
+        self.next_id += 1;
+        let alive = Arc::new(());
+        self.listeners.insert(id, Listener {
+            filter,
+            alive: Arc::downgrade(&alive),
+            capacity: capacity.max(1),
+            delivery: Delivery::default(),
+        });
+        ListenerHandle { id, _alive: alive }
+    }
+
+    /// The number of registered listeners, including dropped ones that haven't been removed yet.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// Records that the voxel at `position` changed to `state` this tick.
+    #[inline]
+    pub fn record(&mut self, position: BlockPos, state: VoxelState) {
+        if !self.listeners.is_empty() {
+            self.pending.insert(position, state);
+        }
+    }
+
+    /// Delivers this tick's changes, and removes the listeners whose handles were dropped.
+    pub fn end_tick(&mut self) {
+        self.listeners.retain(|_, listener| listener.alive.strong_count() != 0);
+        let pending = std::mem::take(&mut self.pending);
+        for listener in self.listeners.values_mut() {
+            let delivery = &mut listener.delivery;
+            for (&position, &state) in &pending {
+                if !listener.filter.contains(position) {
+                    continue;
+                }
+                if delivery.changes.len() == listener.capacity {
+                    delivery.changes.clear();
+                    delivery.overflowed = true;
+                }
+                if !delivery.overflowed {
+                    delivery.changes.push(VoxelChange { position, state });
+                }
+            }
+        }
+    }
+
+    /// Takes everything delivered to `handle`'s listener.
+    pub fn drain(&mut self, handle: &ListenerHandle) -> Delivery {
+        self.listeners.get_mut(&handle.id).map(|listener| std::mem::take(&mut listener.delivery)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+
+    use super::*;
+    use crate::voxel::id::VoxelId;
+
+    fn state(id: u32) -> VoxelState {
+        VoxelState::new(VoxelId::new(id), Orientation::UNORIENTED)
+    }
+
+    #[test]
+    fn listener_test() {
+        let mut listeners = VoxelListeners::new();
+        // Nothing is kept while nobody listens.
+        listeners.record(BlockPos::new(0, 0, 0), state(1));
+        let renderer = listeners.register(ListenerFilter::chunk(ChunkPos::ORIGIN), 16);
+        let sync = listeners.register(ListenerFilter::Box { min: BlockPos::new(-4, -4, -4), max: BlockPos::new(4, 4, 4) }, 2);
+        listeners.end_tick();
+        assert_eq!(listeners.drain(&renderer), Delivery::default());
+
+        listeners.record(BlockPos::new(1, 1, 1), state(2));
+        listeners.record(BlockPos::new(1, 1, 1), state(3));
+        listeners.record(BlockPos::new(-1, 0, 0), state(4));
+        listeners.end_tick();
+        assert_eq!(listeners.drain(&renderer).changes, [VoxelChange { position: BlockPos::new(1, 1, 1), state: state(3) }]);
+
+        // The second listener didn't drain, and fell behind.
+        listeners.record(BlockPos::new(2, 2, 2), state(5));
+        listeners.end_tick();
+        let behind = listeners.drain(&sync);
+        assert!(behind.overflowed && behind.changes.is_empty());
+        assert_eq!(listeners.drain(&renderer).changes.len(), 1);
+
+        drop(sync);
+        assert_eq!(listeners.len(), 2);
+        listeners.end_tick();
+        assert_eq!(listeners.len(), 1);
+        // Clones keep the listener alive.
+        let clone = renderer.clone();
+        drop(renderer);
+        listeners.end_tick();
+        assert_eq!(listeners.len(), 1);
+        drop(clone);
+        listeners.end_tick();
+        assert!(listeners.is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! Paths through the voxel grid.
+//!
+//! [find_path] is an A* search over voxels, moving one face at a time, for things that fly
+//! (drones) or are otherwise free to move in any direction through passable voxels. Whether a voxel
+//! is passable is up to the caller.
+//!
+//! Paths are deterministic: of the shortest paths, the search always finds the same one, whatever
+//! order voxels were loaded or edited in. Ties are broken by distance left to the goal, then by
+//! position, and neighbours are visited in [Direction::ALL] order.
+
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+};
+
+use mfgeometry::Direction;
+
+use crate::chunk::BlockPos;
+
+/// Where a path ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathGoal {
+    /// In the voxel.
+    At(BlockPos),
+    /// In a voxel sharing a face with the voxel, for reaching blocks (a container to load from).
+    Beside(BlockPos),
+}
+
+impl PathGoal {
+    #[inline]
+    #[must_use]
+    pub const fn target(self) -> BlockPos {
+        match self {
+            PathGoal::At(target) | PathGoal::Beside(target) => target,
+        }
+    }
+
+    /// Whether a path ending at `pos` reaches the goal.
+    #[inline]
+    #[must_use]
+    pub const fn is_reached(self, pos: BlockPos) -> bool {
+        match self {
+            PathGoal::At(target) => pos.manhattan_distance(target) == 0,
+            PathGoal::Beside(target) => pos.manhattan_distance(target) == 1,
+        }
+    }
+
+    /// A lower bound on the number of steps from `pos` to the goal.
+    #[inline]
+    const fn estimate(self, pos: BlockPos) -> u64 {
+        match self {
+            PathGoal::At(target) => pos.manhattan_distance(target),
+            PathGoal::Beside(target) => match pos.manhattan_distance(target) {
+                0 => 1,
+                distance => distance - 1,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PathError {
+    #[error("There is no path to the goal.")]
+    Unreachable,
+    #[error("No path to the goal was found within {0} voxels.")]
+    TooFar(u32),
+}
+
+/// Finds a shortest path from `start` to `goal` through voxels that are `passable`, visiting at
+/// most `max_voxels` voxels. `start` itself doesn't have to be passable.
+///
+/// The path is the voxels stepped into, in order, so it's empty when `start` already reaches the
+/// goal, and each voxel shares a face with the one before it.
+pub fn find_path<F: FnMut(BlockPos) -> bool>(
+    start: BlockPos,
+    goal: PathGoal,
+    mut passable: F,
+    max_voxels: u32,
+) -> Result<Vec<BlockPos>, PathError> {
+    // The step count to each voxel found so far, and the voxel it was reached from.
+    let mut found = HashMap::new();
+    found.insert(start, (0u64, start));
+    // (steps + estimate, estimate, pos, steps)
+    let mut open = BinaryHeap::new();
+    open.push(Reverse((goal.estimate(start), goal.estimate(start), start, 0u64)));
+    let mut visited = 0u32;
+    while let Some(Reverse((_, _, pos, steps))) = open.pop() {
+        if found[&pos].0 < steps {
+            // Already reached by a shorter path.
+            continue;
+        }
+        if goal.is_reached(pos) {
+            let mut path = Vec::with_capacity(steps as usize);
+            let mut at = pos;
+            while at != start {
+                path.push(at);
+                at = found[&at].1;
+            }
+            path.reverse();
+            return Ok(path);
+        }
+        if visited == max_voxels {
+            return Err(PathError::TooFar(max_voxels));
+        }
+        visited += 1;
+        for face in Direction::ALL {
+            let Some(next) = pos.checked_step(face) else {
+                continue;
+            };
+            let entry = match found.entry(next) {
+                Entry::Occupied(entry) if entry.get().0 <= steps + 1 => continue,
+                entry => entry,
+            };
+            if !passable(next) {
+                continue;
+            }
+            match entry {
+                Entry::Occupied(mut entry) => {
+                    entry.insert((steps + 1, pos));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((steps + 1, pos));
+                }
+            }
+            let estimate = goal.estimate(next);
+            open.push(Reverse((steps.saturating_add(1).saturating_add(estimate), estimate, next, steps + 1)));
+        }
+    }
+    Err(PathError::Unreachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_path_test() {
+        let pos = BlockPos::new;
+        // A wall at x = 2 with a single gap at y = 3.
+        let passable = |BlockPos { x, y, z }| (0..8).contains(&y) && (-4..4).contains(&z) && (x != 2 || y == 3);
+        let path = find_path(BlockPos::ORIGIN, PathGoal::At(pos(4, 0, 0)), passable, 1000).unwrap();
+        assert_eq!(path.len(), 10);
+        assert!(path.contains(&pos(2, 3, 0)));
+        let mut at = BlockPos::ORIGIN;
+        for &next in &path {
+            assert_eq!(at.manhattan_distance(next), 1);
+            assert!(passable(next));
+            at = next;
+        }
+        // The same path every time.
+        assert_eq!(find_path(BlockPos::ORIGIN, PathGoal::At(pos(4, 0, 0)), passable, 1000).unwrap(), path);
+
+        // Stopping beside a solid block.
+        let path = find_path(BlockPos::ORIGIN, PathGoal::Beside(pos(0, 0, 3)), passable, 1000).unwrap();
+        assert_eq!(path, [pos(0, 0, 1), pos(0, 0, 2)]);
+        assert_eq!(find_path(pos(0, 0, 2), PathGoal::Beside(pos(0, 0, 3)), passable, 1000), Ok(Vec::new()));
+
+        let walled = |BlockPos { x, .. }| x != 2;
+        assert_eq!(find_path(BlockPos::ORIGIN, PathGoal::At(pos(4, 0, 0)), walled, 500), Err(PathError::TooFar(500)));
+        let boxed = |at: BlockPos| at.manhattan_distance(BlockPos::ORIGIN) <= 2;
+        assert_eq!(find_path(BlockPos::ORIGIN, PathGoal::At(pos(4, 0, 0)), boxed, 500), Err(PathError::Unreachable));
+        // The edge of the world is a wall.
+        let edge = pos(i64::MAX, 0, 0);
+        assert_eq!(find_path(edge, PathGoal::At(pos(i64::MAX - 1, 0, 0)), |_| true, 10), Ok(vec![pos(i64::MAX - 1, 0, 0)]));
+    }
+}
@@ -0,0 +1,214 @@
+//! Links between portal voxels, possibly in different dimensions.
+//!
+//! Links are always two-way: linking `a` to `b` also links `b` back to `a`, and removing
+//! either end removes both. Movement and turtle systems call [PortalRegistry::enter] when
+//! something steps into a portal voxel.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::Orientation;
+
+use crate::chunk::{BlockPos, ChunkPos};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DimensionId(pub u32);
+
+impl DimensionId {
+    pub const OVERWORLD: Self = Self(0);
+}
+
+/// A voxel position within a dimension.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PortalPos {
+    pub dimension: DimensionId,
+    pub position: BlockPos,
+}
+
+impl PortalPos {
+    #[inline]
+    pub const fn new(dimension: DimensionId, position: BlockPos) -> Self {
+        Self { dimension, position }
+    }
+
+    /// The chunk containing the portal voxel (see [BlockPos::chunk]).
+    #[inline]
+    pub const fn chunk(self) -> Option<ChunkPos> {
+        self.position.chunk()
+    }
+}
+
+/// Where entering a portal leads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortalLink {
+    pub destination: PortalPos,
+    /// The orientation of the destination portal. Travelers leave through its [Orientation::forward] face.
+    pub facing: Orientation,
+}
+
+/// The result of entering a portal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Teleport {
+    pub dimension: DimensionId,
+    /// The voxel in front of the destination portal.
+    pub position: BlockPos,
+    pub facing: Orientation,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PortalRegistry {
+    links: BTreeMap<PortalPos, PortalLink>,
+}
+
+impl PortalRegistry {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { links: BTreeMap::new() }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Links `a` (facing `a_facing`) with `b` (facing `b_facing`). Any existing links of either end are removed.
+    pub fn link(&mut self, a: PortalPos, a_facing: Orientation, b: PortalPos, b_facing: Orientation) {
+        self.unlink(a);
+        self.unlink(b);
+        self.links.insert(a, PortalLink { destination: b, facing: b_facing });
+        self.links.insert(b, PortalLink { destination: a, facing: a_facing });
+    }
+
+    /// Removes the link at `pos` and its other end. Returns the other end.
+    pub fn unlink(&mut self, pos: PortalPos) -> Option<PortalPos> {
+        let link = self.links.remove(&pos)?;
+        self.links.remove(&link.destination);
+        Some(link.destination)
+    }
+
+    #[inline]
+    pub fn get(&self, pos: PortalPos) -> Option<PortalLink> {
+        self.links.get(&pos).copied()
+    }
+
+    /// Called when something enters the portal voxel at `pos`. `None` if it isn't linked, or its
+    /// destination faces off the edge of the world.
+    pub fn enter(&self, pos: PortalPos) -> Option<Teleport> {
+        let link = self.get(pos)?;
+        Some(Teleport {
+            dimension: link.destination.dimension,
+            position: link.destination.position.checked_step(link.facing.forward())?,
+            facing: link.facing,
+        })
+    }
+
+    /// Validates the links in a freshly loaded chunk. Links whose source voxel is no longer a portal
+    /// (according to `is_portal`) are removed along with their other end. Returns the removed sources.
+    pub fn validate_chunk<F: FnMut(BlockPos) -> bool>(&mut self, dimension: DimensionId, chunk: ChunkPos, mut is_portal: F) -> Vec<PortalPos> {
+        let start = PortalPos::new(dimension, BlockPos::new(i64::MIN, i64::MIN, i64::MIN));
+        let end = PortalPos::new(dimension, BlockPos::new(i64::MAX, i64::MAX, i64::MAX));
+        let broken = self.links.range(start..=end)
+            .map(|(&pos, _)| pos)
+            .filter(|pos| pos.chunk() == Some(chunk) && !is_portal(pos.position))
+            .collect::<Vec<_>>();
+        for &pos in &broken {
+            self.unlink(pos);
+        }
+        broken
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (PortalPos, PortalLink)> + '_ {
+        self.links.iter().map(|(&pos, &link)| (pos, link))
+    }
+}
+
+fn encode_pos<E: Encoder>(pos: PortalPos, encoder: &mut E) -> Result<u64, E::Error> {
+    Ok(encoder.write_u32(pos.dimension.0)? + pos.position.encode(encoder)?)
+}
+
+fn decode_pos<D: Decoder>(decoder: &mut D) -> Result<PortalPos, DecodeError<D::Error>> {
+    Ok(PortalPos::new(
+        DimensionId(decoder.read_u32()?),
+        BlockPos::decode(decoder)?,
+    ))
+}
+
+// Layout: link count (u64), followed by each link as
+// source (dimension u32, x, y, z i64), destination (same), facing (u8).
+impl Encode for PortalRegistry {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u64(self.links.len() as u64)?;
+        for (&pos, link) in &self.links {
+            written += encode_pos(pos, encoder)?;
+            written += encode_pos(link.destination, encoder)?;
+            written += link.facing.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for PortalRegistry {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let count = decoder.read_u64()?;
+        let mut links = BTreeMap::new();
+        for _ in 0..count {
+            let source = decode_pos(decoder)?;
+            let destination = decode_pos(decoder)?;
+            let facing = Orientation::decode(decoder)?;
+            links.insert(source, PortalLink { destination, facing });
+        }
+        let is_symmetric = links.iter().all(|(source, link)| {
+            links.get(&link.destination).is_some_and(|back| back.destination == *source)
+        });
+        if !is_symmetric {
+            return Err(DecodeError::InvalidData("portal link without a matching return link"));
+        }
+        Ok(Self { links })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::{Direction, Rotation, Flip};
+
+    use super::*;
+
+    const NETHER: DimensionId = DimensionId(1);
+
+    #[test]
+    fn portal_registry_test() {
+        let a = PortalPos::new(DimensionId::OVERWORLD, BlockPos::new(10, 64, -3));
+        let b = PortalPos::new(NETHER, BlockPos::new(1, 32, 0));
+        let c = PortalPos::new(NETHER, BlockPos::new(40, 32, 0));
+        let facing = Orientation::new(Rotation::new(Direction::PosY, 1), Flip::NONE);
+        let mut registry = PortalRegistry::new();
+        registry.link(a, Orientation::UNORIENTED, b, facing);
+        assert_eq!(registry.get(b).unwrap().destination, a);
+
+        let teleport = registry.enter(a).unwrap();
+        assert_eq!(teleport, Teleport { dimension: NETHER, position: b.position + facing.forward(), facing });
+
+        // Relinking `a` drops the stale link at `b`.
+        registry.link(a, Orientation::UNORIENTED, c, facing);
+        assert_eq!(registry.len(), 2);
+        assert!(registry.enter(b).is_none());
+
+        let mut bytes = Vec::new();
+        registry.encode(&mut bytes).unwrap();
+        assert_eq!(PortalRegistry::decode(&mut bytes.as_slice()).unwrap(), registry);
+
+        // `c` was broken while its chunk was unloaded.
+        let removed = registry.validate_chunk(NETHER, c.chunk().unwrap(), |_| false);
+        assert_eq!(removed, vec![c]);
+        assert!(registry.is_empty());
+    }
+}
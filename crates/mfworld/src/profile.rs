@@ -0,0 +1,293 @@
+//! Chunk-level profiling spans.
+//!
+//! Chunk work (generation, artifact rebuilds, serialization) is wrapped in a [span]. With the
+//! `profiling` feature enabled, each span is timed and recorded into a global ring buffer that
+//! tools such as `mfbench` or an in-game overlay [drain](drain_spans) periodically. Each record
+//! carries its start time, depth, and thread, so a drained batch can be laid out as a flame chart.
+//!
+//! Without the feature, [span] returns an empty guard and nothing is recorded.
+
+use std::time::Duration;
+
+use crate::{chunk::ChunkPos, invalidation::Artifact};
+
+/// Whether spans are recorded (the `profiling` feature is enabled).
+pub const ENABLED: bool = cfg!(feature = "profiling");
+/// The number of records kept before the oldest are overwritten.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpanKind {
+    Generate,
+    Mesh,
+    Light,
+    Heightmap,
+    Lod,
+    Serialize,
+    Deserialize,
+}
+
+impl SpanKind {
+    pub const ALL: [SpanKind; 7] = [
+        SpanKind::Generate,
+        SpanKind::Mesh,
+        SpanKind::Light,
+        SpanKind::Heightmap,
+        SpanKind::Lod,
+        SpanKind::Serialize,
+        SpanKind::Deserialize,
+    ];
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            SpanKind::Generate => "chunk_gen",
+            SpanKind::Mesh => "mesh",
+            SpanKind::Light => "light",
+            SpanKind::Heightmap => "heightmap",
+            SpanKind::Lod => "lod",
+            SpanKind::Serialize => "serialize",
+            SpanKind::Deserialize => "deserialize",
+        }
+    }
+}
+
+impl From<Artifact> for SpanKind {
+    /// The span kind for rebuilding `artifact`.
+    #[inline]
+    fn from(artifact: Artifact) -> Self {
+        match artifact {
+            Artifact::Mesh => SpanKind::Mesh,
+            Artifact::Light => SpanKind::Light,
+            Artifact::Heightmap => SpanKind::Heightmap,
+            Artifact::Lod => SpanKind::Lod,
+        }
+    }
+}
+
+impl std::fmt::Display for SpanKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A finished span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpanRecord {
+    pub kind: SpanKind,
+    pub chunk: ChunkPos,
+    /// The time the span started, relative to the first span ever recorded.
+    pub start: Duration,
+    pub duration: Duration,
+    /// The number of spans this span was nested in (on the same thread).
+    pub depth: u32,
+    /// A small per-process index of the thread the span ran on.
+    pub thread: u32,
+}
+
+impl SpanRecord {
+    #[inline]
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+}
+
+/// Times a span of work on `chunk` until the returned guard is dropped.
+#[inline]
+pub fn span(kind: SpanKind, chunk: ChunkPos) -> SpanGuard {
+    SpanGuard::new(kind, chunk)
+}
+
+/// Takes every record in the buffer, oldest first.
+#[inline]
+pub fn drain_spans() -> Vec<SpanRecord> {
+    #[cfg(feature = "profiling")]
+    {
+        enabled::buffer().records.drain(..).collect()
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        Vec::new()
+    }
+}
+
+/// The number of records overwritten before they were drained.
+#[inline]
+pub fn dropped_spans() -> u64 {
+    #[cfg(feature = "profiling")]
+    {
+        enabled::buffer().dropped
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        0
+    }
+}
+
+/// Sets the capacity of the ring buffer, discarding the oldest records if it shrinks.
+#[inline]
+pub fn set_span_capacity(capacity: usize) {
+    #[cfg(feature = "profiling")]
+    {
+        let mut buffer = enabled::buffer();
+        buffer.capacity = capacity.max(1);
+        while buffer.records.len() > buffer.capacity {
+            buffer.records.pop_front();
+            buffer.dropped += 1;
+        }
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        let _ = capacity;
+    }
+}
+
+/// The total duration spent in each kind of span, in [SpanKind::ALL] order.
+pub fn totals(records: &[SpanRecord]) -> [(SpanKind, Duration, u64); SpanKind::ALL.len()] {
+    let mut totals = SpanKind::ALL.map(|kind| (kind, Duration::ZERO, 0));
+    for record in records {
+        let entry = &mut totals[record.kind as usize];
+        entry.1 += record.duration;
+        entry.2 += 1;
+    }
+    totals
+}
+
+#[must_use = "the span ends when the guard is dropped"]
+pub struct SpanGuard {
+    #[cfg(feature = "profiling")]
+    open: enabled::OpenSpan,
+}
+
+impl SpanGuard {
+    #[inline]
+    fn new(kind: SpanKind, chunk: ChunkPos) -> Self {
+        #[cfg(feature = "profiling")]
+        {
+            Self { open: enabled::OpenSpan::start(kind, chunk) }
+        }
+        #[cfg(not(feature = "profiling"))]
+        {
+            let _ = (kind, chunk);
+            Self {}
+        }
+    }
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for SpanGuard {
+    #[inline]
+    fn drop(&mut self) {
+        self.open.finish();
+    }
+}
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use std::{
+        cell::Cell,
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Mutex, MutexGuard, OnceLock,
+        },
+        time::Instant,
+    };
+
+    use super::*;
+
+    pub struct SpanBuffer {
+        pub records: VecDeque<SpanRecord>,
+        pub capacity: usize,
+        pub dropped: u64,
+    }
+
+    struct Profiler {
+        epoch: Instant,
+        buffer: Mutex<SpanBuffer>,
+    }
+
+    fn profiler() -> &'static Profiler {
+        static PROFILER: OnceLock<Profiler> = OnceLock::new();
+        PROFILER.get_or_init(|| Profiler {
+            epoch: Instant::now(),
+            buffer: Mutex::new(SpanBuffer {
+                records: VecDeque::new(),
+                capacity: DEFAULT_CAPACITY,
+                dropped: 0,
+            }),
+        })
+    }
+
+    pub fn buffer() -> MutexGuard<'static, SpanBuffer> {
+        profiler().buffer.lock().unwrap_or_else(|poison| poison.into_inner())
+    }
+
+    static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+
+    thread_local! {
+        static THREAD: u32 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+        static DEPTH: Cell<u32> = const { Cell::new(0) };
+    }
+
+    pub struct OpenSpan {
+        kind: SpanKind,
+        chunk: ChunkPos,
+        depth: u32,
+        start: Instant,
+    }
+
+    impl OpenSpan {
+        #[inline]
+        pub fn start(kind: SpanKind, chunk: ChunkPos) -> Self {
+            // Initialize the epoch before the first span starts so that `start` is never negative.
+            profiler();
+            let depth = DEPTH.with(|depth| depth.replace(depth.get() + 1));
+            Self { kind, chunk, depth, start: Instant::now() }
+        }
+
+        pub fn finish(&self) {
+            let duration = self.start.elapsed();
+            DEPTH.with(|depth| depth.set(self.depth));
+            let profiler = profiler();
+            let record = SpanRecord {
+                kind: self.kind,
+                chunk: self.chunk,
+                start: self.start.duration_since(profiler.epoch),
+                duration,
+                depth: self.depth,
+                thread: THREAD.with(|thread| *thread),
+            };
+            let mut buffer = buffer();
+            if buffer.records.len() >= buffer.capacity {
+                buffer.records.pop_front();
+                buffer.dropped += 1;
+            }
+            buffer.records.push_back(record);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_test() {
+        let chunk = ChunkPos::new(1, 2, 3);
+        {
+            let _generate = span(SpanKind::Generate, chunk);
+            let _light = span(SpanKind::Light, chunk);
+        }
+        // Other tests may record spans concurrently, so only look at this chunk's records.
+        let records = drain_spans().into_iter()
+            .filter(|record| record.chunk == chunk)
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        // Inner spans finish first.
+        assert_eq!((records[0].kind, records[0].depth), (SpanKind::Light, 1));
+        assert_eq!((records[1].kind, records[1].depth), (SpanKind::Generate, 0));
+        assert!(records[1].start <= records[0].start && records[0].end() <= records[1].end());
+        assert_eq!(totals(&records)[SpanKind::Light as usize].2, 1);
+    }
+}
@@ -0,0 +1,247 @@
+//! Raycasts through the voxel grid.
+//!
+//! [cells] walks every voxel a ray passes through. [raycast] goes further and tests the ray
+//! against the collision [Shape](crate::voxel::shape::Shape) of each voxel, so that partial blocks are
+//! only hit where they actually have geometry.
+
+use mfgeometry::Direction;
+
+use crate::{
+    history::VoxelState,
+    voxel::shape::{ShapeBox, ShapeId, ShapeRegistry},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: (f64, f64, f64),
+    /// Always normalized, so that distances along the ray are in voxels.
+    direction: (f64, f64, f64),
+}
+
+impl Ray {
+    /// Creates a ray, or `None` if `direction` has no length.
+    pub fn new(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Option<Self> {
+        let (x, y, z) = direction;
+        let length = (x * x + y * y + z * z).sqrt();
+        if length == 0.0 || !length.is_finite() {
+            return None;
+        }
+        Some(Self {
+            origin,
+            direction: (x / length, y / length, z / length),
+        })
+    }
+
+    #[inline]
+    pub const fn direction(&self) -> (f64, f64, f64) {
+        self.direction
+    }
+
+    #[inline]
+    pub fn at(&self, distance: f64) -> (f64, f64, f64) {
+        let (ox, oy, oz) = self.origin;
+        let (dx, dy, dz) = self.direction;
+        (ox + dx * distance, oy + dy * distance, oz + dz * distance)
+    }
+}
+
+#[inline]
+const fn axis_component<T: Copy>(value: (T, T, T), axis: usize) -> T {
+    match axis {
+        0 => value.0,
+        1 => value.1,
+        _ => value.2,
+    }
+}
+
+/// The face a ray moving along `axis` in the direction of `delta` enters through.
+#[inline]
+fn entry_face(axis: usize, delta: f64) -> Direction {
+    match (axis, delta > 0.0) {
+        (0, true) => Direction::NegX,
+        (0, false) => Direction::PosX,
+        (1, true) => Direction::NegY,
+        (1, false) => Direction::PosY,
+        (_, true) => Direction::NegZ,
+        (_, false) => Direction::PosZ,
+    }
+}
+
+/// A voxel passed through by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellHit {
+    pub cell: (i32, i32, i32),
+    /// The distance along the ray where it enters the voxel.
+    pub distance: f64,
+    /// The face the ray entered through, or `None` for the voxel the ray starts in.
+    pub face: Option<Direction>,
+}
+
+/// Iterates the voxels `ray` passes through, in order, up to `max_distance`.
+pub fn cells(ray: Ray, max_distance: f64) -> impl Iterator<Item = CellHit> {
+    let (ox, oy, oz) = ray.origin;
+    let origin = [ox, oy, oz];
+    let (dx, dy, dz) = ray.direction;
+    let direction = [dx, dy, dz];
+    let mut cell = origin.map(|value| value.floor() as i32);
+    let step = direction.map(|delta| if delta > 0.0 { 1 } else { -1 });
+    let t_delta = direction.map(|delta| 1.0 / delta.abs());
+    let mut t_max = [0, 1, 2].map(|axis| {
+        let delta = direction[axis];
+        if delta == 0.0 {
+            f64::INFINITY
+        } else {
+            let boundary = cell[axis] as f64 + if delta > 0.0 { 1.0 } else { 0.0 };
+            (boundary - origin[axis]) / delta
+        }
+    });
+    let mut next = Some(CellHit { cell: (cell[0], cell[1], cell[2]), distance: 0.0, face: None });
+    ::core::iter::from_fn(move || {
+        let current = next?;
+        let axis = (0..3).min_by(|&a, &b| t_max[a].total_cmp(&t_max[b])).unwrap();
+        let distance = t_max[axis];
+        next = if distance <= max_distance {
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            Some(CellHit {
+                cell: (cell[0], cell[1], cell[2]),
+                distance,
+                face: Some(entry_face(axis, direction[axis])),
+            })
+        } else {
+            None
+        };
+        Some(current)
+    })
+}
+
+/// Where a ray hit a voxel's shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub voxel: (i32, i32, i32),
+    pub state: VoxelState,
+    pub distance: f64,
+    /// The face of the hit element the ray entered through.
+    pub face: Direction,
+    /// The exact point of the hit in world coordinates.
+    pub point: (f64, f64, f64),
+    pub shape: ShapeId,
+    /// The index of the element of the shape that was hit.
+    pub element: usize,
+}
+
+/// Intersects `ray` with `bounds`, returning the entry distance and the axis entered through.
+fn intersect_box(ray: &Ray, bounds: ShapeBox) -> Option<(f64, usize)> {
+    let mut enter = f64::NEG_INFINITY;
+    let mut enter_axis = 0;
+    let mut exit = f64::INFINITY;
+    for axis in 0..3 {
+        let origin = axis_component(ray.origin, axis);
+        let delta = axis_component(ray.direction, axis);
+        let (min, max) = (axis_component(bounds.min, axis), axis_component(bounds.max, axis));
+        if delta == 0.0 {
+            if origin < min || origin > max {
+                return None;
+            }
+            continue;
+        }
+        let (near, far) = {
+            let a = (min - origin) / delta;
+            let b = (max - origin) / delta;
+            if a < b { (a, b) } else { (b, a) }
+        };
+        if near > enter {
+            enter = near;
+            enter_axis = axis;
+        }
+        exit = exit.min(far);
+    }
+    (enter <= exit && exit >= 0.0).then_some((enter.max(0.0), enter_axis))
+}
+
+/// Finds the first voxel shape `ray` hits within `max_distance`. `voxel` gets the voxel at a
+/// position, or `None` if it isn't loaded, which ends the raycast.
+pub fn raycast<F>(ray: Ray, max_distance: f64, shapes: &ShapeRegistry, mut voxel: F) -> Option<RayHit>
+where F: FnMut((i32, i32, i32)) -> Option<VoxelState> {
+    for step in cells(ray, max_distance) {
+        let state = voxel(step.cell)?;
+        let shape = shapes.shape_id(state.id);
+        let (x, y, z) = step.cell;
+        let nearest = shapes.get(shape).boxes.iter()
+            .enumerate()
+            .filter_map(|(element, &bounds)| {
+                let local = bounds.oriented(state.orientation);
+                let bounds = ShapeBox::new(
+                    (local.min.0 + x as f64, local.min.1 + y as f64, local.min.2 + z as f64),
+                    (local.max.0 + x as f64, local.max.1 + y as f64, local.max.2 + z as f64),
+                );
+                intersect_box(&ray, bounds).map(|(distance, axis)| (distance, axis, element))
+            })
+            .filter(|&(distance, _, _)| distance <= max_distance)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((distance, axis, element)) = nearest {
+            return Some(RayHit {
+                voxel: step.cell,
+                state,
+                distance,
+                face: entry_face(axis, axis_component(ray.direction, axis)),
+                point: ray.at(distance),
+                shape,
+                element,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+
+    use super::*;
+    use crate::voxel::{id::VoxelId, shape::Shape};
+
+    #[test]
+    fn cells_test() {
+        let ray = Ray::new((0.5, 0.5, 0.5), (1.0, 1.0, 0.0)).unwrap();
+        let visited = cells(ray, 2.0).map(|hit| hit.cell).collect::<Vec<_>>();
+        assert_eq!(visited[0], (0, 0, 0));
+        // Every step moves to a neighboring voxel.
+        for pair in visited.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs() + (a.2 - b.2).abs(), 1);
+        }
+        assert!(visited.contains(&(1, 1, 0)));
+    }
+
+    #[test]
+    fn raycast_test() {
+        let slab_id = VoxelId::new(2);
+        let mut shapes = ShapeRegistry::new();
+        let slab = shapes.register(Shape { boxes: vec![ShapeBox::new((0.0, 0.0, 0.0), (1.0, 0.5, 1.0))] });
+        shapes.assign(slab_id, slab);
+        let world = |(x, y, z): (i32, i32, i32)| Some(match (x, y, z) {
+            (3, 0, 0) => VoxelState::new(slab_id, Orientation::UNORIENTED),
+            (3, 1, 0) => VoxelState::new(slab_id, Orientation::UNORIENTED.flip_y()),
+            (6, y, 0) if y <= 4 => VoxelState::new(VoxelId::new(1), Orientation::UNORIENTED),
+            _ => VoxelState::AIR,
+        });
+
+        // Passes over the bottom slab, into the top slab.
+        let ray = Ray::new((0.5, 0.75, 0.5), (1.0, 0.0, 0.0)).unwrap();
+        assert!(raycast(ray, 16.0, &shapes, world).is_none_or(|hit| hit.voxel != (3, 0, 0)));
+        let ray = Ray::new((0.5, 1.75, 0.5), (1.0, 0.0, 0.0)).unwrap();
+        let hit = raycast(ray, 16.0, &shapes, world).unwrap();
+        assert_eq!((hit.voxel, hit.face, hit.shape, hit.element), ((3, 1, 0), Direction::NegX, slab, 0));
+        assert!((hit.distance - 2.5).abs() < 1e-9);
+        assert!((hit.point.0 - 3.0).abs() < 1e-9);
+
+        let ray = Ray::new((0.5, 0.25, 0.5), (1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(raycast(ray, 16.0, &shapes, world).unwrap().voxel, (3, 0, 0));
+        assert!(raycast(ray, 2.0, &shapes, world).is_none());
+        // Hitting a full block from above.
+        let ray = Ray::new((6.5, 5.0, 0.5), (0.0, -1.0, 0.0)).unwrap();
+        let hit = raycast(ray, 16.0, &shapes, world).unwrap();
+        assert_eq!((hit.voxel, hit.face, hit.shape), ((6, 4, 0), Direction::PosY, ShapeId::FULL_CUBE));
+    }
+}
@@ -0,0 +1,272 @@
+//! Recovery from stored chunks that fail to load.
+//!
+//! Stored chunks are [sealed](seal) with a checksum. When a chunk fails its checksum or fails to
+//! decode, [ChunkRecovery] applies the world's [RecoveryPolicy]: the corrupt blob can be copied to a
+//! sidecar file for later inspection, and the chunk is then regenerated, replaced with an empty
+//! chunk, or left unloaded. Every recovery is reported as a [RecoveryEvent] so that the game can
+//! tell the player.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use mfcereal::decode::{Decode, DecodeError, UnexpectedEof};
+
+use crate::{
+    chunk::ChunkPos,
+    profile::{self, SpanKind},
+};
+
+/// The length of the checksum at the start of a sealed chunk.
+pub const CHECKSUM_LEN: usize = 16;
+
+/// Why a stored chunk couldn't be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum LoadFailure {
+    #[error("The chunk data is truncated.")]
+    Truncated,
+    #[error("The chunk data does not match its checksum.")]
+    ChecksumMismatch,
+    #[error("The chunk data is invalid: {0}")]
+    InvalidData(&'static str),
+    #[error("The chunk data is malformed.")]
+    Malformed,
+}
+
+impl From<DecodeError<UnexpectedEof>> for LoadFailure {
+    #[inline]
+    fn from(error: DecodeError<UnexpectedEof>) -> Self {
+        match error {
+            DecodeError::DecoderError(UnexpectedEof) => LoadFailure::Truncated,
+            DecodeError::InvalidData(message) => LoadFailure::InvalidData(message),
+            _ => LoadFailure::Malformed,
+        }
+    }
+}
+
+#[inline]
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    blake3::hash(payload).as_bytes()[..CHECKSUM_LEN].try_into().unwrap()
+}
+
+/// Prefixes `payload` (an encoded chunk) with its checksum.
+pub fn seal(payload: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(CHECKSUM_LEN + payload.len());
+    blob.extend_from_slice(&checksum(payload));
+    blob.extend_from_slice(payload);
+    blob
+}
+
+/// Checks the checksum of a sealed blob, returning the payload.
+pub fn unseal(blob: &[u8]) -> Result<&[u8], LoadFailure> {
+    let (stored, payload) = blob.split_at_checked(CHECKSUM_LEN).ok_or(LoadFailure::Truncated)?;
+    if stored != checksum(payload) {
+        return Err(LoadFailure::ChecksumMismatch);
+    }
+    Ok(payload)
+}
+
+/// What replaces a chunk that failed to load.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fallback {
+    /// Leave the chunk unloaded and report the failure to the caller.
+    Fail = 0,
+    /// Generate the chunk again from the world seed.
+    #[default]
+    Regenerate = 1,
+    /// Replace the chunk with an empty chunk.
+    Empty = 2,
+}
+
+impl Fallback {
+    #[inline]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Fallback::Fail),
+            1 => Some(Fallback::Regenerate),
+            2 => Some(Fallback::Empty),
+            _ => None,
+        }
+    }
+}
+
+/// How a world recovers from chunks that fail to load.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RecoveryPolicy {
+    /// The directory corrupt blobs are copied to before recovering, if any.
+    pub quarantine: Option<PathBuf>,
+    pub fallback: Fallback,
+}
+
+/// Where a loaded chunk came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkOrigin {
+    Stored,
+    Regenerated,
+    Empty,
+}
+
+/// A chunk that failed to load, and what was done about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecoveryEvent {
+    pub chunk: ChunkPos,
+    pub failure: LoadFailure,
+    pub fallback: Fallback,
+    /// Whether the corrupt blob was copied to the quarantine directory.
+    pub quarantined: bool,
+}
+
+/// Loads sealed chunks, applying a [RecoveryPolicy] to the ones that fail.
+#[derive(Debug, Default, Clone)]
+pub struct ChunkRecovery {
+    policy: RecoveryPolicy,
+    events: Vec<RecoveryEvent>,
+    regenerated: BTreeSet<ChunkPos>,
+}
+
+impl ChunkRecovery {
+    #[inline]
+    pub fn new(policy: RecoveryPolicy) -> Self {
+        Self {
+            policy,
+            events: Vec::new(),
+            regenerated: BTreeSet::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn policy(&self) -> &RecoveryPolicy {
+        &self.policy
+    }
+
+    #[inline]
+    pub fn set_policy(&mut self, policy: RecoveryPolicy) {
+        self.policy = policy;
+    }
+
+    /// Loads a chunk from a sealed `blob`. If it's corrupt, recovers according to the policy,
+    /// using `regenerate` to generate the chunk again.
+    ///
+    /// Fails only if the policy's fallback is [Fallback::Fail].
+    pub fn load<T, G>(&mut self, chunk: ChunkPos, blob: &[u8], regenerate: G) -> Result<(T, ChunkOrigin), LoadFailure>
+    where
+        T: Decode + Default,
+        G: FnOnce(ChunkPos) -> T,
+    {
+        let decoded = {
+            let _span = profile::span(SpanKind::Deserialize, chunk);
+            unseal(blob).and_then(|mut payload| Ok(T::decode(&mut payload)?))
+        };
+        let failure = match decoded {
+            Ok(value) => return Ok((value, ChunkOrigin::Stored)),
+            Err(failure) => failure,
+        };
+        let quarantined = self.policy.quarantine.as_deref()
+            .is_some_and(|dir| quarantine(dir, chunk, blob).is_ok());
+        self.events.push(RecoveryEvent {
+            chunk,
+            failure,
+            fallback: self.policy.fallback,
+            quarantined,
+        });
+        match self.policy.fallback {
+            Fallback::Fail => Err(failure),
+            Fallback::Regenerate => {
+                let value = {
+                    let _span = profile::span(SpanKind::Generate, chunk);
+                    regenerate(chunk)
+                };
+                self.regenerated.insert(chunk);
+                Ok((value, ChunkOrigin::Regenerated))
+            }
+            Fallback::Empty => Ok((T::default(), ChunkOrigin::Empty)),
+        }
+    }
+
+    /// Whether `chunk` was regenerated because its stored data was corrupt, and hasn't been saved since.
+    #[inline]
+    pub fn is_regenerated(&self, chunk: ChunkPos) -> bool {
+        self.regenerated.contains(&chunk)
+    }
+
+    /// Call when `chunk` has been saved, replacing the corrupt data.
+    #[inline]
+    pub fn saved(&mut self, chunk: ChunkPos) {
+        self.regenerated.remove(&chunk);
+    }
+
+    /// Takes the recoveries since the last call, in order.
+    #[inline]
+    pub fn drain_events(&mut self) -> Vec<RecoveryEvent> {
+        ::core::mem::take(&mut self.events)
+    }
+}
+
+/// Writes `blob` to a new file in `dir`, never overwriting an earlier quarantined blob.
+fn quarantine(dir: &Path, chunk: ChunkPos, blob: &[u8]) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let ChunkPos { x, y, z } = chunk;
+    for attempt in 0u32.. {
+        let path = dir.join(format!("chunk.{x}.{y}.{z}.{attempt}.corrupt"));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                std::io::Write::write_all(&mut file, blob)?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use mfcereal::encode::Encode;
+
+    use super::*;
+    use crate::light::LightMap;
+
+    #[test]
+    fn recovery_test() {
+        let chunk = ChunkPos::new(1, -2, 3);
+        let mut payload = Vec::new();
+        LightMap::uniform(7).encode(&mut payload).unwrap();
+        let blob = seal(&payload);
+
+        let dir = std::env::temp_dir().join(format!("mfworld-recovery-{}", std::process::id()));
+        let mut recovery = ChunkRecovery::new(RecoveryPolicy {
+            quarantine: Some(dir.clone()),
+            fallback: Fallback::Regenerate,
+        });
+        let regenerate = |_| LightMap::uniform(15);
+        assert_eq!(recovery.load(chunk, &blob, regenerate), Ok((LightMap::uniform(7), ChunkOrigin::Stored)));
+        assert!(recovery.drain_events().is_empty());
+
+        let mut corrupt = blob.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert_eq!(recovery.load(chunk, &corrupt, regenerate), Ok((LightMap::uniform(15), ChunkOrigin::Regenerated)));
+        assert!(recovery.is_regenerated(chunk));
+        let events = recovery.drain_events();
+        assert_eq!(events, [RecoveryEvent { chunk, failure: LoadFailure::ChecksumMismatch, fallback: Fallback::Regenerate, quarantined: true }]);
+        assert_eq!(std::fs::read(dir.join("chunk.1.-2.3.0.corrupt")).unwrap(), corrupt);
+        recovery.saved(chunk);
+        assert!(!recovery.is_regenerated(chunk));
+
+        // A valid checksum over data that doesn't decode.
+        recovery.set_policy(RecoveryPolicy { quarantine: None, fallback: Fallback::Empty });
+        let invalid = seal(&[9]);
+        assert_eq!(recovery.load(chunk, &invalid, regenerate), Ok((LightMap::default(), ChunkOrigin::Empty)));
+        assert_eq!(recovery.drain_events()[0].failure, LoadFailure::InvalidData("unknown light map tag"));
+
+        recovery.set_policy(RecoveryPolicy { quarantine: None, fallback: Fallback::Fail });
+        assert_eq!(recovery.load::<LightMap, _>(chunk, &blob[..4], regenerate), Err(LoadFailure::Truncated));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
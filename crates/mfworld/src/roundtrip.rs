@@ -0,0 +1,131 @@
+//! Round trip and fuzz decoding tests for every saved world type (see [mfcereal::testing]).
+
+use mfcereal::roundtrip_tests;
+use mfgeometry::{Direction, Flip, Orientation, Rotation};
+
+use crate::{
+    bounds::HeightBounds,
+    chunk::{
+        metadata::MetadataLayer,
+        overlay::{OverlayId, OverlayLayer, OverlayState},
+        stored::StoredChunk,
+        voxel_index,
+        BlockPos,
+        ChunkPos,
+        LocalPos,
+    },
+    entity::{ChunkEntities, EntityWorld},
+    history::{EditHistory, VoxelEdit, VoxelState},
+    light::LightMap,
+    portal::{DimensionId, PortalPos, PortalRegistry},
+    structure::{ChunkStructures, StructureBounds, StructureId, StructureRef},
+    ticket::{ChunkTickets, Ticket, TicketLevel},
+    voxel::id::VoxelId,
+};
+
+fn turned() -> Orientation {
+    Orientation::new(Rotation::new(Direction::NegX, 3), Flip::X)
+}
+
+fn edit(x: i64, id: u32) -> VoxelEdit {
+    VoxelEdit {
+        position: BlockPos::new(x, -4, 9),
+        before: VoxelState::AIR,
+        after: VoxelState::new(VoxelId::new(id), turned()),
+    }
+}
+
+fn edit_history() -> EditHistory {
+    let mut history = EditHistory::new(8);
+    for x in 0..3 {
+        history.record(edit(x, x as u32 + 1));
+    }
+    history
+}
+
+fn chunk_entities() -> ChunkEntities<u32> {
+    let mut world = EntityWorld::new();
+    world.load_chunk(ChunkPos::ORIGIN, ChunkEntities::new()).unwrap();
+    world.spawn((1.5, 2.0, 3.25), 7).unwrap();
+    world.spawn((4.0, 0.5, 8.0), 9).unwrap();
+    world.unload_chunk(ChunkPos::ORIGIN).unwrap()
+}
+
+fn portals() -> PortalRegistry {
+    let mut registry = PortalRegistry::new();
+    registry.link(
+        PortalPos::new(DimensionId::OVERWORLD, BlockPos::new(10, 64, -3)),
+        Orientation::UNORIENTED,
+        PortalPos::new(DimensionId(1), BlockPos::new(1, 32, 0)),
+        turned(),
+    );
+    registry
+}
+
+fn light() -> LightMap {
+    let mut map = LightMap::uniform(15);
+    map.set(voxel_index(3, 4, 5), 9);
+    map
+}
+
+fn metadata() -> MetadataLayer {
+    let mut layer = MetadataLayer::uniform(4);
+    layer.set(100, 255);
+    layer
+}
+
+fn stored_chunk() -> StoredChunk {
+    let mut chunk = StoredChunk::new();
+    for x in 0..4 {
+        chunk.set(voxel_index(x, 1, 2), VoxelState::new(VoxelId::new(x as u32 + 1), turned()));
+    }
+    chunk.block_entities.insert(voxel_index(1, 1, 2) as u16, vec![1, 2, 3]);
+    chunk
+}
+
+fn overlay() -> OverlayLayer {
+    let mut layer = OverlayLayer::new();
+    layer.set(voxel_index(0, 15, 0), Some(OverlayState::new(OverlayId::new(1), Orientation::UNORIENTED)));
+    layer.set(voxel_index(9, 3, 2), Some(OverlayState::new(OverlayId::new(u32::MAX), turned())));
+    layer
+}
+
+fn overlaid_chunk() -> StoredChunk {
+    let mut chunk = stored_chunk();
+    chunk.overlay = overlay();
+    chunk
+}
+
+fn tickets() -> ChunkTickets {
+    let mut tickets = ChunkTickets::new();
+    tickets.add(Ticket::machine(ChunkPos::new(1, 0, -1)));
+    tickets.add(Ticket::temporary(ChunkPos::new(0, 9, 0), 2, TicketLevel::Loaded, 100));
+    tickets
+}
+
+fn chunk_structures() -> ChunkStructures {
+    let mut structures = ChunkStructures::new();
+    structures.insert(StructureRef::new(StructureId(u64::MAX), StructureBounds::new((-40, 0, -8), (8, 30, 2))));
+    structures.insert(StructureRef::new(StructureId(5), StructureBounds::new((0, 0, 0), (4, 4, 4))));
+    structures
+}
+
+roundtrip_tests! {
+    block_pos_roundtrip: BlockPos = BlockPos::new(i64::MIN, -1, 1 << 40);
+    chunk_pos_roundtrip: ChunkPos = ChunkPos::new(-7, i32::MAX, 3);
+    local_pos_roundtrip: LocalPos = LocalPos::MAX;
+    voxel_edit_roundtrip: VoxelEdit = edit(-5, 3);
+    edit_history_roundtrip: EditHistory = edit_history();
+    chunk_entities_roundtrip: ChunkEntities<u32> = chunk_entities();
+    portal_registry_roundtrip: PortalRegistry = portals();
+    uniform_light_roundtrip: LightMap = LightMap::uniform(7);
+    light_map_roundtrip: LightMap = light();
+    metadata_layer_roundtrip: MetadataLayer = metadata();
+    stored_chunk_roundtrip: StoredChunk = stored_chunk();
+    overlay_layer_roundtrip: OverlayLayer = overlay();
+    overlaid_chunk_roundtrip: StoredChunk = overlaid_chunk();
+    chunk_tickets_roundtrip: ChunkTickets = tickets();
+    chunk_structures_roundtrip: ChunkStructures = chunk_structures();
+    height_bounds_roundtrip: HeightBounds = HeightBounds::from_chunks(-4, 19).unwrap();
+    unbounded_roundtrip: HeightBounds = HeightBounds::UNBOUNDED;
+}
@@ -0,0 +1,259 @@
+//! Sky light of a single chunk.
+//!
+//! Sky light enters a chunk through its top face. Light at [MAX_LIGHT] travels straight down
+//! without dimming; every other step (sideways, up, or down from a dimmer voxel) costs one level.
+//! Opaque voxels are dark. Light crossing the side and bottom faces is left to the neighboring
+//! chunks.
+//!
+//! [skylight_reference] floods the whole chunk from its top face. [skylight] gets the same result
+//! faster by using the heightmap: every column that is open to the sky is filled down to its
+//! surface directly, and the flood only starts from sky voxels that border a darker column, so it
+//! only visits voxels below the surface. A chunk that is open to the sky everywhere is never
+//! flooded at all.
+//!
+//! [skylight_in] and [heightmap] respect a dimension's [HeightBounds]: chunks outside of them have
+//! no light or heights, and the top chunk is always open to the sky, since nothing can be built
+//! above it.
+
+use std::collections::VecDeque;
+
+use crate::{
+    bounds::{HeightBounds, HeightError},
+    chunk::{voxel_index, ChunkPos, CHUNK_SIZE, CHUNK_VOLUME},
+    light::{LightMap, MAX_LIGHT},
+};
+
+/// The number of columns in a chunk.
+pub const COLUMNS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Gets the index of a column within column arrays from its local coordinate.
+#[inline(always)]
+pub const fn column_index(x: i32, z: i32) -> usize {
+    voxel_index(x, 0, z)
+}
+
+/// The inputs of the sky light of a chunk.
+#[derive(Debug, Clone, Copy)]
+pub struct SkyInput<'a> {
+    /// Whether each voxel is opaque, in [voxel_index] order.
+    pub opaque: &'a [bool; CHUNK_VOLUME],
+    /// The light of the voxel above the top of each column (in [column_index] order), from the chunk
+    /// above. [MAX_LIGHT] means the column is open to the sky.
+    pub above: &'a [u8; COLUMNS],
+}
+
+const TOP: i32 = CHUNK_SIZE - 1;
+
+/// Computes sky light by flooding the whole chunk from its top face.
+pub fn skylight_reference(input: SkyInput) -> LightMap {
+    let mut levels = [0u8; CHUNK_VOLUME];
+    let mut queue = VecDeque::new();
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let index = voxel_index(x, TOP, z);
+            let level = entering_level(input.above[column_index(x, z)]);
+            if level > 0 && !input.opaque[index] {
+                levels[index] = level;
+                queue.push_back((x, TOP, z));
+            }
+        }
+    }
+    spread(input.opaque, &mut levels, queue);
+    LightMap::from_levels(&levels)
+}
+
+/// Computes the same sky light as [skylight_reference], skipping the flood where the heightmap
+/// already determines the light.
+pub fn skylight(input: SkyInput) -> LightMap {
+    // The lowest Y that is lit by the sky directly, per column (CHUNK_SIZE if none).
+    let mut surface = [CHUNK_SIZE; COLUMNS];
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let column = column_index(x, z);
+            if input.above[column] < MAX_LIGHT {
+                continue;
+            }
+            let mut y = CHUNK_SIZE;
+            while y > 0 && !input.opaque[voxel_index(x, y - 1, z)] {
+                y -= 1;
+            }
+            surface[column] = y;
+        }
+    }
+    if surface.iter().all(|&y| y == 0) {
+        return LightMap::uniform(MAX_LIGHT);
+    }
+
+    let mut levels = [0u8; CHUNK_VOLUME];
+    let mut queue = VecDeque::new();
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let column = column_index(x, z);
+            let bottom = surface[column];
+            for y in bottom..CHUNK_SIZE {
+                levels[voxel_index(x, y, z)] = MAX_LIGHT;
+            }
+            // A sky voxel can only brighten the voxels beside it that are below their own surface.
+            let mut deepest_neighbor = bottom;
+            for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, nz) = (x + dx, z + dz);
+                if (0..CHUNK_SIZE).contains(&nx) && (0..CHUNK_SIZE).contains(&nz) {
+                    deepest_neighbor = deepest_neighbor.max(surface[column_index(nx, nz)]);
+                }
+            }
+            for y in bottom..deepest_neighbor {
+                queue.push_back((x, y, z));
+            }
+            // Columns that aren't open to the sky are lit from above like in the reference flood.
+            let level = entering_level(input.above[column]);
+            let index = voxel_index(x, TOP, z);
+            if level < MAX_LIGHT && level > 0 && !input.opaque[index] {
+                levels[index] = level;
+                queue.push_back((x, TOP, z));
+            }
+        }
+    }
+    spread(input.opaque, &mut levels, queue);
+    LightMap::from_levels(&levels)
+}
+
+/// Computes the sky light of `chunk` in a dimension with `bounds`, like [skylight]. The light from
+/// above is ignored in the top chunk, which is open to the sky.
+pub fn skylight_in(bounds: HeightBounds, chunk: ChunkPos, input: SkyInput) -> Result<LightMap, HeightError> {
+    bounds.check_chunk(chunk)?;
+    if bounds.is_top_chunk(chunk) {
+        return Ok(skylight(SkyInput { above: &[MAX_LIGHT; COLUMNS], ..input }));
+    }
+    Ok(skylight(input))
+}
+
+/// The Y of the highest opaque voxel of each column of `chunk` (in [column_index] order), or one
+/// below the bottom of the world for columns with no opaque voxels in the chunk.
+pub fn heightmap(bounds: HeightBounds, chunk: ChunkPos, opaque: &[bool; CHUNK_VOLUME]) -> Result<[i64; COLUMNS], HeightError> {
+    bounds.check_chunk(chunk)?;
+    let min_y = (chunk.y as i64) * CHUNK_SIZE as i64;
+    let mut heights = [bounds.min_y() - 1; COLUMNS];
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            if let Some(y) = (0..CHUNK_SIZE).rev().find(|&y| opaque[voxel_index(x, y, z)]) {
+                heights[column_index(x, z)] = min_y + y as i64;
+            }
+        }
+    }
+    Ok(heights)
+}
+
+/// The light of the top voxel of a column when the voxel above it has `above`.
+#[inline]
+const fn entering_level(above: u8) -> u8 {
+    if above >= MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        above.saturating_sub(1)
+    }
+}
+
+/// Floods light from the voxels in `queue` until nothing changes.
+fn spread(opaque: &[bool; CHUNK_VOLUME], levels: &mut [u8; CHUNK_VOLUME], mut queue: VecDeque<(i32, i32, i32)>) {
+    const NEIGHBORS: [(i32, i32, i32); 6] = [(0, -1, 0), (0, 1, 0), (-1, 0, 0), (1, 0, 0), (0, 0, -1), (0, 0, 1)];
+    while let Some((x, y, z)) = queue.pop_front() {
+        let level = levels[voxel_index(x, y, z)];
+        for (dx, dy, dz) in NEIGHBORS {
+            let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+            if !(0..CHUNK_SIZE).contains(&nx) || !(0..CHUNK_SIZE).contains(&ny) || !(0..CHUNK_SIZE).contains(&nz) {
+                continue;
+            }
+            let index = voxel_index(nx, ny, nz);
+            let next = if dy == -1 && level == MAX_LIGHT { MAX_LIGHT } else { level.saturating_sub(1) };
+            if !opaque[index] && next > levels[index] {
+                levels[index] = next;
+                queue.push_back((nx, ny, nz));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfhash::HashSeed;
+
+    use super::*;
+
+    fn assert_equivalent(opaque: &[bool; CHUNK_VOLUME], above: &[u8; COLUMNS]) {
+        let input = SkyInput { opaque, above };
+        let expected = skylight_reference(input);
+        let actual = skylight(input);
+        for index in 0..CHUNK_VOLUME {
+            assert_eq!(actual.get(index), expected.get(index), "Light differs at index {index}.");
+        }
+    }
+
+    #[test]
+    fn open_sky_test() {
+        let opaque = [false; CHUNK_VOLUME];
+        let input = SkyInput { opaque: &opaque, above: &[MAX_LIGHT; COLUMNS] };
+        assert_eq!(skylight(input), LightMap::Uniform(MAX_LIGHT));
+        assert_eq!(skylight_reference(input), LightMap::Uniform(MAX_LIGHT));
+        assert_equivalent(&opaque, &[0; COLUMNS]);
+    }
+
+    #[test]
+    fn overhang_test() {
+        // A roof over half of the chunk. Light reaches under it from the open half.
+        let mut opaque = [false; CHUNK_VOLUME];
+        for z in 0..CHUNK_SIZE {
+            for x in 0..8 {
+                opaque[voxel_index(x, 10, z)] = true;
+            }
+        }
+        let above = [MAX_LIGHT; COLUMNS];
+        let light = skylight(SkyInput { opaque: &opaque, above: &above });
+        assert_eq!(light.get(voxel_index(8, 0, 0)), MAX_LIGHT);
+        assert_eq!(light.get(voxel_index(7, 9, 0)), MAX_LIGHT - 1);
+        assert_eq!(light.get(voxel_index(0, 9, 0)), MAX_LIGHT - 8);
+        assert_eq!(light.get(voxel_index(0, 10, 0)), 0);
+        assert_equivalent(&opaque, &above);
+    }
+
+    #[test]
+    fn bounded_test() {
+        let bounds = HeightBounds::from_chunks(-1, 0).unwrap();
+        let mut opaque = [false; CHUNK_VOLUME];
+        opaque[voxel_index(3, 5, 4)] = true;
+        opaque[voxel_index(3, 2, 4)] = true;
+        let dark = [0; COLUMNS];
+        let input = SkyInput { opaque: &opaque, above: &dark };
+
+        // The top chunk is lit from the sky whatever is said to be above it.
+        let top = ChunkPos::new(0, 0, 0);
+        assert_eq!(skylight_in(bounds, top, input).unwrap().get(voxel_index(0, 0, 0)), MAX_LIGHT);
+        let below = ChunkPos::new(0, -1, 0);
+        assert_eq!(skylight_in(bounds, below, input).unwrap(), skylight(input));
+        assert_eq!(skylight_in(bounds, ChunkPos::new(0, 1, 0), input), Err(HeightError::AboveTop { y: 16, max_y: 15 }));
+
+        let heights = heightmap(bounds, below, &opaque).unwrap();
+        assert_eq!(heights[column_index(3, 4)], -11);
+        assert_eq!(heights[column_index(0, 0)], -17);
+        assert!(heightmap(bounds, ChunkPos::new(0, -2, 0), &opaque).is_err());
+    }
+
+    #[test]
+    fn random_equivalence_test() {
+        let seed = HashSeed::derived("mfworld::skylight::random_equivalence_test");
+        for round in 0..24u32 {
+            // Denser terrain toward the bottom of the chunk, with some caves.
+            let mut opaque = [false; CHUNK_VOLUME];
+            for (index, voxel) in opaque.iter_mut().enumerate() {
+                let y = (index >> 8) as u32;
+                *voxel = seed.hash_u32((round, index as u32)) % 16 >= y + round % 4;
+            }
+            let mut above = [MAX_LIGHT; COLUMNS];
+            for (column, level) in above.iter_mut().enumerate() {
+                if round % 3 != 0 {
+                    *level = (seed.hash_u32((round, column as u32, "above")) % 16) as u8;
+                }
+            }
+            assert_equivalent(&opaque, &above);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Which structures own which voxels.
+//!
+//! Generated structures and placed blueprints can span many chunks. Each chunk stores a
+//! [StructureRef] (the structure's id and bounds) for every structure that overlaps it, so asking
+//! what owns a voxel only looks at one chunk, and the references load and unload with their chunks
+//! (like [ChunkEntities](crate::entity::ChunkEntities)). The game uses this to protect generated
+//! structures and to trigger quests and scenarios when a player enters one.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::chunk::{BlockPos, ChunkPos, CHUNK_SHIFT};
+
+/// Identifies a structure. Generated structures derive theirs from the world seed and their
+/// bounds, so every chunk of a structure agrees on its id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StructureId(pub u64);
+
+/// A box of voxels, `min` inclusive and `max` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StructureBounds {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl StructureBounds {
+    #[inline]
+    #[must_use]
+    pub const fn new(min: (i32, i32, i32), max: (i32, i32, i32)) -> Self {
+        Self { min, max }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.min.0 >= self.max.0 || self.min.1 >= self.max.1 || self.min.2 >= self.max.2
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, (x, y, z): (i32, i32, i32)) -> bool {
+        self.min.0 <= x && x < self.max.0
+        && self.min.1 <= y && y < self.max.1
+        && self.min.2 <= z && z < self.max.2
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn intersects(self, other: Self) -> bool {
+        self.min.0 < other.max.0 && other.min.0 < self.max.0
+        && self.min.1 < other.max.1 && other.min.1 < self.max.1
+        && self.min.2 < other.max.2 && other.min.2 < self.max.2
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn volume(self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        (self.max.0.abs_diff(self.min.0) as u64) * (self.max.1.abs_diff(self.min.1) as u64) * (self.max.2.abs_diff(self.min.2) as u64)
+    }
+
+    /// The bounds of `chunk`.
+    #[inline]
+    #[must_use]
+    pub const fn of_chunk(chunk: ChunkPos) -> Self {
+        let min = chunk.min_voxel();
+        let size = 1 << CHUNK_SHIFT;
+        Self::new(min, (min.0 + size, min.1 + size, min.2 + size))
+    }
+
+    /// The chunks the bounds overlap, from the minimum chunk to the maximum chunk.
+    #[inline]
+    #[must_use]
+    pub const fn chunk_range(self) -> (ChunkPos, ChunkPos) {
+        (
+            ChunkPos::containing(self.min.0, self.min.1, self.min.2),
+            ChunkPos::containing(self.max.0 - 1, self.max.1 - 1, self.max.2 - 1),
+        )
+    }
+
+    /// The number of chunks the bounds overlap.
+    #[must_use]
+    pub const fn chunk_count(self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        let (min, max) = self.chunk_range();
+        (max.x.abs_diff(min.x) as u64 + 1) * (max.y.abs_diff(min.y) as u64 + 1) * (max.z.abs_diff(min.z) as u64 + 1)
+    }
+}
+
+/// A structure overlapping a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StructureRef {
+    pub id: StructureId,
+    pub bounds: StructureBounds,
+}
+
+impl StructureRef {
+    #[inline]
+    #[must_use]
+    pub const fn new(id: StructureId, bounds: StructureBounds) -> Self {
+        Self { id, bounds }
+    }
+}
+
+/// The structures overlapping one chunk, sorted by id.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkStructures {
+    refs: Vec<StructureRef>,
+}
+
+impl ChunkStructures {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = StructureRef> + '_ {
+        self.refs.iter().copied()
+    }
+
+    /// Adds a reference, replacing the one with the same id.
+    pub fn insert(&mut self, reference: StructureRef) {
+        match self.refs.binary_search_by_key(&reference.id, |existing| existing.id) {
+            Ok(index) => self.refs[index] = reference,
+            Err(index) => self.refs.insert(index, reference),
+        }
+    }
+
+    pub fn remove(&mut self, id: StructureId) -> Option<StructureRef> {
+        let index = self.refs.binary_search_by_key(&id, |existing| existing.id).ok()?;
+        Some(self.refs.remove(index))
+    }
+
+    /// The innermost structure containing the voxel at `pos`: the smallest, then the lowest id.
+    #[must_use]
+    pub fn structure_at(&self, pos: BlockPos) -> Option<StructureRef> {
+        let pos = pos.to_i32()?;
+        self.iter()
+            .filter(|reference| reference.bounds.contains(pos))
+            .min_by_key(|reference| (reference.bounds.volume(), reference.id))
+    }
+}
+
+// Layout: count (u32), followed by count * (id (u64), min (3 * i32), max (3 * i32)), in id order.
+impl Encode for ChunkStructures {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.refs.len() as u32)?;
+        for reference in &self.refs {
+            let StructureBounds { min, max } = reference.bounds;
+            written += encoder.write_u64(reference.id.0)?;
+            for value in [min.0, min.1, min.2, max.0, max.1, max.2] {
+                written += encoder.write_i32(value)?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for ChunkStructures {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut structures = Self::new();
+        for _ in 0..decoder.read_u32()? {
+            let id = StructureId(decoder.read_u64()?);
+            let min = (decoder.read_i32()?, decoder.read_i32()?, decoder.read_i32()?);
+            let max = (decoder.read_i32()?, decoder.read_i32()?, decoder.read_i32()?);
+            let bounds = StructureBounds::new(min, max);
+            if bounds.is_empty() {
+                return Err(DecodeError::InvalidData("empty structure bounds"));
+            }
+            if structures.refs.last().is_some_and(|last| last.id >= id) {
+                return Err(DecodeError::InvalidData("structure references out of order"));
+            }
+            structures.refs.push(StructureRef::new(id, bounds));
+        }
+        Ok(structures)
+    }
+}
+
+/// The structure references of the loaded chunks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StructureIndex {
+    chunks: BTreeMap<ChunkPos, ChunkStructures>,
+}
+
+impl StructureIndex {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the references of `chunk`, replacing any that were there.
+    #[inline]
+    pub fn load_chunk(&mut self, chunk: ChunkPos, structures: ChunkStructures) {
+        self.chunks.insert(chunk, structures);
+    }
+
+    /// Unloads `chunk`, returning its references to be saved with it.
+    #[inline]
+    pub fn unload_chunk(&mut self, chunk: ChunkPos) -> Option<ChunkStructures> {
+        self.chunks.remove(&chunk)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn chunk(&self, chunk: ChunkPos) -> Option<&ChunkStructures> {
+        self.chunks.get(&chunk)
+    }
+
+    /// Adds a structure to every loaded chunk it overlaps (when a blueprint is placed). Returns
+    /// the number of chunks it was added to.
+    pub fn insert(&mut self, reference: StructureRef) -> usize {
+        self.overlapping_chunks_mut(reference.bounds).map(|structures| structures.insert(reference)).count()
+    }
+
+    /// Removes a structure from every loaded chunk it overlaps. Returns the number of chunks it was
+    /// removed from.
+    pub fn remove(&mut self, reference: StructureRef) -> usize {
+        self.overlapping_chunks_mut(reference.bounds).filter_map(|structures| structures.remove(reference.id)).count()
+    }
+
+    /// The innermost structure that owns the voxel at `pos` (see [ChunkStructures::structure_at]).
+    /// `None` if there isn't one, or its chunk isn't loaded.
+    #[must_use]
+    pub fn structure_at(&self, pos: BlockPos) -> Option<StructureRef> {
+        self.chunks.get(&pos.chunk()?)?.structure_at(pos)
+    }
+
+    /// The structures in loaded chunks that intersect `bounds`, sorted by id.
+    #[must_use]
+    pub fn structures_intersecting(&self, bounds: StructureBounds) -> Vec<StructureRef> {
+        let mut found = Vec::new();
+        for (&chunk, structures) in &self.chunks {
+            if !StructureBounds::of_chunk(chunk).intersects(bounds) {
+                continue;
+            }
+            found.extend(structures.iter().filter(|reference| reference.bounds.intersects(bounds)));
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    fn overlapping_chunks_mut(&mut self, bounds: StructureBounds) -> impl Iterator<Item = &mut ChunkStructures> {
+        self.chunks.iter_mut()
+            .filter(move |(chunk, _)| StructureBounds::of_chunk(**chunk).intersects(bounds))
+            .map(|(_, structures)| structures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structure_index_test() {
+        let mut index = StructureIndex::new();
+        for x in -1..=2 {
+            index.load_chunk(ChunkPos::new(x, 0, 0), ChunkStructures::new());
+        }
+        let tower = StructureRef::new(StructureId(7), StructureBounds::new((-4, 0, 0), (20, 12, 6)));
+        let vault = StructureRef::new(StructureId(3), StructureBounds::new((2, 1, 1), (6, 5, 5)));
+        assert_eq!(index.insert(tower), 3);
+        assert_eq!(index.insert(vault), 1);
+
+        // The vault is inside the tower, so it owns its voxels.
+        assert_eq!(index.structure_at(BlockPos::new(3, 2, 2)), Some(vault));
+        assert_eq!(index.structure_at(BlockPos::new(18, 2, 2)), Some(tower));
+        assert_eq!(index.structure_at(BlockPos::new(18, 2, 7)), None);
+        assert_eq!(index.structures_intersecting(StructureBounds::new((0, 0, 0), (40, 1, 1))), [tower]);
+        assert_eq!(index.structures_intersecting(StructureBounds::new((-8, 0, 0), (8, 8, 8))), [vault, tower]);
+
+        let saved = index.unload_chunk(ChunkPos::ORIGIN).unwrap();
+        assert_eq!(index.structure_at(BlockPos::new(3, 2, 2)), None);
+        let mut bytes = Vec::new();
+        saved.encode(&mut bytes).unwrap();
+        index.load_chunk(ChunkPos::ORIGIN, ChunkStructures::decode(&mut bytes.as_slice()).unwrap());
+        assert_eq!(index.structure_at(BlockPos::new(3, 2, 2)), Some(vault));
+
+        assert_eq!(index.remove(tower), 3);
+        assert_eq!(index.structure_at(BlockPos::new(18, 2, 2)), None);
+        assert_eq!(StructureBounds::new((-1, 0, 0), (17, 1, 1)).chunk_count(), 3);
+    }
+}
@@ -0,0 +1,401 @@
+//! Chunk tickets: the reasons chunks stay loaded.
+//!
+//! A [Ticket] keeps the cube of chunks within its radius of its center at a [TicketLevel]. Players
+//! hold tickets around themselves, machines hold tickets on their own chunk so that they keep
+//! running after the player leaves, and temporary tickets (teleports, generation) expire on a
+//! given tick. A chunk is retained at the highest level of any ticket covering it, and chunks
+//! without a ticket may be unloaded.
+//!
+//! [ChunkTickets] records every change of a chunk's level, which the loader drains to load and
+//! unload chunks. Machine and temporary tickets are saved with the world, so forced-loaded areas
+//! resume simulation after a reload; player tickets are recreated when players join.
+
+use std::collections::{BTreeMap, HashMap};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::chunk::{loaded::LoadedChunks, ChunkPos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TicketKind {
+    Player = 0,
+    Machine = 1,
+    Temporary = 2,
+}
+
+impl TicketKind {
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TicketKind::Player),
+            1 => Some(TicketKind::Machine),
+            2 => Some(TicketKind::Temporary),
+            _ => None,
+        }
+    }
+
+    /// Whether tickets of this kind are saved with the world.
+    #[inline]
+    #[must_use]
+    pub const fn is_persistent(self) -> bool {
+        !matches!(self, TicketKind::Player)
+    }
+}
+
+/// How much of a chunk is kept running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TicketLevel {
+    /// Kept in memory, but not simulated (for example, so neighbors can mesh and light against it).
+    Loaded = 0,
+    /// Kept in memory and simulated.
+    Ticking = 1,
+}
+
+impl TicketLevel {
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TicketLevel::Loaded),
+            1 => Some(TicketLevel::Ticking),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ticket {
+    pub kind: TicketKind,
+    pub center: ChunkPos,
+    /// Chunks within this Chebyshev distance of `center` are covered.
+    pub radius: u8,
+    pub level: TicketLevel,
+    /// The tick on which the ticket is removed.
+    pub expires: Option<u64>,
+}
+
+impl Ticket {
+    /// The largest radius a saved ticket may have. Larger radii in a save are corrupt, and would
+    /// make loading it retain millions of chunks, so [ChunkTickets::add] clamps the radius of
+    /// persistent tickets to it.
+    pub const MAX_SAVED_RADIUS: u8 = 16;
+
+    /// Keeps the chunks around a player ticking.
+    #[inline]
+    #[must_use]
+    pub const fn player(center: ChunkPos, radius: u8) -> Self {
+        Self { kind: TicketKind::Player, center, radius, level: TicketLevel::Ticking, expires: None }
+    }
+
+    /// Keeps a machine's chunk ticking.
+    #[inline]
+    #[must_use]
+    pub const fn machine(chunk: ChunkPos) -> Self {
+        Self { kind: TicketKind::Machine, center: chunk, radius: 0, level: TicketLevel::Ticking, expires: None }
+    }
+
+    /// Keeps chunks loaded until the tick `expires`.
+    #[inline]
+    #[must_use]
+    pub const fn temporary(center: ChunkPos, radius: u8, level: TicketLevel, expires: u64) -> Self {
+        Self { kind: TicketKind::Temporary, center, radius, level, expires: Some(expires) }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn covers(&self, chunk: ChunkPos) -> bool {
+        self.center.chebyshev_distance(chunk) <= self.radius as u32
+    }
+
+    /// The chunks the ticket covers.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkPos> + use<> {
+        let radius = self.radius as i32;
+        let center = self.center;
+        (-radius..=radius).flat_map(move |x| {
+            (-radius..=radius).flat_map(move |y| (-radius..=radius).map(move |z| center.offset(x, y, z)))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId(u32);
+
+impl TicketId {
+    #[inline]
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// The tickets of one dimension, and the level they keep each chunk at.
+#[derive(Debug, Default, Clone)]
+pub struct ChunkTickets {
+    tickets: BTreeMap<TicketId, Ticket>,
+    next_id: u32,
+    /// The number of tickets covering each chunk, by level.
+    coverage: HashMap<ChunkPos, [u32; 2]>,
+    /// The covered chunks, for iterating them in a deterministic order.
+    retained: LoadedChunks,
+    /// The level at the last drain and the current level of every chunk whose level changed since.
+    changes: BTreeMap<ChunkPos, (Option<TicketLevel>, Option<TicketLevel>)>,
+}
+
+impl PartialEq for ChunkTickets {
+    /// Only the tickets are compared; pending changes are ignored.
+    fn eq(&self, other: &Self) -> bool {
+        self.next_id == other.next_id && self.tickets == other.tickets
+    }
+}
+
+impl Eq for ChunkTickets {}
+
+impl ChunkTickets {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, id: TicketId) -> Option<&Ticket> {
+        self.tickets.get(&id)
+    }
+
+    /// The tickets, in the order they were added.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (TicketId, &Ticket)> + '_ {
+        self.tickets.iter().map(|(&id, ticket)| (id, ticket))
+    }
+
+    /// Adds a ticket. The radius of a persistent ticket is clamped to [Ticket::MAX_SAVED_RADIUS],
+    /// so that every ticket that's saved can be loaded again.
+    pub fn add(&mut self, mut ticket: Ticket) -> TicketId {
+        if ticket.kind.is_persistent() {
+            ticket.radius = ticket.radius.min(Ticket::MAX_SAVED_RADIUS);
+        }
+        let id = TicketId(self.next_id);
+        self.next_id += 1;
+        self.insert(id, ticket);
+        id
+    }
+
+    /// Removes a ticket. Its chunks stay loaded if other tickets cover them.
+    pub fn remove(&mut self, id: TicketId) -> Option<Ticket> {
+        let ticket = self.tickets.remove(&id)?;
+        for chunk in ticket.chunks() {
+            let before = self.level(chunk);
+            let counts = self.coverage.get_mut(&chunk).expect("Covered chunks are counted.");
+            counts[ticket.level as usize] -= 1;
+            if *counts == [0, 0] {
+                self.coverage.remove(&chunk);
+                self.retained.remove(chunk);
+            }
+            self.record(chunk, before);
+        }
+        Some(ticket)
+    }
+
+    /// Moves a ticket (for example, a player's ticket when the player enters another chunk).
+    pub fn move_ticket(&mut self, id: TicketId, center: ChunkPos) -> bool {
+        let Some(mut ticket) = self.remove(id) else {
+            return false;
+        };
+        ticket.center = center;
+        self.insert(id, ticket);
+        true
+    }
+
+    /// Removes the tickets that expire on or before `tick`. Returns the number removed.
+    pub fn expire(&mut self, tick: u64) -> usize {
+        let expired = self.tickets.iter()
+            .filter(|(_, ticket)| ticket.expires.is_some_and(|expires| expires <= tick))
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for &id in &expired {
+            self.remove(id);
+        }
+        expired.len()
+    }
+
+    /// The level `chunk` is kept at, or `None` if nothing retains it.
+    #[inline]
+    #[must_use]
+    pub fn level(&self, chunk: ChunkPos) -> Option<TicketLevel> {
+        match self.coverage.get(&chunk) {
+            Some([_, ticking]) if *ticking != 0 => Some(TicketLevel::Ticking),
+            Some(_) => Some(TicketLevel::Loaded),
+            None => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_retained(&self, chunk: ChunkPos) -> bool {
+        self.coverage.contains_key(&chunk)
+    }
+
+    /// Every retained chunk and its level, in Morton order (see [LoadedChunks]).
+    pub fn retained(&self) -> impl Iterator<Item = (ChunkPos, TicketLevel)> + '_ {
+        self.retained.iter().map(|chunk| (chunk, self.level(chunk).expect("Counted chunks are covered.")))
+    }
+
+    /// Takes the chunks whose level changed since the last drain, with their new level, in order.
+    /// Chunks whose level is `None` may be unloaded.
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = (ChunkPos, Option<TicketLevel>)> + use<> {
+        std::mem::take(&mut self.changes).into_iter().map(|(chunk, (_, level))| (chunk, level))
+    }
+
+    fn insert(&mut self, id: TicketId, ticket: Ticket) {
+        for chunk in ticket.chunks() {
+            let before = self.level(chunk);
+            self.coverage.entry(chunk).or_default()[ticket.level as usize] += 1;
+            self.retained.insert(chunk);
+            self.record(chunk, before);
+        }
+        self.tickets.insert(id, ticket);
+    }
+
+    fn record(&mut self, chunk: ChunkPos, before: Option<TicketLevel>) {
+        let after = self.level(chunk);
+        if after == before {
+            return;
+        }
+        let (drained, _) = *self.changes.entry(chunk).or_insert((before, after));
+        // A change that's undone before it's drained isn't a change.
+        if drained == after {
+            self.changes.remove(&chunk);
+        } else {
+            self.changes.insert(chunk, (drained, after));
+        }
+    }
+}
+
+// Layout: next id (u32), count (u32), followed by count * (
+//      id (u32), kind (u8), center (3 * i32), radius (u8), level (u8), expires (Option<u64>)
+// ) for persistent tickets only, in id order.
+impl Encode for ChunkTickets {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let persistent = self.tickets.iter().filter(|(_, ticket)| ticket.kind.is_persistent());
+        let mut written = encoder.write_u32(self.next_id)? + encoder.write_u32(persistent.clone().count() as u32)?;
+        for (id, ticket) in persistent {
+            written += encoder.write_u32(id.0)?
+                + encoder.write_u8(ticket.kind as u8)?
+                + encoder.write_i32(ticket.center.x)?
+                + encoder.write_i32(ticket.center.y)?
+                + encoder.write_i32(ticket.center.z)?
+                + encoder.write_u8(ticket.radius)?
+                + encoder.write_u8(ticket.level as u8)?
+                + ticket.expires.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+/// Decoding records every retained chunk as changed, so the loader brings forced-loaded areas back.
+impl Decode for ChunkTickets {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut tickets = Self::new();
+        tickets.next_id = decoder.read_u32()?;
+        for _ in 0..decoder.read_u32()? {
+            let id = TicketId(decoder.read_u32()?);
+            let kind = TicketKind::from_u8(decoder.read_u8()?)
+                .filter(|kind| kind.is_persistent())
+                .ok_or(DecodeError::InvalidData("invalid ticket kind"))?;
+            let center = ChunkPos::new(decoder.read_i32()?, decoder.read_i32()?, decoder.read_i32()?);
+            let radius = decoder.read_u8()?;
+            if radius > Ticket::MAX_SAVED_RADIUS {
+                return Err(DecodeError::InvalidData("ticket radius too large"));
+            }
+            let level = TicketLevel::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid ticket level"))?;
+            let expires = Option::<u64>::decode(decoder)?;
+            if id.0 >= tickets.next_id || tickets.tickets.contains_key(&id) {
+                return Err(DecodeError::InvalidData("invalid ticket id"));
+            }
+            tickets.insert(id, Ticket { kind, center, radius, level, expires });
+        }
+        Ok(tickets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticket_test() {
+        let mut tickets = ChunkTickets::new();
+        let player = tickets.add(Ticket::player(ChunkPos::ORIGIN, 1));
+        let machine = tickets.add(Ticket::machine(ChunkPos::new(1, 0, 0)));
+        let portal = tickets.add(Ticket::temporary(ChunkPos::new(5, 0, 0), 0, TicketLevel::Loaded, 20));
+        assert_eq!(tickets.drain_changes().count(), 27 + 1);
+        assert_eq!(tickets.level(ChunkPos::new(-1, 1, 1)), Some(TicketLevel::Ticking));
+        assert_eq!(tickets.level(ChunkPos::new(5, 0, 0)), Some(TicketLevel::Loaded));
+        assert_eq!(tickets.level(ChunkPos::new(2, 0, 0)), None);
+
+        // The machine keeps its chunk ticking after the player leaves.
+        tickets.move_ticket(player, ChunkPos::new(-10, 0, 0));
+        assert!(tickets.is_retained(ChunkPos::new(1, 0, 0)));
+        let changes = tickets.drain_changes().collect::<Vec<_>>();
+        assert_eq!(changes.iter().filter(|(_, level)| level.is_none()).count(), 26);
+        assert_eq!(changes.iter().filter(|(_, level)| level.is_some()).count(), 27);
+
+        // Removing and re-adding before the drain isn't a change.
+        let ticket = tickets.remove(machine).unwrap();
+        let machine = tickets.add(ticket);
+        assert_eq!(tickets.drain_changes().count(), 0);
+
+        assert_eq!(tickets.expire(19), 0);
+        assert_eq!(tickets.expire(20), 1);
+        assert!(tickets.get(portal).is_none());
+        assert_eq!(tickets.drain_changes().collect::<Vec<_>>(), [(ChunkPos::new(5, 0, 0), None)]);
+
+        tickets.add(Ticket::temporary(ChunkPos::new(0, 9, 0), 0, TicketLevel::Ticking, 100));
+        let mut bytes = Vec::new();
+        tickets.encode(&mut bytes).unwrap();
+        let mut loaded = ChunkTickets::decode(&mut bytes.as_slice()).unwrap();
+        // Only the machine and temporary tickets survive a reload.
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(machine), tickets.get(machine));
+        assert_eq!(loaded.drain_changes().collect::<Vec<_>>(), [
+            (ChunkPos::new(0, 9, 0), Some(TicketLevel::Ticking)),
+            (ChunkPos::new(1, 0, 0), Some(TicketLevel::Ticking)),
+        ]);
+        // New tickets don't reuse ids.
+        assert!(loaded.add(Ticket::player(ChunkPos::ORIGIN, 0)).get() > machine.get());
+    }
+
+    #[test]
+    fn saved_radius_test() {
+        let mut tickets = ChunkTickets::new();
+        let max = Ticket::MAX_SAVED_RADIUS;
+        let at_limit = tickets.add(Ticket::temporary(ChunkPos::ORIGIN, max, TicketLevel::Loaded, 5));
+        let over = tickets.add(Ticket::temporary(ChunkPos::ORIGIN, max + 1, TicketLevel::Loaded, 5));
+        let player = tickets.add(Ticket::player(ChunkPos::ORIGIN, max + 1));
+        assert_eq!(tickets.get(at_limit).unwrap().radius, max);
+        // Persistent tickets are clamped so that the save can be loaded; player tickets aren't saved.
+        assert_eq!(tickets.get(over).unwrap().radius, max);
+        assert_eq!(tickets.get(player).unwrap().radius, max + 1);
+
+        let mut bytes = Vec::new();
+        tickets.encode(&mut bytes).unwrap();
+        let loaded = ChunkTickets::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!((loaded.get(at_limit), loaded.get(over)), (tickets.get(at_limit), tickets.get(over)));
+    }
+}
@@ -0,0 +1,87 @@
+//! How hard voxels are to break.
+//!
+//! Every voxel type has a [Hardness]: the number of ticks it takes to break by hand, and the
+//! [MiningTier] a tool needs to break it at full speed and harvest it. Voxels without a registered
+//! hardness use [Hardness::DEFAULT], except for [VoxelId::AIR], which can't be broken.
+
+use std::collections::HashMap;
+
+use super::id::VoxelId;
+
+/// The tier of a tool, or the tier a voxel requires. Higher tiers can mine everything lower tiers can.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MiningTier(pub u8);
+
+impl MiningTier {
+    /// No tool at all.
+    pub const HAND: Self = Self(0);
+    pub const COPPER: Self = Self(1);
+    pub const IRON: Self = Self(2);
+    pub const STEEL: Self = Self(3);
+
+    #[inline]
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Whether a tool of this tier can harvest a voxel that requires `required`.
+    #[inline]
+    #[must_use]
+    pub const fn reaches(self, required: MiningTier) -> bool {
+        self.0 >= required.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hardness {
+    /// Ticks to break the voxel by hand. `u32::MAX` is unbreakable.
+    pub ticks: u32,
+    /// The tier needed to break the voxel at tool speed and harvest it.
+    pub tier: MiningTier,
+}
+
+impl Hardness {
+    pub const DEFAULT: Self = Self::new(20, MiningTier::HAND);
+    pub const UNBREAKABLE: Self = Self::new(u32::MAX, MiningTier(u8::MAX));
+
+    #[inline]
+    #[must_use]
+    pub const fn new(ticks: u32, tier: MiningTier) -> Self {
+        Self { ticks, tier }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_unbreakable(self) -> bool {
+        self.ticks == u32::MAX
+    }
+}
+
+/// The hardness of every voxel type.
+#[derive(Debug, Default, Clone)]
+pub struct HardnessRegistry {
+    voxels: HashMap<VoxelId, Hardness>,
+}
+
+impl HardnessRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the hardness of voxels with `voxel`'s id.
+    #[inline]
+    pub fn assign(&mut self, voxel: VoxelId, hardness: Hardness) {
+        self.voxels.insert(voxel, hardness);
+    }
+
+    #[inline]
+    pub fn get(&self, voxel: VoxelId) -> Hardness {
+        match self.voxels.get(&voxel) {
+            Some(&hardness) => hardness,
+            None if voxel == VoxelId::AIR => Hardness::UNBREAKABLE,
+            None => Hardness::DEFAULT,
+        }
+    }
+}
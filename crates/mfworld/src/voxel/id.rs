@@ -1,4 +1,26 @@
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
 
 #[repr(transparent)]
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct VoxelId(u32);
\ No newline at end of file
+pub struct VoxelId(u32);
+
+impl VoxelId {
+    pub const AIR: Self = Self(0);
+    
+    #[inline(always)]
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+    
+    #[inline(always)]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl DeterministicHash for VoxelId {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_u32(self.0);
+    }
+}
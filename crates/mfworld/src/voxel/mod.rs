@@ -1,2 +1,4 @@
+pub mod hardness;
 pub mod id;
+pub mod shape;
 pub mod voxel;
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Collision shapes of voxels.
+//!
+//! A [Shape] is a list of boxes in voxel-local coordinates (`0.0..=1.0` on each axis) describing an
+//! unrotated voxel. Partial blocks (slabs, pipes, machines with protrusions) register their shape in
+//! the [ShapeRegistry] and map their [VoxelId] to it. Shapes are rotated by a voxel's [Orientation].
+
+use std::collections::HashMap;
+
+use mfgeometry::Orientation;
+
+use super::id::VoxelId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeBox {
+    pub min: (f64, f64, f64),
+    pub max: (f64, f64, f64),
+}
+
+impl ShapeBox {
+    pub const FULL: Self = Self::new((0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+
+    #[inline]
+    pub const fn new(min: (f64, f64, f64), max: (f64, f64, f64)) -> Self {
+        Self { min, max }
+    }
+
+    /// Rotates the box around the center of the voxel.
+    pub fn oriented(self, orientation: Orientation) -> Self {
+        let transform = |(x, y, z): (f64, f64, f64)| {
+            let (x, y, z) = orientation.transform((x - 0.5, y - 0.5, z - 0.5));
+            (x + 0.5, y + 0.5, z + 0.5)
+        };
+        let a = transform(self.min);
+        let b = transform(self.max);
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    /// The elements of the shape. Raycasts report which element was hit by its index.
+    pub boxes: Vec<ShapeBox>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShapeId(u32);
+
+impl ShapeId {
+    /// Nothing to collide with.
+    pub const EMPTY: Self = Self(0);
+    pub const FULL_CUBE: Self = Self(1);
+
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// The shapes of every voxel type. Voxels without a registered shape are full cubes,
+/// except for [VoxelId::AIR], which is empty.
+#[derive(Debug, Clone)]
+pub struct ShapeRegistry {
+    shapes: Vec<Shape>,
+    voxels: HashMap<VoxelId, ShapeId>,
+}
+
+impl Default for ShapeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShapeRegistry {
+    pub fn new() -> Self {
+        Self {
+            shapes: vec![
+                Shape { boxes: Vec::new() },
+                Shape { boxes: vec![ShapeBox::FULL] },
+            ],
+            voxels: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, shape: Shape) -> ShapeId {
+        let id = ShapeId(self.shapes.len() as u32);
+        self.shapes.push(shape);
+        id
+    }
+
+    /// Sets the shape of voxels with `voxel`'s id.
+    #[inline]
+    pub fn assign(&mut self, voxel: VoxelId, shape: ShapeId) {
+        assert!((shape.0 as usize) < self.shapes.len(), "Unregistered shape: {shape:?}");
+        self.voxels.insert(voxel, shape);
+    }
+
+    #[inline]
+    pub fn shape_id(&self, voxel: VoxelId) -> ShapeId {
+        match self.voxels.get(&voxel) {
+            Some(&shape) => shape,
+            None if voxel == VoxelId::AIR => ShapeId::EMPTY,
+            None => ShapeId::FULL_CUBE,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, shape: ShapeId) -> &Shape {
+        &self.shapes[shape.0 as usize]
+    }
+}
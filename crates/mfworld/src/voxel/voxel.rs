@@ -1,3 +1,5 @@
+use mfgeometry::Orientation;
+
 use super::id::VoxelId;
 use crate::geometry::Face;
 
@@ -100,6 +102,20 @@ impl VoxelEgress {
         let egress = self.get_egress(face);
         self.set_egress(face, egress.with_exitable(exitable));
     }
+
+    /// Moves the egress of each face along with a voxel that is reoriented from `before` to `after`.
+    pub const fn reoriented(mut self, before: Orientation, after: Orientation) -> Self {
+        const FACES: [Face; 6] = [Face::PosX, Face::PosY, Face::PosZ, Face::NegX, Face::NegY, Face::NegZ];
+        let mut result = Self::CLOSED;
+        let mut i = 0;
+        while i < FACES.len() {
+            let local = before.source_face(FACES[i].direction());
+            let face = Face::from_direction(after.reface(local));
+            result.set_egress(face, self.get_egress(FACES[i]));
+            i += 1;
+        }
+        result
+    }
 }
 
 // 64 bytes max
@@ -112,6 +128,8 @@ pub struct Voxel {
 
 #[cfg(test)]
 mod tests {
+    use mfgeometry::Direction;
+
     use super::*;
     
     #[test]
@@ -120,5 +138,13 @@ mod tests {
         egress.set_enterable(Face::PosY, false);
         let expected = Egress::new(false, true);
         assert_eq!(egress.get_egress(Face::PosY), expected);
+
+        // A quarter turn around Y moves the top along unchanged and the right side to the front.
+        egress.set_egress(Face::PosX, Egress::new(true, false));
+        let turned = Orientation::UNORIENTED.rotate_y(1);
+        let mut rotated = egress.reoriented(Orientation::UNORIENTED, turned);
+        assert_eq!(rotated.get_egress(Face::PosY), expected);
+        assert_eq!(rotated.get_egress(Face::from_direction(turned.reface(Direction::PosX))), Egress::new(true, false));
+        assert_eq!(rotated.reoriented(turned, Orientation::UNORIENTED), egress);
     }
 }
\ No newline at end of file
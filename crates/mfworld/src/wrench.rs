@@ -0,0 +1,195 @@
+//! Turning voxels in place (the wrench).
+//!
+//! Players and machines both rotate voxels through [Wrench::rotate_block], so that every tool turns
+//! voxels the same way. Each voxel type has a [Symmetry]: orientations that look the same are
+//! skipped, so every use of the wrench visibly changes the voxel, and the stored orientation is
+//! always the canonical one for its class.
+
+use std::collections::HashMap;
+
+use mfgeometry::{polarity::Pol, Direction, Orientation, Rotation};
+
+use crate::{
+    chunk::BlockPos,
+    history::{VoxelState, VoxelWorld},
+    invalidation::InvalidationTracker,
+    voxel::id::VoxelId,
+};
+
+/// Which orientations of a voxel type look the same.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    /// Every orientation is distinct (machines, stairs).
+    #[default]
+    None,
+    /// Only the direction of the top matters (torches, pipe ends).
+    Facing,
+    /// Only the axis of the top matters (logs, pillars).
+    Axis,
+    /// Every orientation is the same (stone). These voxels can't be turned.
+    Full,
+}
+
+impl Symmetry {
+    /// The orientation that represents every orientation that looks like `orientation`.
+    #[inline]
+    #[must_use]
+    pub const fn canonical(self, orientation: Orientation) -> Orientation {
+        match self {
+            Symmetry::None => orientation.canonicalize(),
+            Symmetry::Facing => Rotation::from_up(orientation.up()).orientation(),
+            Symmetry::Axis => Rotation::from_up(Direction::from_polar_axis(Pol::Pos, orientation.up().axis())).orientation(),
+            Symmetry::Full => Orientation::UNORIENTED,
+        }
+    }
+}
+
+/// How [Wrench::rotate_block] turns a voxel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RotateMode {
+    /// A quarter turn counter-clockwise around the clicked face.
+    #[default]
+    Turn,
+    /// A quarter turn clockwise around the clicked face.
+    TurnBack,
+    /// The next of the 24 rotations, regardless of the clicked face. Flips are kept.
+    Cycle,
+}
+
+impl RotateMode {
+    #[inline]
+    #[must_use]
+    pub const fn step(self, orientation: Orientation, face: Direction) -> Orientation {
+        match self {
+            RotateMode::Turn => orientation.rotate_face(face, 1),
+            RotateMode::TurnBack => orientation.rotate_face(face, -1),
+            RotateMode::Cycle => orientation.cycle_rotation(1),
+        }
+    }
+}
+
+/// The [Symmetry] of each voxel type. Voxels without a registered symmetry are [Symmetry::None],
+/// except for [VoxelId::AIR], which can't be turned.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Wrench {
+    symmetries: HashMap<VoxelId, Symmetry>,
+}
+
+impl Wrench {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn set_symmetry(&mut self, id: VoxelId, symmetry: Symmetry) {
+        self.symmetries.insert(id, symmetry);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn symmetry(&self, id: VoxelId) -> Symmetry {
+        if id == VoxelId::AIR {
+            return Symmetry::Full;
+        }
+        self.symmetries.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Turns the voxel at `position`, which was clicked on `face`, to its next distinct orientation.
+    /// Reports the change to `world` (see [VoxelWorld::voxel_rotated]) and `tracker`.
+    ///
+    /// Returns the new orientation (unchanged if the voxel can't be turned), or `None` if the
+    /// voxel's chunk isn't loaded or the voxel is air.
+    pub fn rotate_block<W: VoxelWorld>(
+        &self,
+        position: BlockPos,
+        face: Direction,
+        mode: RotateMode,
+        world: &mut W,
+        tracker: &mut InvalidationTracker,
+    ) -> Option<Orientation> {
+        let before = world.voxel(position)?;
+        if before.id == VoxelId::AIR {
+            return None;
+        }
+        let symmetry = self.symmetry(before.id);
+        let current = symmetry.canonical(before.orientation);
+        let mut next = before.orientation;
+        // Any orientation is reached within 24 steps of any of the modes, if it's reachable at all.
+        for _ in 0..24 {
+            next = mode.step(next, face);
+            let canonical = symmetry.canonical(next);
+            if canonical != current {
+                world.set_voxel(position, VoxelState::new(before.id, canonical));
+                world.voxel_rotated(position, before.orientation, canonical);
+                tracker.voxel_changed(position);
+                return Some(canonical);
+            }
+        }
+        Some(before.orientation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{chunk::ChunkPos, invalidation::Artifact};
+
+    #[derive(Default)]
+    struct TestWorld {
+        voxels: HashMap<BlockPos, VoxelState>,
+        rotations: Vec<(Orientation, Orientation)>,
+    }
+
+    impl VoxelWorld for TestWorld {
+        fn voxel(&self, position: BlockPos) -> Option<VoxelState> {
+            Some(self.voxels.get(&position).copied().unwrap_or(VoxelState::AIR))
+        }
+
+        fn set_voxel(&mut self, position: BlockPos, state: VoxelState) {
+            self.voxels.insert(position, state);
+        }
+
+        fn voxel_rotated(&mut self, _: BlockPos, before: Orientation, after: Orientation) {
+            self.rotations.push((before, after));
+        }
+    }
+
+    #[test]
+    fn rotate_block_test() {
+        const MACHINE: VoxelId = VoxelId::new(1);
+        const LOG: VoxelId = VoxelId::new(2);
+        const STONE: VoxelId = VoxelId::new(3);
+        let mut wrench = Wrench::new();
+        wrench.set_symmetry(LOG, Symmetry::Axis);
+        wrench.set_symmetry(STONE, Symmetry::Full);
+        let mut world = TestWorld::default();
+        let mut tracker = InvalidationTracker::default();
+        for (x, id) in [MACHINE, LOG, STONE].into_iter().enumerate() {
+            world.set_voxel(BlockPos::new(x as i64, 0, 0), VoxelState::new(id, Orientation::UNORIENTED));
+        }
+
+        // Turning the machine around its top four times brings it back around.
+        let mut orientations = Vec::new();
+        for _ in 0..4 {
+            orientations.push(wrench.rotate_block(BlockPos::new(0, 0, 0), Direction::PosY, RotateMode::Turn, &mut world, &mut tracker).unwrap());
+        }
+        assert_eq!(orientations[0].up(), Direction::PosY);
+        assert_ne!(orientations[0], Orientation::UNORIENTED);
+        assert_eq!(orientations[3], Orientation::UNORIENTED);
+        assert_eq!(wrench.rotate_block(BlockPos::new(0, 0, 0), Direction::PosY, RotateMode::TurnBack, &mut world, &mut tracker), Some(orientations[2]));
+        assert!(tracker.is_stale(ChunkPos::ORIGIN, Artifact::Mesh));
+        assert_eq!(world.rotations.len(), 5);
+
+        // A log turned around its own axis looks the same, so it's tipped over instead.
+        let log = wrench.rotate_block(BlockPos::new(1, 0, 0), Direction::PosY, RotateMode::Cycle, &mut world, &mut tracker).unwrap();
+        assert_ne!(log.up().axis(), Direction::PosY.axis());
+        assert_eq!(Symmetry::Axis.canonical(log), log);
+
+        assert_eq!(wrench.rotate_block(BlockPos::new(2, 0, 0), Direction::PosY, RotateMode::Turn, &mut world, &mut tracker), Some(Orientation::UNORIENTED));
+        assert_eq!(wrench.rotate_block(BlockPos::new(5, 0, 0), Direction::PosY, RotateMode::Turn, &mut world, &mut tracker), None);
+        assert_eq!(world.rotations.len(), 6);
+    }
+}
@@ -0,0 +1,258 @@
+//! The tick clock, and running the game faster or slower than real time.
+//!
+//! The simulation only ever advances in fixed ticks of [TICK_DURATION]. A [GameClock] turns the
+//! real time that passed each frame into a number of ticks to run: at [GameSpeed::Double] it runs
+//! two ticks for every tick's worth of real time, and while [paused](GameSpeed::Paused) it runs
+//! none. Nothing is ever scaled by the frame time, and every scheduler (machines, block entities,
+//! VMs, events) is handed the tick numbers from the clock, so a run at any speed goes through the
+//! same ticks, in the same states, as a run in real time.
+
+use std::{fmt, ops::Range, str::FromStr, time::Duration};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+pub const TICKS_PER_SECOND: u32 = 20;
+/// The real time a tick takes at [GameSpeed::Normal].
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GameSpeed {
+    Paused,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl GameSpeed {
+    pub const ALL: [GameSpeed; 4] = [GameSpeed::Paused, GameSpeed::Normal, GameSpeed::Double, GameSpeed::Quadruple];
+
+    /// The ticks run for each tick's worth of real time.
+    #[inline]
+    #[must_use]
+    pub const fn multiplier(self) -> u32 {
+        match self {
+            GameSpeed::Paused => 0,
+            GameSpeed::Normal => 1,
+            GameSpeed::Double => 2,
+            GameSpeed::Quadruple => 4,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            GameSpeed::Paused => "paused",
+            GameSpeed::Normal => "1x",
+            GameSpeed::Double => "2x",
+            GameSpeed::Quadruple => "4x",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => GameSpeed::Paused,
+            1 => GameSpeed::Normal,
+            2 => GameSpeed::Double,
+            3 => GameSpeed::Quadruple,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for GameSpeed {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Unknown game speed `{0}` (expected paused, 1x, 2x or 4x).")]
+pub struct ParseSpeedError(pub String);
+
+impl FromStr for GameSpeed {
+    type Err = ParseSpeedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|speed| speed.name() == s).ok_or_else(|| ParseSpeedError(s.to_owned()))
+    }
+}
+
+/// Counts ticks and decides how many to run each frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameClock {
+    tick: u64,
+    speed: GameSpeed,
+    /// Real time that hasn't been turned into ticks yet.
+    behind: Duration,
+    /// The most ticks a frame may run. Time beyond that is dropped, so that a slow machine falls
+    /// behind real time instead of running longer and longer frames.
+    max_ticks_per_frame: u32,
+}
+
+impl Default for GameClock {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl GameClock {
+    pub const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 16;
+
+    /// A clock at normal speed whose next tick is `tick`.
+    #[inline]
+    #[must_use]
+    pub const fn new(tick: u64) -> Self {
+        Self {
+            tick,
+            speed: GameSpeed::Normal,
+            behind: Duration::ZERO,
+            max_ticks_per_frame: Self::DEFAULT_MAX_TICKS_PER_FRAME,
+        }
+    }
+
+    /// The next tick to run.
+    #[inline]
+    #[must_use]
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn speed(&self) -> GameSpeed {
+        self.speed
+    }
+
+    /// Changes the speed. Time already waiting is kept, except when pausing.
+    #[inline]
+    pub fn set_speed(&mut self, speed: GameSpeed) {
+        self.speed = speed;
+        if speed == GameSpeed::Paused {
+            self.behind = Duration::ZERO;
+        }
+    }
+
+    #[inline]
+    pub fn set_max_ticks_per_frame(&mut self, max: u32) {
+        self.max_ticks_per_frame = max.max(1);
+    }
+
+    /// Adds the real time since the last frame and returns the ticks to run this frame.
+    pub fn frame(&mut self, elapsed: Duration) -> Range<u64> {
+        let multiplier = self.speed.multiplier();
+        if multiplier == 0 {
+            return self.tick..self.tick;
+        }
+        // Ticks are run in whole steps of `multiplier`, so each step stands for one tick of real time.
+        let max_steps = (self.max_ticks_per_frame / multiplier).max(1);
+        self.behind += elapsed;
+        let steps = (self.behind.as_nanos() / TICK_DURATION.as_nanos()).min(u128::from(max_steps)) as u32;
+        self.behind = self.behind.saturating_sub(TICK_DURATION * steps);
+        if steps == max_steps {
+            // Drop the time that couldn't be caught up with.
+            self.behind = self.behind.min(TICK_DURATION);
+        }
+        self.advance(u64::from(steps * multiplier))
+    }
+
+    /// Returns the next `count` ticks to run, whatever the speed (for stepping while paused, and
+    /// headless runs).
+    #[inline]
+    pub fn advance(&mut self, count: u64) -> Range<u64> {
+        let start = self.tick;
+        self.tick += count;
+        start..self.tick
+    }
+
+    /// Runs `tick` for each tick of this frame, returning how many ran.
+    pub fn run_frame<F: FnMut(u64)>(&mut self, elapsed: Duration, tick: F) -> u64 {
+        let ticks = self.frame(elapsed);
+        let count = ticks.end - ticks.start;
+        ticks.for_each(tick);
+        count
+    }
+}
+
+// Layout: next tick (u64), speed (u8).
+impl Encode for GameClock {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_u64(self.tick)? + encoder.write_u8(self.speed.to_u8())?)
+    }
+}
+
+impl Decode for GameClock {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut clock = Self::new(decoder.read_u64()?);
+        clock.speed = GameSpeed::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid game speed"))?;
+        Ok(clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfworld::block_entity::{BlockEntityPos, BlockEntityTicker, Priority, TickResult};
+
+    use super::*;
+
+    /// Runs a ticker for `frames` frames of `frame` each, returning the ticks it ran and when.
+    fn run(clock: &mut GameClock, frames: u32, frame: Duration) -> Vec<(u64, BlockEntityPos)> {
+        let mut ticker = BlockEntityTicker::new(1);
+        for x in 0..3 {
+            ticker.add(BlockEntityPos::containing(x, 0, 0), Priority::Low);
+        }
+        let mut ran = Vec::new();
+        for _ in 0..frames {
+            clock.run_frame(frame, |tick| {
+                ticker.tick(tick, |pos, _| {
+                    ran.push((tick, pos));
+                    TickResult::SleepFor(2)
+                });
+            });
+        }
+        ran
+    }
+
+    #[test]
+    fn game_speed_test() {
+        // 40 ticks at normal speed, in uneven frames.
+        let mut normal = GameClock::new(0);
+        let real_time = run(&mut normal, 30, Duration::from_micros(66_667));
+        assert_eq!(normal.tick(), 40);
+
+        // The same 40 ticks at 4x take a quarter of the frames, and go the same way.
+        let mut fast = GameClock::new(0);
+        fast.set_speed(GameSpeed::Quadruple);
+        let fast_run = run(&mut fast, 10, TICK_DURATION);
+        assert_eq!(fast.tick(), 40);
+        assert_eq!(fast_run, real_time);
+
+        let mut paused = GameClock::new(7);
+        paused.set_speed(GameSpeed::Paused);
+        assert_eq!(paused.frame(Duration::from_secs(1)), 7..7);
+        assert_eq!(paused.advance(1), 7..8);
+
+        // A long stall doesn't run a long frame.
+        assert_eq!(GameClock::new(0).frame(Duration::from_secs(60)).count() as u32, GameClock::DEFAULT_MAX_TICKS_PER_FRAME);
+
+        assert_eq!("2x".parse::<GameSpeed>(), Ok(GameSpeed::Double));
+        assert!("3x".parse::<GameSpeed>().is_err());
+        let mut bytes = Vec::new();
+        fast.encode(&mut bytes).unwrap();
+        assert_eq!(GameClock::decode(&mut bytes.as_slice()).unwrap(), GameClock { behind: Duration::ZERO, ..fast });
+    }
+}
@@ -2,6 +2,8 @@
 
 use std::num::NonZeroU32;
 
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
+
 /// Cheaply copyable handle for use as a key since Ids are not copyable.
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -36,6 +38,13 @@ impl Handle {
     }
 }
 
+impl DeterministicHash for Handle {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_u32(self.value());
+    }
+}
+
 macro_rules! handle_types {
     ($(
         $(
@@ -48,7 +57,7 @@ macro_rules! handle_types {
                 #[$attr]
             )*
             #[repr(transparent)]
-            #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
             pub struct $type_name {
                 pub(crate) handle: Handle,
             }
@@ -87,6 +96,13 @@ macro_rules! handle_types {
                     self.handle().inner()
                 }
             }
+
+            impl DeterministicHash for $type_name {
+                #[inline]
+                fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+                    self.handle.deterministic_hash(hasher);
+                }
+            }
         )*
     };
 }
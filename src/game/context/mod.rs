@@ -1,19 +1,46 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{collections::BTreeMap, sync::Arc};
 
-use crate::game::crafting::item::ItemData;
+use mfprocgen::stage::GenContext;
+use mfworld::{
+    chunk::{stored::StoredChunk, ChunkPos},
+    voxel::{hardness::HardnessRegistry, id::VoxelId},
+};
+
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::item::ItemData,
+    machine::recipe::{MachineKind, RecipeBook},
+    world::generate::generate_chunk,
+};
 
 pub mod handles;
+pub mod plugin;
+
+use plugin::GenerationStage;
 
 /*
 The Context stores game data such as Types, Functions, Recipes, etc.
 Data within the context can be accessed via handles, which are 32 bit NonZero values.
+The Context is built from plugins by a ContextBuilder (see plugin).
 */
 
 pub(crate) struct Containers {
+    /// Sorted by id.
     pub items: Vec<ItemData>,
+    // Not registered by plugins yet.
+    #[allow(dead_code)]
     pub types: Vec<()>,
+    #[allow(dead_code)]
     pub functions: Vec<()>,
-    pub recipes: Vec<()>,
+    pub recipes: RecipeBook,
+    pub recipe_names: BTreeMap<String, RecipeId>,
+    pub machines: BTreeMap<String, MachineKind>,
+    pub voxels: BTreeMap<String, VoxelId>,
+    pub hardness: HardnessRegistry,
+    /// In load order.
+    pub stages: Vec<(String, Box<dyn GenerationStage>)>,
+    /// The names of the plugins, in load order.
+    pub plugins: Vec<String>,
 }
 
 pub(crate) struct ContextInner {
@@ -21,13 +48,75 @@ pub(crate) struct ContextInner {
     pub containers: Containers,
 }
 
+/// Shared, immutable game data. Cloning is cheap, and clones can be sent to other threads.
 #[derive(Clone)]
 pub struct Context {
-    pub(crate) inner: Rc<ContextInner>,
+    pub(crate) inner: Arc<ContextInner>,
 }
 
 impl Context {
-    // pub fn seeded(seed: u64) -> Self {
-        
-    // }
-}
\ No newline at end of file
+    #[inline]
+    pub(crate) fn from_inner(inner: ContextInner) -> Self {
+        Self { inner: Arc::new(inner) }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.inner.seed
+    }
+
+    /// The registered items, sorted by id.
+    #[inline]
+    #[must_use]
+    pub fn items(&self) -> &[ItemData] {
+        &self.inner.containers.items
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn recipes(&self) -> &RecipeBook {
+        &self.inner.containers.recipes
+    }
+
+    /// The id of the recipe registered as `name`.
+    #[inline]
+    #[must_use]
+    pub fn recipe(&self, name: &str) -> Option<RecipeId> {
+        self.inner.containers.recipe_names.get(name).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn machine(&self, name: &str) -> Option<MachineKind> {
+        self.inner.containers.machines.get(name).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn voxel(&self, name: &str) -> Option<VoxelId> {
+        self.inner.containers.voxels.get(name).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn hardness(&self) -> &HardnessRegistry {
+        &self.inner.containers.hardness
+    }
+
+    /// The names of the plugins the context was built from, in load order.
+    #[inline]
+    pub fn plugins(&self) -> impl Iterator<Item = &str> + '_ {
+        self.inner.containers.plugins.iter().map(String::as_str)
+    }
+
+    /// Generates the voxels of `chunk`: the built-in generation, then the plugins' stages in load
+    /// order.
+    pub fn generate_chunk(&self, ctx: &GenContext, chunk: ChunkPos) -> StoredChunk {
+        let mut voxels = generate_chunk(ctx, chunk);
+        for (_, stage) in &self.inner.containers.stages {
+            stage.generate(ctx, chunk, &mut voxels);
+        }
+        voxels
+    }
+}
@@ -0,0 +1,530 @@
+//! Registering game content from plugins.
+//!
+//! A [Plugin] adds items, voxels, machines, recipes and generation stages to the [Context] that a
+//! [ContextBuilder] builds. Plugins are statically linked for now (there's no dynamic loading), but
+//! they only reach the game through this API, so mods can later be loaded the same way.
+//!
+//! Content is registered in [LoadPhase]s, in phase order, so that everything a phase refers to
+//! exists by then: recipes name machines and items registered in earlier phases, for example. Each
+//! plugin's [Plugin::register] is called once per phase. Within a phase plugins run after the
+//! plugins they depend on, and in name order otherwise, so the load order (and the ids handed out
+//! in it, such as recipe ids) doesn't depend on the order plugins were added in.
+//!
+//! Problems are collected rather than stopping at the first: [ContextBuilder::build] reports every
+//! conflicting registration (two plugins registering the same key), with both plugins.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use mfprocgen::stage::GenContext;
+use mfworld::{
+    chunk::{stored::StoredChunk, ChunkPos},
+    voxel::{
+        hardness::{Hardness, HardnessRegistry},
+        id::VoxelId,
+    },
+};
+
+use super::{Containers, Context, ContextInner};
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::{
+        item::{ItemData, ItemType},
+        recipe::Recipe,
+    },
+    machine::recipe::{MachineKind, RecipeBook},
+};
+
+/// A source of game content. See the [module documentation](self).
+pub trait Plugin: Send + Sync {
+    /// The unique name of the plugin, which other plugins depend on it by.
+    fn name(&self) -> &str;
+
+    /// The plugins that load before this one in every phase.
+    fn dependencies(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Registers the plugin's content for [Registrar::phase].
+    fn register(&self, registrar: &mut Registrar<'_>);
+}
+
+/// A generation stage added by a plugin. Stages run after the built-in generation, in load order,
+/// and have to be deterministic like the built-in stages (see [mfprocgen::stage]).
+pub trait GenerationStage: Send + Sync {
+    fn generate(&self, ctx: &GenContext, chunk: ChunkPos, voxels: &mut StoredChunk);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadPhase {
+    Voxels,
+    Items,
+    Machines,
+    Recipes,
+    Generation,
+}
+
+impl LoadPhase {
+    /// Every phase, in load order.
+    pub const ALL: [LoadPhase; 5] = [
+        LoadPhase::Voxels,
+        LoadPhase::Items,
+        LoadPhase::Machines,
+        LoadPhase::Recipes,
+        LoadPhase::Generation,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            LoadPhase::Voxels => "voxels",
+            LoadPhase::Items => "items",
+            LoadPhase::Machines => "machines",
+            LoadPhase::Recipes => "recipes",
+            LoadPhase::Generation => "generation",
+        }
+    }
+}
+
+impl fmt::Display for LoadPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The kinds of content, each registered in its own phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContentKind {
+    Voxel,
+    Item,
+    Machine,
+    Recipe,
+    Stage,
+}
+
+impl ContentKind {
+    /// The phase the content is registered in.
+    #[must_use]
+    pub const fn phase(self) -> LoadPhase {
+        match self {
+            ContentKind::Voxel => LoadPhase::Voxels,
+            ContentKind::Item => LoadPhase::Items,
+            ContentKind::Machine => LoadPhase::Machines,
+            ContentKind::Recipe => LoadPhase::Recipes,
+            ContentKind::Stage => LoadPhase::Generation,
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            ContentKind::Voxel => "voxel",
+            ContentKind::Item => "item",
+            ContentKind::Machine => "machine",
+            ContentKind::Recipe => "recipe",
+            ContentKind::Stage => "generation stage",
+        }
+    }
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A registration that was rejected. Voxels, machines and items are keyed both by name and by id
+/// (`#<id>`), so reusing either is a conflict.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LoadProblem {
+    #[error("{kind} {key} is registered by both {first} and {second}")]
+    Conflict { kind: ContentKind, key: String, first: String, second: String },
+    #[error("{plugin} registered {kind} {key} in the {phase} phase instead of the {} phase", kind.phase())]
+    WrongPhase { plugin: String, kind: ContentKind, key: String, phase: LoadPhase },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ContextError {
+    #[error("Plugin {0} was added twice.")]
+    DuplicatePlugin(String),
+    #[error("Plugin {plugin} depends on {dependency}, which wasn't added.")]
+    MissingDependency { plugin: String, dependency: String },
+    #[error("Plugins depend on each other in a cycle: {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+    #[error("Plugins failed to register:\n{}", .0.iter().map(|problem| format!("  {problem}")).collect::<Vec<_>>().join("\n"))]
+    Registration(Vec<LoadProblem>),
+}
+
+/// What a [Plugin] registers content through.
+pub struct Registrar<'a> {
+    phase: LoadPhase,
+    plugin: &'a str,
+    containers: &'a mut Containers,
+    /// The plugin that registered each key.
+    owners: &'a mut BTreeMap<(ContentKind, String), String>,
+    problems: &'a mut Vec<LoadProblem>,
+}
+
+impl Registrar<'_> {
+    #[inline]
+    #[must_use]
+    pub fn phase(&self) -> LoadPhase {
+        self.phase
+    }
+
+    /// The name of the plugin registering.
+    #[inline]
+    #[must_use]
+    pub fn plugin(&self) -> &str {
+        self.plugin
+    }
+
+    pub fn voxel(&mut self, name: &str, id: VoxelId, hardness: Hardness) {
+        if self.claim(ContentKind::Voxel, &[name, &format!("#{}", id.get())]) {
+            self.containers.voxels.insert(name.to_owned(), id);
+            self.containers.hardness.assign(id, hardness);
+        }
+    }
+
+    pub fn item(&mut self, item_type: ItemType) {
+        if self.claim(ContentKind::Item, &[&format!("#{}", item_type.id().get())]) {
+            let index = self.containers.items.partition_point(|item| item.id() < item_type.id());
+            self.containers.items.insert(index, ItemData { item_type });
+        }
+    }
+
+    pub fn machine(&mut self, name: &str, kind: MachineKind) {
+        if self.claim(ContentKind::Machine, &[name, &format!("#{}", kind.0)]) {
+            self.containers.machines.insert(name.to_owned(), kind);
+        }
+    }
+
+    /// Registers a recipe crafted by `machine`. Recipe ids are handed out in load order. Returns
+    /// `None` if the recipe was rejected.
+    pub fn recipe(&mut self, name: &str, machine: MachineKind, recipe: Recipe) -> Option<RecipeId> {
+        if !self.claim(ContentKind::Recipe, &[name]) {
+            return None;
+        }
+        let id = self.containers.recipes.register(machine, recipe);
+        self.containers.recipe_names.insert(name.to_owned(), id);
+        Some(id)
+    }
+
+    pub fn stage<S: GenerationStage + 'static>(&mut self, name: &str, stage: S) {
+        if self.claim(ContentKind::Stage, &[name]) {
+            self.containers.stages.push((name.to_owned(), Box::new(stage)));
+        }
+    }
+
+    /// The id of a voxel registered by this or an earlier plugin.
+    #[inline]
+    #[must_use]
+    pub fn voxel_id(&self, name: &str) -> Option<VoxelId> {
+        self.containers.voxels.get(name).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn machine_kind(&self, name: &str) -> Option<MachineKind> {
+        self.containers.machines.get(name).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn recipe_id(&self, name: &str) -> Option<RecipeId> {
+        self.containers.recipe_names.get(name).copied()
+    }
+
+    /// Claims every key for the plugin, or records why it can't.
+    fn claim(&mut self, kind: ContentKind, keys: &[&str]) -> bool {
+        if kind.phase() != self.phase {
+            self.problems.push(LoadProblem::WrongPhase {
+                plugin: self.plugin.to_owned(),
+                kind,
+                key: keys[0].to_owned(),
+                phase: self.phase,
+            });
+            return false;
+        }
+        let mut claimed = true;
+        for &key in keys {
+            if let Some(first) = self.owners.get(&(kind, key.to_owned())) {
+                self.problems.push(LoadProblem::Conflict {
+                    kind,
+                    key: key.to_owned(),
+                    first: first.clone(),
+                    second: self.plugin.to_owned(),
+                });
+                claimed = false;
+            }
+        }
+        if claimed {
+            for &key in keys {
+                self.owners.insert((kind, key.to_owned()), self.plugin.to_owned());
+            }
+        }
+        claimed
+    }
+}
+
+/// The built-in items. The game has no built-in voxels, machines or recipes yet, so this only
+/// registers in [LoadPhase::Items], and everything else comes from other plugins.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BasePlugin;
+
+impl BasePlugin {
+    pub const NAME: &str = "manufactory";
+}
+
+impl Plugin for BasePlugin {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn register(&self, registrar: &mut Registrar<'_>) {
+        if registrar.phase() == LoadPhase::Items {
+            for &item_type in ItemType::ALL {
+                registrar.item(item_type);
+            }
+        }
+    }
+}
+
+/// Builds a [Context] from plugins.
+pub struct ContextBuilder {
+    seed: u64,
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl ContextBuilder {
+    /// A builder with no plugins.
+    #[inline]
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self { seed, plugins: Vec::new() }
+    }
+
+    /// A builder with the [BasePlugin], and so the built-in items.
+    #[must_use]
+    pub fn with_base(seed: u64) -> Self {
+        Self::new(seed).plugin(BasePlugin)
+    }
+
+    #[must_use]
+    pub fn plugin<P: Plugin + 'static>(mut self, plugin: P) -> Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// The names of the plugins in load order.
+    pub fn load_order(&self) -> Result<Vec<&str>, ContextError> {
+        Ok(self.sorted()?.into_iter().map(|index| self.plugins[index].name()).collect())
+    }
+
+    /// Registers the content of every plugin, phase by phase in load order.
+    pub fn build(self) -> Result<Context, ContextError> {
+        let order = self.sorted()?;
+        let mut containers = Containers {
+            items: Vec::new(),
+            types: Vec::new(),
+            functions: Vec::new(),
+            recipes: RecipeBook::new(),
+            recipe_names: BTreeMap::new(),
+            machines: BTreeMap::new(),
+            voxels: BTreeMap::new(),
+            hardness: HardnessRegistry::new(),
+            stages: Vec::new(),
+            plugins: order.iter().map(|&index| self.plugins[index].name().to_owned()).collect(),
+        };
+        let mut owners = BTreeMap::new();
+        let mut problems = Vec::new();
+        for phase in LoadPhase::ALL {
+            for &index in &order {
+                let plugin = &self.plugins[index];
+                plugin.register(&mut Registrar {
+                    phase,
+                    plugin: plugin.name(),
+                    containers: &mut containers,
+                    owners: &mut owners,
+                    problems: &mut problems,
+                });
+            }
+        }
+        if !problems.is_empty() {
+            return Err(ContextError::Registration(problems));
+        }
+        Ok(Context::from_inner(ContextInner { seed: self.seed, containers }))
+    }
+
+    /// The indices of the plugins in load order: dependencies first, then by name.
+    fn sorted(&self) -> Result<Vec<usize>, ContextError> {
+        let mut by_name = BTreeMap::new();
+        for (index, plugin) in self.plugins.iter().enumerate() {
+            if by_name.insert(plugin.name(), index).is_some() {
+                return Err(ContextError::DuplicatePlugin(plugin.name().to_owned()));
+            }
+        }
+        let mut waiting_on = BTreeMap::new();
+        let mut dependents = BTreeMap::<&str, Vec<&str>>::new();
+        for (&name, &index) in &by_name {
+            let dependencies = self.plugins[index].dependencies().iter().copied().collect::<BTreeSet<_>>();
+            for &dependency in &dependencies {
+                if !by_name.contains_key(dependency) {
+                    return Err(ContextError::MissingDependency { plugin: name.to_owned(), dependency: dependency.to_owned() });
+                }
+                dependents.entry(dependency).or_default().push(name);
+            }
+            waiting_on.insert(name, dependencies.len());
+        }
+        let mut ready = waiting_on.iter().filter(|&(_, &count)| count == 0).map(|(&name, _)| name).collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(self.plugins.len());
+        while let Some(name) = ready.pop_first() {
+            order.push(by_name[name]);
+            waiting_on.remove(name);
+            for &dependent in dependents.get(name).into_iter().flatten() {
+                let count = waiting_on.get_mut(dependent).expect("dependents are waiting");
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+        if !waiting_on.is_empty() {
+            return Err(ContextError::DependencyCycle(waiting_on.keys().map(|&name| name.to_owned()).collect()));
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfprocgen::GeneratorConfig;
+    use mfworld::history::VoxelState;
+
+    use super::*;
+
+    struct Machines {
+        name: &'static str,
+        dependencies: &'static [&'static str],
+        id: u16,
+    }
+
+    impl Plugin for Machines {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn dependencies(&self) -> &[&str] {
+            self.dependencies
+        }
+
+        fn register(&self, registrar: &mut Registrar<'_>) {
+            match registrar.phase() {
+                LoadPhase::Voxels => registrar.voxel(&format!("{}:casing", self.name), VoxelId::new(1000 + self.id as u32), Hardness::DEFAULT),
+                LoadPhase::Machines => registrar.machine(&format!("{}:press", self.name), MachineKind(self.id)),
+                LoadPhase::Recipes => {
+                    let press = registrar.machine_kind(&format!("{}:press", self.name)).unwrap();
+                    let recipe = Recipe {
+                        inputs: vec![(ItemType::IronIngot.id(), 1)],
+                        outputs: vec![(ItemType::IronIngot.id(), 1)],
+                        requires: None,
+                    };
+                    registrar.recipe(&format!("{}:plate", self.name), press, recipe);
+                },
+                LoadPhase::Generation => {
+                    let casing = registrar.voxel_id(&format!("{}:casing", self.name)).unwrap();
+                    registrar.stage(&format!("{}:pillar", self.name), Pillar(casing));
+                },
+                LoadPhase::Items => {},
+            }
+        }
+    }
+
+    struct Pillar(VoxelId);
+
+    impl GenerationStage for Pillar {
+        fn generate(&self, _: &GenContext, _: ChunkPos, voxels: &mut StoredChunk) {
+            voxels.set(0, VoxelState::new(self.0, Orientation::UNORIENTED));
+        }
+    }
+
+    fn plugin(name: &'static str, dependencies: &'static [&'static str], id: u16) -> Machines {
+        Machines { name, dependencies, id }
+    }
+
+    #[test]
+    fn load_order_test() {
+        // Dependencies first, then names, whatever order plugins are added in.
+        let forward = ContextBuilder::with_base(7)
+            .plugin(plugin("b", &["manufactory"], 2))
+            .plugin(plugin("a", &["c"], 1))
+            .plugin(plugin("c", &[], 3));
+        let backward = ContextBuilder::new(7)
+            .plugin(plugin("c", &[], 3))
+            .plugin(plugin("a", &["c"], 1))
+            .plugin(plugin("b", &["manufactory"], 2))
+            .plugin(BasePlugin);
+        assert_eq!(forward.load_order().unwrap(), ["c", "a", "manufactory", "b"]);
+        assert_eq!(backward.load_order().unwrap(), ["c", "a", "manufactory", "b"]);
+
+        let context = forward.build().unwrap();
+        assert_eq!(context.plugins().collect::<Vec<_>>(), ["c", "a", "manufactory", "b"]);
+        assert_eq!(context.items().len(), ItemType::ALL.len());
+        assert_eq!(context.machine("a:press"), Some(MachineKind(1)));
+        assert_eq!(context.voxel("b:casing"), Some(VoxelId::new(1002)));
+        // Recipe ids follow the load order.
+        let plate = context.recipe("c:plate").unwrap();
+        assert_eq!(plate.value(), 1);
+        assert_eq!(context.recipes().machine(plate), Some(MachineKind(3)));
+
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(7, &config);
+        let chunk = context.generate_chunk(&ctx, ChunkPos::new(0, 100, 0));
+        assert_eq!(chunk.id(0), VoxelId::new(1002), "stages run in load order");
+    }
+
+    #[test]
+    fn load_error_test() {
+        let missing = ContextBuilder::new(0).plugin(plugin("a", &["z"], 1));
+        assert_eq!(missing.build().err(), Some(ContextError::MissingDependency { plugin: "a".into(), dependency: "z".into() }));
+        let cycle = ContextBuilder::new(0).plugin(plugin("a", &["b"], 1)).plugin(plugin("b", &["a"], 2)).plugin(plugin("c", &[], 3));
+        assert_eq!(cycle.build().err(), Some(ContextError::DependencyCycle(vec!["a".into(), "b".into()])));
+        let twice = ContextBuilder::with_base(0).plugin(BasePlugin);
+        assert_eq!(twice.build().err(), Some(ContextError::DuplicatePlugin("manufactory".into())));
+
+        // Every conflict is reported, naming both plugins.
+        struct Clash;
+        impl Plugin for Clash {
+            fn name(&self) -> &str {
+                "clash"
+            }
+
+            fn register(&self, registrar: &mut Registrar<'_>) {
+                match registrar.phase() {
+                    LoadPhase::Items => registrar.item(ItemType::IronOre),
+                    LoadPhase::Machines => {
+                        registrar.machine("clash:press", MachineKind(1));
+                        registrar.voxel("clash:casing", VoxelId::new(1), Hardness::DEFAULT);
+                    },
+                    _ => {},
+                }
+            }
+        }
+        let Err(ContextError::Registration(problems)) = ContextBuilder::with_base(0).plugin(plugin("a", &[], 1)).plugin(Clash).build() else {
+            panic!("expected registration problems");
+        };
+        assert_eq!(problems, [
+            LoadProblem::Conflict {
+                kind: ContentKind::Item,
+                key: format!("#{}", ItemType::IronOre.id().get()),
+                first: "clash".into(),
+                second: "manufactory".into(),
+            },
+            LoadProblem::Conflict { kind: ContentKind::Machine, key: "#1".into(), first: "a".into(), second: "clash".into() },
+            LoadProblem::WrongPhase { plugin: "clash".into(), kind: ContentKind::Voxel, key: "clash:casing".into(), phase: LoadPhase::Machines },
+        ]);
+    }
+}
@@ -0,0 +1,73 @@
+//! Matching items by what they are, what they're made of, or what shape they take.
+//!
+//! An [ItemFilter] names a single item, a resource [family](super::item::family) (every iron item),
+//! or a resource [form](super::item::form) (every ingot). Machines use filters to decide which
+//! items their slots take, and sorters use them to decide where items go.
+
+use mfcereal::{decode::Decode, encode::Encode};
+
+use super::item::ItemId;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub enum ItemFilter {
+    #[default]
+    #[cereal(tag = 0)]
+    Any,
+    #[cereal(tag = 1)]
+    Item(ItemId),
+    /// Every item of a resource family.
+    #[cereal(tag = 2)]
+    Family(u32),
+    /// Every item of a resource form.
+    #[cereal(tag = 3)]
+    Form(u32),
+}
+
+impl ItemFilter {
+    #[inline]
+    #[must_use]
+    pub const fn matches(self, item: ItemId) -> bool {
+        match self {
+            ItemFilter::Any => true,
+            ItemFilter::Item(only) => only.get() == item.get(),
+            ItemFilter::Family(family) => item.resource_family() == family,
+            ItemFilter::Form(form) => item.form() == form,
+        }
+    }
+
+    /// How narrow the filter is. When several filters match an item, the most specific one wins:
+    /// an item over a family, a family over a form, and a form over anything.
+    #[inline]
+    #[must_use]
+    pub const fn specificity(self) -> u8 {
+        match self {
+            ItemFilter::Any => 0,
+            ItemFilter::Form(_) => 1,
+            ItemFilter::Family(_) => 2,
+            ItemFilter::Item(_) => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::item::{family, form, ItemType};
+
+    #[test]
+    fn item_filter_test() {
+        let iron_ingot = ItemType::IronIngot.id();
+        assert!(ItemFilter::Family(family::IRON).matches(iron_ingot));
+        assert!(ItemFilter::Form(form::INGOT).matches(iron_ingot));
+        assert!(ItemFilter::Form(form::INGOT).matches(ItemType::CopperIngot.id()));
+        assert!(!ItemFilter::Family(family::IRON).matches(ItemType::CopperIngot.id()));
+        assert!(!ItemFilter::Item(iron_ingot).matches(ItemType::IronOre.id()));
+
+        for filter in [ItemFilter::Any, ItemFilter::Item(iron_ingot), ItemFilter::Family(family::QUARTZ), ItemFilter::Form(form::ROD)] {
+            let mut bytes = Vec::new();
+            filter.encode(&mut bytes).unwrap();
+            assert_eq!(ItemFilter::decode(&mut bytes.as_slice()).unwrap(), filter);
+        }
+        assert!(ItemFilter::decode(&mut [9u8].as_slice()).is_err());
+    }
+}
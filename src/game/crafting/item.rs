@@ -1,5 +1,8 @@
 use std::{marker::PhantomData, num::NonZeroU32};
 
+use mfcereal::{decode::Decode, encode::Encode};
+use mfhash::deterministic::{DeterministicHash, DeterministicHasher};
+
 // pub trait ItemLike {
 //     fn name() -> &'static str;
 // }
@@ -16,7 +19,7 @@ use std::{marker::PhantomData, num::NonZeroU32};
 //     }
 // }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
 pub struct ItemId(pub(crate) u32);
 
 impl ItemId {
@@ -32,6 +35,27 @@ impl ItemId {
     pub const fn get(self) -> u32 {
         self.0
     }
+
+    /// The resource family (see [family]) of the item.
+    #[inline]
+    #[must_use]
+    pub const fn resource_family(self) -> u32 {
+        (self.0 - RESOURCES_START) / RESOURCE_SECTION_SIZE
+    }
+
+    /// The form (see [form]) the item's resource takes: ore, ingot, rod, ...
+    #[inline]
+    #[must_use]
+    pub const fn form(self) -> u32 {
+        (self.0 - RESOURCES_START) % RESOURCE_SECTION_SIZE
+    }
+}
+
+impl DeterministicHash for ItemId {
+    #[inline]
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_u32(self.0);
+    }
 }
 
 macro_rules! make_item_type {
@@ -62,6 +86,13 @@ macro_rules! make_item_type {
         }
         
         impl ItemType {
+            /// Every item type, in declaration order.
+            pub const ALL: &'static [ItemType] = &[
+                $(
+                    ItemType::$variant,
+                )*
+            ];
+
             pub const fn id(self) -> ItemId {
                 ItemId(match self {
                     $(
@@ -95,6 +126,8 @@ macro_rules! res_sub {
     (Screws) => { 17 };
     (Sheet) => { 18 };
     (Plate) => { 19 };
+    // Tools start at 32
+    (Pickaxe) => { 32 };
     ($other:expr) => { $other };
 }
 
@@ -112,6 +145,34 @@ macro_rules! res_type {
     ($other:expr) => { $other };
 }
 
+/// Resource families, for matching items by what they're made of.
+pub mod family {
+    pub const IRON: u32 = res_type!(Iron);
+    pub const STEEL: u32 = res_type!(Steel);
+    pub const COPPER: u32 = res_type!(Copper);
+    pub const ALLUMINUM: u32 = res_type!(Alluminum);
+    pub const GOLD: u32 = res_type!(Gold);
+    pub const BRONZE: u32 = res_type!(Bronze);
+    pub const LEAD: u32 = res_type!(Lead);
+    pub const QUARTZ: u32 = res_type!(Quartz);
+}
+
+/// Resource forms, for matching items by shape whatever they're made of.
+pub mod form {
+    pub const ORE: u32 = res_sub!(Ore);
+    pub const INGOT_PRECURSOR: u32 = res_sub!(IngotPrecursor);
+    pub const INGOT: u32 = res_sub!(Ingot);
+    pub const CUBE: u32 = res_sub!(Cube);
+    pub const KILO_CUBE: u32 = res_sub!(KiloCube);
+    pub const MEGA_CUBE: u32 = res_sub!(MegaCube);
+    pub const GIGA_CUBE: u32 = res_sub!(GigaCube);
+    pub const ROD: u32 = res_sub!(Rod);
+    pub const SCREWS: u32 = res_sub!(Screws);
+    pub const SHEET: u32 = res_sub!(Sheet);
+    pub const PLATE: u32 = res_sub!(Plate);
+    pub const PICKAXE: u32 = res_sub!(Pickaxe);
+}
+
 const RESOURCE_SECTION_SIZE: u32 = 1024;
 const RESOURCES_START: u32 = 0;
 
@@ -174,6 +235,10 @@ make_item_type!(
             text: "Iron Plate",
             id: res_id!(Iron, Plate),
         },
+        IronPickaxe {
+            text: "Iron Pickaxe",
+            id: res_id!(Iron, Pickaxe),
+        },
         
         SteelIngot {
             text: "Steel Ingot",
@@ -211,6 +276,10 @@ make_item_type!(
             text: "Steel Plate",
             id: res_id!(Steel, Plate),
         },
+        SteelPickaxe {
+            text: "Steel Pickaxe",
+            id: res_id!(Steel, Pickaxe),
+        },
         
         CopperOre {
             text: "Copper Ore",
@@ -257,6 +326,10 @@ make_item_type!(
             text: "Copper Plate",
             id: res_id!(Copper, Plate),
         },
+        CopperPickaxe {
+            text: "Copper Pickaxe",
+            id: res_id!(Copper, Pickaxe),
+        },
         
         /// Alluminum Ore
         Bauxite {
@@ -336,6 +409,25 @@ make_item_type!(
     }
 );
 
+impl ItemType {
+    /// The ores that generate in veins, one per resource family.
+    pub const ORES: [ItemType; 4] = [ItemType::IronOre, ItemType::CopperOre, ItemType::Bauxite, ItemType::Quartz];
+
+    /// The resource family (`Iron`, `Copper`, ...) of the item. The world generator's
+    /// [vein rules](mfprocgen::veins::VeinRules) name their ores by family.
+    #[inline]
+    #[must_use]
+    pub const fn resource_family(self) -> u32 {
+        self.id().resource_family()
+    }
+
+    /// The ore of a resource family, which is what mining its veins gives.
+    #[must_use]
+    pub fn ore(family: u32) -> Option<ItemType> {
+        Self::ORES.into_iter().find(|ore| ore.resource_family() == family)
+    }
+}
+
 pub struct ItemData {
     pub(crate) item_type: ItemType,
 }
@@ -358,4 +450,21 @@ impl ItemData {
     pub const fn id(&self) -> ItemId {
         self.item_type().id()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use mfprocgen::veins::VeinRules;
+
+    use super::*;
+
+    #[test]
+    fn ore_family_test() {
+        assert_eq!(ItemType::IronOre.resource_family(), res_type!(Iron));
+        assert_eq!(ItemType::QuartzCube.resource_family(), res_type!(Quartz));
+        assert_eq!(ItemType::ore(res_type!(Alluminum)), Some(ItemType::Bauxite));
+        assert_eq!(ItemType::ore(res_type!(Steel)), None);
+        // Every vein the generator places by default mines into an ore.
+        assert!(VeinRules::default().veins.iter().all(|vein| ItemType::ore(vein.family).is_some()));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use crate::game::{crafting::item::ItemId, mode::GameMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Not enough of item {item:?}: needed {needed}, have {available}")]
+pub struct MissingMaterials {
+    pub item: ItemId,
+    pub needed: u64,
+    pub available: u64,
+}
+
+/// Counted items held by a player or machine.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Materials {
+    counts: BTreeMap<ItemId, u64>,
+}
+
+impl Materials {
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { counts: BTreeMap::new() }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn count(&self, item: ItemId) -> u64 {
+        self.counts.get(&item).copied().unwrap_or(0)
+    }
+
+    pub fn give(&mut self, item: ItemId, count: u64) {
+        if count != 0 {
+            let entry = self.counts.entry(item).or_insert(0);
+            *entry = entry.saturating_add(count);
+        }
+    }
+
+    /// Checks that every `(item, count)` in `items` is available under `mode`.
+    pub fn check(&self, items: &[(ItemId, u64)], mode: GameMode) -> Result<(), MissingMaterials> {
+        if mode.infinite_materials() {
+            return Ok(());
+        }
+        // The same item may be listed more than once.
+        let mut totals = BTreeMap::<ItemId, u64>::new();
+        for &(item, count) in items {
+            *totals.entry(item).or_insert(0) += count;
+        }
+        for (item, needed) in totals {
+            let available = self.count(item);
+            if available < needed {
+                return Err(MissingMaterials { item, needed, available });
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all of `items`, or nothing if any are missing. Nothing is removed when
+    /// `mode` has infinite materials.
+    pub fn take(&mut self, items: &[(ItemId, u64)], mode: GameMode) -> Result<(), MissingMaterials> {
+        self.check(items, mode)?;
+        if mode.infinite_materials() {
+            return Ok(());
+        }
+        for &(item, count) in items {
+            let entry = self.counts.get_mut(&item).expect("Checked above.");
+            *entry -= count;
+            if *entry == 0 {
+                self.counts.remove(&item);
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, u64)> + '_ {
+        self.counts.iter().map(|(&item, &count)| (item, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::item::ItemType;
+
+    #[test]
+    fn materials_test() {
+        let ingot = ItemType::IronIngot.id();
+        let mut materials = Materials::new();
+        materials.give(ingot, 3);
+        assert!(materials.take(&[(ingot, 2), (ingot, 2)], GameMode::Survival).is_err());
+        assert_eq!(materials.count(ingot), 3);
+        materials.take(&[(ingot, 2)], GameMode::Survival).unwrap();
+        assert_eq!(materials.count(ingot), 1);
+        materials.take(&[(ingot, 100)], GameMode::Creative).unwrap();
+        assert_eq!(materials.count(ingot), 1);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod filter;
 pub mod item;
-pub(crate) mod lockout;
\ No newline at end of file
+pub(crate) mod lockout;
+pub mod materials;
+pub mod recipe;
@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+
+use crate::game::{
+    crafting::{item::ItemId, materials::{Materials, MissingMaterials}},
+    mode::GameMode,
+};
+
+/// A step of progression that gates recipes until it is unlocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Milestone(pub u32);
+
+/// The milestones a player has unlocked.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Progression {
+    unlocked: BTreeSet<Milestone>,
+}
+
+impl Progression {
+    #[inline]
+    pub fn unlock(&mut self, milestone: Milestone) -> bool {
+        self.unlocked.insert(milestone)
+    }
+
+    /// Returns `true` if `milestone` is unlocked, or if `mode` has no progression gating.
+    #[inline]
+    #[must_use]
+    pub fn is_unlocked(&self, milestone: Milestone, mode: GameMode) -> bool {
+        !mode.progression_gating() || self.unlocked.contains(&milestone)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    pub inputs: Vec<(ItemId, u64)>,
+    pub outputs: Vec<(ItemId, u64)>,
+    /// The milestone that must be unlocked before the recipe can be used.
+    pub requires: Option<Milestone>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CraftError {
+    #[error("Recipe is locked until {0:?} is unlocked")]
+    Locked(Milestone),
+    #[error(transparent)]
+    MissingMaterials(#[from] MissingMaterials),
+}
+
+impl Recipe {
+    /// Crafts the recipe once, moving the inputs out of `materials` and the outputs into it.
+    pub fn craft(&self, materials: &mut Materials, progression: &Progression, mode: GameMode) -> Result<(), CraftError> {
+        if let Some(milestone) = self.requires
+            && !progression.is_unlocked(milestone, mode)
+        {
+            return Err(CraftError::Locked(milestone));
+        }
+        materials.take(&self.inputs, mode)?;
+        for &(item, count) in &self.outputs {
+            materials.give(item, count);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::item::ItemType;
+
+    #[test]
+    fn craft_test() {
+        let recipe = Recipe {
+            inputs: vec![(ItemType::SteelIngot.id(), 1)],
+            outputs: vec![(ItemType::SteelRod.id(), 2)],
+            requires: Some(Milestone(1)),
+        };
+        let mut progression = Progression::default();
+        let mut materials = Materials::new();
+        materials.give(ItemType::SteelIngot.id(), 1);
+        assert_eq!(recipe.craft(&mut materials, &progression, GameMode::Survival), Err(CraftError::Locked(Milestone(1))));
+        progression.unlock(Milestone(1));
+        recipe.craft(&mut materials, &progression, GameMode::Survival).unwrap();
+        assert_eq!(materials.count(ItemType::SteelRod.id()), 2);
+        assert!(matches!(recipe.craft(&mut materials, &progression, GameMode::Survival), Err(CraftError::MissingMaterials(_))));
+        // Creative ignores both the lock and the inputs.
+        recipe.craft(&mut materials, &Progression::default(), GameMode::Creative).unwrap();
+        assert_eq!(materials.count(ItemType::SteelRod.id()), 4);
+    }
+}
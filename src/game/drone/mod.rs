@@ -0,0 +1,486 @@
+//! Logistics drones: flying carriers that move items between containers.
+//!
+//! A [DeliveryTask] asks for items to be carried from a pickup container to a dropoff container
+//! (both block entities, reached from a voxel beside them). The [Dispatcher] owns every drone and
+//! task, and each tick:
+//!
+//! 1. Assigns each waiting task, in task id order, to the idle drone closest to its pickup (the
+//!    lowest drone id on ties) that can find a path there. A task is only ever reserved by one drone.
+//! 2. Moves each drone, in drone id order, one voxel along its path, using [ENERGY_PER_MOVE].
+//!    Drones without the energy wait where they are until they're charged. A drone whose next voxel
+//!    has been blocked plans a new path.
+//! 3. Drones that reached their pickup take up to a stack of the task's items, and drones that
+//!    reached their dropoff put their cargo in. Tasks for more than a stack take several trips.
+//!
+//! Everything happens in a fixed order and paths are deterministic (see [mfworld::path]), so every
+//! machine running the same ticks moves the same items. Drones save their cargo and the rest of
+//! their path, so deliveries carry on where they were after loading.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfworld::{
+    chunk::BlockPos,
+    path::{find_path, PathGoal},
+};
+
+use crate::game::{
+    events::EventBus,
+    inventory::{ContainerId, Inventories, ItemStack},
+};
+
+/// The energy a drone uses to move one voxel.
+pub const ENERGY_PER_MOVE: u32 = 1;
+/// The most voxels a path search visits before giving up.
+pub const MAX_PATH_VOXELS: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DroneId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(pub u64);
+
+/// A container drones load from or unload into, and where its block is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DronePort {
+    pub container: ContainerId,
+    pub pos: BlockPos,
+}
+
+impl DronePort {
+    #[inline]
+    #[must_use]
+    pub const fn new(container: ContainerId, pos: BlockPos) -> Self {
+        Self { container, pos }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeliveryTask {
+    pub pickup: DronePort,
+    pub dropoff: DronePort,
+    /// The items still to be picked up.
+    pub stack: ItemStack,
+    /// The drone that reserved the task.
+    drone: Option<DroneId>,
+}
+
+impl DeliveryTask {
+    #[inline]
+    #[must_use]
+    pub const fn new(pickup: DronePort, dropoff: DronePort, stack: ItemStack) -> Self {
+        Self { pickup, dropoff, stack, drone: None }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn drone(&self) -> Option<DroneId> {
+        self.drone
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum DroneState {
+    #[default]
+    Idle,
+    /// On the way to the task's pickup.
+    Fetching { task: TaskId },
+    /// Carrying `cargo` to the task's dropoff.
+    Delivering { task: TaskId, cargo: ItemStack },
+}
+
+impl DroneState {
+    #[inline]
+    #[must_use]
+    pub const fn task(&self) -> Option<TaskId> {
+        match self {
+            DroneState::Idle => None,
+            DroneState::Fetching { task } | DroneState::Delivering { task, .. } => Some(*task),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Drone {
+    pub pos: BlockPos,
+    energy: u32,
+    state: DroneState,
+    /// The voxels still to move through, in order.
+    path: Vec<BlockPos>,
+}
+
+impl Drone {
+    /// The most energy a drone holds.
+    pub const MAX_ENERGY: u32 = 10_000;
+
+    #[inline]
+    #[must_use]
+    pub const fn energy(&self) -> u32 {
+        self.energy
+    }
+
+    /// Adds energy, up to [Drone::MAX_ENERGY].
+    #[inline]
+    pub fn charge(&mut self, energy: u32) {
+        self.energy = self.energy.saturating_add(energy).min(Self::MAX_ENERGY);
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn state(&self) -> &DroneState {
+        &self.state
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &[BlockPos] {
+        &self.path
+    }
+}
+
+/// Every drone and delivery task.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Dispatcher {
+    drones: BTreeMap<DroneId, Drone>,
+    tasks: BTreeMap<TaskId, DeliveryTask>,
+    next_drone: u32,
+    next_task: u64,
+}
+
+impl Dispatcher {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, pos: BlockPos, energy: u32) -> DroneId {
+        let id = DroneId(self.next_drone);
+        self.next_drone += 1;
+        let mut drone = Drone { pos, energy: 0, state: DroneState::Idle, path: Vec::new() };
+        drone.charge(energy);
+        self.drones.insert(id, drone);
+        id
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn drone(&self, id: DroneId) -> Option<&Drone> {
+        self.drones.get(&id)
+    }
+
+    #[inline]
+    pub fn drone_mut(&mut self, id: DroneId) -> Option<&mut Drone> {
+        self.drones.get_mut(&id)
+    }
+
+    /// The drones, in id order.
+    #[inline]
+    pub fn drones(&self) -> impl Iterator<Item = (DroneId, &Drone)> + '_ {
+        self.drones.iter().map(|(&id, drone)| (id, drone))
+    }
+
+    pub fn submit(&mut self, task: DeliveryTask) -> TaskId {
+        let id = TaskId(self.next_task);
+        self.next_task += 1;
+        self.tasks.insert(id, DeliveryTask { drone: None, ..task });
+        id
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn task(&self, id: TaskId) -> Option<&DeliveryTask> {
+        self.tasks.get(&id)
+    }
+
+    /// Runs a tick (see the [module docs](self)). `passable` says which voxels drones can fly
+    /// through. Returns the tasks that were finished.
+    pub fn tick<F: FnMut(BlockPos) -> bool>(
+        &mut self,
+        inventories: &mut Inventories,
+        events: &mut EventBus,
+        mut passable: F,
+    ) -> Vec<TaskId> {
+        self.assign(&mut passable);
+        let mut finished = Vec::new();
+        let ids = self.drones.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let drone = &self.drones[&id];
+            let Some(task) = drone.state.task() else {
+                continue;
+            };
+            let goal = self.goal(&drone.state);
+            if goal.is_reached(drone.pos) {
+                if self.arrive(id, task, inventories, events) {
+                    finished.push(task);
+                }
+            } else {
+                self.advance(id, goal, &mut passable);
+            }
+        }
+        finished
+    }
+
+    fn goal(&self, state: &DroneState) -> PathGoal {
+        match *state {
+            DroneState::Idle => unreachable!("idle drones have no goal"),
+            DroneState::Fetching { task } => PathGoal::Beside(self.tasks[&task].pickup.pos),
+            DroneState::Delivering { task, .. } => PathGoal::Beside(self.tasks[&task].dropoff.pos),
+        }
+    }
+
+    fn assign<F: FnMut(BlockPos) -> bool>(&mut self, passable: &mut F) {
+        let waiting = self.tasks.iter()
+            .filter(|(_, task)| task.drone.is_none())
+            .map(|(&id, task)| (id, task.pickup.pos))
+            .collect::<Vec<_>>();
+        for (task, pickup) in waiting {
+            let mut candidates = self.drones.iter()
+                .filter(|(_, drone)| drone.state == DroneState::Idle && drone.energy >= ENERGY_PER_MOVE)
+                .map(|(&id, drone)| (drone.pos.manhattan_distance(pickup), id))
+                .collect::<Vec<_>>();
+            candidates.sort();
+            for (_, id) in candidates {
+                let drone = self.drones.get_mut(&id).expect("candidate exists");
+                if let Ok(path) = find_path(drone.pos, PathGoal::Beside(pickup), &mut *passable, MAX_PATH_VOXELS) {
+                    drone.path = path;
+                    drone.state = DroneState::Fetching { task };
+                    self.tasks.get_mut(&task).expect("waiting task exists").drone = Some(id);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Moves a drone one voxel towards `goal`, planning a new path if it has none or it's blocked.
+    fn advance<F: FnMut(BlockPos) -> bool>(&mut self, id: DroneId, goal: PathGoal, passable: &mut F) {
+        let drone = self.drones.get_mut(&id).expect("drone exists");
+        if drone.energy < ENERGY_PER_MOVE {
+            return;
+        }
+        if drone.path.first().is_none_or(|&next| !passable(next)) {
+            match find_path(drone.pos, goal, &mut *passable, MAX_PATH_VOXELS) {
+                Ok(path) => drone.path = path,
+                Err(_) => {
+                    drone.path.clear();
+                    // A drone that can't reach its pickup gives the task back. One carrying cargo
+                    // keeps it, and tries again next tick.
+                    if let DroneState::Fetching { task } = drone.state {
+                        drone.state = DroneState::Idle;
+                        self.tasks.get_mut(&task).expect("fetched task exists").drone = None;
+                    }
+                    return;
+                }
+            }
+        }
+        if !drone.path.is_empty() {
+            drone.pos = drone.path.remove(0);
+            drone.energy -= ENERGY_PER_MOVE;
+        }
+    }
+
+    /// Loads or unloads a drone that reached its goal. Returns whether the task was finished.
+    fn arrive(&mut self, id: DroneId, task_id: TaskId, inventories: &mut Inventories, events: &mut EventBus) -> bool {
+        let drone = self.drones.get_mut(&id).expect("drone exists");
+        let task = self.tasks.get_mut(&task_id).expect("drone's task exists");
+        match drone.state {
+            DroneState::Idle => false,
+            DroneState::Fetching { .. } => {
+                let wanted = task.stack.with_count(task.stack.count.min(task.stack.max_stack()));
+                // Containers that are gone or empty are waited on.
+                if let Ok(Some(cargo)) = inventories.extract_stack(task.pickup.container, wanted, events) {
+                    task.stack.count -= cargo.count;
+                    drone.state = DroneState::Delivering { task: task_id, cargo };
+                }
+                false
+            }
+            DroneState::Delivering { cargo, .. } => {
+                match inventories.insert_stack(task.dropoff.container, cargo, events) {
+                    Ok(None) => {}
+                    Ok(Some(left)) => {
+                        drone.state = DroneState::Delivering { task: task_id, cargo: left };
+                        return false;
+                    }
+                    Err(_) => return false,
+                }
+                drone.state = DroneState::Idle;
+                if task.stack.count == 0 {
+                    self.tasks.remove(&task_id);
+                    true
+                } else {
+                    task.drone = None;
+                    false
+                }
+            }
+        }
+    }
+}
+
+// Layout: next drone id (u32), next task id (u64),
+// drone count (u32), followed by each drone in id order as id (u32), pos, energy (u32),
+// state (u8: 0 idle, 1 fetching, 2 delivering), task (u64, unless idle), cargo (if delivering),
+// path length (u32) and the path,
+// task count (u32), followed by each task in id order as id (u64), pickup and dropoff (container
+// (u32), pos), stack, and whether it's reserved (u8) and by which drone (u32).
+impl Encode for Dispatcher {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.next_drone)? + encoder.write_u64(self.next_task)?;
+        written += encoder.write_u32(self.drones.len() as u32)?;
+        for (id, drone) in &self.drones {
+            written += encoder.write_u32(id.0)? + drone.pos.encode(encoder)? + encoder.write_u32(drone.energy)?;
+            written += match &drone.state {
+                DroneState::Idle => encoder.write_u8(0)?,
+                DroneState::Fetching { task } => encoder.write_u8(1)? + encoder.write_u64(task.0)?,
+                DroneState::Delivering { task, cargo } => encoder.write_u8(2)? + encoder.write_u64(task.0)? + cargo.encode(encoder)?,
+            };
+            written += encoder.write_u32(drone.path.len() as u32)?;
+            for &pos in &drone.path {
+                written += pos.encode(encoder)?;
+            }
+        }
+        written += encoder.write_u32(self.tasks.len() as u32)?;
+        for (id, task) in &self.tasks {
+            written += encoder.write_u64(id.0)?;
+            for port in [task.pickup, task.dropoff] {
+                written += encoder.write_u32(port.container.0)? + port.pos.encode(encoder)?;
+            }
+            written += task.stack.encode(encoder)?;
+            written += match task.drone {
+                Some(drone) => encoder.write_u8(1)? + encoder.write_u32(drone.0)?,
+                None => encoder.write_u8(0)?,
+            };
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for Dispatcher {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut dispatcher = Self { next_drone: decoder.read_u32()?, next_task: decoder.read_u64()?, ..Self::new() };
+        for _ in 0..decoder.read_u32()? {
+            let id = DroneId(decoder.read_u32()?);
+            if id.0 >= dispatcher.next_drone || dispatcher.drones.last_key_value().is_some_and(|(&last, _)| last >= id) {
+                return Err(DecodeError::InvalidData("invalid drone id"));
+            }
+            let pos = BlockPos::decode(decoder)?;
+            let energy = decoder.read_u32()?;
+            if energy > Drone::MAX_ENERGY {
+                return Err(DecodeError::InvalidData("invalid drone energy"));
+            }
+            let state = match decoder.read_u8()? {
+                0 => DroneState::Idle,
+                1 => DroneState::Fetching { task: TaskId(decoder.read_u64()?) },
+                2 => DroneState::Delivering { task: TaskId(decoder.read_u64()?), cargo: ItemStack::decode(decoder)? },
+                _ => return Err(DecodeError::InvalidData("invalid drone state")),
+            };
+            let mut path = Vec::new();
+            let mut at = pos;
+            for _ in 0..decoder.read_u32()? {
+                let next = BlockPos::decode(decoder)?;
+                if at.manhattan_distance(next) != 1 {
+                    return Err(DecodeError::InvalidData("drone path isn't connected"));
+                }
+                path.push(next);
+                at = next;
+            }
+            dispatcher.drones.insert(id, Drone { pos, energy, state, path });
+        }
+        for _ in 0..decoder.read_u32()? {
+            let id = TaskId(decoder.read_u64()?);
+            if id.0 >= dispatcher.next_task || dispatcher.tasks.last_key_value().is_some_and(|(&last, _)| last >= id) {
+                return Err(DecodeError::InvalidData("invalid delivery task id"));
+            }
+            let pickup = DronePort::new(ContainerId(decoder.read_u32()?), BlockPos::decode(decoder)?);
+            let dropoff = DronePort::new(ContainerId(decoder.read_u32()?), BlockPos::decode(decoder)?);
+            let stack = ItemStack::decode(decoder)?;
+            let drone = match decoder.read_u8()? {
+                0 => None,
+                1 => Some(DroneId(decoder.read_u32()?)),
+                _ => return Err(DecodeError::InvalidData("invalid delivery task reservation")),
+            };
+            dispatcher.tasks.insert(id, DeliveryTask { pickup, dropoff, stack, drone });
+        }
+        // Each task is reserved by the drone working on it, and only that drone.
+        let reserved = dispatcher.tasks.values().filter(|task| task.drone.is_some()).count();
+        let working = dispatcher.drones.iter().filter(|&(&drone, state)| {
+            state.state.task().is_some_and(|task| dispatcher.tasks.get(&task).is_some_and(|task| task.drone == Some(drone)))
+        }).count();
+        if working != reserved || dispatcher.drones.values().filter(|drone| drone.state.task().is_some()).count() != working {
+            return Err(DecodeError::InvalidData("drone tasks don't match their reservations"));
+        }
+        Ok(dispatcher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{crafting::item::ItemType, inventory::Container};
+
+    const MINE: ContainerId = ContainerId(1);
+    const FACTORY: ContainerId = ContainerId(2);
+
+    #[test]
+    fn drone_delivery_test() {
+        let ore = ItemStack::new(ItemType::IronOre.id(), 100);
+        let mut inventories = Inventories::new();
+        let mut mine = Container::new(2);
+        mine.set(0, Some(ore.with_count(64)));
+        mine.set(1, Some(ore.with_count(36)));
+        inventories.insert(MINE, mine);
+        inventories.insert(FACTORY, Container::new(4));
+        let mut events = EventBus::new();
+
+        // The ports sit on the floor, with a wall between them that has a gap at the top.
+        let (mine_pos, factory_pos) = (BlockPos::ORIGIN, BlockPos::new(8, 0, 0));
+        let passable = |pos: BlockPos| {
+            pos != mine_pos && pos != factory_pos && (0..6).contains(&pos.y) && pos.z.abs() < 3 && (pos.x != 4 || pos.y == 5)
+        };
+        let mut dispatcher = Dispatcher::new();
+        let far = dispatcher.spawn(BlockPos::new(-6, 3, 0), 1000);
+        let near = dispatcher.spawn(BlockPos::new(1, 2, 0), 1000);
+        let task = dispatcher.submit(DeliveryTask::new(DronePort::new(MINE, mine_pos), DronePort::new(FACTORY, factory_pos), ore));
+
+        // The nearest drone reserves the task, and the other stays idle.
+        let mut finished = Vec::new();
+        finished.extend(dispatcher.tick(&mut inventories, &mut events, passable));
+        assert_eq!(dispatcher.task(task).unwrap().drone(), Some(near));
+        assert_eq!(dispatcher.drone(far).unwrap().state(), &DroneState::Idle);
+
+        // Halfway through, the dispatcher is saved and loaded.
+        for _ in 0..10 {
+            finished.extend(dispatcher.tick(&mut inventories, &mut events, passable));
+        }
+        assert!(matches!(dispatcher.drone(near).unwrap().state(), DroneState::Delivering { .. }));
+        let mut bytes = Vec::new();
+        dispatcher.encode(&mut bytes).unwrap();
+        let mut dispatcher = Dispatcher::decode(&mut bytes.as_slice()).unwrap();
+
+        for _ in 0..200 {
+            finished.extend(dispatcher.tick(&mut inventories, &mut events, passable));
+        }
+        assert_eq!(finished, [task]);
+        assert_eq!(dispatcher.task(task), None);
+        let factory = inventories.container(FACTORY).unwrap();
+        assert_eq!((factory.get(0), factory.get(1)), (Some(ore.with_count(64)), Some(ore.with_count(36))));
+        assert_eq!(inventories.container(MINE).unwrap().get(1), None);
+        let near = dispatcher.drone(near).unwrap();
+        assert!(near.energy() < 1000);
+        assert_eq!(dispatcher.drone(far).unwrap().energy(), 1000);
+
+        // A drone out of energy doesn't move.
+        let mut stuck = Dispatcher::new();
+        let drone = stuck.spawn(BlockPos::new(1, 2, 0), ENERGY_PER_MOVE);
+        stuck.submit(DeliveryTask::new(DronePort::new(MINE, mine_pos), DronePort::new(FACTORY, factory_pos), ore));
+        for _ in 0..5 {
+            stuck.tick(&mut inventories, &mut events, passable);
+        }
+        assert_eq!(stuck.drone(drone).unwrap().energy(), 0);
+        let pos = stuck.drone(drone).unwrap().pos;
+        stuck.tick(&mut inventories, &mut events, passable);
+        assert_eq!(stuck.drone(drone).unwrap().pos, pos);
+    }
+}
@@ -0,0 +1,467 @@
+//! Time of day and weather, per dimension.
+//!
+//! Weather is a state machine over [Weather]. Each dimension goes through a series of spells; when
+//! one ends, the next spell's weather and length are rolled from the world seed, the dimension and
+//! the spell's index, so a replay with the same seed goes through the same storms at the same
+//! ticks. A dimension's schedule only advances on ticks where the [WEATHER_CYCLE] rule is on, and
+//! the time of day only advances while [DAYLIGHT_CYCLE] is on.
+//!
+//! A [DimensionOverride] pins a dimension's weather or time of day (a dimension with no sky, say).
+//! The schedule keeps running underneath, so removing the override returns to it.
+//!
+//! Systems that depend on the environment read its [Effects] rather than the weather itself, so
+//! that what rain does to solar panels is decided here.
+//!
+//! The [Game](crate::game::Game) owns the environment and advances it in
+//! [Game::tick](crate::game::Game::tick). It's hashed with the game, and saved in the
+//! [SaveHeader](crate::game::save::header::SaveHeader).
+
+use std::{collections::BTreeMap, fmt};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfhash::deterministic_hash_u64;
+use mfworld::portal::DimensionId;
+
+use crate::game::rules::{GameRules, DAYLIGHT_CYCLE, WEATHER_CYCLE};
+
+/// The length of a day: 20 minutes.
+pub const DAY_TICKS: u64 = 24_000;
+/// The ticks at the start and end of the day during which daylight ramps up and down.
+pub const TWILIGHT_TICKS: u64 = 1_000;
+/// [Effects] are in thousandths of their normal value.
+pub const PERMILLE: u32 = 1_000;
+
+const CONTEXT: &str = "manufactory/weather";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Storm,
+}
+
+impl Weather {
+    pub const ALL: [Weather; 3] = [Weather::Clear, Weather::Rain, Weather::Storm];
+
+    #[inline]
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::Rain => "rain",
+            Weather::Storm => "storm",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Weather::Clear,
+            1 => Weather::Rain,
+            2 => Weather::Storm,
+            _ => return None,
+        })
+    }
+
+    /// The chance, in percent, of each weather (in [Self::ALL] order) following a spell of this one.
+    /// Storms only break out of rain, and only clear up through it.
+    #[inline]
+    #[must_use]
+    pub const fn transitions(self) -> [u32; 3] {
+        match self {
+            Weather::Clear => [40, 60, 0],
+            Weather::Rain => [60, 15, 25],
+            Weather::Storm => [0, 100, 0],
+        }
+    }
+
+    /// The shortest and longest a spell of this weather lasts, in ticks.
+    #[inline]
+    #[must_use]
+    pub const fn spell_ticks(self) -> (u64, u64) {
+        match self {
+            Weather::Clear => (12_000, 36_000),
+            Weather::Rain => (3_000, 12_000),
+            Weather::Storm => (2_000, 6_000),
+        }
+    }
+
+    /// The weather after a spell of this one, picked by `roll`.
+    #[must_use]
+    const fn next(self, roll: u64) -> Self {
+        let mut percent = (roll % 100) as u32;
+        let transitions = self.transitions();
+        let mut index = 0;
+        while percent >= transitions[index] {
+            percent -= transitions[index];
+            index += 1;
+        }
+        Self::ALL[index]
+    }
+
+    /// The length of a spell of this weather, picked by `roll`.
+    #[must_use]
+    const fn length(self, roll: u64) -> u64 {
+        let (min, max) = self.spell_ticks();
+        min + (roll >> 32) % (max - min + 1)
+    }
+}
+
+impl fmt::Display for Weather {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How the environment affects the simulation, in [PERMILLE]s of the normal value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Effects {
+    /// The output of solar generators: none at night, less under clouds.
+    pub solar_output: u32,
+    /// The rate that open fluid containers evaporate: faster in sunlight, none while it rains.
+    pub evaporation: u32,
+}
+
+impl Effects {
+    #[must_use]
+    pub const fn new(weather: Weather, daylight: u32) -> Self {
+        let (sun, evaporation) = match weather {
+            Weather::Clear => (1_000, 500 + daylight / 2),
+            Weather::Rain => (400, 0),
+            Weather::Storm => (150, 0),
+        };
+        Self { solar_output: daylight * sun / PERMILLE, evaporation }
+    }
+}
+
+/// Pins part of a dimension's environment.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DimensionOverride {
+    pub weather: Option<Weather>,
+    /// In `0..DAY_TICKS`.
+    pub time_of_day: Option<u64>,
+}
+
+/// Where a dimension is in its weather schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Schedule {
+    weather: Weather,
+    /// The index of the current spell.
+    spell: u64,
+    /// The ticks the schedule has run for.
+    elapsed: u64,
+    /// The value of `elapsed` that ends the current spell.
+    until: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    seed: u64,
+    /// Ticks of daylight cycle since the world began. The time of day is this modulo [DAY_TICKS].
+    time: u64,
+    schedules: BTreeMap<DimensionId, Schedule>,
+    overrides: BTreeMap<DimensionId, DimensionOverride>,
+}
+
+impl Environment {
+    /// The environment of a new world, with the overworld in the first spell of clear weather.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        let mut environment = Self { seed, time: 0, schedules: BTreeMap::new(), overrides: BTreeMap::new() };
+        environment.add_dimension(DimensionId::OVERWORLD);
+        environment
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts the weather schedule of `dimension`, if it hasn't been. Dimensions without a schedule
+    /// are always clear.
+    pub fn add_dimension(&mut self, dimension: DimensionId) {
+        let roll = self.roll(dimension, 0);
+        self.schedules.entry(dimension).or_insert(Schedule {
+            weather: Weather::Clear,
+            spell: 0,
+            elapsed: 0,
+            until: Weather::Clear.length(roll),
+        });
+    }
+
+    pub fn dimensions(&self) -> impl Iterator<Item = DimensionId> + '_ {
+        self.schedules.keys().copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn override_of(&self, dimension: DimensionId) -> DimensionOverride {
+        self.overrides.get(&dimension).copied().unwrap_or_default()
+    }
+
+    pub fn set_override(&mut self, dimension: DimensionId, dimension_override: DimensionOverride) {
+        debug_assert!(dimension_override.time_of_day.is_none_or(|time| time < DAY_TICKS), "Time of day is out of range.");
+        if dimension_override == DimensionOverride::default() {
+            self.overrides.remove(&dimension);
+        } else {
+            self.overrides.insert(dimension, dimension_override);
+        }
+    }
+
+    /// The weather in `dimension`, after overrides.
+    #[must_use]
+    pub fn weather(&self, dimension: DimensionId) -> Weather {
+        self.override_of(dimension).weather
+            .or_else(|| self.schedules.get(&dimension).map(|schedule| schedule.weather))
+            .unwrap_or_default()
+    }
+
+    /// The ticks until the scheduled weather in `dimension` next changes, if it has a schedule.
+    #[must_use]
+    pub fn ticks_until_change(&self, dimension: DimensionId) -> Option<u64> {
+        self.schedules.get(&dimension).map(|schedule| schedule.until - schedule.elapsed)
+    }
+
+    /// The time of day in `dimension`, in `0..DAY_TICKS`. The day starts at sunrise, and night
+    /// falls halfway through.
+    #[must_use]
+    pub fn time_of_day(&self, dimension: DimensionId) -> u64 {
+        self.override_of(dimension).time_of_day.unwrap_or(self.time % DAY_TICKS)
+    }
+
+    /// The strength of daylight in `dimension`, in [PERMILLE]s.
+    #[must_use]
+    pub fn daylight(&self, dimension: DimensionId) -> u32 {
+        daylight(self.time_of_day(dimension))
+    }
+
+    #[must_use]
+    pub fn effects(&self, dimension: DimensionId) -> Effects {
+        Effects::new(self.weather(dimension), self.daylight(dimension))
+    }
+
+    /// Runs one tick, returning every dimension whose weather changed and its new weather.
+    pub fn tick(&mut self, rules: &GameRules) -> Vec<(DimensionId, Weather)> {
+        if rules.get_bool(DAYLIGHT_CYCLE) {
+            self.time += 1;
+        }
+        if !rules.get_bool(WEATHER_CYCLE) {
+            return Vec::new();
+        }
+        let mut changes = Vec::new();
+        let dimensions = self.schedules.keys().copied().collect::<Vec<_>>();
+        for dimension in dimensions {
+            let before = self.weather(dimension);
+            let roll = {
+                let schedule = &self.schedules[&dimension];
+                self.roll(dimension, schedule.spell + 1)
+            };
+            let schedule = self.schedules.get_mut(&dimension).expect("Listed above.");
+            schedule.elapsed += 1;
+            if schedule.elapsed >= schedule.until {
+                schedule.spell += 1;
+                schedule.weather = schedule.weather.next(roll);
+                schedule.until += schedule.weather.length(roll);
+            }
+            let after = self.weather(dimension);
+            if after != before {
+                changes.push((dimension, after));
+            }
+        }
+        changes
+    }
+
+    /// The roll that picks spell `spell` of `dimension`.
+    fn roll(&self, dimension: DimensionId, spell: u64) -> u64 {
+        deterministic_hash_u64((CONTEXT, self.seed, dimension.0, spell))
+    }
+}
+
+/// The strength of daylight at `time_of_day`, in [PERMILLE]s.
+#[must_use]
+pub const fn daylight(time_of_day: u64) -> u32 {
+    let half = DAY_TICKS / 2;
+    let time = time_of_day % DAY_TICKS;
+    if time >= half {
+        return 0;
+    }
+    let edge = if time < half - time { time } else { half - time };
+    let ramp = if edge < TWILIGHT_TICKS { edge } else { TWILIGHT_TICKS };
+    (ramp * PERMILLE as u64 / TWILIGHT_TICKS) as u32
+}
+
+// Layout: seed (u64), time (u64), schedule count (u64), overrides count (u64), then
+//      each schedule: dimension (u32), weather (u8), spell (u64), elapsed (u64), until (u64)
+//      each override: dimension (u32), has weather (bool), [weather (u8)], has time (bool), [time (u64)]
+impl Encode for Environment {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u64(self.seed)?
+            + encoder.write_u64(self.time)?
+            + encoder.write_u64(self.schedules.len() as u64)?
+            + encoder.write_u64(self.overrides.len() as u64)?;
+        for (dimension, schedule) in &self.schedules {
+            written += encoder.write_u32(dimension.0)?
+                + encoder.write_u8(schedule.weather.to_u8())?
+                + encoder.write_u64(schedule.spell)?
+                + encoder.write_u64(schedule.elapsed)?
+                + encoder.write_u64(schedule.until)?;
+        }
+        for (dimension, dimension_override) in &self.overrides {
+            written += encoder.write_u32(dimension.0)?;
+            written += encoder.write_bool(dimension_override.weather.is_some())?;
+            if let Some(weather) = dimension_override.weather {
+                written += encoder.write_u8(weather.to_u8())?;
+            }
+            written += encoder.write_bool(dimension_override.time_of_day.is_some())?;
+            if let Some(time) = dimension_override.time_of_day {
+                written += encoder.write_u64(time)?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+fn decode_weather<D: Decoder>(decoder: &mut D) -> Result<Weather, DecodeError<D::Error>> {
+    let value = decoder.read_u8()?;
+    Weather::from_u8(value).ok_or(DecodeError::UnknownDiscriminant { ty: "Weather", value: value as u64 })
+}
+
+impl Decode for Environment {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let seed = decoder.read_u64()?;
+        let time = decoder.read_u64()?;
+        let schedule_count = decoder.read_u64()?;
+        let override_count = decoder.read_u64()?;
+        let mut schedules = BTreeMap::new();
+        for _ in 0..schedule_count {
+            let dimension = DimensionId(decoder.read_u32()?);
+            let schedule = Schedule {
+                weather: decode_weather(decoder)?,
+                spell: decoder.read_u64()?,
+                elapsed: decoder.read_u64()?,
+                until: decoder.read_u64()?,
+            };
+            if schedule.elapsed >= schedule.until {
+                return Err(DecodeError::InvalidData("weather spell already ended"));
+            }
+            schedules.insert(dimension, schedule);
+        }
+        let mut overrides = BTreeMap::new();
+        for _ in 0..override_count {
+            let dimension = DimensionId(decoder.read_u32()?);
+            let weather = if decoder.read_bool()? { Some(decode_weather(decoder)?) } else { None };
+            let time_of_day = if decoder.read_bool()? { Some(decoder.read_u64()?) } else { None };
+            if time_of_day.is_some_and(|time| time >= DAY_TICKS) {
+                return Err(DecodeError::InvalidData("time of day out of range"));
+            }
+            overrides.insert(dimension, DimensionOverride { weather, time_of_day });
+        }
+        Ok(Self { seed, time, schedules, overrides })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETHER: DimensionId = DimensionId(1);
+
+    /// Every weather change in the first `ticks` ticks, with the tick it happened on.
+    fn run(environment: &mut Environment, rules: &GameRules, ticks: u64) -> Vec<(u64, DimensionId, Weather)> {
+        (0..ticks)
+            .flat_map(|tick| environment.tick(rules).into_iter().map(move |(dimension, weather)| (tick, dimension, weather)))
+            .collect()
+    }
+
+    #[test]
+    fn weather_test() {
+        let rules = GameRules::new();
+        let mut environment = Environment::new(42);
+        environment.add_dimension(NETHER);
+        let changes = run(&mut environment, &rules, DAY_TICKS * 10);
+        assert!(!changes.is_empty());
+        assert!(changes.iter().any(|&(_, dimension, _)| dimension == NETHER));
+        // Storms only come from and clear up to rain.
+        for dimension in [DimensionId::OVERWORLD, NETHER] {
+            let weathers = changes.iter().filter(|change| change.1 == dimension).map(|change| change.2);
+            let mut previous = Weather::Clear;
+            for weather in weathers {
+                assert_ne!(weather, previous);
+                assert!(!matches!((previous, weather), (Weather::Clear, Weather::Storm) | (Weather::Storm, Weather::Clear)));
+                previous = weather;
+            }
+        }
+
+        // Replays reproduce the same storms; other seeds don't.
+        let mut replay = Environment::new(42);
+        replay.add_dimension(NETHER);
+        assert_eq!(run(&mut replay, &rules, DAY_TICKS * 10), changes);
+        assert_eq!(replay, environment);
+        let mut other = Environment::new(43);
+        other.add_dimension(NETHER);
+        assert_ne!(run(&mut other, &rules, DAY_TICKS * 10), changes);
+
+        // Saved mid-spell, the schedule carries on the same.
+        let mut bytes = Vec::new();
+        let Ok(_) = environment.encode(&mut bytes);
+        let mut loaded = Environment::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, environment);
+        assert_eq!(run(&mut loaded, &rules, DAY_TICKS), run(&mut environment, &rules, DAY_TICKS));
+    }
+
+    #[test]
+    fn environment_rules_test() {
+        let mut rules = GameRules::new();
+        let mut environment = Environment::new(7);
+        environment.add_dimension(NETHER);
+        environment.set_override(NETHER, DimensionOverride { weather: Some(Weather::Clear), time_of_day: Some(DAY_TICKS / 2) });
+
+        // Paused cycles freeze the time and the schedule.
+        rules.set_bool(DAYLIGHT_CYCLE, false);
+        rules.set_bool(WEATHER_CYCLE, false);
+        let until = environment.ticks_until_change(DimensionId::OVERWORLD).unwrap();
+        run(&mut environment, &rules, until + 10);
+        assert_eq!(environment.time_of_day(DimensionId::OVERWORLD), 0);
+        assert_eq!(environment.ticks_until_change(DimensionId::OVERWORLD), Some(until));
+
+        // Overrides hide the schedule, which keeps running.
+        rules.set_bool(DAYLIGHT_CYCLE, true);
+        rules.set_bool(WEATHER_CYCLE, true);
+        let changes = run(&mut environment, &rules, DAY_TICKS * 10);
+        assert!(changes.iter().all(|&(_, dimension, _)| dimension == DimensionId::OVERWORLD));
+        assert_eq!(environment.weather(NETHER), Weather::Clear);
+        assert_eq!(environment.daylight(NETHER), 0);
+        assert_eq!(environment.time_of_day(DimensionId::OVERWORLD), 0);
+        environment.set_override(NETHER, DimensionOverride::default());
+        assert_eq!(environment.override_of(NETHER), DimensionOverride::default());
+        assert_eq!(environment.weather(DimensionId(9)), Weather::Clear);
+    }
+
+    #[test]
+    fn effects_test() {
+        assert_eq!(daylight(0), 0);
+        assert_eq!(daylight(TWILIGHT_TICKS / 2), PERMILLE / 2);
+        assert_eq!(daylight(DAY_TICKS / 4), PERMILLE);
+        assert_eq!(daylight(DAY_TICKS / 2 - 1), 1);
+        assert_eq!(daylight(DAY_TICKS * 3 / 4), 0);
+
+        let noon = Effects::new(Weather::Clear, PERMILLE);
+        assert_eq!(noon, Effects { solar_output: PERMILLE, evaporation: PERMILLE });
+        assert!(Effects::new(Weather::Storm, PERMILLE).solar_output < Effects::new(Weather::Rain, PERMILLE).solar_output);
+        assert_eq!(Effects::new(Weather::Rain, PERMILLE).evaporation, 0);
+        assert_eq!(Effects::new(Weather::Clear, 0), Effects { solar_output: 0, evaporation: PERMILLE / 2 });
+    }
+}
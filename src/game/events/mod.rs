@@ -0,0 +1,486 @@
+//! An event bus that lets renderers, audio, and other observers watch the simulation without
+//! being coupled to it.
+//!
+//! Systems [emit](EventBus::emit) events during a tick. At the end of the tick, [EventBus::end_tick]
+//! hands the tick's events to every subscriber whose [EventFilter] accepts them. The bus doesn't count
+//! ticks itself: it's told which tick ended, by whatever runs the [GameClock](crate::game::clock::GameClock). Subscriber queues are
+//! bounded: when a queue is full, its oldest event is dropped.
+//!
+//! Systems that only care about voxels in a region register with the bus's
+//! [VoxelListeners] instead, which are fed every [Event::BlockPlaced] at the end of the tick.
+//! [World::set_voxel](crate::game::world::World::set_voxel) emits one for every voxel it changes.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::num::NonZeroU32;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::Orientation;
+use mfworld::{
+    chunk::ChunkPos,
+    history::VoxelState,
+    listener::VoxelListeners,
+    recovery::{Fallback, RecoveryEvent},
+    voxel::id::VoxelId,
+};
+
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::item::ItemId,
+    inventory::{ContainerId, ItemStack, SlotRef, StackData},
+    machine::recipe::RecipeMode,
+    rules::RuleId,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    BlockPlaced {
+        pos: (i32, i32, i32),
+        id: VoxelId,
+        orientation: Orientation,
+    },
+    MachineCompletedCraft {
+        machine: (i32, i32, i32),
+        recipe: RecipeId,
+    },
+    ExplosionAt {
+        pos: (i32, i32, i32),
+        power: u32,
+    },
+    SlotChanged {
+        slot: SlotRef,
+        stack: Option<ItemStack>,
+    },
+    /// A stored chunk was corrupt and was recovered (see [mfworld::recovery]).
+    ChunkRecovered {
+        chunk: ChunkPos,
+        fallback: Fallback,
+        quarantined: bool,
+    },
+    /// A [game rule](crate::game::rules) changed. `value` is in the rule's raw form.
+    GameRuleChanged {
+        rule: RuleId,
+        value: i64,
+    },
+    /// The [tool](crate::game::tool) in `slot` lost durability. It broke if `remaining` is `0`.
+    ToolWorn {
+        slot: SlotRef,
+        item: ItemId,
+        remaining: u32,
+    },
+    /// A machine's [recipe selection](crate::game::machine::recipe) changed.
+    RecipeSelected {
+        container: ContainerId,
+        recipe: Option<RecipeId>,
+        mode: RecipeMode,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    BlockPlaced,
+    MachineCompletedCraft,
+    ExplosionAt,
+    SlotChanged,
+    ChunkRecovered,
+    GameRuleChanged,
+    ToolWorn,
+    RecipeSelected,
+}
+
+impl Event {
+    #[inline]
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Event::BlockPlaced { .. } => EventKind::BlockPlaced,
+            Event::MachineCompletedCraft { .. } => EventKind::MachineCompletedCraft,
+            Event::ExplosionAt { .. } => EventKind::ExplosionAt,
+            Event::SlotChanged { .. } => EventKind::SlotChanged,
+            Event::ChunkRecovered { .. } => EventKind::ChunkRecovered,
+            Event::GameRuleChanged { .. } => EventKind::GameRuleChanged,
+            Event::ToolWorn { .. } => EventKind::ToolWorn,
+            Event::RecipeSelected { .. } => EventKind::RecipeSelected,
+        }
+    }
+}
+
+impl From<RecoveryEvent> for Event {
+    #[inline]
+    fn from(event: RecoveryEvent) -> Self {
+        Event::ChunkRecovered {
+            chunk: event.chunk,
+            fallback: event.fallback,
+            quarantined: event.quarantined,
+        }
+    }
+}
+
+/// The kinds of [Event] a subscriber wants to receive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventFilter(u8);
+
+impl EventFilter {
+    pub const ALL: Self = Self(u8::MAX);
+    pub const NONE: Self = Self(0);
+
+    #[inline]
+    pub const fn only(kind: EventKind) -> Self {
+        Self::NONE.with(kind)
+    }
+
+    #[inline]
+    pub const fn with(self, kind: EventKind) -> Self {
+        Self(self.0 | (1 << kind as u8))
+    }
+
+    #[inline]
+    pub const fn without(self, kind: EventKind) -> Self {
+        Self(self.0 & !(1 << kind as u8))
+    }
+
+    #[inline]
+    pub const fn accepts(self, kind: EventKind) -> bool {
+        self.0 & (1 << kind as u8) != 0
+    }
+}
+
+impl Default for EventFilter {
+    #[inline]
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriberId(u32);
+
+#[derive(Debug)]
+struct Subscriber {
+    filter: EventFilter,
+    capacity: usize,
+    queue: VecDeque<Event>,
+    dropped: u64,
+}
+
+/// An [Event] and the tick it was emitted on, as written to the replay stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordedEvent {
+    pub tick: u64,
+    pub event: Event,
+}
+
+#[derive(Debug, Default)]
+pub struct EventBus {
+    pending: Vec<Event>,
+    subscribers: BTreeMap<SubscriberId, Subscriber>,
+    next_id: u32,
+    recording: Option<Vec<RecordedEvent>>,
+    voxel_listeners: VoxelListeners,
+}
+
+impl EventBus {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the events accepted by `filter`, keeping at most `capacity` undrained events.
+    pub fn subscribe(&mut self, filter: EventFilter, capacity: usize) -> SubscriberId {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.insert(id, Subscriber {
+            filter,
+            capacity: capacity.max(1),
+            queue: VecDeque::new(),
+            dropped: 0,
+        });
+        id
+    }
+
+    #[inline]
+    pub fn unsubscribe(&mut self, id: SubscriberId) -> bool {
+        self.subscribers.remove(&id).is_some()
+    }
+
+    #[inline]
+    pub fn emit(&mut self, event: Event) {
+        self.pending.push(event);
+    }
+
+    /// The listeners for voxel changes in a region.
+    #[inline]
+    pub fn voxel_listeners(&mut self) -> &mut VoxelListeners {
+        &mut self.voxel_listeners
+    }
+
+    /// Delivers the events emitted during `tick` to subscribers, voxel listeners, and the recording.
+    pub fn end_tick(&mut self, tick: u64) {
+        for &event in &self.pending {
+            if let Event::BlockPlaced { pos, id, orientation } = event {
+                self.voxel_listeners.record(pos.into(), VoxelState::new(id, orientation));
+            }
+            let kind = event.kind();
+            for subscriber in self.subscribers.values_mut() {
+                if !subscriber.filter.accepts(kind) {
+                    continue;
+                }
+                if subscriber.queue.len() == subscriber.capacity {
+                    subscriber.queue.pop_front();
+                    subscriber.dropped += 1;
+                }
+                subscriber.queue.push_back(event);
+            }
+            if let Some(recording) = &mut self.recording {
+                recording.push(RecordedEvent { tick, event });
+            }
+        }
+        self.pending.clear();
+        self.voxel_listeners.end_tick();
+    }
+
+    /// Takes every event delivered to `id` so far.
+    pub fn drain(&mut self, id: SubscriberId) -> impl Iterator<Item = Event> + '_ {
+        self.subscribers.get_mut(&id).into_iter().flat_map(|subscriber| subscriber.queue.drain(..))
+    }
+
+    /// The number of events `id` lost because its queue was full.
+    #[inline]
+    pub fn dropped(&self, id: SubscriberId) -> u64 {
+        self.subscribers.get(&id).map_or(0, |subscriber| subscriber.dropped)
+    }
+
+    /// Starts recording delivered events for the replay stream. Does nothing if already recording.
+    #[inline]
+    pub fn start_recording(&mut self) {
+        self.recording.get_or_insert_with(Vec::new);
+    }
+
+    #[inline]
+    pub const fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Stops recording and returns everything recorded.
+    #[inline]
+    pub fn stop_recording(&mut self) -> Vec<RecordedEvent> {
+        self.recording.take().unwrap_or_default()
+    }
+}
+
+fn encode_pos<E: Encoder>((x, y, z): (i32, i32, i32), encoder: &mut E) -> Result<u64, E::Error> {
+    Ok(encoder.write_i32(x)? + encoder.write_i32(y)? + encoder.write_i32(z)?)
+}
+
+fn decode_pos<D: Decoder>(decoder: &mut D) -> Result<(i32, i32, i32), DecodeError<D::Error>> {
+    Ok((decoder.read_i32()?, decoder.read_i32()?, decoder.read_i32()?))
+}
+
+// Layout: tag (u8), followed by:
+//      0 (BlockPlaced)          : pos (3 * i32), id (u32), orientation (u8)
+//      1 (MachineCompletedCraft): machine (3 * i32), recipe (u32)
+//      2 (ExplosionAt)          : pos (3 * i32), power (u32)
+//      3 (SlotChanged)          : container (u32), index (u16), count (u32), item (u32) and data (only if count != 0)
+//      4 (ChunkRecovered)       : chunk (3 * i32), fallback (u8), quarantined (bool)
+//      5 (GameRuleChanged)      : rule (u16), value (i64)
+//      6 (ToolWorn)             : container (u32), index (u16), item (u32), remaining (u32)
+//      7 (RecipeSelected)       : container (u32), recipe (u32, 0 = none), mode (u8)
+impl Encode for Event {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        match *self {
+            Event::BlockPlaced { pos, id, orientation } => Ok(
+                encoder.write_u8(0)?
+                + encode_pos(pos, encoder)?
+                + encoder.write_u32(id.get())?
+                + orientation.encode(encoder)?
+            ),
+            Event::MachineCompletedCraft { machine, recipe } => Ok(
+                encoder.write_u8(1)?
+                + encode_pos(machine, encoder)?
+                + encoder.write_u32(recipe.value())?
+            ),
+            Event::ExplosionAt { pos, power } => Ok(
+                encoder.write_u8(2)?
+                + encode_pos(pos, encoder)?
+                + encoder.write_u32(power)?
+            ),
+            Event::SlotChanged { slot, stack } => {
+                let mut written = encoder.write_u8(3)?
+                    + encoder.write_u32(slot.container.0)?
+                    + encoder.write_u16(slot.index)?;
+                match stack {
+                    Some(stack) => written += encoder.write_u32(stack.count)?
+                        + encoder.write_u32(stack.item.get())?
+                        + stack.data.encode(encoder)?,
+                    None => written += encoder.write_u32(0)?,
+                }
+                Ok(written)
+            }
+            Event::ChunkRecovered { chunk, fallback, quarantined } => Ok(
+                encoder.write_u8(4)?
+                + encode_pos((chunk.x, chunk.y, chunk.z), encoder)?
+                + encoder.write_u8(fallback.to_u8())?
+                + encoder.write_bool(quarantined)?
+            ),
+            Event::GameRuleChanged { rule, value } => Ok(
+                encoder.write_u8(5)?
+                + encoder.write_u16(rule.index() as u16)?
+                + encoder.write_i64(value)?
+            ),
+            Event::ToolWorn { slot, item, remaining } => Ok(
+                encoder.write_u8(6)?
+                + encoder.write_u32(slot.container.0)?
+                + encoder.write_u16(slot.index)?
+                + encoder.write_u32(item.get())?
+                + encoder.write_u32(remaining)?
+            ),
+            Event::RecipeSelected { container, recipe, mode } => Ok(
+                encoder.write_u8(7)?
+                + encoder.write_u32(container.0)?
+                + encoder.write_u32(recipe.map_or(0, |recipe| recipe.value()))?
+                + encoder.write_u8(mode.to_u8())?
+            ),
+        }
+    }
+}
+
+impl Decode for Event {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        match decoder.read_u8()? {
+            0 => Ok(Event::BlockPlaced {
+                pos: decode_pos(decoder)?,
+                id: VoxelId::new(decoder.read_u32()?),
+                orientation: Orientation::decode(decoder)?,
+            }),
+            1 => Ok(Event::MachineCompletedCraft {
+                machine: decode_pos(decoder)?,
+                recipe: RecipeId::new(NonZeroU32::new(decoder.read_u32()?).ok_or(DecodeError::InvalidData("invalid recipe id"))?),
+            }),
+            2 => Ok(Event::ExplosionAt {
+                pos: decode_pos(decoder)?,
+                power: decoder.read_u32()?,
+            }),
+            3 => {
+                let slot = SlotRef::new(ContainerId(decoder.read_u32()?), decoder.read_u16()?);
+                let stack = match decoder.read_u32()? {
+                    0 => None,
+                    count => Some(ItemStack::new(ItemId::new(decoder.read_u32()?), count).with_data(StackData::decode(decoder)?)),
+                };
+                Ok(Event::SlotChanged { slot, stack })
+            }
+            4 => {
+                let (x, y, z) = decode_pos(decoder)?;
+                Ok(Event::ChunkRecovered {
+                    chunk: ChunkPos::new(x, y, z),
+                    fallback: Fallback::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid recovery fallback"))?,
+                    quarantined: decoder.read_bool()?,
+                })
+            }
+            5 => Ok(Event::GameRuleChanged {
+                rule: RuleId::from_index(decoder.read_u16()?),
+                value: decoder.read_i64()?,
+            }),
+            6 => Ok(Event::ToolWorn {
+                slot: SlotRef::new(ContainerId(decoder.read_u32()?), decoder.read_u16()?),
+                item: ItemId::new(decoder.read_u32()?),
+                remaining: decoder.read_u32()?,
+            }),
+            7 => Ok(Event::RecipeSelected {
+                container: ContainerId(decoder.read_u32()?),
+                recipe: NonZeroU32::new(decoder.read_u32()?).map(RecipeId::new),
+                mode: RecipeMode::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid recipe mode"))?,
+            }),
+            _ => Err(DecodeError::InvalidData("unknown event tag")),
+        }
+    }
+}
+
+// Layout: tick (u64), event.
+impl Encode for RecordedEvent {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_u64(self.tick)? + self.event.encode(encoder)?)
+    }
+}
+
+impl Decode for RecordedEvent {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self {
+            tick: decoder.read_u64()?,
+            event: Event::decode(decoder)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explosion(power: u32) -> Event {
+        Event::ExplosionAt { pos: (0, 0, 0), power }
+    }
+
+    #[test]
+    fn event_bus_test() {
+        let mut bus = EventBus::new();
+        let audio = bus.subscribe(EventFilter::only(EventKind::ExplosionAt), 2);
+        let renderer = bus.subscribe(EventFilter::ALL.without(EventKind::MachineCompletedCraft), 16);
+        bus.start_recording();
+
+        let placed = Event::BlockPlaced { pos: (1, 2, 3), id: VoxelId::new(7), orientation: Orientation::ROTATE_Y };
+        bus.emit(placed);
+        bus.emit(Event::MachineCompletedCraft { machine: (4, 5, 6), recipe: RecipeId::new(NonZeroU32::MIN) });
+        // Nothing is delivered until the tick ends.
+        assert_eq!(bus.drain(renderer).count(), 0);
+        bus.end_tick(7);
+        assert_eq!(bus.drain(renderer).collect::<Vec<_>>(), vec![placed]);
+        assert_eq!(bus.drain(audio).count(), 0);
+
+        for power in 0..3 {
+            bus.emit(explosion(power));
+        }
+        bus.end_tick(8);
+        assert_eq!(bus.drain(audio).collect::<Vec<_>>(), vec![explosion(1), explosion(2)]);
+        assert_eq!(bus.dropped(audio), 1);
+        assert_eq!(bus.drain(renderer).count(), 3);
+
+        let recording = bus.stop_recording();
+        assert_eq!(recording.len(), 5);
+        assert_eq!(recording[4], RecordedEvent { tick: 8, event: explosion(2) });
+        let mut bytes = Vec::new();
+        for event in &recording {
+            event.encode(&mut bytes).unwrap();
+        }
+        let mut input = bytes.as_slice();
+        for event in &recording {
+            assert_eq!(RecordedEvent::decode(&mut input).unwrap(), *event);
+        }
+        assert!(input.is_empty());
+
+        let recovered = Event::ChunkRecovered { chunk: ChunkPos::new(-1, 2, 3), fallback: Fallback::Empty, quarantined: true };
+        let changed = Event::GameRuleChanged { rule: RuleId::from_index(3), value: -1 };
+        let mut bytes = Vec::new();
+        recovered.encode(&mut bytes).unwrap();
+        changed.encode(&mut bytes).unwrap();
+        let mut input = bytes.as_slice();
+        assert_eq!(Event::decode(&mut input).unwrap(), recovered);
+        assert_eq!(Event::decode(&mut input).unwrap(), changed);
+    }
+
+    #[test]
+    fn voxel_listener_test() {
+        use mfworld::listener::ListenerFilter;
+
+        let mut bus = EventBus::new();
+        let near = bus.voxel_listeners().register(ListenerFilter::chunk(ChunkPos::ORIGIN), 64);
+        let placed = |x| Event::BlockPlaced { pos: (x, 0, 0), id: VoxelId::new(3), orientation: Orientation::UNORIENTED };
+        bus.emit(placed(1));
+        bus.emit(placed(100));
+        bus.emit(explosion(1));
+        assert!(bus.voxel_listeners().drain(&near).changes.is_empty());
+        bus.end_tick(0);
+        let delivery = bus.voxel_listeners().drain(&near);
+        assert_eq!(delivery.changes.len(), 1);
+        assert_eq!(delivery.changes[0].position, (1, 0, 0).into());
+    }
+}
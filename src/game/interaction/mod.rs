@@ -0,0 +1,151 @@
+use mfworld::voxel::{
+    hardness::{Hardness, MiningTier},
+    id::VoxelId,
+};
+
+use crate::game::{
+    crafting::{item::ItemId, materials::{Materials, MissingMaterials}},
+    mode::GameMode,
+    tool::ToolDef,
+};
+
+/// The number of ticks it takes to place a voxel outside of instant modes.
+pub const PLACE_TICKS: u32 = 4;
+
+/// Break speed, as a percentage of breaking by hand, when the tool's tier (or the hand) is too low
+/// for the voxel.
+pub const UNDER_TIER_SPEED: u32 = 30;
+
+/// The tier of `tool`, or of the hand if there's no tool.
+#[inline]
+#[must_use]
+const fn tier(tool: Option<ToolDef>) -> MiningTier {
+    match tool {
+        Some(tool) => tool.tier,
+        None => MiningTier::HAND,
+    }
+}
+
+/// Whether breaking a voxel with `hardness` using `tool` drops it.
+#[inline]
+#[must_use]
+pub const fn harvests(hardness: Hardness, tool: Option<ToolDef>) -> bool {
+    tier(tool).reaches(hardness.tier)
+}
+
+/// The number of ticks it takes to break a voxel with the given `hardness` using `tool` (or by hand).
+/// Unbreakable voxels take `u32::MAX` ticks, which never completes.
+///
+/// Only integer math is used, so every peer computes the same result.
+#[must_use]
+pub const fn break_ticks(hardness: Hardness, tool: Option<ToolDef>, mode: GameMode) -> u32 {
+    if hardness.is_unbreakable() {
+        return u32::MAX;
+    }
+    if mode.instant_break() {
+        return 0;
+    }
+    let speed = match tool {
+        _ if !harvests(hardness, tool) => UNDER_TIER_SPEED,
+        Some(tool) => tool.speed,
+        None => 100,
+    };
+    let ticks = (hardness.ticks as u64 * 100).div_ceil(if speed == 0 { 1 } else { speed as u64 });
+    if ticks >= u32::MAX as u64 { u32::MAX - 1 } else { ticks as u32 }
+}
+
+/// The number of ticks it takes to place a voxel.
+#[inline]
+#[must_use]
+pub const fn place_ticks(mode: GameMode) -> u32 {
+    if mode.instant_place() { 0 } else { PLACE_TICKS }
+}
+
+/// Tracks a voxel being broken over several ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakProgress {
+    pub voxel: VoxelId,
+    pub elapsed: u32,
+    pub required: u32,
+    /// Whether the voxel drops when it breaks.
+    pub harvest: bool,
+}
+
+impl BreakProgress {
+    #[inline]
+    #[must_use]
+    pub const fn new(voxel: VoxelId, hardness: Hardness, tool: Option<ToolDef>, mode: GameMode) -> Self {
+        Self {
+            voxel,
+            elapsed: 0,
+            required: break_ticks(hardness, tool, mode),
+            harvest: harvests(hardness, tool),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_done(&self) -> bool {
+        self.elapsed >= self.required
+    }
+
+    /// Advances by one tick. Returns `true` once the voxel is broken. When it is, the tool used
+    /// should be worn with [wear_tool](crate::game::tool::wear_tool).
+    #[inline]
+    pub const fn tick(&mut self) -> bool {
+        if !self.is_done() && self.required != u32::MAX {
+            self.elapsed += 1;
+        }
+        self.is_done()
+    }
+}
+
+/// Takes the item for a placement from `materials`. Nothing is consumed when `mode` has
+/// infinite materials.
+#[inline]
+pub fn consume_for_placement(materials: &mut Materials, item: ItemId, mode: GameMode) -> Result<(), MissingMaterials> {
+    materials.take(&[(item, 1)], mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::item::ItemType;
+
+    #[test]
+    fn interaction_test() {
+        let soft = Hardness::new(3, MiningTier::HAND);
+        let mut progress = BreakProgress::new(VoxelId::new(1), soft, None, GameMode::Survival);
+        assert!(!progress.tick());
+        assert!(!progress.tick());
+        assert!(progress.tick());
+        assert!(BreakProgress::new(VoxelId::new(1), soft, None, GameMode::Creative).is_done());
+        let mut bedrock = BreakProgress::new(VoxelId::new(2), Hardness::UNBREAKABLE, None, GameMode::Creative);
+        assert!(!bedrock.tick());
+        assert_eq!(bedrock.elapsed, 0);
+        assert_eq!(place_ticks(GameMode::Creative), 0);
+        assert_eq!(place_ticks(GameMode::Benchmark), PLACE_TICKS);
+
+        let mut materials = Materials::new();
+        let plate = ItemType::IronPlate.id();
+        assert!(consume_for_placement(&mut materials, plate, GameMode::Survival).is_err());
+        assert!(consume_for_placement(&mut materials, plate, GameMode::Creative).is_ok());
+    }
+
+    #[test]
+    fn break_ticks_test() {
+        let ore = Hardness::new(60, MiningTier::IRON);
+        let copper = ToolDef::of(ItemType::CopperPickaxe.id());
+        let iron = ToolDef::of(ItemType::IronPickaxe.id());
+        let steel = ToolDef::of(ItemType::SteelPickaxe.id());
+        // Under tier: slow and nothing drops, with or without a tool.
+        assert_eq!(break_ticks(ore, None, GameMode::Survival), 200);
+        assert_eq!(break_ticks(ore, copper, GameMode::Survival), 200);
+        assert!(!harvests(ore, copper));
+        assert_eq!(break_ticks(ore, iron, GameMode::Survival), 20);
+        assert_eq!(break_ticks(ore, steel, GameMode::Survival), 14);
+        assert!(harvests(ore, steel));
+        assert_eq!(break_ticks(ore, None, GameMode::Creative), 0);
+        assert_eq!(break_ticks(Hardness::new(7, MiningTier::HAND), copper, GameMode::Benchmark), 4);
+    }
+}
@@ -0,0 +1,820 @@
+//! Slot inventories and the click/drag transactions UI code performs on them.
+//!
+//! UI code never touches slots directly. It sends a [Transaction] naming abstract [SlotRef]s, and
+//! [Inventories::apply] validates it against the current state before changing anything, the
+//! same way a server would validate a client's request. A rejected transaction changes nothing.
+//! Every slot that changes is reported on the [EventBus] as [Event::SlotChanged].
+//!
+//! A machine's container also carries its [MachineEntity], and with it the machine's
+//! [RecipeSelection], which isn't kept anywhere else. Changes to it go through the same
+//! transactions and are reported as [Event::RecipeSelected].
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::{filter::ItemFilter, item::ItemId},
+    events::{Event, EventBus},
+    machine::{
+        recipe::{MachineKind, RecipeBook, RecipeMode, RecipeSelection, SelectionError},
+        MachineEntity,
+    },
+    tool::ToolDef,
+};
+
+/// The most items of one kind that fit in a slot, except for items with a lower
+/// [max stack](ItemStack::max_stack).
+pub const MAX_STACK: u32 = 64;
+
+/// A key of a value in [StackData].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataKey(pub u8);
+
+impl DataKey {
+    /// The durability a [tool](crate::game::tool) has lost.
+    pub const DAMAGE: Self = Self(1);
+}
+
+/// Per-stack state that isn't implied by the item, such as a tool's wear, stored as a few tagged
+/// values. Stacks only merge when their data is equal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StackData {
+    len: u8,
+    /// Sorted by key. Unused entries are zeroed so that equal data compares equal.
+    entries: [(DataKey, u32); StackData::CAPACITY],
+}
+
+impl StackData {
+    pub const CAPACITY: usize = 4;
+    pub const EMPTY: Self = Self { len: 0, entries: [(DataKey(0), 0); Self::CAPACITY] };
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, key: DataKey) -> Option<u32> {
+        self.iter().find(|&(entry, _)| entry == key).map(|(_, value)| value)
+    }
+
+    /// Sets the value of `key`, returning the old value.
+    ///
+    /// # Panics
+    /// Panics if `key` is new and the data already holds [StackData::CAPACITY] values.
+    pub fn insert(&mut self, key: DataKey, value: u32) -> Option<u32> {
+        let len = self.len();
+        let index = match self.entries[..len].binary_search_by_key(&key, |&(key, _)| key) {
+            Ok(index) => return Some(::core::mem::replace(&mut self.entries[index].1, value)),
+            Err(index) => index,
+        };
+        assert!(len < Self::CAPACITY, "Stack data is full.");
+        self.entries.copy_within(index..len, index + 1);
+        self.entries[index] = (key, value);
+        self.len += 1;
+        None
+    }
+
+    pub fn remove(&mut self, key: DataKey) -> Option<u32> {
+        let len = self.len();
+        let index = self.entries[..len].binary_search_by_key(&key, |&(key, _)| key).ok()?;
+        let value = self.entries[index].1;
+        self.entries.copy_within(index + 1..len, index);
+        self.entries[len - 1] = (DataKey(0), 0);
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// The values, in key order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (DataKey, u32)> + '_ {
+        self.entries[..self.len()].iter().copied()
+    }
+}
+
+// Layout: len (u8), followed by len * (key (u8), value (u32)) in key order.
+impl Encode for StackData {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u8(self.len)?;
+        for (key, value) in self.iter() {
+            written += encoder.write_u8(key.0)? + encoder.write_u32(value)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for StackData {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let len = decoder.read_u8()?;
+        if len as usize > Self::CAPACITY {
+            return Err(DecodeError::InvalidData("too many stack data values"));
+        }
+        let mut data = Self::EMPTY;
+        for index in 0..len as usize {
+            let entry = (DataKey(decoder.read_u8()?), decoder.read_u32()?);
+            if index != 0 && data.entries[index - 1].0 >= entry.0 {
+                return Err(DecodeError::InvalidData("unsorted stack data"));
+            }
+            data.entries[index] = entry;
+        }
+        data.len = len;
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+    pub data: StackData,
+}
+
+impl ItemStack {
+    #[inline]
+    #[must_use]
+    pub const fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count, data: StackData::EMPTY }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn with_data(self, data: StackData) -> Self {
+        Self { data, ..self }
+    }
+
+    /// The same item and data with a different count.
+    #[inline]
+    #[must_use]
+    pub const fn with_count(self, count: u32) -> Self {
+        Self { count, ..self }
+    }
+
+    /// Whether `other` can be merged into this stack.
+    #[inline]
+    #[must_use]
+    pub fn stacks_with(&self, other: &ItemStack) -> bool {
+        self.item == other.item && self.data == other.data
+    }
+
+    /// The most items of this kind that fit in a slot.
+    #[inline]
+    #[must_use]
+    pub const fn max_stack(&self) -> u32 {
+        if ToolDef::of(self.item).is_some() { 1 } else { MAX_STACK }
+    }
+}
+
+// Layout: item (u32), count (u32), data.
+impl Encode for ItemStack {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(encoder.write_u32(self.item.get())? + encoder.write_u32(self.count)? + self.data.encode(encoder)?)
+    }
+}
+
+impl Decode for ItemStack {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self {
+            item: ItemId::new(decoder.read_u32()?),
+            count: decoder.read_u32()?,
+            data: StackData::decode(decoder)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContainerId(pub u32);
+
+impl ContainerId {
+    pub const PLAYER: Self = Self(0);
+}
+
+/// A slot in one of the containers of [Inventories].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlotRef {
+    pub container: ContainerId,
+    pub index: u16,
+}
+
+impl SlotRef {
+    #[inline]
+    #[must_use]
+    pub const fn new(container: ContainerId, index: u16) -> Self {
+        Self { container, index }
+    }
+}
+
+/// Which items a slot accepts from the player.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlotFilter {
+    #[default]
+    Any,
+    Only(ItemId),
+    /// Items the filter matches, such as any ore for a furnace's input.
+    Matching(ItemFilter),
+    /// Items can be taken out, but never put in (for example, a machine's output slot).
+    TakeOnly,
+}
+
+impl SlotFilter {
+    #[inline]
+    #[must_use]
+    pub const fn accepts(self, item: ItemId) -> bool {
+        match self {
+            SlotFilter::Any => true,
+            SlotFilter::Only(only) => only.get() == item.get(),
+            SlotFilter::Matching(filter) => filter.matches(item),
+            SlotFilter::TakeOnly => false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Container {
+    slots: Vec<Option<ItemStack>>,
+    filters: Vec<SlotFilter>,
+    machine: Option<(MachineKind, MachineEntity)>,
+}
+
+impl Container {
+    #[must_use]
+    pub fn new(size: u16) -> Self {
+        Self {
+            slots: vec![None; size as usize],
+            filters: vec![SlotFilter::Any; size as usize],
+            machine: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_filters(filters: Vec<SlotFilter>) -> Self {
+        Self {
+            slots: vec![None; filters.len()],
+            filters,
+            machine: None,
+        }
+    }
+
+    /// A machine's container, holding the machine's `entity`. Its input slots are the slots that
+    /// aren't [SlotFilter::TakeOnly], in order, and line up with the inputs of the selected recipe.
+    #[must_use]
+    pub fn for_machine(machine: MachineKind, entity: MachineEntity, filters: Vec<SlotFilter>) -> Self {
+        Self {
+            machine: Some((machine, entity)),
+            ..Self::with_filters(filters)
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: u16) -> Option<ItemStack> {
+        self.slots.get(index as usize).copied().flatten()
+    }
+
+    /// Sets a slot directly, returning the old stack. For filling containers before they're added
+    /// to [Inventories] (generation, loading). Use [Inventories::set] afterwards.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn set(&mut self, index: u16, stack: Option<ItemStack>) -> Option<ItemStack> {
+        ::core::mem::replace(&mut self.slots[index as usize], stack.filter(|stack| stack.count != 0))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn filter(&self, index: u16) -> SlotFilter {
+        self.filters.get(index as usize).copied().unwrap_or_default()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn machine(&self) -> Option<MachineKind> {
+        match self.machine {
+            Some((machine, _)) => Some(machine),
+            None => None,
+        }
+    }
+
+    /// The entity of the machine, to be saved with its voxel.
+    #[inline]
+    #[must_use]
+    pub const fn machine_entity(&self) -> Option<&MachineEntity> {
+        match &self.machine {
+            Some((_, entity)) => Some(entity),
+            None => None,
+        }
+    }
+
+    /// The recipe selection of the [machine entity](Self::machine_entity), or
+    /// [RecipeSelection::NONE] if this isn't a machine's container.
+    #[inline]
+    #[must_use]
+    pub const fn selection(&self) -> RecipeSelection {
+        match self.machine {
+            Some((_, entity)) => entity.recipe,
+            None => RecipeSelection::NONE,
+        }
+    }
+
+    /// The slot indices of the input slots, in order.
+    pub fn input_slots(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.len() as u16).filter(|&index| self.filter(index) != SlotFilter::TakeOnly)
+    }
+}
+
+/// A click or drag performed by the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transaction {
+    /// Picks up the whole stack in the slot. The cursor must be empty.
+    PickUp(SlotRef),
+    /// Picks up the larger half of the stack in the slot. The cursor must be empty.
+    SplitHalf(SlotRef),
+    /// Puts the cursor's stack into the slot, merging with a matching stack or swapping with a different one.
+    Place(SlotRef),
+    /// Puts one item from the cursor into the slot.
+    PlaceOne(SlotRef),
+    /// Moves as much of the slot's stack as fits into `to`, filling matching stacks first.
+    QuickMove { from: SlotRef, to: ContainerId },
+    /// Selects the recipe of a machine's container, or clears it. Items already in the input slots
+    /// stay where they are.
+    SelectRecipe { container: ContainerId, recipe: Option<RecipeId> },
+    /// Sets whether a machine may switch recipes on its own. Only a selected recipe can be locked.
+    SetRecipeMode { container: ContainerId, mode: RecipeMode },
+}
+
+/// A recipe input the UI draws in a machine's input slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputGhost {
+    pub slot: SlotRef,
+    pub item: ItemId,
+    pub count: u64,
+    /// Whether the slot already holds the item.
+    pub filled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InventoryError {
+    #[error("Unknown container: {0:?}")]
+    UnknownContainer(ContainerId),
+    #[error("Slot {0:?} does not exist")]
+    NoSuchSlot(SlotRef),
+    #[error("Slot {0:?} is empty")]
+    EmptySlot(SlotRef),
+    #[error("The cursor is empty")]
+    CursorEmpty,
+    #[error("The cursor is already holding items")]
+    CursorOccupied,
+    #[error("Slot {0:?} does not accept that item")]
+    Rejected(SlotRef),
+    #[error("There is no room for the items")]
+    NoRoom,
+    #[error("Container {0:?} is not a machine")]
+    NotAMachine(ContainerId),
+    #[error(transparent)]
+    Selection(#[from] SelectionError),
+}
+
+/// Every container the player can interact with, plus the stack held by the cursor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Inventories {
+    containers: BTreeMap<ContainerId, Container>,
+    cursor: Option<ItemStack>,
+    recipes: Arc<RecipeBook>,
+}
+
+impl Inventories {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inventories whose machines select recipes from `recipes`.
+    #[inline]
+    #[must_use]
+    pub fn with_recipes(recipes: Arc<RecipeBook>) -> Self {
+        Self { recipes, ..Self::default() }
+    }
+
+    #[inline]
+    pub fn insert(&mut self, id: ContainerId, container: Container) -> Option<Container> {
+        self.containers.insert(id, container)
+    }
+
+    #[inline]
+    pub fn remove(&mut self, id: ContainerId) -> Option<Container> {
+        self.containers.remove(&id)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn container(&self, id: ContainerId) -> Option<&Container> {
+        self.containers.get(&id)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn cursor(&self) -> Option<ItemStack> {
+        self.cursor
+    }
+
+    /// Gets the stack in `slot`.
+    pub fn get(&self, slot: SlotRef) -> Result<Option<ItemStack>, InventoryError> {
+        let container = self.containers.get(&slot.container).ok_or(InventoryError::UnknownContainer(slot.container))?;
+        if slot.index as usize >= container.len() {
+            return Err(InventoryError::NoSuchSlot(slot));
+        }
+        Ok(container.get(slot.index))
+    }
+
+    /// Sets the contents of `slot` directly, bypassing its filter (for machines and loading).
+    pub fn set(&mut self, slot: SlotRef, stack: Option<ItemStack>, events: &mut EventBus) -> Result<(), InventoryError> {
+        self.get(slot)?;
+        self.write(slot, stack, events);
+        Ok(())
+    }
+
+    fn write(&mut self, slot: SlotRef, stack: Option<ItemStack>, events: &mut EventBus) {
+        let stack = stack.filter(|stack| stack.count != 0);
+        let old = &mut self.containers.get_mut(&slot.container).expect("slot was validated").slots[slot.index as usize];
+        if *old != stack {
+            *old = stack;
+            events.emit(Event::SlotChanged { slot, stack });
+        }
+    }
+
+    fn accepts(&self, slot: SlotRef, item: ItemId) -> bool {
+        let container = &self.containers[&slot.container];
+        if !container.filter(slot.index).accepts(item) {
+            return false;
+        }
+        let selection = container.selection();
+        match (selection.is_locked(), selection.recipe().and_then(|recipe| self.recipes.get(recipe))) {
+            (true, Some(recipe)) => container.input_slots()
+                .position(|index| index == slot.index)
+                .and_then(|input| recipe.inputs.get(input))
+                .is_some_and(|&(wanted, _)| wanted == item),
+            _ => true,
+        }
+    }
+
+    /// The selected recipe's inputs, one per input slot of the machine's container.
+    pub fn ghosts(&self, container: ContainerId) -> Result<Vec<InputGhost>, InventoryError> {
+        let target = self.containers.get(&container).ok_or(InventoryError::UnknownContainer(container))?;
+        let Some(recipe) = target.selection().recipe().and_then(|recipe| self.recipes.get(recipe)) else {
+            return Ok(Vec::new());
+        };
+        Ok(target.input_slots().zip(&recipe.inputs).map(|(index, &(item, count))| InputGhost {
+            slot: SlotRef::new(container, index),
+            item,
+            count,
+            filled: target.get(index).is_some_and(|stack| stack.item == item),
+        }).collect())
+    }
+
+    fn write_selection(&mut self, container: ContainerId, selection: RecipeSelection, events: &mut EventBus) {
+        let (_, entity) = self.containers.get_mut(&container).and_then(|target| target.machine.as_mut()).expect("machine was validated");
+        if entity.recipe != selection {
+            entity.recipe = selection;
+            events.emit(Event::RecipeSelected { container, recipe: selection.recipe(), mode: selection.mode() });
+        }
+    }
+
+    fn machine(&self, container: ContainerId) -> Result<(MachineKind, RecipeSelection), InventoryError> {
+        let target = self.containers.get(&container).ok_or(InventoryError::UnknownContainer(container))?;
+        let (machine, entity) = target.machine.ok_or(InventoryError::NotAMachine(container))?;
+        Ok((machine, entity.recipe))
+    }
+
+    /// Validates and performs `transaction`. Nothing changes if it fails.
+    pub fn apply(&mut self, transaction: Transaction, events: &mut EventBus) -> Result<(), InventoryError> {
+        match transaction {
+            Transaction::PickUp(slot) | Transaction::SplitHalf(slot) => {
+                let stack = self.get(slot)?.ok_or(InventoryError::EmptySlot(slot))?;
+                if self.cursor.is_some() {
+                    return Err(InventoryError::CursorOccupied);
+                }
+                let taken = match transaction {
+                    Transaction::SplitHalf(_) => stack.count.div_ceil(2),
+                    _ => stack.count,
+                };
+                self.cursor = Some(stack.with_count(taken));
+                self.write(slot, Some(stack.with_count(stack.count - taken)), events);
+            }
+            Transaction::Place(slot) => {
+                let existing = self.get(slot)?;
+                let held = self.cursor.ok_or(InventoryError::CursorEmpty)?;
+                if !self.accepts(slot, held.item) {
+                    return Err(InventoryError::Rejected(slot));
+                }
+                match existing {
+                    Some(stack) if stack.stacks_with(&held) => {
+                        let moved = held.count.min(stack.max_stack().saturating_sub(stack.count));
+                        if moved == 0 {
+                            return Err(InventoryError::NoRoom);
+                        }
+                        self.cursor = Some(held.with_count(held.count - moved)).filter(|stack| stack.count != 0);
+                        self.write(slot, Some(stack.with_count(stack.count + moved)), events);
+                    }
+                    _ => {
+                        if held.count > held.max_stack() {
+                            return Err(InventoryError::NoRoom);
+                        }
+                        self.cursor = existing;
+                        self.write(slot, Some(held), events);
+                    }
+                }
+            }
+            Transaction::PlaceOne(slot) => {
+                let existing = self.get(slot)?;
+                let held = self.cursor.ok_or(InventoryError::CursorEmpty)?;
+                if !self.accepts(slot, held.item) {
+                    return Err(InventoryError::Rejected(slot));
+                }
+                let count = match existing {
+                    None => 0,
+                    Some(stack) if stack.stacks_with(&held) && stack.count < stack.max_stack() => stack.count,
+                    Some(_) => return Err(InventoryError::NoRoom),
+                };
+                self.cursor = Some(held.with_count(held.count - 1)).filter(|stack| stack.count != 0);
+                self.write(slot, Some(held.with_count(count + 1)), events);
+            }
+            Transaction::QuickMove { from, to } => {
+                let stack = self.get(from)?.ok_or(InventoryError::EmptySlot(from))?;
+                let (remaining, plan) = self.plan_insert(to, stack, Some(from))?;
+                if plan.is_empty() {
+                    return Err(InventoryError::NoRoom);
+                }
+                self.write(from, Some(stack.with_count(remaining)), events);
+                for (slot, stack) in plan {
+                    self.write(slot, Some(stack), events);
+                }
+            }
+            Transaction::SelectRecipe { container, recipe } => {
+                let (machine, mut selection) = self.machine(container)?;
+                match recipe {
+                    Some(recipe) => selection.select(recipe, machine, &self.recipes)?,
+                    None => selection.clear(),
+                }
+                self.write_selection(container, selection, events);
+            }
+            Transaction::SetRecipeMode { container, mode } => {
+                let (_, mut selection) = self.machine(container)?;
+                selection.set_mode(mode)?;
+                self.write_selection(container, selection, events);
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts as much of `stack` into `container` as fits, the way the transport system and drones
+    /// do: into matching stacks, then empty slots, each in slot order, respecting slot filters.
+    /// Returns the items that didn't fit.
+    pub fn insert_stack(&mut self, container: ContainerId, stack: ItemStack, events: &mut EventBus) -> Result<Option<ItemStack>, InventoryError> {
+        let (remaining, plan) = self.plan_insert(container, stack, None)?;
+        for (slot, stack) in plan {
+            self.write(slot, Some(stack), events);
+        }
+        Ok(Some(stack.with_count(remaining)).filter(|stack| stack.count != 0))
+    }
+
+    /// Takes up to `stack.count` items that stack with `stack` out of `container`, in slot order.
+    /// Returns the items taken, if any.
+    pub fn extract_stack(&mut self, container: ContainerId, stack: ItemStack, events: &mut EventBus) -> Result<Option<ItemStack>, InventoryError> {
+        let source = self.containers.get(&container).ok_or(InventoryError::UnknownContainer(container))?;
+        let mut taken = 0;
+        let mut plan = Vec::new();
+        for index in 0..source.len() as u16 {
+            if let Some(existing) = source.get(index).filter(|existing| existing.stacks_with(&stack)) {
+                let moved = existing.count.min(stack.count - taken);
+                if moved != 0 {
+                    taken += moved;
+                    plan.push((SlotRef::new(container, index), existing.with_count(existing.count - moved)));
+                }
+            }
+        }
+        for (slot, stack) in plan {
+            self.write(slot, Some(stack), events);
+        }
+        Ok(Some(stack.with_count(taken)).filter(|stack| stack.count != 0))
+    }
+
+    /// Plans moving `stack` into `to`: matching stacks, then empty slots, each in slot order,
+    /// skipping `skip`. Returns the count that doesn't fit, and the new stack of each slot.
+    fn plan_insert(&self, to: ContainerId, stack: ItemStack, skip: Option<SlotRef>) -> Result<(u32, Vec<(SlotRef, ItemStack)>), InventoryError> {
+        let target = self.containers.get(&to).ok_or(InventoryError::UnknownContainer(to))?;
+        let mut remaining = stack.count;
+        let mut plan = Vec::new();
+        for fill_empty in [false, true] {
+            for index in 0..target.len() as u16 {
+                let slot = SlotRef::new(to, index);
+                if remaining == 0 || Some(slot) == skip || !target.filter(index).accepts(stack.item) {
+                    continue;
+                }
+                let count = match (target.get(index), fill_empty) {
+                    (Some(existing), false) if existing.stacks_with(&stack) => existing.count,
+                    (None, true) => 0,
+                    _ => continue,
+                };
+                let moved = remaining.min(stack.max_stack().saturating_sub(count));
+                if moved != 0 {
+                    remaining -= moved;
+                    plan.push((slot, stack.with_count(count + moved)));
+                }
+            }
+        }
+        Ok((remaining, plan))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfcereal::{decode::Decode, encode::Encode};
+
+    use super::*;
+    use crate::game::{
+        crafting::item::ItemType,
+        events::{EventFilter, EventKind},
+    };
+
+    const CHEST: ContainerId = ContainerId(1);
+
+    fn slot(container: ContainerId, index: u16) -> SlotRef {
+        SlotRef::new(container, index)
+    }
+
+    fn setup() -> (Inventories, EventBus) {
+        let mut inventories = Inventories::new();
+        inventories.insert(ContainerId::PLAYER, Container::new(4));
+        inventories.insert(CHEST, Container::with_filters(vec![
+            SlotFilter::Only(ItemType::IronIngot.id()),
+            SlotFilter::Any,
+            SlotFilter::TakeOnly,
+        ]));
+        (inventories, EventBus::new())
+    }
+
+    #[test]
+    fn click_test() {
+        let (mut inventories, mut events) = setup();
+        let ui = events.subscribe(EventFilter::only(EventKind::SlotChanged), 64);
+        let iron = ItemType::IronIngot.id();
+        let copper = ItemType::CopperIngot.id();
+        inventories.set(slot(ContainerId::PLAYER, 0), Some(ItemStack::new(iron, 9)), &mut events).unwrap();
+        inventories.set(slot(ContainerId::PLAYER, 1), Some(ItemStack::new(copper, 3)), &mut events).unwrap();
+
+        inventories.apply(Transaction::SplitHalf(slot(ContainerId::PLAYER, 0)), &mut events).unwrap();
+        assert_eq!(inventories.cursor(), Some(ItemStack::new(iron, 5)));
+        assert_eq!(
+            inventories.apply(Transaction::PickUp(slot(ContainerId::PLAYER, 1)), &mut events),
+            Err(InventoryError::CursorOccupied),
+        );
+        assert_eq!(
+            inventories.apply(Transaction::PlaceOne(slot(CHEST, 2)), &mut events),
+            Err(InventoryError::Rejected(slot(CHEST, 2))),
+        );
+        inventories.apply(Transaction::PlaceOne(slot(CHEST, 0)), &mut events).unwrap();
+        assert_eq!(inventories.get(slot(CHEST, 0)), Ok(Some(ItemStack::new(iron, 1))));
+        // Placing onto a different item swaps.
+        inventories.apply(Transaction::Place(slot(ContainerId::PLAYER, 1)), &mut events).unwrap();
+        assert_eq!(inventories.cursor(), Some(ItemStack::new(copper, 3)));
+        assert_eq!(inventories.get(slot(ContainerId::PLAYER, 1)), Ok(Some(ItemStack::new(iron, 4))));
+
+        events.end_tick(0);
+        let changes = events.drain(ui).collect::<Vec<_>>();
+        assert_eq!(changes.len(), 5);
+        assert_eq!(changes.last(), Some(&Event::SlotChanged {
+            slot: slot(ContainerId::PLAYER, 1),
+            stack: Some(ItemStack::new(iron, 4)),
+        }));
+        let mut bytes = Vec::new();
+        for change in &changes {
+            change.encode(&mut bytes).unwrap();
+        }
+        let mut input = bytes.as_slice();
+        for change in &changes {
+            assert_eq!(Event::decode(&mut input).unwrap(), *change);
+        }
+    }
+
+    #[test]
+    fn quick_move_test() {
+        let (mut inventories, mut events) = setup();
+        let iron = ItemType::IronIngot.id();
+        let copper = ItemType::CopperIngot.id();
+        inventories.set(slot(CHEST, 1), Some(ItemStack::new(iron, 60)), &mut events).unwrap();
+        inventories.set(slot(ContainerId::PLAYER, 0), Some(ItemStack::new(iron, 10)), &mut events).unwrap();
+
+        // The matching stack is topped up first, and the filtered slot takes the rest.
+        inventories.apply(Transaction::QuickMove { from: slot(ContainerId::PLAYER, 0), to: CHEST }, &mut events).unwrap();
+        assert_eq!(inventories.get(slot(CHEST, 1)), Ok(Some(ItemStack::new(iron, 64))));
+        assert_eq!(inventories.get(slot(CHEST, 0)), Ok(Some(ItemStack::new(iron, 6))));
+        assert_eq!(inventories.get(slot(ContainerId::PLAYER, 0)), Ok(None));
+
+        inventories.set(slot(ContainerId::PLAYER, 0), Some(ItemStack::new(copper, 1)), &mut events).unwrap();
+        let before = inventories.clone();
+        assert_eq!(
+            inventories.apply(Transaction::QuickMove { from: slot(ContainerId::PLAYER, 0), to: CHEST }, &mut events),
+            Err(InventoryError::NoRoom),
+        );
+        assert_eq!(inventories, before);
+    }
+
+    #[test]
+    fn recipe_select_test() {
+        use crate::game::crafting::recipe::Recipe;
+
+        const PRESS: MachineKind = MachineKind(2);
+        const MACHINE: ContainerId = ContainerId(2);
+        let iron = ItemType::IronIngot.id();
+        let steel = ItemType::SteelIngot.id();
+        let mut book = RecipeBook::new();
+        let smelt = book.register(MachineKind(1), Recipe { inputs: vec![(iron, 2)], outputs: vec![(steel, 1)], requires: None });
+        let rod = book.register(PRESS, Recipe { inputs: vec![(steel, 1)], outputs: vec![(ItemType::SteelRod.id(), 2)], requires: None });
+
+        let mut events = EventBus::new();
+        let ui = events.subscribe(EventFilter::only(EventKind::RecipeSelected), 16);
+        let mut inventories = Inventories::with_recipes(Arc::new(book));
+        inventories.insert(ContainerId::PLAYER, Container::new(2));
+        inventories.insert(MACHINE, Container::for_machine(PRESS, MachineEntity::default(), vec![SlotFilter::Any, SlotFilter::TakeOnly]));
+        inventories.set(slot(ContainerId::PLAYER, 0), Some(ItemStack::new(iron, 4)), &mut events).unwrap();
+
+        assert_eq!(
+            inventories.apply(Transaction::SelectRecipe { container: ContainerId::PLAYER, recipe: Some(rod) }, &mut events),
+            Err(InventoryError::NotAMachine(ContainerId::PLAYER)),
+        );
+        assert!(matches!(
+            inventories.apply(Transaction::SelectRecipe { container: MACHINE, recipe: Some(smelt) }, &mut events),
+            Err(InventoryError::Selection(SelectionError::WrongMachine { .. })),
+        ));
+        inventories.apply(Transaction::SelectRecipe { container: MACHINE, recipe: Some(rod) }, &mut events).unwrap();
+        inventories.apply(Transaction::SetRecipeMode { container: MACHINE, mode: RecipeMode::Locked }, &mut events).unwrap();
+        assert_eq!(inventories.ghosts(MACHINE).unwrap(), [InputGhost { slot: slot(MACHINE, 0), item: steel, count: 1, filled: false }]);
+
+        // A locked machine only takes the inputs of its recipe.
+        inventories.apply(Transaction::PickUp(slot(ContainerId::PLAYER, 0)), &mut events).unwrap();
+        assert_eq!(
+            inventories.apply(Transaction::Place(slot(MACHINE, 0)), &mut events),
+            Err(InventoryError::Rejected(slot(MACHINE, 0))),
+        );
+        inventories.apply(Transaction::SelectRecipe { container: MACHINE, recipe: None }, &mut events).unwrap();
+        inventories.apply(Transaction::Place(slot(MACHINE, 0)), &mut events).unwrap();
+        assert_eq!(inventories.container(MACHINE).unwrap().selection(), RecipeSelection::NONE);
+        assert_eq!(inventories.container(MACHINE).unwrap().machine_entity(), Some(&MachineEntity::default()));
+
+        events.end_tick(0);
+        let changes = events.drain(ui).collect::<Vec<_>>();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1], Event::RecipeSelected { container: MACHINE, recipe: Some(rod), mode: RecipeMode::Locked });
+        let mut bytes = Vec::new();
+        changes[1].encode(&mut bytes).unwrap();
+        assert_eq!(Event::decode(&mut bytes.as_slice()).unwrap(), changes[1]);
+    }
+
+    #[test]
+    fn stack_data_test() {
+        let mut a = StackData::EMPTY;
+        a.insert(DataKey(3), 30);
+        a.insert(DataKey::DAMAGE, 10);
+        let mut b = StackData::EMPTY;
+        b.insert(DataKey::DAMAGE, 10);
+        b.insert(DataKey(3), 30);
+        b.insert(DataKey(2), 20);
+        assert_eq!(b.remove(DataKey(2)), Some(20));
+        // Insertion order doesn't matter.
+        assert_eq!(a, b);
+        assert_eq!(a.insert(DataKey::DAMAGE, 11), Some(10));
+        assert_eq!(a.iter().collect::<Vec<_>>(), [(DataKey::DAMAGE, 11), (DataKey(3), 30)]);
+
+        let mut bytes = Vec::new();
+        a.encode(&mut bytes).unwrap();
+        assert_eq!(StackData::decode(&mut bytes.as_slice()).unwrap(), a);
+        let unsorted = [2, 3, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        assert!(StackData::decode(&mut unsorted.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Loot tables, and filling generated structures' containers from them.
+//!
+//! [LootTables] implements [LootSource], so structure generation ([mfprocgen::loot]) rolls the
+//! game's tables, and [containers_from_loot] turns the filled containers into block entity
+//! inventories.
+
+use mfprocgen::loot::{FilledContainer, LootItem, LootSource};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::game::{
+    crafting::item::ItemId,
+    inventory::{Container, ItemStack, MAX_STACK},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LootTableId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LootEntry {
+    pub item: ItemId,
+    /// How likely the entry is to be picked, relative to the other entries.
+    pub weight: u32,
+    /// The inclusive range of the number of items.
+    pub count: (u32, u32),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct LootTable {
+    /// The inclusive range of the number of entries picked.
+    pub rolls: (u32, u32),
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /// Picks entries by weight, one stack per roll.
+    pub fn roll(&self, rng: &mut ChaCha8Rng) -> Vec<ItemStack> {
+        let total = self.entries.iter().map(|entry| entry.weight as u64).sum::<u64>();
+        if total == 0 || self.rolls.1 < self.rolls.0 {
+            return Vec::new();
+        }
+        let rolls = rng.random_range(self.rolls.0..=self.rolls.1);
+        (0..rolls).filter_map(|_| {
+            let mut pick = rng.random_range(0..total);
+            let entry = self.entries.iter().find(|entry| {
+                let found = pick < entry.weight as u64;
+                pick = pick.saturating_sub(entry.weight as u64);
+                found
+            })?;
+            let (min, max) = entry.count;
+            let count = if min < max { rng.random_range(min..=max) } else { min };
+            Some(ItemStack::new(entry.item, count.min(MAX_STACK)))
+        }).collect()
+    }
+}
+
+/// Every loot table, by id.
+#[derive(Debug, Default, Clone)]
+pub struct LootTables {
+    tables: Vec<LootTable>,
+}
+
+impl LootTables {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, table: LootTable) -> LootTableId {
+        let id = LootTableId(self.tables.len() as u32);
+        self.tables.push(table);
+        id
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, id: LootTableId) -> Option<&LootTable> {
+        self.tables.get(id.0 as usize)
+    }
+}
+
+impl LootSource for LootTables {
+    /// Unknown tables roll nothing.
+    fn roll(&self, table: u32, rng: &mut ChaCha8Rng) -> Vec<LootItem> {
+        self.get(LootTableId(table))
+            .map(|table| table.roll(rng))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|stack| LootItem { item: stack.item.get(), count: stack.count })
+            .collect()
+    }
+}
+
+/// Builds the inventory of a filled container.
+#[must_use]
+pub fn container_from_loot(filled: &FilledContainer) -> Container {
+    let mut container = Container::new(filled.slots.len() as u16);
+    for (index, item) in filled.slots.iter().enumerate() {
+        let stack = item.map(|item| ItemStack::new(ItemId::new(item.item), item.count));
+        container.set(index as u16, stack);
+    }
+    container
+}
+
+/// Builds the inventories of filled containers, keyed by position.
+#[must_use]
+pub fn containers_from_loot(filled: &[FilledContainer]) -> Vec<((i32, i32, i32), Container)> {
+    filled.iter().map(|filled| (filled.position, container_from_loot(filled))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use mfprocgen::{
+        GeneratorConfig,
+        loot::{self, LootContainer},
+        stage::GenContext,
+        structure::StructureBox,
+    };
+
+    use super::*;
+
+    #[test]
+    fn loot_test() {
+        let mut tables = LootTables::new();
+        let table = tables.register(LootTable {
+            rolls: (2, 4),
+            entries: vec![
+                LootEntry { item: ItemId::new(1), weight: 3, count: (1, 8) },
+                LootEntry { item: ItemId::new(2), weight: 1, count: (1, 1) },
+                LootEntry { item: ItemId::new(3), weight: 0, count: (1, 1) },
+            ],
+        });
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(9, &config);
+        let seed = loot::structure_seed(&ctx, "ruins", StructureBox::new((0, 0, 0), (4, 4, 4)));
+        let containers = [LootContainer { position: (1, 1, 1), table: table.0, slots: 9 }];
+        let inventories = containers_from_loot(&loot::fill(seed, &containers, &tables));
+        // Regenerating the structure produces the same chest.
+        assert_eq!(inventories, containers_from_loot(&loot::fill(seed, &containers, &tables)));
+
+        let (position, chest) = &inventories[0];
+        assert_eq!(*position, (1, 1, 1));
+        let stacks = (0..chest.len() as u16).filter_map(|index| chest.get(index)).collect::<Vec<_>>();
+        assert!((2..=4).contains(&stacks.len()));
+        assert!(stacks.iter().all(|stack| stack.item != ItemId::new(3)));
+    }
+}
@@ -0,0 +1,97 @@
+//! Machine block entities.
+
+pub mod recipe;
+pub mod sides;
+pub mod sorter;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::{Direction, Orientation};
+
+use recipe::RecipeSelection;
+use sides::SideConfig;
+
+/// The per-machine state stored alongside a machine voxel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MachineEntity {
+    pub orientation: Orientation,
+    pub sides: SideConfig,
+    /// Held by the machine's [Container](crate::game::inventory::Container) while it's loaded, and
+    /// changed through its transactions.
+    pub recipe: RecipeSelection,
+}
+
+impl MachineEntity {
+    #[inline]
+    #[must_use]
+    pub const fn new(orientation: Orientation, sides: SideConfig) -> Self {
+        Self { orientation, sides, recipe: RecipeSelection::NONE }
+    }
+
+    /// Whether the transport system may insert items through the world face `face`.
+    #[inline]
+    #[must_use]
+    pub const fn accepts_from(&self, face: Direction) -> bool {
+        self.sides.world_mode(self.orientation, face).accepts()
+    }
+
+    /// Whether the transport system may pull items out through the world face `face`.
+    #[inline]
+    #[must_use]
+    pub const fn emits_to(&self, face: Direction) -> bool {
+        self.sides.world_mode(self.orientation, face).emits()
+    }
+
+    /// The world faces items are pushed out of, in [Direction::ALL] order.
+    pub fn output_faces(&self) -> impl Iterator<Item = Direction> + '_ {
+        Direction::iter().filter(|&face| self.emits_to(face))
+    }
+}
+
+// Layout: orientation (u8), sides, recipe selection.
+impl Encode for MachineEntity {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            self.orientation.encode(encoder)?
+            + self.sides.encode(encoder)?
+            + self.recipe.encode(encoder)?
+        )
+    }
+}
+
+impl Decode for MachineEntity {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self {
+            orientation: Orientation::decode(decoder)?,
+            sides: SideConfig::decode(decoder)?,
+            recipe: RecipeSelection::decode(decoder)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sides::{LocalSide, SideMode};
+
+    #[test]
+    fn machine_io_test() {
+        let mut sides = SideConfig::default();
+        sides.set(LocalSide::Top, SideMode::Input);
+        sides.set(LocalSide::Front, SideMode::Both);
+        let mut machine = MachineEntity::new(Orientation::UNORIENTED, sides);
+        assert!(machine.accepts_from(Direction::UP));
+        assert_eq!(machine.output_faces().collect::<Vec<_>>(), vec![Direction::FRONT]);
+
+        // Turning the machine upside down moves its inputs to the bottom.
+        machine.orientation = Orientation::UNORIENTED.flip_y();
+        assert!(machine.accepts_from(Direction::DOWN));
+        assert!(!machine.accepts_from(Direction::UP));
+
+        let mut bytes = Vec::new();
+        machine.encode(&mut bytes).unwrap();
+        assert_eq!(MachineEntity::decode(&mut bytes.as_slice()).unwrap(), machine);
+    }
+}
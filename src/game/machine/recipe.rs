@@ -0,0 +1,248 @@
+//! Recipe selection for machines that can craft more than one recipe.
+//!
+//! A machine's [RecipeSelection] names the recipe it's set to craft and whether it may switch on its
+//! own. An [Auto](RecipeMode::Auto) machine runs whichever of its recipes its inputs satisfy,
+//! preferring the selected one. A [Locked](RecipeMode::Locked) machine only runs the selected recipe,
+//! and its input slots only accept that recipe's inputs, so a half-filled machine can't be taken
+//! over by another recipe. The UI draws the selected recipe's inputs as ghosts in the input slots
+//! (see [Inventories::ghosts](crate::game::inventory::Inventories::ghosts)).
+//!
+//! The UI changes a selection with
+//! [Transaction::SelectRecipe](crate::game::inventory::Transaction::SelectRecipe) and
+//! [Transaction::SetRecipeMode](crate::game::inventory::Transaction::SetRecipeMode), which are
+//! validated like any other transaction.
+
+use std::num::NonZeroU32;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::{item::ItemId, recipe::Recipe},
+};
+
+/// The type of a machine, which decides the recipes it can craft.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MachineKind(pub u16);
+
+/// Every machine recipe, and the machine that crafts it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RecipeBook {
+    recipes: Vec<(MachineKind, Recipe)>,
+}
+
+impl RecipeBook {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a recipe crafted by `machine`, returning its id.
+    pub fn register(&mut self, machine: MachineKind, recipe: Recipe) -> RecipeId {
+        self.recipes.push((machine, recipe));
+        RecipeId::new(NonZeroU32::new(self.recipes.len() as u32).expect("recipe ids start at 1"))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, id: RecipeId) -> Option<&Recipe> {
+        self.recipes.get(id.base_index() as usize).map(|(_, recipe)| recipe)
+    }
+
+    /// The machine that crafts the recipe.
+    #[inline]
+    #[must_use]
+    pub fn machine(&self, id: RecipeId) -> Option<MachineKind> {
+        self.recipes.get(id.base_index() as usize).map(|&(machine, _)| machine)
+    }
+
+    /// The recipes `machine` can craft, in registration order.
+    pub fn for_machine(&self, machine: MachineKind) -> impl Iterator<Item = (RecipeId, &Recipe)> + '_ {
+        self.recipes.iter().enumerate()
+            .filter(move |(_, (kind, _))| *kind == machine)
+            .map(|(index, (_, recipe))| (RecipeId::new(NonZeroU32::new(index as u32 + 1).expect("index + 1 is never 0")), recipe))
+    }
+
+    /// Checks that `machine` can craft `id`.
+    pub fn validate(&self, id: RecipeId, machine: MachineKind) -> Result<&Recipe, SelectionError> {
+        let &(kind, ref recipe) = self.recipes.get(id.base_index() as usize).ok_or(SelectionError::UnknownRecipe(id))?;
+        if kind != machine {
+            return Err(SelectionError::WrongMachine { recipe: id, machine });
+        }
+        Ok(recipe)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SelectionError {
+    #[error("Unknown recipe: {0:?}")]
+    UnknownRecipe(RecipeId),
+    #[error("Recipe {recipe:?} can't be crafted by {machine:?}")]
+    WrongMachine {
+        recipe: RecipeId,
+        machine: MachineKind,
+    },
+    #[error("A machine can only be locked to a selected recipe")]
+    NothingSelected,
+}
+
+/// Whether a machine may switch recipes on its own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecipeMode {
+    /// Runs whichever recipe the inputs satisfy, preferring the selected one.
+    #[default]
+    Auto,
+    /// Only runs the selected recipe, and only accepts its inputs.
+    Locked,
+}
+
+impl RecipeMode {
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => RecipeMode::Auto,
+            1 => RecipeMode::Locked,
+            _ => return None,
+        })
+    }
+}
+
+/// The recipe a machine is set to craft.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecipeSelection {
+    recipe: Option<RecipeId>,
+    mode: RecipeMode,
+}
+
+impl RecipeSelection {
+    pub const NONE: Self = Self { recipe: None, mode: RecipeMode::Auto };
+
+    #[inline]
+    #[must_use]
+    pub const fn recipe(&self) -> Option<RecipeId> {
+        self.recipe
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn mode(&self) -> RecipeMode {
+        self.mode
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_locked(&self) -> bool {
+        matches!(self.mode, RecipeMode::Locked)
+    }
+
+    /// Selects `recipe` after checking that `machine` can craft it. The mode is kept.
+    pub fn select(&mut self, recipe: RecipeId, machine: MachineKind, book: &RecipeBook) -> Result<(), SelectionError> {
+        book.validate(recipe, machine)?;
+        self.recipe = Some(recipe);
+        Ok(())
+    }
+
+    /// Clears the selection. A locked machine goes back to [RecipeMode::Auto].
+    #[inline]
+    pub fn clear(&mut self) {
+        *self = Self::NONE;
+    }
+
+    pub fn set_mode(&mut self, mode: RecipeMode) -> Result<(), SelectionError> {
+        if mode == RecipeMode::Locked && self.recipe.is_none() {
+            return Err(SelectionError::NothingSelected);
+        }
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// The recipe the machine should craft next, given how many of each item its inputs hold.
+    /// A locked machine always crafts its selection, and waits if the inputs aren't there yet.
+    pub fn resolve<F: Fn(ItemId) -> u64>(&self, machine: MachineKind, book: &RecipeBook, available: F) -> Option<RecipeId> {
+        let satisfied = |recipe: &Recipe| recipe.inputs.iter().all(|&(item, count)| available(item) >= count);
+        match (self.mode, self.recipe) {
+            (RecipeMode::Locked, recipe) => recipe,
+            (RecipeMode::Auto, Some(recipe)) if book.get(recipe).is_some_and(satisfied) => Some(recipe),
+            (RecipeMode::Auto, _) => book.for_machine(machine)
+                .find(|(_, recipe)| satisfied(recipe))
+                .map(|(id, _)| id),
+        }
+    }
+}
+
+// Layout: recipe (u32, 0 = none), mode (u8).
+impl Encode for RecipeSelection {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_u32(self.recipe.map_or(0, |recipe| recipe.value()))?
+            + encoder.write_u8(self.mode.to_u8())?
+        )
+    }
+}
+
+impl Decode for RecipeSelection {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let recipe = NonZeroU32::new(decoder.read_u32()?).map(RecipeId::new);
+        let mode = RecipeMode::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid recipe mode"))?;
+        if mode == RecipeMode::Locked && recipe.is_none() {
+            return Err(DecodeError::InvalidData("locked to no recipe"));
+        }
+        Ok(Self { recipe, mode })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::item::ItemType;
+
+    const FURNACE: MachineKind = MachineKind(1);
+    const PRESS: MachineKind = MachineKind(2);
+
+    fn recipe(input: ItemType, output: ItemType) -> Recipe {
+        Recipe {
+            inputs: vec![(input.id(), 2)],
+            outputs: vec![(output.id(), 1)],
+            requires: None,
+        }
+    }
+
+    #[test]
+    fn recipe_selection_test() {
+        let mut book = RecipeBook::new();
+        let steel = book.register(FURNACE, recipe(ItemType::IronIngot, ItemType::SteelIngot));
+        let plate = book.register(PRESS, recipe(ItemType::IronIngot, ItemType::IronPlate));
+        let rod = book.register(PRESS, recipe(ItemType::SteelIngot, ItemType::SteelRod));
+        assert_eq!(book.for_machine(PRESS).map(|(id, _)| id).collect::<Vec<_>>(), [plate, rod]);
+
+        let mut selection = RecipeSelection::NONE;
+        assert_eq!(selection.select(steel, PRESS, &book), Err(SelectionError::WrongMachine { recipe: steel, machine: PRESS }));
+        assert_eq!(selection.set_mode(RecipeMode::Locked), Err(SelectionError::NothingSelected));
+        selection.select(rod, PRESS, &book).unwrap();
+
+        // Auto falls back to whatever the inputs allow.
+        let iron_only = |item: ItemId| if item == ItemType::IronIngot.id() { 4 } else { 0 };
+        assert_eq!(selection.resolve(PRESS, &book, iron_only), Some(plate));
+        selection.set_mode(RecipeMode::Locked).unwrap();
+        assert_eq!(selection.resolve(PRESS, &book, iron_only), Some(rod));
+
+        let mut bytes = Vec::new();
+        selection.encode(&mut bytes).unwrap();
+        assert_eq!(RecipeSelection::decode(&mut bytes.as_slice()).unwrap(), selection);
+        assert!(RecipeSelection::decode(&mut [0, 0, 0, 0, 1].as_slice()).is_err());
+
+        selection.clear();
+        assert_eq!(selection, RecipeSelection::NONE);
+    }
+}
@@ -0,0 +1,201 @@
+//! Per-side item IO configuration for machines.
+//!
+//! A [SideConfig] is stored in terms of the machine's own sides (front, back, ...), so it follows
+//! the machine when it's rotated. The transport system asks about world faces, which are resolved
+//! through the machine's [Orientation].
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::{Direction, Orientation, PerFace};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SideMode {
+    #[default]
+    None,
+    Input,
+    Output,
+    /// Both accepts and emits items.
+    Both,
+}
+
+impl SideMode {
+    #[inline]
+    #[must_use]
+    pub const fn accepts(self) -> bool {
+        matches!(self, SideMode::Input | SideMode::Both)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn emits(self) -> bool {
+        matches!(self, SideMode::Output | SideMode::Both)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => SideMode::None,
+            1 => SideMode::Input,
+            2 => SideMode::Output,
+            3 => SideMode::Both,
+            _ => return None,
+        })
+    }
+}
+
+/// A side of a machine, relative to the machine itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LocalSide {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl LocalSide {
+    pub const ALL: [LocalSide; 6] = [
+        LocalSide::Front,
+        LocalSide::Back,
+        LocalSide::Left,
+        LocalSide::Right,
+        LocalSide::Top,
+        LocalSide::Bottom,
+    ];
+
+    /// The side's direction on an unrotated machine.
+    #[inline]
+    #[must_use]
+    pub const fn direction(self) -> Direction {
+        match self {
+            LocalSide::Front => Direction::FRONT,
+            LocalSide::Back => Direction::BACK,
+            LocalSide::Left => Direction::LEFT,
+            LocalSide::Right => Direction::RIGHT,
+            LocalSide::Top => Direction::UP,
+            LocalSide::Bottom => Direction::DOWN,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_direction(direction: Direction) -> Self {
+        match direction {
+            Direction::NegZ => LocalSide::Front,
+            Direction::PosZ => LocalSide::Back,
+            Direction::NegX => LocalSide::Left,
+            Direction::PosX => LocalSide::Right,
+            Direction::PosY => LocalSide::Top,
+            Direction::NegY => LocalSide::Bottom,
+        }
+    }
+
+    /// The side's index in [LocalSide::ALL].
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        if value < 6 {
+            Some(Self::ALL[value as usize])
+        } else {
+            None
+        }
+    }
+}
+
+/// The [SideMode] of each side of a machine.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SideConfig(PerFace<SideMode>);
+
+impl SideConfig {
+    #[inline]
+    #[must_use]
+    pub const fn splat(mode: SideMode) -> Self {
+        Self(PerFace::splat(mode))
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn get(&self, side: LocalSide) -> SideMode {
+        *self.0.get(side.direction())
+    }
+
+    #[inline]
+    pub const fn set(&mut self, side: LocalSide, mode: SideMode) -> SideMode {
+        self.0.set(side.direction(), mode)
+    }
+
+    /// The mode of the side of a machine oriented by `orientation` that faces `face` in the world.
+    #[inline]
+    #[must_use]
+    pub const fn world_mode(&self, orientation: Orientation, face: Direction) -> SideMode {
+        *self.0.get(orientation.source_face(face))
+    }
+
+    /// The mode of every world face of a machine oriented by `orientation`.
+    #[inline]
+    #[must_use]
+    pub fn world_modes(&self, orientation: Orientation) -> PerFace<SideMode> {
+        self.0.reoriented(orientation)
+    }
+}
+
+// Layout: the mode (u8) of each side, in LocalSide::ALL order.
+impl Encode for SideConfig {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = 0;
+        for side in LocalSide::ALL {
+            written += encoder.write_u8(self.get(side).to_u8())?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for SideConfig {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut config = Self::default();
+        for side in LocalSide::ALL {
+            let mode = SideMode::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid side mode"))?;
+            config.set(side, mode);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn side_config_test() {
+        let mut config = SideConfig::default();
+        config.set(LocalSide::Back, SideMode::Input);
+        config.set(LocalSide::Front, SideMode::Output);
+
+        // The configured sides turn with the machine.
+        let orientation = Orientation::ROTATE_Y;
+        let back = orientation.reface(Direction::BACK);
+        assert_eq!(config.world_mode(orientation, back), SideMode::Input);
+        assert_eq!(config.world_modes(orientation)[orientation.forward()], SideMode::Output);
+        assert_eq!(config.world_mode(Orientation::UNORIENTED, Direction::BACK), SideMode::Input);
+
+        let mut bytes = Vec::new();
+        assert_eq!(config.encode(&mut bytes).unwrap(), 6);
+        assert_eq!(SideConfig::decode(&mut bytes.as_slice()).unwrap(), config);
+    }
+}
@@ -0,0 +1,212 @@
+//! Sorters: blocks that route items from the transport system to their sides by [ItemFilter].
+//!
+//! Each side of a sorter has a list of filters. An item that enters through an input side leaves
+//! through the output side whose filters match it most specifically (see
+//! [ItemFilter::specificity]). When several sides match equally well, they take turns, starting
+//! after the side that got the last such item, then in [LocalSide::ALL] order, so routing only
+//! depends on the sorter's saved state. Items no side wants go to the overflow side, if there is one.
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfgeometry::{Direction, Orientation};
+
+use super::sides::{LocalSide, SideConfig};
+use crate::game::crafting::{filter::ItemFilter, item::ItemId};
+
+/// The most filters a side of a sorter can have, so that the count fits in its `u8`.
+pub const MAX_FILTERS: usize = u8::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("A sorter side can have at most {MAX_FILTERS} filters, but {0} were given")]
+pub struct TooManyFilters(pub usize);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SorterEntity {
+    pub orientation: Orientation,
+    pub sides: SideConfig,
+    /// The filters of each side, in [LocalSide::ALL] order.
+    filters: [Vec<ItemFilter>; 6],
+    /// Where items that no side's filters match go.
+    pub overflow: Option<LocalSide>,
+    /// The side ties start from.
+    next: u8,
+}
+
+impl SorterEntity {
+    #[inline]
+    #[must_use]
+    pub fn new(orientation: Orientation, sides: SideConfig) -> Self {
+        Self { orientation, sides, ..Self::default() }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn filters(&self, side: LocalSide) -> &[ItemFilter] {
+        &self.filters[side.to_u8() as usize]
+    }
+
+    /// Sets the filters of `side`, leaving them unchanged if there are more than [MAX_FILTERS].
+    #[inline]
+    pub fn set_filters(&mut self, side: LocalSide, filters: Vec<ItemFilter>) -> Result<(), TooManyFilters> {
+        if filters.len() > MAX_FILTERS {
+            return Err(TooManyFilters(filters.len()));
+        }
+        self.filters[side.to_u8() as usize] = filters;
+        Ok(())
+    }
+
+    /// Whether the transport system may insert items through the world face `face`.
+    #[inline]
+    #[must_use]
+    pub const fn accepts_from(&self, face: Direction) -> bool {
+        self.sides.world_mode(self.orientation, face).accepts()
+    }
+
+    /// The world face `item` leaves through after entering through the world face `from`, or `None`
+    /// if the sorter can't take it.
+    ///
+    /// Items never leave through the face they came in from.
+    pub fn route(&mut self, item: ItemId, from: Direction) -> Option<Direction> {
+        if !self.accepts_from(from) {
+            return None;
+        }
+        let from = LocalSide::from_direction(self.orientation.source_face(from));
+        // (specificity, turn, side)
+        let mut best: Option<(u8, u8, LocalSide)> = None;
+        for side in LocalSide::ALL {
+            if side == from || !self.sides.get(side).emits() {
+                continue;
+            }
+            let Some(specificity) = self.filters(side).iter()
+                .filter(|filter| filter.matches(item))
+                .map(|filter| filter.specificity())
+                .max() else {
+                continue;
+            };
+            let turn = (side.to_u8() + 6 - self.next) % 6;
+            if best.is_none_or(|(best_specificity, best_turn, _)| (specificity, best_turn) > (best_specificity, turn)) {
+                best = Some((specificity, turn, side));
+            }
+        }
+        let side = match best {
+            Some((_, _, side)) => {
+                self.next = (side.to_u8() + 1) % 6;
+                side
+            }
+            None => self.overflow.filter(|&side| side != from && self.sides.get(side).emits())?,
+        };
+        Some(self.orientation.reface(side.direction()))
+    }
+}
+
+// Layout: orientation (u8), sides, then for each side in LocalSide::ALL order the filter count (u8)
+//      and filters, overflow side (u8, 0 for none and the side + 1 otherwise), next side (u8).
+impl Encode for SorterEntity {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = self.orientation.encode(encoder)? + self.sides.encode(encoder)?;
+        for filters in &self.filters {
+            // set_filters keeps the count within MAX_FILTERS.
+            written += encoder.write_u8(filters.len() as u8)?;
+            for filter in filters {
+                written += filter.encode(encoder)?;
+            }
+        }
+        Ok(
+            written
+            + encoder.write_u8(self.overflow.map_or(0, |side| side.to_u8() + 1))?
+            + encoder.write_u8(self.next)?
+        )
+    }
+}
+
+impl Decode for SorterEntity {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let orientation = Orientation::decode(decoder)?;
+        let mut sorter = Self::new(orientation, SideConfig::decode(decoder)?);
+        for filters in &mut sorter.filters {
+            for _ in 0..decoder.read_u8()? {
+                filters.push(ItemFilter::decode(decoder)?);
+            }
+        }
+        sorter.overflow = match decoder.read_u8()? {
+            0 => None,
+            side => Some(LocalSide::from_u8(side - 1).ok_or(DecodeError::InvalidData("invalid sorter overflow side"))?),
+        };
+        sorter.next = decoder.read_u8()?;
+        if sorter.next >= 6 {
+            return Err(DecodeError::InvalidData("invalid sorter turn"));
+        }
+        Ok(sorter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        crafting::item::{family, form, ItemType},
+        machine::sides::SideMode,
+    };
+
+    fn sorter() -> SorterEntity {
+        let mut sides = SideConfig::splat(SideMode::Output);
+        sides.set(LocalSide::Back, SideMode::Input);
+        let mut sorter = SorterEntity::new(Orientation::ROTATE_Y, sides);
+        sorter.set_filters(LocalSide::Left, vec![ItemFilter::Form(form::INGOT)]).unwrap();
+        sorter.set_filters(LocalSide::Right, vec![ItemFilter::Family(family::IRON), ItemFilter::Form(form::ROD)]).unwrap();
+        sorter.set_filters(LocalSide::Top, vec![ItemFilter::Family(family::IRON)]).unwrap();
+        sorter.overflow = Some(LocalSide::Front);
+        sorter
+    }
+
+    #[test]
+    fn route_test() {
+        let mut sorter = sorter();
+        let orientation = sorter.orientation;
+        let face = |side: LocalSide| orientation.reface(side.direction());
+        let back = face(LocalSide::Back);
+
+        // Copper ingots only match the ingot form.
+        assert_eq!(sorter.route(ItemType::CopperIngot.id(), back), Some(face(LocalSide::Left)));
+        // Iron ingots match the iron family more specifically, which two sides share in turns.
+        let iron = ItemType::IronIngot.id();
+        let routed = (0..4).map(|_| sorter.route(iron, back)).collect::<Vec<_>>();
+        assert_eq!(routed, [face(LocalSide::Right), face(LocalSide::Top), face(LocalSide::Right), face(LocalSide::Top)].map(Some));
+        // Nothing wants quartz, so it overflows.
+        assert_eq!(sorter.route(ItemType::Quartz.id(), back), Some(face(LocalSide::Front)));
+        // Items can't come in through an output.
+        assert_eq!(sorter.route(iron, face(LocalSide::Front)), None);
+
+        sorter.overflow = None;
+        assert_eq!(sorter.route(ItemType::Quartz.id(), back), None);
+    }
+
+    #[test]
+    fn too_many_filters_test() {
+        let mut sorter = sorter();
+        let filters = vec![ItemFilter::Form(form::ROD); MAX_FILTERS + 1];
+        assert_eq!(sorter.set_filters(LocalSide::Left, filters), Err(TooManyFilters(MAX_FILTERS + 1)));
+        assert_eq!(sorter.filters(LocalSide::Left), [ItemFilter::Form(form::INGOT)]);
+        let filters = vec![ItemFilter::Form(form::ROD); MAX_FILTERS];
+        sorter.set_filters(LocalSide::Left, filters.clone()).unwrap();
+        let mut bytes = Vec::new();
+        sorter.encode(&mut bytes).unwrap();
+        let decoded = SorterEntity::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded.filters(LocalSide::Left), filters);
+    }
+
+    #[test]
+    fn sorter_encode_test() {
+        let mut sorter = sorter();
+        sorter.route(ItemType::IronIngot.id(), sorter.orientation.reface(Direction::BACK));
+        let mut bytes = Vec::new();
+        sorter.encode(&mut bytes).unwrap();
+        let mut decoded = SorterEntity::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, sorter);
+        // The decoded sorter routes the same way.
+        let back = sorter.orientation.reface(Direction::BACK);
+        assert_eq!(decoded.route(ItemType::IronIngot.id(), back), sorter.route(ItemType::IronIngot.id(), back));
+    }
+}
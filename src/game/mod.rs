@@ -1,12 +1,169 @@
+//! The game simulation.
+//!
+//! # Threading
+//!
+//! The simulation state ([Game] and everything it owns, the [Context](context::Context), saves and
+//! events) is `Send + Sync` so that it can be split between a server thread and client threads
+//! later. This is checked at compile time below and in each crate with [mfcore::assert_send_sync],
+//! so an `Rc` or `Cell` added to simulation state fails the build instead of the split.
+//!
+//! A few subsystems are allowed to be less thread safe, as long as they stay out of the simulation
+//! state:
+//!
+//! - [schedule::Scheduler] is `Send` but not `Sync`: its systems are `FnMut`. It is owned by the
+//!   thread running the tick.
+//! - [vm::host::GameHost] borrows the state it works on for a single call, and isn't stored.
+//! - The profiler ([mfworld::profile]) keeps its span depth in thread locals, and its guards must
+//!   be dropped on the thread that opened them.
+//! - Generation shares its [StructurePlacer](mfprocgen::structure::StructurePlacer)s and
+//!   [LootSource](mfprocgen::loot::LootSource)s across workers, so both traits require `Sync`.
+
+pub mod clock;
 pub mod context;
 pub mod crafting;
+pub mod drone;
+pub mod environment;
+pub mod events;
+pub mod interaction;
+pub mod inventory;
+pub mod loot;
+pub mod machine;
+pub mod mode;
+pub mod placement;
 pub mod player;
+#[cfg(test)]
+mod roundtrip;
+pub mod rules;
+pub mod save;
+pub mod scenario;
+pub mod schedule;
+pub mod sync;
+pub mod tool;
+pub mod validate;
+pub mod vm;
 pub mod world;
 
+use std::collections::BTreeMap;
+
+use mfhash::{
+    canonical::hash_encoded,
+    deterministic::{combine_versions, DeterministicHash, DeterministicHasher},
+    deterministic_hash_u128,
+    Hash128,
+};
+use environment::{Environment, Weather};
+use mfworld::{
+    chunk::{stored::StoredChunk, ChunkPos},
+    portal::DimensionId,
+};
+use mode::GameMode;
 use world::World;
 use player::Player;
+use rules::GameRules;
 
 pub struct Game {
     pub(crate) world: World,
     pub(crate) player: Player,
-}
\ No newline at end of file
+    pub(crate) mode: GameMode,
+    pub(crate) rules: GameRules,
+    pub(crate) environment: Environment,
+}
+
+impl Game {
+    #[inline]
+    #[must_use]
+    pub const fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Runs the parts of the game that advance on their own by one tick. Returns the dimensions
+    /// whose weather changed (see [Environment::tick]).
+    #[inline]
+    pub fn tick(&mut self) -> Vec<(DimensionId, Weather)> {
+        self.environment.tick(&self.rules)
+    }
+
+    /// The hash of the whole simulation state. Two games with the same state hash are in the same
+    /// state, so comparing hashes (see [save::hash_history]) finds where runs diverged.
+    #[inline]
+    #[must_use]
+    pub fn state_hash(&self) -> Hash128 {
+        Hash128(deterministic_hash_u128(self))
+    }
+
+    /// The [state hash](Self::state_hash) together with the contents of the loaded chunks, which
+    /// are kept beside the game rather than in it. A loaded chunk missing from `chunks` is hashed as
+    /// missing.
+    #[must_use]
+    pub fn content_hash(&self, chunks: &BTreeMap<ChunkPos, StoredChunk>) -> Hash128 {
+        Hash128(deterministic_hash_u128(GameContents { game: self, chunks }))
+    }
+}
+
+struct GameContents<'a> {
+    game: &'a Game,
+    chunks: &'a BTreeMap<ChunkPos, StoredChunk>,
+}
+
+// Layout: the game, then each loaded chunk in Morton order as a presence flag and, if present, its
+// save encoding.
+impl DeterministicHash for GameContents<'_> {
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        self.game.deterministic_hash(hasher);
+        for chunk in self.game.world.iter_loaded_chunks_deterministic() {
+            match self.chunks.get(&chunk) {
+                Some(stored) => {
+                    hasher.write_u8(1);
+                    hash_encoded(stored, hasher);
+                }
+                None => hasher.write_u8(0),
+            }
+        }
+    }
+}
+
+// The rules, environment and edit history are hashed through their save encoding, so that anything
+// saved is hashed.
+impl DeterministicHash for Game {
+    const HASH_VERSION: u32 = combine_versions(&[World::HASH_VERSION, u8::HASH_VERSION]);
+
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        self.world.deterministic_hash(hasher);
+        hasher.write_u8(self.mode.to_u8());
+        hash_encoded(&self.rules, hasher);
+        hash_encoded(&self.environment, hasher);
+        hash_encoded(self.player.edit_history(), hasher);
+    }
+}
+
+mfcore::assert_send_sync!(
+    Game,
+    clock::GameClock,
+    context::Context,
+    crafting::materials::Materials,
+    drone::Dispatcher,
+    environment::Environment,
+    events::EventBus,
+    inventory::Inventories,
+    loot::LootTables,
+    rules::GameRules,
+    save::header::SaveHeader,
+    save::dir::SaveDir,
+    validate::Validator,
+    vm::Scheduler,
+    mfprocgen::GeneratorConfig,
+    mfprocgen::structure::ClaimRegistry<'static>,
+);
+mfcore::assert_send!(schedule::Scheduler);
@@ -0,0 +1,123 @@
+use std::{fmt, str::FromStr};
+
+use mfcereal::{decode::Decode, encode::Encode};
+
+/// The rule set a world is played under. Chosen at world creation and stored in the
+/// [save header](crate::game::save::header::SaveHeader).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub enum GameMode {
+    #[default]
+    #[cereal(tag = 0)]
+    Survival,
+    /// Free-build: infinite materials, instant break/place, and everything unlocked.
+    #[cereal(tag = 1)]
+    Creative,
+    /// Like [GameMode::Creative], but breaking and placing take their normal time so that
+    /// simulation costs stay representative.
+    #[cereal(tag = 2)]
+    Benchmark,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Survival, GameMode::Creative, GameMode::Benchmark];
+
+    /// Materials are never consumed.
+    #[inline]
+    #[must_use]
+    pub const fn infinite_materials(self) -> bool {
+        matches!(self, GameMode::Creative | GameMode::Benchmark)
+    }
+
+    /// Voxels break without any delay.
+    #[inline]
+    #[must_use]
+    pub const fn instant_break(self) -> bool {
+        matches!(self, GameMode::Creative)
+    }
+
+    /// Voxels are placed without any delay.
+    #[inline]
+    #[must_use]
+    pub const fn instant_place(self) -> bool {
+        matches!(self, GameMode::Creative)
+    }
+
+    /// Voxel edits can be undone and redone.
+    #[inline]
+    #[must_use]
+    pub const fn undo_history(self) -> bool {
+        matches!(self, GameMode::Creative)
+    }
+
+    /// Recipes and tools must be unlocked before they can be used.
+    #[inline]
+    #[must_use]
+    pub const fn progression_gating(self) -> bool {
+        matches!(self, GameMode::Survival)
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Benchmark => "benchmark",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(GameMode::Survival),
+            1 => Some(GameMode::Creative),
+            2 => Some(GameMode::Benchmark),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Unknown game mode: `{0}` (expected `survival`, `creative`, or `benchmark`)")]
+pub struct UnknownGameMode(pub String);
+
+impl FromStr for GameMode {
+    type Err = UnknownGameMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL.into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownGameMode(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_mode_test() {
+        for mode in GameMode::ALL {
+            assert_eq!(GameMode::from_u8(mode.to_u8()), Some(mode));
+            assert_eq!(mode.name().parse::<GameMode>(), Ok(mode));
+        }
+        assert!("hardcore".parse::<GameMode>().is_err());
+        assert!(GameMode::Survival.progression_gating());
+        assert!(!GameMode::Survival.infinite_materials());
+        assert!(GameMode::Creative.instant_break() && GameMode::Creative.instant_place());
+        assert!(GameMode::Benchmark.infinite_materials() && !GameMode::Benchmark.instant_break());
+    }
+}
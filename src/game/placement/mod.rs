@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use mfgeometry::{
+    cardinal::Cardinal, polarity::Pol, Direction, Orientation, Rotation
+};
+use mfworld::voxel::id::VoxelId;
+
+/// The vertical look direction of the player, bucketed into thirds.
+#[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pitch {
+    /// Looking up at an angle steeper than 45 degrees.
+    Up = 0,
+    /// Looking (roughly) at the horizon.
+    #[default]
+    Level = 1,
+    /// Looking down at an angle steeper than 45 degrees.
+    Down = 2,
+}
+
+impl Pitch {
+    /// Buckets a pitch in degrees where positive is looking up.
+    #[inline]
+    pub fn from_degrees(degrees: f32) -> Self {
+        if degrees > 45.0 {
+            Pitch::Up
+        } else if degrees < -45.0 {
+            Pitch::Down
+        } else {
+            Pitch::Level
+        }
+    }
+
+    /// The Y component of the vector pointing from the placed block back toward the player.
+    #[inline]
+    pub const fn toward_player_y(self) -> i32 {
+        match self {
+            Pitch::Up => -1,
+            Pitch::Level => 0,
+            Pitch::Down => 1,
+        }
+    }
+}
+
+/// Everything needed to decide how a block should be oriented when it's placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlacementContext {
+    /// The face of the targeted block that the ray hit. The new block is placed on this side of the target.
+    pub hit_face: Direction,
+    /// The horizontal direction the player is facing.
+    pub facing: Cardinal,
+    /// The vertical look direction of the player.
+    pub pitch: Pitch,
+}
+
+impl PlacementContext {
+    #[inline]
+    pub const fn new(hit_face: Direction, facing: Cardinal, pitch: Pitch) -> Self {
+        Self {
+            hit_face,
+            facing,
+            pitch,
+        }
+    }
+
+    /// The horizontal direction the player is facing as a [Direction].
+    #[inline]
+    pub const fn facing_direction(self) -> Direction {
+        cardinal_direction(self.facing)
+    }
+
+    /// The dominant direction pointing from the placed block toward the player.
+    #[inline]
+    pub const fn toward_player(self) -> Direction {
+        match self.pitch {
+            Pitch::Up => Direction::NegY,
+            Pitch::Level => cardinal_direction(self.facing).invert(),
+            Pitch::Down => Direction::PosY,
+        }
+    }
+}
+
+/// Determines how the final [Orientation] of a placed block is derived.
+///
+/// Block models are authored unoriented, with the front of the model on the
+/// forward face ([Direction::NegZ]) and the top of the model on [Direction::PosY].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlacementStrategy {
+    /// The front of the block faces the player (pistons, furnaces, observers).
+    FaceTowardPlayer,
+    /// The up axis of the block is aligned to the axis of the hit face (logs, pillars).
+    /// Both faces of an axis produce the same orientation.
+    AxisFromFace,
+    /// The block always has the same orientation.
+    Fixed(Orientation),
+    /// The top of the block points out of the hit face and the front is turned
+    /// toward the player (signs, torches, attachable machines).
+    #[default]
+    FreeRotate,
+}
+
+impl PlacementStrategy {
+    /// Computes the final [Orientation] of the placed block.
+    pub fn orientation(self, context: PlacementContext) -> Orientation {
+        match self {
+            PlacementStrategy::FaceTowardPlayer => {
+                let front = context.toward_player();
+                // When the front is vertical, the top of the block points away from the player.
+                let up = match front {
+                    Direction::PosY | Direction::NegY => context.facing_direction(),
+                    _ => Direction::PosY,
+                };
+                // front and up are always orthogonal.
+                Rotation::from_up_and_forward(up, front)
+                    .expect("front and up are orthogonal")
+                    .orientation()
+            }
+            PlacementStrategy::AxisFromFace => {
+                let up = Direction::from_polar_axis(Pol::Pos, context.hit_face.axis());
+                Rotation::from_up(up).orientation()
+            }
+            PlacementStrategy::Fixed(orientation) => orientation,
+            PlacementStrategy::FreeRotate => {
+                let up = context.hit_face;
+                let (tx, tz) = context.facing.invert().to_ituple2();
+                let toward = (tx, context.pitch.toward_player_y(), tz);
+                // Pick the angle whose forward best aligns with the direction toward the player.
+                // Ties resolve to the lowest angle so that placement is deterministic.
+                let mut best = Rotation::new(up, 0);
+                let mut best_score = i32::MIN;
+                for angle in 0..4 {
+                    let rotation = Rotation::new(up, angle);
+                    let (fx, fy, fz) = rotation.forward().to_ituple();
+                    let score = fx * toward.0 + fy * toward.1 + fz * toward.2;
+                    if score > best_score {
+                        best = rotation;
+                        best_score = score;
+                    }
+                }
+                best.orientation()
+            }
+        }
+    }
+}
+
+/// Selects a [PlacementStrategy] per voxel type.
+#[derive(Debug, Default, Clone)]
+pub struct PlacementRules {
+    default: PlacementStrategy,
+    rules: HashMap<VoxelId, PlacementStrategy>,
+}
+
+impl PlacementRules {
+    #[inline]
+    pub fn new(default: PlacementStrategy) -> Self {
+        Self {
+            default,
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Sets the strategy for `id`, returning the previous strategy if there was one.
+    #[inline]
+    pub fn set(&mut self, id: VoxelId, strategy: PlacementStrategy) -> Option<PlacementStrategy> {
+        self.rules.insert(id, strategy)
+    }
+
+    /// Removes the strategy for `id` so that it falls back to the default strategy.
+    #[inline]
+    pub fn remove(&mut self, id: VoxelId) -> Option<PlacementStrategy> {
+        self.rules.remove(&id)
+    }
+
+    #[inline]
+    pub fn default_strategy(&self) -> PlacementStrategy {
+        self.default
+    }
+
+    #[inline]
+    pub fn strategy(&self, id: VoxelId) -> PlacementStrategy {
+        self.rules.get(&id).copied().unwrap_or(self.default)
+    }
+
+    /// Computes the [Orientation] for a voxel of type `id` placed with `context`.
+    #[inline]
+    pub fn orientation(&self, id: VoxelId, context: PlacementContext) -> Orientation {
+        self.strategy(id).orientation(context)
+    }
+}
+
+#[inline]
+const fn cardinal_direction(cardinal: Cardinal) -> Direction {
+    match cardinal {
+        Cardinal::North => Direction::NegZ,
+        Cardinal::West => Direction::NegX,
+        Cardinal::South => Direction::PosZ,
+        Cardinal::East => Direction::PosX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PITCHES: [Pitch; 3] = [Pitch::Up, Pitch::Level, Pitch::Down];
+
+    fn contexts() -> impl Iterator<Item = PlacementContext> {
+        Direction::ALL.into_iter().flat_map(|hit_face| {
+            Cardinal::ALL.into_iter().flat_map(move |facing| {
+                PITCHES.into_iter().map(move |pitch| PlacementContext::new(hit_face, facing, pitch))
+            })
+        })
+    }
+
+    #[test]
+    fn face_toward_player_test() {
+        for context in contexts() {
+            let orientation = PlacementStrategy::FaceTowardPlayer.orientation(context);
+            assert_eq!(orientation.forward(), context.toward_player(), "{context:?}");
+            assert_eq!(orientation.flip(), mfgeometry::Flip::NONE);
+            match context.pitch {
+                Pitch::Level => assert_eq!(orientation.up(), Direction::PosY),
+                _ => assert_eq!(orientation.up(), context.facing_direction()),
+            }
+        }
+    }
+
+    #[test]
+    fn axis_from_face_test() {
+        for context in contexts() {
+            let orientation = PlacementStrategy::AxisFromFace.orientation(context);
+            assert_eq!(orientation.up().axis(), context.hit_face.axis());
+            assert_eq!(orientation.up().polarity(), Pol::Pos);
+            let opposite = PlacementContext { hit_face: context.hit_face.invert(), ..context };
+            assert_eq!(orientation, PlacementStrategy::AxisFromFace.orientation(opposite));
+        }
+    }
+
+    #[test]
+    fn fixed_test() {
+        for orientation in Orientation::UNORIENTED.iter() {
+            for context in contexts() {
+                assert_eq!(PlacementStrategy::Fixed(orientation).orientation(context), orientation);
+            }
+        }
+    }
+
+    #[test]
+    fn free_rotate_test() {
+        for context in contexts() {
+            let orientation = PlacementStrategy::FreeRotate.orientation(context);
+            assert_eq!(orientation.up(), context.hit_face);
+            assert!(orientation.forward().is_orthogonal_to(context.hit_face));
+        }
+        // Placed on the floor while facing north, the front should face south (toward the player).
+        let floor = PlacementContext::new(Direction::PosY, Cardinal::North, Pitch::Down);
+        assert_eq!(PlacementStrategy::FreeRotate.orientation(floor).forward(), Direction::PosZ);
+        // Placed on the side of a block while looking down, the front should face up.
+        let wall = PlacementContext::new(Direction::PosX, Cardinal::West, Pitch::Down);
+        assert_eq!(PlacementStrategy::FreeRotate.orientation(wall).forward(), Direction::PosY);
+    }
+
+    #[test]
+    fn rules_test() {
+        let log = VoxelId::new(1);
+        let furnace = VoxelId::new(2);
+        let mut rules = PlacementRules::new(PlacementStrategy::Fixed(Orientation::UNORIENTED));
+        rules.set(log, PlacementStrategy::AxisFromFace);
+        rules.set(furnace, PlacementStrategy::FaceTowardPlayer);
+        let context = PlacementContext::new(Direction::NegX, Cardinal::East, Pitch::Level);
+        assert_eq!(rules.orientation(log, context).up(), Direction::PosX);
+        assert_eq!(rules.orientation(furnace, context).forward(), Direction::NegX);
+        assert_eq!(rules.orientation(VoxelId::new(3), context), Orientation::UNORIENTED);
+        assert_eq!(rules.remove(log), Some(PlacementStrategy::AxisFromFace));
+        assert_eq!(rules.strategy(log), rules.default_strategy());
+    }
+}
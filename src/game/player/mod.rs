@@ -1,4 +1,30 @@
+use mfworld::history::{EditHistory, VoxelEdit};
 
+use crate::game::mode::GameMode;
+
+#[derive(Debug, Default)]
 pub struct Player {
-    
-}
\ No newline at end of file
+    /// Undo history of voxel edits. Only recorded when [GameMode::undo_history] allows it.
+    pub(crate) edit_history: EditHistory,
+}
+
+impl Player {
+    #[inline]
+    #[must_use]
+    pub const fn edit_history(&self) -> &EditHistory {
+        &self.edit_history
+    }
+
+    /// Records an edit the player made, if `mode` keeps an undo history.
+    #[inline]
+    pub fn record_edit(&mut self, edit: VoxelEdit, mode: GameMode) {
+        if mode.undo_history() {
+            self.edit_history.record(edit);
+        }
+    }
+
+    #[inline]
+    pub const fn edit_history_mut(&mut self) -> &mut EditHistory {
+        &mut self.edit_history
+    }
+}
@@ -0,0 +1,180 @@
+//! Round trip and fuzz decoding tests for every saved game type (see [mfcereal::testing]).
+
+use std::collections::BTreeMap;
+
+use mfcereal::roundtrip_tests;
+use mfgeometry::Orientation;
+use mfprocgen::GeneratorConfig;
+use mfworld::{chunk::{BlockPos, ChunkPos}, recovery::Fallback, voxel::id::VoxelId};
+
+use crate::game::{
+    clock::{GameClock, GameSpeed},
+    crafting::{
+        filter::ItemFilter,
+        item::{family, form, ItemType},
+        recipe::Recipe,
+    },
+    drone::{DeliveryTask, Dispatcher, DronePort},
+    environment::Environment,
+    events::{Event, EventBus, RecordedEvent},
+    inventory::{ContainerId, DataKey, Inventories, ItemStack, SlotRef, StackData},
+    machine::{
+        recipe::{MachineKind, RecipeBook, RecipeMode, RecipeSelection},
+        sides::{LocalSide, SideConfig, SideMode},
+        sorter::SorterEntity,
+        MachineEntity,
+    },
+    mode::GameMode,
+    player::Player,
+    rules::{GameRules, AUTOSAVE_INTERVAL, EXPLOSION_GRIEFING},
+    save::{hash_history::{CommandBatch, HashHistory}, header::SaveHeader, registry::RegistryManifest},
+    vm::{self, op::{Op, Program}, Status, Vm},
+    world::{pregen::{PregenArea, PregenRecord}, World},
+    Game,
+};
+
+fn sides() -> SideConfig {
+    let mut sides = SideConfig::default();
+    sides.set(LocalSide::Back, SideMode::Input);
+    sides.set(LocalSide::Left, SideMode::Output);
+    sides.set(LocalSide::Top, SideMode::Both);
+    sides
+}
+
+fn selection() -> RecipeSelection {
+    let machine = MachineKind(1);
+    let mut book = RecipeBook::new();
+    let recipe = book.register(machine, Recipe {
+        inputs: vec![(ItemType::IronOre.id(), 2)],
+        outputs: vec![(ItemType::IronIngot.id(), 1)],
+        requires: None,
+    });
+    let mut selection = RecipeSelection::NONE;
+    selection.select(recipe, machine, &book).unwrap();
+    selection.set_mode(RecipeMode::Locked).unwrap();
+    selection
+}
+
+fn sorter() -> SorterEntity {
+    let mut sorter = SorterEntity::new(Orientation::ROTATE_Y, sides());
+    sorter.set_filters(LocalSide::Left, vec![ItemFilter::Family(family::COPPER), ItemFilter::Form(form::ROD)]).unwrap();
+    sorter.set_filters(LocalSide::Top, vec![ItemFilter::Item(ItemType::Quartz.id())]).unwrap();
+    sorter.overflow = Some(LocalSide::Top);
+    sorter
+}
+
+fn stack_data() -> StackData {
+    let mut data = StackData::EMPTY;
+    data.insert(DataKey(2), 40);
+    data.insert(DataKey(1), 7);
+    data
+}
+
+fn stack() -> ItemStack {
+    ItemStack::new(ItemType::CopperIngot.id(), 12).with_data(stack_data())
+}
+
+fn program() -> Program {
+    Program::new(vec![Op::Push(-3), Op::Store(1), Op::Load(1), Op::JumpIf(0), Op::Call(2), Op::Yield, Op::Halt])
+}
+
+fn scheduler() -> vm::Scheduler {
+    let mut scheduler = vm::Scheduler::new(vm::DEFAULT_BUDGET);
+    scheduler.spawn(program());
+    let removed = scheduler.spawn(Program::default());
+    scheduler.remove(removed);
+    scheduler.spawn(Program::new(vec![Op::Halt]));
+    scheduler
+}
+
+fn rules() -> GameRules {
+    let mut rules = GameRules::new();
+    rules.set_bool(EXPLOSION_GRIEFING, false);
+    rules.set_int(AUTOSAVE_INTERVAL, 60).unwrap();
+    rules
+}
+
+fn clock() -> GameClock {
+    let mut clock = GameClock::new(0);
+    clock.set_speed(GameSpeed::Double);
+    clock.advance(1234);
+    clock
+}
+
+fn hash_history() -> HashHistory {
+    let game = Game {
+        world: World::new(),
+        player: Player::default(),
+        mode: GameMode::Creative,
+        rules: rules(),
+        environment: Environment::new(7),
+    };
+    let mut history = HashHistory::new(10, 4, 2);
+    for tick in 0..=30 {
+        history.record(tick, &game, &BTreeMap::new());
+        history.record_batch(CommandBatch::encode_all(tick, &[tick as u32]));
+    }
+    history
+}
+
+fn pregen_record() -> PregenRecord {
+    let mut record = PregenRecord::new(PregenArea::new(-3, 12, 40));
+    record.completed.extend([(-1, 0), (0, 2)]);
+    record
+}
+
+fn header() -> SaveHeader {
+    let mut header = SaveHeader::new(42, GameMode::Survival, GeneratorConfig::preset("flats").unwrap());
+    header.rules = rules();
+    header.bounds = mfworld::bounds::HeightBounds::new(-64, 319).unwrap();
+    header
+}
+
+fn registry() -> RegistryManifest {
+    let mut book = RecipeBook::new();
+    book.register(MachineKind(2), Recipe {
+        inputs: vec![(ItemType::CopperIngot.id(), 1)],
+        outputs: vec![(ItemType::CopperRod.id(), 2), (ItemType::CopperIngot.id(), 1)],
+        requires: None,
+    });
+    RegistryManifest::current(&book)
+}
+
+fn dispatcher() -> Dispatcher {
+    let mut dispatcher = Dispatcher::new();
+    let port = |container, x| DronePort::new(ContainerId(container), BlockPos::new(x, 64, -2));
+    dispatcher.spawn(BlockPos::new(0, 65, 0), 500);
+    dispatcher.spawn(BlockPos::new(-3, 70, 1), 20);
+    dispatcher.submit(DeliveryTask::new(port(1, 2), port(2, 9), stack()));
+    dispatcher.submit(DeliveryTask::new(port(3, -7), port(2, 9), stack()));
+    let mut inventories = Inventories::new();
+    dispatcher.tick(&mut inventories, &mut EventBus::new(), |pos| pos.y > 64);
+    dispatcher
+}
+
+roundtrip_tests! {
+    game_mode_roundtrip: GameMode = GameMode::Creative;
+    item_filter_roundtrip: ItemFilter = ItemFilter::Family(family::IRON);
+    side_config_roundtrip: SideConfig = sides();
+    recipe_selection_roundtrip: RecipeSelection = selection();
+    machine_entity_roundtrip: MachineEntity = MachineEntity { recipe: selection(), ..MachineEntity::new(Orientation::ROTATE_Y, sides()) };
+    sorter_entity_roundtrip: SorterEntity = sorter();
+    stack_data_roundtrip: StackData = stack_data();
+    item_stack_roundtrip: ItemStack = stack();
+    slot_event_roundtrip: Event = Event::SlotChanged { slot: SlotRef::new(ContainerId(3), 1), stack: Some(stack()) };
+    block_event_roundtrip: Event = Event::BlockPlaced { pos: (-1, 70, 9), id: VoxelId::new(6), orientation: Orientation::ROTATE_Y };
+    recovery_event_roundtrip: Event = Event::ChunkRecovered { chunk: ChunkPos::new(2, -1, 5), fallback: Fallback::Regenerate, quarantined: true };
+    recorded_event_roundtrip: RecordedEvent = RecordedEvent { tick: 99, event: Event::ExplosionAt { pos: (0, 1, 2), power: 4 } };
+    op_roundtrip: Op = Op::Push(i64::MIN);
+    program_roundtrip: Program = program();
+    vm_status_roundtrip: Status = Status::Halted;
+    vm_roundtrip: Vm = Vm::new(program());
+    vm_scheduler_roundtrip: vm::Scheduler = scheduler();
+    game_rules_roundtrip: GameRules = rules();
+    game_clock_roundtrip: GameClock = clock();
+    hash_history_roundtrip: HashHistory = hash_history();
+    pregen_record_roundtrip: PregenRecord = pregen_record();
+    save_header_roundtrip: SaveHeader = header();
+    registry_manifest_roundtrip: RegistryManifest = registry();
+    dispatcher_roundtrip: Dispatcher = dispatcher();
+}
@@ -0,0 +1,512 @@
+//! Game rules: per-world settings that change how the simulation behaves.
+//!
+//! Every rule is a bool, an integer within a range, or one of a fixed set of names. The built-in rules
+//! have typed keys ([EXPLOSION_GRIEFING], [MACHINE_TICK_BUDGET], ...), and more can be
+//! [registered](GameRules::register) at startup. Rules are stored in the
+//! [save header](crate::game::save::header::SaveHeader), can be changed from the console with the
+//! `gamerule` [command](GameRules::command), and expose their values as [mfdata] [Value]s through
+//! [Record], so that defaults can be [loaded from data](GameRules::load_defaults) instead of being
+//! compiled in.
+
+use std::fmt;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfdata::object::{Field, FieldError, Record, Value};
+use mfworld::recovery::Fallback;
+
+use crate::game::{events::Event, vm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RuleId(u16);
+
+impl RuleId {
+    #[inline]
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_index(index: u16) -> Self {
+        Self(index)
+    }
+}
+
+/// A typed key for a boolean rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoolRule(RuleId);
+
+/// A typed key for an integer rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntRule(RuleId);
+
+/// A typed key for a rule that takes one of a fixed set of names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnumRule(RuleId);
+
+/// Explosions destroy voxels.
+pub const EXPLOSION_GRIEFING: BoolRule = BoolRule(RuleId(0));
+/// The number of VM instructions each programmable machine may run per tick.
+pub const MACHINE_TICK_BUDGET: IntRule = IntRule(RuleId(1));
+/// Seconds between autosaves. `0` disables autosaving.
+pub const AUTOSAVE_INTERVAL: IntRule = IntRule(RuleId(2));
+/// What to do with chunks that fail to load (see [GameRules::corrupt_chunks]).
+pub const CORRUPT_CHUNKS: EnumRule = EnumRule(RuleId(3));
+/// The time of day advances (see [crate::game::environment]).
+pub const DAYLIGHT_CYCLE: BoolRule = BoolRule(RuleId(4));
+/// The weather changes on its schedule (see [crate::game::environment]).
+pub const WEATHER_CYCLE: BoolRule = BoolRule(RuleId(5));
+
+/// The built-in rules. New rules are appended so that existing [RuleId]s stay stable.
+pub const BUILTIN: [RuleDef; 6] = [
+    RuleDef::bool("explosion_griefing", "Explosions destroy voxels.", true),
+    RuleDef::int("machine_tick_budget", "VM instructions each programmable machine may run per tick.", vm::HOST_CALL_COST as i64, 1_000_000, vm::DEFAULT_BUDGET as i64),
+    RuleDef::int("autosave_interval", "Seconds between autosaves (0 disables autosaving).", 0, 86_400, 300),
+    RuleDef::enumeration("corrupt_chunks", "What to do with chunks that fail to load.", &["fail", "regenerate", "empty"], 1),
+    RuleDef::bool("daylight_cycle", "The time of day advances.", true),
+    RuleDef::bool("weather_cycle", "The weather changes.", true),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleKind {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Enum(&'static [&'static str]),
+}
+
+impl RuleKind {
+    /// Whether `raw` is a valid value. Bools are stored as `0`/`1` and enums as the index of their name.
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, raw: i64) -> bool {
+        match self {
+            RuleKind::Bool => matches!(raw, 0 | 1),
+            RuleKind::Int { min, max } => min <= raw && raw <= max,
+            RuleKind::Enum(names) => 0 <= raw && raw < names.len() as i64,
+        }
+    }
+
+    #[must_use]
+    pub fn to_value(self, raw: i64) -> Value {
+        match self {
+            RuleKind::Bool => Value::Bool(raw != 0),
+            RuleKind::Int { .. } => Value::Int(raw),
+            RuleKind::Enum(names) => Value::String(names[raw as usize].to_owned()),
+        }
+    }
+
+    #[must_use]
+    pub fn from_value(self, value: &Value) -> Option<i64> {
+        let raw = match (self, value) {
+            (RuleKind::Bool, &Value::Bool(value)) => value as i64,
+            (RuleKind::Int { .. }, &Value::Int(value)) => value,
+            (RuleKind::Enum(names), Value::String(name)) => names.iter().position(|candidate| candidate.eq_ignore_ascii_case(name))? as i64,
+            _ => return None,
+        };
+        self.contains(raw).then_some(raw)
+    }
+
+    /// Parses a value typed at the console.
+    #[must_use]
+    pub fn parse(self, text: &str) -> Option<i64> {
+        let value = match self {
+            RuleKind::Bool => Value::Bool(text.parse().ok()?),
+            RuleKind::Int { .. } => Value::Int(text.parse().ok()?),
+            RuleKind::Enum(_) => Value::String(text.to_owned()),
+        };
+        self.from_value(&value)
+    }
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleKind::Bool => f.write_str("`true` or `false`"),
+            RuleKind::Int { min, max } => write!(f, "an integer from {min} to {max}"),
+            RuleKind::Enum(names) => {
+                f.write_str("one of ")?;
+                for (i, name) in names.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "`{name}`")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The definition of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuleDef {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: RuleKind,
+    default: i64,
+}
+
+impl RuleDef {
+    #[inline]
+    #[must_use]
+    pub const fn bool(name: &'static str, description: &'static str, default: bool) -> Self {
+        Self { name, description, kind: RuleKind::Bool, default: default as i64 }
+    }
+
+    /// Panics if `default` is outside of `min..=max`.
+    #[inline]
+    #[must_use]
+    pub const fn int(name: &'static str, description: &'static str, min: i64, max: i64, default: i64) -> Self {
+        assert!(min <= default && default <= max, "Default is out of range.");
+        Self { name, description, kind: RuleKind::Int { min, max }, default }
+    }
+
+    /// Panics if `default` is not an index into `names`.
+    #[inline]
+    #[must_use]
+    pub const fn enumeration(name: &'static str, description: &'static str, names: &'static [&'static str], default: usize) -> Self {
+        assert!(default < names.len(), "Default is out of range.");
+        Self { name, description, kind: RuleKind::Enum(names), default: default as i64 }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn default_value(&self) -> Value {
+        self.kind.to_value(self.default)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RuleError {
+    #[error("Unknown game rule: `{0}`")]
+    UnknownRule(String),
+    #[error("Invalid value for `{name}`: `{value}` (expected {expected})")]
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        expected: RuleKind,
+    },
+    #[error("Game rule registered twice: `{0}`")]
+    Duplicate(&'static str),
+    #[error("Invalid defaults on line {0} (expected `name = value`)")]
+    Malformed(usize),
+}
+
+/// The rules of one world.
+#[derive(Debug, Clone)]
+pub struct GameRules {
+    defs: Vec<RuleDef>,
+    values: Vec<i64>,
+    /// Saved rules that aren't registered (yet). Kept so that they survive a load and save.
+    unregistered: Vec<(String, Value)>,
+    changes: Vec<(RuleId, i64)>,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            defs: BUILTIN.to_vec(),
+            values: BUILTIN.iter().map(|def| def.default).collect(),
+            unregistered: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+}
+
+// Undrained change events aren't part of the rules.
+impl PartialEq for GameRules {
+    fn eq(&self, other: &Self) -> bool {
+        self.defs == other.defs && self.values == other.values && self.unregistered == other.unregistered
+    }
+}
+
+impl Eq for GameRules {}
+
+impl GameRules {
+    /// The built-in rules, at their defaults.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule. If a save already had a value for it, that value is used.
+    pub fn register(&mut self, def: RuleDef) -> Result<RuleId, RuleError> {
+        if self.id(def.name).is_some() {
+            return Err(RuleError::Duplicate(def.name));
+        }
+        let id = RuleId(u16::try_from(self.defs.len()).expect("Too many game rules."));
+        let saved = self.unregistered.iter()
+            .position(|(name, _)| name == def.name)
+            .map(|index| self.unregistered.remove(index).1);
+        self.defs.push(def);
+        self.values.push(saved.and_then(|value| def.kind.from_value(&value)).unwrap_or(def.default));
+        Ok(id)
+    }
+
+    #[inline]
+    pub fn id(&self, name: &str) -> Option<RuleId> {
+        self.defs.iter().position(|def| def.name == name).map(|index| RuleId(index as u16))
+    }
+
+    /// Panics if `id` is not registered.
+    #[inline]
+    pub fn def(&self, id: RuleId) -> &RuleDef {
+        &self.defs[id.index()]
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (RuleId, &RuleDef)> + '_ {
+        self.defs.iter().enumerate().map(|(index, def)| (RuleId(index as u16), def))
+    }
+
+    /// The current value of `id`, in the raw form described by [RuleKind::contains].
+    #[inline]
+    pub fn raw(&self, id: RuleId) -> i64 {
+        self.values[id.index()]
+    }
+
+    #[inline]
+    pub fn value(&self, id: RuleId) -> Value {
+        self.def(id).kind.to_value(self.raw(id))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get_bool(&self, rule: BoolRule) -> bool {
+        self.raw(rule.0) != 0
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get_int(&self, rule: IntRule) -> i64 {
+        self.raw(rule.0)
+    }
+
+    /// The index of the current name of `rule`.
+    #[inline]
+    #[must_use]
+    pub fn get_enum(&self, rule: EnumRule) -> usize {
+        self.raw(rule.0) as usize
+    }
+
+    /// [CORRUPT_CHUNKS] as a recovery [Fallback].
+    #[inline]
+    #[must_use]
+    pub fn corrupt_chunks(&self) -> Fallback {
+        Fallback::from_u8(self.get_enum(CORRUPT_CHUNKS) as u8).unwrap_or_default()
+    }
+
+    /// Sets a rule from its raw value, emitting a change event if it changed.
+    pub fn set_raw(&mut self, id: RuleId, raw: i64) -> Result<(), RuleError> {
+        let def = *self.def(id);
+        if !def.kind.contains(raw) {
+            return Err(RuleError::InvalidValue { name: def.name, value: raw.to_string(), expected: def.kind });
+        }
+        if std::mem::replace(&mut self.values[id.index()], raw) != raw {
+            self.changes.push((id, raw));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn set_bool(&mut self, rule: BoolRule, value: bool) {
+        self.set_raw(rule.0, value as i64).expect("Typed rule key has the wrong kind.");
+    }
+
+    pub fn set_int(&mut self, rule: IntRule, value: i64) -> Result<(), RuleError> {
+        self.set_raw(rule.0, value)
+    }
+
+    /// Parses `text` as a value of the rule called `name` and sets it.
+    pub fn set_from_str(&mut self, name: &str, text: &str) -> Result<(), RuleError> {
+        let id = self.id(name).ok_or_else(|| RuleError::UnknownRule(name.to_owned()))?;
+        let def = *self.def(id);
+        let raw = def.kind.parse(text)
+            .ok_or_else(|| RuleError::InvalidValue { name: def.name, value: text.to_owned(), expected: def.kind })?;
+        self.set_raw(id, raw)
+    }
+
+    /// Resets a rule to its default.
+    pub fn reset(&mut self, id: RuleId) {
+        let default = self.def(id).default;
+        self.set_raw(id, default).expect("Defaults are always valid.");
+    }
+
+    /// Replaces the defaults of registered rules (and resets those rules to them) from `name = value`
+    /// lines, with values written as at the console. Blank lines and lines starting with `#` are
+    /// skipped. Doesn't emit change events.
+    pub fn load_defaults(&mut self, text: &str) -> Result<(), RuleError> {
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once('=').ok_or(RuleError::Malformed(line_index + 1))?;
+            let (name, value) = (name.trim(), value.trim());
+            let id = self.id(name).ok_or_else(|| RuleError::UnknownRule(name.to_owned()))?;
+            let def = &mut self.defs[id.index()];
+            def.default = def.kind.parse(value)
+                .ok_or_else(|| RuleError::InvalidValue { name: def.name, value: value.to_owned(), expected: def.kind })?;
+            self.values[id.index()] = def.default;
+        }
+        Ok(())
+    }
+
+    /// Takes the [Event::GameRuleChanged] events for every change since the last drain.
+    #[inline]
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.changes.drain(..).map(|(rule, value)| Event::GameRuleChanged { rule, value })
+    }
+
+    /// Runs the `gamerule` console command with the arguments after the command name:
+    /// - No arguments lists every rule and its value.
+    /// - `<name>` shows the value and description of a rule.
+    /// - `<name> <value>` sets a rule.
+    /// - `<name> default` resets a rule.
+    ///
+    /// Returns the text to print.
+    pub fn command(&mut self, args: &str) -> Result<String, RuleError> {
+        let mut args = args.split_whitespace();
+        let Some(name) = args.next() else {
+            let lines: Vec<String> = self.iter().map(|(id, def)| format!("{} = {}", def.name, self.value(id))).collect();
+            return Ok(lines.join("\n"));
+        };
+        let id = self.id(name).ok_or_else(|| RuleError::UnknownRule(name.to_owned()))?;
+        match args.next() {
+            None => {
+                let def = self.def(id);
+                Ok(format!("{} = {} ({} Default: {}, expected {}.)", def.name, self.value(id), def.description, def.default_value(), def.kind))
+            }
+            Some("default") => {
+                self.reset(id);
+                Ok(format!("{} = {}", name, self.value(id)))
+            }
+            Some(value) => {
+                self.set_from_str(name, value)?;
+                Ok(format!("{} = {}", name, self.value(id)))
+            }
+        }
+    }
+}
+
+impl Record for GameRules {
+    fn fields(&self) -> Vec<Field> {
+        self.iter().map(|(id, def)| Field::new(def.name, self.value(id))).collect()
+    }
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), FieldError> {
+        let id = self.id(name).ok_or_else(|| FieldError::UnknownField(name.to_owned()))?;
+        let def = *self.def(id);
+        let raw = def.kind.from_value(&value).ok_or(FieldError::InvalidValue { name: def.name, value })?;
+        self.set_raw(id, raw).expect("Validated by from_value.");
+        Ok(())
+    }
+}
+
+// Layout: rule count (u64), followed by each rule as name (str), tag (u8), value.
+//      0 (Bool)  : bool
+//      1 (Int)   : i64
+//      2 (String): str
+// Rules missing from the save keep their defaults. Saved rules that aren't registered are kept
+// until they are.
+impl Encode for GameRules {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let fields = self.iter().map(|(id, def)| (def.name, self.value(id)));
+        let unregistered = self.unregistered.iter().map(|(name, value)| (name.as_str(), value.clone()));
+        let mut written = encoder.write_u64((self.defs.len() + self.unregistered.len()) as u64)?;
+        for (name, value) in fields.chain(unregistered) {
+            written += encoder.write_str(name)?;
+            written += match &value {
+                Value::Bool(value) => encoder.write_u8(0)? + encoder.write_bool(*value)?,
+                Value::Int(value) => encoder.write_u8(1)? + encoder.write_i64(*value)?,
+                Value::String(value) => encoder.write_u8(2)? + encoder.write_str(value)?,
+            };
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for GameRules {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut rules = Self::default();
+        let count = decoder.read_u64()?;
+        for _ in 0..count {
+            let name = decoder.read_str()?;
+            let value = match decoder.read_u8()? {
+                0 => Value::Bool(decoder.read_bool()?),
+                1 => Value::Int(decoder.read_i64()?),
+                2 => Value::String(decoder.read_str()?),
+                _ => return Err(DecodeError::InvalidData("unknown game rule tag")),
+            };
+            match rules.id(&name) {
+                Some(id) => {
+                    let raw = rules.def(id).kind.from_value(&value).ok_or(DecodeError::InvalidData("invalid game rule value"))?;
+                    rules.values[id.index()] = raw;
+                }
+                None => rules.unregistered.push((name, value)),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_rules_test() {
+        let mut rules = GameRules::new();
+        assert!(rules.get_bool(EXPLOSION_GRIEFING));
+        assert_eq!(rules.get_int(MACHINE_TICK_BUDGET), vm::DEFAULT_BUDGET as i64);
+        assert_eq!(rules.corrupt_chunks(), Fallback::Regenerate);
+
+        rules.command("explosion_griefing false").unwrap();
+        rules.command("corrupt_chunks Empty").unwrap();
+        rules.set_int(AUTOSAVE_INTERVAL, 300).unwrap();
+        assert!(!rules.get_bool(EXPLOSION_GRIEFING));
+        assert_eq!(rules.corrupt_chunks(), Fallback::Empty);
+        assert_eq!(rules.drain_events().collect::<Vec<_>>(), vec![
+            Event::GameRuleChanged { rule: EXPLOSION_GRIEFING.0, value: 0 },
+            Event::GameRuleChanged { rule: CORRUPT_CHUNKS.0, value: 2 },
+        ]);
+        assert!(matches!(rules.command("autosave_interval -1"), Err(RuleError::InvalidValue { .. })));
+        assert!(matches!(rules.command("keep_inventory true"), Err(RuleError::UnknownRule(_))));
+        assert!(rules.command("").unwrap().contains("machine_tick_budget = 1000"));
+        rules.command("explosion_griefing default").unwrap();
+        assert!(rules.get_bool(EXPLOSION_GRIEFING));
+
+        rules.load_defaults("# Server defaults\nautosave_interval = 60\n\nmachine_tick_budget=50").unwrap();
+        assert_eq!(rules.get_int(AUTOSAVE_INTERVAL), 60);
+        assert_eq!(rules.def(MACHINE_TICK_BUDGET.0).default_value(), Value::Int(50));
+        assert_eq!(rules.load_defaults("autosave_interval"), Err(RuleError::Malformed(1)));
+    }
+
+    #[test]
+    fn encode_test() {
+        const KEEP_INVENTORY: RuleDef = RuleDef::bool("keep_inventory", "Players keep their items.", false);
+        let mut modded = GameRules::new();
+        let keep_inventory = modded.register(KEEP_INVENTORY).unwrap();
+        assert_eq!(modded.register(KEEP_INVENTORY), Err(RuleError::Duplicate("keep_inventory")));
+        modded.set_raw(keep_inventory, 1).unwrap();
+        modded.set_int(MACHINE_TICK_BUDGET, 64).unwrap();
+
+        let mut bytes = Vec::new();
+        modded.encode(&mut bytes).unwrap();
+        // Loaded before the rule is registered, the value is kept until it is.
+        let mut loaded = GameRules::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.get_int(MACHINE_TICK_BUDGET), 64);
+        let mut resaved = Vec::new();
+        loaded.encode(&mut resaved).unwrap();
+        assert_eq!(resaved, bytes);
+        let keep_inventory = loaded.register(KEEP_INVENTORY).unwrap();
+        assert_eq!(loaded.value(keep_inventory), Value::Bool(true));
+    }
+}
@@ -0,0 +1,166 @@
+//! Compacting a save's chunk storage while the world isn't running.
+//!
+//! Chunks are stored one file per chunk (see [dir](super::dir)), so rewriting a chunk replaces its
+//! file instead of leaving a dead copy behind in a shared region file. What does build up in
+//! long-lived saves is:
+//!
+//! - Temporary files left by writes that were interrupted before they were moved into place. They're
+//!   never read, so they're deleted.
+//! - Chunks saved before their smallest [ChunkFormat](mfworld::chunk::format::ChunkFormat) existed,
+//!   and chunks whose palettes still have entries that no voxel uses any more. They're
+//!   [compacted](mfworld::chunk::stored::StoredChunk::compact) and re-encoded, and replaced if
+//!   that's smaller.
+//!
+//! Every chunk's checksum is checked before it's rewritten. Chunks that fail are left untouched for
+//! [recovery](mfworld::recovery) to deal with when they're loaded, and are listed in the report.
+//! Each replacement is atomic, so compaction can be interrupted at any point without losing a chunk.
+//! It must not run while the game is saving to the same directory.
+
+use std::{fs, path::PathBuf};
+
+use mfcereal::encode::Encode;
+use mfworld::{
+    chunk::ChunkPos,
+    portal::DimensionId,
+    recovery::{self, LoadFailure},
+};
+
+use super::dir::{decode_chunk_blob, SaveDir, SaveError};
+
+/// What [SaveDir::compact] did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompactReport {
+    /// The chunks whose checksums were checked.
+    pub chunks_checked: usize,
+    /// The chunks that were replaced with a smaller encoding.
+    pub chunks_rewritten: usize,
+    /// The leftover temporary files that were deleted.
+    pub removed: Vec<PathBuf>,
+    /// The chunks that failed to load, and were left as they are.
+    pub corrupt: Vec<(DimensionId, ChunkPos, LoadFailure)>,
+    /// The bytes freed on disk.
+    pub reclaimed_bytes: u64,
+}
+
+impl SaveDir {
+    /// Compacts every dimension of the save. See the [module docs](self).
+    pub fn compact(&self) -> Result<CompactReport, SaveError> {
+        let mut report = CompactReport::default();
+        self.remove_temp_files(self.root().to_owned(), &mut report)?;
+        for dimension in self.dimensions()? {
+            self.remove_temp_files(self.dimension_dir(dimension), &mut report)?;
+            for chunk in self.chunks(dimension)? {
+                self.compact_chunk(dimension, chunk, &mut report)?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn remove_temp_files(&self, dir: PathBuf, report: &mut CompactReport) -> Result<(), SaveError> {
+        let mut removed = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "tmp") && entry.file_type()?.is_file() {
+                report.reclaimed_bytes += entry.metadata()?.len();
+                removed.push(path);
+            }
+        }
+        removed.sort();
+        for path in removed {
+            fs::remove_file(&path)?;
+            report.removed.push(path);
+        }
+        Ok(())
+    }
+
+    fn compact_chunk(&self, dimension: DimensionId, chunk: ChunkPos, report: &mut CompactReport) -> Result<(), SaveError> {
+        let Some(blob) = self.read_chunk_blob(dimension, chunk)? else {
+            return Ok(());
+        };
+        report.chunks_checked += 1;
+        let mut stored = match decode_chunk_blob(&blob) {
+            Ok(stored) => stored,
+            Err(failure) => {
+                report.corrupt.push((dimension, chunk, failure));
+                return Ok(());
+            },
+        };
+        stored.compact();
+        let mut payload = Vec::new();
+        let Ok(_) = stored.encode(&mut payload);
+        let compacted = recovery::seal(&payload);
+        if compacted.len() < blob.len() {
+            self.write_chunk_blob(dimension, chunk, &compacted)?;
+            report.chunks_rewritten += 1;
+            report.reclaimed_bytes += (blob.len() - compacted.len()) as u64;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfworld::{
+        chunk::{format::ChunkFormat, stored::StoredChunk, voxel_index},
+        history::VoxelState,
+        voxel::id::VoxelId,
+    };
+
+    use super::*;
+    use crate::game::save::header::SaveHeader;
+
+    #[test]
+    fn compact_test() {
+        let root = std::env::temp_dir().join(format!("manufactory_compact_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let save = SaveDir::create(&root, &SaveHeader::default()).unwrap();
+        let dimension = DimensionId::OVERWORLD;
+
+        // A layered chunk saved in the old packed format, and one already at its smallest.
+        let mut layers = StoredChunk::new();
+        for index in 0..1024 {
+            layers.set(voxel_index(index % 32, 0, index / 32), VoxelState::new(VoxelId::new(1), Orientation::UNORIENTED));
+        }
+        let mut packed = Vec::new();
+        let Ok(_) = layers.encode_as(ChunkFormat::PACKED, &mut packed);
+        let old = ChunkPos::new(0, 0, 0);
+        save.write_chunk_blob(dimension, old, &recovery::seal(&packed)).unwrap();
+        let current = ChunkPos::new(1, 0, 0);
+        save.save_chunk(dimension, current, &layers).unwrap();
+        let corrupt = ChunkPos::new(2, 0, 0);
+        save.write_chunk_blob(dimension, corrupt, &[0; 20]).unwrap();
+        // A chunk whose palette still has a voxel that was replaced.
+        let mut stale = layers.clone();
+        for index in 0..1024 {
+            stale.set(voxel_index(index % 32, 1, index / 32), VoxelState::new(VoxelId::new(2), Orientation::UNORIENTED));
+            stale.set(voxel_index(index % 32, 1, index / 32), VoxelState::AIR);
+        }
+        let edited = ChunkPos::new(4, 0, 0);
+        save.save_chunk(dimension, edited, &stale).unwrap();
+        let stale_before = fs::metadata(save.chunk_path(dimension, edited)).unwrap().len();
+        let temp = save.chunk_path(dimension, ChunkPos::new(3, 0, 0)).with_extension("chunk.tmp");
+        fs::write(&temp, [1; 100]).unwrap();
+
+        let before = fs::metadata(save.chunk_path(dimension, old)).unwrap().len();
+        let report = save.compact().unwrap();
+        let after = fs::metadata(save.chunk_path(dimension, old)).unwrap().len();
+        let stale_after = fs::metadata(save.chunk_path(dimension, edited)).unwrap().len();
+        assert_eq!(report.chunks_checked, 4);
+        assert_eq!(report.chunks_rewritten, 2);
+        assert_eq!(report.removed, std::slice::from_ref(&temp));
+        assert_eq!(report.corrupt, [(dimension, corrupt, LoadFailure::ChecksumMismatch)]);
+        assert!(stale_after < stale_before);
+        assert_eq!(report.reclaimed_bytes, before - after + stale_before - stale_after + 100);
+        assert!(!temp.exists());
+        assert_eq!(save.load_chunk(dimension, old).unwrap().unwrap(), layers);
+        assert_eq!(save.load_chunk(dimension, edited).unwrap().unwrap(), layers);
+        assert_eq!(save.read_chunk_blob(dimension, corrupt).unwrap().unwrap(), [0; 20]);
+
+        // Nothing is left to do.
+        let again = save.compact().unwrap();
+        assert_eq!((again.chunks_rewritten, again.reclaimed_bytes), (0, 0));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,494 @@
+//! The layout of a save on disk.
+//!
+//! ```text
+//! <save>/
+//!     header.mfsv             The SaveHeader.
+//!     history.mfsv            The HashHistory, sealed with a checksum.
+//!     registry.mfsv           The RegistryManifest of the items and recipes the save refers to.
+//!     dim/<id>/<x>.<y>.<z>.chunk
+//!                             One StoredChunk per file, sealed with a checksum (see mfworld::recovery).
+//!     dim/<id>/<x>.<y>.<z>.structures
+//!                             The ChunkStructures of the chunk, sealed the same way.
+//!     dim/<id>/<x>.<z>.biomes The ColumnBiomes of the chunk column, sealed the same way.
+//!     dim/<id>/tickets.mfsv   The dimension's persistent ChunkTickets.
+//!     dim/<id>/pregen.mfsv    The PregenRecord of the dimension's last pregeneration.
+//!     dim/<id>/journal.mfwj   The voxel edits made since the dimension was last saved (see mfworld::journal).
+//!     signature.mfsv          The SaveSignature of everything else, in signed saves (`signing` feature).
+//! ```
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, UnexpectedEof},
+    encode::Encode,
+};
+#[cfg(feature = "signing")]
+use mfhash::{
+    domain::{DomainHash, SaveContent},
+    signing::{SaveSignature, SignatureError, SigningKey},
+};
+use mfworld::{
+    biome::ColumnBiomes,
+    chunk::{stored::StoredChunk, ChunkPos},
+    journal::JournalError,
+    portal::DimensionId,
+    recovery::{self, LoadFailure},
+    structure::ChunkStructures,
+    ticket::ChunkTickets,
+};
+
+use super::{hash_history::HashHistory, header::SaveHeader, registry::RegistryManifest};
+use crate::game::world::pregen::PregenRecord;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid save header: {0}")]
+    InvalidHeader(DecodeError<UnexpectedEof>),
+    #[error("Invalid chunk tickets: {0}")]
+    InvalidTickets(DecodeError<UnexpectedEof>),
+    #[error("Invalid hash history: {0}")]
+    InvalidHistory(LoadFailure),
+    #[error("Invalid registry manifest: {0}")]
+    InvalidRegistry(DecodeError<UnexpectedEof>),
+    #[error("Invalid pregeneration record: {0}")]
+    InvalidPregen(DecodeError<UnexpectedEof>),
+    #[error("Invalid journal: {0}")]
+    InvalidJournal(#[from] JournalError),
+    #[error("Chunk {chunk} failed to load: {failure}")]
+    InvalidChunk {
+        chunk: ChunkPos,
+        failure: LoadFailure,
+    },
+    #[error("The structures of chunk {chunk} failed to load: {failure}")]
+    InvalidStructures {
+        chunk: ChunkPos,
+        failure: LoadFailure,
+    },
+    #[error("The biomes of chunk column ({x}, {z}) failed to load: {failure}")]
+    InvalidBiomes {
+        x: i32,
+        z: i32,
+        failure: LoadFailure,
+    },
+    #[cfg(feature = "signing")]
+    #[error("Invalid save signature: {0}")]
+    InvalidSignature(DecodeError<UnexpectedEof>),
+    #[cfg(feature = "signing")]
+    #[error("The save isn't signed.")]
+    Unsigned,
+    #[cfg(feature = "signing")]
+    #[error("{0}")]
+    Signature(SignatureError),
+}
+
+/// A save directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveDir {
+    root: PathBuf,
+}
+
+impl SaveDir {
+    pub const HEADER_FILE: &'static str = "header.mfsv";
+    pub const DIMENSIONS_DIR: &'static str = "dim";
+    pub const CHUNK_EXTENSION: &'static str = "chunk";
+    pub const STRUCTURES_EXTENSION: &'static str = "structures";
+    pub const BIOMES_EXTENSION: &'static str = "biomes";
+    pub const TICKETS_FILE: &'static str = "tickets.mfsv";
+    pub const HISTORY_FILE: &'static str = "history.mfsv";
+    pub const REGISTRY_FILE: &'static str = "registry.mfsv";
+    pub const PREGEN_FILE: &'static str = "pregen.mfsv";
+    pub const JOURNAL_FILE: &'static str = "journal.mfwj";
+    pub const SIGNATURE_FILE: &'static str = "signature.mfsv";
+
+    /// Creates a new save at `root` with `header`. `root` may already exist, but must not contain a save.
+    pub fn create<P: AsRef<Path>>(root: P, header: &SaveHeader) -> Result<Self, SaveError> {
+        let save = Self { root: root.as_ref().to_owned() };
+        fs::create_dir_all(&save.root)?;
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        fs::OpenOptions::new().write(true).create_new(true).open(save.root.join(Self::HEADER_FILE))
+            .and_then(|mut file| io::Write::write_all(&mut file, &bytes))?;
+        Ok(save)
+    }
+
+    /// Opens the save at `root`, which must have a header.
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, SaveError> {
+        let save = Self { root: root.as_ref().to_owned() };
+        if !save.root.join(Self::HEADER_FILE).is_file() {
+            return Err(SaveError::Io(io::Error::new(io::ErrorKind::NotFound, "no save header")));
+        }
+        Ok(save)
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn read_header(&self) -> Result<SaveHeader, SaveError> {
+        let bytes = fs::read(self.root.join(Self::HEADER_FILE))?;
+        SaveHeader::decode(&mut bytes.as_slice()).map_err(SaveError::InvalidHeader)
+    }
+
+    pub fn write_header(&self, header: &SaveHeader) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        write_replacing(&self.root.join(Self::HEADER_FILE), &bytes)
+    }
+
+    /// Reads the state hash history. A save without one has an empty history.
+    pub fn read_history(&self) -> Result<HashHistory, SaveError> {
+        let blob = match fs::read(self.root.join(Self::HISTORY_FILE)) {
+            Ok(blob) => blob,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashHistory::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let decode = || -> Result<HashHistory, LoadFailure> {
+            let mut payload = recovery::unseal(&blob)?;
+            Ok(HashHistory::decode(&mut payload)?)
+        };
+        decode().map_err(SaveError::InvalidHistory)
+    }
+
+    pub fn write_history(&self, history: &HashHistory) -> Result<(), SaveError> {
+        let mut payload = Vec::new();
+        history.encode(&mut payload).expect("Encoding to a Vec can't fail.");
+        write_replacing(&self.root.join(Self::HISTORY_FILE), &recovery::seal(&payload))
+    }
+
+    /// Reads the registry manifest, or `None` for saves written before manifests were.
+    pub fn read_registry(&self) -> Result<Option<RegistryManifest>, SaveError> {
+        match fs::read(self.root.join(Self::REGISTRY_FILE)) {
+            Ok(bytes) => RegistryManifest::decode(&mut bytes.as_slice()).map(Some).map_err(SaveError::InvalidRegistry),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn write_registry(&self, manifest: &RegistryManifest) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        manifest.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        write_replacing(&self.root.join(Self::REGISTRY_FILE), &bytes)
+    }
+
+    #[inline]
+    pub fn dimension_dir(&self, dimension: DimensionId) -> PathBuf {
+        self.root.join(Self::DIMENSIONS_DIR).join(dimension.0.to_string())
+    }
+
+    #[inline]
+    pub fn chunk_path(&self, dimension: DimensionId, chunk: ChunkPos) -> PathBuf {
+        self.dimension_dir(dimension).join(format!("{}.{}.{}.{}", chunk.x, chunk.y, chunk.z, Self::CHUNK_EXTENSION))
+    }
+
+    #[inline]
+    pub fn structures_path(&self, dimension: DimensionId, chunk: ChunkPos) -> PathBuf {
+        self.dimension_dir(dimension).join(format!("{}.{}.{}.{}", chunk.x, chunk.y, chunk.z, Self::STRUCTURES_EXTENSION))
+    }
+
+    #[inline]
+    pub fn biomes_path(&self, dimension: DimensionId, x: i32, z: i32) -> PathBuf {
+        self.dimension_dir(dimension).join(format!("{x}.{z}.{}", Self::BIOMES_EXTENSION))
+    }
+
+    /// The path of the journal of `dimension`, which the game appends its voxel edits to between saves.
+    #[inline]
+    pub fn journal_path(&self, dimension: DimensionId) -> PathBuf {
+        self.dimension_dir(dimension).join(Self::JOURNAL_FILE)
+    }
+
+    /// Reads the tickets of `dimension`. A dimension without saved tickets has none.
+    pub fn read_tickets(&self, dimension: DimensionId) -> Result<ChunkTickets, SaveError> {
+        match fs::read(self.dimension_dir(dimension).join(Self::TICKETS_FILE)) {
+            Ok(bytes) => ChunkTickets::decode(&mut bytes.as_slice()).map_err(SaveError::InvalidTickets),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(ChunkTickets::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn write_tickets(&self, dimension: DimensionId, tickets: &ChunkTickets) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        tickets.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        fs::create_dir_all(self.dimension_dir(dimension))?;
+        write_replacing(&self.dimension_dir(dimension).join(Self::TICKETS_FILE), &bytes)
+    }
+
+    /// Reads the record of the last pregeneration of `dimension`, if there was one.
+    pub fn read_pregen(&self, dimension: DimensionId) -> Result<Option<PregenRecord>, SaveError> {
+        match fs::read(self.dimension_dir(dimension).join(Self::PREGEN_FILE)) {
+            Ok(bytes) => PregenRecord::decode(&mut bytes.as_slice()).map(Some).map_err(SaveError::InvalidPregen),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn write_pregen(&self, dimension: DimensionId, record: &PregenRecord) -> Result<(), SaveError> {
+        let mut bytes = Vec::new();
+        record.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        fs::create_dir_all(self.dimension_dir(dimension))?;
+        write_replacing(&self.dimension_dir(dimension).join(Self::PREGEN_FILE), &bytes)
+    }
+
+    /// The dimensions that have a directory in the save, in order.
+    pub fn dimensions(&self) -> Result<Vec<DimensionId>, SaveError> {
+        let dir = self.root.join(Self::DIMENSIONS_DIR);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut dimensions = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if let Some(id) = entry.file_name().to_str().and_then(|name| name.parse().ok()) && entry.path().is_dir() {
+                dimensions.push(DimensionId(id));
+            }
+        }
+        dimensions.sort();
+        Ok(dimensions)
+    }
+
+    /// The chunks stored for `dimension`, in order. Files that aren't named like chunks are ignored.
+    pub fn chunks(&self, dimension: DimensionId) -> Result<Vec<ChunkPos>, SaveError> {
+        let dir = self.dimension_dir(dimension);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut chunks = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            if let Some(chunk) = entry?.file_name().to_str().and_then(parse_chunk_file_name) {
+                chunks.push(chunk);
+            }
+        }
+        chunks.sort();
+        Ok(chunks)
+    }
+
+    /// Reads the sealed blob of a chunk, or `None` if the chunk isn't stored.
+    pub fn read_chunk_blob(&self, dimension: DimensionId, chunk: ChunkPos) -> Result<Option<Vec<u8>>, SaveError> {
+        match fs::read(self.chunk_path(dimension, chunk)) {
+            Ok(blob) => Ok(Some(blob)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Replaces the sealed blob of a chunk. The blob isn't checked.
+    pub fn write_chunk_blob(&self, dimension: DimensionId, chunk: ChunkPos, blob: &[u8]) -> Result<(), SaveError> {
+        fs::create_dir_all(self.dimension_dir(dimension))?;
+        write_replacing(&self.chunk_path(dimension, chunk), blob)
+    }
+
+    /// Loads a chunk, or `None` if the chunk isn't stored.
+    pub fn load_chunk(&self, dimension: DimensionId, chunk: ChunkPos) -> Result<Option<StoredChunk>, SaveError> {
+        let Some(blob) = self.read_chunk_blob(dimension, chunk)? else {
+            return Ok(None);
+        };
+        decode_chunk_blob(&blob).map(Some).map_err(|failure| SaveError::InvalidChunk { chunk, failure })
+    }
+
+    pub fn save_chunk(&self, dimension: DimensionId, chunk: ChunkPos, stored: &StoredChunk) -> Result<(), SaveError> {
+        let mut payload = Vec::new();
+        stored.encode(&mut payload).expect("Encoding to a Vec can't fail.");
+        self.write_chunk_blob(dimension, chunk, &recovery::seal(&payload))
+    }
+
+    /// Loads the structure references of a chunk, or `None` if they aren't stored (the chunk hasn't
+    /// been saved, or was saved before structures were).
+    pub fn load_chunk_structures(&self, dimension: DimensionId, chunk: ChunkPos) -> Result<Option<ChunkStructures>, SaveError> {
+        let blob = match fs::read(self.structures_path(dimension, chunk)) {
+            Ok(blob) => blob,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let invalid = |failure| SaveError::InvalidStructures { chunk, failure };
+        let mut payload = recovery::unseal(&blob).map_err(invalid)?;
+        let structures = ChunkStructures::decode(&mut payload).map_err(|err| invalid(err.into()))?;
+        if !payload.is_empty() {
+            return Err(invalid(LoadFailure::Malformed));
+        }
+        Ok(Some(structures))
+    }
+
+    pub fn save_chunk_structures(&self, dimension: DimensionId, chunk: ChunkPos, structures: &ChunkStructures) -> Result<(), SaveError> {
+        let mut payload = Vec::new();
+        structures.encode(&mut payload).expect("Encoding to a Vec can't fail.");
+        fs::create_dir_all(self.dimension_dir(dimension))?;
+        write_replacing(&self.structures_path(dimension, chunk), &recovery::seal(&payload))
+    }
+
+    /// Loads the biomes of the chunk column `(x, z)`, or `None` if they aren't stored (no chunk in
+    /// the column has been saved, or they were saved before biomes were).
+    pub fn load_column_biomes(&self, dimension: DimensionId, x: i32, z: i32) -> Result<Option<ColumnBiomes>, SaveError> {
+        let blob = match fs::read(self.biomes_path(dimension, x, z)) {
+            Ok(blob) => blob,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let invalid = |failure| SaveError::InvalidBiomes { x, z, failure };
+        let mut payload = recovery::unseal(&blob).map_err(invalid)?;
+        let biomes = ColumnBiomes::decode(&mut payload).map_err(|err| invalid(err.into()))?;
+        if !payload.is_empty() {
+            return Err(invalid(LoadFailure::Malformed));
+        }
+        Ok(Some(biomes))
+    }
+
+    pub fn save_column_biomes(&self, dimension: DimensionId, x: i32, z: i32, biomes: &ColumnBiomes) -> Result<(), SaveError> {
+        let mut payload = Vec::new();
+        biomes.encode(&mut payload).expect("Encoding to a Vec can't fail.");
+        fs::create_dir_all(self.dimension_dir(dimension))?;
+        write_replacing(&self.biomes_path(dimension, x, z), &recovery::seal(&payload))
+    }
+}
+
+/// Signing, for servers distributing an authoritative world (see [mfhash::signing]). A save is
+/// signed once everything in it is written, and any write after that invalidates the signature
+/// until it's signed again.
+#[cfg(feature = "signing")]
+impl SaveDir {
+    /// The canonical hash of the save: every file but the signature and leftover temp files, in
+    /// path order, each as its path relative to the root (`/` separated) and its contents, both
+    /// length prefixed.
+    pub fn content_hash(&self) -> Result<DomainHash<SaveContent>, SaveError> {
+        let mut files = Vec::new();
+        collect_files(&self.root, String::new(), &mut files)?;
+        files.retain(|name| name != Self::SIGNATURE_FILE && !name.ends_with(".tmp"));
+        files.sort();
+        let mut hasher = blake3::Hasher::new();
+        for name in files {
+            let bytes = fs::read(self.root.join(&name))?;
+            hasher.update(&(name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(&bytes);
+        }
+        Ok(DomainHash::from_bytes(*hasher.finalize().as_bytes()))
+    }
+
+    /// Signs the save as it is now with `key`, replacing any previous signature.
+    pub fn sign(&self, key: &SigningKey) -> Result<SaveSignature, SaveError> {
+        let signature = key.sign_hash(self.content_hash()?);
+        let mut bytes = Vec::new();
+        signature.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        write_replacing(&self.root.join(Self::SIGNATURE_FILE), &bytes)?;
+        Ok(signature)
+    }
+
+    /// Reads the signature, or `None` if the save isn't signed.
+    pub fn read_signature(&self) -> Result<Option<SaveSignature>, SaveError> {
+        let bytes = match fs::read(self.root.join(Self::SIGNATURE_FILE)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        SaveSignature::decode(&mut bytes.as_slice()).map(Some).map_err(SaveError::InvalidSignature)
+    }
+
+    /// Checks that the save is intact and was signed with the key for `passphrase`.
+    pub fn verify_signature(&self, passphrase: &str) -> Result<(), SaveError> {
+        let signature = self.read_signature()?.ok_or(SaveError::Unsigned)?;
+        signature.verify_hash(self.content_hash()?, passphrase).map_err(SaveError::Signature)
+    }
+}
+
+/// Adds the path of every file under `dir` to `files`, relative to the save root.
+#[cfg(feature = "signing")]
+fn collect_files(dir: &Path, prefix: String, files: &mut Vec<String>) -> Result<(), SaveError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().into_string()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "save file name isn't UTF-8"))?;
+        let name = if prefix.is_empty() { name } else { format!("{prefix}/{name}") };
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), name, files)?;
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Checks and decodes the sealed blob of a chunk.
+pub(crate) fn decode_chunk_blob(blob: &[u8]) -> Result<StoredChunk, LoadFailure> {
+    let mut payload = recovery::unseal(blob)?;
+    let stored = StoredChunk::decode(&mut payload)?;
+    if !payload.is_empty() {
+        return Err(LoadFailure::Malformed);
+    }
+    Ok(stored)
+}
+
+/// Parses `<x>.<y>.<z>.chunk`.
+pub fn parse_chunk_file_name(name: &str) -> Option<ChunkPos> {
+    let stem = name.strip_suffix(SaveDir::CHUNK_EXTENSION)?.strip_suffix('.')?;
+    let mut parts = stem.split('.').map(str::parse::<i32>);
+    let chunk = ChunkPos::new(parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+    parts.next().is_none().then_some(chunk)
+}
+
+/// Writes `bytes` to a temporary file next to `path`, then moves it over `path`, so that a crash
+/// never leaves a half-written file behind.
+fn write_replacing(path: &Path, bytes: &[u8]) -> Result<(), SaveError> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfworld::{chunk::voxel_index, history::VoxelState, ticket::Ticket, voxel::id::VoxelId};
+
+    use super::*;
+    use crate::game::save::hash_history::CommandBatch;
+
+    #[test]
+    fn save_dir_test() {
+        let root = std::env::temp_dir().join(format!("manufactory_save_dir_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let header = SaveHeader::default();
+        let save = SaveDir::create(&root, &header).unwrap();
+        assert!(SaveDir::create(&root, &header).is_err());
+        assert_eq!(SaveDir::open(&root).unwrap().read_header().unwrap(), header);
+
+        let mut stored = StoredChunk::new();
+        stored.set(voxel_index(1, 2, 3), VoxelState::new(VoxelId::new(4), Orientation::UNORIENTED));
+        let chunk = ChunkPos::new(-1, 0, 2);
+        save.save_chunk(DimensionId(3), chunk, &stored).unwrap();
+        save.save_chunk(DimensionId::OVERWORLD, ChunkPos::ORIGIN, &StoredChunk::new()).unwrap();
+        fs::write(save.dimension_dir(DimensionId(3)).join("notes.txt"), "").unwrap();
+        assert_eq!(save.dimensions().unwrap(), [DimensionId::OVERWORLD, DimensionId(3)]);
+        assert_eq!(save.chunks(DimensionId(3)).unwrap(), [chunk]);
+        assert_eq!(save.load_chunk(DimensionId(3), chunk).unwrap(), Some(stored));
+        assert_eq!(save.load_chunk(DimensionId(3), ChunkPos::ORIGIN).unwrap(), None);
+
+        assert!(save.read_tickets(DimensionId(3)).unwrap().is_empty());
+        let mut tickets = ChunkTickets::new();
+        tickets.add(Ticket::machine(chunk));
+        save.write_tickets(DimensionId(3), &tickets).unwrap();
+        assert_eq!(save.read_tickets(DimensionId(3)).unwrap(), tickets);
+        assert_eq!(save.chunks(DimensionId(3)).unwrap(), [chunk]);
+
+        assert_eq!(save.read_history().unwrap(), HashHistory::default());
+        let mut history = HashHistory::new(1, 8, 8);
+        history.record_batch(CommandBatch { tick: 3, commands: vec![1, 2] });
+        save.write_history(&history).unwrap();
+        assert_eq!(save.read_history().unwrap(), history);
+
+        let mut blob = save.read_chunk_blob(DimensionId(3), chunk).unwrap().unwrap();
+        *blob.last_mut().unwrap() ^= 1;
+        save.write_chunk_blob(DimensionId(3), chunk, &blob).unwrap();
+        assert!(matches!(
+            save.load_chunk(DimensionId(3), chunk),
+            Err(SaveError::InvalidChunk { failure: LoadFailure::ChecksumMismatch, .. }),
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
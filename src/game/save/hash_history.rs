@@ -0,0 +1,322 @@
+//! A rolling history of state hashes, saved with the world for tracking down corruption.
+//!
+//! Every `interval` ticks the game records its [content hash](Game::content_hash) (the state hash
+//! and the loaded chunks) in a [HashHistory], along with the last few command batches it ran. The
+//! history is saved next to the header, so a save that loads with a different hash than it was
+//! saved with is caught straight away ([HashHistory::verify], run by
+//! [open_world](super::open::open_world)), and two saves of the same world (from a client and the server, or
+//! from before and after a bad patch) can be compared to find the last tick they agreed on
+//! ([HashHistory::compare]). The commands recorded after that tick are the ones to replay.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfhash::Hash128;
+use mfworld::chunk::{stored::StoredChunk, ChunkPos};
+
+use crate::game::Game;
+
+/// The state hash of the game at the end of a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateSnapshot {
+    pub tick: u64,
+    pub hash: Hash128,
+}
+
+/// The commands run during a tick, encoded by whoever issued them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandBatch {
+    pub tick: u64,
+    pub commands: Vec<u8>,
+}
+
+impl CommandBatch {
+    /// Encodes every command in `commands` into one batch.
+    pub fn encode_all<'a, T: Encode + 'a, I: IntoIterator<Item = &'a T>>(tick: u64, commands: I) -> Self {
+        let mut bytes = Vec::new();
+        for command in commands {
+            command.encode(&mut bytes).expect("Encoding to a Vec can't fail.");
+        }
+        Self { tick, commands: bytes }
+    }
+}
+
+/// The loaded game doesn't have the state hash its save recorded last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("State hash mismatch at tick {tick}: saved {expected}, loaded {actual}")]
+pub struct StateMismatch {
+    pub tick: u64,
+    pub expected: Hash128,
+    pub actual: Hash128,
+}
+
+/// Where two hash histories stop agreeing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Divergence {
+    /// Every tick recorded by both histories has the same hash.
+    None,
+    /// The histories have no tick in common, so they can't be compared.
+    NoOverlap,
+    /// `tick` is the first tick recorded by both whose hashes differ, and `last_match` is the last
+    /// tick before it that both recorded with the same hash, if there is one.
+    At { tick: u64, last_match: Option<u64> },
+}
+
+/// The most recent state hashes and command batches of a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashHistory {
+    interval: u64,
+    snapshot_capacity: usize,
+    batch_capacity: usize,
+    /// Oldest first.
+    snapshots: VecDeque<StateSnapshot>,
+    /// Oldest first.
+    batches: VecDeque<CommandBatch>,
+}
+
+impl Default for HashHistory {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL, Self::DEFAULT_SNAPSHOTS, Self::DEFAULT_BATCHES)
+    }
+}
+
+impl HashHistory {
+    pub const DEFAULT_INTERVAL: u64 = 20;
+    pub const DEFAULT_SNAPSHOTS: usize = 256;
+    pub const DEFAULT_BATCHES: usize = 64;
+
+    /// Records a snapshot every `interval` ticks, keeping the newest `snapshots` snapshots and `batches`
+    /// command batches.
+    #[must_use]
+    pub fn new(interval: u64, snapshots: usize, batches: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            snapshot_capacity: snapshots.max(1),
+            batch_capacity: batches,
+            snapshots: VecDeque::new(),
+            batches: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The snapshots, oldest first.
+    #[inline]
+    pub fn snapshots(&self) -> impl DoubleEndedIterator<Item = StateSnapshot> + ExactSizeIterator + '_ {
+        self.snapshots.iter().copied()
+    }
+
+    /// The command batches, oldest first.
+    #[inline]
+    pub fn batches(&self) -> impl DoubleEndedIterator<Item = &CommandBatch> + ExactSizeIterator + '_ {
+        self.batches.iter()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn newest(&self) -> Option<StateSnapshot> {
+        self.snapshots.back().copied()
+    }
+
+    /// The hash recorded for `tick`, if it's still in the history.
+    #[must_use]
+    pub fn hash_at(&self, tick: u64) -> Option<Hash128> {
+        self.snapshots.binary_search_by_key(&tick, |snapshot| snapshot.tick).ok().map(|index| self.snapshots[index].hash)
+    }
+
+    /// Records the state of `game` and its loaded `chunks` at the end of `tick`, if `tick` falls
+    /// on the interval.
+    pub fn record(&mut self, tick: u64, game: &Game, chunks: &BTreeMap<ChunkPos, StoredChunk>) {
+        if tick.is_multiple_of(self.interval) {
+            self.snapshot(tick, game, chunks);
+        }
+    }
+
+    /// Records the state of `game` and its loaded `chunks` at the end of `tick`, whatever the
+    /// interval. The game records a snapshot when it saves, so that [Self::verify] can check the
+    /// state it saved. Only what the save restores can be checked, so that snapshot is of the
+    /// [restored_game](super::open::restored_game) and the chunks it loads.
+    pub fn snapshot(&mut self, tick: u64, game: &Game, chunks: &BTreeMap<ChunkPos, StoredChunk>) {
+        self.push_snapshot(StateSnapshot { tick, hash: game.content_hash(chunks) });
+    }
+
+    fn push_snapshot(&mut self, snapshot: StateSnapshot) {
+        // Ticks only move forwards. Anything at or after `tick` is from a timeline that was rolled back.
+        while self.snapshots.back().is_some_and(|last| last.tick >= snapshot.tick) {
+            self.snapshots.pop_back();
+        }
+        if self.snapshots.len() == self.snapshot_capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Records the commands run during a tick.
+    pub fn record_batch(&mut self, batch: CommandBatch) {
+        if self.batch_capacity == 0 {
+            return;
+        }
+        if self.batches.len() == self.batch_capacity {
+            self.batches.pop_front();
+        }
+        self.batches.push_back(batch);
+    }
+
+    /// Checks that `game` and its loaded `chunks` have the newest recorded hash. Called after
+    /// loading a save.
+    pub fn verify(&self, game: &Game, chunks: &BTreeMap<ChunkPos, StoredChunk>) -> Result<(), StateMismatch> {
+        let Some(newest) = self.newest() else {
+            return Ok(());
+        };
+        let actual = game.content_hash(chunks);
+        if actual != newest.hash {
+            return Err(StateMismatch { tick: newest.tick, expected: newest.hash, actual });
+        }
+        Ok(())
+    }
+
+    /// Finds the first tick recorded by both histories where their hashes differ.
+    #[must_use]
+    pub fn compare(&self, other: &HashHistory) -> Divergence {
+        let mut last_match = None;
+        let mut overlap = false;
+        for snapshot in &self.snapshots {
+            let Some(hash) = other.hash_at(snapshot.tick) else {
+                continue;
+            };
+            overlap = true;
+            if hash != snapshot.hash {
+                return Divergence::At { tick: snapshot.tick, last_match };
+            }
+            last_match = Some(snapshot.tick);
+        }
+        if overlap { Divergence::None } else { Divergence::NoOverlap }
+    }
+
+    /// The command batches recorded after `tick`, which are the ones to replay from a state at `tick`.
+    pub fn batches_after(&self, tick: u64) -> impl Iterator<Item = &CommandBatch> + '_ {
+        self.batches.iter().filter(move |batch| batch.tick > tick)
+    }
+}
+
+// Layout: interval (u64), snapshot capacity (u32), batch capacity (u32),
+//      snapshot count (u32), each as tick (u64), hash (u128),
+//      batch count (u32), each as tick (u64), commands (usize length, bytes).
+impl Encode for HashHistory {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u64(self.interval)?
+            + encoder.write_u32(self.snapshot_capacity as u32)?
+            + encoder.write_u32(self.batch_capacity as u32)?
+            + encoder.write_u32(self.snapshots.len() as u32)?;
+        for snapshot in &self.snapshots {
+            written += encoder.write_u64(snapshot.tick)? + snapshot.hash.encode(encoder)?;
+        }
+        written += encoder.write_u32(self.batches.len() as u32)?;
+        for batch in &self.batches {
+            written += encoder.write_u64(batch.tick)? + encoder.write_u8_slice(&batch.commands, true)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for HashHistory {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let interval = decoder.read_u64()?;
+        let snapshot_capacity = decoder.read_u32()? as usize;
+        let batch_capacity = decoder.read_u32()? as usize;
+        if interval == 0 || snapshot_capacity == 0 {
+            return Err(DecodeError::InvalidData("invalid hash history settings"));
+        }
+        let mut history = Self::new(interval, snapshot_capacity, batch_capacity);
+        let snapshots = decoder.read_u32()? as usize;
+        if snapshots > snapshot_capacity {
+            return Err(DecodeError::InvalidData("too many state snapshots"));
+        }
+        for _ in 0..snapshots {
+            let snapshot = StateSnapshot { tick: decoder.read_u64()?, hash: Hash128::decode(decoder)? };
+            if history.newest().is_some_and(|last| last.tick >= snapshot.tick) {
+                return Err(DecodeError::InvalidData("state snapshots out of order"));
+            }
+            history.snapshots.push_back(snapshot);
+        }
+        let batches = decoder.read_u32()? as usize;
+        if batches > batch_capacity {
+            return Err(DecodeError::InvalidData("too many command batches"));
+        }
+        for _ in 0..batches {
+            history.batches.push_back(CommandBatch { tick: decoder.read_u64()?, commands: decoder.read_u8_vec()? });
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfworld::{history::VoxelState, ticket::{ChunkTickets, Ticket}, voxel::id::VoxelId};
+
+    use super::*;
+    use crate::game::{environment::Environment, mode::GameMode, player::Player, rules::GameRules, world::World};
+
+    fn game() -> Game {
+        Game {
+            world: World::new(),
+            player: Player::default(),
+            mode: GameMode::Survival,
+            rules: GameRules::new(),
+            environment: Environment::new(0),
+        }
+    }
+
+    #[test]
+    fn hash_history_test() {
+        let mut game = game();
+        let mut tickets = ChunkTickets::new();
+        let mut history = HashHistory::new(10, 4, 2);
+        for tick in 0..=60 {
+            if tick == 35 {
+                tickets.add(Ticket::player(ChunkPos::ORIGIN, 1));
+                game.world.sync_tickets(&mut tickets);
+            }
+            history.record(tick, &game, &BTreeMap::new());
+            history.record_batch(CommandBatch::encode_all(tick, &[tick as u32]));
+        }
+        assert_eq!(history.snapshots().map(|snapshot| snapshot.tick).collect::<Vec<_>>(), [30, 40, 50, 60]);
+        assert_ne!(history.hash_at(30), history.hash_at(40));
+        assert_eq!(history.batches().map(|batch| batch.tick).collect::<Vec<_>>(), [59, 60]);
+        assert!(history.verify(&game, &BTreeMap::new()).is_ok());
+
+        let mut bytes = Vec::new();
+        history.encode(&mut bytes).unwrap();
+        let loaded = HashHistory::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, history);
+        assert_eq!(history.compare(&loaded), Divergence::None);
+
+        // Another run that went wrong after tick 40.
+        let mut other = history.clone();
+        other.snapshot(50, &self::game(), &BTreeMap::new());
+        assert_eq!(history.compare(&other), Divergence::At { tick: 50, last_match: Some(40) });
+        assert_eq!(other.newest().map(|snapshot| snapshot.tick), Some(50));
+        assert!(matches!(other.verify(&game, &BTreeMap::new()), Err(StateMismatch { tick: 50, .. })));
+
+        // The contents of the loaded chunks are part of the hash.
+        let chunks = BTreeMap::from([(ChunkPos::ORIGIN, StoredChunk::new())]);
+        history.snapshot(61, &game, &chunks);
+        assert!(history.verify(&game, &chunks).is_ok());
+        assert!(history.verify(&game, &BTreeMap::new()).is_err());
+        let mut edited = chunks.clone();
+        edited.get_mut(&ChunkPos::ORIGIN).unwrap().set(0, VoxelState::new(VoxelId::new(3), Orientation::UNORIENTED));
+        assert!(matches!(history.verify(&game, &edited), Err(StateMismatch { tick: 61, .. })));
+        assert_eq!(history.batches_after(59).count(), 1);
+
+        assert_eq!(history.compare(&HashHistory::default()), Divergence::NoOverlap);
+    }
+}
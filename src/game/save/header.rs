@@ -0,0 +1,156 @@
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use mfprocgen::{stage::GenContext, GeneratorConfig};
+use mfworld::bounds::HeightBounds;
+
+use crate::game::{environment::Environment, mode::GameMode, rules::GameRules};
+
+/// The first thing in every save. Holds the settings that were chosen when the world was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveHeader {
+    /// The version the header was read at. Headers are always written at [Self::VERSION], since
+    /// every field is.
+    pub version: u32,
+    pub seed: u64,
+    pub game_mode: GameMode,
+    pub generator: GeneratorConfig,
+    pub rules: GameRules,
+    /// The height range of every dimension.
+    pub bounds: HeightBounds,
+    /// The time of day and weather when the world was saved.
+    pub environment: Environment,
+}
+
+impl SaveHeader {
+    pub const MAGIC: [u8; 4] = *b"MFSV";
+    pub const VERSION: u32 = 5;
+
+    #[inline]
+    #[must_use]
+    pub fn new(seed: u64, game_mode: GameMode, generator: GeneratorConfig) -> Self {
+        Self {
+            version: Self::VERSION,
+            seed,
+            game_mode,
+            generator,
+            rules: GameRules::new(),
+            bounds: HeightBounds::UNBOUNDED,
+            environment: Environment::new(seed),
+        }
+    }
+
+    /// The context that every generation stage for this world runs with.
+    #[inline]
+    pub fn gen_context(&self) -> GenContext<'_> {
+        GenContext::new(self.seed, &self.generator)
+    }
+}
+
+impl Default for SaveHeader {
+    /// A header for a new world with seed `0`, at the current version.
+    #[inline]
+    fn default() -> Self {
+        Self::new(0, GameMode::default(), GeneratorConfig::default())
+    }
+}
+
+// Layout: magic ("MFSV"), version (u32), seed (u64), game mode (u8), generator config, game rules,
+// height bounds, environment.
+// Version 1 saves have no generator config; they were generated with the default one. Saves before
+// version 3 have no game rules; they load with the defaults. Saves before version 4 have no height
+// bounds; they load unbounded, as they were made. Saves before version 5 have no environment; they
+// load at the first morning of the seed's weather.
+impl Encode for SaveHeader {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_exact(&Self::MAGIC)?
+            + encoder.write_u32(Self::VERSION)?
+            + encoder.write_u64(self.seed)?
+            + self.game_mode.encode(encoder)?
+            + self.generator.encode(encoder)?
+            + self.rules.encode(encoder)?
+            + self.bounds.encode(encoder)?
+            + self.environment.encode(encoder)?
+        )
+    }
+}
+
+impl Decode for SaveHeader {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut magic = [0u8; 4];
+        decoder.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(DecodeError::InvalidData("not a save file"));
+        }
+        let version = decoder.read_u32()?;
+        if version > Self::VERSION {
+            return Err(DecodeError::InvalidData("unsupported save version"));
+        }
+        let seed = decoder.read_u64()?;
+        Ok(Self {
+            version,
+            seed,
+            game_mode: GameMode::decode(decoder)?,
+            generator: if version >= 2 { GeneratorConfig::decode(decoder)? } else { GeneratorConfig::default() },
+            rules: if version >= 3 { GameRules::decode(decoder)? } else { GameRules::new() },
+            bounds: if version >= 4 { HeightBounds::decode(decoder)? } else { HeightBounds::UNBOUNDED },
+            environment: if version >= 5 { Environment::decode(decoder)? } else { Environment::new(seed) },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_header_test() {
+        let mut header = SaveHeader::new(0xC0FFEE, GameMode::Creative, GeneratorConfig::preset("mountains").unwrap());
+        header.rules.set_bool(crate::game::rules::EXPLOSION_GRIEFING, false);
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes).unwrap();
+        assert_eq!(SaveHeader::decode(&mut bytes.as_slice()).unwrap(), header);
+
+        // The environment is saved, and version 4 saves start it over.
+        header.environment.tick(&header.rules);
+        let mut environment = Vec::new();
+        header.environment.encode(&mut environment).unwrap();
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes).unwrap();
+        assert_eq!(SaveHeader::decode(&mut bytes.as_slice()).unwrap(), header);
+        bytes[4..8].copy_from_slice(&4u32.to_be_bytes());
+        bytes.truncate(bytes.len() - environment.len());
+        let old = SaveHeader::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!((old.version, old.environment), (4, Environment::new(header.seed)));
+
+        // Bounded worlds keep their bounds, and version 3 saves load unbounded.
+        header.bounds = HeightBounds::new(-64, 319).unwrap();
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes).unwrap();
+        assert_eq!(SaveHeader::decode(&mut bytes.as_slice()).unwrap(), header);
+        bytes[4..8].copy_from_slice(&3u32.to_be_bytes());
+        bytes.truncate(bytes.len() - environment.len() - 16);
+        let old = SaveHeader::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!((old.version, old.bounds), (3, HeightBounds::UNBOUNDED));
+
+        // Saving an old header upgrades it rather than writing new fields under the old version.
+        let mut bytes = Vec::new();
+        old.encode(&mut bytes).unwrap();
+        let resaved = SaveHeader::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(resaved, SaveHeader { version: SaveHeader::VERSION, ..old });
+
+        // Version 1 saves are only the seed and game mode.
+        let mut bytes = SaveHeader::MAGIC.to_vec();
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(0xC0FFEEu64.to_be_bytes());
+        GameMode::Creative.encode(&mut bytes).unwrap();
+        let old = SaveHeader::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!((old.version, old.seed, old.game_mode), (1, 0xC0FFEE, GameMode::Creative));
+        assert_eq!((old.generator, old.rules), (GeneratorConfig::default(), GameRules::new()));
+        bytes[0] = b'X';
+        assert!(SaveHeader::decode(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod compact;
+pub mod dir;
+pub mod hash_history;
+pub mod header;
+pub mod open;
+pub mod registry;
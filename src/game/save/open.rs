@@ -0,0 +1,547 @@
+//! Opening a save, one stage at a time.
+//!
+//! [open_world] reads and checks everything a [Game] needs before constructing it, so a bad save
+//! fails with an [OpenError] naming what was wrong instead of panicking halfway through loading:
+//!
+//! 1. [OpenStage::Header]: check the save's signature if [OpenOptions::passphrase] asks for one
+//!    (`signing` feature), then read the [SaveHeader].
+//! 2. [OpenStage::Validate]: check the save version and the generator config, which everything
+//!    generated from now on depends on.
+//! 3. [OpenStage::Journal]: if the game didn't shut down cleanly, replay the voxel edits in the
+//!    dimension's journal (see [mfworld::journal]) onto the chunks they were made in, save those
+//!    chunks, and clear the journal.
+//! 4. [OpenStage::Chunks]: load the chunks around spawn, and those kept loaded by saved tickets.
+//!    Missing chunks are generated, and corrupt ones are recovered with the [RecoveryPolicy]. Their
+//!    structure references are loaded with them, or found from the structure placers for chunks
+//!    saved without any (see [chunk_structures]). The biomes of their columns are loaded the same
+//!    way, or generated (see [column_biomes]). The game as saved (see [restored_game]), before the spawn ticket is added, is then checked against
+//!    the newest snapshot of the save's [HashHistory]. A mismatch is reported rather than failing,
+//!    so a damaged save can still be opened and investigated.
+//! 5. [OpenStage::Registry]: compare the save's [RegistryManifest] with the game's (see
+//!    [registry](super::registry)).
+//! 6. [OpenStage::Construct]: build the [Game].
+//!
+//! Each stage reports its progress for the loading screen.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt,
+    fs,
+    io::ErrorKind,
+};
+
+use mfprocgen::{
+    config::InvalidConfig,
+    stage::GenContext,
+    structure::{ClaimRegistry, StructurePlacer},
+};
+use mfworld::{
+    biome::{AmbientTable, BiomeMap},
+    chunk::{stored::StoredChunk, ChunkPos},
+    history::VoxelEdit,
+    journal::read_journal_file,
+    portal::DimensionId,
+    recovery::{ChunkOrigin, ChunkRecovery, LoadFailure, RecoveryEvent, RecoveryPolicy},
+    structure::StructureIndex,
+    ticket::{ChunkTickets, Ticket},
+};
+
+use super::{
+    dir::{SaveDir, SaveError},
+    hash_history::{HashHistory, StateMismatch},
+    header::SaveHeader,
+    registry::{RegistryManifest, RegistryReport},
+};
+use crate::game::{
+    player::Player,
+    world::{generate::{chunk_structures, column_biomes, generate_chunk}, World},
+    Game,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpenStage {
+    Header,
+    Validate,
+    Journal,
+    Chunks,
+    Registry,
+    Construct,
+}
+
+impl OpenStage {
+    pub const ALL: [OpenStage; 6] = [
+        OpenStage::Header,
+        OpenStage::Validate,
+        OpenStage::Journal,
+        OpenStage::Chunks,
+        OpenStage::Registry,
+        OpenStage::Construct,
+    ];
+
+    /// What the loading screen shows during the stage.
+    #[inline]
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            OpenStage::Header => "Reading save",
+            OpenStage::Validate => "Checking world settings",
+            OpenStage::Journal => "Recovering unsaved changes",
+            OpenStage::Chunks => "Loading terrain",
+            OpenStage::Registry => "Checking items and recipes",
+            OpenStage::Construct => "Starting world",
+        }
+    }
+}
+
+impl fmt::Display for OpenStage {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// How far opening has got: `done` of the `total` steps of `stage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpenProgress {
+    pub stage: OpenStage,
+    pub done: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub dimension: DimensionId,
+    /// The radius (in chunks) of the region around spawn loaded before the game starts.
+    pub spawn_radius: u8,
+    pub recovery: RecoveryPolicy,
+    /// Open saves whose items or recipes don't match the game's. The differences are still
+    /// reported, for the caller to remap.
+    pub allow_missing: bool,
+    /// Only open the save if it's intact and signed with the key for this passphrase (see
+    /// [SaveDir::sign]).
+    #[cfg(feature = "signing")]
+    pub passphrase: Option<String>,
+}
+
+impl Default for OpenOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            dimension: DimensionId::OVERWORLD,
+            spawn_radius: 2,
+            recovery: RecoveryPolicy::default(),
+            allow_missing: false,
+            #[cfg(feature = "signing")]
+            passphrase: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenError {
+    #[error("{stage} failed: {error}")]
+    Save {
+        stage: OpenStage,
+        error: SaveError,
+    },
+    #[error("Unsupported save version {0}.")]
+    UnsupportedVersion(u32),
+    #[error("{0}")]
+    InvalidGenerator(InvalidConfig),
+    #[error("Chunk {chunk} failed to load: {failure}")]
+    Chunk {
+        chunk: ChunkPos,
+        failure: LoadFailure,
+    },
+    #[error("The save refers to {} items and {} recipes the game doesn't have.", .0.missing_items.len(), .0.missing_recipes.len())]
+    Registry(RegistryReport),
+}
+
+impl OpenError {
+    /// The stage that failed.
+    #[must_use]
+    pub const fn stage(&self) -> OpenStage {
+        match self {
+            OpenError::Save { stage, .. } => *stage,
+            OpenError::UnsupportedVersion(_) | OpenError::InvalidGenerator(_) => OpenStage::Validate,
+            OpenError::Chunk { .. } => OpenStage::Chunks,
+            OpenError::Registry(_) => OpenStage::Registry,
+        }
+    }
+}
+
+/// What happened while opening a save.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OpenReport {
+    pub spawn: ChunkPos,
+    /// The chunks that were loaded from the save.
+    pub stored: u32,
+    /// The chunks that weren't in the save yet, and were generated.
+    pub generated: u32,
+    /// The voxel edits replayed from the journal, which are only there after an unclean shutdown.
+    pub replayed: u32,
+    /// Whether the journal ended with a batch of edits that was only partly written, and was lost.
+    pub journal_torn: bool,
+    /// The chunks that were corrupt, and how they were recovered, including those the journal was
+    /// replayed onto.
+    pub recovered: Vec<RecoveryEvent>,
+    /// Set when the game as saved doesn't have the hash of the newest snapshot in its history.
+    pub state_mismatch: Option<StateMismatch>,
+    /// Whether the save had a registry manifest to check.
+    pub has_registry: bool,
+    pub registry: RegistryReport,
+}
+
+/// An opened save.
+pub struct OpenedWorld {
+    pub header: SaveHeader,
+    pub game: Game,
+    pub tickets: ChunkTickets,
+    pub history: HashHistory,
+    /// The loaded chunks.
+    pub chunks: BTreeMap<ChunkPos, StoredChunk>,
+    /// The structure references of the loaded chunks.
+    pub structures: StructureIndex,
+    /// The biomes of the columns of the loaded chunks.
+    pub biomes: BiomeMap,
+    pub report: OpenReport,
+}
+
+/// Opens `save`, calling `progress` as each stage goes. `registry` is the manifest of the running
+/// game (see [RegistryManifest::current]), and `placers` the structures it generates.
+pub fn open_world<F: FnMut(OpenProgress)>(
+    save: &SaveDir,
+    registry: &RegistryManifest,
+    placers: &[&dyn StructurePlacer],
+    options: &OpenOptions,
+    mut progress: F,
+) -> Result<OpenedWorld, OpenError> {
+    let io = |stage| move |error| OpenError::Save { stage, error };
+    let mut report = OpenReport::default();
+
+    progress(OpenProgress { stage: OpenStage::Header, done: 0, total: 1 });
+    #[cfg(feature = "signing")]
+    if let Some(passphrase) = &options.passphrase {
+        save.verify_signature(passphrase).map_err(io(OpenStage::Header))?;
+    }
+    let header = save.read_header().map_err(io(OpenStage::Header))?;
+
+    progress(OpenProgress { stage: OpenStage::Validate, done: 0, total: 1 });
+    if header.version == 0 || header.version > SaveHeader::VERSION {
+        return Err(OpenError::UnsupportedVersion(header.version));
+    }
+    header.generator.validate().map_err(OpenError::InvalidGenerator)?;
+    let history = save.read_history().map_err(io(OpenStage::Validate))?;
+    let mut tickets = save.read_tickets(options.dimension).map_err(io(OpenStage::Validate))?;
+
+    let ctx = header.gen_context();
+    let mut recovery = ChunkRecovery::new(options.recovery.clone());
+    report.recovered = replay_journal(save, options.dimension, &header, &ctx, &mut recovery, &mut report, &mut progress)?;
+
+    // The game as saved is kept apart from the spawn ticket, which it wasn't saved with, so that it
+    // can be checked against the history once its chunks are loaded.
+    let mut game = restored_game(&header, &tickets);
+    let spawn_y = header.bounds.clamp_y(ctx.surface_column(0, 0).height.into());
+    report.spawn = ChunkPos::containing(0, spawn_y as i32, 0);
+    tickets.add(Ticket::player(report.spawn, options.spawn_radius));
+    let mut world = game.world.clone();
+    world.sync_tickets(&mut tickets);
+    let to_load = world.iter_loaded_chunks_deterministic().collect::<Vec<_>>();
+    let total = to_load.len() as u32;
+    let mut chunks = BTreeMap::new();
+    let mut claims = ClaimRegistry::new(placers.to_vec());
+    let mut structures = StructureIndex::new();
+    let mut biomes = BiomeMap::new(AmbientTable::new());
+    for (done, chunk) in (0..).zip(to_load) {
+        progress(OpenProgress { stage: OpenStage::Chunks, done, total });
+        let stored = match save.read_chunk_blob(options.dimension, chunk).map_err(io(OpenStage::Chunks))? {
+            Some(blob) => {
+                let (stored, origin) = recovery.load(chunk, &blob, |chunk| generate_chunk(&ctx, chunk))
+                    .map_err(|failure| OpenError::Chunk { chunk, failure })?;
+                if origin == ChunkOrigin::Stored {
+                    report.stored += 1;
+                }
+                stored
+            }
+            None => {
+                report.generated += 1;
+                generate_chunk(&ctx, chunk)
+            }
+        };
+        chunks.insert(chunk, stored);
+        let references = match save.load_chunk_structures(options.dimension, chunk).map_err(io(OpenStage::Chunks))? {
+            Some(references) => references,
+            None => chunk_structures(&ctx, &mut claims, chunk),
+        };
+        structures.load_chunk(chunk, references);
+        if !biomes.is_loaded(chunk.x, chunk.z) {
+            let column = match save.load_column_biomes(options.dimension, chunk.x, chunk.z).map_err(io(OpenStage::Chunks))? {
+                Some(column) => column,
+                None => column_biomes(&ctx, chunk.x, chunk.z),
+            };
+            biomes.load_column(chunk.x, chunk.z, column);
+        }
+    }
+    report.recovered.extend(recovery.drain_events());
+    report.state_mismatch = history.verify(&game, &chunks).err();
+
+    progress(OpenProgress { stage: OpenStage::Registry, done: 0, total: 1 });
+    if let Some(saved) = save.read_registry().map_err(io(OpenStage::Registry))? {
+        report.has_registry = true;
+        report.registry = saved.check(registry);
+        if !options.allow_missing && !report.registry.is_compatible() {
+            return Err(OpenError::Registry(report.registry));
+        }
+    }
+
+    progress(OpenProgress { stage: OpenStage::Construct, done: 0, total: 1 });
+    game.world = world;
+    progress(OpenProgress { stage: OpenStage::Construct, done: 1, total: 1 });
+    Ok(OpenedWorld { header, game, tickets, history, chunks, structures, biomes, report })
+}
+
+/// The game as [open_world] restores it from `header` and the saved `tickets`, before it adds any
+/// ticket of its own. Player tickets and the player's edit history aren't saved, so they're left
+/// out. Snapshot this game when saving (see [HashHistory::snapshot]) so the snapshot verifies when
+/// the save is opened.
+#[must_use]
+pub fn restored_game(header: &SaveHeader, tickets: &ChunkTickets) -> Game {
+    let mut saved = ChunkTickets::new();
+    for (_, ticket) in tickets.iter().filter(|(_, ticket)| ticket.kind.is_persistent()) {
+        saved.add(*ticket);
+    }
+    let mut world = World::with_bounds(header.bounds);
+    world.sync_tickets(&mut saved);
+    Game {
+        world,
+        player: Player::default(),
+        mode: header.game_mode,
+        rules: header.rules.clone(),
+        environment: header.environment.clone(),
+    }
+}
+
+/// Applies the edits in the journal of `dimension` to the chunks they were made in, and saves them.
+/// Edits outside the header's height bounds are dropped. The journal is removed once they're saved,
+/// so the edits are only ever applied once. Returns how the corrupt chunks edits were made in were
+/// recovered.
+fn replay_journal<F: FnMut(OpenProgress)>(
+    save: &SaveDir,
+    dimension: DimensionId,
+    header: &SaveHeader,
+    ctx: &GenContext,
+    recovery: &mut ChunkRecovery,
+    report: &mut OpenReport,
+    progress: &mut F,
+) -> Result<Vec<RecoveryEvent>, OpenError> {
+    let io = |error| OpenError::Save { stage: OpenStage::Journal, error };
+    let path = save.journal_path(dimension);
+    let replay = read_journal_file::<VoxelEdit>(&path).map_err(|error| io(error.into()))?;
+    report.journal_torn = replay.torn;
+    let total = replay.batches.len() as u32;
+    let mut touched = BTreeMap::new();
+    for (done, batch) in (0..).zip(&replay.batches) {
+        progress(OpenProgress { stage: OpenStage::Journal, done, total });
+        for edit in &batch.entries {
+            // No edit can be made outside every chunk, or outside the bounds.
+            let Some((chunk, local)) = edit.position.split().filter(|_| header.bounds.check_block(edit.position).is_ok()) else {
+                continue;
+            };
+            let stored = match touched.entry(chunk) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(match save.read_chunk_blob(dimension, chunk).map_err(io)? {
+                    Some(blob) => recovery.load(chunk, &blob, |chunk| generate_chunk(ctx, chunk))
+                        .map_err(|failure| OpenError::Chunk { chunk, failure })?.0,
+                    None => generate_chunk(ctx, chunk),
+                }),
+            };
+            stored.set(local.index(), edit.after);
+            report.replayed += 1;
+        }
+    }
+    progress(OpenProgress { stage: OpenStage::Journal, done: total, total });
+    for (chunk, stored) in &touched {
+        save.save_chunk(dimension, *chunk, stored).map_err(io)?;
+        recovery.saved(*chunk);
+    }
+    match fs::remove_file(&path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(io(error.into())),
+        _ => Ok(recovery.drain_events()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use mfprocgen::GeneratorConfig;
+
+    use mfgeometry::Orientation;
+    use mfworld::{
+        biome::{BiomeId, ColumnBiomes},
+        chunk::LocalPos,
+        history::VoxelState,
+        journal::Journal,
+        structure::{ChunkStructures, StructureBounds, StructureId, StructureRef},
+        voxel::id::VoxelId,
+    };
+
+    use super::*;
+    use crate::game::{
+        crafting::item::ItemId,
+        machine::recipe::RecipeBook,
+        mode::GameMode,
+    };
+
+    #[test]
+    fn open_world_test() {
+        let root = std::env::temp_dir().join(format!("manufactory_open_world_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let header = SaveHeader::new(5, GameMode::Creative, GeneratorConfig::preset("flats").unwrap());
+        let save = SaveDir::create(&root, &header).unwrap();
+        let registry = RegistryManifest::current(&RecipeBook::new());
+        save.write_registry(&registry).unwrap();
+        let options = OpenOptions { spawn_radius: 1, ..OpenOptions::default() };
+
+        // Every chunk is generated the first time, and each stage is reported in order.
+        let mut stages = Vec::new();
+        let opened = open_world(&save, &registry, &[], &options, |progress| stages.push(progress.stage)).unwrap();
+        stages.dedup();
+        assert_eq!(stages, OpenStage::ALL);
+        assert_eq!(opened.report.generated, 27);
+        assert_eq!(opened.chunks.len(), 27);
+        assert!(opened.game.world.is_ticking(opened.report.spawn));
+        assert_eq!(opened.game.mode(), GameMode::Creative);
+
+        // Saved chunks are loaded, and corrupt ones regenerated.
+        let spawn = opened.report.spawn;
+        save.save_chunk(options.dimension, spawn, &opened.chunks[&spawn]).unwrap();
+        save.write_chunk_blob(options.dimension, ChunkPos::new(spawn.x + 1, spawn.y, spawn.z), b"junk").unwrap();
+        let reopened = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        assert_eq!((reopened.report.stored, reopened.report.generated, reopened.report.recovered.len()), (1, 25, 1));
+        assert_eq!(reopened.chunks, opened.chunks);
+
+        // Structures are found for every loaded chunk, and the saved ones are loaded instead.
+        assert!(opened.game.world.iter_loaded_chunks_deterministic().all(|chunk| opened.structures.chunk(chunk).is_some()));
+        let mut references = ChunkStructures::new();
+        references.insert(StructureRef::new(StructureId(3), StructureBounds::of_chunk(spawn)));
+        save.save_chunk_structures(options.dimension, spawn, &references).unwrap();
+        let reopened = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        assert_eq!(reopened.structures.structure_at(spawn.block(LocalPos::new(0, 0, 0).unwrap())).map(|reference| reference.id), Some(StructureId(3)));
+
+        // So are biomes, by chunk column.
+        let origin = spawn.block(LocalPos::new(0, 0, 0).unwrap());
+        assert!(opened.game.world.iter_loaded_chunks_deterministic().all(|chunk| opened.biomes.is_loaded(chunk.x, chunk.z)));
+        save.save_column_biomes(options.dimension, spawn.x, spawn.z, &ColumnBiomes::filled(BiomeId(7))).unwrap();
+        let reopened = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        assert_eq!(reopened.biomes.biome_at(origin), Some(BiomeId(7)));
+
+        // Edits journaled before a crash are replayed into the save, and the journal is cleared.
+        let local = LocalPos::new(1, 2, 3).unwrap();
+        let edit = VoxelEdit {
+            position: spawn.block(local),
+            before: VoxelState::new(VoxelId::AIR, Orientation::UNORIENTED),
+            after: VoxelState::new(VoxelId::new(9), Orientation::UNORIENTED),
+        };
+        let (journal, _) = Journal::open::<VoxelEdit>(&save.journal_path(options.dimension)).unwrap();
+        journal.append(1, &[edit]);
+        journal.sync().unwrap();
+        drop(journal);
+        let reopened = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        assert_eq!((reopened.report.replayed, reopened.report.journal_torn), (1, false));
+        assert_eq!(reopened.chunks[&spawn].id(local.index()), VoxelId::new(9));
+        assert!(!save.journal_path(options.dimension).exists());
+        assert_eq!(open_world(&save, &registry, &[], &options, |_| ()).unwrap().report.replayed, 0);
+
+        // Chunks recovered to replay edits onto are reported, and saved as recovered.
+        let corrupt = ChunkPos::new(spawn.x - 1, spawn.y, spawn.z);
+        save.write_chunk_blob(options.dimension, corrupt, b"junk").unwrap();
+        let (journal, _) = Journal::open::<VoxelEdit>(&save.journal_path(options.dimension)).unwrap();
+        journal.append(2, &[VoxelEdit { position: corrupt.block(local), ..edit }]);
+        journal.sync().unwrap();
+        drop(journal);
+        let replayed = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        let recovered = |opened: OpenedWorld| opened.report.recovered.iter().map(|event| event.chunk).collect::<Vec<_>>();
+        assert_eq!(replayed.chunks[&corrupt].id(local.index()), VoxelId::new(9));
+        // The junk chunk from before is still recovered, after the journal.
+        let junk = ChunkPos::new(spawn.x + 1, spawn.y, spawn.z);
+        assert_eq!(recovered(replayed), [corrupt, junk]);
+        assert_eq!(recovered(open_world(&save, &registry, &[], &options, |_| ()).unwrap()), [junk]);
+
+        // The game as saved is checked against the newest snapshot of its history, whatever the
+        // spawn ticket added on opening loads.
+        assert_eq!(reopened.report.state_mismatch, None);
+        let machine = ChunkPos::new(spawn.x + 4, spawn.y, spawn.z);
+        let mut tickets = ChunkTickets::new();
+        tickets.add(Ticket::machine(machine));
+        tickets.add(Ticket::player(spawn, 1));
+        save.write_tickets(options.dimension, &tickets).unwrap();
+        let chunks = open_world(&save, &registry, &[], &options, |_| ()).unwrap().chunks;
+        save.save_chunk(options.dimension, machine, &chunks[&machine]).unwrap();
+        let mut history = HashHistory::default();
+        history.snapshot(100, &restored_game(&header, &tickets), &chunks);
+        save.write_history(&history).unwrap();
+        assert_eq!(open_world(&save, &registry, &[], &options, |_| ()).unwrap().report.state_mismatch, None);
+        let mut edited = chunks[&machine].clone();
+        edited.set(0, VoxelState::new(VoxelId::new(9), Orientation::UNORIENTED));
+        save.save_chunk(options.dimension, machine, &edited).unwrap();
+        let mismatch = open_world(&save, &registry, &[], &options, |_| ()).unwrap().report.state_mismatch;
+        assert_eq!(mismatch.map(|mismatch| mismatch.tick), Some(100));
+
+        // A save with an item the game doesn't have is reported, and only opens when allowed.
+        let mut saved = registry.clone();
+        saved.items.push(ItemId::new(u32::MAX));
+        save.write_registry(&saved).unwrap();
+        let error = open_world(&save, &registry, &[], &options, |_| ()).err().unwrap();
+        assert_eq!(error.stage(), OpenStage::Registry);
+        let options = OpenOptions { allow_missing: true, ..options };
+        let opened = open_world(&save, &registry, &[], &options, |_| ()).unwrap();
+        assert_eq!(opened.report.registry.missing_items.len(), 1);
+
+        // A config that would panic generation fails before any chunk is touched.
+        let mut header = header;
+        header.generator.terrain_scale = 0;
+        save.write_header(&header).unwrap();
+        assert!(matches!(open_world(&save, &registry, &[], &options, |_| ()), Err(OpenError::InvalidGenerator(_))));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signed_open_test() {
+        use mfhash::signing::{SignatureError, SigningKey};
+
+        let root = std::env::temp_dir().join(format!("manufactory_signed_open_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let header = SaveHeader::new(11, GameMode::Survival, GeneratorConfig::preset("flats").unwrap());
+        let save = SaveDir::create(&root, &header).unwrap();
+        let registry = RegistryManifest::current(&RecipeBook::new());
+        save.write_registry(&registry).unwrap();
+        let chunk = ChunkPos::new(0, 3, 0);
+        save.save_chunk(DimensionId::OVERWORLD, chunk, &generate_chunk(&header.gen_context(), chunk)).unwrap();
+        let signature = save.sign(&SigningKey::from_passphrase("hunter2", [11; 16])).unwrap();
+        assert_eq!(save.read_signature().unwrap(), Some(signature));
+
+        let signed = |passphrase: &str| OpenOptions { spawn_radius: 0, passphrase: Some(passphrase.to_owned()), ..OpenOptions::default() };
+        let signature_error = |options: &OpenOptions| match open_world(&save, &registry, &[], options, |_| ()) {
+            Err(OpenError::Save { stage: OpenStage::Header, error: SaveError::Signature(error) }) => Some(error),
+            _ => None,
+        };
+        assert!(open_world(&save, &registry, &[], &signed("hunter2"), |_| ()).is_ok());
+        assert_eq!(signature_error(&signed("hunter3")), Some(SignatureError::WrongKey));
+
+        // Any change after signing is caught, and the save only opens unchecked.
+        let mut tampered = save.load_chunk(DimensionId::OVERWORLD, chunk).unwrap().unwrap();
+        tampered.set(0, VoxelState::new(VoxelId::new(9), Orientation::UNORIENTED));
+        save.save_chunk(DimensionId::OVERWORLD, chunk, &tampered).unwrap();
+        assert_eq!(signature_error(&signed("hunter2")), Some(SignatureError::Corrupt));
+        assert!(open_world(&save, &registry, &[], &OpenOptions::default(), |_| ()).is_ok());
+
+        fs::remove_file(root.join(SaveDir::SIGNATURE_FILE)).unwrap();
+        assert!(matches!(
+            open_world(&save, &registry, &[], &signed("hunter2"), |_| ()),
+            Err(OpenError::Save { error: SaveError::Unsigned, .. }),
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
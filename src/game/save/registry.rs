@@ -0,0 +1,213 @@
+//! The items and recipes a save was made with.
+//!
+//! Saves refer to items and recipes by id, so a save only makes sense with the registries it was
+//! written with. The [RegistryManifest] records them next to the header. Opening a save compares
+//! the manifest with the running game's registries, and reports each item or recipe that is gone
+//! or changed, with the ids it could be remapped to, before anything refers to them.
+
+use std::num::NonZeroU32;
+
+use mfcereal::{
+    decode::{decoder_read_vec, Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::game::{
+    context::handles::RecipeId,
+    crafting::item::{ItemId, ItemType},
+    machine::recipe::{MachineKind, RecipeBook},
+};
+
+/// A recipe as the manifest records it: enough to tell whether the recipe with the same id is
+/// still the same recipe.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecipeEntry {
+    pub machine: MachineKind,
+    pub outputs: Vec<ItemId>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RegistryManifest {
+    /// Sorted.
+    pub items: Vec<ItemId>,
+    /// In id order: the recipe with id `n` is at `n - 1`.
+    pub recipes: Vec<RecipeEntry>,
+}
+
+/// An item in the save that the game doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingItem {
+    pub item: ItemId,
+    /// Items it could be replaced with: those of the same form (another ore for an ore), then those
+    /// of the same family.
+    pub options: Vec<ItemId>,
+}
+
+/// A recipe in the save that the game doesn't have, or has with another machine or other outputs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingRecipe {
+    pub recipe: RecipeId,
+    pub saved: RecipeEntry,
+    /// Recipes of the same machine it could be replaced with: those with the same outputs first.
+    /// Machines set to a recipe that isn't remapped go back to having none.
+    pub options: Vec<RecipeId>,
+}
+
+/// How a save's registries differ from the game's.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RegistryReport {
+    pub missing_items: Vec<MissingItem>,
+    pub missing_recipes: Vec<MissingRecipe>,
+}
+
+impl RegistryReport {
+    #[inline]
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        self.missing_items.is_empty() && self.missing_recipes.is_empty()
+    }
+}
+
+impl RegistryManifest {
+    /// The manifest of the running game.
+    #[must_use]
+    pub fn current(recipes: &RecipeBook) -> Self {
+        let mut items = ItemType::ALL.iter().map(|item| item.id()).collect::<Vec<_>>();
+        items.sort();
+        items.dedup();
+        let recipes = (1..)
+            .map_while(|id| {
+                let id = RecipeId::new(NonZeroU32::new(id)?);
+                let recipe = recipes.get(id)?;
+                Some(RecipeEntry {
+                    machine: recipes.machine(id)?,
+                    outputs: recipe.outputs.iter().map(|&(item, _)| item).collect(),
+                })
+            })
+            .collect();
+        Self { items, recipes }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn has_item(&self, item: ItemId) -> bool {
+        self.items.binary_search(&item).is_ok()
+    }
+
+    /// Compares a saved manifest (`self`) with the game's.
+    #[must_use]
+    pub fn check(&self, current: &Self) -> RegistryReport {
+        let missing_items = self.items.iter()
+            .filter(|&&item| !current.has_item(item))
+            .map(|&item| {
+                let same_form = current.items.iter().filter(|other| other.form() == item.form());
+                let same_family = current.items.iter().filter(|other| other.resource_family() == item.resource_family());
+                let mut options = same_form.chain(same_family).copied().collect::<Vec<_>>();
+                dedup_keeping_order(&mut options);
+                MissingItem { item, options }
+            })
+            .collect();
+        let missing_recipes = self.recipes.iter().zip(1..)
+            .filter(|&(saved, index)| current.recipes.get(index as usize - 1) != Some(saved))
+            .map(|(saved, index)| {
+                let same_machine = || current.recipes.iter().zip(1u32..).filter(|(entry, _)| entry.machine == saved.machine);
+                let mut options = same_machine().filter(|(entry, _)| entry.outputs == saved.outputs)
+                    .chain(same_machine())
+                    .map(|(_, id)| recipe_id(id))
+                    .collect::<Vec<_>>();
+                dedup_keeping_order(&mut options);
+                MissingRecipe { recipe: recipe_id(index), saved: saved.clone(), options }
+            })
+            .collect();
+        RegistryReport { missing_items, missing_recipes }
+    }
+}
+
+#[inline]
+fn recipe_id(id: u32) -> RecipeId {
+    RecipeId::new(NonZeroU32::new(id).expect("recipe ids start at 1"))
+}
+
+fn dedup_keeping_order<T: PartialEq + Copy>(values: &mut Vec<T>) {
+    let mut kept = Vec::with_capacity(values.len());
+    for &value in values.iter() {
+        if !kept.contains(&value) {
+            kept.push(value);
+        }
+    }
+    *values = kept;
+}
+
+// Layout: item count (u64), the items (u32 each), recipe count (u64), then each recipe as
+// machine (u16), output count (u64) and the outputs (u32 each).
+impl Encode for RegistryManifest {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u64(self.items.len() as u64)?;
+        for item in &self.items {
+            written += encoder.write_u32(item.get())?;
+        }
+        written += encoder.write_u64(self.recipes.len() as u64)?;
+        for recipe in &self.recipes {
+            written += encoder.write_u16(recipe.machine.0)? + encoder.write_u64(recipe.outputs.len() as u64)?;
+            for output in &recipe.outputs {
+                written += encoder.write_u32(output.get())?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for RegistryManifest {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        fn read_item<D: Decoder>(decoder: &mut D) -> Result<ItemId, DecodeError<D::Error>> {
+            Ok(ItemId::new(decoder.read_u32()?))
+        }
+        let items = decoder_read_vec(decoder, read_item)?;
+        if !items.is_sorted_by(|a, b| a < b) {
+            return Err(DecodeError::InvalidData("registry items out of order"));
+        }
+        let recipes = decoder_read_vec(decoder, |decoder| Ok(RecipeEntry {
+            machine: MachineKind(decoder.read_u16()?),
+            outputs: decoder_read_vec(decoder, read_item)?,
+        }))?;
+        Ok(Self { items, recipes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::crafting::recipe::Recipe;
+
+    fn smelt(ore: ItemType, ingot: ItemType) -> Recipe {
+        Recipe { inputs: vec![(ore.id(), 1)], outputs: vec![(ingot.id(), 1)], requires: None }
+    }
+
+    #[test]
+    fn registry_check_test() {
+        let furnace = MachineKind(1);
+        let mut book = RecipeBook::new();
+        book.register(furnace, smelt(ItemType::IronOre, ItemType::IronIngot));
+        book.register(furnace, smelt(ItemType::CopperOre, ItemType::CopperIngot));
+        let current = RegistryManifest::current(&book);
+        assert!(current.check(&current).is_compatible());
+
+        // A save from a version with another ore, and the recipes in another order.
+        let mut saved = current.clone();
+        let mythril = ItemId::new(ItemType::IronOre.id().get() + 40 * 1024);
+        saved.items.push(mythril);
+        saved.items.sort();
+        saved.recipes.swap(0, 1);
+        let report = saved.check(&current);
+        assert_eq!(report.missing_items.len(), 1);
+        assert_eq!(report.missing_items[0].item, mythril);
+        assert!(report.missing_items[0].options.contains(&ItemType::IronOre.id()));
+        assert!(report.missing_items[0].options.iter().all(|item| item.form() == mythril.form()));
+        let recipes = report.missing_recipes.iter().map(|missing| (missing.recipe.value(), missing.options[0].value())).collect::<Vec<_>>();
+        assert_eq!(recipes, [(1, 2), (2, 1)]);
+
+        let mut bytes = Vec::new();
+        saved.encode(&mut bytes).unwrap();
+        assert_eq!(RegistryManifest::decode(&mut bytes.as_slice()).unwrap(), saved);
+    }
+}
@@ -0,0 +1,560 @@
+//! Small scripted game runs, for checking that the simulation still behaves the same.
+//!
+//! A scenario file sets up a world with `key = value` settings, like the benchmark scenarios, and
+//! then scripts what happens in it with commands scheduled on ticks and queries read at the end:
+//!
+//! ```text
+//! # A drone carries ore between two chests.
+//! seed = 7
+//! generator = flats
+//! mode = survival
+//! ticks = 40
+//!
+//! @0 ticket 0 4 0 1
+//! @0 container 1 9 2 72 0
+//! @0 container 2 9 8 72 0
+//! @0 give 1 3 80
+//! @0 drone 4 72 4 500
+//! @1 deliver 1 2 3 80
+//!
+//! query container 2
+//! query drone 0
+//! ```
+//!
+//! [Scenario::run] plays the script through the real game state (tickets and loaded chunks, player
+//! edits, inventories, drones and events), and returns its [Golden] record: the [Game::state_hash],
+//! a hash of everything else the scenario touched, and the answer to each query. The golden tests
+//! in `tests/golden.rs` compare those records with committed ones (see [golden]), so a change in
+//! behavior anywhere in the stack shows up as a failing scenario instead of passing silently.
+//!
+//! # Commands
+//!
+//! Each command line is `@<tick> <command> <args...>`, and runs at the start of that tick, before
+//! tickets are synced and drones move. Commands on the same tick run in file order.
+//!
+//! - `ticket <cx> <cy> <cz> <radius>`: adds a player ticket around a chunk. Tickets are numbered
+//!   from 0 in the order they're added.
+//! - `unticket <ticket>`: removes a ticket.
+//! - `set <x> <y> <z> <voxel>`: the player sets a voxel in a loaded chunk.
+//! - `container <id> <size> <x> <y> <z>`: adds a container with its block at a position.
+//! - `give <container> <item> <count>`: puts items in a container.
+//! - `drone <x> <y> <z> <energy>`: spawns a drone. Drones are numbered from 0.
+//! - `charge <drone> <energy>`: charges a drone.
+//! - `deliver <from> <to> <item> <count>`: asks for items to be carried between containers.
+//!
+//! Drones fly through air in loaded chunks.
+//!
+//! # Queries
+//!
+//! - `loaded`, `ticking`: the number of loaded or ticking chunks.
+//! - `voxel <x> <y> <z>`: the voxel id at a position, if its chunk has been loaded.
+//! - `container <id>`: the non-empty slots of a container.
+//! - `drone <id>`: a drone's position, energy and task.
+//! - `finished`: the delivery tasks finished, in the order they were.
+//! - `edits`: the length of the player's undo history.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path, str::FromStr};
+
+use mfhash::{
+    canonical::hash_encoded,
+    deterministic::{DeterministicHash, DeterministicHasher},
+    deterministic_hash_u128,
+    Hash128,
+};
+use mfprocgen::{stage::GenContext, GeneratorConfig};
+use mfworld::{
+    chunk::{stored::StoredChunk, BlockPos, ChunkPos},
+    history::{VoxelEdit, VoxelState},
+    ticket::{ChunkTickets, Ticket, TicketId},
+    voxel::id::VoxelId,
+};
+
+use crate::game::{
+    crafting::item::ItemId,
+    drone::{DeliveryTask, Dispatcher, DronePort, DroneState, DroneId, TaskId},
+    environment::Environment,
+    events::EventBus,
+    inventory::{Container, ContainerId, Inventories, ItemStack},
+    mode::GameMode,
+    player::Player,
+    rules::GameRules,
+    world::{generate::generate_chunk, World},
+    Game,
+};
+
+pub use mfcore::golden::{self, Golden};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScenarioError {
+    #[error("Failed to read scenario: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("Line {line}, tick {tick}: {message}")]
+    Command { line: usize, tick: u32, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Ticket { center: ChunkPos, radius: u8 },
+    Unticket(usize),
+    Set { pos: BlockPos, voxel: VoxelId },
+    Container { id: ContainerId, size: u16, pos: BlockPos },
+    Give { container: ContainerId, stack: ItemStack },
+    Drone { pos: BlockPos, energy: u32 },
+    Charge { drone: DroneId, energy: u32 },
+    Deliver { from: ContainerId, to: ContainerId, stack: ItemStack },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Scheduled {
+    tick: u32,
+    line: usize,
+    command: Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Query {
+    Loaded,
+    Ticking,
+    Voxel(BlockPos),
+    Container(ContainerId),
+    Drone(DroneId),
+    Finished,
+    Edits,
+}
+
+/// A scripted run (see the [module docs](self)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub name: String,
+    pub seed: u64,
+    pub generator: GeneratorConfig,
+    pub mode: GameMode,
+    pub ticks: u32,
+    commands: Vec<Scheduled>,
+    /// The queries, and the text they were written as, which names them in the [Golden] record.
+    queries: Vec<(String, Query)>,
+}
+
+impl Scenario {
+    /// An empty scenario on the default generator.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            seed: 0,
+            generator: GeneratorConfig::default(),
+            mode: GameMode::default(),
+            ticks: 0,
+            commands: Vec::new(),
+            queries: Vec::new(),
+        }
+    }
+
+    /// Loads a scenario file, named after the file.
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let source = std::fs::read_to_string(path)?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::parse(&name, &source)
+    }
+
+    /// Parses a scenario. Settings apply in order, so a `ticks` line has to come before any command
+    /// scheduled for one of the ticks.
+    pub fn parse(name: &str, source: &str) -> Result<Self, ScenarioError> {
+        let mut scenario = Self::new(name);
+        for (line, text) in (1..).zip(source.lines()) {
+            let text = text.trim();
+            let parse_error = |message: String| ScenarioError::Parse { line, message };
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            if let Some(scheduled) = text.strip_prefix('@') {
+                let (tick, command) = scheduled.split_once(char::is_whitespace)
+                    .ok_or_else(|| parse_error("expected `@<tick> <command>`".to_owned()))?;
+                let tick = parse::<u32>("tick", tick).map_err(parse_error)?;
+                if tick >= scenario.ticks {
+                    return Err(parse_error(format!("tick {tick} is past the end of the scenario ({} ticks)", scenario.ticks)));
+                }
+                let command = parse_command(command.trim()).map_err(parse_error)?;
+                scenario.commands.push(Scheduled { tick, line, command });
+            } else if let Some(query) = text.strip_prefix("query ") {
+                let query = query.trim();
+                scenario.queries.push((query.to_owned(), parse_query(query).map_err(parse_error)?));
+            } else {
+                let (key, value) = text.split_once('=')
+                    .ok_or_else(|| parse_error("expected `key = value`, `@<tick> <command>` or `query <query>`".to_owned()))?;
+                scenario.set(key.trim(), value.trim()).map_err(parse_error)?;
+            }
+        }
+        // Commands run in tick order, then file order.
+        scenario.commands.sort_by_key(|scheduled| (scheduled.tick, scheduled.line));
+        Ok(scenario)
+    }
+
+    /// Applies a `key = value` setting.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "name" => self.name = value.to_owned(),
+            "seed" => self.seed = parse(key, value)?,
+            "generator" => {
+                self.generator = GeneratorConfig::preset(value)
+                    .ok_or_else(|| format!("Unknown generator preset: `{value}`"))?;
+            }
+            "mode" => self.mode = GameMode::from_str(value).map_err(|_| format!("Unknown game mode: `{value}`"))?,
+            "ticks" => self.ticks = parse(key, value)?,
+            _ => return Err(format!("Unknown setting: `{key}`")),
+        }
+        Ok(())
+    }
+
+    /// Plays the scenario, returning what it ended with.
+    pub fn run(&self) -> Result<Golden, ScenarioError> {
+        let mut run = Run::new(self);
+        let mut commands = self.commands.iter().peekable();
+        for tick in 0..self.ticks {
+            while let Some(scheduled) = commands.next_if(|scheduled| scheduled.tick == tick) {
+                run.apply(&scheduled.command).map_err(|message| ScenarioError::Command {
+                    line: scheduled.line,
+                    tick,
+                    message,
+                })?;
+            }
+            run.tick(tick);
+        }
+
+        let mut golden = Golden::new();
+        golden.push("ticks", self.ticks.to_string());
+        golden.push("game_hash", run.game.state_hash().to_string());
+        golden.push("scenario_hash", Hash128(deterministic_hash_u128(&run)).to_string());
+        for (text, query) in &self.queries {
+            golden.push(&format!("query {text}"), run.query(query));
+        }
+        Ok(golden)
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for `{key}`: `{value}`"))
+}
+
+/// Parses the `N` whitespace separated arguments of a command or query.
+fn args<const N: usize>(name: &str, args: &[&str]) -> Result<[i64; N], String> {
+    if args.len() != N {
+        return Err(format!("`{name}` takes {N} arguments, not {}", args.len()));
+    }
+    let mut values = [0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = parse(name, arg)?;
+    }
+    Ok(values)
+}
+
+/// Narrows a script argument.
+fn narrow<T: TryFrom<i64>>(name: &str, value: i64) -> Result<T, String> {
+    T::try_from(value).map_err(|_| format!("Argument of `{name}` out of range: {value}"))
+}
+
+fn parse_command(text: &str) -> Result<Command, String> {
+    let mut words = text.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let rest = words.collect::<Vec<_>>();
+    let stack = |item: i64, count: i64| -> Result<ItemStack, String> {
+        Ok(ItemStack::new(ItemId::new(narrow(name, item)?), narrow(name, count)?))
+    };
+    Ok(match name {
+        "ticket" => {
+            let [x, y, z, radius] = args(name, &rest)?;
+            Command::Ticket {
+                center: ChunkPos::new(narrow(name, x)?, narrow(name, y)?, narrow(name, z)?),
+                radius: narrow(name, radius)?,
+            }
+        }
+        "unticket" => {
+            let [ticket] = args(name, &rest)?;
+            Command::Unticket(narrow(name, ticket)?)
+        }
+        "set" => {
+            let [x, y, z, voxel] = args(name, &rest)?;
+            Command::Set { pos: BlockPos::new(x, y, z), voxel: VoxelId::new(narrow(name, voxel)?) }
+        }
+        "container" => {
+            let [id, size, x, y, z] = args(name, &rest)?;
+            Command::Container { id: ContainerId(narrow(name, id)?), size: narrow(name, size)?, pos: BlockPos::new(x, y, z) }
+        }
+        "give" => {
+            let [container, item, count] = args(name, &rest)?;
+            Command::Give { container: ContainerId(narrow(name, container)?), stack: stack(item, count)? }
+        }
+        "drone" => {
+            let [x, y, z, energy] = args(name, &rest)?;
+            Command::Drone { pos: BlockPos::new(x, y, z), energy: narrow(name, energy)? }
+        }
+        "charge" => {
+            let [drone, energy] = args(name, &rest)?;
+            Command::Charge { drone: DroneId(narrow(name, drone)?), energy: narrow(name, energy)? }
+        }
+        "deliver" => {
+            let [from, to, item, count] = args(name, &rest)?;
+            Command::Deliver {
+                from: ContainerId(narrow(name, from)?),
+                to: ContainerId(narrow(name, to)?),
+                stack: stack(item, count)?,
+            }
+        }
+        _ => return Err(format!("Unknown command: `{name}`")),
+    })
+}
+
+fn parse_query(text: &str) -> Result<Query, String> {
+    let mut words = text.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let rest = words.collect::<Vec<_>>();
+    Ok(match name {
+        "loaded" => {
+            args::<0>(name, &rest)?;
+            Query::Loaded
+        }
+        "ticking" => {
+            args::<0>(name, &rest)?;
+            Query::Ticking
+        }
+        "voxel" => {
+            let [x, y, z] = args(name, &rest)?;
+            Query::Voxel(BlockPos::new(x, y, z))
+        }
+        "container" => {
+            let [id] = args(name, &rest)?;
+            Query::Container(ContainerId(narrow(name, id)?))
+        }
+        "drone" => {
+            let [id] = args(name, &rest)?;
+            Query::Drone(DroneId(narrow(name, id)?))
+        }
+        "finished" => {
+            args::<0>(name, &rest)?;
+            Query::Finished
+        }
+        "edits" => {
+            args::<0>(name, &rest)?;
+            Query::Edits
+        }
+        _ => return Err(format!("Unknown query: `{name}`")),
+    })
+}
+
+/// The state of a scenario being played.
+struct Run<'a> {
+    ctx: GenContext<'a>,
+    game: Game,
+    tickets: ChunkTickets,
+    ticket_ids: Vec<TicketId>,
+    /// Every chunk that has been loaded. Unloaded chunks keep their voxels, as if they were saved.
+    chunks: BTreeMap<ChunkPos, StoredChunk>,
+    inventories: Inventories,
+    ports: BTreeMap<ContainerId, BlockPos>,
+    dispatcher: Dispatcher,
+    events: EventBus,
+    finished: Vec<TaskId>,
+}
+
+impl<'a> Run<'a> {
+    fn new(scenario: &'a Scenario) -> Self {
+        Self {
+            ctx: GenContext::new(scenario.seed, &scenario.generator),
+            game: Game {
+                world: World::new(),
+                player: Player::default(),
+                mode: scenario.mode,
+                rules: GameRules::new(),
+                environment: Environment::new(scenario.seed),
+            },
+            tickets: ChunkTickets::new(),
+            ticket_ids: Vec::new(),
+            chunks: BTreeMap::new(),
+            inventories: Inventories::new(),
+            ports: BTreeMap::new(),
+            dispatcher: Dispatcher::new(),
+            events: EventBus::new(),
+            finished: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, command: &Command) -> Result<(), String> {
+        match *command {
+            Command::Ticket { center, radius } => {
+                self.ticket_ids.push(self.tickets.add(Ticket::player(center, radius)));
+            }
+            Command::Unticket(index) => {
+                let id = self.ticket_ids.get(index).ok_or_else(|| format!("No ticket {index}"))?;
+                self.tickets.remove(*id).ok_or_else(|| format!("Ticket {index} was already removed"))?;
+            }
+            Command::Set { pos, voxel } => {
+                let orientation = pos.split()
+                    .and_then(|(chunk, local)| Some(self.chunks.get(&chunk)?.get(local.index()).orientation))
+                    .unwrap_or_default();
+                let after = VoxelState::new(voxel, orientation);
+                let before = self.game.world.set_voxel(&mut self.chunks, pos, after, &mut self.events).map_err(|error| error.to_string())?;
+                self.game.player.record_edit(VoxelEdit { position: pos, before, after }, self.game.mode);
+            }
+            Command::Container { id, size, pos } => {
+                if self.inventories.insert(id, Container::new(size)).is_some() {
+                    return Err(format!("Container {} already exists", id.0));
+                }
+                self.ports.insert(id, pos);
+            }
+            Command::Give { container, stack } => {
+                let left = self.inventories.insert_stack(container, stack, &mut self.events).map_err(|err| err.to_string())?;
+                if let Some(left) = left {
+                    return Err(format!("{} items didn't fit in container {}", left.count, container.0));
+                }
+            }
+            Command::Drone { pos, energy } => {
+                self.dispatcher.spawn(pos, energy);
+            }
+            Command::Charge { drone, energy } => {
+                self.dispatcher.drone_mut(drone).ok_or_else(|| format!("No drone {}", drone.0))?.charge(energy);
+            }
+            Command::Deliver { from, to, stack } => {
+                let port = |id: ContainerId| {
+                    self.ports.get(&id).map(|&pos| DronePort::new(id, pos)).ok_or_else(|| format!("No container {}", id.0))
+                };
+                let task = DeliveryTask::new(port(from)?, port(to)?, stack);
+                self.dispatcher.submit(task);
+            }
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self, tick: u32) {
+        self.game.tick();
+        self.game.world.sync_tickets(&mut self.tickets);
+        for chunk in self.game.world.iter_loaded_chunks_deterministic() {
+            self.chunks.entry(chunk).or_insert_with(|| generate_chunk(&self.ctx, chunk));
+        }
+        let (world, chunks) = (&self.game.world, &self.chunks);
+        let passable = |pos: BlockPos| match pos.split() {
+            Some((chunk, local)) => world.is_loaded(chunk) && chunks[&chunk].id(local.index()) == VoxelId::AIR,
+            None => false,
+        };
+        let finished = self.dispatcher.tick(&mut self.inventories, &mut self.events, passable);
+        self.finished.extend(finished);
+        self.events.end_tick(u64::from(tick));
+    }
+
+    fn query(&self, query: &Query) -> String {
+        match *query {
+            Query::Loaded => self.game.world.iter_loaded_chunks_deterministic().count().to_string(),
+            Query::Ticking => self.game.world.iter_ticking_chunks_deterministic().count().to_string(),
+            Query::Voxel(pos) => match pos.split().and_then(|(chunk, local)| Some(self.chunks.get(&chunk)?.id(local.index()))) {
+                Some(voxel) => voxel.get().to_string(),
+                None => "unloaded".to_owned(),
+            },
+            Query::Container(id) => match self.inventories.container(id) {
+                Some(container) => {
+                    let mut slots = String::new();
+                    for (index, stack) in (0..container.len() as u16).filter_map(|index| Some((index, container.get(index)?))) {
+                        let separator = if slots.is_empty() { "" } else { " " };
+                        write!(slots, "{separator}{index}:{}x{}", stack.item.get(), stack.count).expect("writing to a string");
+                    }
+                    if slots.is_empty() { "empty".to_owned() } else { slots }
+                }
+                None => "missing".to_owned(),
+            },
+            Query::Drone(id) => match self.dispatcher.drone(id) {
+                Some(drone) => {
+                    let state = match drone.state() {
+                        DroneState::Idle => "idle".to_owned(),
+                        DroneState::Fetching { task } => format!("fetching {}", task.0),
+                        DroneState::Delivering { task, cargo } => {
+                            format!("delivering {} {}x{}", task.0, cargo.item.get(), cargo.count)
+                        }
+                    };
+                    format!("{} energy {} {state}", drone.pos, drone.energy())
+                }
+                None => "missing".to_owned(),
+            },
+            Query::Finished => self.finished.iter().map(|task| task.0.to_string()).collect::<Vec<_>>().join(" "),
+            Query::Edits => self.game.player.edit_history().undo_len().to_string(),
+        }
+    }
+}
+
+// Layout: the loaded chunks (count, then position and encoding of each in Morton order), each
+// container (count, then id and non-empty slots in id order), the dispatcher's encoding, and the
+// finished tasks. The game is left to the game hash.
+impl DeterministicHash for Run<'_> {
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hasher.write_u64(self.game.world.iter_loaded_chunks_deterministic().count() as u64);
+        for chunk in self.game.world.iter_loaded_chunks_deterministic() {
+            chunk.deterministic_hash(hasher);
+            hash_encoded(&self.chunks[&chunk], hasher);
+        }
+        hasher.write_u64(self.ports.len() as u64);
+        for &id in self.ports.keys() {
+            hasher.write_u32(id.0);
+            let container = self.inventories.container(id).expect("ported containers exist");
+            for index in 0..container.len() as u16 {
+                if let Some(stack) = container.get(index) {
+                    hasher.write_u16(index);
+                    hash_encoded(&stack, hasher);
+                }
+            }
+            hasher.write_u16(u16::MAX);
+        }
+        hash_encoded(&self.dispatcher, hasher);
+        hasher.write_u64(self.finished.len() as u64);
+        for task in &self.finished {
+            hasher.write_u64(task.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELIVERY: &str = "
+        # One drone, one delivery.
+        seed = 3
+        generator = flats
+        ticks = 30
+        @0 ticket 0 4 0 1
+        @0 container 1 4 2 72 2
+        @0 container 2 4 6 72 2
+        @0 give 1 5 10
+        @0 drone 4 70 4 100
+        @1 deliver 1 2 5 10
+        @2 set 4 63 4 0
+        query container 2
+        query finished
+        query drone 0
+        query voxel 4 63 4
+        query edits
+    ";
+
+    #[test]
+    fn scenario_run_test() {
+        let scenario = Scenario::parse("delivery", DELIVERY).unwrap();
+        assert_eq!((scenario.seed, scenario.ticks, scenario.commands.len()), (3, 30, 7));
+        let golden = scenario.run().unwrap();
+        assert_eq!(golden.get("query container 2"), Some("0:5x10"));
+        assert_eq!(golden.get("query finished"), Some("0"));
+        assert_eq!(golden.get("query voxel 4 63 4"), Some("0"));
+        assert!(golden.get("query drone 0").unwrap().ends_with("idle"));
+        // The same script always ends the same way.
+        assert_eq!(scenario.run().unwrap(), golden);
+    }
+
+    #[test]
+    fn scenario_error_test() {
+        let error = |source| Scenario::parse("broken", source).err().unwrap().to_string();
+        assert_eq!(error("ticks = 4\n@4 drone 0 0 0 1"), "Line 2: tick 4 is past the end of the scenario (4 ticks)");
+        assert_eq!(error("ticks = 4\n@0 drone 0 0"), "Line 2: `drone` takes 4 arguments, not 2");
+        assert_eq!(error("colour = red"), "Line 1: Unknown setting: `colour`");
+        assert_eq!(error("query drone x"), "Line 1: Invalid value for `drone`: `x`");
+
+        let scenario = Scenario::parse("unloaded", "ticks = 2\n@1 set 0 0 0 1").unwrap();
+        assert_eq!(scenario.run().err().unwrap().to_string(), "Line 2, tick 1: (0, 0, 0) isn't loaded");
+    }
+}
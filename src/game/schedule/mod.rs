@@ -0,0 +1,416 @@
+//! A system scheduler where each system declares the resources it reads and writes.
+//!
+//! Systems that conflict (one writes a resource that the other reads or writes) always
+//! run in the order they were registered (unless an explicit [System::after] says otherwise).
+//! Systems that don't conflict are grouped into the same stage, and the systems in a stage
+//! may run in parallel. Because conflicting systems are never reordered, running a schedule
+//! in parallel produces the same results as running it sequentially.
+
+pub mod resources;
+
+use std::{any::TypeId, collections::{BTreeSet, HashMap}};
+
+pub use resources::{Res, ResMut, Resources};
+
+/// Identifies a resource by type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResourceId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl ResourceId {
+    #[inline]
+    pub fn of<T: 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        self.name
+    }
+}
+
+/// The set of resources a system reads and writes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Access {
+    reads: BTreeSet<ResourceId>,
+    writes: BTreeSet<ResourceId>,
+}
+
+impl Access {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn add_read(&mut self, id: ResourceId) {
+        self.reads.insert(id);
+    }
+
+    #[inline]
+    pub fn add_write(&mut self, id: ResourceId) {
+        self.writes.insert(id);
+    }
+
+    #[inline]
+    pub fn can_read(&self, id: ResourceId) -> bool {
+        self.reads.contains(&id) || self.writes.contains(&id)
+    }
+
+    #[inline]
+    pub fn can_write(&self, id: ResourceId) -> bool {
+        self.writes.contains(&id)
+    }
+
+    /// Two accesses conflict when either writes a resource that the other reads or writes.
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        self.writes.iter().any(|id| other.can_read(*id))
+        || other.writes.iter().any(|id| self.can_read(*id))
+    }
+}
+
+/// The view of the [Resources] given to a running system, limited to its declared [Access].
+pub struct SystemContext<'a> {
+    name: &'static str,
+    access: &'a Access,
+    resources: &'a Resources,
+}
+
+impl<'a> SystemContext<'a> {
+    #[inline]
+    pub const fn system_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Borrows a resource immutably.
+    ///
+    /// # Panics
+    /// Panics if the system did not declare that it reads or writes `T`, if the resource doesn't exist,
+    /// or if the system is still writing it (see [Resources::read]).
+    #[track_caller]
+    pub fn read<T: Send + Sync + 'static>(&self) -> Res<'a, T> {
+        let id = ResourceId::of::<T>();
+        assert!(self.access.can_read(id), "System `{}` did not declare read access to `{}`.", self.name, id.name());
+        self.resources.read::<T>().unwrap_or_else(|| panic!("Resource `{}` does not exist.", id.name()))
+    }
+
+    /// Borrows a resource mutably.
+    ///
+    /// # Panics
+    /// Panics if the system did not declare that it writes `T`, if the resource doesn't exist, or
+    /// if the system is still borrowing it (see [Resources::write]).
+    #[track_caller]
+    pub fn write<T: Send + Sync + 'static>(&self) -> ResMut<'a, T> {
+        let id = ResourceId::of::<T>();
+        assert!(self.access.can_write(id), "System `{}` did not declare write access to `{}`.", self.name, id.name());
+        self.resources.write::<T>().unwrap_or_else(|| panic!("Resource `{}` does not exist.", id.name()))
+    }
+}
+
+type SystemFn = Box<dyn FnMut(&SystemContext) + Send>;
+
+/// A named unit of work with declared [Access].
+pub struct System {
+    name: &'static str,
+    access: Access,
+    after: Vec<&'static str>,
+    run: SystemFn,
+}
+
+impl System {
+    pub fn new<F: FnMut(&SystemContext) + Send + 'static>(name: &'static str, run: F) -> Self {
+        Self {
+            name,
+            access: Access::new(),
+            after: Vec::new(),
+            run: Box::new(run),
+        }
+    }
+
+    #[inline]
+    pub fn reads<T: Send + Sync + 'static>(mut self) -> Self {
+        self.access.add_read(ResourceId::of::<T>());
+        self
+    }
+
+    #[inline]
+    pub fn writes<T: Send + Sync + 'static>(mut self) -> Self {
+        self.access.add_write(ResourceId::of::<T>());
+        self
+    }
+
+    /// Requires this system to run after the system named `system`.
+    #[inline]
+    pub fn after(mut self, system: &'static str) -> Self {
+        self.after.push(system);
+        self
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub const fn access(&self) -> &Access {
+        &self.access
+    }
+}
+
+impl std::fmt::Debug for System {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("System")
+            .field("name", &self.name)
+            .field("access", &self.access)
+            .field("after", &self.after)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("System `{0}` was registered more than once.")]
+    DuplicateSystem(&'static str),
+    #[error("System `{system}` must run after `{dependency}`, which does not exist.")]
+    UnknownDependency {
+        system: &'static str,
+        dependency: &'static str,
+    },
+    #[error("Systems have cyclic ordering constraints: {0:?}")]
+    Cycle(Vec<&'static str>),
+}
+
+/// Whether the systems of a stage run one after another or at the same time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecutionMode {
+    #[default]
+    Sequential,
+    Parallel,
+}
+
+/// An ordered set of [System]s split into stages of non-conflicting systems.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    systems: Vec<System>,
+    /// Indices into `systems`, in execution order, grouped by stage.
+    stages: Option<Vec<Vec<usize>>>,
+    mode: ExecutionMode,
+}
+
+impl Scheduler {
+    #[inline]
+    pub fn new(mode: ExecutionMode) -> Self {
+        Self {
+            systems: Vec::new(),
+            stages: None,
+            mode,
+        }
+    }
+
+    #[inline]
+    pub const fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn set_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    pub fn add_system(&mut self, system: System) -> Result<(), ScheduleError> {
+        if self.systems.iter().any(|existing| existing.name == system.name) {
+            return Err(ScheduleError::DuplicateSystem(system.name));
+        }
+        self.systems.push(system);
+        self.stages = None;
+        Ok(())
+    }
+
+    /// Derives the execution order, returning the system names grouped by stage.
+    pub fn build(&mut self) -> Result<Vec<Vec<&'static str>>, ScheduleError> {
+        self.ensure_built()?;
+        let stages = self.stages.as_ref().unwrap();
+        Ok(stages.iter().map(|stage| {
+            stage.iter().map(|&index| self.systems[index].name).collect()
+        }).collect())
+    }
+
+    fn ensure_built(&mut self) -> Result<(), ScheduleError> {
+        if self.stages.is_none() {
+            self.stages = Some(self.compute_stages()?);
+        }
+        Ok(())
+    }
+
+    fn compute_stages(&self) -> Result<Vec<Vec<usize>>, ScheduleError> {
+        let count = self.systems.len();
+        let indices: HashMap<&'static str, usize> = self.systems.iter()
+            .enumerate()
+            .map(|(index, system)| (system.name, index))
+            .collect();
+        // Explicit ordering constraints.
+        let mut predecessors: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); count];
+        for (index, system) in self.systems.iter().enumerate() {
+            for &dependency in system.after.iter() {
+                let Some(&dep_index) = indices.get(dependency) else {
+                    return Err(ScheduleError::UnknownDependency { system: system.name, dependency });
+                };
+                predecessors[index].insert(dep_index);
+            }
+        }
+        // Topological sort, always picking the earliest registered ready system so the order is deterministic.
+        let mut order = Vec::with_capacity(count);
+        let mut placed = vec![false; count];
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&index| {
+                !placed[index] && predecessors[index].iter().all(|&pred| placed[pred])
+            }) else {
+                let cycle = (0..count).filter(|&index| !placed[index]).map(|index| self.systems[index].name).collect();
+                return Err(ScheduleError::Cycle(cycle));
+            };
+            placed[next] = true;
+            order.push(next);
+        }
+        // Conflicting systems keep their relative order.
+        for (position, &index) in order.iter().enumerate() {
+            for &earlier in order[..position].iter() {
+                if self.systems[index].access.conflicts_with(&self.systems[earlier].access) {
+                    predecessors[index].insert(earlier);
+                }
+            }
+        }
+        // Each system goes in the stage after its latest predecessor.
+        let mut stage_of = vec![0usize; count];
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for &index in order.iter() {
+            let stage = predecessors[index].iter().map(|&pred| stage_of[pred] + 1).max().unwrap_or(0);
+            stage_of[index] = stage;
+            if stages.len() <= stage {
+                stages.resize_with(stage + 1, Vec::new);
+            }
+            stages[stage].push(index);
+        }
+        Ok(stages)
+    }
+
+    /// Runs every system once.
+    pub fn run(&mut self, resources: &Resources) -> Result<(), ScheduleError> {
+        self.ensure_built()?;
+        let stages = self.stages.as_ref().unwrap();
+        let mode = self.mode;
+        let mut systems: Vec<Option<&mut System>> = self.systems.iter_mut().map(Some).collect();
+        for stage in stages.iter() {
+            let mut stage_systems: Vec<&mut System> = stage.iter()
+                .map(|&index| systems[index].take().expect("systems appear in exactly one stage"))
+                .collect();
+            let run_system = |system: &mut System| {
+                let context = SystemContext {
+                    name: system.name,
+                    access: &system.access,
+                    resources,
+                };
+                (system.run)(&context);
+            };
+            if mode == ExecutionMode::Parallel && stage_systems.len() > 1 {
+                std::thread::scope(|scope| {
+                    for system in stage_systems.iter_mut() {
+                        scope.spawn(|| run_system(system));
+                    }
+                });
+            } else {
+                stage_systems.into_iter().for_each(run_system);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Machines(Vec<u32>);
+    struct Belts(Vec<u32>);
+    struct Log(Vec<&'static str>);
+
+    fn scheduler(mode: ExecutionMode) -> Scheduler {
+        let mut scheduler = Scheduler::new(mode);
+        scheduler.add_system(System::new("machines", |ctx| {
+            ctx.write::<Machines>().0.iter_mut().for_each(|m| *m += 1);
+        }).writes::<Machines>()).unwrap();
+        scheduler.add_system(System::new("belts", |ctx| {
+            ctx.write::<Belts>().0.iter_mut().for_each(|b| *b *= 2);
+        }).writes::<Belts>()).unwrap();
+        scheduler.add_system(System::new("transfer", |ctx| {
+            let total: u32 = ctx.read::<Machines>().0.iter().sum();
+            ctx.write::<Belts>().0.push(total);
+            ctx.write::<Log>().0.push("transfer");
+        }).reads::<Machines>().writes::<Belts>().writes::<Log>()).unwrap();
+        scheduler.add_system(System::new("report", |ctx| {
+            ctx.write::<Log>().0.push("report");
+        }).writes::<Log>().after("belts")).unwrap();
+        scheduler
+    }
+
+    fn resources() -> Resources {
+        let mut resources = Resources::new();
+        resources.insert(Machines(vec![1, 2, 3]));
+        resources.insert(Belts(vec![5]));
+        resources.insert(Log(Vec::new()));
+        resources
+    }
+
+    #[test]
+    fn stage_test() {
+        let mut scheduler = scheduler(ExecutionMode::Sequential);
+        let stages = scheduler.build().unwrap();
+        assert_eq!(stages, vec![
+            vec!["machines", "belts"],
+            vec!["transfer"],
+            vec!["report"],
+        ]);
+    }
+
+    #[test]
+    fn parallel_determinism_test() {
+        let mut sequential = scheduler(ExecutionMode::Sequential);
+        let mut parallel = scheduler(ExecutionMode::Parallel);
+        let seq_resources = resources();
+        let par_resources = resources();
+        for _ in 0..10 {
+            sequential.run(&seq_resources).unwrap();
+            parallel.run(&par_resources).unwrap();
+        }
+        assert_eq!(seq_resources.read::<Belts>().unwrap().0, par_resources.read::<Belts>().unwrap().0);
+        assert_eq!(seq_resources.read::<Log>().unwrap().0, par_resources.read::<Log>().unwrap().0);
+        assert_eq!(par_resources.read::<Log>().unwrap().0[..2], ["transfer", "report"]);
+    }
+
+    #[test]
+    fn error_test() {
+        let mut scheduler = Scheduler::default();
+        scheduler.add_system(System::new("a", |_| ()).after("b")).unwrap();
+        assert_eq!(scheduler.add_system(System::new("a", |_| ())), Err(ScheduleError::DuplicateSystem("a")));
+        assert_eq!(
+            scheduler.build(),
+            Err(ScheduleError::UnknownDependency { system: "a", dependency: "b" }),
+        );
+        scheduler.add_system(System::new("b", |_| ()).after("a")).unwrap();
+        assert_eq!(scheduler.build(), Err(ScheduleError::Cycle(vec!["a", "b"])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn undeclared_access_test() {
+        let mut scheduler = Scheduler::default();
+        scheduler.add_system(System::new("sneaky", |ctx| {
+            ctx.write::<Machines>();
+        }).reads::<Machines>()).unwrap();
+        scheduler.run(&resources()).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+};
+
+type AnyResource = Box<dyn Any + Send + Sync>;
+
+/// Type-keyed storage for the data that systems operate on.
+#[derive(Debug, Default)]
+pub struct Resources {
+    map: HashMap<TypeId, RwLock<AnyResource>>,
+}
+
+impl Resources {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a resource, returning the previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map.insert(TypeId::of::<T>(), RwLock::new(Box::new(value)))
+            .and_then(|old| old.into_inner().ok())
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map.remove(&TypeId::of::<T>())
+            .and_then(|old| old.into_inner().ok())
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    #[inline]
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Exclusive access without locking.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map.get_mut(&TypeId::of::<T>())
+            .and_then(|lock| lock.get_mut().ok())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Borrows a resource immutably.
+    ///
+    /// Borrows never wait: systems that run at the same time never conflict, so a conflicting
+    /// borrow is a bug that would otherwise deadlock.
+    ///
+    /// # Panics
+    /// Panics if the resource is already borrowed mutably.
+    #[track_caller]
+    pub fn read<T: Send + Sync + 'static>(&self) -> Option<Res<'_, T>> {
+        let lock = self.map.get(&TypeId::of::<T>())?;
+        let guard = match lock.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poison)) => poison.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("Resource `{}` is already borrowed mutably.", std::any::type_name::<T>()),
+        };
+        guard.is::<T>().then(|| Res { guard, _phantom: PhantomData })
+    }
+
+    /// Borrows a resource mutably. Like [Resources::read], this never waits.
+    ///
+    /// # Panics
+    /// Panics if the resource is already borrowed.
+    #[track_caller]
+    pub fn write<T: Send + Sync + 'static>(&self) -> Option<ResMut<'_, T>> {
+        let lock = self.map.get(&TypeId::of::<T>())?;
+        let guard = match lock.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poison)) => poison.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("Resource `{}` is already borrowed.", std::any::type_name::<T>()),
+        };
+        guard.is::<T>().then(|| ResMut { guard, _phantom: PhantomData })
+    }
+}
+
+/// An immutable borrow of a resource.
+pub struct Res<'a, T: 'static> {
+    guard: RwLockReadGuard<'a, AnyResource>,
+    _phantom: PhantomData<&'a T>,
+}
+
+impl<T: 'static> Deref for Res<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // The type was checked when the borrow was created.
+        self.guard.downcast_ref().unwrap()
+    }
+}
+
+/// A mutable borrow of a resource.
+pub struct ResMut<'a, T: 'static> {
+    guard: RwLockWriteGuard<'a, AnyResource>,
+    _phantom: PhantomData<&'a mut T>,
+}
+
+impl<T: 'static> Deref for ResMut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.guard.downcast_ref().unwrap()
+    }
+}
+
+impl<T: 'static> DerefMut for ResMut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.downcast_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_borrow_test() {
+        let mut resources = Resources::new();
+        resources.insert(7u32);
+        let a = resources.read::<u32>().unwrap();
+        let b = resources.read::<u32>().unwrap();
+        assert_eq!(*a + *b, 14);
+        drop((a, b));
+        *resources.write::<u32>().unwrap() += 1;
+        assert_eq!(*resources.read::<u32>().unwrap(), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "Resource `u32` is already borrowed mutably.")]
+    fn conflicting_read_test() {
+        let mut resources = Resources::new();
+        resources.insert(7u32);
+        let _write = resources.write::<u32>().unwrap();
+        resources.read::<u32>();
+    }
+
+    #[test]
+    #[should_panic(expected = "Resource `u32` is already borrowed.")]
+    fn conflicting_write_test() {
+        let mut resources = Resources::new();
+        resources.insert(7u32);
+        let _read = resources.read::<u32>().unwrap();
+        resources.write::<u32>();
+    }
+}
@@ -0,0 +1,616 @@
+//! Snapshots of a live game for players joining late.
+//!
+//! A [Snapshot] is the whole simulation state that a joining client needs: the world header (the
+//! [Game] itself), the voxels of every loaded chunk, their entities, and their machines. It's
+//! written as a stream of [frames](mfcereal::frame), one per chunk payload, and sent as
+//! [SnapshotMessage]s of at most a given size. Chunks are written in their smallest
+//! [ChunkFormat](mfworld::chunk::format::ChunkFormat); the stream itself isn't compressed.
+//!
+//! # Consistency
+//!
+//! A snapshot is taken at a tick barrier: [Snapshot::take] borrows the state between two ticks, and
+//! encodes all of it before returning, so everything in it is from the same tick and the game
+//! keeps ticking while it's sent. The header carries the tick and the [Game::state_hash], which the
+//! [SnapshotReceiver] checks against the state it rebuilt.
+//!
+//! # Resuming
+//!
+//! The receiver's [SnapshotCursor] is the tick of the snapshot and how much of it has arrived. A
+//! client that reconnects sends its cursor back, and the server carries on from it with
+//! [Snapshot::message]. If the server has taken a newer snapshot since, it answers
+//! [SyncError::Stale], and the client starts over with a new receiver.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder, UnexpectedEof},
+    encode::{Encode, Encoder},
+    frame::{FrameEncoder, FrameHeader, HEADER_LEN},
+};
+use mfhash::Hash128;
+use mfworld::{
+    bounds::HeightBounds,
+    chunk::{stored::StoredChunk, BlockPos, ChunkPos},
+    entity::{chunk_at, ChunkEntities, EntityWorld},
+    history::EditHistory,
+};
+
+use super::{
+    clock::GameClock, environment::Environment, machine::MachineEntity, mode::GameMode, player::Player, rules::GameRules,
+    world::World, Game,
+};
+
+/// The payload size of messages, unless another is asked for.
+pub const DEFAULT_PAYLOAD_LEN: usize = 16 * 1024;
+/// The largest payload a message may have. Larger messages fail to decode, and so do frames with
+/// a larger body, so that a receiver never buffers more than this of a frame.
+pub const MAX_PAYLOAD_LEN: usize = 1024 * 1024;
+
+/// The frame tags of a snapshot stream.
+pub mod tags {
+    /// The tick, state hash, and [Game](crate::game::Game). Always first.
+    pub const HEADER: u32 = 1;
+    /// A loaded chunk's position and voxels.
+    pub const CHUNK: u32 = 2;
+    /// A chunk's position and entities.
+    pub const ENTITIES: u32 = 3;
+    /// A chunk's position and the machines in it.
+    pub const MACHINES: u32 = 4;
+    /// The end of the snapshot. Always last.
+    pub const END: u32 = 5;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    /// The cursor is for a snapshot that has been replaced.
+    #[error("The snapshot of tick {cursor} was replaced by the snapshot of tick {snapshot}.")]
+    Stale {
+        snapshot: u64,
+        cursor: u64,
+    },
+    /// A message didn't start where the last one ended.
+    #[error("Expected a message at offset {expected}, but it was at offset {found}.")]
+    OutOfOrder {
+        expected: u64,
+        found: u64,
+    },
+    #[error("Invalid snapshot: {0}")]
+    Invalid(DecodeError<UnexpectedEof>),
+    #[error("The snapshot hasn't been received in full.")]
+    Incomplete,
+    /// The rebuilt state doesn't hash to the state hash that was sent.
+    #[error("The snapshot's state doesn't match its state hash.")]
+    StateMismatch,
+}
+
+impl From<DecodeError<UnexpectedEof>> for SyncError {
+    #[inline]
+    fn from(error: DecodeError<UnexpectedEof>) -> Self {
+        Self::Invalid(error)
+    }
+}
+
+/// How much of a snapshot a client has received.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SnapshotCursor {
+    /// The tick of the snapshot, or `None` before the first message.
+    pub tick: Option<u64>,
+    /// The bytes of the stream received so far.
+    pub offset: u64,
+}
+
+impl SnapshotCursor {
+    /// The cursor of a client that hasn't received anything.
+    pub const START: Self = Self { tick: None, offset: 0 };
+}
+
+// Layout: tick (Option<u64>), offset (u64).
+impl Encode for SnapshotCursor {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(self.tick.encode(encoder)? + encoder.write_u64(self.offset)?)
+    }
+}
+
+impl Decode for SnapshotCursor {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(Self { tick: Option::decode(decoder)?, offset: decoder.read_u64()? })
+    }
+}
+
+/// A piece of a snapshot stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMessage {
+    pub tick: u64,
+    /// Where the payload starts in the stream.
+    pub offset: u64,
+    /// The length of the whole stream.
+    pub total: u64,
+    pub payload: Vec<u8>,
+}
+
+impl SnapshotMessage {
+    /// Whether this is the last message of the snapshot.
+    #[inline]
+    #[must_use]
+    pub fn is_last(&self) -> bool {
+        self.offset + self.payload.len() as u64 == self.total
+    }
+}
+
+// Layout: tick (u64), offset (u64), total (u64), payload length (u32), payload.
+impl Encode for SnapshotMessage {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        Ok(
+            encoder.write_u64(self.tick)?
+            + encoder.write_u64(self.offset)?
+            + encoder.write_u64(self.total)?
+            + encoder.write_u32(self.payload.len() as u32)?
+            + encoder.write_exact(&self.payload)?
+        )
+    }
+}
+
+impl Decode for SnapshotMessage {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let tick = decoder.read_u64()?;
+        let offset = decoder.read_u64()?;
+        let total = decoder.read_u64()?;
+        let len = decoder.read_u32()? as usize;
+        if len > MAX_PAYLOAD_LEN {
+            return Err(DecodeError::InvalidData("snapshot message is too large"));
+        }
+        if offset.checked_add(len as u64).is_none_or(|end| end > total) {
+            return Err(DecodeError::InvalidData("snapshot message is past the end of the snapshot"));
+        }
+        let mut payload = vec![0; len];
+        decoder.read_exact(&mut payload)?;
+        Ok(Self { tick, offset, total, payload })
+    }
+}
+
+/// The state of a game at a tick, encoded for sending. See the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    tick: u64,
+    stream: Vec<u8>,
+    /// Where each frame starts, so that messages end on frames when they can.
+    frames: Vec<usize>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the state before `clock`'s next tick. `chunks` must hold every chunk
+    /// that's loaded in `game`. Chunks that aren't loaded are left out, along with their machines.
+    pub fn take<T: Encode>(
+        clock: &GameClock,
+        game: &Game,
+        chunks: &BTreeMap<ChunkPos, StoredChunk>,
+        entities: &EntityWorld<T>,
+        machines: &BTreeMap<BlockPos, MachineEntity>,
+    ) -> Self {
+        let tick = clock.tick();
+        let mut encoder = FrameEncoder::new();
+        let mut frames = Vec::new();
+        let mut frame = |encoder: &mut FrameEncoder, tag: u32, body: &dyn Fn(&mut FrameEncoder)| {
+            frames.push(encoder.bytes().len());
+            encoder.begin_frame(tag);
+            body(encoder);
+            encoder.end_frame();
+        };
+
+        frame(&mut encoder, tags::HEADER, &|encoder| {
+            let Ok(_) = encode_header(tick, game, entities.next_id(), encoder);
+        });
+        for chunk in game.world.iter_loaded_chunks_deterministic() {
+            let Some(stored) = chunks.get(&chunk) else {
+                continue;
+            };
+            frame(&mut encoder, tags::CHUNK, &|encoder| {
+                let Ok(_) = chunk.encode(encoder);
+                let Ok(_) = stored.encode(encoder);
+            });
+        }
+        for (chunk, entities) in entities.iter_chunks().filter(|(_, entities)| !entities.is_empty()) {
+            frame(&mut encoder, tags::ENTITIES, &|encoder| {
+                let Ok(_) = chunk.encode(encoder);
+                let Ok(_) = entities.encode(encoder);
+            });
+        }
+        let mut by_chunk = BTreeMap::<ChunkPos, Vec<(BlockPos, MachineEntity)>>::new();
+        for (&pos, &machine) in machines {
+            if let Some(chunk) = pos.chunk().filter(|&chunk| game.world.is_loaded(chunk)) {
+                by_chunk.entry(chunk).or_default().push((pos, machine));
+            }
+        }
+        for (chunk, machines) in &by_chunk {
+            frame(&mut encoder, tags::MACHINES, &|encoder| {
+                let Ok(_) = chunk.encode(encoder);
+                let Ok(_) = encoder.write_u32(machines.len() as u32);
+                for (pos, machine) in machines {
+                    let Ok(_) = pos.encode(encoder);
+                    let Ok(_) = machine.encode(encoder);
+                }
+            });
+        }
+        frame(&mut encoder, tags::END, &|_| {});
+        Self { tick, stream: encoder.finish(), frames }
+    }
+
+    /// The tick the snapshot was taken before.
+    #[inline]
+    #[must_use]
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The length of the whole stream.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.stream.len()
+    }
+
+    /// Whether the stream is empty, which it never is: it always has a header and an end.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.stream.is_empty()
+    }
+
+    /// The message after `cursor`, with a payload of at most `max_payload` bytes, or `None` once
+    /// the client has the whole snapshot. Messages end on a frame when one fits, and only frames
+    /// larger than `max_payload` are split.
+    ///
+    /// # Panics
+    /// If `max_payload` is 0 or larger than [MAX_PAYLOAD_LEN].
+    pub fn message(&self, cursor: SnapshotCursor, max_payload: usize) -> Result<Option<SnapshotMessage>, SyncError> {
+        assert!((1..=MAX_PAYLOAD_LEN).contains(&max_payload), "Invalid message size: {max_payload}");
+        if let Some(tick) = cursor.tick.filter(|&tick| tick != self.tick) {
+            return Err(SyncError::Stale { snapshot: self.tick, cursor: tick });
+        }
+        let start = usize::try_from(cursor.offset).unwrap_or(usize::MAX);
+        if start >= self.stream.len() {
+            return Ok(None);
+        }
+        let limit = start.saturating_add(max_payload).min(self.stream.len());
+        let end = match self.frames.partition_point(|&frame| frame <= limit) {
+            // Every frame after the cursor starts past the limit.
+            _ if limit == self.stream.len() => limit,
+            after => match self.frames[..after].last() {
+                Some(&frame) if frame > start => frame,
+                _ => limit,
+            },
+        };
+        Ok(Some(SnapshotMessage {
+            tick: self.tick,
+            offset: start as u64,
+            total: self.stream.len() as u64,
+            payload: self.stream[start..end].to_vec(),
+        }))
+    }
+}
+
+// Layout: tick (u64), state hash (u128), game mode, game rules, environment, height bounds, loaded chunks and ticking chunks
+// (each a count (u32) and the chunks in Morton order), the player's edit history, and the next
+// entity id (u64).
+fn encode_header<E: Encoder>(tick: u64, game: &Game, next_entity_id: u64, encoder: &mut E) -> Result<u64, E::Error> {
+    let mut written = encoder.write_u64(tick)?
+        + game.state_hash().encode(encoder)?
+        + game.mode.encode(encoder)?
+        + game.rules.encode(encoder)?
+        + game.environment.encode(encoder)?
+        + game.world.bounds().encode(encoder)?;
+    let loaded = game.world.iter_loaded_chunks_deterministic().collect::<Vec<_>>();
+    let ticking = game.world.iter_ticking_chunks_deterministic().collect::<Vec<_>>();
+    for chunks in [loaded, ticking] {
+        written += encoder.write_u32(chunks.len() as u32)?;
+        for chunk in chunks {
+            written += chunk.encode(encoder)?;
+        }
+    }
+    Ok(written + game.player.edit_history.encode(encoder)? + encoder.write_u64(next_entity_id)?)
+}
+
+/// The state a [SnapshotReceiver] rebuilt.
+pub struct SyncedState<T> {
+    pub clock: GameClock,
+    pub game: Game,
+    pub chunks: BTreeMap<ChunkPos, StoredChunk>,
+    pub entities: EntityWorld<T>,
+    pub machines: BTreeMap<BlockPos, MachineEntity>,
+}
+
+/// Rebuilds the state in a snapshot from its messages.
+pub struct SnapshotReceiver<T> {
+    cursor: SnapshotCursor,
+    /// Received bytes that don't make a whole frame yet.
+    pending: Vec<u8>,
+    header: Option<(Hash128, Game)>,
+    chunks: BTreeMap<ChunkPos, StoredChunk>,
+    entities: EntityWorld<T>,
+    machines: BTreeMap<BlockPos, MachineEntity>,
+    ended: bool,
+}
+
+impl<T> Default for SnapshotReceiver<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SnapshotReceiver<T> {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cursor: SnapshotCursor::START,
+            pending: Vec::new(),
+            header: None,
+            chunks: BTreeMap::new(),
+            entities: EntityWorld::new(),
+            machines: BTreeMap::new(),
+            ended: false,
+        }
+    }
+
+    /// What to ask the server for next.
+    #[inline]
+    #[must_use]
+    pub const fn cursor(&self) -> SnapshotCursor {
+        self.cursor
+    }
+
+    /// Whether the whole snapshot has arrived.
+    #[inline]
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.ended
+    }
+
+    /// The rebuilt state, once the whole snapshot has arrived and its state hash checks out.
+    pub fn finish(self) -> Result<SyncedState<T>, SyncError> {
+        let (Some(tick), Some((state_hash, game)), true) = (self.cursor.tick, self.header, self.ended) else {
+            return Err(SyncError::Incomplete);
+        };
+        if game.state_hash() != state_hash {
+            return Err(SyncError::StateMismatch);
+        }
+        Ok(SyncedState {
+            clock: GameClock::new(tick),
+            game,
+            chunks: self.chunks,
+            entities: self.entities,
+            machines: self.machines,
+        })
+    }
+}
+
+impl<T: Decode> SnapshotReceiver<T> {
+    /// Adds the next message of the snapshot. Returns whether the snapshot is complete.
+    pub fn receive(&mut self, message: &SnapshotMessage) -> Result<bool, SyncError> {
+        if let Some(tick) = self.cursor.tick.filter(|&tick| tick != message.tick) {
+            return Err(SyncError::Stale { snapshot: message.tick, cursor: tick });
+        }
+        if message.offset != self.cursor.offset {
+            return Err(SyncError::OutOfOrder { expected: self.cursor.offset, found: message.offset });
+        }
+        self.cursor = SnapshotCursor { tick: Some(message.tick), offset: message.offset + message.payload.len() as u64 };
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.extend_from_slice(&message.payload);
+
+        let mut rest = pending.as_slice();
+        while let Ok(FrameHeader { len, .. }) = FrameHeader::decode(&mut { rest }) {
+            // The length comes from the peer.
+            let Some(frame_len) = HEADER_LEN.checked_add(len).filter(|_| len <= MAX_PAYLOAD_LEN) else {
+                return Err(SyncError::Invalid(DecodeError::InvalidData("snapshot frame is too large")));
+            };
+            if rest.len() < frame_len {
+                break;
+            }
+            let (mut frame, after) = rest.split_at(frame_len);
+            self.read_frame(&mut frame, message.tick)?;
+            rest = after;
+        }
+        self.pending = rest.to_vec();
+        if message.is_last() && !(self.ended && self.pending.is_empty()) {
+            return Err(SyncError::Invalid(DecodeError::InvalidData("snapshot ended without an end frame")));
+        }
+        Ok(self.ended)
+    }
+
+    fn read_frame(&mut self, stream: &mut &[u8], tick: u64) -> Result<(), DecodeError<UnexpectedEof>> {
+        let mut frame = stream.enter_frame()?;
+        if self.ended {
+            return Err(DecodeError::InvalidData("snapshot frame after the end frame"));
+        }
+        if (frame.tag() == tags::HEADER) == self.header.is_some() {
+            return Err(DecodeError::InvalidData("snapshot doesn't start with exactly one header"));
+        }
+        match frame.tag() {
+            tags::HEADER => {
+                if frame.read_u64()? != tick {
+                    return Err(DecodeError::InvalidData("snapshot header is for another tick"));
+                }
+                let state_hash = Hash128::decode(&mut frame)?;
+                let mode = GameMode::decode(&mut frame)?;
+                let rules = GameRules::decode(&mut frame)?;
+                let environment = Environment::decode(&mut frame)?;
+                let mut world = World::with_bounds(HeightBounds::decode(&mut frame)?);
+                for ticking in [false, true] {
+                    for _ in 0..frame.read_u32()? {
+                        world.insert_chunk(ChunkPos::decode(&mut frame)?, ticking)
+                            .map_err(|_| DecodeError::InvalidData("snapshot chunk is outside of the height bounds"))?;
+                    }
+                }
+                let player = Player { edit_history: EditHistory::decode(&mut frame)? };
+                self.entities.set_next_id(frame.read_u64()?);
+                self.header = Some((state_hash, Game { world, player, mode, rules, environment }));
+            }
+            tags::CHUNK => {
+                let chunk = ChunkPos::decode(&mut frame)?;
+                self.chunks.insert(chunk, StoredChunk::decode(&mut frame)?);
+            }
+            tags::ENTITIES => {
+                let chunk = ChunkPos::decode(&mut frame)?;
+                let entities = ChunkEntities::<T>::decode(&mut frame)?;
+                if entities.iter().any(|entity| chunk_at(entity.position()) != chunk) {
+                    return Err(DecodeError::InvalidData("snapshot entity is outside of its chunk"));
+                }
+                self.entities.load_chunk(chunk, entities)
+                    .map_err(|_| DecodeError::InvalidData("snapshot entity is in more than one chunk"))?;
+            }
+            tags::MACHINES => {
+                let chunk = ChunkPos::decode(&mut frame)?;
+                for _ in 0..frame.read_u32()? {
+                    let pos = BlockPos::decode(&mut frame)?;
+                    if pos.chunk() != Some(chunk) {
+                        return Err(DecodeError::InvalidData("snapshot machine is outside of its chunk"));
+                    }
+                    self.machines.insert(pos, MachineEntity::decode(&mut frame)?);
+                }
+            }
+            tags::END => self.ended = true,
+            // Sections from newer versions.
+            _ => {}
+        }
+        frame.exit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfworld::{
+        history::{VoxelEdit, VoxelState},
+        ticket::{ChunkTickets, Ticket},
+        voxel::id::VoxelId,
+    };
+
+    use super::*;
+    use crate::game::machine::sides::SideConfig;
+
+    struct Live {
+        clock: GameClock,
+        game: Game,
+        chunks: BTreeMap<ChunkPos, StoredChunk>,
+        entities: EntityWorld<u32>,
+        machines: BTreeMap<BlockPos, MachineEntity>,
+    }
+
+    fn live() -> Live {
+        let mut game = Game {
+            world: World::new(),
+            player: Player::default(),
+            mode: GameMode::Survival,
+            rules: GameRules::new(),
+            environment: Environment::new(0),
+        };
+        let mut tickets = ChunkTickets::new();
+        tickets.add(Ticket::player(ChunkPos::new(0, 0, 0), 1));
+        game.world.sync_tickets(&mut tickets);
+        game.player.edit_history = EditHistory::new(4);
+        game.player.edit_history.record(VoxelEdit {
+            position: BlockPos::new(1, 2, 3),
+            before: VoxelState::default(),
+            after: VoxelState::new(VoxelId::new(2), Orientation::UNORIENTED),
+        });
+
+        let mut chunks = BTreeMap::new();
+        let mut entities = EntityWorld::new();
+        for (index, chunk) in game.world.iter_loaded_chunks_deterministic().enumerate() {
+            let mut stored = StoredChunk::new();
+            for voxel in 0..index * 100 {
+                stored.set(voxel, VoxelState::new(VoxelId::new(1 + voxel as u32 % 3), Orientation::UNORIENTED));
+            }
+            chunks.insert(chunk, stored);
+            entities.load_chunk(chunk, ChunkEntities::new()).unwrap();
+        }
+        entities.spawn((1.5, 2.0, 3.0), 7).unwrap();
+        entities.spawn((-10.0, 2.0, 3.0), 8).unwrap();
+        // An unloaded chunk, and a machine in it, aren't sent.
+        chunks.insert(ChunkPos::new(9, 0, 0), StoredChunk::new());
+        let machines = BTreeMap::from([
+            (BlockPos::new(1, 2, 3), MachineEntity::new(Orientation::ROTATE_Y, SideConfig::default())),
+            (BlockPos::new(-5, 0, 20), MachineEntity::default()),
+            (BlockPos::new(300, 0, 0), MachineEntity::default()),
+        ]);
+        Live { clock: GameClock::new(120), game, chunks, entities, machines }
+    }
+
+    fn snapshot(live: &Live) -> Snapshot {
+        Snapshot::take(&live.clock, &live.game, &live.chunks, &live.entities, &live.machines)
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let live = live();
+        let snapshot = snapshot(&live);
+        assert_eq!(snapshot.tick(), 120);
+
+        // Send it in small messages, reconnecting halfway through.
+        let mut receiver = SnapshotReceiver::<u32>::new();
+        let mut messages = 0;
+        while let Some(message) = snapshot.message(receiver.cursor(), 1000).unwrap() {
+            assert!(message.payload.len() <= 1000);
+            let mut bytes = Vec::new();
+            let Ok(_) = message.encode(&mut bytes);
+            let message = SnapshotMessage::decode(&mut bytes.as_slice()).unwrap();
+            assert_eq!(receiver.receive(&message).unwrap(), message.is_last());
+            messages += 1;
+            if messages == 3 {
+                let mut bytes = Vec::new();
+                let Ok(_) = receiver.cursor().encode(&mut bytes);
+                assert_eq!(SnapshotCursor::decode(&mut bytes.as_slice()).unwrap(), receiver.cursor());
+            }
+        }
+        assert!(messages > 3 && receiver.is_complete());
+
+        let synced = receiver.finish().unwrap();
+        assert_eq!(synced.clock.tick(), 120);
+        assert_eq!(synced.game.state_hash(), live.game.state_hash());
+        assert_eq!(synced.game.world, live.game.world);
+        assert_eq!(synced.chunks.len(), 27);
+        assert!(synced.chunks.iter().all(|(chunk, stored)| live.chunks[chunk] == *stored));
+        assert_eq!(synced.entities.len(), 2);
+        assert_eq!(synced.entities.next_id(), live.entities.next_id());
+        assert_eq!(synced.machines.keys().collect::<Vec<_>>(), [&BlockPos::new(-5, 0, 20), &BlockPos::new(1, 2, 3)]);
+
+        // Frames that fit in a message aren't split.
+        let whole = snapshot.message(SnapshotCursor::START, MAX_PAYLOAD_LEN).unwrap().unwrap();
+        assert_eq!(whole.payload.len(), snapshot.len());
+        let first = snapshot.message(SnapshotCursor::START, snapshot.frames[2] + 1).unwrap().unwrap();
+        assert_eq!(first.payload.len(), snapshot.frames[2]);
+    }
+
+    #[test]
+    fn stale_snapshot_test() {
+        let mut live = live();
+        let old = snapshot(&live);
+        let mut receiver = SnapshotReceiver::<u32>::new();
+        receiver.receive(&old.message(receiver.cursor(), 100).unwrap().unwrap()).unwrap();
+
+        live.clock = GameClock::new(121);
+        let new = snapshot(&live);
+        assert!(matches!(new.message(receiver.cursor(), 100), Err(SyncError::Stale { snapshot: 121, cursor: 120 })));
+        let skipped = SnapshotCursor { offset: 200, ..receiver.cursor() };
+        let ahead = old.message(skipped, 100).unwrap().unwrap();
+        assert!(matches!(receiver.receive(&ahead), Err(SyncError::OutOfOrder { expected: 100, found: 200 })));
+        assert!(matches!(SnapshotReceiver::<u32>::new().finish(), Err(SyncError::Incomplete)));
+
+        // A state that doesn't match its hash is rejected.
+        let mut receiver = SnapshotReceiver::<u32>::new();
+        let mut message = new.message(SnapshotCursor::START, MAX_PAYLOAD_LEN).unwrap().unwrap();
+        let mode = HEADER_LEN + 8 + 16;
+        message.payload[mode] = GameMode::Creative.to_u8();
+        assert!(receiver.receive(&message).unwrap());
+        assert!(matches!(receiver.finish(), Err(SyncError::StateMismatch)));
+
+        // Frame lengths that would overflow or outgrow a message are rejected.
+        for len in [usize::MAX, MAX_PAYLOAD_LEN + 1] {
+            let mut payload = Vec::new();
+            let Ok(_) = FrameHeader { tag: tags::HEADER, len }.encode(&mut payload);
+            let message = SnapshotMessage { tick: 121, offset: 0, total: payload.len() as u64, payload };
+            assert!(matches!(SnapshotReceiver::<u32>::new().receive(&message), Err(SyncError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Tools: their mining tier, speed and durability, and the wear they take from use.
+//!
+//! A tool's wear is kept in its stack's [StackData] under [DataKey::DAMAGE], so it's saved with the
+//! inventory the tool is in. A tool breaks (its slot empties) when its damage reaches its durability.
+
+use mfworld::voxel::hardness::MiningTier;
+
+use crate::game::{
+    crafting::item::{ItemId, ItemType},
+    events::{Event, EventBus},
+    inventory::{DataKey, Inventories, InventoryError, ItemStack, SlotRef},
+    mode::GameMode,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ToolDef {
+    pub tier: MiningTier,
+    /// Break speed as a percentage of breaking by hand.
+    pub speed: u32,
+    /// The number of uses before the tool breaks.
+    pub durability: u32,
+}
+
+const COPPER_PICKAXE: u32 = ItemType::CopperPickaxe.id().get();
+const IRON_PICKAXE: u32 = ItemType::IronPickaxe.id().get();
+const STEEL_PICKAXE: u32 = ItemType::SteelPickaxe.id().get();
+
+impl ToolDef {
+    #[inline]
+    #[must_use]
+    pub const fn new(tier: MiningTier, speed: u32, durability: u32) -> Self {
+        Self { tier, speed, durability }
+    }
+
+    /// The tool `item` is, if it's a tool.
+    #[must_use]
+    pub const fn of(item: ItemId) -> Option<ToolDef> {
+        match item.get() {
+            COPPER_PICKAXE => Some(ToolDef::new(MiningTier::COPPER, 200, 128)),
+            IRON_PICKAXE => Some(ToolDef::new(MiningTier::IRON, 300, 256)),
+            STEEL_PICKAXE => Some(ToolDef::new(MiningTier::STEEL, 450, 1024)),
+            _ => None,
+        }
+    }
+}
+
+/// The durability `stack` has lost.
+#[inline]
+#[must_use]
+pub fn damage(stack: &ItemStack) -> u32 {
+    stack.data.get(DataKey::DAMAGE).unwrap_or(0)
+}
+
+/// The uses `stack` has left, if it's a tool.
+#[inline]
+#[must_use]
+pub fn durability_left(stack: &ItemStack) -> Option<u32> {
+    ToolDef::of(stack.item).map(|tool| tool.durability.saturating_sub(damage(stack)))
+}
+
+/// The result of wearing a tool down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wear {
+    Worn { remaining: u32 },
+    Broke,
+}
+
+/// Wears the tool in `stack` down by `amount` uses. Returns `None` if `stack` isn't a tool.
+#[must_use]
+pub fn wear(stack: ItemStack, amount: u32) -> Option<(Option<ItemStack>, Wear)> {
+    let tool = ToolDef::of(stack.item)?;
+    let damage = damage(&stack).saturating_add(amount);
+    if damage >= tool.durability {
+        return Some((None, Wear::Broke));
+    }
+    let mut data = stack.data;
+    data.insert(DataKey::DAMAGE, damage);
+    Some((Some(stack.with_data(data)), Wear::Worn { remaining: tool.durability - damage }))
+}
+
+/// Wears down the tool in `slot` after it was used, emitting [Event::ToolWorn]. Tools don't wear in
+/// modes with infinite materials. Returns `None` if nothing was worn.
+pub fn wear_tool(
+    inventories: &mut Inventories,
+    slot: SlotRef,
+    amount: u32,
+    mode: GameMode,
+    events: &mut EventBus,
+) -> Result<Option<Wear>, InventoryError> {
+    let Some(stack) = inventories.get(slot)? else {
+        return Ok(None);
+    };
+    if mode.infinite_materials() || amount == 0 {
+        return Ok(None);
+    }
+    let Some((worn, wear)) = wear(stack, amount) else {
+        return Ok(None);
+    };
+    inventories.set(slot, worn, events)?;
+    let remaining = match wear {
+        Wear::Worn { remaining } => remaining,
+        Wear::Broke => 0,
+    };
+    events.emit(Event::ToolWorn { slot, item: stack.item, remaining });
+    Ok(Some(wear))
+}
+
+#[cfg(test)]
+mod tests {
+    use mfcereal::{decode::Decode, encode::Encode};
+
+    use super::*;
+    use crate::game::{
+        events::{EventFilter, EventKind},
+        inventory::{Container, ContainerId, Transaction},
+    };
+
+    #[test]
+    fn wear_test() {
+        let mut inventories = Inventories::new();
+        let mut events = EventBus::new();
+        let hud = events.subscribe(EventFilter::only(EventKind::ToolWorn), 16);
+        inventories.insert(ContainerId::PLAYER, Container::new(2));
+        let slot = SlotRef::new(ContainerId::PLAYER, 0);
+        let pickaxe = ItemType::CopperPickaxe.id();
+        inventories.set(slot, Some(ItemStack::new(pickaxe, 1)), &mut events).unwrap();
+
+        assert_eq!(wear_tool(&mut inventories, slot, 1, GameMode::Creative, &mut events), Ok(None));
+        assert_eq!(wear_tool(&mut inventories, slot, 100, GameMode::Survival, &mut events), Ok(Some(Wear::Worn { remaining: 28 })));
+        let worn = inventories.get(slot).unwrap().unwrap();
+        assert_eq!((damage(&worn), durability_left(&worn)), (100, Some(28)));
+
+        // The wear survives a round trip through the stack's encoding.
+        let mut bytes = Vec::new();
+        worn.encode(&mut bytes).unwrap();
+        assert_eq!(ItemStack::decode(&mut bytes.as_slice()).unwrap(), worn);
+
+        // Tools don't stack, and worn tools don't merge with fresh ones.
+        inventories.set(SlotRef::new(ContainerId::PLAYER, 1), Some(ItemStack::new(pickaxe, 1)), &mut events).unwrap();
+        inventories.apply(Transaction::PickUp(slot), &mut events).unwrap();
+        assert!(inventories.apply(Transaction::PlaceOne(SlotRef::new(ContainerId::PLAYER, 1)), &mut events).is_err());
+        inventories.apply(Transaction::Place(slot), &mut events).unwrap();
+
+        assert_eq!(wear_tool(&mut inventories, slot, 28, GameMode::Survival, &mut events), Ok(Some(Wear::Broke)));
+        assert_eq!(inventories.get(slot), Ok(None));
+        assert_eq!(wear_tool(&mut inventories, slot, 1, GameMode::Survival, &mut events), Ok(None));
+
+        events.end_tick(0);
+        assert_eq!(events.drain(hud).collect::<Vec<_>>(), [
+            Event::ToolWorn { slot, item: pickaxe, remaining: 28 },
+            Event::ToolWorn { slot, item: pickaxe, remaining: 0 },
+        ]);
+    }
+}
@@ -0,0 +1,525 @@
+//! Validation of actions before they reach the simulation.
+//!
+//! Actions that actors ask for (breaking, placing or using a voxel) are meant to go through a
+//! [Validator] before they touch the game. Nothing receives actions from actors yet, so nothing
+//! calls it: player edits and scenario commands are applied directly, and whatever accepts actions
+//! from peers is expected to [submit](Validator::submit) each one first. An action is rejected if:
+//!
+//! 1. Its sequence number isn't after the last one accepted from the actor: it was already handled,
+//!    or arrived out of order.
+//! 2. The actor's last action of the same kind was less than its cooldown ago.
+//! 3. Its target is outside of the world's [height bounds](mfworld::bounds).
+//! 4. Its target is farther from the actor than their reach.
+//! 5. Its target's chunk isn't loaded.
+//! 6. Its target is in a structure owned by another actor, or in an unowned (generated) structure
+//!    while those are protected.
+//!
+//! The first failing check is the [Rejection]. Checking never changes anything:
+//! [check](Validator::check) takes `&self`, and [submit](Validator::submit) only records an action
+//! once it's accepted. So a rejected action leaves no trace, checking it again gives the same
+//! rejection, and a stream of invalid or malicious actions can't change the deterministic state or
+//! make peers that rejected it diverge. Rejections encode, so they can be sent back to the actor.
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mfhash::{
+    canonical::hash_encoded,
+    deterministic::{DeterministicHash, DeterministicHasher},
+};
+use mfworld::{
+    bounds::HeightError,
+    chunk::BlockPos,
+    structure::{StructureId, StructureIndex},
+};
+
+use crate::game::{interaction::PLACE_TICKS, Game};
+
+/// How far (in voxels) actors can reach by default.
+pub const DEFAULT_REACH: u32 = 6;
+
+/// Identifies whoever sends actions: the local player, or later, a remote peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActorId(pub u32);
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ActionKind {
+    /// Starts breaking a voxel.
+    Break = 0,
+    Place = 1,
+    /// Opens a container or machine.
+    Use = 2,
+}
+
+impl ActionKind {
+    pub const COUNT: usize = 3;
+    pub const ALL: [Self; Self::COUNT] = [Self::Break, Self::Place, Self::Use];
+
+    #[inline]
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Break),
+            1 => Some(Self::Place),
+            2 => Some(Self::Use),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Break => "break",
+            Self::Place => "place",
+            Self::Use => "use",
+        }
+    }
+}
+
+impl std::fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An action an actor asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Action {
+    pub actor: ActorId,
+    /// Numbers the actor's actions, increasing with each one.
+    pub seq: u64,
+    pub kind: ActionKind,
+    pub target: BlockPos,
+}
+
+impl Action {
+    #[inline]
+    #[must_use]
+    pub const fn new(actor: ActorId, seq: u64, kind: ActionKind, target: BlockPos) -> Self {
+        Self { actor, seq, kind, target }
+    }
+}
+
+/// Why an action was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum Rejection {
+    #[error("Action {seq} was already handled (the last accepted action is {last}).")]
+    Duplicate {
+        seq: u64,
+        last: u64,
+    },
+    #[error("Can't {kind} again until tick {ready_at}.")]
+    Cooldown {
+        kind: ActionKind,
+        ready_at: u64,
+    },
+    #[error(transparent)]
+    OutOfBounds(#[from] HeightError),
+    /// Distances are squared, in voxels.
+    #[error("The target is out of reach ({distance_sq} > {reach_sq}).")]
+    OutOfReach {
+        distance_sq: u64,
+        reach_sq: u64,
+    },
+    #[error("The chunk of {pos:?} isn't loaded.")]
+    NotLoaded {
+        pos: BlockPos,
+    },
+    /// `owner` is `None` for generated structures.
+    #[error("The target belongs to structure {structure:?}.")]
+    Protected {
+        structure: StructureId,
+        owner: Option<ActorId>,
+    },
+}
+
+// Layout: tag (u8), then
+//      0 (Duplicate)    : seq (u64), last (u64)
+//      1 (Cooldown)     : kind (u8), ready at (u64)
+//      2 (BelowBottom)  : y (i64), min y (i64)
+//      3 (AboveTop)     : y (i64), max y (i64)
+//      4 (OutOfReach)   : distance squared (u64), reach squared (u64)
+//      5 (NotLoaded)    : pos
+//      6 (Protected)    : structure (u64), owner (u8, 0 for none and 1 otherwise, followed by the actor (u32))
+//      7 (InvalidBounds): min y (i64), max y (i64)
+impl Encode for Rejection {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        match *self {
+            Rejection::Duplicate { seq, last } => Ok(encoder.write_u8(0)? + encoder.write_u64(seq)? + encoder.write_u64(last)?),
+            Rejection::Cooldown { kind, ready_at } => Ok(encoder.write_u8(1)? + encoder.write_u8(kind.to_u8())? + encoder.write_u64(ready_at)?),
+            Rejection::OutOfBounds(HeightError::BelowBottom { y, min_y }) => Ok(encoder.write_u8(2)? + encoder.write_i64(y)? + encoder.write_i64(min_y)?),
+            Rejection::OutOfBounds(HeightError::AboveTop { y, max_y }) => Ok(encoder.write_u8(3)? + encoder.write_i64(y)? + encoder.write_i64(max_y)?),
+            // Checks never fail with invalid bounds, but the error can still be built by hand.
+            Rejection::OutOfBounds(HeightError::InvalidBounds { min_y, max_y }) => Ok(encoder.write_u8(7)? + encoder.write_i64(min_y)? + encoder.write_i64(max_y)?),
+            Rejection::OutOfReach { distance_sq, reach_sq } => Ok(encoder.write_u8(4)? + encoder.write_u64(distance_sq)? + encoder.write_u64(reach_sq)?),
+            Rejection::NotLoaded { pos } => Ok(encoder.write_u8(5)? + pos.encode(encoder)?),
+            Rejection::Protected { structure, owner } => {
+                let written = encoder.write_u8(6)? + encoder.write_u64(structure.0)?;
+                Ok(written + match owner {
+                    Some(actor) => encoder.write_u8(1)? + encoder.write_u32(actor.0)?,
+                    None => encoder.write_u8(0)?,
+                })
+            }
+        }
+    }
+}
+
+impl Decode for Rejection {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(match decoder.read_u8()? {
+            0 => Rejection::Duplicate { seq: decoder.read_u64()?, last: decoder.read_u64()? },
+            1 => Rejection::Cooldown {
+                kind: ActionKind::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid action kind"))?,
+                ready_at: decoder.read_u64()?,
+            },
+            2 => Rejection::OutOfBounds(HeightError::BelowBottom { y: decoder.read_i64()?, min_y: decoder.read_i64()? }),
+            3 => Rejection::OutOfBounds(HeightError::AboveTop { y: decoder.read_i64()?, max_y: decoder.read_i64()? }),
+            4 => Rejection::OutOfReach { distance_sq: decoder.read_u64()?, reach_sq: decoder.read_u64()? },
+            5 => Rejection::NotLoaded { pos: BlockPos::decode(decoder)? },
+            6 => Rejection::Protected {
+                structure: StructureId(decoder.read_u64()?),
+                owner: match decoder.read_u8()? {
+                    0 => None,
+                    1 => Some(ActorId(decoder.read_u32()?)),
+                    _ => return Err(DecodeError::InvalidData("invalid structure owner")),
+                },
+            },
+            7 => Rejection::OutOfBounds(HeightError::InvalidBounds { min_y: decoder.read_i64()?, max_y: decoder.read_i64()? }),
+            _ => return Err(DecodeError::InvalidData("unknown rejection")),
+        })
+    }
+}
+
+/// What actions are allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// How far (in voxels, measured between voxel centers) an actor can reach.
+    pub reach: u32,
+    /// The ticks after an action before the same actor can do another of the same kind, by
+    /// [ActionKind]. `0` and `1` allow one every tick.
+    pub cooldowns: [u32; ActionKind::COUNT],
+    /// Whether unowned structures (the generated ones) are protected from everyone.
+    pub protect_unowned: bool,
+}
+
+impl Default for Limits {
+    /// Breaking can start once a tick, placing as often as placing takes ([PLACE_TICKS]), and
+    /// blocks can be used every other tick.
+    fn default() -> Self {
+        Self {
+            reach: DEFAULT_REACH,
+            cooldowns: [1, PLACE_TICKS, 2],
+            protect_unowned: true,
+        }
+    }
+}
+
+impl Limits {
+    #[inline]
+    #[must_use]
+    pub const fn cooldown(&self, kind: ActionKind) -> u32 {
+        self.cooldowns[kind as usize]
+    }
+}
+
+/// The state that actions are checked against. The game doesn't own actors or structures, so they
+/// come from the caller.
+#[derive(Clone, Copy)]
+pub struct ActionContext<'a> {
+    pub tick: u64,
+    pub game: &'a Game,
+    pub structures: &'a StructureIndex,
+}
+
+impl<'a> ActionContext<'a> {
+    #[inline]
+    #[must_use]
+    pub const fn new(tick: u64, game: &'a Game, structures: &'a StructureIndex) -> Self {
+        Self { tick, game, structures }
+    }
+}
+
+/// Checks actions, and keeps the cooldowns, sequence numbers and structure owners they're checked
+/// against. See the [module docs](self).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Validator {
+    limits: Limits,
+    /// The tick each actor can next do each kind of action.
+    ready_at: BTreeMap<(ActorId, ActionKind), u64>,
+    /// The sequence number of the last action accepted from each actor.
+    last_seq: BTreeMap<ActorId, u64>,
+    owners: BTreeMap<StructureId, ActorId>,
+}
+
+impl Validator {
+    #[inline]
+    #[must_use]
+    pub fn new(limits: Limits) -> Self {
+        Self { limits, ..Self::default() }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    #[inline]
+    pub const fn limits_mut(&mut self) -> &mut Limits {
+        &mut self.limits
+    }
+
+    /// The first tick `actor` can do an action of `kind`.
+    #[inline]
+    #[must_use]
+    pub fn ready_at(&self, actor: ActorId, kind: ActionKind) -> u64 {
+        self.ready_at.get(&(actor, kind)).copied().unwrap_or(0)
+    }
+
+    /// The sequence number of the last action accepted from `actor`.
+    #[inline]
+    #[must_use]
+    pub fn last_seq(&self, actor: ActorId) -> Option<u64> {
+        self.last_seq.get(&actor).copied()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn owner(&self, structure: StructureId) -> Option<ActorId> {
+        self.owners.get(&structure).copied()
+    }
+
+    /// Gives `structure` to `owner`, or makes it unowned. Returns the previous owner.
+    pub fn set_owner(&mut self, structure: StructureId, owner: Option<ActorId>) -> Option<ActorId> {
+        match owner {
+            Some(owner) => self.owners.insert(structure, owner),
+            None => self.owners.remove(&structure),
+        }
+    }
+
+    /// Forgets an actor that left. Their structures stay theirs.
+    pub fn remove_actor(&mut self, actor: ActorId) {
+        self.ready_at.retain(|&(other, _), _| other != actor);
+        self.last_seq.remove(&actor);
+    }
+
+    /// Checks `action` by an actor at `origin`, without recording anything. `origin` must come
+    /// from the simulation, never from whoever sent the action.
+    pub fn check(&self, ctx: &ActionContext, origin: BlockPos, action: &Action) -> Result<(), Rejection> {
+        if let Some(last) = self.last_seq(action.actor) && action.seq <= last {
+            return Err(Rejection::Duplicate { seq: action.seq, last });
+        }
+        let ready_at = self.ready_at(action.actor, action.kind);
+        if ctx.tick < ready_at {
+            return Err(Rejection::Cooldown { kind: action.kind, ready_at });
+        }
+        let target = action.target;
+        ctx.game.world.bounds().check_block(target)?;
+        let distance_sq = [(origin.x, target.x), (origin.y, target.y), (origin.z, target.z)].into_iter()
+            .map(|(from, to)| from.abs_diff(to).saturating_mul(from.abs_diff(to)))
+            .fold(0u64, u64::saturating_add);
+        let reach_sq = u64::from(self.limits.reach).pow(2);
+        if distance_sq > reach_sq {
+            return Err(Rejection::OutOfReach { distance_sq, reach_sq });
+        }
+        if !target.chunk().is_some_and(|chunk| ctx.game.world.is_loaded(chunk)) {
+            return Err(Rejection::NotLoaded { pos: target });
+        }
+        if let Some(structure) = ctx.structures.structure_at(target) {
+            let owner = self.owner(structure.id);
+            let allowed = match owner {
+                Some(owner) => owner == action.actor,
+                None => !self.limits.protect_unowned,
+            };
+            if !allowed {
+                return Err(Rejection::Protected { structure: structure.id, owner });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks `action`, and if it's accepted, records its sequence number and starts its cooldown.
+    /// The caller then applies it to the game. Rejected actions change nothing.
+    pub fn submit(&mut self, ctx: &ActionContext, origin: BlockPos, action: &Action) -> Result<(), Rejection> {
+        self.check(ctx, origin, action)?;
+        self.last_seq.insert(action.actor, action.seq);
+        let cooldown = self.limits.cooldown(action.kind);
+        if cooldown > 0 {
+            self.ready_at.insert((action.actor, action.kind), ctx.tick + u64::from(cooldown));
+        }
+        Ok(())
+    }
+}
+
+// Layout: reach (u32), cooldowns (u32 each, in ActionKind::ALL order), protect unowned (bool),
+//      ready at count (u32) and entries (actor (u32), kind (u8), tick (u64)),
+//      last seq count (u32) and entries (actor (u32), seq (u64)),
+//      owner count (u32) and entries (structure (u64), actor (u32)), each list sorted.
+impl Encode for Validator {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.limits.reach)?;
+        for cooldown in self.limits.cooldowns {
+            written += encoder.write_u32(cooldown)?;
+        }
+        written += encoder.write_bool(self.limits.protect_unowned)?;
+        written += encoder.write_u32(self.ready_at.len() as u32)?;
+        for (&(actor, kind), &tick) in &self.ready_at {
+            written += encoder.write_u32(actor.0)? + encoder.write_u8(kind.to_u8())? + encoder.write_u64(tick)?;
+        }
+        written += encoder.write_u32(self.last_seq.len() as u32)?;
+        for (&actor, &seq) in &self.last_seq {
+            written += encoder.write_u32(actor.0)? + encoder.write_u64(seq)?;
+        }
+        written += encoder.write_u32(self.owners.len() as u32)?;
+        for (&structure, &actor) in &self.owners {
+            written += encoder.write_u64(structure.0)? + encoder.write_u32(actor.0)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for Validator {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let reach = decoder.read_u32()?;
+        let mut cooldowns = [0; ActionKind::COUNT];
+        for cooldown in &mut cooldowns {
+            *cooldown = decoder.read_u32()?;
+        }
+        let mut validator = Self::new(Limits { reach, cooldowns, protect_unowned: decoder.read_bool()? });
+        for _ in 0..decoder.read_u32()? {
+            let actor = ActorId(decoder.read_u32()?);
+            let kind = ActionKind::from_u8(decoder.read_u8()?).ok_or(DecodeError::InvalidData("invalid action kind"))?;
+            if validator.ready_at.last_key_value().is_some_and(|(&last, _)| last >= (actor, kind)) {
+                return Err(DecodeError::InvalidData("unsorted cooldowns"));
+            }
+            validator.ready_at.insert((actor, kind), decoder.read_u64()?);
+        }
+        for _ in 0..decoder.read_u32()? {
+            let actor = ActorId(decoder.read_u32()?);
+            if validator.last_seq.last_key_value().is_some_and(|(&last, _)| last >= actor) {
+                return Err(DecodeError::InvalidData("unsorted sequence numbers"));
+            }
+            validator.last_seq.insert(actor, decoder.read_u64()?);
+        }
+        for _ in 0..decoder.read_u32()? {
+            let structure = StructureId(decoder.read_u64()?);
+            if validator.owners.last_key_value().is_some_and(|(&last, _)| last >= structure) {
+                return Err(DecodeError::InvalidData("unsorted structure owners"));
+            }
+            validator.owners.insert(structure, ActorId(decoder.read_u32()?));
+        }
+        Ok(validator)
+    }
+}
+
+// Cooldowns decide which actions are accepted, so they're part of the deterministic state.
+impl DeterministicHash for Validator {
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        hash_encoded(self, hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfhash::deterministic_hash_u128;
+    use mfworld::{
+        bounds::HeightBounds,
+        chunk::ChunkPos,
+        structure::{StructureBounds, StructureRef},
+    };
+
+    use super::*;
+    use crate::game::{environment::Environment, mode::GameMode, player::Player, rules::GameRules, world::World};
+
+    fn game() -> Game {
+        let mut world = World::with_bounds(HeightBounds::from_chunks(-1, 0).unwrap());
+        for x in -1..=1 {
+            world.insert_chunk(ChunkPos::new(x, 0, 0), true).unwrap();
+        }
+        Game { world, player: Player::default(), mode: GameMode::Survival, rules: GameRules::new(), environment: Environment::new(0) }
+    }
+
+    #[test]
+    fn validator_test() {
+        let game = game();
+        let mut structures = StructureIndex::new();
+        let hut = StructureRef::new(StructureId(7), StructureBounds::new((4, 0, 4), (8, 4, 8)));
+        let ruin = StructureRef::new(StructureId(9), StructureBounds::new((-4, 0, 0), (0, 4, 4)));
+        for chunk in [-1, 0] {
+            let mut chunk_structures = mfworld::structure::ChunkStructures::new();
+            for reference in [hut, ruin] {
+                if reference.bounds.intersects(StructureBounds::of_chunk(ChunkPos::new(chunk, 0, 0))) {
+                    chunk_structures.insert(reference);
+                }
+            }
+            structures.load_chunk(ChunkPos::new(chunk, 0, 0), chunk_structures);
+        }
+        let (alice, bob) = (ActorId(1), ActorId(2));
+        let mut validator = Validator::default();
+        validator.set_owner(hut.id, Some(alice));
+        let origin = BlockPos::new(2, 2, 2);
+        let ctx = ActionContext::new(10, &game, &structures);
+
+        let place = Action::new(alice, 1, ActionKind::Place, BlockPos::new(5, 1, 5));
+        assert_eq!(validator.submit(&ctx, origin, &place), Ok(()));
+        // The same action again is a duplicate, however many times it's sent, and changes nothing.
+        let before = validator.clone();
+        for _ in 0..3 {
+            assert_eq!(validator.submit(&ctx, origin, &place), Err(Rejection::Duplicate { seq: 1, last: 1 }));
+        }
+        assert_eq!(validator, before);
+
+        let ready_at = 10 + u64::from(PLACE_TICKS);
+        let again = Action { seq: 2, target: BlockPos::new(3, 1, 3), ..place };
+        assert_eq!(validator.check(&ctx, origin, &again), Err(Rejection::Cooldown { kind: ActionKind::Place, ready_at }));
+        assert_eq!(validator.check(&ActionContext { tick: ready_at, ..ctx }, origin, &again), Ok(()));
+        // Cooldowns are per kind and per actor.
+        assert_eq!(validator.check(&ctx, origin, &Action { kind: ActionKind::Use, ..again }), Ok(()));
+
+        let rejected = |action: Action| validator.check(&ctx, origin, &action).unwrap_err();
+        let use_at = |actor, x, y, z| Action::new(actor, 100, ActionKind::Use, BlockPos::new(x, y, z));
+        assert_eq!(rejected(use_at(bob, 2, 40, 2)), Rejection::OutOfBounds(HeightError::AboveTop { y: 40, max_y: 15 }));
+        assert_eq!(rejected(use_at(bob, 2, 2, 9)), Rejection::OutOfReach { distance_sq: 49, reach_sq: 36 });
+        assert_eq!(rejected(use_at(bob, 2, 2, -3)), Rejection::NotLoaded { pos: BlockPos::new(2, 2, -3) });
+        assert_eq!(rejected(use_at(bob, 5, 1, 5)), Rejection::Protected { structure: hut.id, owner: Some(alice) });
+        assert_eq!(rejected(use_at(alice, -2, 1, 2)), Rejection::Protected { structure: ruin.id, owner: None });
+        validator.limits_mut().protect_unowned = false;
+        assert_eq!(validator.check(&ctx, origin, &use_at(alice, -2, 1, 2)), Ok(()));
+
+        let mut bytes = Vec::new();
+        let Ok(_) = validator.encode(&mut bytes);
+        let decoded = Validator::decode(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, validator);
+        assert_eq!(deterministic_hash_u128(&decoded), deterministic_hash_u128(&validator));
+        validator.remove_actor(alice);
+        assert_eq!((validator.last_seq(alice), validator.ready_at(alice, ActionKind::Place)), (None, 0));
+        assert_eq!(validator.owner(hut.id), Some(alice));
+
+        for rejection in [
+            Rejection::Duplicate { seq: 3, last: 4 },
+            Rejection::Cooldown { kind: ActionKind::Break, ready_at: 12 },
+            Rejection::OutOfBounds(HeightError::BelowBottom { y: -17, min_y: -16 }),
+            Rejection::OutOfBounds(HeightError::AboveTop { y: 16, max_y: 15 }),
+            Rejection::OutOfBounds(HeightError::InvalidBounds { min_y: 16, max_y: 15 }),
+            Rejection::OutOfReach { distance_sq: 50, reach_sq: 36 },
+            Rejection::NotLoaded { pos: BlockPos::new(1, 2, 3) },
+            Rejection::Protected { structure: StructureId(5), owner: Some(bob) },
+            Rejection::Protected { structure: StructureId(5), owner: None },
+        ] {
+            let mut bytes = Vec::new();
+            let Ok(_) = rejection.encode(&mut bytes);
+            assert_eq!(Rejection::decode(&mut bytes.as_slice()).unwrap(), rejection);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use mfworld::{chunk::BlockPos, history::VoxelWorld};
+
+use crate::game::{
+    crafting::lockout::Lockout,
+    events::EventBus,
+    inventory::{ContainerId, Inventories, SlotRef, Transaction},
+};
+
+/// The functions a program can reach with [Op::Call](super::op::Op::Call).
+///
+/// Only Manufactory can call host functions: [Host::call] takes a [Lockout], which can't be created
+/// outside of the crate, so programs can only reach the game through the VM.
+pub trait Host {
+    /// The number of arguments `function` takes, or `None` if there is no such function.
+    fn arity(&self, function: u16) -> Option<usize>;
+
+    /// Calls `function` with `args` (in the order they were pushed), pushing its results onto `results`.
+    fn call(&mut self, lockout: Lockout, function: u16, args: &[i64], results: &mut Vec<i64>);
+}
+
+/// Host function ids of [GameHost].
+pub mod functions {
+    /// `(container, index) -> (item, count)`. Pushes `-1` for the item of an empty slot, and
+    /// `-1` for both if the slot doesn't exist.
+    pub const SLOT: u16 = 0;
+    /// `(container, index, to) -> moved`. Quick moves a stack into another container. Pushes
+    /// `1` if anything moved, otherwise `0`.
+    pub const QUICK_MOVE: u16 = 1;
+    /// `(x, y, z) -> voxel`. Pushes the voxel's id, or `-1` if its chunk isn't loaded.
+    pub const VOXEL: u16 = 2;
+}
+
+/// Bridges programs to inventories and the world.
+pub struct GameHost<'a, W: VoxelWorld> {
+    pub inventories: &'a mut Inventories,
+    pub events: &'a mut EventBus,
+    pub world: &'a W,
+}
+
+#[inline]
+fn slot_ref(container: i64, index: i64) -> Option<SlotRef> {
+    Some(SlotRef::new(ContainerId(u32::try_from(container).ok()?), u16::try_from(index).ok()?))
+}
+
+impl<W: VoxelWorld> Host for GameHost<'_, W> {
+    fn arity(&self, function: u16) -> Option<usize> {
+        match function {
+            functions::SLOT => Some(2),
+            functions::QUICK_MOVE => Some(3),
+            functions::VOXEL => Some(3),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, _: Lockout, function: u16, args: &[i64], results: &mut Vec<i64>) {
+        match function {
+            functions::SLOT => {
+                let stack = slot_ref(args[0], args[1]).and_then(|slot| self.inventories.get(slot).ok());
+                match stack {
+                    Some(Some(stack)) => results.extend([stack.item.get() as i64, stack.count as i64]),
+                    Some(None) => results.extend([-1, 0]),
+                    None => results.extend([-1, -1]),
+                }
+            }
+            functions::QUICK_MOVE => {
+                let moved = slot_ref(args[0], args[1])
+                    .zip(u32::try_from(args[2]).ok())
+                    .is_some_and(|(from, to)| {
+                        self.inventories.apply(Transaction::QuickMove { from, to: ContainerId(to) }, self.events).is_ok()
+                    });
+                results.push(moved as i64);
+            }
+            functions::VOXEL => {
+                let voxel = self.world.voxel(BlockPos::new(args[0], args[1], args[2]));
+                results.push(voxel.map_or(-1, |state| state.id.get() as i64));
+            }
+            _ => unreachable!("unknown host function {function}"),
+        }
+    }
+}
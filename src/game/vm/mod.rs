@@ -0,0 +1,477 @@
+//! A sandboxed stack VM for user-programmable machines.
+//!
+//! Each [Vm] runs a [Program] for at most a fixed instruction budget per tick, picking up where it
+//! left off on the next tick. Programs can't do anything but compute, except through host functions
+//! ([Host]), which are the only bridge to inventories and the world. A program that misbehaves
+//! (stack overflow, division by zero, a bad jump) faults and stops, without affecting the game.
+//!
+//! The [Scheduler] runs its VMs in order of [VmId] every tick, and everything a VM does depends only
+//! on its state and what its host functions return, so running the same programs from the same
+//! state always produces the same results. VM state is serialized with the save.
+
+pub mod host;
+pub mod op;
+
+use std::collections::BTreeMap;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+use crate::game::crafting::lockout::lock;
+
+pub use host::{GameHost, Host};
+pub use op::{Op, Program};
+
+/// The most values the stack can hold.
+pub const MAX_STACK: usize = 256;
+/// The number of registers.
+pub const REGISTERS: usize = 16;
+/// The instruction budget used by a host function call.
+pub const HOST_CALL_COST: u32 = 10;
+/// The instruction budget of each VM per tick, unless configured otherwise.
+pub const DEFAULT_BUDGET: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error, Encode, Decode)]
+pub enum Fault {
+    #[error("Stack overflow")]
+    #[cereal(tag = 0)]
+    StackOverflow,
+    #[error("Stack underflow")]
+    #[cereal(tag = 1)]
+    StackUnderflow,
+    #[error("Division by zero")]
+    #[cereal(tag = 2)]
+    DivisionByZero,
+    #[error("Instruction index {0} is out of bounds")]
+    #[cereal(tag = 3)]
+    OutOfBounds(u32),
+    #[error("Invalid register: {0}")]
+    #[cereal(tag = 4)]
+    InvalidRegister(u8),
+    #[error("Unknown host function: {0}")]
+    #[cereal(tag = 5)]
+    UnknownFunction(u16),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum Status {
+    #[default]
+    #[cereal(tag = 0)]
+    Running,
+    #[cereal(tag = 1)]
+    Halted,
+    #[cereal(tag = 2)]
+    Faulted(Fault),
+}
+
+/// A program and its execution state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vm {
+    program: Program,
+    pc: u32,
+    stack: Vec<i64>,
+    registers: [i64; REGISTERS],
+    status: Status,
+}
+
+impl Vm {
+    #[must_use]
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            pc: 0,
+            stack: Vec::new(),
+            registers: [0; REGISTERS],
+            status: Status::Running,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn program(&self) -> &Program {
+        &self.program
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn status(&self) -> Status {
+        self.status
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn stack(&self) -> &[i64] {
+        &self.stack
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn registers(&self) -> &[i64; REGISTERS] {
+        &self.registers
+    }
+
+    /// Starts the program over, clearing its stack and registers.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.stack.clear();
+        self.registers = [0; REGISTERS];
+        self.status = Status::Running;
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Result<i64, Fault> {
+        self.stack.pop().ok_or(Fault::StackUnderflow)
+    }
+
+    #[inline]
+    fn pop2(&mut self) -> Result<(i64, i64), Fault> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        Ok((a, b))
+    }
+
+    #[inline]
+    fn push(&mut self, value: i64) -> Result<(), Fault> {
+        if self.stack.len() == MAX_STACK {
+            return Err(Fault::StackOverflow);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    fn register(index: u8) -> Result<usize, Fault> {
+        if (index as usize) < REGISTERS { Ok(index as usize) } else { Err(Fault::InvalidRegister(index)) }
+    }
+
+    /// Executes `op`, the instruction at the program counter. Returns whether the program stopped for this tick.
+    fn step<H: Host>(&mut self, op: Op, host: &mut H) -> Result<bool, Fault> {
+        let mut next = self.pc + 1;
+        match op {
+            Op::Push(value) => self.push(value)?,
+            Op::Pop => {
+                self.pop()?;
+            }
+            Op::Dup => {
+                let value = *self.stack.last().ok_or(Fault::StackUnderflow)?;
+                self.push(value)?;
+            }
+            Op::Swap => {
+                let (a, b) = self.pop2()?;
+                self.stack.extend([b, a]);
+            }
+            Op::Over => {
+                let len = self.stack.len();
+                let value = *self.stack.get(len.wrapping_sub(2)).ok_or(Fault::StackUnderflow)?;
+                self.push(value)?;
+            }
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem | Op::Eq | Op::Lt | Op::And | Op::Or => {
+                let (a, b) = self.pop2()?;
+                if matches!(op, Op::Div | Op::Rem) && b == 0 {
+                    return Err(Fault::DivisionByZero);
+                }
+                let result = match op {
+                    Op::Add => a.wrapping_add(b),
+                    Op::Sub => a.wrapping_sub(b),
+                    Op::Mul => a.wrapping_mul(b),
+                    Op::Div => a.wrapping_div(b),
+                    Op::Rem => a.wrapping_rem(b),
+                    Op::Eq => (a == b) as i64,
+                    Op::Lt => (a < b) as i64,
+                    Op::And => a & b,
+                    Op::Or => a | b,
+                    _ => unreachable!(),
+                };
+                self.stack.push(result);
+            }
+            Op::Neg => {
+                let value = self.pop()?;
+                self.stack.push(value.wrapping_neg());
+            }
+            Op::Not => {
+                let value = self.pop()?;
+                self.stack.push((value == 0) as i64);
+            }
+            Op::Load(register) => self.push(self.registers[Self::register(register)?])?,
+            Op::Store(register) => {
+                let index = Self::register(register)?;
+                self.registers[index] = self.pop()?;
+            }
+            Op::Jump(target) => next = target,
+            Op::JumpIf(target) => {
+                if self.pop()? != 0 {
+                    next = target;
+                }
+            }
+            Op::Call(function) => {
+                let arity = host.arity(function).ok_or(Fault::UnknownFunction(function))?;
+                let split = self.stack.len().checked_sub(arity).ok_or(Fault::StackUnderflow)?;
+                let args = self.stack.split_off(split);
+                host.call(lock(), function, &args, &mut self.stack);
+                if self.stack.len() > MAX_STACK {
+                    return Err(Fault::StackOverflow);
+                }
+            }
+            Op::Yield => {
+                self.pc = next;
+                return Ok(true);
+            }
+            Op::Halt => {
+                self.status = Status::Halted;
+                return Ok(true);
+            }
+        }
+        self.pc = next;
+        Ok(false)
+    }
+
+    /// Runs until the program yields, halts, or faults, or until the next instruction would exceed
+    /// `budget`. Returns the budget used.
+    pub fn run<H: Host>(&mut self, budget: u32, host: &mut H) -> u32 {
+        let mut used = 0;
+        while self.status == Status::Running {
+            let Some(&op) = self.program.ops.get(self.pc as usize) else {
+                self.status = Status::Faulted(Fault::OutOfBounds(self.pc));
+                break;
+            };
+            if used + op.cost() > budget {
+                break;
+            }
+            used += op.cost();
+            match self.step(op, host) {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(fault) => self.status = Status::Faulted(fault),
+            }
+        }
+        used
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VmId(pub u32);
+
+/// Runs a set of VMs every tick, each with its own instruction budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheduler {
+    budget: u32,
+    vms: BTreeMap<VmId, Vm>,
+    next_id: u32,
+}
+
+impl Default for Scheduler {
+    #[inline]
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler giving each VM `budget` per tick. The budget must cover a host function call.
+    #[must_use]
+    pub fn new(budget: u32) -> Self {
+        assert!(budget >= HOST_CALL_COST, "The budget must be at least {HOST_CALL_COST}.");
+        Self {
+            budget,
+            vms: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn budget(&self) -> u32 {
+        self.budget
+    }
+
+    pub fn spawn(&mut self, program: Program) -> VmId {
+        let id = VmId(self.next_id);
+        self.next_id += 1;
+        self.vms.insert(id, Vm::new(program));
+        id
+    }
+
+    #[inline]
+    pub fn remove(&mut self, id: VmId) -> Option<Vm> {
+        self.vms.remove(&id)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn get(&self, id: VmId) -> Option<&Vm> {
+        self.vms.get(&id)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, id: VmId) -> Option<&mut Vm> {
+        self.vms.get_mut(&id)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (VmId, &Vm)> {
+        self.vms.iter().map(|(&id, vm)| (id, vm))
+    }
+
+    /// Runs every running VM for one tick, in order of id. Returns the total budget used.
+    pub fn tick<H: Host>(&mut self, host: &mut H) -> u64 {
+        self.vms.values_mut()
+            .map(|vm| vm.run(self.budget, host) as u64)
+            .sum()
+    }
+}
+
+// Layout: program, pc (u32), status, stack length (u16), stack (i64s), registers (REGISTERS i64s).
+impl Encode for Vm {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = self.program.encode(encoder)?
+            + encoder.write_u32(self.pc)?
+            + self.status.encode(encoder)?
+            + encoder.write_u16(self.stack.len() as u16)?;
+        for &value in self.stack.iter().chain(&self.registers) {
+            written += encoder.write_i64(value)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for Vm {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let mut vm = Self::new(Program::decode(decoder)?);
+        vm.pc = decoder.read_u32()?;
+        vm.status = Status::decode(decoder)?;
+        let len = decoder.read_u16()? as usize;
+        if len > MAX_STACK {
+            return Err(DecodeError::InvalidData("VM stack is too large"));
+        }
+        for _ in 0..len {
+            vm.stack.push(decoder.read_i64()?);
+        }
+        for register in &mut vm.registers {
+            *register = decoder.read_i64()?;
+        }
+        Ok(vm)
+    }
+}
+
+// Layout: budget (u32), next id (u32), VM count (u32), then each VM's id (u32) and VM.
+impl Encode for Scheduler {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.budget)?
+            + encoder.write_u32(self.next_id)?
+            + encoder.write_u32(self.vms.len() as u32)?;
+        for (id, vm) in &self.vms {
+            written += encoder.write_u32(id.0)? + vm.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for Scheduler {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let budget = decoder.read_u32()?;
+        if budget < HOST_CALL_COST {
+            return Err(DecodeError::InvalidData("VM budget is too small"));
+        }
+        let mut scheduler = Self::new(budget);
+        scheduler.next_id = decoder.read_u32()?;
+        for _ in 0..decoder.read_u32()? {
+            let id = VmId(decoder.read_u32()?);
+            if id.0 >= scheduler.next_id {
+                return Err(DecodeError::InvalidData("VM id was never assigned"));
+            }
+            scheduler.vms.insert(id, Vm::decode(decoder)?);
+        }
+        Ok(scheduler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfworld::{chunk::BlockPos, history::{VoxelState, VoxelWorld}, voxel::id::VoxelId};
+
+    use super::*;
+    use crate::game::{
+        crafting::item::ItemId,
+        events::EventBus,
+        inventory::{Container, ContainerId, Inventories, ItemStack, SlotRef},
+    };
+    use host::functions;
+
+    struct Flat;
+
+    impl VoxelWorld for Flat {
+        fn voxel(&self, position: BlockPos) -> Option<VoxelState> {
+            Some(if position.y < 0 { VoxelState::new(VoxelId::new(1), Default::default()) } else { VoxelState::AIR })
+        }
+
+        fn set_voxel(&mut self, _: BlockPos, _: VoxelState) {}
+    }
+
+    /// Counts from 0 to `limit` in register 0.
+    fn counter(limit: i64) -> Program {
+        Program::new(vec![
+            Op::Load(0),
+            Op::Push(1),
+            Op::Add,
+            Op::Dup,
+            Op::Store(0),
+            Op::Push(limit),
+            Op::Lt,
+            Op::JumpIf(0),
+            Op::Halt,
+        ])
+    }
+
+    #[test]
+    fn budget_test() {
+        let mut inventories = Inventories::new();
+        let mut events = EventBus::new();
+        let mut host = GameHost { inventories: &mut inventories, events: &mut events, world: &Flat };
+        let mut scheduler = Scheduler::new(100);
+        let id = scheduler.spawn(counter(50));
+        let faulty = scheduler.spawn(Program::new(vec![Op::Push(1), Op::Push(0), Op::Div]));
+        assert_eq!(scheduler.tick(&mut host), 100 + 3);
+        assert_eq!(scheduler.get(faulty).unwrap().status(), Status::Faulted(Fault::DivisionByZero));
+        let mut ticks = 1;
+        while scheduler.get(id).unwrap().status() == Status::Running {
+            // Saving and loading between ticks doesn't change anything.
+            let mut bytes = Vec::new();
+            scheduler.encode(&mut bytes).unwrap();
+            scheduler = Scheduler::decode(&mut bytes.as_slice()).unwrap();
+            scheduler.tick(&mut host);
+            ticks += 1;
+        }
+        // 8 instructions per count, plus the halt.
+        assert_eq!(ticks, (50 * 8 + 1usize).div_ceil(100));
+        assert_eq!(scheduler.get(id).unwrap().registers()[0], 50);
+    }
+
+    #[test]
+    fn host_test() {
+        let mut inventories = Inventories::new();
+        let mut events = EventBus::new();
+        inventories.insert(ContainerId(1), Container::new(2));
+        inventories.insert(ContainerId(2), Container::new(2));
+        let stone = ItemStack::new(ItemId::new(7), 12);
+        inventories.set(SlotRef::new(ContainerId(1), 1), Some(stone), &mut events).unwrap();
+        let mut host = GameHost { inventories: &mut inventories, events: &mut events, world: &Flat };
+        let mut vm = Vm::new(Program::new(vec![
+            Op::Push(1), Op::Push(1), Op::Call(functions::SLOT),
+            Op::Push(1), Op::Push(1), Op::Push(2), Op::Call(functions::QUICK_MOVE),
+            Op::Push(0), Op::Push(-3), Op::Push(0), Op::Call(functions::VOXEL),
+            Op::Call(99),
+        ]));
+        vm.run(1000, &mut host);
+        assert_eq!(vm.stack(), &[7, 12, 1, 1]);
+        assert_eq!(vm.status(), Status::Faulted(Fault::UnknownFunction(99)));
+        assert_eq!(inventories.get(SlotRef::new(ContainerId(2), 0)), Ok(Some(stone)));
+    }
+}
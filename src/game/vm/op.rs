@@ -0,0 +1,167 @@
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+
+/// A single instruction. Values are `i64`, and arithmetic wraps on overflow.
+/// Comparisons and [Op::Not] produce `1` for true and `0` for false.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Push(i64),
+    Pop,
+    /// Duplicates the top value.
+    Dup,
+    /// Swaps the top two values.
+    Swap,
+    /// Copies the second value to the top.
+    Over,
+    Add,
+    Sub,
+    Mul,
+    /// Divides the second value by the top value, rounding toward zero.
+    Div,
+    Rem,
+    Neg,
+    /// Pushes whether the top two values are equal.
+    Eq,
+    /// Pushes whether the second value is less than the top value.
+    Lt,
+    /// Pushes `1` if the top value is zero, otherwise `0`.
+    Not,
+    /// Bitwise and.
+    And,
+    /// Bitwise or.
+    Or,
+    /// Pushes the value of a register.
+    Load(u8),
+    /// Pops a value into a register.
+    Store(u8),
+    /// Continues at an instruction index.
+    Jump(u32),
+    /// Pops a value and jumps if it isn't zero.
+    JumpIf(u32),
+    /// Calls a host function, see [Host](super::host::Host).
+    Call(u16),
+    /// Ends the program's turn for this tick. It continues from the next instruction next tick.
+    Yield,
+    Halt,
+}
+
+impl Op {
+    /// The instruction budget used by executing the instruction.
+    #[inline]
+    #[must_use]
+    pub const fn cost(self) -> u32 {
+        match self {
+            Op::Call(_) => super::HOST_CALL_COST,
+            _ => 1,
+        }
+    }
+}
+
+/// The instructions of a program. Execution starts at the first instruction.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Program {
+    pub ops: Vec<Op>,
+}
+
+impl Program {
+    #[inline]
+    #[must_use]
+    pub const fn new(ops: Vec<Op>) -> Self {
+        Self { ops }
+    }
+}
+
+// Layout: tag (u8), followed by the operand, if any.
+impl Encode for Op {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let tag = match self {
+            Op::Push(_) => 0,
+            Op::Pop => 1,
+            Op::Dup => 2,
+            Op::Swap => 3,
+            Op::Over => 4,
+            Op::Add => 5,
+            Op::Sub => 6,
+            Op::Mul => 7,
+            Op::Div => 8,
+            Op::Rem => 9,
+            Op::Neg => 10,
+            Op::Eq => 11,
+            Op::Lt => 12,
+            Op::Not => 13,
+            Op::And => 14,
+            Op::Or => 15,
+            Op::Load(_) => 16,
+            Op::Store(_) => 17,
+            Op::Jump(_) => 18,
+            Op::JumpIf(_) => 19,
+            Op::Call(_) => 20,
+            Op::Yield => 21,
+            Op::Halt => 22,
+        };
+        let written = encoder.write_u8(tag)?;
+        let operand = match *self {
+            Op::Push(value) => encoder.write_i64(value)?,
+            Op::Load(register) | Op::Store(register) => encoder.write_u8(register)?,
+            Op::Jump(target) | Op::JumpIf(target) => encoder.write_u32(target)?,
+            Op::Call(function) => encoder.write_u16(function)?,
+            _ => 0,
+        };
+        Ok(written + operand)
+    }
+}
+
+impl Decode for Op {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        Ok(match decoder.read_u8()? {
+            0 => Op::Push(decoder.read_i64()?),
+            1 => Op::Pop,
+            2 => Op::Dup,
+            3 => Op::Swap,
+            4 => Op::Over,
+            5 => Op::Add,
+            6 => Op::Sub,
+            7 => Op::Mul,
+            8 => Op::Div,
+            9 => Op::Rem,
+            10 => Op::Neg,
+            11 => Op::Eq,
+            12 => Op::Lt,
+            13 => Op::Not,
+            14 => Op::And,
+            15 => Op::Or,
+            16 => Op::Load(decoder.read_u8()?),
+            17 => Op::Store(decoder.read_u8()?),
+            18 => Op::Jump(decoder.read_u32()?),
+            19 => Op::JumpIf(decoder.read_u32()?),
+            20 => Op::Call(decoder.read_u16()?),
+            21 => Op::Yield,
+            22 => Op::Halt,
+            _ => return Err(DecodeError::InvalidData("invalid instruction")),
+        })
+    }
+}
+
+// Layout: instruction count (u32), instructions.
+impl Encode for Program {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let mut written = encoder.write_u32(self.ops.len() as u32)?;
+        for op in &self.ops {
+            written += op.encode(encoder)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for Program {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let len = decoder.read_u32()?;
+        let mut ops = Vec::new();
+        for _ in 0..len {
+            ops.push(Op::decode(decoder)?);
+        }
+        Ok(Self { ops })
+    }
+}
@@ -0,0 +1,149 @@
+//! Generating chunks from the world's [GeneratorConfig](mfprocgen::GeneratorConfig).
+//!
+//! A chunk depends only on the world seed, the generator config and its position, so chunks can be
+//! generated on any number of threads. [generate_parallel] streams them from worker threads (see
+//! [mfprocgen::stream]) and hands each back as soon as it and the chunks before it are done, in the
+//! order the chunks were asked for, so what's done with them (such as saving) overlaps with
+//! generation and happens in the same order however many threads there are.
+
+use mfgeometry::Orientation;
+use mfprocgen::{
+    stage::GenContext,
+    stream::generate_stream,
+    structure::{self, ClaimRegistry, StructureBox},
+    surface::voxels,
+};
+use mfworld::{
+    chunk::{stored::StoredChunk, voxel_index, ChunkPos, CHUNK_SHIFT, CHUNK_SIZE},
+    history::VoxelState,
+    biome::{BiomeId, ColumnBiomes},
+    structure::{ChunkStructures, StructureBounds, StructureId, StructureRef},
+    voxel::id::VoxelId,
+};
+
+/// Generates the voxels of `chunk`.
+pub fn generate_chunk(ctx: &GenContext, chunk: ChunkPos) -> StoredChunk {
+    let (min_x, min_y, min_z) = (chunk.x << CHUNK_SHIFT, chunk.y << CHUNK_SHIFT, chunk.z << CHUNK_SHIFT);
+    // Veins are only looked for at depths where some vein can be.
+    let vein_depth = ctx.config.veins.veins.iter().map(|vein| vein.max_depth as i32).max();
+    let mut stored = StoredChunk::new();
+    let mut column_voxels = [0u32; CHUNK_SIZE as usize];
+    for z in min_z..min_z + CHUNK_SIZE {
+        for x in min_x..min_x + CHUNK_SIZE {
+            let column = ctx.surface_column(x, z);
+            column.fill(min_y, &mut column_voxels);
+            for (y, &voxel) in (min_y..).zip(&column_voxels) {
+                let mut voxel = voxel;
+                if voxel == voxels::AIR {
+                    continue;
+                }
+                if ctx.config.ores && vein_depth.is_some_and(|depth| column.height - 1 - y <= depth)
+                    && let Some(vein) = ctx.vein_at(x, y, z) {
+                    voxel = vein.voxel;
+                }
+                stored.set(voxel_index(x, y, z), VoxelState::new(VoxelId::new(voxel), Orientation::UNORIENTED));
+            }
+        }
+    }
+    stored
+}
+
+/// The biomes of the chunk column `(x, z)`, as ids of the biomes of the generator's surface rules
+/// in order. Every column is biome 0 if they have none.
+pub fn column_biomes(ctx: &GenContext, x: i32, z: i32) -> ColumnBiomes {
+    let (min_x, min_z) = (x << CHUNK_SHIFT, z << CHUNK_SHIFT);
+    ColumnBiomes::from_fn(|x, z| {
+        let index = ctx.biome_index_at(min_x + x, min_z + z).unwrap_or(0);
+        BiomeId(index as u16)
+    })
+}
+
+/// The id of a generated structure, which every chunk it spans derives the same way.
+#[inline]
+pub fn structure_id(ctx: &GenContext, placer: &'static str, bounds: StructureBox) -> StructureId {
+    StructureId(ctx.stage_seed(structure::STAGE).hash_u64(("id", placer, bounds.min, bounds.max)))
+}
+
+/// The structures claimed in `chunk`, to be stored with it in a
+/// [StructureIndex](mfworld::structure::StructureIndex).
+pub fn chunk_structures(ctx: &GenContext, claims: &mut ClaimRegistry, chunk: ChunkPos) -> ChunkStructures {
+    let chunk_bounds = StructureBounds::of_chunk(chunk);
+    let mut structures = ChunkStructures::new();
+    // Regions are a whole number of chunks wide, so a chunk is always inside one.
+    for claim in claims.claims_in(ctx, StructureBox { min: chunk_bounds.min, max: chunk_bounds.max }) {
+        let id = structure_id(ctx, claims.placer_name(claim.placer), claim.bounds);
+        structures.insert(StructureRef::new(id, StructureBounds::new(claim.bounds.min, claim.bounds.max)));
+    }
+    structures
+}
+
+/// Generates `chunks` on up to `threads` threads, passing each to `sink` in the order of `chunks`.
+pub fn generate_parallel<F: FnMut(ChunkPos, StoredChunk)>(ctx: &GenContext, chunks: &[ChunkPos], threads: usize, mut sink: F) {
+    let coords = chunks.iter().map(|chunk| (chunk.x, chunk.y, chunk.z)).collect::<Vec<_>>();
+    generate_stream(
+        ctx,
+        &coords,
+        threads,
+        |ctx, (x, y, z)| generate_chunk(ctx, ChunkPos::new(x, y, z)),
+        |(x, y, z), stored| sink(ChunkPos::new(x, y, z), stored),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use mfprocgen::{structure::{RegionPos, StructurePlacer}, GeneratorConfig};
+    use mfworld::{chunk::BlockPos, structure::StructureIndex};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// A hall in the corner of every region, four chunks long.
+    struct Halls;
+
+    impl StructurePlacer for Halls {
+        fn name(&self) -> &'static str {
+            "halls"
+        }
+
+        fn propose(&self, _ctx: &GenContext, region: RegionPos, _rng: &mut ChaCha8Rng) -> Vec<StructureBox> {
+            vec![StructureBox::new((region.x * structure::REGION_SIZE + 8, 0, region.z * structure::REGION_SIZE), (56, 8, 8))]
+        }
+    }
+
+    #[test]
+    fn generate_chunk_test() {
+        let config = GeneratorConfig::preset("flats").unwrap();
+        let ctx = GenContext::new(1, &config);
+        let surface = ChunkPos::containing(0, config.base_height - 1, 0);
+        let stored = generate_chunk(&ctx, surface);
+        let top = (config.base_height - 1) & (CHUNK_SIZE - 1);
+        assert_ne!(stored.id(voxel_index(3, top, 3)), VoxelId::AIR);
+
+        let chunks = [surface, ChunkPos::new(1, surface.y, 0), ChunkPos::new(0, surface.y + 1, 0)];
+        let mut generated = Vec::new();
+        generate_parallel(&ctx, &chunks, 2, |chunk, stored| generated.push((chunk, stored)));
+        assert_eq!(generated.iter().map(|(chunk, _)| *chunk).collect::<Vec<_>>(), chunks);
+        assert_eq!(generated[0].1, stored);
+        assert_eq!(generated[2].1, StoredChunk::new());
+    }
+
+    #[test]
+    fn chunk_structures_test() {
+        let config = GeneratorConfig::default();
+        let ctx = GenContext::new(3, &config);
+        let halls = Halls;
+        let mut claims = ClaimRegistry::new(vec![&halls]);
+        let mut index = StructureIndex::new();
+        for x in 0..8 {
+            let chunk = ChunkPos::new(x, 0, 0);
+            index.load_chunk(chunk, chunk_structures(&ctx, &mut claims, chunk));
+        }
+        // Every chunk of the hall found it on its own, and agrees on its id.
+        let hall = index.structure_at(BlockPos::new(10, 2, 2)).unwrap();
+        assert_eq!(hall.id, structure_id(&ctx, "halls", StructureBox::new((8, 0, 0), (56, 8, 8))));
+        assert_eq!(index.structure_at(BlockPos::new(63, 7, 7)), Some(hall));
+        assert_eq!(index.structure_at(BlockPos::new(64, 2, 2)), None);
+        assert_eq!((0..8).filter(|&x| !index.chunk(ChunkPos::new(x, 0, 0)).unwrap().is_empty()).count(), 4);
+        assert_eq!(index.structures_intersecting(StructureBounds::new((0, 0, 0), (128, 16, 16))), [hall]);
+    }
+}
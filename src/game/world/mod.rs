@@ -1,5 +1,222 @@
 pub mod chunk;
+pub mod generate;
+pub mod pregen;
 
+use std::collections::BTreeMap;
+
+use mfhash::deterministic::{combine_versions, DeterministicHash, DeterministicHasher};
+use mfworld::{
+    bounds::{HeightBounds, HeightError},
+    chunk::{loaded::LoadedChunks, stored::StoredChunk, BlockPos, ChunkPos},
+    history::VoxelState,
+    ticket::{ChunkTickets, TicketLevel},
+};
+
+use crate::game::events::{Event, EventBus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SetVoxelError {
+    #[error(transparent)]
+    OutOfBounds(#[from] HeightError),
+    #[error("{0} isn't loaded")]
+    NotLoaded(BlockPos),
+}
+
+/// The loaded part of a dimension.
+///
+/// Systems that visit chunks during a tick iterate [World::iter_loaded_chunks_deterministic] or
+/// [World::iter_ticking_chunks_deterministic], never a hash map, so that every machine visits them
+/// in the same order.
+///
+/// Only chunks within the dimension's [HeightBounds] are ever loaded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct World {
-    
-}
\ No newline at end of file
+    bounds: HeightBounds,
+    loaded: LoadedChunks,
+    ticking: LoadedChunks,
+}
+
+impl World {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty world with the height range `bounds`.
+    #[inline]
+    #[must_use]
+    pub fn with_bounds(bounds: HeightBounds) -> Self {
+        Self { bounds, ..Self::default() }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn bounds(&self) -> HeightBounds {
+        self.bounds
+    }
+
+    /// Loads and unloads chunks to match the ticket levels that changed since the last sync.
+    /// Tickets reaching outside of the height bounds don't load anything there.
+    pub fn sync_tickets(&mut self, tickets: &mut ChunkTickets) {
+        for (chunk, level) in tickets.drain_changes() {
+            match level {
+                Some(_) if !self.bounds.contains_chunk(chunk) => {}
+                Some(level) => {
+                    self.loaded.insert(chunk);
+                    if level == TicketLevel::Ticking {
+                        self.ticking.insert(chunk);
+                    } else {
+                        self.ticking.remove(chunk);
+                    }
+                }
+                None => {
+                    self.loaded.remove(chunk);
+                    self.ticking.remove(chunk);
+                }
+            }
+        }
+    }
+
+    /// Loads `chunk` without a ticket, for rebuilding a world that was sent whole (see
+    /// [sync](crate::game::sync)). The next [Self::sync_tickets] unloads it unless a ticket holds it.
+    pub(crate) fn insert_chunk(&mut self, chunk: ChunkPos, ticking: bool) -> Result<(), HeightError> {
+        self.bounds.check_chunk(chunk)?;
+        self.loaded.insert(chunk);
+        if ticking {
+            self.ticking.insert(chunk);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_loaded(&self, chunk: ChunkPos) -> bool {
+        self.loaded.contains(chunk)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_ticking(&self, chunk: ChunkPos) -> bool {
+        self.ticking.contains(chunk)
+    }
+
+    /// Sets the voxel at `pos` in `chunks` (the voxels of the loaded chunks), returning the state it
+    /// replaced. A voxel that changes is emitted as [Event::BlockPlaced], which is what the bus's
+    /// [voxel listeners](EventBus::voxel_listeners) are fed from. Nothing changes if `pos` is
+    /// outside the world's [HeightBounds] or isn't loaded.
+    pub fn set_voxel(
+        &self,
+        chunks: &mut BTreeMap<ChunkPos, StoredChunk>,
+        pos: BlockPos,
+        state: VoxelState,
+        events: &mut EventBus,
+    ) -> Result<VoxelState, SetVoxelError> {
+        self.bounds.check_block(pos)?;
+        let (chunk, local) = pos.split().ok_or(SetVoxelError::NotLoaded(pos))?;
+        let stored = chunks.get_mut(&chunk).filter(|_| self.is_loaded(chunk)).ok_or(SetVoxelError::NotLoaded(pos))?;
+        let before = stored.set(local.index(), state);
+        if before != state && let Some(pos) = pos.to_i32() {
+            events.emit(Event::BlockPlaced { pos, id: state.id, orientation: state.orientation });
+        }
+        Ok(before)
+    }
+
+    /// Every loaded chunk, in Morton order.
+    #[inline]
+    pub fn iter_loaded_chunks_deterministic(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.loaded.iter()
+    }
+
+    /// The loaded chunks that are simulated, in Morton order.
+    #[inline]
+    pub fn iter_ticking_chunks_deterministic(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.ticking.iter()
+    }
+}
+
+// Layout: the loaded chunks, then the ticking chunks, each as a count and the chunks in Morton order,
+// then the height bounds.
+impl DeterministicHash for World {
+    const HASH_VERSION: u32 = combine_versions(&[u64::HASH_VERSION, ChunkPos::HASH_VERSION, i64::HASH_VERSION]);
+
+    fn deterministic_hash<H: DeterministicHasher>(&self, hasher: &mut H) {
+        for chunks in [&self.loaded, &self.ticking] {
+            hasher.write_u64(chunks.len() as u64);
+            for chunk in chunks.iter() {
+                chunk.deterministic_hash(hasher);
+            }
+        }
+        hasher.write_i64(self.bounds.min_y());
+        hasher.write_i64(self.bounds.max_y());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mfgeometry::Orientation;
+    use mfworld::{
+        chunk::loaded::check_order,
+        listener::{ListenerFilter, VoxelChange},
+        ticket::Ticket,
+        voxel::id::VoxelId,
+    };
+
+    use super::*;
+
+    #[test]
+    fn sync_tickets_test() {
+        let mut tickets = ChunkTickets::new();
+        let player = tickets.add(Ticket::player(ChunkPos::ORIGIN, 1));
+        let mut world = World::new();
+        world.sync_tickets(&mut tickets);
+        let loaded = check_order(world.iter_loaded_chunks_deterministic()).collect::<Vec<_>>();
+        assert_eq!(loaded, tickets.retained().map(|(chunk, _)| chunk).collect::<Vec<_>>());
+        assert!(world.is_ticking(ChunkPos::ORIGIN));
+
+        tickets.remove(player);
+        world.sync_tickets(&mut tickets);
+        assert_eq!(world.iter_loaded_chunks_deterministic().count(), 0);
+        assert!(!world.is_ticking(ChunkPos::ORIGIN));
+    }
+
+    #[test]
+    fn bounded_sync_test() {
+        let mut tickets = ChunkTickets::new();
+        tickets.add(Ticket::player(ChunkPos::ORIGIN, 1));
+        let mut world = World::with_bounds(HeightBounds::from_chunks(0, 3).unwrap());
+        world.sync_tickets(&mut tickets);
+        assert_eq!(world.iter_loaded_chunks_deterministic().count(), 18);
+        assert!(world.iter_loaded_chunks_deterministic().all(|chunk| chunk.y >= 0));
+        assert!(world.insert_chunk(ChunkPos::new(0, -1, 0), true).is_err());
+    }
+
+    #[test]
+    fn set_voxel_test() {
+        let mut tickets = ChunkTickets::new();
+        tickets.add(Ticket::player(ChunkPos::ORIGIN, 0));
+        let mut world = World::new();
+        world.sync_tickets(&mut tickets);
+        let mut chunks = BTreeMap::from([(ChunkPos::ORIGIN, StoredChunk::new()), (ChunkPos::new(2, 0, 0), StoredChunk::new())]);
+        let mut events = EventBus::new();
+        let renderer = events.voxel_listeners().register(ListenerFilter::chunk(ChunkPos::ORIGIN), 16);
+
+        let stone = VoxelState::new(VoxelId::new(1), Orientation::UNORIENTED);
+        let pos = BlockPos::new(1, 2, 3);
+        assert_eq!(world.set_voxel(&mut chunks, pos, stone, &mut events), Ok(VoxelState::AIR));
+        // Setting a voxel to what it already is changes nothing, and isn't reported.
+        assert_eq!(world.set_voxel(&mut chunks, pos, stone, &mut events), Ok(stone));
+        // Stored chunks that aren't loaded can't be written.
+        let unloaded = BlockPos::new(33, 0, 0);
+        assert_eq!(world.set_voxel(&mut chunks, unloaded, stone, &mut events), Err(SetVoxelError::NotLoaded(unloaded)));
+        assert_eq!(chunks[&ChunkPos::new(2, 0, 0)], StoredChunk::new());
+        // Neither can voxels outside the height bounds, even in a stored chunk.
+        let world = World::with_bounds(HeightBounds::new(0, 15).unwrap());
+        assert_eq!(
+            world.set_voxel(&mut chunks, BlockPos::new(0, 16, 0), stone, &mut events),
+            Err(SetVoxelError::OutOfBounds(HeightError::AboveTop { y: 16, max_y: 15 })),
+        );
+        events.end_tick(0);
+        assert_eq!(events.voxel_listeners().drain(&renderer).changes, [VoxelChange { position: pos, state: stone }]);
+    }
+}
@@ -0,0 +1,307 @@
+//! Pregeneration: generating and saving every chunk within a radius ahead of time.
+//!
+//! The area is split into regions of [REGION_SIZE] × [REGION_SIZE] chunk columns. Regions are
+//! generated in order with [generate_parallel], saved with their structure references, and recorded as complete in the dimension's
+//! [PregenRecord], so a pregeneration that was stopped picks up at the first region it hadn't
+//! finished. Chunks that are already saved are left as they are, and chunks outside the world's
+//! [height bounds](mfworld::bounds::HeightBounds) are never generated. Once every region is done,
+//! every chunk in the area is loaded again to check that it was saved intact.
+
+use std::collections::BTreeSet;
+
+use mfcereal::{
+    decode::{Decode, DecodeError, Decoder},
+    encode::{Encode, Encoder},
+};
+use mffmt::progress::{Progress, ProgressSink};
+use mfprocgen::structure::{ClaimRegistry, StructurePlacer};
+use mfworld::{chunk::ChunkPos, portal::DimensionId};
+
+use crate::game::{
+    save::dir::{SaveDir, SaveError},
+    world::generate::{chunk_structures, column_biomes, generate_parallel},
+};
+
+/// The width of a region in chunk columns.
+pub const REGION_SIZE: i32 = 8;
+
+/// The chunks to pregenerate: every chunk column whose center is within `radius` chunks of the
+/// center column, from `min_y` to `max_y` (in chunk coordinates, inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PregenArea {
+    pub center_x: i32,
+    pub center_z: i32,
+    pub radius: u32,
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl PregenArea {
+    /// Columns around `(center_x, center_z)` from chunk `0` to `7` (voxels `0` to `127`).
+    #[inline]
+    #[must_use]
+    pub const fn new(center_x: i32, center_z: i32, radius: u32) -> Self {
+        Self { center_x, center_z, radius, min_y: 0, max_y: 7 }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn contains_column(&self, x: i32, z: i32) -> bool {
+        let (dx, dz) = ((x - self.center_x) as i64, (z - self.center_z) as i64);
+        dx * dx + dz * dz <= self.radius as i64 * self.radius as i64
+    }
+
+    /// The regions that overlap the area, in order.
+    pub fn regions(&self) -> Vec<(i32, i32)> {
+        let radius = self.radius as i32;
+        let region = |chunk: i32| chunk.div_euclid(REGION_SIZE);
+        let mut regions = Vec::new();
+        for region_z in region(self.center_z - radius)..=region(self.center_z + radius) {
+            for region_x in region(self.center_x - radius)..=region(self.center_x + radius) {
+                if !self.region_chunks((region_x, region_z)).is_empty() {
+                    regions.push((region_x, region_z));
+                }
+            }
+        }
+        regions
+    }
+
+    /// The chunks of the area inside a region, in order.
+    pub fn region_chunks(&self, (region_x, region_z): (i32, i32)) -> Vec<ChunkPos> {
+        let mut chunks = Vec::new();
+        for z in region_z * REGION_SIZE..(region_z + 1) * REGION_SIZE {
+            for x in region_x * REGION_SIZE..(region_x + 1) * REGION_SIZE {
+                if self.contains_column(x, z) {
+                    chunks.extend((self.min_y..=self.max_y).map(|y| ChunkPos::new(x, y, z)));
+                }
+            }
+        }
+        chunks
+    }
+}
+
+/// The regions of a pregeneration that are done, saved so that it can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PregenRecord {
+    pub area: PregenArea,
+    pub completed: BTreeSet<(i32, i32)>,
+}
+
+impl PregenRecord {
+    #[inline]
+    #[must_use]
+    pub const fn new(area: PregenArea) -> Self {
+        Self { area, completed: BTreeSet::new() }
+    }
+}
+
+// Layout: center x (i32), center z (i32), radius (u32), min y (i32), max y (i32),
+//      completed region count (u32), each as x (i32), z (i32).
+impl Encode for PregenRecord {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<u64, E::Error> {
+        let area = &self.area;
+        let mut written = encoder.write_i32(area.center_x)?
+            + encoder.write_i32(area.center_z)?
+            + encoder.write_u32(area.radius)?
+            + encoder.write_i32(area.min_y)?
+            + encoder.write_i32(area.max_y)?
+            + encoder.write_u32(self.completed.len() as u32)?;
+        for &(x, z) in &self.completed {
+            written += encoder.write_i32(x)? + encoder.write_i32(z)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for PregenRecord {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError<D::Error>> {
+        let area = PregenArea {
+            center_x: decoder.read_i32()?,
+            center_z: decoder.read_i32()?,
+            radius: decoder.read_u32()?,
+            min_y: decoder.read_i32()?,
+            max_y: decoder.read_i32()?,
+        };
+        let mut record = Self::new(area);
+        for _ in 0..decoder.read_u32()? {
+            record.completed.insert((decoder.read_i32()?, decoder.read_i32()?));
+        }
+        Ok(record)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PregenReport {
+    /// Chunks generated by this run.
+    pub generated: usize,
+    /// Chunks that were already saved, and kept.
+    pub existing: usize,
+    /// Regions skipped because an earlier run had finished them.
+    pub resumed_regions: usize,
+    /// Chunks checked by the verification pass.
+    pub verified: usize,
+    /// Chunks that failed verification.
+    pub bad: Vec<ChunkPos>,
+}
+
+/// Generates and saves every chunk of `area` in `dimension` on `threads` threads, then verifies them.
+/// The structures of `placers` are saved with each chunk, and the biomes with each chunk column.
+/// Chunks that are already saved (possibly edited by players) are kept, and the area is clipped to
+/// the world's height bounds.
+///
+/// A record of a pregeneration of the same area is resumed; one of a different area is started over.
+pub fn pregen<S: ProgressSink>(
+    save: &SaveDir,
+    dimension: DimensionId,
+    area: PregenArea,
+    placers: &[&dyn StructurePlacer],
+    threads: usize,
+    progress: &mut Progress<S>,
+) -> Result<PregenReport, SaveError> {
+    let header = save.read_header()?;
+    let ctx = header.gen_context();
+    let mut claims = ClaimRegistry::new(placers.to_vec());
+    let mut record = save.read_pregen(dimension)?.filter(|record| record.area == area).unwrap_or(PregenRecord::new(area));
+    let mut report = PregenReport::default();
+    let regions = area.regions();
+    let region_chunks = |region| {
+        let mut chunks = area.region_chunks(region);
+        chunks.retain(|&chunk| header.bounds.contains_chunk(chunk));
+        chunks
+    };
+
+    progress.begin("pregen", regions.len() as u64);
+    for &region in &regions {
+        if record.completed.contains(&region) {
+            report.resumed_regions += 1;
+            progress.advance(1);
+            continue;
+        }
+        let mut chunks = Vec::new();
+        for chunk in region_chunks(region) {
+            if save.read_chunk_blob(dimension, chunk)?.is_some() {
+                report.existing += 1;
+            } else {
+                chunks.push(chunk);
+            }
+        }
+        let mut saved = Ok(());
+        generate_parallel(&ctx, &chunks, threads, |chunk, stored| {
+            if saved.is_ok() {
+                saved = save.save_chunk(dimension, chunk, &stored)
+                    .and_then(|()| save.save_chunk_structures(dimension, chunk, &chunk_structures(&ctx, &mut claims, chunk)))
+                    .and_then(|()| match save.load_column_biomes(dimension, chunk.x, chunk.z) {
+                        Ok(Some(_)) => Ok(()),
+                        // Biomes that are missing or damaged are regenerated.
+                        Ok(None) | Err(SaveError::InvalidBiomes { .. }) => save.save_column_biomes(dimension, chunk.x, chunk.z, &column_biomes(&ctx, chunk.x, chunk.z)),
+                        Err(err) => Err(err),
+                    });
+            }
+        });
+        saved?;
+        report.generated += chunks.len();
+        record.completed.insert(region);
+        save.write_pregen(dimension, &record)?;
+        progress.advance(1);
+    }
+    progress.end();
+
+    progress.begin("verify", regions.len() as u64);
+    for &region in &regions {
+        for chunk in region_chunks(region) {
+            report.verified += 1;
+            if !matches!(save.load_chunk(dimension, chunk), Ok(Some(_))) {
+                report.bad.push(chunk);
+            }
+        }
+        progress.advance(1);
+    }
+    progress.end();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use mffmt::progress::ProgressUpdate;
+    use mfgeometry::Orientation;
+    use mfprocgen::GeneratorConfig;
+    use mfworld::{bounds::HeightBounds, history::VoxelState, voxel::id::VoxelId};
+
+    use super::*;
+    use crate::game::{mode::GameMode, save::header::SaveHeader};
+
+    #[test]
+    fn pregen_area_test() {
+        let area = PregenArea { center_x: 0, center_z: 0, radius: 9, min_y: 4, max_y: 4 };
+        let chunks = area.regions().into_iter().flat_map(|region| area.region_chunks(region)).collect::<Vec<_>>();
+        let columns = (-9..=9).flat_map(|z| (-9..=9).map(move |x| (x, z))).filter(|&(x, z)| x * x + z * z <= 81);
+        assert_eq!(chunks.len(), columns.count());
+        assert!(chunks.iter().all(|chunk| area.contains_column(chunk.x, chunk.z) && chunk.y == 4));
+        assert!(chunks.contains(&ChunkPos::new(9, 4, 0)) && !chunks.contains(&ChunkPos::new(9, 4, 1)));
+        assert_eq!(chunks.iter().collect::<BTreeSet<_>>().len(), chunks.len());
+
+        let mut record = PregenRecord::new(area);
+        record.completed.extend([(-1, -2), (0, 1)]);
+        let mut bytes = Vec::new();
+        record.encode(&mut bytes).unwrap();
+        assert_eq!(PregenRecord::decode(&mut bytes.as_slice()).unwrap(), record);
+    }
+
+    #[test]
+    fn pregen_test() {
+        let root = std::env::temp_dir().join(format!("manufactory_pregen_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let header = SaveHeader::new(5, GameMode::Survival, GeneratorConfig::preset("flats").unwrap());
+        let save = SaveDir::create(&root, &header).unwrap();
+        let area = PregenArea { center_x: 7, center_z: 0, radius: 1, min_y: 3, max_y: 4 };
+        let mut progress = Progress::with_interval(|_: &ProgressUpdate<'_>| (), Duration::ZERO);
+
+        // Pretend an earlier run finished the first region.
+        let mut record = PregenRecord::new(area);
+        record.completed.insert((0, 0));
+        save.write_pregen(DimensionId::OVERWORLD, &record).unwrap();
+
+        let report = pregen(&save, DimensionId::OVERWORLD, area, &[], 2, &mut progress).unwrap();
+        assert_eq!(report.resumed_regions, 1);
+        assert_eq!(report.generated, 2 * 2);
+        assert_eq!(report.verified, 5 * 2);
+        // The resumed region's chunks were never saved.
+        assert_eq!(report.bad.len(), 3 * 2);
+        assert_eq!(save.read_pregen(DimensionId::OVERWORLD).unwrap().unwrap().completed.len(), 3);
+        assert!(save.load_chunk_structures(DimensionId::OVERWORLD, ChunkPos::new(8, 3, 0)).unwrap().is_some());
+        let ctx = header.gen_context();
+        assert_eq!(save.load_column_biomes(DimensionId::OVERWORLD, 8, 0).unwrap(), Some(column_biomes(&ctx, 8, 0)));
+
+        // Another area starts over.
+        let report = pregen(&save, DimensionId::OVERWORLD, PregenArea { radius: 0, ..area }, &[], 1, &mut progress).unwrap();
+        assert_eq!((report.generated, report.resumed_regions, report.bad.len()), (2, 0, 0));
+
+        // Chunks that are already saved are kept.
+        let edited = ChunkPos::new(8, 3, 0);
+        let mut stored = save.load_chunk(DimensionId::OVERWORLD, edited).unwrap().unwrap();
+        stored.set(0, VoxelState::new(VoxelId::new(42), Orientation::UNORIENTED));
+        save.save_chunk(DimensionId::OVERWORLD, edited, &stored).unwrap();
+        let report = pregen(&save, DimensionId::OVERWORLD, PregenArea { center_x: 8, radius: 0, ..area }, &[], 1, &mut progress).unwrap();
+        assert_eq!((report.generated, report.existing, report.bad.len()), (0, 2, 0));
+        assert_eq!(save.load_chunk(DimensionId::OVERWORLD, edited).unwrap().unwrap(), stored);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn pregen_bounds_test() {
+        let root = std::env::temp_dir().join(format!("manufactory_pregen_bounds_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut header = SaveHeader::new(5, GameMode::Survival, GeneratorConfig::preset("flats").unwrap());
+        header.bounds = HeightBounds::from_chunks(0, 1).unwrap();
+        let save = SaveDir::create(&root, &header).unwrap();
+        let area = PregenArea { center_x: 0, center_z: 0, radius: 0, min_y: -1, max_y: 3 };
+        let mut progress = Progress::with_interval(|_: &ProgressUpdate<'_>| (), Duration::ZERO);
+
+        let report = pregen(&save, DimensionId::OVERWORLD, area, &[], 1, &mut progress).unwrap();
+        assert_eq!((report.generated, report.verified, report.bad.len()), (2, 2, 0));
+        assert_eq!(save.chunks(DimensionId::OVERWORLD).unwrap(), [ChunkPos::new(0, 0, 0), ChunkPos::new(0, 1, 0)]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+//! Runs every scenario in `tests/scenarios` and compares how it ends with its golden file in
+//! `tests/golden`. See [manufactory::game::scenario::golden] for blessing new values.
+
+use std::{fs, path::Path};
+
+use manufactory::game::scenario::{
+    golden::{self, BLESS_VAR},
+    Scenario,
+};
+
+#[test]
+fn scenario_golden_test() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let bless = golden::bless_requested();
+    let mut paths = fs::read_dir(root.join("scenarios")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "scenario"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenarios found");
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let scenario = Scenario::load(path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        let actual = scenario.run().unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        // A scenario that doesn't end the same way twice would make its golden file meaningless.
+        assert_eq!(scenario.run().unwrap(), actual, "{} isn't deterministic", scenario.name);
+        let golden_path = root.join("golden").join(format!("{}.golden", scenario.name));
+        if let Err(err) = golden::check(&golden_path, &actual, bless) {
+            failures.push(err.to_string());
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n\nIf the change is intended, rerun with {BLESS_VAR}=1 and commit the updated golden files.",
+        failures.join("\n"),
+    );
+}